@@ -1,14 +1,18 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Re-run the build script if the protobuf definition changes
     println!("cargo:rerun-if-changed=proto/kari/agent/v1/agent.proto");
+    println!("cargo:rerun-if-changed=proto/grpc/health/v1/health.proto");
 
     tonic_build::configure()
-        // The agent is a server only; do not generate client stubs
-        .build_client(false)
+        // The agent is a server; the client stubs exist for the testkit
+        .build_client(true)
         .build_server(true)
         .compile(
-            &["proto/kari/agent/v1/agent.proto"], // actual proto file
-            &["proto"],                           // include root
+            &[
+                "proto/kari/agent/v1/agent.proto",
+                "proto/grpc/health/v1/health.proto", // standard health checking
+            ],
+            &["proto"], // include root
         )?;
 
     Ok(())
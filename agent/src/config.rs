@@ -1,5 +1,16 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::env;
-use std::path::PathBuf;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use crate::sys::acme::LETS_ENCRYPT_DIRECTORY;
+use crate::sys::build_log::build_logs_root;
+use crate::sys::key_rotation::max_key_age_secs;
+use crate::sys::log_retention::{ClassPolicy, LogClass, RetentionPolicy};
+use crate::sys::mac::MacMode;
+use crate::sys::scan::ScanPolicy;
+use crate::sys::traits::TlsPolicy;
 
 #[derive(Clone, Debug)]
 pub struct AgentConfig {
@@ -14,6 +25,150 @@ pub struct AgentConfig {
     pub logrotate_dir: PathBuf,
     pub ssl_storage_dir: PathBuf,
     pub proxy_conf_dir: PathBuf,
+    pub postfix_dir: PathBuf,
+    pub chrony_conf: PathBuf,
+    pub chrony_sources_dir: PathBuf,
+    pub timesyncd_dir: PathBuf,
+    pub secrets_dir: PathBuf,
+    pub state_dir: PathBuf,
+    pub bundle_dir: PathBuf,
+
+    // 🧹 Container Image GC (disk-pressure driven)
+    pub image_gc_interval_secs: u64,
+    pub image_gc_disk_threshold_percent: f64,
+
+    // 📏 Pre-flight Capacity Checks
+    pub min_free_disk_mb: u64,
+    pub overcommit_ratio: Option<f64>, // Unset: memory may not exceed physical, CPU unlimited
+
+    // 🔎 Post-Clone Source Scanning
+    pub scan_policy: ScanPolicy,
+    pub scan_clamav: bool,
+
+    // ⏳ Long-Running Operations
+    pub package_timeout_secs: u64,
+    pub host_op_timeout_secs: u64, // Cap on one RunHostOperation
+    pub operation_retention_secs: i64,
+
+    // ⚡ Cold Start
+    pub prewarm_timeout_secs: u64, // Serve anyway once this passes
+
+    // ⚡ Deploy Pipeline (independent phases of one deploy run side by side)
+    pub deploy_parallelism: usize, // Phases in flight and git submodule jobs; 1 = sequential
+    pub build_timeout_secs: u64,   // Per fetch/build step; the step's whole process tree is stopped
+    pub build_tmp_max_mb: u64, // Each step's private /tmp and /var/tmp (tmpfs, charged to its memory)
+
+    // 🪞 Build Mirrors (dependency registries and proxy for networked build steps)
+    pub npm_registry_mirror: Option<String>,
+    pub pip_index_mirror: Option<String>,
+    pub cargo_registry_mirror: Option<String>, // Sparse index URL
+    pub build_proxy: Option<String>,           // HTTP(S) proxy URL
+    pub build_no_proxy: Option<String>,
+    pub build_mirrors_only: bool, // Builds may reach only the mirrors, the proxy and DNS
+
+    // 🗄️ Artifact Store (built releases by sha256, reused for identical builds)
+    pub artifact_store: bool,
+    pub artifact_dir: PathBuf,
+    pub artifact_store_max_mb: u64, // Least recently used archives are pruned past this
+
+    // 📜 Build Logs (gzip'd per release under state_dir/build-logs)
+    pub build_log_max_kb: u64, // Uncompressed; larger logs keep head and tail
+    pub build_log_keep: usize, // Newest logs kept per domain
+
+    // 📦 Source Archives (DeployArtifact: a tar.gz instead of a git clone)
+    pub source_archive_max_mb: u64, // Caps the archive and what it unpacks to
+
+    // 🩺 Self-Test (the local ingress the probe goes through)
+    pub selftest_probe_addr: String,
+    pub selftest_timeout_secs: u64,
+
+    // 🧊 Activation (the unit must still be running this long after its restart)
+    pub activation_health_secs: u64,
+    pub approval_timeout_secs: u64, // Held builds abort after this without a ConfirmDeployment
+
+    // 🩺 Deploy Health Gate (defaults for DeployRequest.health_check)
+    pub health_check_timeout_secs: u64, // Per attempt
+    pub health_check_retries: u32,      // Attempts after the first
+    pub health_check_interval_secs: u64,
+
+    // 🧹 Release Hygiene (pruned after every successful deploy and by PruneReleases)
+    pub releases_keep: usize, // Newest releases kept per domain; at least 1
+
+    // 🕵️ Tamper Watch (inotify on the systemd, proxy and certificate dirs)
+    pub tamper_watch: bool,
+    pub tamper_self_heal: bool, // Rewrite clobbered vhosts and job timers from state
+    pub tamper_settle_ms: u64,  // Quiet time before a changed file is judged
+
+    // 🔌 Circuit Breaker (crash-looping apps go to a maintenance page)
+    pub crash_loop_starts: u32, // Failed starts within the window that trip it
+    pub crash_loop_window_secs: u64,
+    pub circuit_poll_ms: u64, // How often units are checked for a tripped limit
+
+    // 🔄 Key Rotation (certificate keys older than this get a staged successor)
+    pub key_max_age_secs: u64, // Set in days by KARI_KEY_MAX_AGE_DAYS (at most 3650); 0 disables
+    pub key_rotation_poll_ms: u64,
+
+    // 🔒 HTTPS Vhosts (modern | intermediate | old; vhosts are rewritten at startup)
+    pub tls_policy: TlsPolicy,
+    pub ocsp_stapling: bool,
+    pub ocsp_resolvers: Vec<String>, // Empty: resolv.conf's nameservers
+    pub must_staple: bool,           // CSRs ask for the TLS Feature (status_request) extension
+
+    // 🔏 ACME (IssueCertificate: HTTP-01 through the proxy; keys are generated here)
+    pub acme_directory_url: String,
+    pub acme_contact: Option<String>, // Mail address the CA sends expiry notices to
+    pub acme_account_key: PathBuf,    // Generated on first use, 0600
+    pub acme_caa_domain: String,      // What CAA records must name for issuance to be allowed
+
+    // 🔑 SSH Clones (host keys pinned through PinSshHostKeys; nothing else is trusted)
+    pub known_hosts_file: PathBuf,
+
+    // 📂 SFTP-Only Access (per-app sshd drop-ins and root-owned chroots)
+    pub sshd_config_dir: PathBuf,
+    pub sftp_root: PathBuf,
+
+    // 🩹 OS Patching (window and scope set through SetPatchPolicy, kept in state)
+    pub apt_conf_dir: PathBuf,
+    pub patch_poll_ms: u64, // How often the window is checked for
+
+    // 🚦 Build Queue (512 MB / half-core slots shared by every deploy's build)
+    pub build_slots: u32, // 0 = derived from the host's memory and cores
+
+    // 🛡️ App Confinement (auto | apparmor | selinux | off; per-app MAC profiles)
+    pub mac_mode: MacMode,
+    pub apparmor_dir: PathBuf,
+
+    // 🔭 Telemetry (EnvFilter directives, e.g. "info,kari_agent::sys::build=debug")
+    pub log_filter: String,
+
+    // 📦 Config Bundles (offline specs signed by the operator's Ed25519 key)
+    pub config_bundle_key: PathBuf, // Hex public key, agent-owned; absent disables ApplyConfigBundle
+
+    // 💓 Heartbeat (outbound HTTPS to the control plane; unset URL disables)
+    pub heartbeat_url: Option<String>,
+    pub heartbeat_key: PathBuf, // Root-owned, 0600; shared with the control plane
+    pub heartbeat_interval_ms: u64,
+
+    // 📤 Log Forwarding (every app's journal, off-host over TLS; unset URL disables)
+    pub log_forward_url: Option<String>, // https://…/loki/api/v1/push or syslog+tls://host[:6514]
+    pub log_forward_headers: Option<PathBuf>, // Root-owned, 0600; extra Loki headers, one per line
+    pub log_forward_batch_lines: usize,
+    pub log_forward_batch_ms: u64, // How long the oldest line waits for a fuller batch
+    pub log_forward_poll_ms: u64,  // How often new and deleted apps are picked up
+
+    // 📣 Notifications (channels and per-event routes in agent.toml's [notify.*] tables)
+    pub agent_toml: PathBuf, // Root-owned, 0600; absent disables notifications
+    pub notify_poll_ms: u64, // How often failed jobs are looked for
+
+    // 💽 Disk Health (smartctl & kernel I/O errors)
+    pub disk_health_poll_ms: u64,
+
+    // 🗑️ Log Retention (days per log class, enforced daily by a kari timer; 0 keeps forever)
+    pub deploy_log_retention_days: u32, // state_dir/build-logs
+    pub audit_log_retention_days: u32,
+    pub access_log_retention_days: u32,
+    pub audit_log_dir: PathBuf, // Per-domain files: <domain>/… or <domain>.log*
+    pub access_log_dir: PathBuf,
 }
 
 impl AgentConfig {
@@ -62,6 +217,494 @@ impl AgentConfig {
                 env::var("KARI_PROXY_CONF_DIR")
                     .unwrap_or_else(|_| "/etc/nginx/sites-available".to_string()),
             ),
+
+            postfix_dir: PathBuf::from(
+                env::var("KARI_POSTFIX_DIR").unwrap_or_else(|_| "/etc/postfix".to_string()),
+            ),
+
+            chrony_conf: PathBuf::from(
+                env::var("KARI_CHRONY_CONF")
+                    .unwrap_or_else(|_| "/etc/chrony/chrony.conf".to_string()),
+            ),
+
+            chrony_sources_dir: PathBuf::from(
+                env::var("KARI_CHRONY_SOURCES_DIR")
+                    .unwrap_or_else(|_| "/etc/chrony/sources.d".to_string()),
+            ),
+
+            timesyncd_dir: PathBuf::from(
+                env::var("KARI_TIMESYNCD_DIR")
+                    .unwrap_or_else(|_| "/etc/systemd/timesyncd.conf.d".to_string()),
+            ),
+
+            secrets_dir: PathBuf::from(
+                env::var("KARI_SECRETS_DIR").unwrap_or_else(|_| "/etc/kari/secrets".to_string()),
+            ),
+
+            state_dir: PathBuf::from(
+                env::var("KARI_STATE_DIR").unwrap_or_else(|_| "/var/lib/kari".to_string()),
+            ),
+
+            bundle_dir: PathBuf::from(
+                env::var("KARI_BUNDLE_DIR").unwrap_or_else(|_| "/var/lib/kari/bundles".to_string()),
+            ),
+
+            image_gc_interval_secs: env::var("KARI_IMAGE_GC_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3600),
+
+            image_gc_disk_threshold_percent: env::var("KARI_IMAGE_GC_DISK_PERCENT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(85.0),
+
+            min_free_disk_mb: env::var("KARI_MIN_FREE_DISK_MB")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1024),
+
+            overcommit_ratio: env::var("KARI_OVERCOMMIT_RATIO")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|r: &f64| *r > 0.0),
+
+            scan_policy: env::var("KARI_SCAN_POLICY")
+                .ok()
+                .and_then(|v| ScanPolicy::parse(&v))
+                .unwrap_or(ScanPolicy::Warn),
+
+            scan_clamav: env::var("KARI_SCAN_CLAMAV")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+
+            package_timeout_secs: env::var("KARI_PACKAGE_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3600),
+            host_op_timeout_secs: env::var("KARI_HOST_OP_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(600),
+
+            operation_retention_secs: env::var("KARI_OPERATION_RETENTION_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(7 * 24 * 3600),
+
+            prewarm_timeout_secs: env::var("KARI_PREWARM_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+
+            selftest_probe_addr: env::var("KARI_SELFTEST_PROBE_ADDR")
+                .unwrap_or_else(|_| "127.0.0.1:80".to_string()),
+
+            deploy_parallelism: env::var("KARI_DEPLOY_PARALLELISM")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n: &usize| *n > 0)
+                .unwrap_or(4),
+
+            build_timeout_secs: env::var("KARI_BUILD_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n: &u64| *n > 0)
+                .unwrap_or(3600),
+
+            build_tmp_max_mb: env::var("KARI_BUILD_TMP_MAX_MB")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n: &u64| *n > 0)
+                .unwrap_or(1024),
+
+            npm_registry_mirror: env::var("KARI_NPM_MIRROR").ok().filter(|v| !v.is_empty()),
+            pip_index_mirror: env::var("KARI_PIP_MIRROR").ok().filter(|v| !v.is_empty()),
+            cargo_registry_mirror: env::var("KARI_CARGO_MIRROR").ok().filter(|v| !v.is_empty()),
+            build_proxy: env::var("KARI_BUILD_PROXY").ok().filter(|v| !v.is_empty()),
+            build_no_proxy: env::var("KARI_BUILD_NO_PROXY")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            build_mirrors_only: env::var("KARI_BUILD_MIRRORS_ONLY")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+
+            artifact_store: env::var("KARI_ARTIFACT_STORE")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            artifact_dir: PathBuf::from(
+                env::var("KARI_ARTIFACT_DIR")
+                    .unwrap_or_else(|_| "/var/lib/kari/artifacts".to_string()),
+            ),
+            artifact_store_max_mb: env::var("KARI_ARTIFACT_STORE_MAX_MB")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10_240),
+
+            build_log_max_kb: env::var("KARI_BUILD_LOG_MAX_KB")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2048),
+
+            build_log_keep: env::var("KARI_BUILD_LOG_KEEP")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+
+            source_archive_max_mb: env::var("KARI_SOURCE_ARCHIVE_MAX_MB")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2048),
+
+            selftest_timeout_secs: env::var("KARI_SELFTEST_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(15),
+
+            activation_health_secs: env::var("KARI_ACTIVATION_HEALTH_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+
+            approval_timeout_secs: env::var("KARI_APPROVAL_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3600),
+
+            health_check_timeout_secs: env::var("KARI_HEALTH_CHECK_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+
+            health_check_retries: env::var("KARI_HEALTH_CHECK_RETRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+
+            health_check_interval_secs: env::var("KARI_HEALTH_CHECK_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2),
+
+            releases_keep: env::var("KARI_RELEASES_KEEP")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5)
+                .max(1),
+
+            tamper_watch: env::var("KARI_TAMPER_WATCH")
+                .map(|v| v != "0" && !v.eq_ignore_ascii_case("false"))
+                .unwrap_or(true),
+
+            tamper_self_heal: env::var("KARI_TAMPER_SELF_HEAL")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+
+            tamper_settle_ms: env::var("KARI_TAMPER_SETTLE_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2000),
+
+            crash_loop_starts: env::var("KARI_CRASH_LOOP_STARTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n: &u32| *n > 0)
+                .unwrap_or(5),
+
+            crash_loop_window_secs: env::var("KARI_CRASH_LOOP_WINDOW_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n: &u64| *n > 0)
+                .unwrap_or(300),
+
+            circuit_poll_ms: env::var("KARI_CIRCUIT_POLL_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(15_000),
+
+            key_max_age_secs: env::var("KARI_KEY_MAX_AGE_DAYS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(max_key_age_secs)
+                .unwrap_or(0),
+
+            key_rotation_poll_ms: env::var("KARI_KEY_ROTATION_POLL_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3_600_000),
+
+            tls_policy: env::var("KARI_TLS_POLICY")
+                .ok()
+                .and_then(|v| TlsPolicy::parse(&v))
+                .unwrap_or_default(),
+            ocsp_stapling: env::var("KARI_OCSP_STAPLING")
+                .map(|v| v != "0" && !v.eq_ignore_ascii_case("false"))
+                .unwrap_or(true),
+            ocsp_resolvers: env::var("KARI_OCSP_RESOLVERS")
+                .map(|v| {
+                    v.split(',')
+                        .map(|r| r.trim().to_string())
+                        .filter(|r| !r.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            must_staple: env::var("KARI_MUST_STAPLE")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+
+            acme_directory_url: env::var("KARI_ACME_DIRECTORY")
+                .unwrap_or_else(|_| LETS_ENCRYPT_DIRECTORY.to_string()),
+            acme_contact: env::var("KARI_ACME_EMAIL")
+                .ok()
+                .filter(|email| !email.is_empty()),
+            acme_account_key: PathBuf::from(
+                env::var("KARI_ACME_ACCOUNT_KEY")
+                    .unwrap_or_else(|_| "/etc/kari/acme-account.key".to_string()),
+            ),
+            acme_caa_domain: env::var("KARI_ACME_CAA_DOMAIN")
+                .unwrap_or_else(|_| "letsencrypt.org".to_string()),
+
+            known_hosts_file: PathBuf::from(
+                env::var("KARI_KNOWN_HOSTS")
+                    .unwrap_or_else(|_| "/etc/kari/ssh_known_hosts".to_string()),
+            ),
+
+            sshd_config_dir: PathBuf::from(
+                env::var("KARI_SSHD_CONFIG_DIR")
+                    .unwrap_or_else(|_| "/etc/ssh/sshd_config.d".to_string()),
+            ),
+            sftp_root: PathBuf::from(
+                env::var("KARI_SFTP_ROOT").unwrap_or_else(|_| "/srv/kari-sftp".to_string()),
+            ),
+
+            apt_conf_dir: PathBuf::from(
+                env::var("KARI_APT_CONF_DIR").unwrap_or_else(|_| "/etc/apt/apt.conf.d".to_string()),
+            ),
+            patch_poll_ms: env::var("KARI_PATCH_POLL_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60_000),
+
+            build_slots: env::var("KARI_BUILD_SLOTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+
+            mac_mode: env::var("KARI_MAC")
+                .ok()
+                .and_then(|v| MacMode::parse(&v))
+                .unwrap_or_default(),
+            apparmor_dir: PathBuf::from(
+                env::var("KARI_APPARMOR_DIR").unwrap_or_else(|_| "/etc/apparmor.d".to_string()),
+            ),
+
+            log_filter: env::var("KARI_LOG")
+                .or_else(|_| env::var("RUST_LOG"))
+                .unwrap_or_else(|_| "info".to_string()),
+
+            config_bundle_key: PathBuf::from(
+                env::var("KARI_CONFIG_BUNDLE_KEY")
+                    .unwrap_or_else(|_| "/etc/kari/config-bundle.pub".to_string()),
+            ),
+
+            heartbeat_url: env::var("KARI_HEARTBEAT_URL")
+                .ok()
+                .filter(|url| !url.is_empty()),
+
+            heartbeat_key: PathBuf::from(
+                env::var("KARI_HEARTBEAT_KEY")
+                    .unwrap_or_else(|_| "/etc/kari/heartbeat.key".to_string()),
+            ),
+
+            heartbeat_interval_ms: env::var("KARI_HEARTBEAT_INTERVAL_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60_000),
+
+            log_forward_url: env::var("KARI_LOG_FORWARD_URL")
+                .ok()
+                .filter(|url| !url.is_empty()),
+            log_forward_headers: env::var("KARI_LOG_FORWARD_HEADERS")
+                .ok()
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            log_forward_batch_lines: env::var("KARI_LOG_FORWARD_BATCH_LINES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(500),
+            log_forward_batch_ms: env::var("KARI_LOG_FORWARD_BATCH_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2_000),
+            log_forward_poll_ms: env::var("KARI_LOG_FORWARD_POLL_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10_000),
+
+            agent_toml: PathBuf::from(
+                env::var("KARI_AGENT_TOML").unwrap_or_else(|_| "/etc/kari/agent.toml".to_string()),
+            ),
+
+            notify_poll_ms: env::var("KARI_NOTIFY_POLL_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60_000),
+
+            disk_health_poll_ms: env::var("KARI_DISK_HEALTH_POLL_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300_000),
+
+            deploy_log_retention_days: env::var("KARI_DEPLOY_LOG_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+
+            audit_log_retention_days: env::var("KARI_AUDIT_LOG_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+
+            access_log_retention_days: env::var("KARI_ACCESS_LOG_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
+
+            audit_log_dir: PathBuf::from(
+                env::var("KARI_AUDIT_LOG_DIR")
+                    .unwrap_or_else(|_| "/var/log/kari/audit".to_string()),
+            ),
+
+            access_log_dir: PathBuf::from(
+                env::var("KARI_ACCESS_LOG_DIR")
+                    .unwrap_or_else(|_| "/var/log/kari/access".to_string()),
+            ),
+        }
+    }
+
+    /// 🗑️ Where each log class lives and how long it is kept.
+    pub fn log_retention(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            classes: vec![
+                ClassPolicy {
+                    class: LogClass::Deploy,
+                    dir: build_logs_root(&self.state_dir),
+                    retention_days: self.deploy_log_retention_days,
+                },
+                ClassPolicy {
+                    class: LogClass::Audit,
+                    dir: self.audit_log_dir.clone(),
+                    retention_days: self.audit_log_retention_days,
+                },
+                ClassPolicy {
+                    class: LogClass::Access,
+                    dir: self.access_log_dir.clone(),
+                    retention_days: self.access_log_retention_days,
+                },
+            ],
+        }
+    }
+}
+
+// ==============================================================================
+// 📄 agent.toml
+// ==============================================================================
+//
+// What is too structured for environment variables lives in agent.toml: the
+// [notify.*] channels and routes (notify.rs), [operations.*] (host_ops.rs) and
+// [satellites.*] (satellite.rs). Each module validates its own tables; here
+// they only have to be well-formed. Unknown tables and keys are errors rather
+// than silently ignored settings.
+
+/// A string or an array of strings, e.g. `to = "ops@example.com"`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Strings {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Strings {
+    pub fn into_vec(self) -> Vec<String> {
+        match self {
+            Self::One(value) => vec![value],
+            Self::Many(values) => values,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AgentToml {
+    pub notify: NotifyToml,
+    pub operations: BTreeMap<String, OperationToml>,
+    pub satellites: BTreeMap<String, SatelliteToml>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyToml {
+    pub channels: BTreeMap<String, ChannelToml>,
+    pub routes: BTreeMap<String, Strings>, // Event type → channel names
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChannelToml {
+    #[serde(rename = "type")]
+    pub kind: String, // slack | webhook | pagerduty | smtp
+    pub url: Option<String>,
+    pub routing_key: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<Strings>,
+    pub server: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OperationToml {
+    pub argv: Vec<String>,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub params: BTreeMap<String, String>, // Slot → parameter kind
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SatelliteToml {
+    pub host: String,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub sudo: bool,
+    pub proxy: Option<String>,
+    pub services: Vec<String>,
+}
+
+impl AgentToml {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// agent.toml at `path`, or None when it does not exist.
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        let meta = match std::fs::metadata(path) {
+            Ok(meta) => meta,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        // 🛡️ Zero-Trust: Webhook URLs and routing keys are credentials, and
+        // [operations.*] decide what runs as root
+        if meta.mode() & 0o077 != 0 || meta.uid() != nix::unistd::geteuid().as_raw() {
+            return Err(format!(
+                "{} must be owned by the agent and mode 0600",
+                path.display()
+            ));
         }
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&text)
+            .map(Some)
+            .map_err(|e| format!("{}: {}", path.display(), e))
     }
 }
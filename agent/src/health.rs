@@ -0,0 +1,227 @@
+// agent/src/health.rs
+//
+// 🩺 The standard gRPC health service (grpc.health.v1), served next to
+// SystemAgent on the same socket. Orchestrators, grpc_health_probe and the Go
+// API's stock health client can ask whether the agent is up without knowing
+// any kari RPC; GetSystemStatus stays the detailed view.
+//
+// Two services are reported: "" (the agent as a whole) and
+// kari.agent.v1.SystemAgent. Both turn SERVING once state recovery is done and
+// the socket serves; before that nothing answers at all. On shutdown both turn
+// NOT_SERVING while the socket still answers, so watchers hear it before their
+// streams are cut. Callers still pass the peer credential check, like any
+// other request.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio_stream::Stream;
+use tonic::{Request, Response, Status};
+
+pub mod proto {
+    tonic::include_proto!("grpc.health.v1");
+}
+
+use proto::health_check_response::ServingStatus;
+use proto::health_server::{Health, HealthServer};
+use proto::{HealthCheckRequest, HealthCheckResponse};
+
+/// The agent as a whole, by the protocol's convention.
+pub const OVERALL: &str = "";
+
+/// How long the socket keeps serving once shutdown is reported.
+pub const SHUTDOWN_GRACE: Duration = Duration::from_millis(500);
+
+type Statuses = Arc<Mutex<HashMap<String, watch::Sender<ServingStatus>>>>;
+
+/// Sets what the health service reports.
+#[derive(Clone, Default)]
+pub struct HealthReporter {
+    statuses: Statuses,
+}
+
+impl HealthReporter {
+    pub fn set_serving(&self, service: &str) {
+        self.set(service, ServingStatus::Serving);
+    }
+
+    pub fn set_not_serving(&self, service: &str) {
+        self.set(service, ServingStatus::NotServing);
+    }
+
+    fn set(&self, service: &str, status: ServingStatus) {
+        let mut statuses = self.statuses.lock().unwrap_or_else(|e| e.into_inner());
+        match statuses.get(service) {
+            Some(sender) => {
+                sender.send_replace(status);
+            }
+            None => {
+                statuses.insert(service.to_string(), watch::channel(status).0);
+            }
+        }
+    }
+
+    /// The service's status as it changes, starting with the current one.
+    /// An unknown service is SERVICE_UNKNOWN until it is first set.
+    fn subscribe(&self, service: &str) -> watch::Receiver<ServingStatus> {
+        let mut statuses = self.statuses.lock().unwrap_or_else(|e| e.into_inner());
+        statuses
+            .entry(service.to_string())
+            .or_insert_with(|| watch::channel(ServingStatus::ServiceUnknown).0)
+            .subscribe()
+    }
+}
+
+/// Reports `services` NOT_SERVING, then keeps `server` running for `grace`
+/// (or until it stops) so open Watch streams deliver the change.
+pub async fn wind_down<F: Future>(
+    reporter: &HealthReporter,
+    services: &[&str],
+    server: Pin<&mut F>,
+    grace: Duration,
+) {
+    for service in services {
+        reporter.set_not_serving(service);
+    }
+    let _ = tokio::time::timeout(grace, server).await;
+}
+
+/// A reporter, every service unknown, and the gRPC service that reads it.
+pub fn health_service() -> (HealthReporter, HealthServer<HealthService>) {
+    let reporter = HealthReporter::default();
+    let service = HealthService {
+        reporter: reporter.clone(),
+    };
+    (reporter, HealthServer::new(service))
+}
+
+pub struct HealthService {
+    reporter: HealthReporter,
+}
+
+#[tonic::async_trait]
+impl Health for HealthService {
+    async fn check(
+        &self,
+        request: Request<HealthCheckRequest>,
+    ) -> Result<Response<HealthCheckResponse>, Status> {
+        let service = request.into_inner().service;
+        let status = self
+            .reporter
+            .statuses
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&service)
+            .map(|sender| *sender.borrow())
+            .filter(|status| *status != ServingStatus::ServiceUnknown);
+        match status {
+            Some(status) => Ok(Response::new(HealthCheckResponse {
+                status: status as i32,
+            })),
+            None => Err(Status::not_found(format!("Unknown service '{}'", service))),
+        }
+    }
+
+    type WatchStream = Pin<Box<dyn Stream<Item = Result<HealthCheckResponse, Status>> + Send>>;
+
+    async fn watch(
+        &self,
+        request: Request<HealthCheckRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        let mut statuses = self.reporter.subscribe(&request.into_inner().service);
+        let stream = async_stream::stream! {
+            loop {
+                let status = *statuses.borrow_and_update();
+                yield Ok(HealthCheckResponse { status: status as i32 });
+                if statuses.changed().await.is_err() {
+                    break;
+                }
+            }
+        };
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_stream::StreamExt;
+
+    fn check(service: &str) -> Request<HealthCheckRequest> {
+        Request::new(HealthCheckRequest {
+            service: service.to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn checks_and_watches_follow_the_reporter() {
+        let reporter = HealthReporter::default();
+        let health = HealthService {
+            reporter: reporter.clone(),
+        };
+        let unknown = health.check(check(OVERALL)).await.unwrap_err();
+        assert_eq!(unknown.code(), tonic::Code::NotFound);
+
+        // A watch may start before the service is known
+        let mut watch = health.watch(check(OVERALL)).await.unwrap().into_inner();
+        let first = watch.next().await.unwrap().unwrap();
+        assert_eq!(first.status, ServingStatus::ServiceUnknown as i32);
+
+        reporter.set_serving(OVERALL);
+        let serving = watch.next().await.unwrap().unwrap();
+        assert_eq!(serving.status, ServingStatus::Serving as i32);
+        let checked = health.check(check(OVERALL)).await.unwrap().into_inner();
+        assert_eq!(checked.status, ServingStatus::Serving as i32);
+
+        reporter.set_not_serving(OVERALL);
+        let stopped = watch.next().await.unwrap().unwrap();
+        assert_eq!(stopped.status, ServingStatus::NotServing as i32);
+    }
+
+    #[tokio::test]
+    async fn watchers_hear_the_shutdown_before_the_socket_goes() {
+        use proto::health_client::HealthClient;
+        use tonic::transport::{Endpoint, Server, Uri};
+
+        let (reporter, health) = health_service();
+        reporter.set_serving(OVERALL);
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let server = Server::builder().add_service(health).serve_with_incoming(
+            tokio_stream::once(Ok::<_, std::io::Error>(server_io)).chain(tokio_stream::pending()),
+        );
+        tokio::pin!(server);
+
+        let mut client_io = Some(client_io);
+        let channel = Endpoint::from_static("http://kari-agent.test").connect_with_connector_lazy(
+            tower::service_fn(move |_: Uri| {
+                let io = client_io.take();
+                async move { io.ok_or_else(|| std::io::Error::other("Channel already used")) }
+            }),
+        );
+        let mut client = HealthClient::new(channel);
+
+        let mut watch = tokio::select! {
+            _ = &mut server => unreachable!("the server stopped on its own"),
+            watch = client.watch(HealthCheckRequest { service: OVERALL.to_string() }) => {
+                watch.unwrap().into_inner()
+            }
+        };
+        let serving = tokio::select! {
+            _ = &mut server => unreachable!("the server stopped on its own"),
+            serving = watch.message() => serving.unwrap().unwrap(),
+        };
+        assert_eq!(serving.status, ServingStatus::Serving as i32);
+
+        let (_, stopped) = tokio::join!(
+            wind_down(&reporter, &[OVERALL], server.as_mut(), SHUTDOWN_GRACE),
+            watch.message()
+        );
+        assert_eq!(
+            stopped.unwrap().unwrap().status,
+            ServingStatus::NotServing as i32
+        );
+    }
+}
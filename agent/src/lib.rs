@@ -0,0 +1,15 @@
+// 🛡️ SLA: tonic::Status is the deliberate error boundary for every handler and validator.
+#![allow(clippy::result_large_err)]
+
+//! The agent as a library: `main.rs` wires it to the host, and the optional
+//! `kari-agent-testkit` feature wires it to in-memory fakes instead.
+
+pub mod config;
+pub mod health;
+pub mod server;
+pub mod sys;
+pub mod telemetry;
+pub mod validation;
+
+#[cfg(any(test, feature = "kari-agent-testkit"))]
+pub mod testkit; // In-process fakes and test runtime
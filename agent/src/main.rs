@@ -1,26 +1,34 @@
+// 🛡️ SLA: tonic::Status is the deliberate error boundary for every handler and validator.
+#![allow(clippy::result_large_err)]
+
 use std::fs;
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::UnixListener;
 use tokio::signal;
+use tonic::server::NamedService;
 use tonic::transport::Server;
 use tracing::{debug, error, info, warn};
 
-mod config;
-mod server;
-mod sys;
-
-use crate::config::AgentConfig;
-use crate::server::KariAgentService;
-use crate::server::kari_agent::system_agent_server::SystemAgentServer;
+use kari_agent::config::AgentConfig;
+use kari_agent::health;
+use kari_agent::server::KariAgentService;
+use kari_agent::server::kari_agent::system_agent_server::SystemAgentServer;
+use kari_agent::telemetry;
+use kari_agent::validation::ValidatingAgent;
 
 // 🛡️ SOLID: Import trait types for discovery, concrete types for construction
-use crate::sys::firewall::LinuxFirewallManager;
-use crate::sys::proxy::{ApacheManager, NginxManager};
-use crate::sys::scheduler::SystemdTimerManager;
-use crate::sys::ssl::LinuxSslEngine;
-use crate::sys::traits::ProxyManager;
+use kari_agent::sys::caller::{AuthenticatedStream, Caller};
+use kari_agent::sys::firewall::{self, LinuxFirewallManager};
+use kari_agent::sys::log_retention;
+use kari_agent::sys::privilege::{self, Capability, Privileges};
+use kari_agent::sys::proxy::{ApacheManager, NginxManager};
+use kari_agent::sys::scheduler::SystemdTimerManager;
+use kari_agent::sys::ssl::LinuxSslEngine;
+use kari_agent::sys::state::JsonStateStore;
+use kari_agent::sys::traits::ProxyManager;
 
 /// 🛡️ SLA: Automatic Proxy Discovery
 /// Probes the host system to determine the available ingress controller.
@@ -42,11 +50,53 @@ fn discover_proxy_manager() -> Result<Arc<dyn ProxyManager>, Box<dyn std::error:
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // 1. Core Telemetry
-    tracing_subscriber::fmt::init();
+    // 1. Core Telemetry (redacting; the filter comes from KARI_LOG)
+    let config = AgentConfig::load();
+    telemetry::init(&config.log_filter);
     info!("🚀 Karı Rust Agent (The Muscle) v2026.1 initializing...");
 
-    let config = AgentConfig::load();
+    // 🔁 Boot oneshot (kari-firewall.service): put the stored firewall rules
+    // back before the network comes up, then exit without serving
+    if std::env::args().nth(1).as_deref() == Some("--restore-firewall") {
+        let state_store = JsonStateStore::open(config.state_dir.clone())
+            .map_err(|e| format!("SLA Failure: State store unavailable: {}", e))?;
+        let firewall_mgr = LinuxFirewallManager::new(config.systemd_dir.clone());
+        let restored = firewall::restore(&state_store, &firewall_mgr).await?;
+        info!("🛡️ Firewall: {} rules restored from state", restored);
+        return Ok(());
+    }
+
+    // 🐞 Timer oneshot (kari-job-debug-port-*): shut the debug ports whose time is up
+    if std::env::args().nth(1).as_deref() == Some(firewall::REVOKE_EXPIRED_FLAG) {
+        let state_store = JsonStateStore::open(config.state_dir.clone())
+            .map_err(|e| format!("SLA Failure: State store unavailable: {}", e))?;
+        let firewall_mgr = LinuxFirewallManager::new(config.systemd_dir.clone());
+        let scheduler = SystemdTimerManager::new(config.systemd_dir.to_string_lossy().to_string());
+        let revoked = firewall::revoke_expired(
+            &state_store,
+            &firewall_mgr,
+            &scheduler,
+            chrono::Utc::now().timestamp(),
+        )
+        .await?;
+        info!("🐞 Firewall: {} lapsed debug ports revoked", revoked);
+        return Ok(());
+    }
+
+    // 🗑️ Timer oneshot (kari-job-log-retention): remove logs past their class's retention
+    if std::env::args().nth(1).as_deref() == Some(log_retention::ENFORCE_FLAG) {
+        let purged = log_retention::enforce(&config.log_retention(), std::time::SystemTime::now())?;
+        for purge in purged.iter().filter(|purge| purge.files > 0) {
+            info!(
+                "🗑️ Log retention: {} {} log file(s) removed ({} bytes)",
+                purge.files,
+                purge.class.name(),
+                purge.bytes
+            );
+        }
+        return Ok(());
+    }
+
     let socket_path = PathBuf::from(&config.socket_path);
 
     // 🛡️ Zero-Trust: Safe parent resolution
@@ -63,17 +113,78 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         fs::remove_file(&socket_path)?;
     }
 
-    // 3. 🛡️ SOLID: Dependency Discovery & Injection
+    // 3. 🛡️ Rootless: What this process may do, before anything tries to do it
+    let time_sync_dir = if config.chrony_sources_dir.exists() {
+        &config.chrony_sources_dir
+    } else {
+        &config.timesyncd_dir
+    };
+    let mut writable = vec![
+        (Capability::Users, config.web_root.as_path()),
+        (Capability::Users, config.logrotate_dir.as_path()),
+        (Capability::Units, config.systemd_dir.as_path()),
+        (Capability::Proxy, config.proxy_conf_dir.as_path()),
+        (Capability::Certificates, config.ssl_storage_dir.as_path()),
+        (Capability::Mail, config.postfix_dir.as_path()),
+        (Capability::TimeSync, time_sync_dir.as_path()),
+    ];
+    if config.apparmor_dir.exists() {
+        writable.push((Capability::Mac, config.apparmor_dir.as_path()));
+    }
+    if config.sshd_config_dir.exists() {
+        writable.push((Capability::Sftp, config.sshd_config_dir.as_path()));
+    }
+    // 🩹 Only apt hosts have one; the patch policy turns unattended-upgrades off there
+    if config.apt_conf_dir.exists() {
+        writable.push((Capability::Packages, config.apt_conf_dir.as_path()));
+    }
+    if let Some(known_hosts_dir) = config.known_hosts_file.parent() {
+        writable.push((Capability::Store, known_hosts_dir));
+    }
+    writable.push((Capability::Store, config.secrets_dir.as_path()));
+    for dir in [
+        &config.artifact_dir,
+        &config.audit_log_dir,
+        &config.access_log_dir,
+    ] {
+        if dir.exists() {
+            writable.push((Capability::Store, dir.as_path()));
+        }
+    }
+    let privileges = Arc::new(Privileges::probe(&writable).await);
+    if privileges.rootless {
+        let unavailable = privileges.unavailable_rpcs();
+        info!(
+            "🛡️ Rootless: running as {}; {} RPCs unavailable{}",
+            privileges.user,
+            unavailable.len(),
+            if unavailable.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", unavailable.join(", "))
+            }
+        );
+    }
+    privilege::install(Arc::clone(&privileges));
+
+    // 4. 🛡️ SOLID: Dependency Discovery & Injection
     // Each manager is discovered/constructed BEFORE the socket binds.
     // If the host isn't ready, the Muscle refuses to start.
     let proxy_mgr = discover_proxy_manager()?;
-    let firewall_mgr = Arc::new(LinuxFirewallManager::new());
-    let ssl_engine = Arc::new(LinuxSslEngine::new(config.ssl_storage_dir.clone()));
+    let firewall_mgr = Arc::new(LinuxFirewallManager::new(config.systemd_dir.clone()));
+    let ssl_engine = Arc::new(LinuxSslEngine::new(
+        config.ssl_storage_dir.clone(),
+        config.must_staple,
+    ));
     let job_scheduler = Arc::new(SystemdTimerManager::new(
         config.systemd_dir.to_string_lossy().to_string(),
     ));
+    let state_store = Arc::new(
+        JsonStateStore::open(config.state_dir.clone())
+            .map_err(|e| format!("SLA Failure: State store unavailable: {}", e))?,
+    );
 
-    // 4. Bind and Secure the Socket
+    // 5. Bind and Secure the Socket
     let listener = UnixListener::bind(&socket_path)?;
 
     let mut perms = fs::metadata(&socket_path)?.permissions();
@@ -81,16 +192,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     fs::set_permissions(&socket_path, perms)?;
 
     // 🛡️ Kernel-Level Handover (SO_PEERCRED Pre-requisite)
+    // A rootless agent keeps the socket and hands it over through the group,
+    // which the agent's user must belong to.
     let uid = config.expected_api_uid;
     let gid = config.expected_api_gid;
     nix::unistd::chown(
         &socket_path,
-        Some(nix::unistd::Uid::from_raw(uid)),
+        (!privileges.rootless).then(|| nix::unistd::Uid::from_raw(uid)),
         Some(nix::unistd::Gid::from_raw(gid)),
     )
     .map_err(|e| format!("SLA Failure: Failed to chown socket: {}", e))?;
 
-    // 5. Peer Credential Guard (Kernel-Level Auth)
+    // 6. Peer Credential Guard (Kernel-Level Auth)
     let incoming_stream = async_stream::stream! {
         loop {
             match listener.accept().await {
@@ -99,7 +212,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         // 🛡️ Zero-Trust: Only the Go API User or Root can talk to this socket
                         if cred.uid() == uid || cred.uid() == 0 {
                             debug!("✅ Verified connection: UID {}", cred.uid());
-                            yield Ok::<_, std::io::Error>(stream);
+                            // The identity rides along to every request on it
+                            let caller = Caller::Peer { uid: cred.uid(), pid: cred.pid() };
+                            yield Ok::<_, std::io::Error>(AuthenticatedStream::new(stream, caller));
                         } else {
                             warn!("🚨 SECURITY ALERT: Unauthorized connection from UID {}", cred.uid());
                         }
@@ -113,27 +228,65 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
-    // 6. Start the Service
-    let agent_service =
-        KariAgentService::new(config, proxy_mgr, firewall_mgr, ssl_engine, job_scheduler);
+    // 7. Start the Service
+    let prewarm_timeout = Duration::from_secs(config.prewarm_timeout_secs);
+    let agent_service = KariAgentService::new(
+        config,
+        proxy_mgr,
+        firewall_mgr,
+        ssl_engine,
+        job_scheduler,
+        state_store,
+    );
+    // ⚡ Readiness: recovery and prewarming both finish before the socket serves
+    let (recovered, prewarmed) = tokio::join!(
+        agent_service.recover_state(),
+        tokio::time::timeout(prewarm_timeout, agent_service.prewarm())
+    );
+    recovered.map_err(|e| format!("SLA Failure: State recovery failed: {}", e))?;
+    if prewarmed.is_err() {
+        warn!(
+            "⚡ Prewarm still running after {:?}; serving anyway",
+            prewarm_timeout
+        );
+    }
+    agent_service.start_background_tasks();
+    let agent_server =
+        SystemAgentServer::new(ValidatingAgent::with_privileges(agent_service, privileges));
+    let agent_name = <SystemAgentServer<ValidatingAgent<KariAgentService>> as NamedService>::NAME;
+    // 🩺 Ready: recovery is done and the socket serves from here on
+    let (health_reporter, health_server) = health::health_service();
+    for service in [health::OVERALL, agent_name] {
+        health_reporter.set_serving(service);
+    }
     let grpc_server = Server::builder()
-        .add_service(SystemAgentServer::new(agent_service))
+        .add_service(health_server)
+        .add_service(agent_server)
         .serve_with_incoming(incoming_stream);
+    tokio::pin!(grpc_server);
 
     info!(
         "⚙️ Agent listening on {:?} [Target UID: {}]",
         socket_path, uid
     );
 
-    // 7. Graceful Shutdown
+    // 8. Graceful Shutdown
     tokio::select! {
-        res = grpc_server => {
+        res = &mut grpc_server => {
             if let Err(e) = res {
                 error!("CRITICAL: Server crashed: {}", e);
             }
         }
         _ = signal::ctrl_c() => {
             info!("🛑 Shutdown signal received. Cleaning up...");
+            // 🩺 Watchers hear NOT_SERVING before the socket goes
+            health::wind_down(
+                &health_reporter,
+                &[health::OVERALL, agent_name],
+                grpc_server.as_mut(),
+                health::SHUTDOWN_GRACE,
+            )
+            .await;
         }
     }
 
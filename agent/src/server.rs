@@ -1,24 +1,108 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::os::unix::fs::PermissionsExt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use sysinfo::System;
-use tokio::sync::mpsc;
+use tokio::io::AsyncReadExt;
+use tokio::sync::{broadcast, mpsc, oneshot};
 use tokio_stream::wrappers::ReceiverStream;
+use tonic::metadata::MetadataMap;
 use tonic::{Request, Response, Status};
 use tracing::{info, warn};
 use zeroize::Zeroizing;
 
 use crate::config::AgentConfig;
-use crate::sys::build::SystemBuildManager;
+use crate::sys::acme::AcmeEngine;
+use crate::sys::app_cron;
+use crate::sys::artifacts::{self, ArtifactStore};
+use crate::sys::autostart::{self, AutostartManagers};
+use crate::sys::build::{SystemBuildManager, describe_usage};
+use crate::sys::build_log::{self, BuildLogWriter};
+use crate::sys::build_queue::{self, BuildQueue, BuildTicket};
+use crate::sys::bundle::BundleKey;
+use crate::sys::caller::Caller;
+use crate::sys::capacity;
+use crate::sys::certificates;
+use crate::sys::circuit::{self, CircuitBreaker};
+use crate::sys::cleanup::{self, SystemReleaseManager};
+use crate::sys::config_bundle;
+use crate::sys::disk_health::{self, DiskHealthWatch, DiskStatus, SmartctlDiskHealthProbe};
+use crate::sys::dns::StubDnsResolver;
+use crate::sys::env_file::DotenvFileManager;
+use crate::sys::executor::{CommandSpec, Executor, SystemExecutor};
+use crate::sys::facts;
+use crate::sys::firewall::{self, policy_rule_key, protocol_name, record_policy, rule_log_id};
+use crate::sys::firewall_log::JournalFirewallEventSource;
 use crate::sys::git::SystemGitManager;
-use crate::sys::jail::{JailManager, LinuxJailManager};
+use crate::sys::heartbeat::{self, CurlHeartbeatTransport, Heartbeat, HostIdentity};
+use crate::sys::host_ops;
+use crate::sys::http_probe;
+use crate::sys::image_gc;
+use crate::sys::issuance::{self, Diagnosis};
+use crate::sys::jail::{DirectoryAudit, JailManager, LinuxJailManager, PermissionProblem};
+use crate::sys::journal::{self, JournalctlReader};
+use crate::sys::key_rotation::{self, KeyRotation};
+use crate::sys::known_hosts;
+use crate::sys::log_forward::{HostLogShipper, LogForwarder, LogTarget};
+use crate::sys::log_retention::{self, ClassPurge, LogClass};
+use crate::sys::logs::LinuxLogManager;
+use crate::sys::mac::LinuxMacManager;
+use crate::sys::mail::PostfixRelayManager;
+use crate::sys::managed_configs::{self, ManagedConfigPaths};
+use crate::sys::migration::{self, AppExport, MigrationPaths};
+use crate::sys::mirrors::{self, BuildMirrors};
+use crate::sys::network::ProcNetworkInspector;
+use crate::sys::notify::{self, Notice, Notifications, Notifier};
+use crate::sys::operations::{OperationRecord, OperationResult, OperationState, OperationTracker};
+use crate::sys::packages;
+use crate::sys::paging::{self, FieldSelector};
+use crate::sys::patching::{self, HostPatcher, Patching};
+use crate::sys::pipeline::{Phase, phase, run_stage};
+use crate::sys::placement::{self, PlacementInputs};
+use crate::sys::podman::PodmanServiceManager;
+use crate::sys::privilege::{self, Privileges};
+use crate::sys::processes::{self, ProcProcessInspector};
+use crate::sys::reboot::{self, RebootRecord, RebootWindow, SystemRebootManager};
+use crate::sys::satellite::{self, Satellite, SshExecutor};
+use crate::sys::sbom;
+use crate::sys::scan::{ClamavScanner, ScanPolicy, SecretPatternScanner};
+use crate::sys::secret_store::{FileSecretStore, secret_ref};
 use crate::sys::secrets::ProviderCredential;
-use crate::sys::systemd::{LinuxSystemdManager, ServiceConfig, ServiceManager};
+use crate::sys::selftest;
+use crate::sys::sftp::{self, OpensshSftpManager};
+use crate::sys::snapshot::{self, SnapshotPaths};
+use crate::sys::source_archive::{self, StagedArchive};
+use crate::sys::state::{
+    AppRecord, CertificateRecord, FirewallRuleRecord, HealthCheckRecord, JobRecord, NS_APP_JOBS,
+    NS_APPS, NS_FIREWALL_RULES, NS_JOBS, NS_RELEASES, NS_TENANTS, ReleaseRecord, app_unit_holder,
+    firewall_rule_key, get_record, job_unit_holder, list_records, matches_labels, put_record,
+    release_key,
+};
+use crate::sys::systemd::{
+    DependencyKind, LinuxSystemdManager, ServiceConfig, ServiceManager, StartLimit, UnitDependency,
+    is_valid_env_key,
+};
+use crate::sys::tamper::{self, InotifyChangeSource, TamperWatch, WatchedDirs};
+use crate::sys::tenant::{SystemdSliceManager, tenant_slice_name};
+use crate::sys::timesync::SystemTimeSyncManager;
+use crate::sys::tls_policy;
 use crate::sys::traits::{
-    BuildManager, FirewallAction, FirewallManager, FirewallPolicy as TraitFirewallPolicy,
-    GitManager, JobIntent as TraitJobIntent, JobScheduler, Protocol, ProxyManager, SslEngine,
-    SslPayload as TraitSslPayload,
+    BuildManager, BuildNetwork, BuildUsage, CertificateIssuer, ClientLimits, ConfigChangeSource,
+    DiskHealthProbe, DnsResolver, EnvFileManager, FirewallAction, FirewallEventSource,
+    FirewallManager, FirewallPolicy as TraitFirewallPolicy, GitManager, HeartbeatTransport,
+    ImageManager, JobIntent as TraitJobIntent, JobScheduler, JournalQuery, JournalReader, Listener,
+    LogManager, LogShipper, MacManager, MacTarget, MailRelayConfig, MailRelayManager,
+    NetworkInspector, OcspStapling, Patcher, ProcessInspector, Protocol, ProxyManager, RateLimit,
+    RealIp, RebootManager, ReleaseManager, SecretStore, SftpAccessManager, SftpGrant,
+    SourceScanner, SslEngine, SslPayload as TraitSslPayload, StateStore, TenantQuota,
+    TenantSliceManager, TimeSyncManager, TimeSyncStatus as TraitTimeSyncStatus, VhostOptions,
+    VhostTls,
+};
+use crate::validation::{
+    Validate, redirect_rules, validate_domain_name, validate_identifier, validate_job_binary,
+    validate_labels, validate_package_intent, validate_port, validate_real_ip, validate_tenant_id,
 };
 use zeroize::Zeroize;
 
@@ -27,33 +111,620 @@ pub mod kari_agent {
     tonic::include_proto!("kari.agent.v1");
 }
 
+use kari_agent::deployment_event::{Phase as DeployPhase, Severity, State as DeployState};
+use kari_agent::log_class_purge::LogClass as PurgedLogClass;
+use kari_agent::permission_deviation::Problem as DeviationProblem;
 use kari_agent::system_agent_server::SystemAgent;
 use kari_agent::{
-    AgentResponse, DeleteRequest, DeployRequest, Empty, FileWriteRequest, FirewallPolicy,
-    JobIntent, LogChunk, PackageRequest, ProvisionJailRequest, ServiceRequest, SslPayload,
-    SystemStatus, TeardownRequest,
+    ActivateReleaseRequest, AgentResponse, AppEnvFileRequest, AppHealth, AppHealthRequest,
+    AppPermissionsReport, ArtifactChunk, ArtifactReceipt, ArtifactRequest, AutostartDiscrepancy,
+    AutostartReport, BuildClass, BuildLogRequest, BuildLogResponse, BuildQueueStatus,
+    BundleResponse, CancelDeploymentRequest, CertificateInfo, CertificateInfoRequest,
+    CircuitBreakerRequest, CircuitEvent, CircuitEventsRequest, Compression, ConfigBundleItem,
+    ConfigBundleReport, ConfigBundleRequest, ConfirmDeploymentRequest, DeleteRequest,
+    DeployArtifactChunk, DeployRequest, Deployment, DeploymentEvent, DeploymentList,
+    DeploymentUsage, DiskHealth, DiskHealthEvent, DiskHealthEventsRequest, DiskInfo, Empty,
+    ExportAppRequest, ExportStateRequest, ExposeDebugPortRequest, FileWriteRequest,
+    FirewallEventSummary, FirewallEventsRequest, FirewallPolicy, FirewallRuleHits,
+    FirewallRuleList, HostFacts, HostOperationRequest, ImportAppRequest, ImportStateRequest,
+    IssuanceCheck, IssuanceCheckRequest, IssueCertificateRequest, JailProcess, JailProcessList,
+    JailProcessesRequest, JobIntent, JobList, KeyRotationEvent, KeyRotationEventsRequest,
+    ListReleasesRequest, ListRequest, ListeningSocket, LogChunk, LogClassPurge, LogPurgeReport,
+    MacEnforcement, MailRelayRequest, ManagedConfig, ManagedConfigsResponse, ManagedJob,
+    NetworkInterfaceInfo, NetworkInventory, OcspStapleStatus, Operation, OperationRequest,
+    PackageChange, PackageRequest, PatchPolicy, PatchPolicyRequest, PatchReports,
+    PatchReportsRequest, PatchRun, PatchScope, PermissionDeviation, PlacementScore, PrivilegeGrant,
+    PrivilegeReport, ProvisionJailRequest, PruneImagesRequest, PruneImagesResponse,
+    PruneReleasesRequest, PruneReleasesResponse, PurgeTenantLogsRequest, RegistryCredentialRequest,
+    ReleaseInfo, ReleaseList, RollbackDeploymentRequest, SatelliteFirewallRequest, SatelliteInfo,
+    SatelliteKeyRequest, SatelliteList, SatelliteServiceRequest, SatelliteVhostRequest,
+    SbomRequest, SbomResponse, ScanFinding as ScanFindingEvent, ScheduleRebootRequest,
+    SecretRequest, SelfTestPhase, SelfTestReport, SelfTestRequest, ServiceLogEntry,
+    ServiceLogsRequest, ServiceRequest, SetRedirectsRequest, SftpAccess, SftpAccessRequest,
+    SshHostKeysRequest, SshHostKeysResponse, SslPayload, StackComponent, SystemStatus, TamperEvent,
+    TamperEventsRequest, TeardownRequest, TenantQuotaRequest, TimeSyncRequest, TimeSyncStatus,
+    VerifyAppPermissionsRequest, VerifyAutostartRequest, Vhost, VhostClientLimits, VhostList,
 };
 
-const ALLOWED_PKG_COMMANDS: &[&str] = &["apt-get", "apt", "dnf", "yum", "zypper"];
+/// The protobuf package this agent serves; heartbeats advertise it.
+const API_PACKAGE: &str = "kari.agent.v1";
+
+/// StreamFirewallEvents window when the client leaves interval_secs at 0.
+const DEFAULT_FIREWALL_EVENTS_INTERVAL_SECS: u64 = 10;
+
+/// StreamServiceLogs backlog when the client leaves lines at 0.
+const DEFAULT_SERVICE_LOG_LINES: u32 = 100;
+
+/// 🐞 ExposeDebugPort's lifetime when the client leaves duration_secs at 0.
+const DEFAULT_DEBUG_PORT_SECS: u32 = 3600;
+
+/// Writes the release's CycloneDX SBOM from its lockfiles; returns the component count.
+fn write_sbom(release_dir: &Path, path: &Path, domain: &str, release: &str) -> Result<u32, String> {
+    let components = sbom::scan_release(release_dir)?;
+    let doc = sbom::render_cyclonedx(domain, release, &components);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create SBOM directory: {}", e))?;
+    }
+    std::fs::write(path, doc).map_err(|e| format!("Failed to write SBOM: {}", e))?;
+    Ok(components.len() as u32)
+}
+
+/// 🛡️ Zero-Trust: Maps a policy's proto enums to our strict trait types.
+/// 🛡️ `created_by` is the authenticated caller, never the request's own claim.
+fn firewall_intent(req: &FirewallPolicy, caller: &Caller) -> Result<TraitFirewallPolicy, Status> {
+    use kari_agent::firewall_policy::{Action, Protocol as ProtoProtocol};
+
+    let action = match Action::try_from(req.action) {
+        Ok(Action::Allow) => FirewallAction::Allow,
+        Ok(Action::Deny) => FirewallAction::Deny,
+        Ok(Action::Reject) => FirewallAction::Reject,
+        Err(_) => return Err(Status::invalid_argument("Invalid firewall action")),
+    };
+
+    let protocol = match ProtoProtocol::try_from(req.protocol) {
+        Ok(ProtoProtocol::Tcp) => Protocol::Tcp,
+        Ok(ProtoProtocol::Udp) => Protocol::Udp,
+        Ok(ProtoProtocol::Both) => Protocol::Both,
+        Err(_) => return Err(Status::invalid_argument("Invalid protocol")),
+    };
+
+    // 🛡️ Zero-Trust: Parse and validate source IP if provided
+    let source_ip = match req.source_ip.as_deref().filter(|ip| !ip.is_empty()) {
+        Some(ip_str) => {
+            ip_str.parse::<std::net::IpAddr>().map_err(|_| {
+                Status::invalid_argument(format!("Zero-Trust: Invalid source IP: '{}'", ip_str))
+            })?;
+            Some(ip_str.to_string())
+        }
+        None => None,
+    };
+
+    Ok(TraitFirewallPolicy {
+        action,
+        port: req.port as u16,
+        protocol,
+        source_ip,
+        description: Some(req.description.clone()).filter(|d| !d.is_empty()),
+        created_by: Some(caller.to_string()),
+        log_denied: req.log_denied,
+    })
+}
+
+/// Runs one ManageService action against `services`.
+async fn run_service_action(
+    services: &dyn ServiceManager,
+    service_name: &str,
+    action: kari_agent::ServiceAction,
+) -> Result<(), String> {
+    use kari_agent::ServiceAction;
+
+    match action {
+        ServiceAction::Start => services.start(service_name).await,
+        ServiceAction::Stop => services.stop(service_name).await,
+        ServiceAction::Restart => services.restart(service_name).await,
+        ServiceAction::Reload => services.reload_daemon().await,
+        ServiceAction::Enable => services.enable_and_start(service_name).await,
+        ServiceAction::Disable => services.stop(service_name).await,
+    }
+}
+
+fn patch_scope(scope: patching::PatchScope) -> i32 {
+    match scope {
+        patching::PatchScope::Security => PatchScope::Security as i32,
+        patching::PatchScope::Full => PatchScope::Full as i32,
+    }
+}
+
+fn build_class(class: BuildClass) -> build_queue::BuildClass {
+    match class {
+        BuildClass::Standard => build_queue::BuildClass::Standard,
+        BuildClass::Light => build_queue::BuildClass::Light,
+        BuildClass::Heavy => build_queue::BuildClass::Heavy,
+    }
+}
+
+/// SetPatchPolicy's answer; no policy reads as disabled.
+fn patch_policy_message(policy: Option<&patching::PatchPolicy>) -> PatchPolicy {
+    policy.map_or_else(PatchPolicy::default, |policy| PatchPolicy {
+        enabled: true,
+        window: policy.window.clone(),
+        scope: patch_scope(policy.scope),
+        drain_secs: policy.drain_secs,
+        distro_automation_disabled: policy.handed_over.clone(),
+    })
+}
+
+/// 🗜️ A compressed log batch is sent once it holds this much output...
+const LOG_BATCH_BYTES: usize = 64 * 1024;
+/// ...or once its oldest line has waited this long, so slow builds still stream.
+const LOG_BATCH_WINDOW: Duration = Duration::from_millis(200);
+
+/// Sends the pending batch as one zstd frame. A batch too small to shrink goes
+/// out compressed all the same: the client asked for one encoding.
+async fn flush_log_batch(
+    client: &mpsc::Sender<Result<LogChunk, Status>>,
+    trace_id: &str,
+    batch: &mut String,
+) {
+    if batch.is_empty() {
+        return;
+    }
+    let item = zstd::encode_all(batch.as_bytes(), 3)
+        .map(|compressed_content| LogChunk {
+            trace_id: trace_id.to_string(),
+            content: String::new(),
+            finding: None,
+            usage: None,
+            compressed_content,
+            raw_content: Vec::new(),
+            event: None,
+            queued: None,
+        })
+        .map_err(|e| Status::internal(format!("[SLA ERROR] Log compression failed: {}", e)));
+    batch.clear();
+    let _ = client.send(item).await;
+}
 
 // ==============================================================================
 // 🛡️ SOLID: KariAgentService is the single gRPC boundary.
 // All execution is delegated to injected trait objects (SLA: Single Layer Abstraction).
 // ==============================================================================
 
+#[derive(Clone)]
 pub struct KariAgentService {
     config: AgentConfig,
     jail_mgr: Arc<dyn JailManager>,
     svc_mgr: Arc<dyn ServiceManager>,
+    container_svc_mgr: Arc<dyn ServiceManager>,
     git_mgr: Arc<dyn GitManager>,
     build_mgr: Arc<dyn BuildManager>,
     proxy_mgr: Arc<dyn ProxyManager>,
     firewall_mgr: Arc<dyn FirewallManager>,
     ssl_engine: Arc<dyn SslEngine>,
     job_scheduler: Arc<dyn JobScheduler>,
+    mail_relay: Arc<dyn MailRelayManager>,
+    secret_store: Arc<dyn SecretStore>,
+    image_mgr: Arc<dyn ImageManager>,
+    state_store: Arc<dyn StateStore>,
+    env_file_mgr: Arc<dyn EnvFileManager>,
+    log_mgr: Arc<dyn LogManager>,
+    tenant_slice_mgr: Arc<dyn TenantSliceManager>,
+    source_scanners: Vec<Arc<dyn SourceScanner>>,
+    firewall_events: Arc<dyn FirewallEventSource>,
+    config_changes: Arc<dyn ConfigChangeSource>,
+    network: Arc<dyn NetworkInspector>,
+    time_sync: Arc<dyn TimeSyncManager>,
+    dns: Arc<dyn DnsResolver>,
+    heartbeat: Arc<dyn HeartbeatTransport>,
+    notifiers: BTreeMap<String, Arc<dyn Notifier>>,
+    disk_probe: Arc<dyn DiskHealthProbe>,
+    reboot_mgr: Arc<dyn RebootManager>,
+    mac_mgr: Arc<dyn MacManager>,
+    processes: Arc<dyn ProcessInspector>,
+    journal: Arc<dyn JournalReader>,
+    sftp_mgr: Arc<dyn SftpAccessManager>,
+    patcher: Arc<dyn Patcher>,
+    log_shipper: Arc<dyn LogShipper>,
+    privileges: Arc<Privileges>,
+    acme: Arc<dyn CertificateIssuer>,
+    release_mgr: Arc<dyn ReleaseManager>,
+    ssh_client: Arc<dyn Executor>,
+    executor: Arc<dyn Executor>,
+    notices: broadcast::Sender<Notice>,
+    disk_health_events: broadcast::Sender<disk_health::DiskHealthEvent>,
+    disk_status: Arc<Mutex<Vec<DiskStatus>>>, // Latest DiskHealthWatch assessment
+    tamper_events: broadcast::Sender<tamper::TamperEvent>,
+    circuit_events: broadcast::Sender<circuit::CircuitEvent>,
+    key_rotation_events: broadcast::Sender<key_rotation::KeyRotationEvent>,
+    operations: Arc<OperationTracker>,
+    pending_approvals: Arc<Mutex<HashMap<String, oneshot::Sender<bool>>>>, // trace_id → approve?
+    cancellable_deploys: Arc<Mutex<HashMap<String, oneshot::Sender<()>>>>, // trace_id → cancel
+    deploys_in_flight: Arc<AtomicU64>, // Streams started and not yet ended
+    build_queue: Arc<BuildQueue>,
     system_monitor: Arc<Mutex<System>>,
 }
 
+/// Every trait object the service delegates to. Production wiring lives in
+/// `KariAgentService::new`; the testkit assembles the same set from fakes.
+pub struct AgentManagers {
+    pub jail_mgr: Arc<dyn JailManager>,
+    pub svc_mgr: Arc<dyn ServiceManager>,
+    pub container_svc_mgr: Arc<dyn ServiceManager>,
+    pub git_mgr: Arc<dyn GitManager>,
+    pub build_mgr: Arc<dyn BuildManager>,
+    pub proxy_mgr: Arc<dyn ProxyManager>,
+    pub firewall_mgr: Arc<dyn FirewallManager>,
+    pub ssl_engine: Arc<dyn SslEngine>,
+    pub job_scheduler: Arc<dyn JobScheduler>,
+    pub mail_relay: Arc<dyn MailRelayManager>,
+    pub secret_store: Arc<dyn SecretStore>,
+    pub image_mgr: Arc<dyn ImageManager>,
+    pub state_store: Arc<dyn StateStore>,
+    pub env_file_mgr: Arc<dyn EnvFileManager>,
+    pub log_mgr: Arc<dyn LogManager>,
+    pub tenant_slice_mgr: Arc<dyn TenantSliceManager>,
+    pub source_scanners: Vec<Arc<dyn SourceScanner>>,
+    pub firewall_events: Arc<dyn FirewallEventSource>,
+    pub config_changes: Arc<dyn ConfigChangeSource>,
+    pub network: Arc<dyn NetworkInspector>,
+    pub time_sync: Arc<dyn TimeSyncManager>,
+    pub dns: Arc<dyn DnsResolver>,
+    pub heartbeat: Arc<dyn HeartbeatTransport>,
+    /// Channels routable from agent.toml without being declared there.
+    pub notifiers: BTreeMap<String, Arc<dyn Notifier>>,
+    pub disk_probe: Arc<dyn DiskHealthProbe>,
+    pub reboot_mgr: Arc<dyn RebootManager>,
+    pub mac_mgr: Arc<dyn MacManager>,
+    pub processes: Arc<dyn ProcessInspector>,
+    pub journal: Arc<dyn JournalReader>,
+    pub sftp_mgr: Arc<dyn SftpAccessManager>,
+    pub patcher: Arc<dyn Patcher>,
+    pub log_shipper: Arc<dyn LogShipper>,
+    pub privileges: Arc<Privileges>,
+    pub acme: Arc<dyn CertificateIssuer>,
+    pub release_mgr: Arc<dyn ReleaseManager>,
+    /// Runs the local `ssh` client for satellites.
+    pub ssh_client: Arc<dyn Executor>,
+    /// Runs the commands handlers issue themselves (teardown, chown, host operations).
+    pub executor: Arc<dyn Executor>,
+}
+
+/// A deploy request that passed pre-flight, ready for its build.
+struct PreparedDeploy {
+    req: DeployRequest,
+    source: DeploySource,
+    release: String, // Timestamp; names the release directory and its log
+    release_dir: PathBuf,
+    app_user: String,
+    cron_intents: Vec<TraitJobIntent>,
+    offline_build: bool,
+}
+
+/// Where a deploy's source comes from.
+enum DeploySource {
+    Git, // req.repo_url at req.branch
+    Archive(Box<ArchiveSource>),
+    Image, // req.image, pulled in place of a clone and build
+}
+
+/// 📦 DeployArtifact's tar.gz: the first chunk's data, with the rest still on
+/// the request stream, or a URL to fetch it from.
+struct ArchiveSource {
+    sha256: String,
+    url: Option<String>,
+    strip_components: usize,
+    first: Vec<u8>,
+    rest: tonic::Streaming<DeployArtifactChunk>,
+}
+
+/// 🪝 A deploy's hooks and what they run with. The build clears its own copy
+/// of the environment, so this one is kept for them and cleared on drop.
+struct DeployHooks {
+    pre_deploy: Vec<String>,
+    post_deploy: Vec<String>,
+    release_dir: PathBuf,
+    app_user: String,
+    envs: HashMap<String, String>,
+}
+
+impl DeployHooks {
+    fn of(job: &PreparedDeploy) -> Self {
+        let (pre_deploy, post_deploy) = (&job.req.pre_deploy_argv, &job.req.post_deploy_argv);
+        Self {
+            pre_deploy: pre_deploy.clone(),
+            post_deploy: post_deploy.clone(),
+            release_dir: job.release_dir.clone(),
+            app_user: job.app_user.clone(),
+            envs: if pre_deploy.is_empty() && post_deploy.is_empty() {
+                HashMap::new()
+            } else {
+                job.req.env_vars.clone()
+            },
+        }
+    }
+}
+
+impl Drop for DeployHooks {
+    fn drop(&mut self) {
+        // 🛡️ Privacy: Clear the hook environment variables from RAM
+        for (_, mut val) in self.envs.drain() {
+            val.zeroize();
+        }
+    }
+}
+
+/// 🛑 The handle CancelDeployment pulls on. Registered under the deploy's
+/// trace id for as long as the deploy may still be stopped; dropping it (when
+/// the switch begins, or the deploy ends) withdraws the registration.
+struct CancelSlot {
+    deploys: Arc<Mutex<HashMap<String, oneshot::Sender<()>>>>,
+    trace_id: String,
+    cancelled: Option<oneshot::Receiver<()>>, // None without a trace id
+}
+
+impl CancelSlot {
+    /// Resolves once CancelDeployment names this deploy; never otherwise.
+    async fn cancelled(&mut self) {
+        if let Some(cancelled) = self.cancelled.as_mut()
+            && cancelled.await.is_ok()
+        {
+            return;
+        }
+        std::future::pending().await
+    }
+}
+
+impl Drop for CancelSlot {
+    fn drop(&mut self) {
+        if self.cancelled.take().is_some() {
+            // Closed by the line above; a later deploy reusing the id keeps its own
+            self.deploys
+                .lock()
+                .unwrap()
+                .retain(|trace_id, cancel| trace_id != &self.trace_id || !cancel.is_closed());
+        }
+    }
+}
+
+/// 🧭 One deploy counted in flight (GetPlacementScore's queue) for as long
+/// as it is held.
+struct InFlight(Arc<AtomicU64>);
+
+impl InFlight {
+    fn enter(counter: &Arc<AtomicU64>) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(Arc::clone(counter))
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A phase that has sent STARTED and not yet its outcome.
+struct OpenPhase {
+    phase: DeployPhase,
+    started_ms: i64,
+    warnings: Vec<String>,
+}
+
+/// 📍 A deploy's `DeploymentEvent`s, sent on its log next to the text. Keeps
+/// the phases still open, so an outcome carries its phase's start time and a
+/// deploy cut short can fail whatever it was in the middle of.
+struct DeployProgress {
+    tx: mpsc::Sender<Result<LogChunk, Status>>,
+    trace_id: String,
+    open: Mutex<Vec<OpenPhase>>,
+}
+
+impl DeployProgress {
+    fn new(tx: mpsc::Sender<Result<LogChunk, Status>>, trace_id: &str) -> Self {
+        Self {
+            tx,
+            trace_id: trace_id.to_string(),
+            open: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Sends STARTED, unless the phase is already open.
+    async fn start(&self, phase: DeployPhase) {
+        let now = chrono::Utc::now().timestamp_millis();
+        {
+            let mut open = self.open.lock().unwrap();
+            if open.iter().any(|p| p.phase == phase) {
+                return;
+            }
+            open.push(OpenPhase {
+                phase,
+                started_ms: now,
+                warnings: Vec::new(),
+            });
+        }
+        self.send(
+            phase,
+            DeployState::Started,
+            Severity::Info,
+            now,
+            now,
+            String::new(),
+        )
+        .await;
+    }
+
+    /// Notes a problem the phase survives; its SUCCEEDED becomes a WARNING.
+    fn warn(&self, phase: DeployPhase, warning: String) {
+        if let Some(open) = self
+            .open
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find(|p| p.phase == phase)
+        {
+            open.warnings.push(warning);
+        }
+    }
+
+    async fn succeed(&self, phase: DeployPhase) {
+        let Some(open) = self.close(phase) else {
+            return;
+        };
+        let severity = if open.warnings.is_empty() {
+            Severity::Info
+        } else {
+            Severity::Warning
+        };
+        self.send(
+            phase,
+            DeployState::Succeeded,
+            severity,
+            open.started_ms,
+            chrono::Utc::now().timestamp_millis(),
+            open.warnings.join("; "),
+        )
+        .await;
+    }
+
+    async fn fail(&self, phase: DeployPhase, reason: &str) {
+        if let Some(open) = self.close(phase) {
+            self.send(
+                phase,
+                DeployState::Failed,
+                Severity::Error,
+                open.started_ms,
+                chrono::Utc::now().timestamp_millis(),
+                reason.to_string(),
+            )
+            .await;
+        }
+    }
+
+    /// 🚦 Takes a place in the build queue and waits for its slots, telling the
+    /// client its position whenever that changes. The ticket holds the slots
+    /// until it is dropped.
+    async fn queue_for_build(&self, queue: &Arc<BuildQueue>, class: BuildClass) -> BuildTicket {
+        let mut ticket = queue.enqueue(build_class(class));
+        let (slots, slots_total) = (ticket.slots(), queue.total());
+        let status = |ahead: usize, admitted| BuildQueueStatus {
+            ahead: ahead as u32,
+            slots,
+            slots_total,
+            admitted,
+        };
+        let mut reported = None;
+        while let Some(ahead) = ticket.ahead() {
+            if reported != Some(ahead) {
+                reported = Some(ahead);
+                self.send_queued(
+                    format!(
+                        "⏳ Waiting for {} of {} build slots: {} deploy(s) ahead\n",
+                        slots, slots_total, ahead
+                    ),
+                    status(ahead, false),
+                )
+                .await;
+            }
+            ticket.changed().await;
+        }
+        if reported.is_some() {
+            self.send_queued(
+                "🚦 Build slots free; starting\n".to_string(),
+                status(0, true),
+            )
+            .await;
+        }
+        ticket
+    }
+
+    async fn send_queued(&self, content: String, status: BuildQueueStatus) {
+        let _ = self
+            .tx
+            .send(Ok(LogChunk {
+                content,
+                trace_id: self.trace_id.clone(),
+                queued: Some(status),
+                ..Default::default()
+            }))
+            .await;
+    }
+
+    /// Fails every open phase with `reason`.
+    async fn fail_open(&self, reason: &str) {
+        let open: Vec<DeployPhase> = self.open.lock().unwrap().iter().map(|p| p.phase).collect();
+        for phase in open {
+            self.fail(phase, reason).await;
+        }
+    }
+
+    fn close(&self, phase: DeployPhase) -> Option<OpenPhase> {
+        let mut open = self.open.lock().unwrap();
+        let at = open.iter().position(|p| p.phase == phase)?;
+        Some(open.remove(at))
+    }
+
+    async fn send(
+        &self,
+        phase: DeployPhase,
+        state: DeployState,
+        severity: Severity,
+        phase_started_ms: i64,
+        timestamp_ms: i64,
+        message: String,
+    ) {
+        let event = DeploymentEvent {
+            phase: phase as i32,
+            state: state as i32,
+            severity: severity as i32,
+            timestamp_ms,
+            phase_started_ms,
+            message,
+        };
+        let _ = self
+            .tx
+            .send(Ok(LogChunk {
+                trace_id: self.trace_id.clone(),
+                event: Some(event),
+                ..Default::default()
+            }))
+            .await;
+    }
+}
+
+/// A release that built and was recorded, and what it cost.
+struct BuiltRelease {
+    record: ReleaseRecord,
+    usage: BuildUsage,
+    cloned_bytes: u64,
+    artifact_bytes: u64,
+    artifact_digest: Option<String>, // In the artifact store
+}
+
+impl BuiltRelease {
+    fn usage_message(&self) -> DeploymentUsage {
+        DeploymentUsage {
+            peak_memory_bytes: self.usage.peak_memory_bytes,
+            cpu_usec: self.usage.cpu_usec,
+            cloned_bytes: self.cloned_bytes,
+            artifact_bytes: self.artifact_bytes,
+            artifact_digest: self.artifact_digest.clone().unwrap_or_default(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        if let Some(image) = &self.record.image {
+            return format!("image {}", image);
+        }
+        format!(
+            "{}, {} MiB cloned, {} MiB after build",
+            describe_usage(&self.usage),
+            self.cloned_bytes.div_ceil(1024 * 1024),
+            self.artifact_bytes.div_ceil(1024 * 1024)
+        )
+    }
+}
+
 impl KariAgentService {
     pub fn new(
         config: AgentConfig,
@@ -61,750 +732,7271 @@ impl KariAgentService {
         firewall_mgr: Arc<dyn FirewallManager>,
         ssl_engine: Arc<dyn SslEngine>,
         job_scheduler: Arc<dyn JobScheduler>,
+        state_store: Arc<dyn StateStore>,
     ) -> Self {
-        Self {
-            jail_mgr: Arc::new(LinuxJailManager),
+        // One Podman backend serves both unit generation and image pulls
+        let podman = Arc::new(PodmanServiceManager::new(config.systemd_dir.clone()));
+
+        let mut source_scanners: Vec<Arc<dyn SourceScanner>> = Vec::new();
+        if config.scan_policy != ScanPolicy::Off {
+            source_scanners.push(Arc::new(SecretPatternScanner));
+            if config.scan_clamav {
+                source_scanners.push(Arc::new(ClamavScanner));
+            }
+        }
+
+        let managers = AgentManagers {
+            jail_mgr: Arc::new(LinuxJailManager::new()),
             svc_mgr: Arc::new(LinuxSystemdManager::new(config.systemd_dir.clone())),
-            git_mgr: Arc::new(SystemGitManager),
-            build_mgr: Arc::new(SystemBuildManager),
+            container_svc_mgr: podman.clone(),
+            git_mgr: Arc::new(SystemGitManager::new(
+                config.deploy_parallelism,
+                config.known_hosts_file.clone(),
+            )),
+            build_mgr: Arc::new(SystemBuildManager::new(
+                config.build_timeout_secs,
+                config.build_tmp_max_mb,
+                BuildMirrors {
+                    npm_registry: config.npm_registry_mirror.clone(),
+                    pip_index: config.pip_index_mirror.clone(),
+                    cargo_registry: config.cargo_registry_mirror.clone(),
+                    proxy: config.build_proxy.clone(),
+                    no_proxy: config.build_no_proxy.clone(),
+                    mirrors_only: config.build_mirrors_only,
+                },
+            )),
             proxy_mgr,
             firewall_mgr,
             ssl_engine,
             job_scheduler,
+            mail_relay: Arc::new(PostfixRelayManager::new(config.postfix_dir.clone())),
+            secret_store: Arc::new(FileSecretStore::new(config.secrets_dir.clone())),
+            image_mgr: podman,
+            state_store,
+            env_file_mgr: Arc::new(DotenvFileManager),
+            log_mgr: Arc::new(LinuxLogManager::new(config.logrotate_dir.clone())),
+            tenant_slice_mgr: Arc::new(SystemdSliceManager::new(config.systemd_dir.clone())),
+            source_scanners,
+            firewall_events: Arc::new(JournalFirewallEventSource),
+            config_changes: Arc::new(InotifyChangeSource),
+            network: Arc::new(ProcNetworkInspector),
+            time_sync: Arc::new(SystemTimeSyncManager::new(
+                config.chrony_conf.clone(),
+                config.chrony_sources_dir.clone(),
+                config.timesyncd_dir.clone(),
+            )),
+            dns: Arc::new(StubDnsResolver::new(
+                PathBuf::from("/etc/resolv.conf"),
+                Duration::from_secs(3),
+            )),
+            heartbeat: Arc::new(CurlHeartbeatTransport {
+                timeout: Duration::from_secs(10),
+            }),
+            notifiers: BTreeMap::new(),
+            disk_probe: Arc::new(SmartctlDiskHealthProbe),
+            reboot_mgr: Arc::new(SystemRebootManager),
+            mac_mgr: Arc::new(LinuxMacManager::new(
+                config.mac_mode,
+                config.apparmor_dir.clone(),
+                config.systemd_dir.clone(),
+            )),
+            processes: Arc::new(ProcProcessInspector),
+            journal: Arc::new(JournalctlReader),
+            sftp_mgr: Arc::new(OpensshSftpManager::new(
+                config.sshd_config_dir.clone(),
+                config.sftp_root.clone(),
+                config.systemd_dir.clone(),
+            )),
+            patcher: Arc::new(HostPatcher::new(
+                config.apt_conf_dir.clone(),
+                Duration::from_secs(config.package_timeout_secs),
+            )),
+            log_shipper: Arc::new(HostLogShipper {
+                timeout: Duration::from_secs(30),
+                headers_file: config.log_forward_headers.clone(),
+            }),
+            privileges: privilege::current(),
+            acme: Arc::new(AcmeEngine::new(
+                config.acme_directory_url.clone(),
+                config.acme_contact.clone(),
+                config.acme_account_key.clone(),
+            )),
+            release_mgr: Arc::new(SystemReleaseManager),
+            ssh_client: Arc::new(SystemExecutor),
+            executor: Arc::new(SystemExecutor),
+        };
+        Self::with_managers(config, managers)
+    }
+
+    /// Assembles the service from an explicit set of managers.
+    pub fn with_managers(config: AgentConfig, managers: AgentManagers) -> Self {
+        Self {
+            jail_mgr: managers.jail_mgr,
+            svc_mgr: managers.svc_mgr,
+            container_svc_mgr: managers.container_svc_mgr,
+            git_mgr: managers.git_mgr,
+            build_mgr: managers.build_mgr,
+            proxy_mgr: managers.proxy_mgr,
+            firewall_mgr: managers.firewall_mgr,
+            ssl_engine: managers.ssl_engine,
+            job_scheduler: managers.job_scheduler,
+            mail_relay: managers.mail_relay,
+            secret_store: managers.secret_store,
+            image_mgr: managers.image_mgr,
+            operations: Arc::new(OperationTracker::new(Arc::clone(&managers.state_store))),
+            state_store: managers.state_store,
+            env_file_mgr: managers.env_file_mgr,
+            log_mgr: managers.log_mgr,
+            tenant_slice_mgr: managers.tenant_slice_mgr,
+            source_scanners: managers.source_scanners,
+            firewall_events: managers.firewall_events,
+            config_changes: managers.config_changes,
+            network: managers.network,
+            time_sync: managers.time_sync,
+            dns: managers.dns,
+            heartbeat: managers.heartbeat,
+            notifiers: managers.notifiers,
+            disk_probe: managers.disk_probe,
+            reboot_mgr: managers.reboot_mgr,
+            mac_mgr: managers.mac_mgr,
+            processes: managers.processes,
+            journal: managers.journal,
+            sftp_mgr: managers.sftp_mgr,
+            patcher: managers.patcher,
+            log_shipper: managers.log_shipper,
+            privileges: managers.privileges,
+            acme: managers.acme,
+            release_mgr: managers.release_mgr,
+            ssh_client: managers.ssh_client,
+            executor: managers.executor,
+            notices: broadcast::channel(64).0,
+            disk_health_events: broadcast::channel(64).0,
+            disk_status: Arc::new(Mutex::new(Vec::new())),
+            tamper_events: broadcast::channel(64).0,
+            circuit_events: broadcast::channel(64).0,
+            key_rotation_events: broadcast::channel(64).0,
+            build_queue: Arc::new(BuildQueue::new(match config.build_slots {
+                0 => build_queue::host_slots(capacity::physical_resources()),
+                slots => slots,
+            })),
             config,
-            system_monitor: Arc::new(Mutex::new(System::new_all())),
+            pending_approvals: Arc::new(Mutex::new(HashMap::new())),
+            cancellable_deploys: Arc::new(Mutex::new(HashMap::new())),
+            deploys_in_flight: Arc::new(AtomicU64::new(0)),
+            // Empty until prewarm takes the baseline, off the runtime
+            system_monitor: Arc::new(Mutex::new(System::new())),
         }
     }
 
-    /// 🛡️ Zero-Trust: Strictly prevents directory traversal
-    fn secure_join(base: &Path, unsafe_suffix: &str) -> Result<std::path::PathBuf, Status> {
-        if unsafe_suffix.contains("..")
-            || unsafe_suffix.contains('/')
-            || unsafe_suffix.contains('\\')
-        {
-            return Err(Status::invalid_argument(
-                "Path traversal detected in identifier",
-            ));
+    /// 🛡️ SLA: Reconciles persisted state left by a previous agent process.
+    /// Must complete before the socket starts serving requests.
+    pub async fn recover_state(&self) -> Result<(), String> {
+        // 🔁 Before operations.recover, so a finished reboot is not reported as interrupted
+        self.recover_reboot().await?;
+        self.operations
+            .recover(self.config.operation_retention_secs)
+            .await?;
+        // 🐞 A debug port that lapsed while the agent was down is shut now
+        let revoked = firewall::revoke_expired(
+            self.state_store.as_ref(),
+            self.firewall_mgr.as_ref(),
+            self.job_scheduler.as_ref(),
+            chrono::Utc::now().timestamp(),
+        )
+        .await?;
+        if revoked > 0 {
+            info!("🐞 Firewall: {} lapsed debug ports revoked", revoked);
         }
-        Ok(base.join(unsafe_suffix))
+        // 🛡️ iptables rules do not survive a reboot; the state store does
+        let restored =
+            firewall::restore(self.state_store.as_ref(), self.firewall_mgr.as_ref()).await?;
+        if restored > 0 {
+            info!("🛡️ Firewall: {} rules restored from state", restored);
+        }
+        // 🔒 KARI_TLS_POLICY or OCSP stapling may have changed since these vhosts were written
+        tls_policy::apply(
+            self.state_store.as_ref(),
+            self.proxy_mgr.as_ref(),
+            self.config.tls_policy,
+            self.ocsp_stapling().as_ref(),
+        )
+        .await
+        .map(|_| ())
     }
 
-    /// 🛡️ Zero-Trust: Validates that a string is a safe alphanumeric-dash identifier
-    fn validate_identifier(value: &str, field_name: &str) -> Result<(), Status> {
-        if value.is_empty()
-            || value.contains("..")
-            || !value
-                .chars()
-                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
-        {
-            return Err(Status::invalid_argument(format!(
-                "Zero-Trust: Invalid {} format: '{}'",
-                field_name, value
-            )));
+    /// Puts back what a scheduled reboot drained, and completes its operation
+    /// if the host did reboot.
+    async fn recover_reboot(&self) -> Result<(), String> {
+        let Some(record) = reboot::pending(self.state_store.as_ref()).await? else {
+            return Ok(());
+        };
+        let kernel = self.reboot_mgr.kernel_state().await?;
+        reboot::restore(
+            self.state_store.as_ref(),
+            self.svc_mgr.as_ref(),
+            self.proxy_mgr.as_ref(),
+            &record,
+        )
+        .await?;
+        let rebooted = record.rebooting_at.is_some_and(|at| kernel.booted_at >= at);
+        if rebooted && !record.operation_id.is_empty() {
+            info!(
+                "🔁 Rebooted from kernel {} into {}; {} apps restored",
+                record.from_kernel,
+                kernel.running,
+                record.drained.len()
+            );
+            self.operations
+                .finish(
+                    &record.operation_id,
+                    OperationResult {
+                        success: true,
+                        stdout: format!(
+                            "Rebooted from kernel {} into {}",
+                            record.from_kernel, kernel.running
+                        ),
+                        ..Default::default()
+                    },
+                )
+                .await?;
         }
         Ok(())
     }
 
-    /// 🛡️ Zero-Trust: Strictly validates domain names to prevent Nginx/Apache config injection.
-    /// This is more restrictive than `validate_identifier` to satisfy RFC 1035/1123 where possible
-    /// while ensuring no special characters (`;`, `{`, `}`, spaces, etc.) can slip through.
-    fn validate_domain_name(domain: &str) -> Result<(), Status> {
-        if domain.is_empty() {
-            return Err(Status::invalid_argument("Domain name cannot be empty"));
+    /// Waits for `window`, drains and reboots. The reboot record was saved by
+    /// the handler; everything done from here is added to it as it happens, so
+    /// on failure `reboot::restore` undoes exactly that.
+    async fn run_reboot(
+        &self,
+        op_id: String,
+        window: Option<RebootWindow>,
+        only_if_needed: bool,
+        grace: Duration,
+    ) -> Result<AgentResponse, Status> {
+        let state = self.state_store.as_ref();
+        let internal = |e: String| Status::internal(format!("[SLA ERROR] Reboot failed: {}", e));
+        let mut record = RebootRecord {
+            operation_id: op_id.clone(),
+            ..reboot::pending(state)
+                .await
+                .map_err(internal)?
+                .unwrap_or_default()
+        };
+        reboot::save(state, &record).await.map_err(internal)?;
+
+        if let Some(window) = window {
+            use chrono::Timelike;
+            let now = chrono::Local::now().time().num_seconds_from_midnight();
+            let wait = window.wait_from(now);
+            if !wait.is_zero() {
+                self.report_progress(
+                    Some(&op_id),
+                    5,
+                    &format!(
+                        "Waiting {} min for the {} window",
+                        wait.as_secs() / 60,
+                        record.window
+                    ),
+                )
+                .await;
+                tokio::time::sleep(wait).await;
+            }
         }
 
-        if domain.contains("..") || domain.contains('/') || domain.contains('\\') {
-            return Err(Status::invalid_argument(
-                "Path traversal detected in domain name",
-            ));
+        // A livepatch may have landed while we waited
+        let kernel = self.reboot_mgr.kernel_state().await.map_err(internal)?;
+        if only_if_needed && !reboot::advise(&kernel).needs_reboot {
+            reboot::forget(state).await.map_err(internal)?;
+            return Ok(AgentResponse {
+                success: true,
+                stdout: "No reboot needed".into(),
+                ..Default::default()
+            });
         }
+        record.from_kernel = kernel.running;
+
+        self.report_progress(Some(&op_id), 30, "Draining apps")
+            .await;
+        let drained = reboot::drain(
+            state,
+            self.svc_mgr.as_ref(),
+            self.proxy_mgr.as_ref(),
+            &mut record,
+            grace,
+        )
+        .await;
+        drained.map_err(|e| Status::internal(format!("[SLA ERROR] Drain failed: {}", e)))?;
+
+        self.report_progress(Some(&op_id), 80, "Rebooting").await;
+        record.rebooting_at = Some(chrono::Utc::now().timestamp());
+        reboot::save(state, &record).await.map_err(internal)?;
+        self.reboot_mgr.reboot().await.map_err(internal)?;
+        Ok(AgentResponse {
+            success: true,
+            stdout: format!(
+                "Drained {} apps and stopped {} units; rebooting from kernel {}",
+                record.drained.len(),
+                record.stopped.len(),
+                record.from_kernel
+            ),
+            ..Default::default()
+        })
+    }
 
-        // Nginx configuration injection prevention:
-        // Strictly allow only alphanumeric, dots, hyphens, and underscores.
-        // Underscores are technically not allowed in DNS hostnames but are allowed in Nginx server_names.
-        if !domain
-            .chars()
-            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_')
-        {
-            return Err(Status::invalid_argument(format!(
-                "Zero-Trust: Invalid domain name format: '{}' (contains potentially dangerous characters)",
-                domain
-            )));
+    /// HTTPS settings for a domain whose certificate is installed here.
+    fn vhost_tls(&self, domain: &str) -> VhostTls {
+        let dir = self.config.ssl_storage_dir.join(domain);
+        VhostTls {
+            cert_path: dir.join("fullchain.pem"),
+            key_path: dir.join("privkey.pem"),
+            policy: self.config.tls_policy,
+            ocsp_stapling: self.ocsp_stapling(),
         }
+    }
 
-        // Ensure it doesn't start or end with a hyphen or dot (basic RFC compliance + Nginx safety)
-        if domain.starts_with('-')
-            || domain.ends_with('-')
-            || domain.starts_with('.')
-            || domain.ends_with('.')
-        {
-            return Err(Status::invalid_argument(format!(
-                "Zero-Trust: Invalid domain name format: '{}' (cannot start/end with '-' or '.')",
-                domain
-            )));
+    /// 🩺 OCSP stapling for HTTPS vhosts, through KARI_OCSP_RESOLVERS or else
+    /// the host's nameservers. None when KARI_OCSP_STAPLING is off.
+    fn ocsp_stapling(&self) -> Option<OcspStapling> {
+        if !self.config.ocsp_stapling {
+            return None;
         }
+        let resolvers = if self.config.ocsp_resolvers.is_empty() {
+            let resolv_conf = std::fs::read_to_string("/etc/resolv.conf").unwrap_or_default();
+            mirrors::parse_nameservers(&resolv_conf)
+                .into_iter()
+                .map(|ip| match ip {
+                    std::net::IpAddr::V4(ip) => ip.to_string(),
+                    std::net::IpAddr::V6(ip) => format!("[{}]", ip),
+                })
+                .collect()
+        } else {
+            self.config.ocsp_resolvers.clone()
+        };
+        Some(OcspStapling { resolvers })
+    }
 
-        Ok(())
+    /// 🌐 HTTPS settings for a domain whose certificate was installed before
+    /// its app: its own, or a shared one listing it as a SAN.
+    async fn covering_tls(&self, domain: &str) -> Result<Option<VhostTls>, Status> {
+        let state_err =
+            |e: String| Status::internal(format!("[SLA ERROR] State read failed: {}", e));
+        let Some(cert) = certificates::covering(self.state_store.as_ref(), domain)
+            .await
+            .map_err(state_err)?
+        else {
+            return Ok(None);
+        };
+        let installed = self
+            .ssl_engine
+            .certificate_exists(&cert.name)
+            .await
+            .map_err(state_err)?;
+        Ok(installed.then(|| self.vhost_tls(&cert.name)))
     }
-}
 
-#[tonic::async_trait]
-impl SystemAgent for KariAgentService {
-    type StreamDeploymentStream = ReceiverStream<Result<LogChunk, Status>>;
+    /// 🛡️ SLA: Long-lived housekeeping loops, started once the service is constructed.
+    /// ⚡ Cold start: pays for the expensive first touches before the socket
+    /// serves, so the first RPC after a restart is as fast as the rest. The
+    /// sysinfo baseline (a full process and CPU scan) runs on a blocking
+    /// thread; the systemctl probe loads the binary and opens the manager's
+    /// bus once. Failures only cost that first caller the wait, so they are
+    /// logged and never block startup.
+    pub async fn prewarm(&self) {
+        let started = Instant::now();
+        let monitor = Arc::clone(&self.system_monitor);
+        let baseline = tokio::task::spawn_blocking(move || monitor.lock().unwrap().refresh_all());
+        let (baseline, probe) =
+            tokio::join!(baseline, self.svc_mgr.is_active("kari-agent.service"));
+        if let Err(e) = baseline {
+            warn!("⚡ Prewarm: sysinfo baseline panicked: {}", e);
+        }
+        if let Err(e) = probe {
+            warn!("⚡ Prewarm: systemctl probe failed: {}", e);
+        }
+        info!("⚡ Prewarmed in {:?}", started.elapsed());
+    }
 
-    // =========================================================================
-    // 1. 🛡️ SLA: System Health Telemetry
-    // =========================================================================
-    async fn get_system_status(
-        &self,
-        _request: Request<Empty>,
-    ) -> Result<Response<SystemStatus>, Status> {
-        // ⚡ Performance: Reuse System instance
-        let mut sys = self.system_monitor.lock().unwrap();
-        sys.refresh_all();
-
-        // 🛡️ SLA: Calculate metrics from kernel-level sources
-        let cpu_usage = sys.global_cpu_info().cpu_usage();
-        let _total_memory = sys.total_memory() as f64;
-        let used_memory = sys.used_memory() as f64;
-        let memory_usage_mb = (used_memory / 1_048_576.0) as f32;
-
-        // Active jails: count systemd services matching our naming convention
-        let active_jails = sys
-            .processes()
-            .values()
-            .filter(|p| p.name().starts_with("kari-"))
-            .count() as u32;
+    pub fn start_background_tasks(&self) {
+        tokio::spawn(image_gc::run_disk_pressure_gc(
+            Arc::clone(&self.state_store),
+            Arc::clone(&self.image_mgr),
+            self.config.web_root.clone(),
+            std::time::Duration::from_secs(self.config.image_gc_interval_secs.max(60)),
+            self.config.image_gc_disk_threshold_percent,
+            self.notices.clone(),
+        ));
+
+        // 🔁 Boot survival: anything that did not come back after a reboot is
+        // repaired now instead of waiting for a tenant to notice
+        let state = Arc::clone(&self.state_store);
+        let services = Arc::clone(&self.svc_mgr);
+        let proxy = Arc::clone(&self.proxy_mgr);
+        let ssl = Arc::clone(&self.ssl_engine);
+        let scheduler = Arc::clone(&self.job_scheduler);
+        let firewall = Arc::clone(&self.firewall_mgr);
+        tokio::spawn(async move {
+            let managers = AutostartManagers {
+                state: state.as_ref(),
+                services: services.as_ref(),
+                proxy: proxy.as_ref(),
+                ssl: ssl.as_ref(),
+                scheduler: scheduler.as_ref(),
+                firewall: firewall.as_ref(),
+            };
+            match autostart::reconcile(&managers, true).await {
+                Ok(report) if report.healthy() => info!(
+                    "🔁 Autostart verified: {} checks, {} repaired",
+                    report.checked,
+                    report.discrepancies.len()
+                ),
+                Ok(report) => warn!(
+                    "🔁 Autostart: {} unresolved discrepancies (see VerifyAutostart)",
+                    report.discrepancies.iter().filter(|d| !d.repaired).count()
+                ),
+                Err(e) => warn!("🔁 Autostart reconciliation failed: {}", e),
+            }
+        });
 
-        let uptime = System::uptime();
+        self.start_tamper_watch();
+        self.start_circuit_breaker();
+        self.start_key_rotation();
+        self.start_heartbeat();
+        self.start_notifications();
+        self.start_disk_health();
+        self.start_log_retention();
+        self.start_patching();
+        self.start_log_forwarding();
+    }
 
-        Ok(Response::new(SystemStatus {
-            healthy: true,
-            active_jails,
-            cpu_usage_percent: cpu_usage,
-            memory_usage_mb,
-            agent_version: env!("CARGO_PKG_VERSION").to_string(),
-            uptime_seconds: uptime,
-        }))
+    fn circuit_breaker(&self) -> CircuitBreaker {
+        CircuitBreaker {
+            state: Arc::clone(&self.state_store),
+            services: Arc::clone(&self.svc_mgr),
+            proxy: Arc::clone(&self.proxy_mgr),
+        }
     }
 
-    // =========================================================================
-    // 2. 📦 Package Management (Hardened)
-    // =========================================================================
-    async fn execute_package_command(
-        &self,
-        request: Request<PackageRequest>,
-    ) -> Result<Response<AgentResponse>, Status> {
-        let req = request.into_inner();
+    /// 🔌 Parks apps systemd stopped restarting on the maintenance page (see
+    /// StreamCircuitEvents and ResetCircuitBreaker).
+    pub fn start_circuit_breaker(&self) {
+        tokio::spawn(self.circuit_breaker().run(
+            Duration::from_millis(self.config.circuit_poll_ms),
+            self.circuit_events.clone(),
+        ));
+    }
 
-        if !ALLOWED_PKG_COMMANDS.contains(&req.command.as_str()) {
-            return Err(Status::permission_denied(
-                "Zero-Trust: Command not in allowlist",
-            ));
+    /// 🔄 Stages successors for certificate keys past their maximum age (see
+    /// StreamKeyRotationEvents). A no-op when KARI_KEY_MAX_AGE_DAYS is unset.
+    pub fn start_key_rotation(&self) {
+        if self.config.key_max_age_secs == 0 {
+            return;
         }
+        let rotation = KeyRotation {
+            state: Arc::clone(&self.state_store),
+            ssl: Arc::clone(&self.ssl_engine),
+            max_key_age: Duration::from_secs(self.config.key_max_age_secs),
+        };
+        tokio::spawn(rotation.run(
+            Duration::from_millis(self.config.key_rotation_poll_ms),
+            self.key_rotation_events.clone(),
+        ));
+    }
 
-        let output = tokio::process::Command::new(&req.command)
-            .args(&req.args)
-            .output()
+    /// 💓 Reports identity and health to the control plane on a timer. A no-op
+    /// when KARI_HEARTBEAT_URL is unset.
+    pub fn start_heartbeat(&self) {
+        let Some(url) = self.config.heartbeat_url.clone() else {
+            return;
+        };
+        // 🛡️ Zero-Trust: The signature proves origin, not secrecy; health and
+        // inventory still must not cross the network in the clear
+        if !url.starts_with("https://") {
+            warn!("💓 Heartbeat disabled: KARI_HEARTBEAT_URL must be https://");
+            return;
+        }
+        let heartbeat = Heartbeat {
+            transport: Arc::clone(&self.heartbeat),
+            state: Arc::clone(&self.state_store),
+            services: Arc::clone(&self.svc_mgr),
+            url,
+            key_path: self.config.heartbeat_key.clone(),
+            identity: HostIdentity::default(),
+        };
+        let interval = Duration::from_millis(self.config.heartbeat_interval_ms);
+        tokio::spawn(async move {
+            let identity = tokio::task::spawn_blocking(|| {
+                let dirs: Vec<&Path> = facts::BIN_DIRS.iter().map(Path::new).collect();
+                let mut capabilities = vec![format!("api:{}", API_PACKAGE)];
+                capabilities.extend(
+                    facts::detect_components(&dirs)
+                        .into_iter()
+                        .map(|component| component.name),
+                );
+                HostIdentity {
+                    host_id: heartbeat::read_host_id(),
+                    hostname: System::host_name().unwrap_or_default(),
+                    agent_version: env!("CARGO_PKG_VERSION").to_string(),
+                    capabilities,
+                }
+            })
             .await
-            .map_err(|e| Status::internal(format!("[SLA ERROR] Execution failed: {}", e)))?;
-
-        Ok(Response::new(AgentResponse {
-            success: output.status.success(),
-            exit_code: output.status.code().unwrap_or(-1),
-            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
-            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
-            error_message: String::new(),
-        }))
+            .unwrap_or_default();
+            Heartbeat {
+                identity,
+                ..heartbeat
+            }
+            .run(interval)
+            .await
+        });
     }
 
-    // =========================================================================
-    // 3. 🔒 Application Jail Provisioning (cgroup v2 + systemd-run)
-    // =========================================================================
-    async fn provision_app_jail(
-        &self,
-        request: Request<ProvisionJailRequest>,
-    ) -> Result<Response<AgentResponse>, Status> {
-        let req = request.into_inner();
+    /// 📣 Routes crash, failed-job, key-rotation and disk-pressure notices to
+    /// the channels agent.toml names. A no-op when it routes nothing.
+    pub fn start_notifications(&self) {
+        let host = System::host_name().unwrap_or_default();
+        let notifications =
+            match Notifications::load(&self.config.agent_toml, host, &self.notifiers) {
+                Ok(Some(notifications)) => notifications,
+                Ok(None) => return,
+                Err(e) => {
+                    warn!("📣 Notifications disabled: {}", e);
+                    return;
+                }
+            };
+        // Subscribed before returning, so nothing raised from here on is missed
+        tokio::spawn(notifications.run(
+            self.circuit_events.subscribe(),
+            self.key_rotation_events.subscribe(),
+            self.disk_health_events.subscribe(),
+            self.notices.subscribe(),
+        ));
+        tokio::spawn(notify::watch_failed_jobs(
+            Arc::clone(&self.state_store),
+            Arc::clone(&self.job_scheduler),
+            Duration::from_millis(self.config.notify_poll_ms),
+            self.notices.clone(),
+        ));
+    }
 
-        // 🛡️ Zero-Trust Input Validation
-        Self::validate_identifier(&req.app_id, "app_id")?;
-        Self::validate_domain_name(&req.domain_name)?;
+    /// 💽 Checks SMART data and the kernel log for failing disks (see
+    /// GetSystemStatus and StreamDiskHealthEvents).
+    pub fn start_disk_health(&self) {
+        let watch = DiskHealthWatch {
+            probe: Arc::clone(&self.disk_probe),
+            status: Arc::clone(&self.disk_status),
+        };
+        tokio::spawn(watch.run(
+            Duration::from_millis(self.config.disk_health_poll_ms),
+            System::boot_time() as i64,
+            self.disk_health_events.clone(),
+        ));
+    }
 
-        let app_user = format!("kari-app-{}", req.app_id);
-        let app_dir = Self::secure_join(&self.config.web_root, &req.domain_name)?;
-        let service_name = format!("kari-{}", req.domain_name);
+    /// 🗑️ Keeps the daily log retention timer in line with the configured
+    /// retentions: scheduled while any class expires, removed once none does.
+    pub fn start_log_retention(&self) {
+        let scheduler = Arc::clone(&self.job_scheduler);
+        let enabled = self.config.log_retention().enabled();
+        tokio::spawn(async move {
+            let outcome = if !enabled {
+                scheduler.unschedule_job(log_retention::RETENTION_JOB).await
+            } else {
+                match std::env::current_exe() {
+                    Ok(agent) => {
+                        scheduler
+                            .schedule_job(&TraitJobIntent {
+                                name: log_retention::RETENTION_JOB.to_string(),
+                                binary: agent.to_string_lossy().to_string(),
+                                args: vec![log_retention::ENFORCE_FLAG.to_string()],
+                                schedule: "daily".to_string(),
+                                run_as_user: "root".to_string(),
+                            })
+                            .await
+                    }
+                    Err(e) => Err(format!("Agent binary unknown: {}", e)),
+                }
+            };
+            if let Err(e) = outcome {
+                warn!("🗑️ Log retention timer not updated: {}", e);
+            }
+        });
+    }
+
+    /// 🩹 Applies the patch policy in its window (see SetPatchPolicy). Idle
+    /// while no policy is set.
+    pub fn start_patching(&self) {
+        let patching = Patching {
+            state: Arc::clone(&self.state_store),
+            services: Arc::clone(&self.svc_mgr),
+            proxy: Arc::clone(&self.proxy_mgr),
+            patcher: Arc::clone(&self.patcher),
+            reboots: Arc::clone(&self.reboot_mgr),
+        };
+        tokio::spawn(patching.run(Duration::from_millis(self.config.patch_poll_ms)));
+    }
+
+    /// 📤 Ships every app's journal to KARI_LOG_FORWARD_URL. A no-op when unset.
+    pub fn start_log_forwarding(&self) {
+        let Some(url) = self.config.log_forward_url.as_deref() else {
+            return;
+        };
+        let target = match LogTarget::parse(url) {
+            Ok(target) => target,
+            Err(e) => {
+                warn!("📤 Log forwarding disabled: KARI_LOG_FORWARD_URL {}", e);
+                return;
+            }
+        };
+        let forwarder = LogForwarder {
+            state: Arc::clone(&self.state_store),
+            journal: Arc::clone(&self.journal),
+            shipper: Arc::clone(&self.log_shipper),
+            target,
+            host: System::host_name().unwrap_or_default(),
+            batch_lines: self.config.log_forward_batch_lines,
+            batch_window: Duration::from_millis(self.config.log_forward_batch_ms),
+        };
+        tokio::spawn(forwarder.run(Duration::from_millis(self.config.log_forward_poll_ms)));
+    }
+
+    /// 🕵️ Watches the directories kari writes into for changes made by anyone
+    /// else (see StreamTamperEvents). A no-op when KARI_TAMPER_WATCH is off.
+    pub fn start_tamper_watch(&self) {
+        if !self.config.tamper_watch {
+            return;
+        }
+        let watch = TamperWatch {
+            dirs: WatchedDirs {
+                systemd_dir: self.config.systemd_dir.clone(),
+                proxy_conf_dir: self.config.proxy_conf_dir.clone(),
+                ssl_storage_dir: self.config.ssl_storage_dir.clone(),
+            },
+            state: Arc::clone(&self.state_store),
+            proxy: Arc::clone(&self.proxy_mgr),
+            ssl: Arc::clone(&self.ssl_engine),
+            scheduler: Arc::clone(&self.job_scheduler),
+            self_heal: self.config.tamper_self_heal,
+            settle: Duration::from_millis(self.config.tamper_settle_ms),
+        };
+        tokio::spawn(watch.run(Arc::clone(&self.config_changes), self.tamper_events.clone()));
+    }
+
+    /// 🔌 The crash-loop threshold written into every app unit.
+    fn start_limit(&self) -> StartLimit {
+        StartLimit {
+            burst: self.config.crash_loop_starts,
+            interval_secs: self.config.crash_loop_window_secs,
+        }
+    }
+
+    /// 🕰️ The daemon's view of the clock, with the skew verdict filled in.
+    fn time_sync_message(status: TraitTimeSyncStatus) -> TimeSyncStatus {
+        TimeSyncStatus {
+            daemon: status.daemon.unit().to_string(),
+            synchronized: status.synchronized,
+            offset_seconds: status.offset_secs,
+            skewed: status.skewed(),
+            servers: status.servers,
+            current_source: status.current_source.unwrap_or_default(),
+        }
+    }
+
+    /// 🌐 A listener as reported, attributed to the app whose unit holds it.
+    /// Anything outside a `kari-*` unit is flagged as not kari's.
+    fn listening_socket_message(listener: Listener, apps: &[AppRecord]) -> ListeningSocket {
+        let unit = listener.unit.unwrap_or_default();
+        let app_id = apps
+            .iter()
+            .find(|app| unit.strip_suffix(".service") == Some(app.service_name.as_str()))
+            .map(|app| app.app_id.clone())
+            .unwrap_or_default();
+        ListeningSocket {
+            protocol: protocol_name(listener.protocol).to_string(),
+            address: listener.address,
+            port: u32::from(listener.port),
+            pid: listener.pid.unwrap_or(0),
+            process: listener.process.unwrap_or_default(),
+            kari_owned: unit.starts_with("kari-"),
+            unit,
+            app_id,
+        }
+    }
+
+    /// 🛰️ A satellite's agent.toml entry; NOT_FOUND when none has that name.
+    fn satellite_config(&self, name: &str) -> Result<satellite::SatelliteConfig, Status> {
+        let mut satellites = satellite::load(&self.config.agent_toml)
+            .map_err(|e| Status::failed_precondition(format!("Satellites unavailable: {}", e)))?;
+        satellites.remove(name).ok_or_else(|| {
+            Status::not_found(format!(
+                "No satellite named '{}' (declared: {})",
+                name,
+                satellites.keys().cloned().collect::<Vec<_>>().join(", ")
+            ))
+        })
+    }
+
+    /// 🛰️ The named satellite's managers, driven over SSH with its stored key.
+    async fn satellite(&self, name: &str) -> Result<Satellite, Status> {
+        let config = self.satellite_config(name)?;
+        let key = self
+            .secret_store
+            .get_secret(&satellite::key_secret(name))
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Secret lookup failed: {}", e)))?
+            .ok_or_else(|| {
+                Status::failed_precondition(format!(
+                    "Satellite '{}' has no SSH key; set one with SetSatelliteKey",
+                    name
+                ))
+            })?;
+        let ssh = SshExecutor::with_local(
+            &config,
+            key,
+            self.config.known_hosts_file.clone(),
+            Arc::clone(&self.ssh_client),
+        );
+        Ok(Satellite::connect(&config, Arc::new(ssh)))
+    }
+
+    /// Secret store names the agent manages itself; callers may not write or read them.
+    fn is_reserved_secret(name: &str) -> bool {
+        name.starts_with("registry-auth-") || name.starts_with(satellite::KEY_SECRET_PREFIX)
+    }
+
+    /// 🔑 Replaces every `secret://name` env value with the named secret, read
+    /// from the store now. Callers never hold the plaintext, and a rotated
+    /// secret reaches the app on its next deploy or provision. Every reference
+    /// is resolved before any is used: one missing secret refuses the request.
+    async fn resolve_secret_refs(&self, vars: &mut HashMap<String, String>) -> Result<(), Status> {
+        for value in vars.values_mut() {
+            let Some(name) = secret_ref(value) else {
+                continue;
+            };
+            validate_identifier(name, "secret name")?;
+            if Self::is_reserved_secret(name) {
+                return Err(Status::permission_denied(format!(
+                    "Zero-Trust: Secret '{}' cannot be exposed to apps",
+                    name
+                )));
+            }
+            let credential = self
+                .secret_store
+                .get_secret(name)
+                .await
+                .map_err(|e| Status::internal(format!("[SLA ERROR] Secret lookup failed: {}", e)))?
+                .ok_or_else(|| {
+                    Status::failed_precondition(format!("Secret '{}' does not exist", name))
+                })?;
+            *value = credential.use_secret(str::to_string);
+            credential.destroy();
+        }
+        Ok(())
+    }
+
+    /// 📏 Capacity shortfalls are RESOURCE_EXHAUSTED with a stable `[CAPACITY]` prefix,
+    /// so callers can tell "host is full" apart from every other failure.
+    fn capacity_error(detail: String) -> Status {
+        Status::resource_exhausted(format!("[CAPACITY] {}", detail))
+    }
+
+    /// 🔌 Whoever other than `service_name` already listens on TCP `port`,
+    /// described for a `[PORT_IN_USE]` error. The unit's own previous process
+    /// is not a conflict: the restart frees the port. A host whose sockets
+    /// cannot be listed is not held up; the app then finds out as before.
+    async fn port_conflict(&self, port: u16, service_name: &str) -> Option<String> {
+        let listeners = match self.network.listeners().await {
+            Ok(listeners) => listeners,
+            Err(e) => {
+                warn!("🔌 Port check for {} skipped: {}", service_name, e);
+                return None;
+            }
+        };
+        let own_unit = format!("{}.service", service_name);
+        let holder = listeners.into_iter().find(|l| {
+            l.protocol == Protocol::Tcp && l.port == port && l.unit.as_ref() != Some(&own_unit)
+        })?;
+        let mut owner = holder
+            .process
+            .unwrap_or_else(|| "an unknown process".to_string());
+        if let Some(pid) = holder.pid {
+            owner.push_str(&format!(" (pid {}", pid));
+            if let Some(unit) = holder.unit {
+                owner.push_str(&format!(", unit {}", unit));
+            }
+            owner.push(')');
+        }
+        Some(format!(
+            "[PORT_IN_USE] Port {} is already bound on {} by {}",
+            port, holder.address, owner
+        ))
+    }
+
+    /// 🛡️ A refusal to overwrite a unit someone else owns is ALREADY_EXISTS with
+    /// the sys layer's `Conflict:` message; anything else is an agent failure.
+    fn unit_error(context: &str, e: String) -> Status {
+        if e.starts_with("Conflict:") {
+            Status::already_exists(e)
+        } else {
+            Status::internal(format!("[SLA ERROR] {}: {}", context, e))
+        }
+    }
+
+    /// 🛡️ Privacy: The raw key material is wiped as soon as the keys are derived.
+    fn bundle_key(material: Vec<u8>) -> Result<BundleKey, Status> {
+        let material = Zeroizing::new(material);
+        BundleKey::derive(&material)
+            .map_err(|e| Status::invalid_argument(format!("Zero-Trust: {}", e)))
+    }
+
+    /// Creates the root-only bundle directory and resolves `name` inside it.
+    fn bundle_path(&self, name: &str) -> Result<std::path::PathBuf, Status> {
+        validate_identifier(name, "bundle_name")?;
+        Self::secure_join(self.bundle_dir()?, name)
+    }
+
+    /// The artifact store, when this host keeps one.
+    fn artifact_store(&self) -> Option<ArtifactStore> {
+        self.config.artifact_store.then(|| {
+            ArtifactStore::new(
+                self.config.artifact_dir.clone(),
+                self.config.artifact_store_max_mb,
+            )
+        })
+    }
+
+    /// The bundle directory, created root-only if missing.
+    fn bundle_dir(&self) -> Result<&Path, Status> {
+        std::fs::create_dir_all(&self.config.bundle_dir)
+            .and_then(|_| {
+                std::fs::set_permissions(
+                    &self.config.bundle_dir,
+                    std::fs::Permissions::from_mode(0o700),
+                )
+            })
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Bundle dir unavailable: {}", e)))?;
+        Ok(&self.config.bundle_dir)
+    }
+
+    fn migration_paths(&self) -> MigrationPaths<'_> {
+        MigrationPaths {
+            web_root: &self.config.web_root,
+            systemd_dir: &self.config.systemd_dir,
+            ssl_storage_dir: &self.config.ssl_storage_dir,
+        }
+    }
+
+    fn snapshot_paths(&self) -> SnapshotPaths<'_> {
+        SnapshotPaths {
+            systemd_dir: &self.config.systemd_dir,
+            proxy_conf_dir: &self.config.proxy_conf_dir,
+            ssl_storage_dir: &self.config.ssl_storage_dir,
+        }
+    }
+
+    /// Secret store key holding an app's podman auth.json
+    fn registry_auth_secret(app_id: &str) -> String {
+        format!("registry-auth-{}", app_id)
+    }
+
+    /// 🛡️ Zero-Trust: Strictly prevents directory traversal
+    fn secure_join(base: &Path, unsafe_suffix: &str) -> Result<std::path::PathBuf, Status> {
+        if unsafe_suffix.contains("..")
+            || unsafe_suffix.contains('/')
+            || unsafe_suffix.contains('\\')
+        {
+            return Err(Status::invalid_argument(
+                "Path traversal detected in identifier",
+            ));
+        }
+        Ok(base.join(unsafe_suffix))
+    }
+
+    /// Parses the caller's `grpc-timeout` header (e.g. `30S`, `500m`) into a Duration.
+    fn request_deadline(metadata: &MetadataMap) -> Option<Duration> {
+        let raw = metadata.get("grpc-timeout")?.to_str().ok()?;
+        if raw.len() < 2 || raw.len() > 9 {
+            return None; // Spec: at most 8 ASCII digits plus a unit
+        }
+        let (digits, unit) = raw.split_at(raw.len() - 1);
+        let value: u64 = digits.parse().ok()?;
+        Some(match unit {
+            "H" => Duration::from_secs(value.saturating_mul(3600)),
+            "M" => Duration::from_secs(value.saturating_mul(60)),
+            "S" => Duration::from_secs(value),
+            "m" => Duration::from_millis(value),
+            "u" => Duration::from_micros(value),
+            "n" => Duration::from_nanos(value),
+            _ => return None,
+        })
+    }
+
+    /// 🛡️ SLA: Bounds a handler by the caller's deadline. Once the caller has given up,
+    /// no further steps start; a step already in flight is dropped at its next await.
+    async fn with_deadline<T>(
+        deadline: Option<Duration>,
+        what: &str,
+        work: impl std::future::Future<Output = Result<T, Status>>,
+    ) -> Result<T, Status> {
+        match deadline {
+            Some(limit) => tokio::time::timeout(limit, work).await.map_err(|_| {
+                Status::deadline_exceeded(format!(
+                    "[SLA ERROR] {} exceeded the caller's {:?} deadline",
+                    what, limit
+                ))
+            })?,
+            None => work.await,
+        }
+    }
+
+    /// ⏳ Starts `work` as a tracked operation and returns its id straight away.
+    /// The work receives a clone of the service and its operation id for progress reports;
+    /// the record names the caller it was started for.
+    async fn spawn_operation<F, Fut>(
+        &self,
+        caller: &Caller,
+        kind: &str,
+        work: F,
+    ) -> Result<AgentResponse, Status>
+    where
+        F: FnOnce(KariAgentService, String) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = Result<AgentResponse, Status>> + Send + 'static,
+    {
+        let op = self.operations.start(kind, caller).await.map_err(|e| {
+            Status::internal(format!("[SLA ERROR] Operation tracking failed: {}", e))
+        })?;
+
+        let this = self.clone();
+        let op_id = op.id.clone();
+        tokio::spawn(async move {
+            let operations = Arc::clone(&this.operations);
+            let result = match work(this, op_id.clone()).await {
+                Ok(resp) => Self::operation_result(resp),
+                Err(status) => OperationResult {
+                    exit_code: -1,
+                    error_message: status.message().to_string(),
+                    ..Default::default()
+                },
+            };
+            if let Err(e) = operations.finish(&op_id, result).await {
+                warn!("Failed to record result of operation {}: {}", op_id, e);
+            }
+        });
+
+        Ok(AgentResponse {
+            success: true,
+            stdout: format!("Operation {} started", op.id),
+            operation_id: op.id,
+            ..Default::default()
+        })
+    }
+
+    /// 🔐 What follows a certificate landing on disk, pushed or issued: its key
+    /// is dated, its domains' vhosts turn on HTTPS, and it is registered for
+    /// each domain it covers. Returns whether the key is new, and the domains.
+    async fn certificate_installed(
+        &self,
+        domain_name: &str,
+        san_domains: &[String],
+        key_rotation_opt_out: bool,
+    ) -> Result<(bool, String), Status> {
+        // 🔄 A new key starts its age now; a renewal that kept the key does not
+        let rotated = key_rotation::record_installed_key(
+            self.state_store.as_ref(),
+            self.ssl_engine.as_ref(),
+            domain_name,
+            key_rotation_opt_out,
+        )
+        .await
+        .map_err(|e| Status::internal(format!("[SLA ERROR] State update failed: {}", e)))?;
+
+        // Remembered so VerifyAutostart can tell a lost certificate from one never
+        // installed; the first certificate also turns on the vhost's 443 block.
+        // 🌐 Every domain the certificate covers is served from its one copy.
+        let mut domains = vec![domain_name.to_string()];
+        domains.extend(san_domains.iter().cloned());
+        let apps: Vec<AppRecord> = list_records(self.state_store.as_ref(), NS_APPS)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        let tls = self.vhost_tls(domain_name);
+        for mut app in apps.into_iter().filter(|a| {
+            domains.contains(&a.domain_name)
+                && (!a.certificate_installed || a.vhost.tls.as_ref() != Some(&tls))
+        }) {
+            app.certificate_installed = true;
+            app.vhost.tls = Some(tls.clone());
+            if let Some(port) = app.port {
+                self.proxy_mgr
+                    .create_vhost(&app.domain_name, port, &app.vhost)
+                    .await
+                    .map_err(|e| {
+                        Status::internal(format!("[SLA ERROR] HTTPS vhost failed: {}", e))
+                    })?;
+            }
+            put_record(self.state_store.as_ref(), NS_APPS, &app.app_id, &app)
+                .await
+                .map_err(|e| Status::internal(format!("[SLA ERROR] State update failed: {}", e)))?;
+        }
+        let covered = domains.join(", ");
+        certificates::register(
+            self.state_store.as_ref(),
+            &self.config.ssl_storage_dir,
+            &CertificateRecord {
+                name: domain_name.to_string(),
+                domains,
+            },
+        )
+        .await
+        .map_err(|e| Status::internal(format!("[SLA ERROR] State update failed: {}", e)))?;
+
+        Ok((rotated, covered))
+    }
+
+    /// 🔏 Proves the domains to the ACME CA, has it certify a key staged on
+    /// this host, and installs the result as InstallCertificate would.
+    async fn issue_and_install(
+        &self,
+        req: IssueCertificateRequest,
+    ) -> Result<AgentResponse, Status> {
+        let mut domains = vec![req.domain_name.clone()];
+        domains.extend(req.san_domains.iter().cloned());
+
+        // Refused here rather than spending one of the CA's rate-limited orders
+        let interfaces = self.network.interfaces().await.map_err(|e| {
+            Status::internal(format!("[SLA ERROR] Reading interfaces failed: {}", e))
+        })?;
+        let expected = issuance::public_addresses(&interfaces);
+        for domain in &domains {
+            let check = issuance::check(
+                self.dns.as_ref(),
+                domain,
+                &self.config.acme_caa_domain,
+                &expected,
+            )
+            .await;
+            if check.diagnosis != Diagnosis::Ready {
+                return Err(Status::failed_precondition(check.detail));
+            }
+            let served =
+                self.proxy_mgr.vhost_exists(domain).await.map_err(|e| {
+                    Status::internal(format!("[SLA ERROR] Proxy check failed: {}", e))
+                })?;
+            if !served {
+                return Err(Status::failed_precondition(format!(
+                    "{} has no vhost to answer the HTTP-01 challenge; provision its app first",
+                    domain
+                )));
+            }
+        }
+
+        let csr = self
+            .ssl_engine
+            .stage_next_key(&req.domain_name, &req.san_domains)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Key generation failed: {}", e)))?;
+        let fullchain = self
+            .acme
+            .issue(&domains, &csr, self.proxy_mgr.as_ref())
+            .await
+            .map_err(|e| Status::unavailable(format!("[SLA ERROR] ACME issuance failed: {}", e)))?;
+        // No key sent: the certificate must certify the key just staged
+        self.ssl_engine
+            .install_certificate(TraitSslPayload {
+                domain_name: req.domain_name.clone(),
+                fullchain_pem: fullchain,
+                privkey_pem: ProviderCredential::from_string(String::new()),
+            })
+            .await
+            .map_err(|e| {
+                Status::internal(format!(
+                    "[SLA ERROR] Certificate installation failed: {}",
+                    e
+                ))
+            })?;
+        info!("🔏 Certificate issued over ACME for: {}", req.domain_name);
+
+        let (_, covered) = self
+            .certificate_installed(&req.domain_name, &req.san_domains, req.key_rotation_opt_out)
+            .await?;
+        Ok(AgentResponse {
+            success: true,
+            stdout: format!(
+                "Certificate issued for {} by {}",
+                covered, self.config.acme_directory_url
+            ),
+            ..Default::default()
+        })
+    }
+
+    /// Advisory progress for a tracked operation; a no-op on the synchronous path.
+    async fn report_progress(&self, op_id: Option<&str>, percent: u32, message: &str) {
+        if let Some(id) = op_id
+            && let Err(e) = self.operations.progress(id, percent, message).await
+        {
+            warn!("Failed to record progress for operation {}: {}", id, e);
+        }
+    }
+
+    /// 🛡️ SLA: kill_on_drop ensures a timed-out package manager or host
+    /// operation is killed, not orphaned.
+    async fn run_host_command(
+        &self,
+        command: &str,
+        args: &[String],
+        limit: Duration,
+    ) -> Result<AgentResponse, Status> {
+        let spec = CommandSpec::privileged(command)
+            .args(args.iter().cloned())
+            .env("DEBIAN_FRONTEND", "noninteractive")
+            .kill_on_drop();
+        let child = self.executor.run(&spec);
+
+        let output = tokio::time::timeout(limit, child)
+            .await
+            .map_err(|_| {
+                Status::deadline_exceeded(format!(
+                    "[SLA ERROR] {} killed after {:?}",
+                    command, limit
+                ))
+            })?
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Execution failed: {}", e)))?;
+
+        Ok(AgentResponse {
+            success: output.success(),
+            exit_code: output.code.unwrap_or(-1),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            error_message: String::new(),
+            operation_id: String::new(),
+        })
+    }
+
+    fn operation_result(resp: AgentResponse) -> OperationResult {
+        OperationResult {
+            success: resp.success,
+            exit_code: resp.exit_code,
+            stdout: resp.stdout,
+            stderr: resp.stderr,
+            error_message: resp.error_message,
+        }
+    }
+
+    fn operation_to_proto(op: OperationRecord) -> Operation {
+        use kari_agent::OperationState as ProtoState;
+
+        Operation {
+            id: op.id,
+            kind: op.kind,
+            state: match op.state {
+                OperationState::Running => ProtoState::Running,
+                OperationState::Succeeded => ProtoState::Succeeded,
+                OperationState::Failed => ProtoState::Failed,
+            } as i32,
+            progress_percent: op.progress_percent,
+            message: op.message,
+            result: op.result.map(|r| AgentResponse {
+                success: r.success,
+                exit_code: r.exit_code,
+                stdout: r.stdout,
+                stderr: r.stderr,
+                error_message: r.error_message,
+                operation_id: String::new(),
+            }),
+            created_at: op.created_at,
+            updated_at: op.updated_at,
+            initiated_by: op.initiated_by,
+        }
+    }
+
+    /// (Re)writes the tenant's slice from its stored quota (unlimited if none was
+    /// ever set) and returns the slice name for the app's `Slice=` line.
+    async fn ensure_tenant_slice(&self, tenant_id: &str) -> Result<String, Status> {
+        let quota: TenantQuota = get_record(self.state_store.as_ref(), NS_TENANTS, tenant_id)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?
+            .unwrap_or_else(|| TenantQuota {
+                tenant_id: tenant_id.to_string(),
+                ..Default::default()
+            });
+        self.tenant_slice_mgr
+            .write_slice(&quota)
+            .await
+            .map_err(|e| Self::unit_error("Tenant slice failed", e))?;
+        Ok(tenant_slice_name(tenant_id))
+    }
+
+    /// Pre-flight shared by StreamDeployment and PrebuildRelease. Everything
+    /// that can refuse the request is checked here, before anything on disk
+    /// changes.
+    async fn prepare_deploy(&self, mut req: DeployRequest) -> Result<PreparedDeploy, Status> {
+        use kari_agent::Runtime;
+
+        // 🛡️ Zero-Trust: Validate identifiers before processing
+        validate_identifier(&req.app_id, "app_id")?;
+        validate_domain_name(&req.domain_name)?;
+        self.resolve_secret_refs(&mut req.env_vars).await?;
+
+        let release = chrono::Utc::now().format("%Y%m%d%H%M%S").to_string();
+
+        let base_dir = Self::secure_join(&self.config.web_root, &req.domain_name)?;
+        let release_dir = base_dir.join("releases").join(&release);
+        let app_user = format!("kari-app-{}", req.app_id);
+
+        // 📏 Clone + build typically needs about twice the previous release on disk
+        let needed_mb =
+            (capacity::last_release_mb(&base_dir) * 2).max(self.config.min_free_disk_mb);
+        capacity::check_disk(&self.config.web_root, needed_mb).map_err(Self::capacity_error)?;
+
+        // 🛡️ Zero-Trust: Validate the whole cron spec up front so a bad entry
+        // fails the deploy before anything on disk changes.
+        let mut cron_intents: Vec<TraitJobIntent> = Vec::with_capacity(req.cron_jobs.len());
+        for entry in &req.cron_jobs {
+            let job_name = app_cron::app_job_name(&req.app_id, &entry.name);
+            if entry.name.is_empty()
+                || !job_name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-')
+            {
+                return Err(Status::invalid_argument(format!(
+                    "Zero-Trust: Invalid cron job name: '{}'",
+                    job_name
+                )));
+            }
+            if cron_intents.iter().any(|i| i.name == job_name) {
+                return Err(Status::invalid_argument(format!(
+                    "Duplicate cron job name: '{}'",
+                    entry.name
+                )));
+            }
+            validate_job_binary(&entry.binary)?;
+
+            cron_intents.push(TraitJobIntent {
+                name: job_name,
+                binary: entry.binary.clone(),
+                args: entry.args.clone(),
+                schedule: entry.schedule_expression.clone(),
+                run_as_user: app_user.clone(),
+            });
+        }
+
+        let app = get_record::<AppRecord>(self.state_store.as_ref(), NS_APPS, &req.app_id)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        // 📦 Only a container unit can be pointed at an image
+        let source = if req.runtime() == Runtime::Container {
+            if app.as_ref().is_none_or(|app| app.runtime != "container") {
+                return Err(Status::failed_precondition(format!(
+                    "{} is not a container app; provision it with runtime CONTAINER first",
+                    req.app_id
+                )));
+            }
+            DeploySource::Image
+        } else {
+            DeploySource::Git
+        };
+        // 🛡️ The offline flag is per-app, so a deploy request cannot opt back out of it
+        let offline_build = app.is_some_and(|app| app.offline_build);
+
+        Ok(PreparedDeploy {
+            req,
+            source,
+            release,
+            release_dir,
+            app_user,
+            cron_intents,
+            offline_build,
+        })
+    }
+
+    /// Clone, scan, jail, fetch, build and SBOM, then records the release. Every
+    /// failure is reported on the log, and fails its phase; `None` means the
+    /// release must not go live.
+    async fn build_release(
+        &self,
+        job: PreparedDeploy,
+        progress: &DeployProgress,
+    ) -> Option<BuiltRelease> {
+        let PreparedDeploy {
+            mut req,
+            source,
+            release,
+            release_dir,
+            app_user,
+            cron_intents,
+            offline_build,
+        } = job;
+        let tx = &progress.tx;
+        let t = req.trace_id.clone();
+        let log = |m: &str| LogChunk {
+            content: m.to_string(),
+            trace_id: t.clone(),
+            finding: None,
+            usage: None,
+            compressed_content: Vec::new(),
+            raw_content: Vec::new(),
+            event: None,
+            queued: None,
+        };
+
+        // -- Step 1: Secure Git Clone (or the deploy's verified archive) --
+        progress.start(DeployPhase::Clone).await;
+        let mut archive_digest = None;
+        let mut image = None;
+        let fetched = match source {
+            DeploySource::Git => {
+                let ssh_cred = req.ssh_key.take().map(ProviderCredential::from_string);
+                let _ = tx.send(Ok(log("📦 Pulling source...\n"))).await;
+                self.git_mgr
+                    .clone_repo(&req.repo_url, &req.branch, &release_dir, ssh_cred)
+                    .await
+                    .map_err(|e| format!("Git Error: {}", e))
+            }
+            DeploySource::Archive(archive) => {
+                archive_digest = Some(archive.sha256.clone());
+                self.unpack_archive_source(*archive, &release_dir, tx, &t)
+                    .await
+                    .map_err(|e| format!("Archive Error: {}", e))
+            }
+            DeploySource::Image => {
+                image = Some(req.image.clone());
+                let _ = tx
+                    .send(Ok(log(&format!("🐳 Pulling image {}...\n", req.image))))
+                    .await;
+                self.pull_release_image(&req.app_id, &req.image, &app_user, &req.domain_name)
+                    .await
+                    .map_err(|e| format!("Image Error: {}", e))
+            }
+        };
+        if let Err(e) = fetched {
+            let _ = tx.send(Ok(log(&format!("❌ {}\n", e)))).await;
+            progress.fail(DeployPhase::Clone, &e).await;
+            return None;
+        }
+        progress.succeed(DeployPhase::Clone).await;
+
+        // 📦 A container release is its image: there is nothing to scan or build,
+        // only an empty release directory for `current` to point at
+        if let Some(image) = image {
+            progress.start(DeployPhase::Secure).await;
+            if let Err(e) = self
+                .jail_mgr
+                .secure_directory(&release_dir, &app_user)
+                .await
+            {
+                let e = format!("Security Error: {}", e);
+                let _ = tx.send(Ok(log(&format!("❌ {}\n", e)))).await;
+                progress.fail(DeployPhase::Secure, &e).await;
+                return None;
+            }
+            progress.succeed(DeployPhase::Secure).await;
+            let record = ReleaseRecord {
+                image: Some(image),
+                ..Self::release_record(&req, &release, &cron_intents)
+            };
+            let _ = put_record(
+                self.state_store.as_ref(),
+                NS_RELEASES,
+                &release_key(&req.domain_name, &release),
+                &record,
+            )
+            .await;
+            return Some(BuiltRelease {
+                record,
+                usage: BuildUsage::default(),
+                cloned_bytes: 0,
+                artifact_bytes: 0,
+                artifact_digest: None,
+            });
+        }
+        let limit = self.config.deploy_parallelism;
+
+        // -- Stage 1: Post-Clone Scan (operator policy: warn or block) alongside
+        // jailing and sizing; all three only read the fresh clone or its modes --
+        let blocking = self.config.scan_policy == ScanPolicy::Block;
+        let mut cloned_bytes = 0;
+        let mut jail_error = None;
+        progress.start(DeployPhase::Secure).await;
+        let mut phases: Vec<Phase<'_, bool>> = Vec::new();
+        phases.push(phase(async {
+            let dir = release_dir.clone();
+            cloned_bytes = tokio::task::spawn_blocking(move || capacity::dir_size_bytes(&dir))
+                .await
+                .unwrap_or(0);
+            false
+        }));
+        for scanner in &self.source_scanners {
+            let (tx, log, t, release_dir) = (tx, &log, &t, &release_dir);
+            phases.push(phase(async move {
+                let _ = tx
+                    .send(Ok(log(&format!(
+                        "🔎 Scanning source ({})...\n",
+                        scanner.name()
+                    ))))
+                    .await;
+                let findings = match scanner.scan(release_dir).await {
+                    Ok(findings) => findings,
+                    Err(e) => {
+                        // 🛡️ Block fails closed: an unscanned release is not deployed
+                        let _ = tx
+                            .send(Ok(log(&format!(
+                                "⚠️ Scanner {} failed: {}\n",
+                                scanner.name(),
+                                e
+                            ))))
+                            .await;
+                        if !blocking {
+                            progress.warn(
+                                DeployPhase::Secure,
+                                format!("Scanner {} failed: {}", scanner.name(), e),
+                            );
+                        }
+                        return blocking;
+                    }
+                };
+                let mut blocked = false;
+                for f in findings {
+                    if !blocking {
+                        progress.warn(
+                            DeployPhase::Secure,
+                            format!("{}: {} at {}:{}", f.scanner, f.rule, f.path, f.line),
+                        );
+                    }
+                    let chunk = LogChunk {
+                        content: format!(
+                            "{} {}: {} at {}:{}\n",
+                            if blocking { "❌" } else { "⚠️" },
+                            f.scanner,
+                            f.rule,
+                            f.path,
+                            f.line
+                        ),
+                        trace_id: t.clone(),
+                        finding: Some(ScanFindingEvent {
+                            scanner: f.scanner,
+                            rule: f.rule,
+                            path: f.path,
+                            line: f.line,
+                            blocking,
+                        }),
+                        usage: None,
+                        compressed_content: Vec::new(),
+                        raw_content: Vec::new(),
+                        event: None,
+                        queued: None,
+                    };
+                    let _ = tx.send(Ok(chunk)).await;
+                    blocked |= blocking;
+                }
+                blocked
+            }));
+        }
+        // -- Step 2: Permissions Jailing --
+        // (ssh_cred ownership transferred to clone_repo; zeroized on drop)
+        phases.push(phase(async {
+            let _ = tx.send(Ok(log("🔒 Securing directory...\n"))).await;
+            match self
+                .jail_mgr
+                .secure_directory(&release_dir, &app_user)
+                .await
+            {
+                Ok(()) => {}
+                Err(e) => {
+                    let e = format!("Security Error: {}", e);
+                    let _ = tx.send(Ok(log(&format!("❌ {}\n", e)))).await;
+                    jail_error = Some(e);
+                }
+            }
+            false
+        }));
+        let blocked = run_stage(phases, limit).await.contains(&true);
+        if blocked {
+            let _ = tx
+                .send(Ok(log(
+                    "❌ Scan Policy: deploy blocked, release discarded\n",
+                )))
+                .await;
+            let _ = tokio::fs::remove_dir_all(&release_dir).await;
+            progress
+                .fail(
+                    DeployPhase::Secure,
+                    "Scan Policy: deploy blocked, release discarded",
+                )
+                .await;
+            return None;
+        }
+        if let Some(e) = jail_error {
+            progress.fail(DeployPhase::Secure, &e).await;
+            return None;
+        }
+        progress.succeed(DeployPhase::Secure).await;
+
+        let mut envs: HashMap<String, String> = std::mem::take(&mut req.env_vars);
+        let mut usage = BuildUsage::default();
+
+        // -- Step 2b: Artifact Store (an identical build is restored, not rerun) --
+        progress.start(DeployPhase::Build).await;
+        // An archive's digest names its source as a commit does a clone's
+        let artifact_key = self
+            .artifact_store()
+            .and_then(|_| archive_digest.or_else(|| artifacts::head_commit(&release_dir)))
+            .map(|commit| {
+                artifacts::cache_key(
+                    &commit,
+                    &req.fetch_argv,
+                    &req.build_argv,
+                    offline_build,
+                    &envs,
+                )
+            });
+        let mut restored = false;
+        let mut artifact_digest = None;
+        if let (Some(store), Some(key)) = (self.artifact_store(), artifact_key.clone())
+            && !req.rebuild
+        {
+            let dir = release_dir.clone();
+            let restore = tokio::task::spawn_blocking(move || store.restore(&key, &dir))
+                .await
+                .map_err(|e| format!("Restore task failed: {}", e))
+                .and_then(|r| r);
+            match restore {
+                Ok(Some(artifact)) => {
+                    // Unpacked as root: the jail takes ownership again
+                    if let Err(e) = self
+                        .jail_mgr
+                        .secure_directory(&release_dir, &app_user)
+                        .await
+                    {
+                        for (_, mut val) in envs.drain() {
+                            val.zeroize();
+                        }
+                        let e = format!("Security Error: {}", e);
+                        let _ = tx.send(Ok(log(&format!("❌ {}\n", e)))).await;
+                        progress.fail(DeployPhase::Build, &e).await;
+                        return None;
+                    }
+                    restored = true;
+                    artifact_digest = Some(artifact.digest.clone());
+                    let _ = tx
+                        .send(Ok(log(&format!(
+                            "♻️ Restored build artifact sha256:{} ({:.1} MiB); skipping fetch and build\n",
+                            artifact.digest,
+                            artifact.size_bytes as f64 / 1_048_576.0
+                        ))))
+                        .await;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    let e = format!("Artifact store: {}; building from source", e);
+                    let _ = tx.send(Ok(log(&format!("⚠️ {}\n", e)))).await;
+                    progress.warn(DeployPhase::Build, e);
+                }
+            }
+        }
+
+        // -- Step 3 gate: fetch and build run within the host's build slots --
+        let build_slots = if restored || (req.fetch_argv.is_empty() && req.build_argv.is_empty()) {
+            None
+        } else {
+            Some(
+                progress
+                    .queue_for_build(&self.build_queue, req.build_class())
+                    .await,
+            )
+        };
+
+        // -- Step 3a: Dependency Fetch (the only networked build phase) --
+        let fetched = !restored && !req.fetch_argv.is_empty();
+        if fetched {
+            let _ = tx.send(Ok(log("📥 Fetching dependencies...\n"))).await;
+            let no_envs = HashMap::new();
+            let fetch_res = self
+                .build_mgr
+                .execute_build(
+                    &req.fetch_argv,
+                    &release_dir,
+                    &app_user,
+                    BuildNetwork::Host,
+                    if offline_build { &no_envs } else { &envs },
+                    tx.clone(),
+                    t.clone(),
+                )
+                .await;
+            match fetch_res {
+                Ok(fetch_usage) => usage.merge(fetch_usage),
+                Err(e) => {
+                    for (_, mut val) in envs.drain() {
+                        val.zeroize();
+                    }
+                    let _ = tx.send(Ok(log(&format!("❌ Fetch Error: {}\n", e)))).await;
+                    progress
+                        .fail(DeployPhase::Build, &format!("Fetch Error: {}", e))
+                        .await;
+                    return None;
+                }
+            }
+        }
+
+        // -- Stage 3: Isolated Build, with the SBOM alongside once a fetch
+        // phase has settled the lockfiles (otherwise the build may still write them) --
+        let network = if offline_build {
+            BuildNetwork::Isolated
+        } else {
+            BuildNetwork::Host
+        };
+        let sbom_path = sbom::sbom_path(&self.config.state_dir, &req.domain_name, &release);
+        let sbom_phase = || async {
+            let _ = tx.send(Ok(log("📜 Generating SBOM...\n"))).await;
+            let (dir, path) = (release_dir.clone(), sbom_path.clone());
+            let (domain, release) = (req.domain_name.clone(), release.clone());
+            tokio::task::spawn_blocking(move || write_sbom(&dir, &path, &domain, &release))
+                .await
+                .map_err(|e| format!("SBOM task failed: {}", e))?
+        };
+        let sbom_early = req.generate_sbom && fetched;
+        let mut build_res = Ok(BuildUsage::default());
+        let mut sbom_res = None;
+        let mut phases: Vec<Phase<'_, ()>> = vec![phase(async {
+            if restored {
+                return;
+            }
+            if req.build_argv.is_empty() {
+                let _ = tx.send(Ok(log("⏭️ No build argv; skipping build\n"))).await;
+                return;
+            }
+            let _ = tx.send(Ok(log("🏗️ Executing build...\n"))).await;
+            build_res = self
+                .build_mgr
+                .execute_build(
+                    &req.build_argv,
+                    &release_dir,
+                    &app_user,
+                    network,
+                    &envs,
+                    tx.clone(),
+                    t.clone(),
+                )
+                .await;
+        })];
+        if sbom_early {
+            phases.push(phase(async {
+                sbom_res = Some(sbom_phase().await);
+            }));
+        }
+        run_stage(phases, limit).await;
+
+        // 🛡️ Privacy: Clear the build environment variables from RAM
+        for (_, mut val) in envs.drain() {
+            val.zeroize();
+        }
+        drop(build_slots);
+
+        match build_res {
+            Ok(build_usage) => usage.merge(build_usage),
+            Err(e) => {
+                if sbom_early {
+                    let _ = std::fs::remove_file(&sbom_path);
+                }
+                let _ = tx.send(Ok(log(&format!("❌ Build Error: {}\n", e)))).await;
+                progress
+                    .fail(DeployPhase::Build, &format!("Build Error: {}", e))
+                    .await;
+                return None;
+            }
+        }
+        let artifact_bytes = capacity::dir_size_bytes(&release_dir);
+
+        // -- Step 3b: Archive the build (best effort) for identical redeploys --
+        if let (Some(store), Some(key)) = (self.artifact_store(), artifact_key)
+            && !restored
+        {
+            let dir = release_dir.clone();
+            let archived = tokio::task::spawn_blocking(move || {
+                let artifact = store.archive(&key, &dir)?;
+                let _ = store.prune();
+                Ok::<_, String>(artifact)
+            })
+            .await
+            .map_err(|e| format!("Archive task failed: {}", e))
+            .and_then(|r| r);
+            let message = match archived {
+                Ok(artifact) => {
+                    let message = format!(
+                        "🗄️ Archived build artifact sha256:{} ({:.1} MiB)\n",
+                        artifact.digest,
+                        artifact.size_bytes as f64 / 1_048_576.0
+                    );
+                    artifact_digest = Some(artifact.digest);
+                    message
+                }
+                Err(e) => {
+                    let e = format!("Build artifact not archived: {}", e);
+                    let message = format!("⚠️ {}\n", e);
+                    progress.warn(DeployPhase::Build, e);
+                    message
+                }
+            };
+            let _ = tx.send(Ok(log(&message))).await;
+        }
+
+        // -- Step 3c: SBOM (best effort; a scan failure never fails the deploy) --
+        let mut record = Self::release_record(&req, &release, &cron_intents);
+        if req.generate_sbom && !sbom_early {
+            sbom_res = Some(sbom_phase().await);
+        }
+        match sbom_res {
+            Some(Ok(count)) => record.sbom_components = Some(count),
+            Some(Err(e)) => {
+                let _ = tx.send(Ok(log(&format!("⚠️ SBOM skipped: {}\n", e)))).await;
+                progress.warn(DeployPhase::Build, format!("SBOM skipped: {}", e));
+            }
+            None => {}
+        }
+        let _ = put_record(
+            self.state_store.as_ref(),
+            NS_RELEASES,
+            &release_key(&req.domain_name, &release),
+            &record,
+        )
+        .await;
+        progress.succeed(DeployPhase::Build).await;
+
+        Some(BuiltRelease {
+            record,
+            usage,
+            cloned_bytes,
+            artifact_bytes,
+            artifact_digest,
+        })
+    }
+
+    /// What activating a release needs, so a prebuilt release can go live
+    /// later without the original request.
+    fn release_record(
+        req: &DeployRequest,
+        release: &str,
+        cron_intents: &[TraitJobIntent],
+    ) -> ReleaseRecord {
+        ReleaseRecord {
+            app_id: req.app_id.clone(),
+            domain_name: req.domain_name.clone(),
+            release: release.to_string(),
+            created_at: chrono::Utc::now().timestamp(),
+            sbom_components: None,
+            port: Some(req.port.unwrap_or(3000) as u16),
+            cron_jobs: cron_intents.iter().map(JobRecord::from).collect(),
+            activated_at: None,
+            health_check: req.health_check.as_ref().map(|c| HealthCheckRecord {
+                path: c.path.clone(),
+                timeout_secs: c.timeout_secs,
+                retries: c.retries,
+                interval_secs: c.interval_secs,
+            }),
+            image: None,
+        }
+    }
+
+    /// 🐳 Pulls a container release's image into the app's rootless storage,
+    /// with the app's registry auth present for this one pull.
+    async fn pull_release_image(
+        &self,
+        app_id: &str,
+        image: &str,
+        app_user: &str,
+        domain: &str,
+    ) -> Result<(), String> {
+        let auth = self
+            .secret_store
+            .get_secret(&Self::registry_auth_secret(app_id))
+            .await
+            .map_err(|e| format!("Secret lookup failed: {}", e))?;
+        self.image_mgr
+            .pull_image(image, app_user, &self.config.web_root.join(domain), auth)
+            .await
+    }
+
+    /// 📦 Points a container app's unit at a release's image. Source releases
+    /// leave the unit alone.
+    async fn retarget_unit(
+        &self,
+        service_name: &str,
+        record: &ReleaseRecord,
+    ) -> Result<(), String> {
+        let Some(image) = &record.image else {
+            return Ok(());
+        };
+        self.image_mgr.set_unit_image(service_name, image).await?;
+        self.svc_mgr.reload_daemon().await
+    }
+
+    /// 📦 Receives (or fetches) an artifact deploy's tar.gz, checks it against
+    /// its digest and unpacks it as the release.
+    async fn unpack_archive_source(
+        &self,
+        archive: ArchiveSource,
+        release_dir: &Path,
+        tx: &mpsc::Sender<Result<LogChunk, Status>>,
+        trace_id: &str,
+    ) -> Result<(), String> {
+        let ArchiveSource {
+            sha256,
+            url,
+            strip_components,
+            first,
+            mut rest,
+        } = archive;
+        let max_bytes = self
+            .config
+            .source_archive_max_mb
+            .saturating_mul(1024 * 1024);
+        let log = |m: String| LogChunk {
+            content: m,
+            trace_id: trace_id.to_string(),
+            finding: None,
+            usage: None,
+            compressed_content: Vec::new(),
+            raw_content: Vec::new(),
+            event: None,
+            queued: None,
+        };
+
+        let staged = match url {
+            Some(url) => {
+                // 🛡️ The URL may carry a token: only its host is logged
+                let host = url
+                    .trim_start_matches("https://")
+                    .split(['/', '?', '#'])
+                    .next()
+                    .unwrap_or_default()
+                    .rsplit('@')
+                    .next()
+                    .unwrap_or_default()
+                    .to_string();
+                let _ = tx
+                    .send(Ok(log(format!(
+                        "📦 Fetching source archive from {}...\n",
+                        host
+                    ))))
+                    .await;
+                let staged = source_archive::download(&url, max_bytes).await?;
+                if rest.message().await.map_err(|e| e.to_string())?.is_some() {
+                    return Err("no chunk may follow one that names a url".into());
+                }
+                staged
+            }
+            None => {
+                let _ = tx
+                    .send(Ok(log("📦 Receiving source archive...\n".into())))
+                    .await;
+                let mut staged = StagedArchive::new(max_bytes)?;
+                let mut next = Some(first);
+                while let Some(data) = next {
+                    // Staged writes block; keep them off the runtime's threads
+                    staged =
+                        tokio::task::spawn_blocking(move || staged.write(&data).map(|_| staged))
+                            .await
+                            .map_err(|e| format!("Archive task failed: {}", e))??;
+                    next = match rest.message().await.map_err(|e| e.to_string())? {
+                        Some(chunk) => {
+                            chunk.validate().map_err(|e| e.message().to_string())?;
+                            if chunk.deploy.is_some()
+                                || !chunk.sha256.is_empty()
+                                || !chunk.url.is_empty()
+                                || chunk.strip_components != 0
+                            {
+                                return Err("only the first chunk names the deploy".into());
+                            }
+                            Some(chunk.data)
+                        }
+                        None => None,
+                    };
+                }
+                staged
+            }
+        };
+
+        let dir = release_dir.to_path_buf();
+        let (size_bytes, unpacked) = tokio::task::spawn_blocking(move || {
+            let archive = staged.finish(&sha256)?;
+            // A half-unpacked tree is never scanned or built
+            let unpacked = archive.unpack(&dir, strip_components).inspect_err(|_| {
+                let _ = std::fs::remove_dir_all(&dir);
+            })?;
+            Ok::<_, String>((archive.size_bytes, unpacked))
+        })
+        .await
+        .map_err(|e| format!("Archive task failed: {}", e))??;
+        let _ = tx
+            .send(Ok(log(format!(
+                "📦 Archive verified ({:.1} MiB); unpacked {:.1} MiB\n",
+                size_bytes as f64 / 1_048_576.0,
+                unpacked as f64 / 1_048_576.0
+            ))))
+            .await;
+        Ok(())
+    }
+
+    /// `build_release`, abandoned as soon as the client stops reading the
+    /// stream. Dropping the build stops its process tree; the half-built release
+    /// directory goes too unless the release was already recorded.
+    async fn build_release_watched(
+        &self,
+        job: PreparedDeploy,
+        progress: &DeployProgress,
+        cancel: &mut CancelSlot,
+    ) -> Option<BuiltRelease> {
+        let tx = &progress.tx;
+        let domain = job.req.domain_name.clone();
+        let release = job.release.clone();
+        let release_dir = job.release_dir.clone();
+        let trace_id = job.req.trace_id.clone();
+        tokio::select! {
+            built = self.build_release(job, progress) => built,
+            _ = tx.closed() => {
+                warn!("📦 Deploy {} {} abandoned by its client; build stopped", domain, release);
+                self.discard_partial_release(&domain, &release, &release_dir).await;
+                None
+            }
+            _ = cancel.cancelled() => {
+                warn!("🛑 Deploy {} {} cancelled; build stopped", domain, release);
+                self.discard_partial_release(&domain, &release, &release_dir).await;
+                let _ = tx
+                    .send(Ok(LogChunk {
+                        content: format!(
+                            "🛑 Deployment cancelled: build stopped and release {} removed.\n",
+                            release
+                        ),
+                        trace_id,
+                        finding: None,
+                        usage: None,
+                        compressed_content: Vec::new(),
+                        raw_content: Vec::new(),
+                        event: None,
+                        queued: None,
+                    }))
+                    .await;
+                progress.fail_open("Deployment cancelled").await;
+                None
+            }
+        }
+    }
+
+    /// Removes a release directory the build never finished. Dropping the
+    /// build future has already stopped its build unit and signalled its
+    /// clone; the clone's processes are reaped before the directory goes, so
+    /// none of them writes into it after it is removed.
+    async fn discard_partial_release(&self, domain: &str, release: &str, release_dir: &Path) {
+        if let Err(e) = self.git_mgr.stop_clone(release_dir).await {
+            warn!(
+                "📦 Kept partial release {} {}: its clone did not stop: {}",
+                domain, release, e
+            );
+            return;
+        }
+        let recorded = get_record::<ReleaseRecord>(
+            self.state_store.as_ref(),
+            NS_RELEASES,
+            &release_key(domain, release),
+        )
+        .await;
+        if !matches!(recorded, Ok(Some(_))) {
+            let _ = tokio::fs::remove_dir_all(release_dir).await;
+        }
+    }
+
+    /// Switches a built release live: `current` link, unit restart, health
+    /// gate, then the ingress port and the release's app timers.
+    /// StreamDeployment, ActivateRelease and rollbacks all go through here. A
+    /// release that fails the health gate is switched back to the one it
+    /// replaced before the vhost ever points at it. PROXY is left open for the
+    /// caller, which may still have a post_deploy hook to run.
+    async fn switch_to_release(
+        &self,
+        record: &ReleaseRecord,
+        progress: &DeployProgress,
+    ) -> Result<(), String> {
+        let (tx, trace_id) = (&progress.tx, progress.trace_id.as_str());
+        let log = |m: &str| LogChunk {
+            content: m.to_string(),
+            trace_id: trace_id.to_string(),
+            finding: None,
+            usage: None,
+            compressed_content: Vec::new(),
+            raw_content: Vec::new(),
+            event: None,
+            queued: None,
+        };
+        let domain = &record.domain_name;
+        let app_dir = self.config.web_root.join(domain);
+        let service_name = format!("kari-{}", domain);
+        let app = get_record::<AppRecord>(self.state_store.as_ref(), NS_APPS, &record.app_id)
+            .await
+            .ok()
+            .flatten();
+        let vhost = app.as_ref().map(|a| a.vhost.clone()).unwrap_or_default();
+        let previous_release = cleanup::current_release(&app_dir);
+        let port = record.port.unwrap_or(3000);
+        progress.start(DeployPhase::Activate).await;
+        // 🔌 Refused before anything is switched, rather than a crash loop on EADDRINUSE
+        if let Some(conflict) = self.port_conflict(port, &service_name).await {
+            return Err(conflict);
+        }
+
+        // -- Step 4: Release Switch & Service Activation --
+        let _ = tx
+            .send(Ok(log("🌐 Updating Proxy & Restarting...\n")))
+            .await;
+        self.retarget_unit(&service_name, record)
+            .await
+            .map_err(|e| format!("Image Error: {}", e))?;
+        cleanup::switch_current_release(&app_dir, &record.release)
+            .map_err(|e| format!("Release Error: {}", e))?;
+
+        self.svc_mgr
+            .restart(&service_name)
+            .await
+            .map_err(|e| format!("Service Error: {}", e))?;
+
+        // -- Step 4b: Health Gate (the unit must survive its first seconds,
+        // then answer its health check) before any traffic moves --
+        let settle = Duration::from_secs(self.config.activation_health_secs);
+        let _ = tx
+            .send(Ok(log(&format!(
+                "🩺 Waiting {}s for {} to settle...\n",
+                settle.as_secs(),
+                service_name
+            ))))
+            .await;
+        tokio::time::sleep(settle).await;
+        let mut unhealthy = None;
+        if !self.svc_mgr.is_active(&service_name).await.unwrap_or(false) {
+            unhealthy = Some(format!(
+                "{} was not running {}s after restart",
+                service_name,
+                settle.as_secs()
+            ));
+        } else if let Some(check) = record.health_check.as_ref().map(|c| self.health_check(c)) {
+            let _ = tx
+                .send(Ok(log(&format!(
+                    "🩺 GET {} on port {} (up to {} attempts)...\n",
+                    check.path,
+                    port,
+                    check.retries + 1
+                ))))
+                .await;
+            match http_probe::wait_healthy(port, domain, &check).await {
+                Ok(attempts) => {
+                    let _ = tx
+                        .send(Ok(log(&format!(
+                            "✅ Healthy after {} attempt(s)\n",
+                            attempts
+                        ))))
+                        .await;
+                }
+                Err(e) => {
+                    unhealthy = Some(format!(
+                        "{} did not pass its health check: {}",
+                        service_name, e
+                    ))
+                }
+            }
+        }
+        if let Some(reason) = unhealthy {
+            // The vhost still points at the previous release's port, so
+            // switching `current` back is all it takes to keep it serving
+            let outcome = match previous_release {
+                Some(previous) if previous != record.release => {
+                    let restored = cleanup::switch_current_release(&app_dir, &previous);
+                    if let Ok(Some(previous)) = get_record::<ReleaseRecord>(
+                        self.state_store.as_ref(),
+                        NS_RELEASES,
+                        &release_key(domain, &previous),
+                    )
+                    .await
+                    {
+                        let _ = self.retarget_unit(&service_name, &previous).await;
+                    }
+                    let _ = self.svc_mgr.restart(&service_name).await;
+                    match restored {
+                        Ok(()) => format!("rolled back to {}", previous),
+                        Err(e) => format!("rollback to {} failed: {}", previous, e),
+                    }
+                }
+                _ => "no previous release to roll back to".to_string(),
+            };
+            return Err(format!("Health Gate: {}; {}", reason, outcome));
+        }
+        progress.succeed(DeployPhase::Activate).await;
+
+        // -- Step 4c: Traffic Switch --
+        progress.start(DeployPhase::Proxy).await;
+        // 🛡️ Zero-Trust: ProxyManager implementation strictly validates domain_name to prevent Nginx/Apache injection.
+        // This is Defense-in-Depth as validate_identifier() also checks it upstream.
+        self.proxy_mgr
+            .create_vhost(domain, port, &vhost)
+            .await
+            .map_err(|e| format!("Proxy Error: {}", e))?;
+
+        // Remember the ingress port so the app can be migrated or rebuilt later
+        // (and, for a container release, the image that image GC must keep)
+        if let Some(mut app) = app {
+            app.port = Some(port);
+            if record.image.is_some() {
+                app.image = record.image.clone();
+            }
+            let _ = put_record(self.state_store.as_ref(), NS_APPS, &record.app_id, &app).await;
+        }
+
+        // ⏪ Remembered so RollbackDeployment knows what was live before
+        let activated = ReleaseRecord {
+            activated_at: Some(chrono::Utc::now().timestamp()),
+            ..record.clone()
+        };
+        let _ = put_record(
+            self.state_store.as_ref(),
+            NS_RELEASES,
+            &release_key(domain, &record.release),
+            &activated,
+        )
+        .await;
+
+        // -- Step 5: App Cron (replaces the previous release's timers) --
+        let _ = tx.send(Ok(log("⏰ Syncing scheduled jobs...\n"))).await;
+        let cron_intents: Vec<TraitJobIntent> = record
+            .cron_jobs
+            .iter()
+            .map(|job| TraitJobIntent {
+                name: job.job_name.clone(),
+                binary: job.binary.clone(),
+                args: job.args.clone(),
+                schedule: job.schedule.clone(),
+                run_as_user: job.run_as_user.clone(),
+            })
+            .collect();
+        app_cron::sync_app_jobs(
+            self.job_scheduler.as_ref(),
+            self.state_store.as_ref(),
+            &record.app_id,
+            &cron_intents,
+        )
+        .await
+        .map_err(|e| format!("Cron Error: {}", e))
+    }
+
+    /// 🩺 A release's recorded health check, with the agent's defaults for
+    /// whatever the deploy left at zero.
+    fn health_check(&self, check: &HealthCheckRecord) -> http_probe::HealthCheck {
+        let or = |value: u32, default: u64| match value {
+            0 => Duration::from_secs(default),
+            secs => Duration::from_secs(u64::from(secs)),
+        };
+        http_probe::HealthCheck {
+            path: check.path.clone(),
+            timeout: or(check.timeout_secs, self.config.health_check_timeout_secs),
+            retries: match check.retries {
+                0 => self.config.health_check_retries,
+                retries => retries,
+            },
+            interval: or(check.interval_secs, self.config.health_check_interval_secs),
+        }
+    }
+
+    /// 🪝 Runs one of a deploy's hooks like a build step, on the deploy's log.
+    /// Empty argv means the deploy has no such hook.
+    async fn run_hook(
+        &self,
+        name: &str,
+        argv: &[String],
+        hooks: &DeployHooks,
+        tx: &mpsc::Sender<Result<LogChunk, Status>>,
+        trace_id: &str,
+    ) -> Result<BuildUsage, String> {
+        if argv.is_empty() {
+            return Ok(BuildUsage::default());
+        }
+        let _ = tx
+            .send(Ok(LogChunk {
+                content: format!("🪝 Running {} hook...\n", name),
+                trace_id: trace_id.to_string(),
+                finding: None,
+                usage: None,
+                compressed_content: Vec::new(),
+                raw_content: Vec::new(),
+                event: None,
+                queued: None,
+            }))
+            .await;
+        self.build_mgr
+            .execute_build(
+                argv,
+                &hooks.release_dir,
+                &hooks.app_user,
+                BuildNetwork::Host,
+                &hooks.envs,
+                tx.clone(),
+                trace_id.to_string(),
+            )
+            .await
+            .map_err(|e| format!("{} hook failed: {}", name, e))
+    }
+
+    /// 🧹 Removes all but the domain's newest `keep` releases; the live one
+    /// always stays.
+    async fn prune_releases_of(&self, domain: &str, keep: usize) -> Result<usize, String> {
+        let releases_dir = Self::secure_join(&self.config.web_root, domain)
+            .map_err(|e| e.message().to_string())?
+            .join("releases");
+        let pruned = self
+            .release_mgr
+            .prune_old_releases(&releases_dir, keep)
+            .await?;
+        if pruned > 0 {
+            info!("🧹 Pruned {} old release(s) of {}", pruned, domain);
+        }
+        Ok(pruned)
+    }
+
+    /// 🐢 An app's slow-client limits: the defaults, with whatever the
+    /// request sets in their place.
+    fn client_limits(requested: Option<&VhostClientLimits>) -> ClientLimits {
+        let defaults = ClientLimits::default();
+        let Some(requested) = requested else {
+            return defaults;
+        };
+        let or = |value: u32, default: u32| if value == 0 { default } else { value };
+        ClientLimits {
+            header_timeout_secs: or(requested.header_timeout_secs, defaults.header_timeout_secs),
+            body_timeout_secs: or(requested.body_timeout_secs, defaults.body_timeout_secs),
+            send_timeout_secs: or(requested.send_timeout_secs, defaults.send_timeout_secs),
+            keepalive_timeout_secs: or(
+                requested.keepalive_timeout_secs,
+                defaults.keepalive_timeout_secs,
+            ),
+            max_body_mb: or(requested.max_body_mb, defaults.max_body_mb),
+            max_connections: or(requested.max_connections, defaults.max_connections),
+        }
+    }
+
+    /// 🔐 An app directory's audit as VerifyAppPermissions reports it.
+    fn permissions_report(
+        app_user: &str,
+        audit: DirectoryAudit,
+        repaired: u64,
+    ) -> AppPermissionsReport {
+        let deviations = audit
+            .deviations
+            .into_iter()
+            .map(|deviation| PermissionDeviation {
+                path: deviation.entry.path,
+                uid: deviation.entry.uid,
+                gid: deviation.entry.gid,
+                mode: deviation.entry.mode,
+                is_dir: deviation.entry.is_dir,
+                problems: deviation
+                    .problems
+                    .into_iter()
+                    .map(|problem| {
+                        let problem = match problem {
+                            PermissionProblem::Owner => DeviationProblem::Owner,
+                            PermissionProblem::Group => DeviationProblem::Group,
+                            PermissionProblem::Writable => DeviationProblem::Writable,
+                            PermissionProblem::OtherAccess => DeviationProblem::OtherAccess,
+                            PermissionProblem::SpecialBits => DeviationProblem::SpecialBits,
+                            PermissionProblem::Unreadable => DeviationProblem::Unreadable,
+                        };
+                        problem as i32
+                    })
+                    .collect(),
+            })
+            .collect();
+        AppPermissionsReport {
+            app_user: app_user.to_string(),
+            checked: audit.checked,
+            deviating: audit.deviating,
+            deviations,
+            repaired,
+        }
+    }
+
+    /// 🗑️ What PurgeTenantLogs removed, class by class.
+    fn log_purge_report(purged: Vec<ClassPurge>) -> LogPurgeReport {
+        let classes = purged
+            .into_iter()
+            .map(|purge| {
+                let log_class = match purge.class {
+                    LogClass::Deploy => PurgedLogClass::Deploy,
+                    LogClass::Audit => PurgedLogClass::Audit,
+                    LogClass::Access => PurgedLogClass::Access,
+                };
+                LogClassPurge {
+                    log_class: log_class as i32,
+                    files: purge.files,
+                    bytes: purge.bytes,
+                    erased: purge.class.sensitive(),
+                }
+            })
+            .collect();
+        LogPurgeReport { classes }
+    }
+
+    /// Runs `switch_to_release` in the background for ActivateRelease and
+    /// RollbackDeployment, ending the log with the outcome.
+    fn stream_switch(
+        &self,
+        record: ReleaseRecord,
+        trace_id: String,
+    ) -> ReceiverStream<Result<LogChunk, Status>> {
+        let (tx, rx) = mpsc::channel(64);
+        let this = self.clone();
+        tokio::spawn(async move {
+            let progress = DeployProgress::new(tx.clone(), &trace_id);
+            let content = match this.switch_to_release(&record, &progress).await {
+                Ok(()) => {
+                    progress.succeed(DeployPhase::Proxy).await;
+                    format!("✅ Release {} is live.\n", record.release)
+                }
+                Err(e) => {
+                    progress.fail_open(&e).await;
+                    format!("❌ {}\n", e)
+                }
+            };
+            let _ = tx
+                .send(Ok(LogChunk {
+                    content,
+                    trace_id,
+                    finding: None,
+                    usage: None,
+                    compressed_content: Vec::new(),
+                    raw_content: Vec::new(),
+                    event: None,
+                    queued: None,
+                }))
+                .await;
+        });
+        ReceiverStream::new(rx)
+    }
+
+    /// Builds a prepared deploy and takes it live in the background:
+    /// StreamDeployment and DeployArtifact differ only in where the source
+    /// comes from.
+    fn run_deployment(
+        &self,
+        job: PreparedDeploy,
+    ) -> Result<ReceiverStream<Result<LogChunk, Status>>, Status> {
+        let mut cancel = self.register_cancel(&job.req.trace_id)?;
+        let approval = if job.req.require_approval {
+            Some(self.register_approval(&job.req.trace_id)?)
+        } else {
+            None
+        };
+        let approval_timeout_secs = job.req.approval_timeout_secs;
+
+        let (client_tx, rx) = mpsc::channel(512);
+        // 📜 Failed deploys are logged too; they are the ones worth reading back
+        let client_tx = if job.req.log_compression() == Compression::Zstd {
+            Self::compress_log_stream(client_tx, &job.req.trace_id)
+        } else {
+            client_tx
+        };
+        let tx = self.tee_build_log(client_tx, &job.req.domain_name, &job.release);
+        let hooks = DeployHooks::of(&job);
+        let in_flight = InFlight::enter(&self.deploys_in_flight);
+
+        let this = self.clone();
+        tokio::spawn(async move {
+            let _in_flight = in_flight;
+            let t = job.req.trace_id.clone();
+            let log = |m: &str| LogChunk {
+                content: m.to_string(),
+                trace_id: t.clone(),
+                finding: None,
+                usage: None,
+                compressed_content: Vec::new(),
+                raw_content: Vec::new(),
+                event: None,
+                queued: None,
+            };
+            let progress = DeployProgress::new(tx.clone(), &t);
+            let Some(mut built) = this
+                .build_release_watched(job, &progress, &mut cancel)
+                .await
+            else {
+                if approval.is_some() {
+                    this.pending_approvals.lock().unwrap().remove(&t);
+                }
+                return;
+            };
+            progress.start(DeployPhase::Activate).await;
+
+            if let Some(decision) = approval {
+                let approved = tokio::select! {
+                    approved = this.await_approval(
+                        &t,
+                        decision,
+                        approval_timeout_secs,
+                        &built.record.release,
+                        &tx,
+                    ) => approved,
+                    _ = cancel.cancelled() => {
+                        this.pending_approvals.lock().unwrap().remove(&t);
+                        Err(format!(
+                            "Deployment cancelled; release {} stays built but inactive",
+                            built.record.release
+                        ))
+                    }
+                };
+                if let Err(e) = approved {
+                    let _ = tx.send(Ok(log(&format!("❌ {}\n", e)))).await;
+                    progress.fail(DeployPhase::Activate, &e).await;
+                    return;
+                }
+            }
+            // 🛑 From here on the deploy runs to the end
+            drop(cancel);
+            // 🛡️ Nobody is watching: leave the release built but not live. A
+            // switch already under way is finished, never cut off halfway.
+            if tx.is_closed() {
+                warn!(
+                    "📦 Deploy {} {} abandoned by its client; not activated",
+                    built.record.domain_name, built.record.release
+                );
+                return;
+            }
+            // Left built but inactive when it fails, as with a rejected approval
+            match this
+                .run_hook("pre_deploy", &hooks.pre_deploy, &hooks, &tx, &t)
+                .await
+            {
+                Ok(usage) => built.usage.merge(usage),
+                Err(e) => {
+                    let _ = tx.send(Ok(log(&format!("❌ {}\n", e)))).await;
+                    progress.fail(DeployPhase::Activate, &e).await;
+                    return;
+                }
+            }
+            if let Err(e) = this.switch_to_release(&built.record, &progress).await {
+                let _ = tx.send(Ok(log(&format!("❌ {}\n", e)))).await;
+                progress.fail_open(&e).await;
+                return;
+            }
+            match this
+                .run_hook("post_deploy", &hooks.post_deploy, &hooks, &tx, &t)
+                .await
+            {
+                Ok(usage) => built.usage.merge(usage),
+                Err(e) => {
+                    let e = format!("{}; release {} is live", e, built.record.release);
+                    let _ = tx.send(Ok(log(&format!("❌ {}\n", e)))).await;
+                    progress.fail(DeployPhase::Proxy, &e).await;
+                    return;
+                }
+            }
+            progress.succeed(DeployPhase::Proxy).await;
+
+            // 🧹 Best effort: a failed prune never fails a deploy that went live
+            match this
+                .prune_releases_of(&built.record.domain_name, this.config.releases_keep)
+                .await
+            {
+                Ok(0) => {}
+                Ok(pruned) => {
+                    let _ = tx
+                        .send(Ok(log(&format!("🧹 Pruned {} old release(s)\n", pruned))))
+                        .await;
+                }
+                Err(e) => {
+                    let _ = tx
+                        .send(Ok(log(&format!("⚠️ Release pruning skipped: {}\n", e))))
+                        .await;
+                }
+            }
+
+            // 📊 Measured cost, so plans can follow real build spend
+            let _ = tx
+                .send(Ok(LogChunk {
+                    usage: Some(built.usage_message()),
+                    compressed_content: Vec::new(),
+                    raw_content: Vec::new(),
+                    ..log(&format!(
+                        "✅ Deployment successful ({}).\n",
+                        built.describe()
+                    ))
+                }))
+                .await;
+        });
+
+        Ok(ReceiverStream::new(rx))
+    }
+
+    /// ✋ Opens the approval slot for `trace_id` up front, so a confirmation
+    /// sent while the build is still running is not lost.
+    fn register_approval(&self, trace_id: &str) -> Result<oneshot::Receiver<bool>, Status> {
+        let mut pending = self.pending_approvals.lock().unwrap();
+        if pending.contains_key(trace_id) {
+            return Err(Status::already_exists(format!(
+                "A deployment with trace id {} is already awaiting approval",
+                trace_id
+            )));
+        }
+        let (decide, decision) = oneshot::channel();
+        pending.insert(trace_id.to_string(), decide);
+        Ok(decision)
+    }
+
+    /// 🛑 Makes the deploy under `trace_id` cancellable. A deploy sent without
+    /// a trace id has nothing to be named by, and runs to the end.
+    fn register_cancel(&self, trace_id: &str) -> Result<CancelSlot, Status> {
+        let mut slot = CancelSlot {
+            deploys: Arc::clone(&self.cancellable_deploys),
+            trace_id: trace_id.to_string(),
+            cancelled: None,
+        };
+        if trace_id.is_empty() {
+            return Ok(slot);
+        }
+        let mut deploys = self.cancellable_deploys.lock().unwrap();
+        if deploys.contains_key(trace_id) {
+            return Err(Status::already_exists(format!(
+                "A deployment with trace id {} is already running",
+                trace_id
+            )));
+        }
+        let (cancel, cancelled) = oneshot::channel();
+        deploys.insert(trace_id.to_string(), cancel);
+        slot.cancelled = Some(cancelled);
+        Ok(slot)
+    }
+
+    /// Holds a built release until ConfirmDeployment answers or the timeout
+    /// passes. Anything but an approval leaves the release built but inactive.
+    async fn await_approval(
+        &self,
+        trace_id: &str,
+        decision: oneshot::Receiver<bool>,
+        timeout_secs: u32,
+        release: &str,
+        tx: &mpsc::Sender<Result<LogChunk, Status>>,
+    ) -> Result<(), String> {
+        let timeout = match timeout_secs {
+            0 => self.config.approval_timeout_secs,
+            secs => u64::from(secs),
+        };
+        let _ = tx
+            .send(Ok(LogChunk {
+                content: format!(
+                    "⏸️ Release {} built; awaiting ConfirmDeployment({}) for up to {}s...\n",
+                    release, trace_id, timeout
+                ),
+                trace_id: trace_id.to_string(),
+                finding: None,
+                usage: None,
+                compressed_content: Vec::new(),
+                raw_content: Vec::new(),
+                event: None,
+                queued: None,
+            }))
+            .await;
+        let outcome = tokio::time::timeout(Duration::from_secs(timeout), decision).await;
+        self.pending_approvals.lock().unwrap().remove(trace_id);
+        match outcome {
+            Ok(Ok(true)) => Ok(()),
+            Ok(Ok(false)) => Err(format!(
+                "Approval Error: deployment rejected; release {} stays built but inactive",
+                release
+            )),
+            Ok(Err(_)) => Err("Approval Error: approval slot closed".to_string()),
+            Err(_) => Err(format!(
+                "Approval Error: no approval within {}s; release {} stays built but inactive",
+                timeout, release
+            )),
+        }
+    }
+
+    /// Puts a copy of every deploy chunk on disk on its way to the client. The
+    /// returned sender feeds both; the log keeps filling after the client
+    /// hangs up, and is finished and rotated once the deploy drops its sender.
+    fn tee_build_log(
+        &self,
+        client: mpsc::Sender<Result<LogChunk, Status>>,
+        domain: &str,
+        release: &str,
+    ) -> mpsc::Sender<Result<LogChunk, Status>> {
+        let (tx, mut rx) = mpsc::channel::<Result<LogChunk, Status>>(512);
+        let state_dir = self.config.state_dir.clone();
+        let domain = domain.to_string();
+        let path = build_log::build_log_path(&state_dir, &domain, release);
+        let max_bytes = (self.config.build_log_max_kb as usize).saturating_mul(1024);
+        let keep = self.config.build_log_keep;
+
+        tokio::spawn(async move {
+            let mut writer = BuildLogWriter::create(&path, max_bytes)
+                .inspect_err(|e| warn!("📜 Build log disabled for {}: {}", domain, e))
+                .ok();
+            // 🛡️ A disconnected client drops the receiver, which stops the running build
+            while let Some(item) = tokio::select! {
+                item = rx.recv() => item,
+                _ = client.closed() => None,
+            } {
+                if let (Some(w), Ok(chunk)) = (writer.as_mut(), &item)
+                    && let Err(e) = w.append(&chunk.content)
+                {
+                    warn!("📜 Build log for {} abandoned: {}", domain, e);
+                    writer = None;
+                }
+                let _ = client.send(item).await;
+            }
+            if let Some(w) = writer {
+                if let Err(e) = w.finish() {
+                    warn!("📜 Build log for {} not finished: {}", domain, e);
+                }
+                if let Err(e) = build_log::prune_build_logs(&state_dir, &domain, keep) {
+                    warn!("📜 Build log rotation for {} failed: {}", domain, e);
+                }
+            }
+        });
+        tx
+    }
+
+    /// 🗜️ Batches plain log output into zstd frames for clients that asked for
+    /// it. Structured chunks (findings, usage, events) and errors pass through as they
+    /// are, after whatever output preceded them, so ordering is kept.
+    fn compress_log_stream(
+        client: mpsc::Sender<Result<LogChunk, Status>>,
+        trace_id: &str,
+    ) -> mpsc::Sender<Result<LogChunk, Status>> {
+        let (tx, mut rx) = mpsc::channel::<Result<LogChunk, Status>>(512);
+        let trace_id = trace_id.to_string();
+
+        tokio::spawn(async move {
+            let mut batch = String::new();
+            let mut deadline: Option<tokio::time::Instant> = None;
+            loop {
+                let item = match deadline {
+                    Some(at) => match tokio::time::timeout_at(at, rx.recv()).await {
+                        Ok(item) => item,
+                        Err(_) => {
+                            flush_log_batch(&client, &trace_id, &mut batch).await;
+                            deadline = None;
+                            continue;
+                        }
+                    },
+                    None => tokio::select! {
+                        item = rx.recv() => item,
+                        _ = client.closed() => break,
+                    },
+                };
+                match item {
+                    Some(Ok(chunk))
+                        if chunk.finding.is_none()
+                            && chunk.usage.is_none()
+                            && chunk.event.is_none()
+                            && chunk.queued.is_none() =>
+                    {
+                        batch.push_str(&chunk.content);
+                        if batch.len() >= LOG_BATCH_BYTES {
+                            flush_log_batch(&client, &trace_id, &mut batch).await;
+                            deadline = None;
+                        } else if deadline.is_none() {
+                            deadline = Some(tokio::time::Instant::now() + LOG_BATCH_WINDOW);
+                        }
+                    }
+                    Some(item) => {
+                        flush_log_batch(&client, &trace_id, &mut batch).await;
+                        deadline = None;
+                        let _ = client.send(item).await;
+                    }
+                    None => {
+                        flush_log_batch(&client, &trace_id, &mut batch).await;
+                        break;
+                    }
+                }
+            }
+        });
+        tx
+    }
+
+    /// Resolves one window of hits against the stored rules. Ids that no longer
+    /// match a stored rule (removed since they logged) are reported without one.
+    async fn firewall_event_summary(
+        state_store: &dyn StateStore,
+        window: HashMap<String, (u64, String)>,
+        window_start: i64,
+        window_end: i64,
+    ) -> Result<FirewallEventSummary, String> {
+        let records: Vec<FirewallRuleRecord> = list_records(state_store, NS_FIREWALL_RULES).await?;
+        let mut by_id: HashMap<String, FirewallRuleRecord> = records
+            .into_iter()
+            .map(|record| {
+                let key =
+                    firewall_rule_key(record.port, &record.protocol, record.source_ip.as_deref());
+                (rule_log_id(&key), record)
+            })
+            .collect();
+
+        let mut rules: Vec<FirewallRuleHits> = window
+            .into_iter()
+            .map(|(rule_id, (hits, last_source_ip))| FirewallRuleHits {
+                rule: by_id.remove(&rule_id).map(Self::firewall_rule_message),
+                rule_id,
+                hits,
+                last_source_ip,
+            })
+            .collect();
+        rules.sort_by(|a, b| b.hits.cmp(&a.hits).then_with(|| a.rule_id.cmp(&b.rule_id)));
+
+        Ok(FirewallEventSummary {
+            window_start,
+            window_end,
+            rules,
+        })
+    }
+
+    /// 📑 The list's field filters, refusing fields it does not offer.
+    fn field_selector(req: &ListRequest, fields: &[&str]) -> Result<FieldSelector, Status> {
+        FieldSelector::parse(&req.field_selector, fields).map_err(Status::invalid_argument)
+    }
+
+    /// 📑 The requested page of an already filtered listing, and the token for the next.
+    fn page<T>(
+        items: Vec<T>,
+        key: impl Fn(&T) -> String,
+        req: &ListRequest,
+    ) -> Result<(Vec<T>, String), Status> {
+        paging::paginate(items, key, req.page_size, &req.page_token)
+            .map_err(Status::invalid_argument)
+    }
+
+    /// Converts a stored rule back into its wire form (ListFirewallRules, events).
+    fn firewall_rule_message(rule: FirewallRuleRecord) -> FirewallPolicy {
+        use kari_agent::firewall_policy::{Action, Protocol as ProtoProtocol};
+
+        FirewallPolicy {
+            action: match rule.action.as_str() {
+                "deny" => Action::Deny,
+                "reject" => Action::Reject,
+                _ => Action::Allow,
+            } as i32,
+            port: u32::from(rule.port),
+            protocol: match rule.protocol.as_str() {
+                "udp" => ProtoProtocol::Udp,
+                "both" => ProtoProtocol::Both,
+                _ => ProtoProtocol::Tcp,
+            } as i32,
+            source_ip: rule.source_ip,
+            labels: rule.labels.into_iter().collect(),
+            description: rule.description.unwrap_or_default(),
+            created_by: rule.created_by.unwrap_or_default(),
+            created_at: rule.created_at,
+            log_denied: rule.log_denied,
+            expires_at: rule.expires_at.unwrap_or(0),
+        }
+    }
+
+    /// Appends one self-test phase to the report; returns whether it passed.
+    fn record_phase(
+        phases: &mut Vec<SelfTestPhase>,
+        name: &str,
+        started: Instant,
+        result: Result<String, String>,
+    ) -> bool {
+        let success = result.is_ok();
+        phases.push(SelfTestPhase {
+            name: name.to_string(),
+            success,
+            detail: result.unwrap_or_else(|e| e),
+            duration_ms: started.elapsed().as_millis() as u64,
+        });
+        success
+    }
+
+    /// 🩺 Provisions a throwaway hello-world app through the same managers a real
+    /// app uses, probes it through the local ingress, then always tears it down.
+    async fn self_test(&self, with_certificate: bool) -> SelfTestReport {
+        let suffix = selftest::run_suffix();
+        let app_user = format!("kari-app-selftest-{}", suffix);
+        let service_name = format!("kari-selftest-{}", suffix);
+        let domain = format!("selftest-{}.kari.invalid", suffix);
+        let token = format!("kari-selftest-ok-{}", suffix);
+        let app_dir = self.config.web_root.join(&domain);
+
+        let mut phases = Vec::new();
+        let (mut unit_started, mut vhost_started, mut cert_started) = (false, false, false);
+
+        // Phase 1: Unprivileged user (always attempted, so always undone)
+        let t = Instant::now();
+        let res = self
+            .jail_mgr
+            .provision_app_user(&app_user, 0)
+            .await
+            .map(|_| app_user.clone());
+        let mut ok = Self::record_phase(&mut phases, "user", t, res);
+
+        // Phase 2: Jailed directory with a page only this run can serve
+        if ok {
+            let t = Instant::now();
+            let res = async {
+                self.jail_mgr.secure_directory(&app_dir, &app_user).await?;
+                let page = app_dir.join("index.html");
+                tokio::fs::write(&page, format!("<h1>{}</h1>\n", token))
+                    .await
+                    .map_err(|e| format!("Failed to write test page: {}", e))?;
+                tokio::fs::set_permissions(&page, std::fs::Permissions::from_mode(0o644))
+                    .await
+                    .map_err(|e| format!("Failed to set test page mode: {}", e))?;
+                Ok(app_dir.display().to_string())
+            }
+            .await;
+            ok = Self::record_phase(&mut phases, "directory", t, res);
+        }
+
+        // Phase 3: Unit on a free loopback port
+        let mut port = 0;
+        if ok {
+            let t = Instant::now();
+            unit_started = true;
+            let res = async {
+                port = selftest::free_loopback_port()?;
+                let config = ServiceConfig {
+                    service_name: service_name.clone(),
+                    username: app_user.clone(),
+                    working_directory: app_dir.clone(),
+                    start_argv: selftest::hello_world_argv(port, &app_dir),
+                    env_vars: HashMap::new(),
+                    memory_limit_mb: 64,
+                    cpu_limit_percent: 50,
+                    image: None,
+                    port: None,
+                    slice: None,
+                    dependencies: Vec::new(),
+                    start_limit: self.start_limit(),
+                };
+                self.svc_mgr.write_unit_file(&config).await?;
+                self.svc_mgr.reload_daemon().await?;
+                self.svc_mgr.enable_and_start(&service_name).await?;
+                Ok(format!("{} on 127.0.0.1:{}", service_name, port))
+            }
+            .await;
+            ok = Self::record_phase(&mut phases, "unit", t, res);
+        }
+
+        // Phase 4: Ingress
+        if ok {
+            let t = Instant::now();
+            vhost_started = true;
+            let res = self
+                .proxy_mgr
+                .create_vhost(&domain, port, &VhostOptions::default())
+                .await
+                .map(|_| domain.clone());
+            ok = Self::record_phase(&mut phases, "vhost", t, res);
+        }
+
+        // Phase 5 (optional): Certificate installation
+        if ok && with_certificate {
+            let t = Instant::now();
+            cert_started = true;
+            let res = async {
+                let (fullchain_pem, privkey_pem) =
+                    selftest::self_signed_certificate(&domain).await?;
+                self.ssl_engine
+                    .install_certificate(TraitSslPayload {
+                        domain_name: domain.clone(),
+                        fullchain_pem,
+                        privkey_pem,
+                    })
+                    .await?;
+                Ok("self-signed certificate installed".to_string())
+            }
+            .await;
+            ok = Self::record_phase(&mut phases, "certificate", t, res);
+        }
+
+        // Phase 6: Real traffic through the local ingress
+        if ok {
+            let t = Instant::now();
+            let res = selftest::probe_http(
+                &self.config.selftest_probe_addr,
+                &domain,
+                &token,
+                Duration::from_secs(self.config.selftest_timeout_secs),
+            )
+            .await
+            .map(|_| format!("served via {}", self.config.selftest_probe_addr));
+            Self::record_phase(&mut phases, "traffic", t, res);
+        }
+
+        // Teardown: undo every phase that started, whatever happened above
+        let t = Instant::now();
+        let mut errors = Vec::new();
+        if vhost_started && let Err(e) = self.proxy_mgr.remove_vhost(&domain).await {
+            errors.push(format!("vhost: {}", e));
+        }
+        if unit_started {
+            let _ = self.svc_mgr.stop(&service_name).await;
+            if let Err(e) = self.svc_mgr.remove_unit_file(&service_name).await {
+                errors.push(format!("unit: {}", e));
+            }
+            let _ = self.svc_mgr.reload_daemon().await;
+        }
+        if let Err(e) = self.jail_mgr.deprovision_app_user(&app_user).await {
+            errors.push(format!("user: {}", e));
+        }
+        let mut dirs = vec![app_dir];
+        if cert_started {
+            dirs.push(self.config.ssl_storage_dir.join(&domain));
+        }
+        for dir in dirs {
+            match tokio::fs::remove_dir_all(&dir).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    errors.push(format!("{}: {}", dir.display(), e))
+                }
+                _ => {}
+            }
+        }
+        let res = if errors.is_empty() {
+            Ok("all test resources removed".to_string())
+        } else {
+            Err(errors.join("; "))
+        };
+        Self::record_phase(&mut phases, "teardown", t, res);
+
+        SelfTestReport {
+            success: phases.iter().all(|p| p.success),
+            phases,
+        }
+    }
+
+    /// 🔗 Maps requested dependencies to unit names. App targets must already be
+    /// provisioned, and none may lead back to `service_name` (systemd would
+    /// break such a cycle by dropping a job at boot, starting apps in any order).
+    async fn resolve_dependencies(
+        &self,
+        service_name: &str,
+        deps: &[kari_agent::ServiceDependency],
+    ) -> Result<Vec<UnitDependency>, Status> {
+        use kari_agent::service_dependency::Kind;
+
+        if deps.is_empty() {
+            return Ok(Vec::new());
+        }
+        let apps: Vec<AppRecord> = list_records(self.state_store.as_ref(), NS_APPS)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        let unit_of = |app: &AppRecord| format!("{}.service", app.service_name);
+
+        let mut resolved = Vec::with_capacity(deps.len());
+        for dep in deps {
+            let unit = if dep.app_id.is_empty() {
+                dep.unit.clone()
+            } else {
+                let app = apps
+                    .iter()
+                    .find(|a| a.app_id == dep.app_id)
+                    .ok_or_else(|| {
+                        Status::failed_precondition(format!(
+                            "Dependency app '{}' is not provisioned",
+                            dep.app_id
+                        ))
+                    })?;
+                unit_of(app)
+            };
+            let kind = match Kind::try_from(dep.kind) {
+                Ok(Kind::BindsTo) => DependencyKind::BindsTo,
+                _ => DependencyKind::Requires,
+            };
+            if !resolved.iter().any(|d: &UnitDependency| d.unit == unit) {
+                resolved.push(UnitDependency { unit, kind });
+            }
+        }
+
+        // Walk the existing app graph from each dependency looking for ourselves
+        let own_unit = format!("{}.service", service_name);
+        let mut pending: Vec<&str> = resolved.iter().map(|d| d.unit.as_str()).collect();
+        let mut seen = std::collections::HashSet::new();
+        while let Some(unit) = pending.pop() {
+            if unit == own_unit {
+                return Err(Status::failed_precondition(format!(
+                    "Dependency cycle: {} would depend on itself",
+                    service_name
+                )));
+            }
+            if !seen.insert(unit) {
+                continue;
+            }
+            if let Some(app) = apps.iter().find(|a| unit_of(a) == unit) {
+                pending.extend(app.dependencies.iter().map(|d| d.unit.as_str()));
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// Steps shared by the synchronous and long-running provisioning paths.
+    async fn provision_jail(
+        &self,
+        mut req: ProvisionJailRequest,
+        op_id: Option<&str>,
+    ) -> Result<AgentResponse, Status> {
+        use kari_agent::Runtime;
+
+        // 🛡️ Zero-Trust Input Validation
+        validate_identifier(&req.app_id, "app_id")?;
+        validate_domain_name(&req.domain_name)?;
+        let labels = validate_labels(req.labels.clone())?;
+        let tenant_id = validate_tenant_id(&req.tenant_id)?;
+
+        let runtime = Runtime::try_from(req.runtime)
+            .map_err(|_| Status::invalid_argument("Invalid runtime"))?;
+        let port = match req.port {
+            Some(p) => Some(
+                u16::try_from(p)
+                    .ok()
+                    .filter(|p| *p != 0)
+                    .ok_or_else(|| Status::invalid_argument("Zero-Trust: port must be 1-65535"))?,
+            ),
+            None => None,
+        };
+        let (unit_writer, image) = match runtime {
+            Runtime::Source => (Arc::clone(&self.svc_mgr), None),
+            Runtime::Container => {
+                if port.is_none() {
+                    return Err(Status::invalid_argument(
+                        "Container runtime requires a port to publish",
+                    ));
+                }
+                (Arc::clone(&self.container_svc_mgr), Some(req.image.clone()))
+            }
+        };
+
+        let app_user = format!("kari-app-{}", req.app_id);
+        let app_dir = Self::secure_join(&self.config.web_root, &req.domain_name)?;
+        let service_name = format!("kari-{}", req.domain_name);
+        let holder = app_unit_holder(self.state_store.as_ref(), &service_name)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        if let Some(holder) = holder {
+            return Err(Status::already_exists(format!(
+                "Conflict: unit {}.service already belongs to {}",
+                service_name, holder
+            )));
+        }
+        let dependencies = self
+            .resolve_dependencies(&service_name, &req.depends_on)
+            .await?;
+
+        // Step 0: Fail fast if the host cannot hold this jail
+        let committed = capacity::committed_resources(self.state_store.as_ref(), Some(&req.app_id))
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        let requested = capacity::Reservations {
+            memory_mb: req.memory_limit_mb as u64,
+            cpu_percent: 100, // Matches the unit's CPUQuota below
+        };
+        capacity::check_reservation(requested, committed, self.config.overcommit_ratio)
+            .and_then(|_| capacity::check_disk(&self.config.web_root, self.config.min_free_disk_mb))
+            .map_err(Self::capacity_error)?;
+        self.resolve_secret_refs(&mut req.env_vars).await?;
+
+        self.report_progress(op_id, 5, "Provisioning app user")
+            .await;
 
         // Step 1: Provision the unprivileged OS user
         self.jail_mgr
             .provision_app_user(&app_user, 0) // UID auto-assigned by useradd
             .await
-            .map_err(|e| {
-                Status::internal(format!("[SLA ERROR] User provisioning failed: {}", e))
+            .map_err(|e| {
+                Status::internal(format!("[SLA ERROR] User provisioning failed: {}", e))
+            })?;
+
+        self.report_progress(op_id, 20, "Securing app directory")
+            .await;
+
+        // Step 2: Create and secure the application directory
+        self.jail_mgr
+            .secure_directory(&app_dir, &app_user)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("[SLA ERROR] Directory jailing failed: {}", e))
+            })?;
+
+        // Step 2b: Rotate the log files the app writes under logs/
+        let log_dir = app_dir.join("logs");
+        self.jail_mgr
+            .secure_directory(&log_dir, &app_user)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("[SLA ERROR] Directory jailing failed: {}", e))
+            })?;
+        self.log_mgr
+            .configure_logrotate(&req.domain_name, &app_user, &log_dir)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("[SLA ERROR] Log rotation setup failed: {}", e))
+            })?;
+
+        self.report_progress(op_id, 35, "Writing service unit")
+            .await;
+
+        // Step 3: Write systemd unit file with cgroup v2 resource limits, inside the
+        // tenant's quota slice when the app belongs to one
+        let slice = match tenant_id {
+            Some(ref id) => Some(self.ensure_tenant_slice(id).await?),
+            None => None,
+        };
+        let svc_config = ServiceConfig {
+            service_name: service_name.clone(),
+            username: app_user.clone(),
+            working_directory: app_dir.clone(),
+            start_argv: req.start_argv.clone(),
+            env_vars: req.env_vars.clone(),
+            memory_limit_mb: req.memory_limit_mb as i32,
+            cpu_limit_percent: 100, // Default: full single core
+            image,
+            port,
+            slice,
+            dependencies,
+            start_limit: self.start_limit(),
+        };
+
+        unit_writer
+            .write_unit_file(&svc_config)
+            .await
+            .map_err(|e| Self::unit_error("Unit file creation failed", e))?;
+
+        // Step 3b: Confine source apps with the host's MAC framework. Podman
+        // confines its containers itself.
+        let mac_target = MacTarget {
+            app_user: app_user.clone(),
+            service_name: service_name.clone(),
+            app_dir: app_dir.clone(),
+        };
+        match runtime {
+            Runtime::Source => self.mac_mgr.confine(&mac_target).await,
+            Runtime::Container => self.mac_mgr.release(&mac_target).await,
+        }
+        .map_err(|e| Status::internal(format!("[SLA ERROR] MAC confinement failed: {}", e)))?;
+
+        self.report_progress(op_id, 50, "Activating service").await;
+
+        // Step 4: Reload systemd and enable the service
+        self.svc_mgr
+            .reload_daemon()
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Daemon reload failed: {}", e)))?;
+
+        // Container images are pulled up-front so private-registry auth is only
+        // ever present for the duration of this one pull.
+        if let Some(ref image) = svc_config.image {
+            let auth = self
+                .secret_store
+                .get_secret(&Self::registry_auth_secret(&req.app_id))
+                .await
+                .map_err(|e| {
+                    Status::internal(format!("[SLA ERROR] Secret lookup failed: {}", e))
+                })?;
+            self.image_mgr
+                .pull_image(image, &app_user, &app_dir, auth)
+                .await
+                .map_err(|e| Status::internal(format!("[SLA ERROR] Image pull failed: {}", e)))?;
+        }
+
+        if let Some(port) = svc_config.port
+            && let Some(conflict) = self.port_conflict(port, &service_name).await
+        {
+            return Err(Status::failed_precondition(conflict));
+        }
+        self.svc_mgr
+            .enable_and_start(&service_name)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("[SLA ERROR] Service activation failed: {}", e))
+            })?;
+
+        self.report_progress(op_id, 80, "Wiring ingress").await;
+
+        let previous: Option<AppRecord> =
+            get_record(self.state_store.as_ref(), NS_APPS, &req.app_id)
+                .await
+                .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        validate_real_ip(&req.trusted_proxy_cidrs, &req.real_ip_header)?;
+        let vhost = VhostOptions {
+            rate_limit: req.rate_limit.as_ref().map(|limit| RateLimit {
+                requests_per_second: limit.requests_per_second,
+                burst: limit.burst,
+                max_connections: limit.max_connections,
+            }),
+            real_ip: (!req.trusted_proxy_cidrs.is_empty()).then(|| RealIp {
+                header: match req.real_ip_header.as_str() {
+                    "" => "X-Forwarded-For".to_string(),
+                    header => header.to_string(),
+                },
+                trusted_proxies: req.trusted_proxy_cidrs.clone(),
+            }),
+            // 🔌 Only ResetCircuitBreaker closes an open breaker
+            maintenance: previous.as_ref().is_some_and(|p| p.vhost.maintenance),
+            // The certificate outlives re-provisioning (see certificate_installed below)
+            tls: previous
+                .as_ref()
+                .filter(|p| p.domain_name == req.domain_name)
+                .and_then(|p| p.vhost.tls.clone()),
+            // ↪️ Redirects are the domain's, set by SetRedirects alone
+            redirects: previous
+                .as_ref()
+                .filter(|p| p.domain_name == req.domain_name)
+                .map(|p| p.vhost.redirects.clone())
+                .unwrap_or_default(),
+            client_limits: Self::client_limits(req.client_limits.as_ref()),
+            request_tracing: req.request_tracing,
+        };
+        let covered_tls = match vhost.tls {
+            Some(_) => None,
+            None => self.covering_tls(&req.domain_name).await?,
+        };
+        let certificate_installed = covered_tls.is_some()
+            || previous
+                .as_ref()
+                .is_some_and(|p| p.certificate_installed && p.domain_name == req.domain_name);
+        let vhost = VhostOptions {
+            tls: vhost.tls.or(covered_tls),
+            ..vhost
+        };
+
+        // Step 5: Container apps have no build step, so ingress is wired here
+        // exactly as stream_deployment does for source releases. A source app
+        // that is already deployed keeps its port, and its vhost is rewritten
+        // so changed ingress options apply without a redeploy.
+        let port = match runtime {
+            Runtime::Container => port,
+            Runtime::Source => previous
+                .as_ref()
+                .filter(|p| p.domain_name == req.domain_name)
+                .and_then(|p| p.port),
+        };
+        if let Some(port) = port {
+            self.proxy_mgr
+                .create_vhost(&req.domain_name, port, &vhost)
+                .await
+                .map_err(|e| Status::internal(format!("[SLA ERROR] Proxy wiring failed: {}", e)))?;
+        }
+
+        self.report_progress(op_id, 95, "Recording app state").await;
+
+        // Step 6: Record what was provisioned (drives image GC and reconciliation).
+        // A certificate outlives re-provisioning, since its files are untouched.
+        let record = AppRecord {
+            app_id: req.app_id.clone(),
+            domain_name: req.domain_name.clone(),
+            service_name: service_name.clone(),
+            app_user: app_user.clone(),
+            runtime: match runtime {
+                Runtime::Source => "source",
+                Runtime::Container => "container",
+            }
+            .to_string(),
+            image: svc_config.image.clone(),
+            port,
+            memory_limit_mb: req.memory_limit_mb,
+            cpu_limit_percent: svc_config.cpu_limit_percent as u32,
+            labels,
+            tenant_id,
+            offline_build: req.offline_build,
+            dependencies: svc_config.dependencies.clone(),
+            certificate_installed,
+            vhost,
+        };
+        put_record(self.state_store.as_ref(), NS_APPS, &req.app_id, &record)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State update failed: {}", e)))?;
+
+        // 🛡️ Privacy: Clear the transient env variables from RAM
+        let mut transient_req = req;
+        for (_, mut val) in transient_req.env_vars.drain() {
+            val.zeroize();
+        }
+
+        info!(
+            "🔒 Jail provisioned: {} (user: {}, mem: {}MB)",
+            service_name, app_user, transient_req.memory_limit_mb
+        );
+
+        Ok(AgentResponse {
+            success: true,
+            exit_code: 0,
+            stdout: format!(
+                "Jail '{}' provisioned with {}MB memory limit",
+                service_name, transient_req.memory_limit_mb
+            ),
+            stderr: String::new(),
+            error_message: String::new(),
+            operation_id: String::new(),
+        })
+    }
+
+    /// Teardown steps shared by the synchronous and long-running paths.
+    async fn delete_app(
+        &self,
+        req: DeleteRequest,
+        op_id: Option<&str>,
+    ) -> Result<AgentResponse, Status> {
+        // 🛡️ Zero-Trust: Validate inputs
+        validate_identifier(&req.app_id, "app_id")?;
+        validate_domain_name(&req.domain_name)?;
+
+        let app_dir = Self::secure_join(&self.config.web_root, &req.domain_name)?;
+        let app_user = format!("kari-app-{}", req.app_id);
+        let service_name = format!("kari-{}", req.domain_name);
+
+        self.report_progress(op_id, 10, "Stopping service").await;
+
+        // 🛡️ Deterministic Cleanup Order: Service → Proxy → User → Files
+        let _ = self.svc_mgr.stop(&service_name).await;
+        let _ = self.svc_mgr.remove_unit_file(&service_name).await;
+        let _ = self.proxy_mgr.remove_vhost(&req.domain_name).await;
+        let _ = self.log_mgr.remove_logrotate(&req.domain_name).await;
+        // 📂 Before the user goes: its sessions end and the bind mount is released
+        if let Err(e) = self.sftp_mgr.revoke(&app_user).await {
+            warn!("📂 SFTP access for {} not revoked: {}", app_user, e);
+        }
+        let _ = self.jail_mgr.deprovision_app_user(&app_user).await;
+        let _ = self
+            .mac_mgr
+            .release(&MacTarget {
+                app_user: app_user.clone(),
+                service_name: service_name.clone(),
+                app_dir: app_dir.clone(),
+            })
+            .await;
+        let _ = self
+            .secret_store
+            .delete_secret(&Self::registry_auth_secret(&req.app_id))
+            .await;
+        let _ = app_cron::remove_app_jobs(
+            self.job_scheduler.as_ref(),
+            self.state_store.as_ref(),
+            &req.app_id,
+        )
+        .await;
+        let _ = self.state_store.delete(NS_APPS, &req.app_id).await;
+        // 🌐 A certificate other domains still use stays installed
+        if let Err(e) = certificates::release(
+            self.state_store.as_ref(),
+            &self.config.ssl_storage_dir,
+            &req.domain_name,
+        )
+        .await
+        {
+            warn!("🔐 Certificate for {} not released: {}", req.domain_name, e);
+        }
+        if let Ok(releases) =
+            list_records::<ReleaseRecord>(self.state_store.as_ref(), NS_RELEASES).await
+        {
+            for release in releases.iter().filter(|r| r.domain_name == req.domain_name) {
+                let key = release_key(&release.domain_name, &release.release);
+                let _ = self.state_store.delete(NS_RELEASES, &key).await;
+            }
+        }
+        let _ =
+            tokio::fs::remove_dir_all(self.config.state_dir.join("sboms").join(&req.domain_name))
+                .await;
+
+        self.report_progress(op_id, 60, "Purging app files").await;
+
+        if app_dir.exists() {
+            tokio::fs::remove_dir_all(&app_dir).await.map_err(|e| {
+                Status::internal(format!(
+                    "[SLA ERROR] Filesystem purge failed for {}: {}",
+                    req.domain_name, e
+                ))
+            })?;
+        }
+
+        info!(
+            "🔥 Deployment torn down: {} (user: {})",
+            service_name, app_user
+        );
+
+        Ok(AgentResponse {
+            success: true,
+            ..Default::default()
+        })
+    }
+
+    /// Unpacks an app bundle and re-provisions it on this host.
+    async fn import_app_bundle(
+        &self,
+        bundle_path: std::path::PathBuf,
+        key: BundleKey,
+        op_id: Option<&str>,
+    ) -> Result<AgentResponse, Status> {
+        let state_err =
+            |e: String| Status::internal(format!("[SLA ERROR] State read failed: {}", e));
+        let existing: Vec<AppRecord> = list_records(self.state_store.as_ref(), NS_APPS)
+            .await
+            .map_err(state_err)?;
+        let web_root = self.config.web_root.clone();
+
+        self.report_progress(op_id, 5, "Verifying bundle").await;
+
+        // 🛡️ Zero-Trust: The spec is vetted before a single file is written. Derived
+        // names are recomputed, never trusted from the bundle.
+        let imported = migration::unpack_app(&self.migration_paths(), &key, &bundle_path, |spec| {
+            validate_identifier(&spec.app_id, "app_id")
+                .and_then(|_| validate_domain_name(&spec.domain_name))
+                .map_err(|s| s.message().to_string())?;
+            if spec.app_user != format!("kari-app-{}", spec.app_id)
+                || spec.service_name != format!("kari-{}", spec.domain_name)
+                || !matches!(spec.runtime.as_str(), "source" | "container")
+            {
+                return Err("SECURITY VIOLATION: App spec does not match kari naming".into());
+            }
+            if existing
+                .iter()
+                .any(|a| a.app_id == spec.app_id || a.domain_name == spec.domain_name)
+                || web_root.join(&spec.domain_name).exists()
+            {
+                return Err(format!(
+                    "App {} ({}) already exists on this host",
+                    spec.app_id, spec.domain_name
+                ));
+            }
+            Ok(())
+        })
+        .map_err(|e| Status::internal(format!("[SLA ERROR] App import failed: {}", e)))?;
+
+        let mut app = imported.record;
+        // The certificate came along, but under this host's directory and policy
+        if app.vhost.tls.is_some() {
+            app.vhost.tls = Some(self.vhost_tls(&app.domain_name));
+        }
+        let app_dir = Self::secure_join(&self.config.web_root, &app.domain_name)?;
+
+        self.report_progress(op_id, 40, "Provisioning app user")
+            .await;
+        self.jail_mgr
+            .provision_app_user(&app.app_user, 0)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("[SLA ERROR] User provisioning failed: {}", e))
+            })?;
+        self.jail_mgr
+            .secure_directory(&app_dir, &app.app_user)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("[SLA ERROR] Directory jailing failed: {}", e))
+            })?;
+
+        if let Some(auth) = imported.registry_auth {
+            self.secret_store
+                .put_secret(&Self::registry_auth_secret(&app.app_id), auth)
+                .await
+                .map_err(|e| {
+                    Status::internal(format!("[SLA ERROR] Secret storage failed: {}", e))
+                })?;
+        }
+
+        let state_err =
+            |e: String| Status::internal(format!("[SLA ERROR] State update failed: {}", e));
+        put_record(self.state_store.as_ref(), NS_APPS, &app.app_id, &app)
+            .await
+            .map_err(state_err)?;
+        put_record(
+            self.state_store.as_ref(),
+            NS_APP_JOBS,
+            &app.app_id,
+            &imported.job_names,
+        )
+        .await
+        .map_err(state_err)?;
+
+        // The restored unit names its tenant slice; make sure that slice exists here
+        if let Some(ref tenant_id) = app.tenant_id {
+            validate_tenant_id(tenant_id)?;
+            self.ensure_tenant_slice(tenant_id).await?;
+        }
+
+        // MAC profiles are this host's, never part of the bundle
+        if app.runtime == "source" {
+            self.mac_mgr
+                .confine(&MacTarget {
+                    app_user: app.app_user.clone(),
+                    service_name: app.service_name.clone(),
+                    app_dir: app_dir.clone(),
+                })
+                .await
+                .map_err(|e| {
+                    Status::internal(format!("[SLA ERROR] MAC confinement failed: {}", e))
+                })?;
+        }
+
+        self.report_progress(op_id, 60, "Activating service").await;
+        self.svc_mgr
+            .reload_daemon()
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Daemon reload failed: {}", e)))?;
+
+        if let Some(ref image) = app.image {
+            let auth = self
+                .secret_store
+                .get_secret(&Self::registry_auth_secret(&app.app_id))
+                .await
+                .map_err(|e| {
+                    Status::internal(format!("[SLA ERROR] Secret lookup failed: {}", e))
+                })?;
+            self.image_mgr
+                .pull_image(image, &app.app_user, &app_dir, auth)
+                .await
+                .map_err(|e| Status::internal(format!("[SLA ERROR] Image pull failed: {}", e)))?;
+        }
+
+        if let Some(port) = app.port
+            && let Some(conflict) = self.port_conflict(port, &app.service_name).await
+        {
+            return Err(Status::failed_precondition(conflict));
+        }
+        self.svc_mgr
+            .enable_and_start(&app.service_name)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("[SLA ERROR] Service activation failed: {}", e))
+            })?;
+        for job in &imported.job_names {
+            self.svc_mgr
+                .enable_and_start(&format!("kari-job-{}.timer", job))
+                .await
+                .map_err(|e| {
+                    Status::internal(format!("[SLA ERROR] Timer activation failed: {}", e))
+                })?;
+        }
+
+        if let Some(port) = app.port {
+            self.report_progress(op_id, 85, "Wiring ingress").await;
+            self.proxy_mgr
+                .create_vhost(&app.domain_name, port, &app.vhost)
+                .await
+                .map_err(|e| Status::internal(format!("[SLA ERROR] Proxy wiring failed: {}", e)))?;
+        }
+
+        info!(
+            "🚚 App imported: {} ({} bundle entries)",
+            app.domain_name, imported.entries
+        );
+
+        Ok(AgentResponse {
+            success: true,
+            stdout: format!(
+                "Imported {} ({} bundle entries)",
+                app.domain_name, imported.entries
+            ),
+            ..Default::default()
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl SystemAgent for KariAgentService {
+    type StreamDeploymentStream = ReceiverStream<Result<LogChunk, Status>>;
+    type PrebuildReleaseStream = ReceiverStream<Result<LogChunk, Status>>;
+    type ActivateReleaseStream = ReceiverStream<Result<LogChunk, Status>>;
+    type RollbackDeploymentStream = ReceiverStream<Result<LogChunk, Status>>;
+    type StreamFirewallEventsStream = ReceiverStream<Result<FirewallEventSummary, Status>>;
+    type StreamTamperEventsStream = ReceiverStream<Result<TamperEvent, Status>>;
+    type StreamCircuitEventsStream = ReceiverStream<Result<CircuitEvent, Status>>;
+    type PullArtifactStream = ReceiverStream<Result<ArtifactChunk, Status>>;
+    type StreamKeyRotationEventsStream = ReceiverStream<Result<KeyRotationEvent, Status>>;
+    type StreamDiskHealthEventsStream = ReceiverStream<Result<DiskHealthEvent, Status>>;
+    type DeployArtifactStream = ReceiverStream<Result<LogChunk, Status>>;
+    type StreamServiceLogsStream = ReceiverStream<Result<ServiceLogEntry, Status>>;
+
+    // =========================================================================
+    // 1. 🛡️ SLA: System Health Telemetry
+    // =========================================================================
+    async fn get_system_status(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<SystemStatus>, Status> {
+        // Reservations come from the state store, so read them before taking the lock
+        let committed = capacity::committed_resources(self.state_store.as_ref(), None)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        let physical = capacity::physical_resources();
+        let kernel = match self.reboot_mgr.kernel_state().await {
+            Ok(state) => reboot::advise(&state),
+            Err(e) => {
+                warn!("🔁 Kernel state unavailable: {}", e);
+                Default::default()
+            }
+        };
+
+        // ⚡ Performance: Reuse System instance; the scan blocks, so keep it off the runtime
+        let monitor = Arc::clone(&self.system_monitor);
+        let (cpu_usage, memory_usage_mb, active_jails) = tokio::task::spawn_blocking(move || {
+            let mut sys = monitor.lock().unwrap();
+            sys.refresh_all();
+
+            // 🛡️ SLA: Calculate metrics from kernel-level sources
+            let cpu_usage = sys.global_cpu_info().cpu_usage();
+            let used_memory = sys.used_memory() as f64;
+            let memory_usage_mb = (used_memory / 1_048_576.0) as f32;
+
+            // Active jails: count systemd services matching our naming convention
+            let active_jails = sys
+                .processes()
+                .values()
+                .filter(|p| p.name().starts_with("kari-"))
+                .count() as u32;
+            (cpu_usage, memory_usage_mb, active_jails)
+        })
+        .await
+        .map_err(|e| Status::internal(format!("[SLA ERROR] System scan panicked: {}", e)))?;
+
+        let uptime = System::uptime();
+
+        let disks: Vec<DiskHealth> = self
+            .disk_status
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|disk| {
+                let smart = disk.smart.clone().unwrap_or_default();
+                DiskHealth {
+                    device: disk.device.clone(),
+                    smart_available: disk.smart.is_some(),
+                    model: smart.model,
+                    smart_passed: smart.passed,
+                    reallocated_sectors: smart.reallocated_sectors,
+                    pending_sectors: smart.pending_sectors,
+                    uncorrectable_sectors: smart.uncorrectable_sectors,
+                    media_errors: smart.media_errors,
+                    percent_used: smart.percent_used,
+                    temperature_celsius: smart.temperature_celsius,
+                    io_errors: disk.io_errors,
+                    last_error: disk.last_error.clone(),
+                    problems: disk.problems.clone(),
+                }
+            })
+            .collect();
+
+        Ok(Response::new(SystemStatus {
+            healthy: disks.iter().all(|disk| disk.problems.is_empty()),
+            active_jails,
+            cpu_usage_percent: cpu_usage,
+            memory_usage_mb,
+            agent_version: env!("CARGO_PKG_VERSION").to_string(),
+            uptime_seconds: uptime,
+            memory_committed_mb: committed.memory_mb,
+            memory_physical_mb: physical.memory_mb,
+            cpu_committed_percent: committed.cpu_percent,
+            cpu_physical_percent: physical.cpu_percent,
+            disks,
+            running_kernel: kernel.running,
+            installed_kernel: kernel.latest_installed,
+            needs_reboot: kernel.needs_reboot,
+            security_patch_pending: kernel.security_patch_pending,
+            livepatches: kernel.livepatches,
+        }))
+    }
+
+    // =========================================================================
+    // 2. 📦 Package Management (Hardened)
+    // =========================================================================
+    async fn execute_package_command(
+        &self,
+        request: Request<PackageRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let deadline = Self::request_deadline(request.metadata());
+        let caller = Caller::of(&request);
+        let req = request.into_inner();
+
+        // 🛡️ Zero-Trust: Only an intent is accepted; the argv is built here
+        #[allow(deprecated)]
+        if !req.command.is_empty() || !req.args.is_empty() {
+            return Err(Status::permission_denied(
+                "Zero-Trust: raw package commands are no longer accepted",
+            ));
+        }
+        let intent = req
+            .intent
+            .ok_or_else(|| Status::invalid_argument("Zero-Trust: intent is required"))?;
+        let action = validate_package_intent(&intent)?;
+        let kind = packages::detect().ok_or_else(|| {
+            Status::failed_precondition("No supported package manager found on this host")
+        })?;
+        let argv = packages::build_argv(kind, action, &intent.packages)
+            .map_err(Status::invalid_argument)?;
+
+        let cap = Duration::from_secs(self.config.package_timeout_secs);
+
+        // Upgrades routinely outlive any sensible RPC deadline: hand back an
+        // operation id and let the caller poll GetOperation instead.
+        if req.run_async {
+            return self
+                .spawn_operation(&caller, "package_command", move |this, _| async move {
+                    this.run_host_command(&argv[0], &argv[1..], cap).await
+                })
+                .await
+                .map(Response::new);
+        }
+
+        let limit = deadline.map_or(cap, |d| d.min(cap));
+        self.run_host_command(&argv[0], &argv[1..], limit)
+            .await
+            .map(Response::new)
+    }
+
+    // =========================================================================
+    // 3. 🔒 Application Jail Provisioning (cgroup v2 + systemd-run)
+    // =========================================================================
+    async fn provision_app_jail(
+        &self,
+        request: Request<ProvisionJailRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let deadline = Self::request_deadline(request.metadata());
+        let caller = Caller::of(&request);
+        let req = request.into_inner();
+
+        if req.run_async {
+            // Reject malformed input now rather than as a failed operation
+            validate_identifier(&req.app_id, "app_id")?;
+            validate_domain_name(&req.domain_name)?;
+            validate_labels(req.labels.clone())?;
+            validate_tenant_id(&req.tenant_id)?;
+
+            return self
+                .spawn_operation(&caller, "provision_app_jail", |this, op_id| async move {
+                    this.provision_jail(req, Some(&op_id)).await
+                })
+                .await
+                .map(Response::new);
+        }
+
+        Self::with_deadline(deadline, "Provisioning", self.provision_jail(req, None))
+            .await
+            .map(Response::new)
+    }
+
+    // =========================================================================
+    // 4. ⚙️ Service Management (systemd lifecycle)
+    // =========================================================================
+    async fn manage_service(
+        &self,
+        request: Request<ServiceRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        use kari_agent::ServiceAction;
+
+        let req = request.into_inner();
+        validate_identifier(&req.service_name, "service_name")?;
+
+        // 🛡️ Zero-Trust: Only allow management of kari-prefixed services
+        if !req.service_name.starts_with("kari-") {
+            return Err(Status::permission_denied(
+                "Zero-Trust: Refusing to manage non-Kari service",
+            ));
+        }
+
+        let action = ServiceAction::try_from(req.action)
+            .map_err(|_| Status::invalid_argument("Invalid service action"))?;
+
+        let result = run_service_action(self.svc_mgr.as_ref(), &req.service_name, action).await;
+
+        match result {
+            Ok(()) => {
+                info!(
+                    "⚙️ Service {} action {:?} succeeded",
+                    req.service_name, action
+                );
+                Ok(Response::new(AgentResponse {
+                    success: true,
+                    exit_code: 0,
+                    stdout: format!("Service '{}' action completed", req.service_name),
+                    stderr: String::new(),
+                    error_message: String::new(),
+                    operation_id: String::new(),
+                }))
+            }
+            Err(e) => Ok(Response::new(AgentResponse {
+                success: false,
+                exit_code: 1,
+                stdout: String::new(),
+                stderr: e.clone(),
+                error_message: format!("[SLA ERROR] Service management failed: {}", e),
+                operation_id: String::new(),
+            })),
+        }
+    }
+
+    // =========================================================================
+    // 5. 📡 Streaming Deployment (Hardened Blue-Green)
+    // =========================================================================
+    async fn stream_deployment(
+        &self,
+        request: Request<DeployRequest>,
+    ) -> Result<Response<Self::StreamDeploymentStream>, Status> {
+        let job = self.prepare_deploy(request.into_inner()).await?;
+        self.run_deployment(job).map(Response::new)
+    }
+
+    // =========================================================================
+    // 6. 🔥 Resource Teardown (Clean Hygiene)
+    // =========================================================================
+    async fn delete_deployment(
+        &self,
+        request: Request<DeleteRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let caller = Caller::of(&request);
+        let req = request.into_inner();
+
+        if req.run_async {
+            // Reject malformed input now rather than as a failed operation
+            validate_identifier(&req.app_id, "app_id")?;
+            validate_domain_name(&req.domain_name)?;
+
+            return self
+                .spawn_operation(&caller, "delete_deployment", |this, op_id| async move {
+                    this.delete_app(req, Some(&op_id)).await
+                })
+                .await
+                .map(Response::new);
+        }
+
+        self.delete_app(req, None).await.map(Response::new)
+    }
+
+    // =========================================================================
+    // 6b. 🔥 Teardown Jail (Force-Stop PID Namespace)
+    // =========================================================================
+    async fn teardown_jail(
+        &self,
+        request: Request<TeardownRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let req = request.into_inner();
+
+        // 🛡️ Zero-Trust: Validate input
+        validate_identifier(&req.app_id, "app_id")?;
+
+        let service_name = format!("kari-app-{}", req.app_id);
+
+        // 🛡️ SIGKILL via systemctl stop — this tears down the entire cgroup scope,
+        // killing all child processes in the jail's PID namespace.
+        let stop = CommandSpec::privileged("systemctl").args(["stop", "--no-block", &service_name]);
+        let output = self
+            .executor
+            .run(&stop)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Teardown failed: {}", e)))?;
+
+        if !output.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            // Not fatal — service may already be stopped
+            warn!("⚠️ Teardown warning for {}: {}", service_name, stderr);
+        }
+
+        info!(
+            "🔥 Jail torn down: {} (trace: {})",
+            service_name, req.trace_id
+        );
+
+        Ok(Response::new(AgentResponse {
+            success: true,
+            exit_code: 0,
+            stdout: format!("Jail '{}' teardown initiated", service_name),
+            stderr: String::new(),
+            error_message: String::new(),
+            operation_id: String::new(),
+        }))
+    }
+
+    // =========================================================================
+    // 7. 📝 Filesystem Operations (Zero-Trust Path Validation)
+    // =========================================================================
+    async fn write_system_file(
+        &self,
+        request: Request<FileWriteRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let req = request.into_inner();
+
+        // 🛡️ Zero-Trust: Validate path is within allowed directories
+        let path = std::path::Path::new(&req.absolute_path);
+        let allowed_prefixes = [
+            self.config.web_root.as_path(),
+            self.config.ssl_storage_dir.as_path(),
+            self.config.proxy_conf_dir.as_path(),
+            self.config.systemd_dir.as_path(),
+        ];
+
+        let is_allowed = allowed_prefixes
+            .iter()
+            .any(|prefix| path.starts_with(prefix));
+        if !is_allowed {
+            return Err(Status::permission_denied(format!(
+                "Zero-Trust: Path '{}' is outside all allowed boundaries",
+                req.absolute_path
+            )));
+        }
+
+        // 🛡️ Zero-Trust: Prevent path traversal
+        if req.absolute_path.contains("..") {
+            return Err(Status::invalid_argument(
+                "Zero-Trust: Path traversal detected",
+            ));
+        }
+
+        // Write the content
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(|e| {
+                Status::internal(format!("[SLA ERROR] Directory creation failed: {}", e))
+            })?;
+        }
+
+        // Moved, not borrowed: tokio copies borrowed contents for its blocking write
+        tokio::fs::write(path, req.content)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] File write failed: {}", e)))?;
+
+        // Apply file mode
+        if !req.file_mode.is_empty() {
+            let mode = u32::from_str_radix(&req.file_mode, 8)
+                .map_err(|_| Status::invalid_argument("Invalid octal file mode"))?;
+            let mut perms = tokio::fs::metadata(path)
+                .await
+                .map_err(|e| Status::internal(format!("[SLA ERROR] Metadata read failed: {}", e)))?
+                .permissions();
+            perms.set_mode(mode);
+            tokio::fs::set_permissions(path, perms).await.map_err(|e| {
+                Status::internal(format!("[SLA ERROR] Permission set failed: {}", e))
+            })?;
+        }
+
+        // Apply ownership
+        if !req.owner.is_empty() {
+            let owner_arg = if !req.group.is_empty() {
+                format!("{}:{}", req.owner, req.group)
+            } else {
+                req.owner.clone()
+            };
+
+            let chown =
+                CommandSpec::privileged("chown").args(["-P", &owner_arg, "--", &req.absolute_path]);
+            let output = self
+                .executor
+                .run(&chown)
+                .await
+                .map_err(|e| Status::internal(format!("[SLA ERROR] chown failed: {}", e)))?;
+
+            if !output.success() {
+                return Err(Status::internal(format!(
+                    "[SLA ERROR] Ownership change failed: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                )));
+            }
+        }
+
+        info!(
+            "📝 File written: {} (trace: {})",
+            req.absolute_path, req.trace_id
+        );
+
+        Ok(Response::new(AgentResponse {
+            success: true,
+            exit_code: 0,
+            stdout: format!("File written to {}", req.absolute_path),
+            stderr: String::new(),
+            error_message: String::new(),
+            operation_id: String::new(),
+        }))
+    }
+
+    // =========================================================================
+    // 7. 🔐 SSL Certificate Installation (Privacy-First: Zeroize on Drop)
+    // =========================================================================
+    async fn install_certificate(
+        &self,
+        request: Request<SslPayload>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let req = request.into_inner();
+
+        // 🛡️ Zero-Trust: Validate domain
+        validate_domain_name(&req.domain_name)?;
+
+        // 🛡️ Privacy: The decoded request buffer becomes the credential itself,
+        // so the key exists once in RAM and is overwritten with 0x00 on drop.
+        let privkey_pem = ProviderCredential::from_utf8(req.privkey_pem)
+            .map_err(|e| Status::invalid_argument(format!("privkey_pem is {}", e)))?;
+
+        // Convert protobuf payload to our trait's SslPayload
+        let trait_payload = TraitSslPayload {
+            domain_name: req.domain_name.clone(),
+            fullchain_pem: String::from_utf8(req.fullchain_pem)
+                .map_err(|_| Status::invalid_argument("fullchain_pem is not valid UTF-8"))?,
+            privkey_pem,
+        };
+
+        self.ssl_engine
+            .install_certificate(trait_payload)
+            .await
+            .map_err(|e| {
+                Status::internal(format!(
+                    "[SLA ERROR] Certificate installation failed: {}",
+                    e
+                ))
+            })?;
+
+        info!("🔐 Certificate installed for domain: {}", req.domain_name);
+        let (rotated, covered) = self
+            .certificate_installed(&req.domain_name, &req.san_domains, req.key_rotation_opt_out)
+            .await?;
+
+        Ok(Response::new(AgentResponse {
+            success: true,
+            exit_code: 0,
+            stdout: if rotated {
+                format!("SSL certificate installed for {} with a new key", covered)
+            } else {
+                format!("SSL certificate installed for {}", covered)
+            },
+            stderr: String::new(),
+            error_message: String::new(),
+            operation_id: String::new(),
+        }))
+    }
+    // =========================================================================
+    // 8. 🛡️ Firewall Policy Enforcement
+    // =========================================================================
+    async fn apply_firewall_policy(
+        &self,
+        request: Request<FirewallPolicy>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let caller = Caller::of(&request);
+        let req = request.into_inner();
+        let policy = firewall_intent(&req, &caller)?;
+        let labels = validate_labels(req.labels)?;
+
+        // Lapsed debug port exposures go first, as in ExposeDebugPort
+        firewall::revoke_expired(
+            self.state_store.as_ref(),
+            self.firewall_mgr.as_ref(),
+            self.job_scheduler.as_ref(),
+            chrono::Utc::now().timestamp(),
+        )
+        .await
+        .map_err(|e| {
+            Status::internal(format!("[SLA ERROR] Debug port revocation failed: {}", e))
+        })?;
+
+        let key = policy_rule_key(&policy);
+        let previous =
+            get_record::<FirewallRuleRecord>(self.state_store.as_ref(), NS_FIREWALL_RULES, &key)
+                .await
+                .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        // 🛡️ A temporary rule is not turned into a permanent one: its timer
+        // would find nothing to revoke and the debug port would stay open
+        if let Some(expires_at) = previous.as_ref().and_then(|rule| rule.expires_at) {
+            let lapse = chrono::DateTime::from_timestamp(expires_at, 0).unwrap_or_default();
+            return Err(Status::already_exists(format!(
+                "Conflict: a debug port exposure covers port {} until {}",
+                policy.port,
+                lapse.format("%Y-%m-%d %H:%M:%S UTC")
+            )));
+        }
+        firewall::replace_policy(self.firewall_mgr.as_ref(), previous.as_ref(), &policy)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Firewall policy failed: {}", e)))?;
+
+        let record = FirewallRuleRecord {
+            action: match policy.action {
+                FirewallAction::Allow => "allow",
+                FirewallAction::Deny => "deny",
+                FirewallAction::Reject => "reject",
+            }
+            .to_string(),
+            port: policy.port,
+            protocol: protocol_name(policy.protocol).to_string(),
+            source_ip: policy.source_ip.clone(),
+            labels,
+            description: policy.description.clone(),
+            created_by: policy.created_by.clone(),
+            created_at: chrono::Utc::now().timestamp(),
+            log_denied: policy.log_denied,
+            expires_at: None,
+        };
+        put_record(self.state_store.as_ref(), NS_FIREWALL_RULES, &key, &record)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State update failed: {}", e)))?;
+
+        Ok(Response::new(AgentResponse {
+            success: true,
+            exit_code: 0,
+            stdout: format!("Firewall rule applied: port {}", req.port),
+            stderr: String::new(),
+            error_message: String::new(),
+            operation_id: String::new(),
+        }))
+    }
+
+    // =========================================================================
+    // 9. ⏰ Job Scheduling (Zero-Trust Cron via systemd timers)
+    // =========================================================================
+    async fn schedule_job(
+        &self,
+        request: Request<JobIntent>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let req = request.into_inner();
+
+        // 🛡️ Zero-Trust: Validate all fields
+        validate_identifier(&req.job_name, "job_name")?;
+        validate_identifier(&req.run_as_user, "run_as_user")?;
+
+        validate_job_binary(&req.binary)?;
+        let labels = validate_labels(req.labels)?;
+        if req.job_name.starts_with(firewall::DEBUG_PORT_JOB_PREFIX) {
+            return Err(Status::permission_denied(format!(
+                "Zero-Trust: Job names starting with '{}' are reserved for the agent",
+                firewall::DEBUG_PORT_JOB_PREFIX
+            )));
+        }
+
+        let holder = job_unit_holder(self.state_store.as_ref(), &req.job_name, None)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        if let Some(holder) = holder {
+            return Err(Status::already_exists(format!(
+                "Conflict: job unit kari-job-{} already belongs to {}",
+                req.job_name, holder
+            )));
+        }
+
+        let intent = TraitJobIntent {
+            name: req.job_name.clone(),
+            binary: req.binary,
+            args: req.args,
+            schedule: req.schedule_expression,
+            run_as_user: req.run_as_user,
+        };
+
+        self.job_scheduler
+            .schedule_job(&intent)
+            .await
+            .map_err(|e| Self::unit_error("Job scheduling failed", e))?;
+
+        let record = JobRecord {
+            labels,
+            ..JobRecord::from(&intent)
+        };
+        put_record(self.state_store.as_ref(), NS_JOBS, &req.job_name, &record)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State update failed: {}", e)))?;
+
+        info!("⏰ Job scheduled: {}", req.job_name);
+
+        Ok(Response::new(AgentResponse {
+            success: true,
+            exit_code: 0,
+            stdout: format!("Job '{}' scheduled successfully", req.job_name),
+            stderr: String::new(),
+            error_message: String::new(),
+            operation_id: String::new(),
+        }))
+    }
+
+    // =========================================================================
+    // 10. ✉️ Transactional Mail Relay (Privacy-First: Zeroize on Drop)
+    // =========================================================================
+    async fn configure_mail_relay(
+        &self,
+        request: Request<MailRelayRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let req = request.into_inner();
+
+        // 🛡️ Zero-Trust: Validate identifiers before the secret is unwrapped
+        validate_domain_name(&req.origin_domain)?;
+        let relay_port = u16::try_from(req.relay_port)
+            .ok()
+            .filter(|p| *p != 0)
+            .ok_or_else(|| Status::invalid_argument("Zero-Trust: relay_port must be 1-65535"))?;
+
+        // 🛡️ Privacy: The request buffer moves straight into the ProviderCredential.
+        let config = MailRelayConfig {
+            relay_host: req.relay_host.clone(),
+            relay_port,
+            username: req.username,
+            password: ProviderCredential::from_utf8(req.password)
+                .map_err(|e| Status::invalid_argument(format!("password is {}", e)))?,
+            origin_domain: req.origin_domain,
+        };
+
+        self.mail_relay.configure_relay(config).await.map_err(|e| {
+            Status::internal(format!(
+                "[SLA ERROR] Mail relay configuration failed: {}",
+                e
+            ))
+        })?;
+
+        Ok(Response::new(AgentResponse {
+            success: true,
+            exit_code: 0,
+            stdout: format!("Mail relay configured via {}", req.relay_host),
+            stderr: String::new(),
+            error_message: String::new(),
+            operation_id: String::new(),
+        }))
+    }
+
+    // =========================================================================
+    // 11. 🔑 Private Registry Credentials (Secret Store, Never On-Disk Long-Term)
+    // =========================================================================
+    async fn set_registry_credential(
+        &self,
+        request: Request<RegistryCredentialRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let req = request.into_inner();
+
+        // 🛡️ Zero-Trust: Everything except the password lands in JSON unescaped,
+        // so the charset is restricted instead.
+        validate_identifier(&req.app_id, "app_id")?;
+        if req.registry.is_empty()
+            || !req
+                .registry
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == ':')
+        {
+            return Err(Status::invalid_argument(format!(
+                "Zero-Trust: Invalid registry host: '{}'",
+                req.registry
+            )));
+        }
+        if req.username.is_empty()
+            || req
+                .username
+                .chars()
+                .any(|c| c.is_control() || c.is_whitespace() || matches!(c, ':' | '"' | '\\'))
+        {
+            return Err(Status::invalid_argument(
+                "Zero-Trust: Invalid registry username format",
+            ));
+        }
+
+        // 🛡️ Privacy: Every intermediate buffer holding the password is zeroized on
+        // drop, and each is sized up front: a buffer that grows leaves its old,
+        // unwiped allocation behind.
+        let password = ProviderCredential::from_utf8(req.password)
+            .map_err(|e| Status::invalid_argument(format!("password is {}", e)))?;
+        let encoded = password.use_secret(|password| {
+            let mut basic = Zeroizing::new(String::with_capacity(
+                req.username.len() + 1 + password.len(),
+            ));
+            basic.push_str(&req.username);
+            basic.push(':');
+            basic.push_str(password);
+            Zeroizing::new(base64::Engine::encode(
+                &base64::engine::general_purpose::STANDARD,
+                basic.as_bytes(),
+            ))
+        });
+        password.destroy();
+        let (head, tail) = (r#"{"auths":{""#, r#"":{"auth":""#);
+        let mut auth_json =
+            String::with_capacity(head.len() + req.registry.len() + tail.len() + encoded.len() + 4);
+        auth_json.push_str(head);
+        auth_json.push_str(&req.registry);
+        auth_json.push_str(tail);
+        auth_json.push_str(&encoded);
+        auth_json.push_str(r#""}}}"#);
+
+        self.secret_store
+            .put_secret(
+                &Self::registry_auth_secret(&req.app_id),
+                ProviderCredential::from_string(auth_json),
+            )
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Secret storage failed: {}", e)))?;
+
+        info!(
+            "🔑 Registry credential stored for app {} ({})",
+            req.app_id, req.registry
+        );
+
+        Ok(Response::new(AgentResponse {
+            success: true,
+            exit_code: 0,
+            stdout: format!("Registry credential stored for {}", req.registry),
+            stderr: String::new(),
+            error_message: String::new(),
+            operation_id: String::new(),
+        }))
+    }
+
+    // =========================================================================
+    // 12. 🧹 Container Image Garbage Collection
+    // =========================================================================
+    async fn prune_images(
+        &self,
+        request: Request<PruneImagesRequest>,
+    ) -> Result<Response<PruneImagesResponse>, Status> {
+        let req = request.into_inner();
+
+        let app_filter = if req.app_id.is_empty() {
+            None
+        } else {
+            validate_identifier(&req.app_id, "app_id")?;
+            Some(req.app_id.as_str())
+        };
+
+        let report = image_gc::collect_images(
+            self.state_store.as_ref(),
+            self.image_mgr.as_ref(),
+            &self.config.web_root,
+            app_filter,
+        )
+        .await
+        .map_err(|e| Status::internal(format!("[SLA ERROR] Image GC failed: {}", e)))?;
+
+        info!(
+            "🧹 Image GC removed {} images across {} apps",
+            report.images_removed, report.apps_scanned
+        );
+
+        Ok(Response::new(PruneImagesResponse {
+            apps_scanned: report.apps_scanned,
+            images_removed: report.images_removed,
+        }))
+    }
+
+    // =========================================================================
+    // 13. 🔐 Agent Secret Store
+    // =========================================================================
+    async fn put_secret(
+        &self,
+        request: Request<SecretRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let req = request.into_inner();
+
+        validate_identifier(&req.name, "secret name")?;
+        if Self::is_reserved_secret(&req.name) {
+            return Err(Status::permission_denied(format!(
+                "Zero-Trust: Secret name '{}' is reserved for the agent",
+                req.name
+            )));
+        }
+
+        // 🛡️ Privacy: The request buffer moves straight into the ProviderCredential.
+        let value = ProviderCredential::from_utf8(req.value)
+            .map_err(|e| Status::invalid_argument(format!("secret value is {}", e)))?;
+
+        self.secret_store
+            .put_secret(&req.name, value)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Secret storage failed: {}", e)))?;
+
+        info!("🔐 Secret stored: {}", req.name);
+
+        Ok(Response::new(AgentResponse {
+            success: true,
+            ..Default::default()
+        }))
+    }
+
+    // =========================================================================
+    // 14. 📝 App Environment Files (Dotenv)
+    // =========================================================================
+    async fn write_app_env_file(
+        &self,
+        request: Request<AppEnvFileRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let req = request.into_inner();
+
+        // 🛡️ Zero-Trust: Validate inputs
+        validate_identifier(&req.app_id, "app_id")?;
+        validate_domain_name(&req.domain_name)?;
+        if let Some(bad) = req
+            .vars
+            .keys()
+            .chain(req.secret_refs.keys())
+            .find(|k| !is_valid_env_key(k))
+        {
+            return Err(Status::invalid_argument(format!(
+                "Zero-Trust: Invalid env key: '{}'",
+                bad
+            )));
+        }
+        if let Some(dup) = req.secret_refs.keys().find(|k| req.vars.contains_key(*k)) {
+            return Err(Status::invalid_argument(format!(
+                "Env key '{}' is both a plain var and a secret ref",
+                dup
+            )));
+        }
+
+        let shared_dir = Self::secure_join(&self.config.web_root, &req.domain_name)?.join("shared");
+        let app_user = format!("kari-app-{}", req.app_id);
+
+        // Resolve every reference before touching the file, so a missing secret
+        // leaves the previous .env in place.
+        let mut secrets = std::collections::BTreeMap::new();
+        for (key, name) in &req.secret_refs {
+            validate_identifier(name, "secret name")?;
+            if Self::is_reserved_secret(name) {
+                return Err(Status::permission_denied(format!(
+                    "Zero-Trust: Secret '{}' cannot be exposed to apps",
+                    name
+                )));
+            }
+            let credential = self
+                .secret_store
+                .get_secret(name)
+                .await
+                .map_err(|e| Status::internal(format!("[SLA ERROR] Secret lookup failed: {}", e)))?
+                .ok_or_else(|| {
+                    Status::failed_precondition(format!("Secret '{}' does not exist", name))
+                })?;
+            secrets.insert(key.clone(), credential);
+        }
+
+        let vars: std::collections::BTreeMap<String, String> = req.vars.into_iter().collect();
+        let write_result = self
+            .env_file_mgr
+            .write_env_file(&shared_dir, &app_user, &vars, secrets)
+            .await;
+
+        // 🛡️ Privacy: Plain values can still be sensitive (DSNs, internal hosts)
+        for (_, mut val) in vars {
+            val.zeroize();
+        }
+
+        write_result
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Env file write failed: {}", e)))?;
+
+        info!(
+            "📝 Env file written for {} ({} secret refs)",
+            req.domain_name,
+            req.secret_refs.len()
+        );
+
+        Ok(Response::new(AgentResponse {
+            success: true,
+            ..Default::default()
+        }))
+    }
+
+    // =========================================================================
+    // 15. ⏳ Long-Running Operations
+    // =========================================================================
+    async fn get_operation(
+        &self,
+        request: Request<OperationRequest>,
+    ) -> Result<Response<Operation>, Status> {
+        let req = request.into_inner();
+        validate_identifier(&req.operation_id, "operation_id")?;
+
+        let op = self
+            .operations
+            .get(&req.operation_id)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Operation lookup failed: {}", e)))?
+            .ok_or_else(|| {
+                Status::not_found(format!("Unknown operation '{}'", req.operation_id))
+            })?;
+
+        Ok(Response::new(Self::operation_to_proto(op)))
+    }
+
+    // =========================================================================
+    // 16. 🗄️ Control-Layer Snapshots
+    // =========================================================================
+    async fn export_state(
+        &self,
+        request: Request<ExportStateRequest>,
+    ) -> Result<Response<BundleResponse>, Status> {
+        let req = request.into_inner();
+        let key = Self::bundle_key(req.bundle_key)?;
+
+        let timestamp = chrono::Utc::now().format("%Y%m%d%H%M%S");
+        let bundle_path = self.bundle_path(&format!("state-{}.kbundle", timestamp))?;
+
+        let report = snapshot::export_state(
+            self.state_store.as_ref(),
+            &self.snapshot_paths(),
+            &key,
+            &bundle_path,
+        )
+        .await
+        .map_err(|e| Status::internal(format!("[SLA ERROR] State export failed: {}", e)))?;
+
+        info!(
+            "🗄️ State exported to {:?} ({} entries)",
+            bundle_path, report.entries
+        );
+
+        Ok(Response::new(BundleResponse {
+            bundle_path: bundle_path.to_string_lossy().to_string(),
+            entry_count: report.entries,
+        }))
+    }
+
+    async fn import_state(
+        &self,
+        request: Request<ImportStateRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let req = request.into_inner();
+        let key = Self::bundle_key(req.bundle_key)?;
+        let bundle_path = self.bundle_path(&req.bundle_name)?;
+
+        // 🛡️ SLA: Restoring over a live host replaces its records; require intent.
+        let existing: Vec<AppRecord> = list_records(self.state_store.as_ref(), NS_APPS)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        if !existing.is_empty() && !req.force {
+            return Err(Status::failed_precondition(format!(
+                "Host already manages {} apps; set force to overwrite",
+                existing.len()
+            )));
+        }
+
+        let report = snapshot::import_state(
+            self.state_store.as_ref(),
+            &self.snapshot_paths(),
+            &key,
+            &bundle_path,
+        )
+        .await
+        .map_err(|e| Status::internal(format!("[SLA ERROR] State import failed: {}", e)))?;
+
+        self.svc_mgr
+            .reload_daemon()
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Daemon reload failed: {}", e)))?;
+
+        info!(
+            "🗄️ State imported from {:?} ({} entries)",
+            bundle_path, report.entries
+        );
+
+        Ok(Response::new(AgentResponse {
+            success: true,
+            stdout: format!("Restored {} entries", report.entries),
+            ..Default::default()
+        }))
+    }
+
+    // =========================================================================
+    // 17. 🚚 App Migration Between Hosts
+    // =========================================================================
+    async fn export_app(
+        &self,
+        request: Request<ExportAppRequest>,
+    ) -> Result<Response<BundleResponse>, Status> {
+        let req = request.into_inner();
+        validate_domain_name(&req.domain_name)?;
+        let key = Self::bundle_key(req.bundle_key)?;
+
+        let state_err =
+            |e: String| Status::internal(format!("[SLA ERROR] State read failed: {}", e));
+        let record = list_records::<AppRecord>(self.state_store.as_ref(), NS_APPS)
+            .await
+            .map_err(state_err)?
+            .into_iter()
+            .find(|a| a.domain_name == req.domain_name)
+            .ok_or_else(|| Status::not_found(format!("No kari app serves {}", req.domain_name)))?;
+        let job_names: Vec<String> =
+            get_record(self.state_store.as_ref(), NS_APP_JOBS, &record.app_id)
+                .await
+                .map_err(state_err)?
+                .unwrap_or_default();
+        let registry_auth = self
+            .secret_store
+            .get_secret(&Self::registry_auth_secret(&record.app_id))
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Secret lookup failed: {}", e)))?;
+
+        let timestamp = chrono::Utc::now().format("%Y%m%d%H%M%S");
+        let bundle_path =
+            self.bundle_path(&format!("app-{}-{}.kbundle", req.domain_name, timestamp))?;
+
+        let entries = migration::export_app(
+            AppExport {
+                record,
+                job_names,
+                registry_auth,
+            },
+            &self.migration_paths(),
+            &key,
+            &bundle_path,
+        )
+        .map_err(|e| Status::internal(format!("[SLA ERROR] App export failed: {}", e)))?;
+
+        info!(
+            "🚚 App exported: {} -> {:?} ({} entries)",
+            req.domain_name, bundle_path, entries
+        );
+
+        Ok(Response::new(BundleResponse {
+            bundle_path: bundle_path.to_string_lossy().to_string(),
+            entry_count: entries,
+        }))
+    }
+
+    async fn import_app(
+        &self,
+        request: Request<ImportAppRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let caller = Caller::of(&request);
+        let req = request.into_inner();
+        let key = Self::bundle_key(req.bundle_key)?;
+        let bundle_path = self.bundle_path(&req.bundle_name)?;
+
+        if req.run_async {
+            return self
+                .spawn_operation(&caller, "import_app", |this, op_id| async move {
+                    this.import_app_bundle(bundle_path, key, Some(&op_id)).await
+                })
+                .await
+                .map(Response::new);
+        }
+
+        self.import_app_bundle(bundle_path, key, None)
+            .await
+            .map(Response::new)
+    }
+
+    // =========================================================================
+    // 18. 🏢 Tenant Quota Groups (Aggregate cgroup Limits)
+    // =========================================================================
+    async fn set_tenant_quota(
+        &self,
+        request: Request<TenantQuotaRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let req = request.into_inner();
+
+        let tenant_id = validate_tenant_id(&req.tenant_id)?
+            .ok_or_else(|| Status::invalid_argument("Zero-Trust: tenant_id is required"))?;
+        let quota = TenantQuota {
+            tenant_id: tenant_id.clone(),
+            memory_max_mb: Some(req.memory_max_mb).filter(|mb| *mb > 0),
+            cpu_quota_percent: Some(req.cpu_quota_percent).filter(|p| *p > 0),
+        };
+
+        self.tenant_slice_mgr
+            .write_slice(&quota)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Tenant slice failed: {}", e)))?;
+        put_record(self.state_store.as_ref(), NS_TENANTS, &tenant_id, &quota)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State update failed: {}", e)))?;
+
+        // daemon-reload re-applies the slice's limits to apps already running in it
+        self.svc_mgr
+            .reload_daemon()
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Daemon reload failed: {}", e)))?;
+
+        info!(
+            "🏢 Tenant quota set: {} (mem: {:?}MB, cpu: {:?}%)",
+            tenant_id, quota.memory_max_mb, quota.cpu_quota_percent
+        );
+
+        Ok(Response::new(AgentResponse {
+            success: true,
+            stdout: format!("Quota applied to {}", tenant_slice_name(&tenant_id)),
+            ..Default::default()
+        }))
+    }
+
+    // =========================================================================
+    // 19. 📜 Supply Chain (Build-Time SBOMs)
+    // =========================================================================
+    async fn get_deploy_sbom(
+        &self,
+        request: Request<SbomRequest>,
+    ) -> Result<Response<SbomResponse>, Status> {
+        let req = request.into_inner();
+        validate_domain_name(&req.domain_name)?;
+
+        let releases: Vec<ReleaseRecord> = list_records(self.state_store.as_ref(), NS_RELEASES)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        let record = if req.release.is_empty() {
+            releases
+                .into_iter()
+                .filter(|r| r.domain_name == req.domain_name && r.sbom_components.is_some())
+                .max_by_key(|r| r.created_at)
+        } else {
+            validate_identifier(&req.release, "release")?;
+            releases
+                .into_iter()
+                .find(|r| r.domain_name == req.domain_name && r.release == req.release)
+        };
+        let Some((record, component_count)) =
+            record.and_then(|r| r.sbom_components.map(|count| (r, count)))
+        else {
+            return Err(Status::not_found(format!(
+                "No SBOM recorded for {} {}",
+                req.domain_name, req.release
+            )));
+        };
+
+        let path = sbom::sbom_path(&self.config.state_dir, &record.domain_name, &record.release);
+        let cyclonedx_json = tokio::fs::read(&path)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] SBOM read failed: {}", e)))?;
+
+        Ok(Response::new(SbomResponse {
+            release: record.release,
+            cyclonedx_json,
+            component_count,
+        }))
+    }
+
+    async fn get_build_log(
+        &self,
+        request: Request<BuildLogRequest>,
+    ) -> Result<Response<BuildLogResponse>, Status> {
+        let req = request.into_inner();
+        validate_domain_name(&req.domain_name)?;
+        let compression = req.compression();
+
+        // 🛡️ Logs are files, not records: a failed deploy never gets a ReleaseRecord
+        let state_dir = &self.config.state_dir;
+        let release = if req.release.is_empty() {
+            build_log::list_build_logs(state_dir, &req.domain_name).pop()
+        } else {
+            validate_identifier(&req.release, "release")?;
+            Some(req.release)
+        };
+        let Some(release) = release else {
+            return Err(Status::not_found(format!(
+                "No build log recorded for {}",
+                req.domain_name
+            )));
+        };
+
+        let path = build_log::build_log_path(state_dir, &req.domain_name, &release);
+        if !path.exists() {
+            return Err(Status::not_found(format!(
+                "No build log recorded for {} {}",
+                req.domain_name, release
+            )));
+        }
+        let content = tokio::task::spawn_blocking(move || build_log::read_build_log(&path))
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Build log read panicked: {}", e)))?
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Build log read failed: {}", e)))?;
+
+        if compression == Compression::Zstd {
+            let compressed_content = zstd::encode_all(content.as_bytes(), 3).map_err(|e| {
+                Status::internal(format!("[SLA ERROR] Build log compression failed: {}", e))
+            })?;
+            return Ok(Response::new(BuildLogResponse {
+                release,
+                content: String::new(),
+                compressed_content,
+            }));
+        }
+        Ok(Response::new(BuildLogResponse {
+            release,
+            content,
+            compressed_content: Vec::new(),
+        }))
+    }
+
+    // =========================================================================
+    // 20. 🏷️ Inventory (Label-Filtered Listing)
+    // =========================================================================
+    async fn list_deployments(
+        &self,
+        request: Request<ListRequest>,
+    ) -> Result<Response<DeploymentList>, Status> {
+        use kari_agent::Runtime;
+
+        let req = request.into_inner();
+        let fields =
+            Self::field_selector(&req, &["app_id", "domain_name", "runtime", "tenant_id"])?;
+        let selector = validate_labels(req.label_selector.clone())?;
+        let apps: Vec<AppRecord> = list_records(self.state_store.as_ref(), NS_APPS)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+
+        let apps = apps
+            .into_iter()
+            .filter(|app| {
+                matches_labels(&app.labels, &selector)
+                    && fields.accepts("app_id", &app.app_id)
+                    && fields.accepts("domain_name", &app.domain_name)
+                    && fields.accepts("runtime", &app.runtime)
+                    && fields.accepts("tenant_id", app.tenant_id.as_deref().unwrap_or_default())
+            })
+            .collect();
+        let (apps, next_page_token) = Self::page(apps, |app| app.app_id.clone(), &req)?;
+
+        let deployments = apps
+            .into_iter()
+            .map(|app| Deployment {
+                runtime: match app.runtime.as_str() {
+                    "container" => Runtime::Container,
+                    _ => Runtime::Source,
+                } as i32,
+                image: app.image.unwrap_or_default(),
+                port: app.port.map(u32::from).unwrap_or(0),
+                memory_limit_mb: app.memory_limit_mb,
+                cpu_limit_percent: app.cpu_limit_percent,
+                labels: app.labels.into_iter().collect(),
+                app_id: app.app_id,
+                domain_name: app.domain_name,
+                service_name: app.service_name,
+            })
+            .collect();
+
+        Ok(Response::new(DeploymentList {
+            deployments,
+            next_page_token,
+        }))
+    }
+
+    async fn list_jobs(&self, request: Request<ListRequest>) -> Result<Response<JobList>, Status> {
+        let req = request.into_inner();
+        let fields = Self::field_selector(&req, &["job_name", "app_id", "run_as_user"])?;
+        let selector = validate_labels(req.label_selector.clone())?;
+        let state = self.state_store.as_ref();
+        let (jobs, apps) = match (
+            list_records::<JobRecord>(state, NS_JOBS).await,
+            list_records::<AppRecord>(state, NS_APPS).await,
+        ) {
+            (Ok(jobs), Ok(apps)) => (jobs, apps),
+            (Err(e), _) | (_, Err(e)) => {
+                return Err(Status::internal(format!(
+                    "[SLA ERROR] State read failed: {}",
+                    e
+                )));
+            }
+        };
+
+        let jobs = jobs
+            .into_iter()
+            .filter(|job| {
+                fields.accepts("job_name", &job.job_name)
+                    && fields.accepts("app_id", job.app_id.as_deref().unwrap_or_default())
+                    && fields.accepts("run_as_user", &job.run_as_user)
+            })
+            .filter_map(|job| {
+                // App-owned jobs inherit the app's labels; their own take precedence
+                let mut labels = job
+                    .app_id
+                    .as_deref()
+                    .and_then(|id| apps.iter().find(|a| a.app_id == id))
+                    .map(|app| app.labels.clone())
+                    .unwrap_or_default();
+                labels.extend(job.labels);
+                if !matches_labels(&labels, &selector) {
+                    return None;
+                }
+                Some(ManagedJob {
+                    intent: Some(JobIntent {
+                        job_name: job.job_name,
+                        binary: job.binary,
+                        args: job.args,
+                        schedule_expression: job.schedule,
+                        run_as_user: job.run_as_user,
+                        labels: labels.into_iter().collect(),
+                    }),
+                    app_id: job.app_id.unwrap_or_default(),
+                })
+            })
+            .collect();
+
+        let (jobs, next_page_token) = Self::page(
+            jobs,
+            |job| {
+                job.intent
+                    .as_ref()
+                    .map(|i| i.job_name.clone())
+                    .unwrap_or_default()
+            },
+            &req,
+        )?;
+        Ok(Response::new(JobList {
+            jobs,
+            next_page_token,
+        }))
+    }
+
+    async fn list_firewall_rules(
+        &self,
+        request: Request<ListRequest>,
+    ) -> Result<Response<FirewallRuleList>, Status> {
+        let req = request.into_inner();
+        let fields = Self::field_selector(&req, &["action", "protocol", "port", "source_ip"])?;
+        let selector = validate_labels(req.label_selector.clone())?;
+        let rules: Vec<FirewallRuleRecord> =
+            list_records(self.state_store.as_ref(), NS_FIREWALL_RULES)
+                .await
+                .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+
+        let rules = rules
+            .into_iter()
+            .filter(|rule| {
+                matches_labels(&rule.labels, &selector)
+                    && fields.accepts("action", &rule.action)
+                    && fields.accepts("protocol", &rule.protocol)
+                    && fields.accepts("port", &rule.port.to_string())
+                    && fields.accepts("source_ip", rule.source_ip.as_deref().unwrap_or("any"))
+            })
+            .collect();
+        let (rules, next_page_token) = Self::page(
+            rules,
+            |rule: &FirewallRuleRecord| {
+                firewall_rule_key(rule.port, &rule.protocol, rule.source_ip.as_deref())
+            },
+            &req,
+        )?;
+
+        Ok(Response::new(FirewallRuleList {
+            rules: rules.into_iter().map(Self::firewall_rule_message).collect(),
+            next_page_token,
+        }))
+    }
+
+    // =========================================================================
+    // 21. 🩺 Host Self-Test (Disposable App, End to End)
+    // =========================================================================
+    async fn run_self_test(
+        &self,
+        request: Request<SelfTestRequest>,
+    ) -> Result<Response<SelfTestReport>, Status> {
+        let req = request.into_inner();
+        let report = self.self_test(req.with_certificate).await;
+        match report.phases.iter().find(|p| !p.success) {
+            None => info!("🩺 Self-test passed"),
+            Some(p) => warn!("🩺 Self-test failed at {}: {}", p.name, p.detail),
+        }
+        Ok(Response::new(report))
+    }
+
+    // =========================================================================
+    // 22. 📈 Firewall Events (Per-Rule Hit Counts)
+    // =========================================================================
+    async fn stream_firewall_events(
+        &self,
+        request: Request<FirewallEventsRequest>,
+    ) -> Result<Response<Self::StreamFirewallEventsStream>, Status> {
+        let req = request.into_inner();
+        let interval = Duration::from_secs(match req.interval_secs {
+            0 => DEFAULT_FIREWALL_EVENTS_INTERVAL_SECS,
+            secs => u64::from(secs),
+        });
+
+        let (tx, rx) = mpsc::channel(4);
+        let (hit_tx, mut hit_rx) = mpsc::channel(256);
+        let source = self.firewall_events.clone();
+        let state_store = self.state_store.clone();
+
+        tokio::spawn(async move {
+            let follower = tokio::spawn(async move {
+                if let Err(e) = source.follow(hit_tx).await {
+                    warn!("📈 Firewall event source stopped: {}", e);
+                }
+            });
+
+            // rule_id -> (hits, last source)
+            let mut window: HashMap<String, (u64, String)> = HashMap::new();
+            let mut window_start = chrono::Utc::now().timestamp();
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await; // The first tick completes immediately
+
+            loop {
+                tokio::select! {
+                    _ = tx.closed() => break,
+                    hit = hit_rx.recv() => match hit {
+                        Some(hit) => {
+                            let entry = window.entry(hit.rule_id).or_default();
+                            entry.0 += 1;
+                            entry.1 = hit.source_ip;
+                        }
+                        None => {
+                            let _ = tx
+                                .send(Err(Status::unavailable(
+                                    "[SLA ERROR] Firewall event source ended",
+                                )))
+                                .await;
+                            break;
+                        }
+                    },
+                    _ = ticker.tick() => {
+                        let window_end = chrono::Utc::now().timestamp();
+                        let summary = match Self::firewall_event_summary(
+                            state_store.as_ref(),
+                            std::mem::take(&mut window),
+                            window_start,
+                            window_end,
+                        )
+                        .await
+                        {
+                            Ok(summary) => Ok(summary),
+                            Err(e) => Err(Status::internal(format!(
+                                "[SLA ERROR] State read failed: {}",
+                                e
+                            ))),
+                        };
+                        window_start = window_end;
+                        if tx.send(summary).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+
+            // 🛡️ Dropping the receiver ends follow(); abort covers sources that ignore it
+            drop(hit_rx);
+            follower.abort();
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    // =========================================================================
+    // 23. 🔁 Boot Survival (Autostart Verification)
+    // =========================================================================
+    async fn verify_autostart(
+        &self,
+        request: Request<VerifyAutostartRequest>,
+    ) -> Result<Response<AutostartReport>, Status> {
+        let req = request.into_inner();
+        let managers = AutostartManagers {
+            state: self.state_store.as_ref(),
+            services: self.svc_mgr.as_ref(),
+            proxy: self.proxy_mgr.as_ref(),
+            ssl: self.ssl_engine.as_ref(),
+            scheduler: self.job_scheduler.as_ref(),
+            firewall: self.firewall_mgr.as_ref(),
+        };
+        let report = autostart::reconcile(&managers, req.repair)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+
+        Ok(Response::new(AutostartReport {
+            healthy: report.healthy(),
+            checked: report.checked,
+            discrepancies: report
+                .discrepancies
+                .into_iter()
+                .map(|d| AutostartDiscrepancy {
+                    kind: d.kind.to_string(),
+                    target: d.target,
+                    app_id: d.app_id,
+                    repaired: d.repaired,
+                    detail: d.detail,
+                })
+                .collect(),
+        }))
+    }
+
+    // =========================================================================
+    // 24. 🧊 Warm Standby (Prebuilt Releases)
+    // =========================================================================
+    async fn prebuild_release(
+        &self,
+        request: Request<DeployRequest>,
+    ) -> Result<Response<Self::PrebuildReleaseStream>, Status> {
+        let req = request.into_inner();
+        // 🪝 Nothing here goes live, so there is nothing for a hook to run against
+        if !req.pre_deploy_argv.is_empty() || !req.post_deploy_argv.is_empty() {
+            return Err(Status::invalid_argument(
+                "pre_deploy_argv and post_deploy_argv run only with StreamDeployment",
+            ));
+        }
+        let job = self.prepare_deploy(req).await?;
+        let mut cancel = self.register_cancel(&job.req.trace_id)?;
+
+        let (client_tx, rx) = mpsc::channel(512);
+        let client_tx = if job.req.log_compression() == Compression::Zstd {
+            Self::compress_log_stream(client_tx, &job.req.trace_id)
+        } else {
+            client_tx
+        };
+        let tx = self.tee_build_log(client_tx, &job.req.domain_name, &job.release);
+
+        let this = self.clone();
+        tokio::spawn(async move {
+            let trace_id = job.req.trace_id.clone();
+            let progress = DeployProgress::new(tx.clone(), &trace_id);
+            // 🛡️ Nothing past the build runs: proxy, unit and timers stay on the live release
+            let Some(built) = this
+                .build_release_watched(job, &progress, &mut cancel)
+                .await
+            else {
+                return;
+            };
+            drop(cancel);
+            let _ = tx
+                .send(Ok(LogChunk {
+                    content: format!(
+                        "✅ Release {} built and standing by ({}).\n",
+                        built.record.release,
+                        built.describe()
+                    ),
+                    trace_id,
+                    finding: None,
+                    usage: Some(built.usage_message()),
+                    compressed_content: Vec::new(),
+                    raw_content: Vec::new(),
+                    event: None,
+                    queued: None,
+                }))
+                .await;
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn activate_release(
+        &self,
+        request: Request<ActivateReleaseRequest>,
+    ) -> Result<Response<Self::ActivateReleaseStream>, Status> {
+        let req = request.into_inner();
+        validate_domain_name(&req.domain_name)?;
+        validate_identifier(&req.release, "release")?;
+
+        let Some(record) = get_record::<ReleaseRecord>(
+            self.state_store.as_ref(),
+            NS_RELEASES,
+            &release_key(&req.domain_name, &req.release),
+        )
+        .await
+        .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?
+        else {
+            return Err(Status::not_found(format!(
+                "No release {} recorded for {}",
+                req.release, req.domain_name
+            )));
+        };
+        let release_dir = Self::secure_join(&self.config.web_root, &req.domain_name)?
+            .join("releases")
+            .join(&record.release);
+        if !release_dir.is_dir() {
+            return Err(Status::failed_precondition(format!(
+                "Release {} of {} has been pruned from disk",
+                record.release, record.domain_name
+            )));
+        }
+
+        Ok(Response::new(self.stream_switch(record, req.trace_id)))
+    }
+
+    // =========================================================================
+    // 25. ✋ Approval Gate
+    // =========================================================================
+    async fn confirm_deployment(
+        &self,
+        request: Request<ConfirmDeploymentRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let req = request.into_inner();
+        validate_identifier(&req.trace_id, "trace_id")?;
+
+        let Some(decide) = self.pending_approvals.lock().unwrap().remove(&req.trace_id) else {
+            return Err(Status::not_found(format!(
+                "No deployment is awaiting approval under trace id {}",
+                req.trace_id
+            )));
+        };
+        if decide.send(!req.reject).is_err() {
+            return Err(Status::failed_precondition(format!(
+                "Deployment {} stopped waiting before the answer arrived",
+                req.trace_id
+            )));
+        }
+
+        info!(
+            "✋ Deployment {} {}",
+            req.trace_id,
+            if req.reject { "rejected" } else { "approved" }
+        );
+        Ok(Response::new(AgentResponse {
+            success: true,
+            ..Default::default()
+        }))
+    }
+
+    // =========================================================================
+    // 26. 🕵️ Tamper Watch
+    // =========================================================================
+    async fn stream_tamper_events(
+        &self,
+        _request: Request<TamperEventsRequest>,
+    ) -> Result<Response<Self::StreamTamperEventsStream>, Status> {
+        if !self.config.tamper_watch {
+            return Err(Status::failed_precondition(
+                "Tamper watch is disabled (KARI_TAMPER_WATCH=0)",
+            ));
+        }
+
+        let mut events = self.tamper_events.subscribe();
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    _ = tx.closed() => break,
+                    event = events.recv() => event,
+                };
+                let event = match event {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!(
+                            "🕵️ Tamper subscriber fell behind; {} events dropped",
+                            missed
+                        );
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let message = TamperEvent {
+                    kind: event.kind.to_string(),
+                    target: event.target,
+                    app_id: event.app_id,
+                    path: event.path.display().to_string(),
+                    detail: event.detail,
+                    repaired: event.repaired,
+                    detected_at: event.detected_at,
+                };
+                if tx.send(Ok(message)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    // =========================================================================
+    // 27. 🔌 Circuit Breaker
+    // =========================================================================
+    async fn stream_circuit_events(
+        &self,
+        _request: Request<CircuitEventsRequest>,
+    ) -> Result<Response<Self::StreamCircuitEventsStream>, Status> {
+        let mut events = self.circuit_events.subscribe();
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    _ = tx.closed() => break,
+                    event = events.recv() => event,
+                };
+                let event = match event {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!(
+                            "🔌 Circuit subscriber fell behind; {} events dropped",
+                            missed
+                        );
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let message = CircuitEvent {
+                    app_id: event.app_id,
+                    domain_name: event.domain_name,
+                    service_name: event.service_name,
+                    open: event.open,
+                    detail: event.detail,
+                    at: event.at,
+                    initiated_by: event.initiated_by.to_string(),
+                };
+                if tx.send(Ok(message)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn reset_circuit_breaker(
+        &self,
+        request: Request<CircuitBreakerRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let caller = Caller::of(&request);
+        let req = request.into_inner();
+        validate_domain_name(&req.domain_name)?;
+
+        let apps: Vec<AppRecord> = list_records(self.state_store.as_ref(), NS_APPS)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        let Some(app) = apps
+            .into_iter()
+            .find(|app| app.domain_name == req.domain_name)
+        else {
+            return Err(Status::not_found(format!(
+                "No app serves {}",
+                req.domain_name
+            )));
+        };
+        if !app.vhost.maintenance {
+            return Err(Status::failed_precondition(format!(
+                "Circuit for {} is not open",
+                req.domain_name
+            )));
+        }
+
+        let event = self
+            .circuit_breaker()
+            .reset(app, caller)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Circuit reset failed: {}", e)))?;
+        let _ = self.circuit_events.send(event.clone());
+
+        Ok(Response::new(AgentResponse {
+            success: true,
+            exit_code: 0,
+            stdout: format!(
+                "{} restarted; {} routed to it again",
+                event.service_name, event.domain_name
+            ),
+            stderr: String::new(),
+            error_message: String::new(),
+            operation_id: String::new(),
+        }))
+    }
+
+    // =========================================================================
+    // 28. 🌐 Network Inventory
+    // =========================================================================
+    async fn get_network_inventory(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<NetworkInventory>, Status> {
+        let interfaces =
+            self.network.interfaces().await.map_err(|e| {
+                Status::internal(format!("[SLA ERROR] Interface scan failed: {}", e))
             })?;
+        let listeners = self
+            .network
+            .listeners()
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Socket scan failed: {}", e)))?;
+        let apps: Vec<AppRecord> = list_records(self.state_store.as_ref(), NS_APPS)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+
+        Ok(Response::new(NetworkInventory {
+            interfaces: interfaces
+                .into_iter()
+                .map(|i| NetworkInterfaceInfo {
+                    name: i.name,
+                    up: i.up,
+                    addresses: i.addresses,
+                })
+                .collect(),
+            listeners: listeners
+                .into_iter()
+                .map(|l| Self::listening_socket_message(l, &apps))
+                .collect(),
+        }))
+    }
 
-        // Step 2: Create and secure the application directory
-        self.jail_mgr
-            .secure_directory(&app_dir, &app_user)
+    // =========================================================================
+    // 29. 🕰️ Time Synchronization
+    // =========================================================================
+    async fn configure_time_sync(
+        &self,
+        request: Request<TimeSyncRequest>,
+    ) -> Result<Response<TimeSyncStatus>, Status> {
+        let req = request.into_inner();
+        self.time_sync
+            .configure(&req.ntp_servers)
             .await
-            .map_err(|e| {
-                Status::internal(format!("[SLA ERROR] Directory jailing failed: {}", e))
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Time sync setup failed: {}", e)))?;
+        // Read straight after the restart: `synchronized` follows within a few polls
+        let status = self
+            .time_sync
+            .status()
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Clock status failed: {}", e)))?;
+        Ok(Response::new(Self::time_sync_message(status)))
+    }
+
+    async fn get_time_sync_status(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<TimeSyncStatus>, Status> {
+        let status = self
+            .time_sync
+            .status()
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Clock status failed: {}", e)))?;
+        if status.skewed() {
+            warn!(
+                "🕰️ Clock skew: synchronized={}, offset={:?}s",
+                status.synchronized, status.offset_secs
+            );
+        }
+        Ok(Response::new(Self::time_sync_message(status)))
+    }
+
+    // =========================================================================
+    // 30. 🗂️ Host Facts
+    // =========================================================================
+    async fn get_host_facts(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<HostFacts>, Status> {
+        let virtualization = facts::detect_virtualization().await;
+        let (os, disks, components) = tokio::task::spawn_blocking(|| {
+            let dirs: Vec<&Path> = facts::BIN_DIRS.iter().map(Path::new).collect();
+            (
+                facts::read_os_release(),
+                facts::read_disks(),
+                facts::detect_components(&dirs),
+            )
+        })
+        .await
+        .map_err(|e| Status::internal(format!("[SLA ERROR] Fact gathering panicked: {}", e)))?;
+
+        let (cpu_model, cpu_cores, cpu_threads, memory_total) = {
+            let mut sys = self.system_monitor.lock().unwrap();
+            sys.refresh_cpu();
+            sys.refresh_memory();
+            let model = sys
+                .cpus()
+                .first()
+                .map(|cpu| cpu.brand().trim().to_string())
+                .unwrap_or_default();
+            let threads = sys.cpus().len() as u32;
+            let cores = sys
+                .physical_core_count()
+                .map_or(threads, |cores| cores as u32);
+            (model, cores, threads, sys.total_memory())
+        };
+
+        Ok(Response::new(HostFacts {
+            hostname: System::host_name().unwrap_or_default(),
+            os_id: os.id,
+            os_name: os.name,
+            os_version: os.version,
+            kernel: System::kernel_version().unwrap_or_default(),
+            architecture: std::env::consts::ARCH.to_string(),
+            virtualization,
+            cpu_model,
+            cpu_cores,
+            cpu_threads,
+            memory_total_mb: memory_total / 1_048_576,
+            disks: disks
+                .into_iter()
+                .map(|d| DiskInfo {
+                    device: d.device,
+                    mount_point: d.mount_point,
+                    filesystem: d.filesystem,
+                    total_mb: d.total_mb,
+                    available_mb: d.available_mb,
+                    removable: d.removable,
+                })
+                .collect(),
+            components: components
+                .into_iter()
+                .map(|c| StackComponent {
+                    name: c.name,
+                    path: c.path.display().to_string(),
+                })
+                .collect(),
+        }))
+    }
+
+    // =========================================================================
+    // 31. 📦 Signed Config Bundles (Air-Gapped Provisioning)
+    // =========================================================================
+    async fn apply_config_bundle(
+        &self,
+        request: Request<ConfigBundleRequest>,
+    ) -> Result<Response<ConfigBundleReport>, Status> {
+        let req = request.into_inner();
+        let key = config_bundle::pinned_key(&self.config.config_bundle_key)
+            .map_err(|e| Status::failed_precondition(format!("Zero-Trust: {}", e)))?;
+        // Staged next to the other bundles, in the root-only bundle dir
+        let scratch_dir = self.bundle_dir()?.to_path_buf();
+        let (manifest, spec) = tokio::task::spawn_blocking(move || {
+            config_bundle::open(&req.bundle, &req.signature, &key, &scratch_dir)
+        })
+        .await
+        .map_err(|e| Status::internal(format!("[SLA ERROR] Bundle read panicked: {}", e)))?
+        .map_err(|e| {
+            if e.starts_with("SECURITY VIOLATION") {
+                Status::permission_denied(e)
+            } else {
+                Status::invalid_argument(format!("Zero-Trust: {}", e))
+            }
+        })?;
+
+        // 🛡️ Zero-Trust: Every spec passes the RPC validators before anything changes
+        let firewall_name = |rule: &FirewallPolicy| {
+            format!(
+                "{}/{}",
+                rule.port,
+                rule.protocol().as_str_name().to_lowercase()
+            )
+        };
+        for app in &spec.apps {
+            app.validate().map_err(|e| {
+                Status::invalid_argument(format!("app {}: {}", app.domain_name, e.message()))
+            })?;
+        }
+        for rule in &spec.firewall_rules {
+            rule.validate().map_err(|e| {
+                Status::invalid_argument(format!(
+                    "firewall_rule {}: {}",
+                    firewall_name(rule),
+                    e.message()
+                ))
+            })?;
+        }
+        for job in &spec.jobs {
+            job.validate().map_err(|e| {
+                Status::invalid_argument(format!("job {}: {}", job.job_name, e.message()))
             })?;
+        }
 
-        // Step 3: Write systemd unit file with cgroup v2 resource limits
-        let svc_config = ServiceConfig {
-            service_name: service_name.clone(),
-            username: app_user.clone(),
-            working_directory: app_dir.clone(),
-            start_command: req.start_command.clone(),
-            env_vars: req.env_vars.clone(),
-            memory_limit_mb: req.memory_limit_mb as i32,
-            cpu_limit_percent: 100, // Default: full single core
+        // Apps first, so the rules and jobs that refer to them find them
+        let mut items = Vec::new();
+        let item = |kind: &str, name: String, result: Result<Response<AgentResponse>, Status>| {
+            let error = match result {
+                Ok(resp) if resp.get_ref().success => String::new(),
+                Ok(resp) => resp.into_inner().error_message,
+                Err(status) => status.message().to_string(),
+            };
+            ConfigBundleItem {
+                kind: kind.to_string(),
+                name,
+                success: error.is_empty(),
+                error,
+            }
+        };
+        let ok = || {
+            Ok(Response::new(AgentResponse {
+                success: true,
+                ..Default::default()
+            }))
         };
+        for mut app in spec.apps {
+            let name = app.domain_name.clone();
+            app.run_async = false;
+            let result = if req.dry_run {
+                ok()
+            } else {
+                self.provision_jail(app, None).await.map(Response::new)
+            };
+            items.push(item("app", name, result));
+        }
+        for rule in spec.firewall_rules {
+            let name = firewall_name(&rule);
+            let result = if req.dry_run {
+                ok()
+            } else {
+                self.apply_firewall_policy(Request::new(rule)).await
+            };
+            items.push(item("firewall_rule", name, result));
+        }
+        for job in spec.jobs {
+            let name = job.job_name.clone();
+            let result = if req.dry_run {
+                ok()
+            } else {
+                self.schedule_job(Request::new(job)).await
+            };
+            items.push(item("job", name, result));
+        }
 
-        self.svc_mgr
-            .write_unit_file(&svc_config)
+        let failed = items.iter().filter(|i| !i.success).count();
+        info!(
+            "📦 Config bundle from {} {}: {} items, {} failed",
+            manifest.source_host,
+            if req.dry_run { "checked" } else { "applied" },
+            items.len(),
+            failed
+        );
+        Ok(Response::new(ConfigBundleReport {
+            success: failed == 0,
+            source_host: manifest.source_host,
+            created_at: manifest.created_at,
+            items,
+        }))
+    }
+
+    // =========================================================================
+    // 32. 🔑 SSH Host Key Pinning
+    // =========================================================================
+    async fn pin_ssh_host_keys(
+        &self,
+        request: Request<SshHostKeysRequest>,
+    ) -> Result<Response<SshHostKeysResponse>, Status> {
+        let req = request.into_inner();
+        let keys = req
+            .keys
+            .iter()
+            .map(|line| known_hosts::parse_host_key(line))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Status::invalid_argument(format!("Zero-Trust: {}", e)))?;
+        let port = if req.port == 0 { 22 } else { req.port as u16 };
+        let pattern = known_hosts::host_pattern(&req.host, port);
+
+        let path = self.config.known_hosts_file.clone();
+        let written = pattern.clone();
+        let fingerprints = tokio::task::spawn_blocking(move || {
+            known_hosts::pin(&path, &written, &keys)?;
+            Ok::<_, String>(keys.iter().map(|k| k.fingerprint()).collect::<Vec<_>>())
+        })
+        .await
+        .map_err(|e| Status::internal(format!("[SLA ERROR] Pinning panicked: {}", e)))?
+        .map_err(|e| Status::internal(format!("[SLA ERROR] {}", e)))?;
+
+        if fingerprints.is_empty() {
+            info!("🔑 Unpinned SSH host keys for {}", pattern);
+        } else {
+            info!("🔑 Pinned {} for {}", fingerprints.join(", "), pattern);
+        }
+        Ok(Response::new(SshHostKeysResponse {
+            host: pattern,
+            fingerprints,
+        }))
+    }
+
+    // =========================================================================
+    // 33. 🗄️ Artifact Replication
+    // =========================================================================
+    async fn pull_artifact(
+        &self,
+        request: Request<ArtifactRequest>,
+    ) -> Result<Response<Self::PullArtifactStream>, Status> {
+        let digest = request.into_inner().digest;
+        let store = self.artifact_store().ok_or_else(|| {
+            Status::failed_precondition("This host keeps no artifact store (KARI_ARTIFACT_STORE)")
+        })?;
+        let lookup = digest.clone();
+        let (file, artifact, keys) = tokio::task::spawn_blocking(move || {
+            let opened = store.open(&lookup)?;
+            Ok::<_, String>(opened.map(|(file, artifact)| {
+                let keys = store.keys_for(&artifact.digest);
+                (file, artifact, keys)
+            }))
+        })
+        .await
+        .map_err(|e| Status::internal(format!("[SLA ERROR] Artifact task panicked: {}", e)))?
+        .map_err(|e| Status::internal(format!("[SLA ERROR] {}", e)))?
+        .ok_or_else(|| Status::not_found(format!("No artifact sha256:{}", digest)))?;
+
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            let mut file = tokio::fs::File::from_std(file);
+            let mut first = Some(ArtifactChunk {
+                digest: artifact.digest,
+                size_bytes: artifact.size_bytes,
+                keys,
+                data: Vec::new(),
+            });
+            loop {
+                let mut data = vec![0u8; artifacts::TRANSFER_CHUNK_BYTES];
+                let n = match file.read(&mut data).await {
+                    Ok(n) => n,
+                    Err(e) => {
+                        let _ = tx
+                            .send(Err(Status::internal(format!(
+                                "[SLA ERROR] Failed to read artifact: {}",
+                                e
+                            ))))
+                            .await;
+                        return;
+                    }
+                };
+                if n == 0 && first.is_none() {
+                    return;
+                }
+                data.truncate(n);
+                let chunk = match first.take() {
+                    Some(header) => ArtifactChunk { data, ..header },
+                    None => ArtifactChunk {
+                        data,
+                        ..Default::default()
+                    },
+                };
+                if tx.send(Ok(chunk)).await.is_err() {
+                    return; // The receiver went away
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn push_artifact(
+        &self,
+        request: Request<tonic::Streaming<ArtifactChunk>>,
+    ) -> Result<Response<ArtifactReceipt>, Status> {
+        let mut chunks = request.into_inner();
+        let store = self.artifact_store().ok_or_else(|| {
+            Status::failed_precondition("This host keeps no artifact store (KARI_ARTIFACT_STORE)")
+        })?;
+        let header = chunks
+            .message()
+            .await?
+            .ok_or_else(|| Status::invalid_argument("Zero-Trust: no artifact was sent"))?;
+        header.validate()?;
+        if header.digest.is_empty() {
+            return Err(Status::invalid_argument(
+                "Zero-Trust: the first chunk must name the artifact's digest",
+            ));
+        }
+        let mut upload = store
+            .receive(&header.digest)
+            .map_err(|e| Status::internal(format!("[SLA ERROR] {}", e)))?;
+
+        let mut next = Some(header.data);
+        while let Some(data) = next {
+            // Staged writes block; keep them off the runtime's threads
+            upload = tokio::task::spawn_blocking(move || upload.write(&data).map(|_| upload))
+                .await
+                .map_err(|e| {
+                    Status::internal(format!("[SLA ERROR] Artifact task panicked: {}", e))
+                })?
+                .map_err(|e| Status::invalid_argument(format!("Zero-Trust: {}", e)))?;
+            next = match chunks.message().await? {
+                Some(chunk) => {
+                    chunk.validate()?;
+                    if !chunk.digest.is_empty() || !chunk.keys.is_empty() {
+                        return Err(Status::invalid_argument(
+                            "Zero-Trust: only the first chunk names the artifact",
+                        ));
+                    }
+                    Some(chunk.data)
+                }
+                None => None,
+            };
+        }
+
+        let keys = header.keys;
+        let artifact = tokio::task::spawn_blocking(move || upload.finish(&keys))
             .await
-            .map_err(|e| {
-                Status::internal(format!("[SLA ERROR] Unit file creation failed: {}", e))
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Artifact task panicked: {}", e)))?
+            .map_err(|e| Status::invalid_argument(format!("Zero-Trust: {}", e)))?;
+        info!(
+            "🗄️ Received build artifact sha256:{} ({:.1} MiB)",
+            artifact.digest,
+            artifact.size_bytes as f64 / 1_048_576.0
+        );
+        Ok(Response::new(ArtifactReceipt {
+            digest: artifact.digest,
+            size_bytes: artifact.size_bytes,
+        }))
+    }
+
+    // =========================================================================
+    // 34. 🔄 Key Rotation
+    // =========================================================================
+    async fn stream_key_rotation_events(
+        &self,
+        _request: Request<KeyRotationEventsRequest>,
+    ) -> Result<Response<Self::StreamKeyRotationEventsStream>, Status> {
+        let mut events = self.key_rotation_events.subscribe();
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    _ = tx.closed() => break,
+                    event = events.recv() => event,
+                };
+                let event = match event {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!(
+                            "🔄 Key rotation subscriber fell behind; {} events dropped",
+                            missed
+                        );
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let message = KeyRotationEvent {
+                    domain_name: event.domain_name,
+                    key_created_at: event.key_created_at,
+                    csr_pem: event.csr_pem,
+                    detail: event.detail,
+                    at: event.at,
+                };
+                if tx.send(Ok(message)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+    // =========================================================================
+    // 35. 🔎 Issuance Pre-Check
+    // =========================================================================
+    async fn check_issuance(
+        &self,
+        request: Request<IssuanceCheckRequest>,
+    ) -> Result<Response<IssuanceCheck>, Status> {
+        use kari_agent::issuance_check::Diagnosis as ProtoDiagnosis;
+        let req = request.into_inner();
+        let ca_domain = if req.ca_domain.is_empty() {
+            "letsencrypt.org".to_string()
+        } else {
+            req.ca_domain.to_ascii_lowercase()
+        };
+        let expected: Vec<std::net::IpAddr> = if req.expected_addresses.is_empty() {
+            let interfaces = self.network.interfaces().await.map_err(|e| {
+                Status::internal(format!("[SLA ERROR] Reading interfaces failed: {}", e))
             })?;
+            issuance::public_addresses(&interfaces)
+        } else {
+            req.expected_addresses
+                .iter()
+                .filter_map(|addr| addr.parse().ok())
+                .collect()
+        };
 
-        // Step 4: Reload systemd and enable the service
-        self.svc_mgr
-            .reload_daemon()
+        let check =
+            issuance::check(self.dns.as_ref(), &req.domain_name, &ca_domain, &expected).await;
+        info!(
+            "🔎 Issuance pre-check for {}: {:?} ({})",
+            req.domain_name, check.diagnosis, check.detail
+        );
+        let diagnosis = match check.diagnosis {
+            Diagnosis::Ready => ProtoDiagnosis::Ready,
+            Diagnosis::Nxdomain => ProtoDiagnosis::Nxdomain,
+            Diagnosis::NoAddress => ProtoDiagnosis::NoAddress,
+            Diagnosis::PointsElsewhere => ProtoDiagnosis::PointsElsewhere,
+            Diagnosis::CaaForbids => ProtoDiagnosis::CaaForbids,
+            Diagnosis::DnsError => ProtoDiagnosis::DnsError,
+        };
+        Ok(Response::new(IssuanceCheck {
+            diagnosis: diagnosis as i32,
+            detail: check.detail,
+            addresses: check.addresses.iter().map(|ip| ip.to_string()).collect(),
+            caa_domain: check.caa_domain.unwrap_or_default(),
+            caa_records: check.caa_records,
+        }))
+    }
+
+    // =========================================================================
+    // 36. 💽 Disk Health
+    // =========================================================================
+    async fn stream_disk_health_events(
+        &self,
+        _request: Request<DiskHealthEventsRequest>,
+    ) -> Result<Response<Self::StreamDiskHealthEventsStream>, Status> {
+        let mut events = self.disk_health_events.subscribe();
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    _ = tx.closed() => break,
+                    event = events.recv() => event,
+                };
+                let event = match event {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!(
+                            "💽 Disk health subscriber fell behind; {} events dropped",
+                            missed
+                        );
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let message = DiskHealthEvent {
+                    device: event.device,
+                    failing: event.failing,
+                    detail: event.detail,
+                    at: event.at,
+                };
+                if tx.send(Ok(message)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    // =========================================================================
+    // 37. 🔁 Scheduled Reboot
+    // =========================================================================
+    async fn schedule_reboot(
+        &self,
+        request: Request<ScheduleRebootRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let caller = Caller::of(&request);
+        let req = request.into_inner();
+        let window = (!req.window.is_empty())
+            .then(|| RebootWindow::parse(&req.window))
+            .transpose()
+            .map_err(Status::invalid_argument)?;
+        let state = self.state_store.as_ref();
+        let internal =
+            |e: String| Status::internal(format!("[SLA ERROR] State access failed: {}", e));
+        if reboot::pending(state).await.map_err(internal)?.is_some() {
+            return Err(Status::failed_precondition("A reboot is already scheduled"));
+        }
+
+        // Claimed before the operation starts, so a second request is refused
+        let record = RebootRecord {
+            window: req.window.clone(),
+            ..Default::default()
+        };
+        reboot::save(state, &record).await.map_err(internal)?;
+        let grace = Duration::from_secs(req.drain_secs.into());
+        let started = self
+            .spawn_operation(&caller, "schedule_reboot", move |this, op_id| async move {
+                let result = this
+                    .run_reboot(op_id, window, req.only_if_needed, grace)
+                    .await;
+                if result.is_err() {
+                    let state = this.state_store.as_ref();
+                    if let Ok(Some(record)) = reboot::pending(state).await
+                        && let Err(e) = reboot::restore(
+                            state,
+                            this.svc_mgr.as_ref(),
+                            this.proxy_mgr.as_ref(),
+                            &record,
+                        )
+                        .await
+                    {
+                        warn!("🔁 Could not undo the failed reboot: {}", e);
+                    }
+                }
+                result
+            })
+            .await;
+        if started.is_err() {
+            let _ = reboot::forget(state).await;
+        }
+        let started = started?;
+        info!("🔁 Reboot scheduled (window: {:?})", record.window);
+        Ok(Response::new(started))
+    }
+
+    // =========================================================================
+    // 38. 🩺 Certificate Info (OCSP Staple Health)
+    // =========================================================================
+    async fn get_certificate_info(
+        &self,
+        request: Request<CertificateInfoRequest>,
+    ) -> Result<Response<CertificateInfo>, Status> {
+        let req = request.into_inner();
+        validate_domain_name(&req.domain_name)?;
+        let internal =
+            |e: String| Status::internal(format!("[SLA ERROR] Certificate read failed: {}", e));
+
+        // A SAN is answered for with the shared certificate covering it
+        let (name, domains) =
+            match certificates::covering(self.state_store.as_ref(), &req.domain_name)
+                .await
+                .map_err(internal)?
+            {
+                Some(cert) => (cert.name, cert.domains),
+                None => (req.domain_name.clone(), vec![req.domain_name.clone()]),
+            };
+        let details = self
+            .ssl_engine
+            .certificate_details(&name)
             .await
-            .map_err(|e| Status::internal(format!("[SLA ERROR] Daemon reload failed: {}", e)))?;
+            .map_err(internal)?
+            .ok_or_else(|| {
+                Status::not_found(format!(
+                    "No certificate is installed for {}",
+                    req.domain_name
+                ))
+            })?;
+        let apps: Vec<AppRecord> = list_records(self.state_store.as_ref(), NS_APPS)
+            .await
+            .map_err(internal)?;
+        let stapling_enabled = apps.iter().any(|a| {
+            a.domain_name == req.domain_name
+                && a.vhost
+                    .tls
+                    .as_ref()
+                    .is_some_and(|tls| tls.ocsp_stapling.is_some())
+        });
+        let staple = self.ssl_engine.ocsp_staple(&name).await.map_err(internal)?;
+        let now = chrono::Utc::now().timestamp();
+        let staple_healthy = stapling_enabled
+            && staple.status == "good"
+            && staple.next_update.is_none_or(|next| next > now);
+        if details.must_staple && !staple_healthy {
+            warn!(
+                "🩺 {} must be stapled but its staple is not healthy ({})",
+                name, staple.status
+            );
+        }
+
+        Ok(Response::new(CertificateInfo {
+            name,
+            domains,
+            not_after: details.not_after,
+            must_staple: details.must_staple,
+            ocsp_url: details.ocsp_url.unwrap_or_default(),
+            stapling_enabled,
+            staple: Some(OcspStapleStatus {
+                status: staple.status,
+                this_update: staple.this_update.unwrap_or_default(),
+                next_update: staple.next_update.unwrap_or_default(),
+                detail: staple.detail,
+            }),
+            staple_healthy,
+        }))
+    }
+
+    // =========================================================================
+    // 39. 🧾 Managed Configs Export (Read-Only Audit)
+    // =========================================================================
+    async fn export_managed_configs(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<ManagedConfigsResponse>, Status> {
+        let timestamp = chrono::Utc::now().format("%Y%m%d%H%M%S");
+        let archive_path = self.bundle_path(&format!("configs-{}.tar.gz", timestamp))?;
+        let paths = ManagedConfigPaths {
+            systemd_dir: &self.config.systemd_dir,
+            proxy_conf_dir: &self.config.proxy_conf_dir,
+            logrotate_dir: &self.config.logrotate_dir,
+        };
+        let configs = managed_configs::collect(self.state_store.as_ref(), &paths)
+            .await
+            .and_then(|configs| {
+                managed_configs::write_archive(&archive_path, &configs).map(|_| configs)
+            })
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Config export failed: {}", e)))?;
+
+        info!(
+            "🧾 Managed configs exported to {:?} ({} files)",
+            archive_path,
+            configs.len()
+        );
+
+        Ok(Response::new(ManagedConfigsResponse {
+            archive_path: archive_path.to_string_lossy().to_string(),
+            configs: configs
+                .into_iter()
+                .map(|(config, _)| ManagedConfig {
+                    kind: config.kind,
+                    name: config.name,
+                    path: config.path.to_string_lossy().to_string(),
+                    sha256: config.sha256,
+                    size: config.size,
+                    owner: config.owner,
+                    spec_json: config.spec.map(|spec| spec.to_string()).unwrap_or_default(),
+                    redacted: config.redacted,
+                })
+                .collect(),
+        }))
+    }
+
+    // =========================================================================
+    // 40. 🛡️ Privileges (Rootless Mode)
+    // =========================================================================
+    async fn get_privileges(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<PrivilegeReport>, Status> {
+        let privileges = &self.privileges;
+        Ok(Response::new(PrivilegeReport {
+            rootless: privileges.rootless,
+            user: privileges.user.clone(),
+            grants: privileges
+                .grants
+                .iter()
+                .map(|(capability, grant)| PrivilegeGrant {
+                    capability: capability.name().to_string(),
+                    granted: grant.granted(),
+                    via: grant.via.clone(),
+                    missing: grant.missing.clone(),
+                })
+                .collect(),
+            unavailable_rpcs: privileges.unavailable_rpcs(),
+        }))
+    }
+
+    // =========================================================================
+    // 41. 🛡️ App Health (Unit State & MAC Enforcement)
+    // =========================================================================
+    async fn get_app_health(
+        &self,
+        request: Request<AppHealthRequest>,
+    ) -> Result<Response<AppHealth>, Status> {
+        let req = request.into_inner();
+        let app: AppRecord = get_record(self.state_store.as_ref(), NS_APPS, &req.app_id)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?
+            .ok_or_else(|| Status::not_found(format!("Unknown app: {}", req.app_id)))?;
+        let app_dir = Self::secure_join(&self.config.web_root, &app.domain_name)?;
+        let unit_err =
+            |e: String| Status::internal(format!("[SLA ERROR] Unit query failed: {}", e));
+        let active = self
+            .svc_mgr
+            .is_active(&app.service_name)
+            .await
+            .map_err(unit_err)?;
+        let enabled = self
+            .svc_mgr
+            .is_enabled(&app.service_name)
+            .await
+            .map_err(unit_err)?;
+        let mac = self
+            .mac_mgr
+            .status(&MacTarget {
+                app_user: app.app_user.clone(),
+                service_name: app.service_name.clone(),
+                app_dir,
+            })
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] MAC status failed: {}", e)))?;
+
+        Ok(Response::new(AppHealth {
+            app_id: app.app_id,
+            service_name: app.service_name,
+            active,
+            enabled,
+            mac: Some(MacEnforcement {
+                framework: mac.framework,
+                profile: mac.profile,
+                mode: mac.mode,
+                loaded: mac.loaded,
+            }),
+        }))
+    }
+
+    // =========================================================================
+    // 42. 🌳 Jail Processes (Process Tree per App)
+    // =========================================================================
+    async fn list_jail_processes(
+        &self,
+        request: Request<JailProcessesRequest>,
+    ) -> Result<Response<JailProcessList>, Status> {
+        let req = request.into_inner();
+        let apps: Vec<AppRecord> = list_records(self.state_store.as_ref(), NS_APPS)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        let app = apps
+            .into_iter()
+            .find(|app| app.domain_name == req.domain_name)
+            .ok_or_else(|| Status::not_found(format!("No app serves {}", req.domain_name)))?;
+
+        let unit = format!("{}.service", app.service_name);
+        let found =
+            self.processes.unit_processes(&unit).await.map_err(|e| {
+                Status::internal(format!("[SLA ERROR] Process listing failed: {}", e))
+            })?;
+        let processes: Vec<JailProcess> = processes::tree(found)
+            .into_iter()
+            .map(|(depth, p)| JailProcess {
+                pid: p.pid,
+                ppid: p.ppid,
+                depth,
+                user: p.user,
+                command: p.command,
+                cpu_percent: p.cpu_percent,
+                rss_bytes: p.rss_bytes,
+                threads: p.threads,
+            })
+            .collect();
+
+        Ok(Response::new(JailProcessList {
+            service_name: app.service_name,
+            total_cpu_percent: processes.iter().map(|p| p.cpu_percent).sum(),
+            total_rss_bytes: processes.iter().map(|p| p.rss_bytes).sum(),
+            processes,
+        }))
+    }
+
+    // =========================================================================
+    // 43. 🧰 Host Operations (Allowlisted Commands)
+    // =========================================================================
+    async fn run_host_operation(
+        &self,
+        request: Request<HostOperationRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let deadline = Self::request_deadline(request.metadata());
+        let caller = Caller::of(&request);
+        let req = request.into_inner();
+
+        // 🛡️ Zero-Trust: Only named operations run, with their template's argv
+        let operations = host_ops::load(&self.config.agent_toml).map_err(|e| {
+            Status::failed_precondition(format!("Host operations unavailable: {}", e))
+        })?;
+        let operation = operations.get(&req.name).ok_or_else(|| {
+            Status::not_found(format!(
+                "No host operation named '{}' (available: {})",
+                req.name,
+                operations.keys().cloned().collect::<Vec<_>>().join(", ")
+            ))
+        })?;
+        let params = req.params.into_iter().collect();
+        let argv = operation
+            .render(&params)
+            .map_err(|e| Status::invalid_argument(format!("Zero-Trust: {}", e)))?;
+        info!("🧰 Running host operation {}: {:?}", operation.name, argv);
+
+        let cap = Duration::from_secs(self.config.host_op_timeout_secs);
+        if req.run_async {
+            return self
+                .spawn_operation(&caller, "host_operation", move |this, _| async move {
+                    this.run_host_command(&argv[0], &argv[1..], cap).await
+                })
+                .await
+                .map(Response::new);
+        }
+
+        let limit = deadline.map_or(cap, |d| d.min(cap));
+        self.run_host_command(&argv[0], &argv[1..], limit)
+            .await
+            .map(Response::new)
+    }
+
+    // =========================================================================
+    // 44. 🔏 ACME Issuance (Let's Encrypt over HTTP-01)
+    // =========================================================================
+    async fn issue_certificate(
+        &self,
+        request: Request<IssueCertificateRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let caller = Caller::of(&request);
+        let req = request.into_inner();
+
+        // Orders wait on the CA's validators; hand back an operation id if asked
+        if req.run_async {
+            return self
+                .spawn_operation(&caller, "issue_certificate", move |this, _| async move {
+                    this.issue_and_install(req).await
+                })
+                .await
+                .map(Response::new);
+        }
+        self.issue_and_install(req).await.map(Response::new)
+    }
+
+    // =========================================================================
+    // 45. ↪️ Managed Redirects (Answered by the Proxy)
+    // =========================================================================
+    async fn set_redirects(
+        &self,
+        request: Request<SetRedirectsRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let req = request.into_inner();
 
-        self.svc_mgr
-            .enable_and_start(&service_name)
+        let apps: Vec<AppRecord> = list_records(self.state_store.as_ref(), NS_APPS)
             .await
-            .map_err(|e| {
-                Status::internal(format!("[SLA ERROR] Service activation failed: {}", e))
-            })?;
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        let Some(mut app) = apps
+            .into_iter()
+            .find(|app| app.domain_name == req.domain_name)
+        else {
+            return Err(Status::not_found(format!(
+                "No app serves {}",
+                req.domain_name
+            )));
+        };
 
-        // 🛡️ Privacy: Clear the transient env variables from RAM
-        let mut transient_req = req;
-        for (_, mut val) in transient_req.env_vars.drain() {
-            val.zeroize();
+        // Kept with the app, so every later vhost rewrite reapplies them
+        let previous = std::mem::replace(&mut app.vhost.redirects, redirect_rules(&req.rules));
+        if let Some(port) = app.port
+            && let Err(e) = self
+                .proxy_mgr
+                .create_vhost(&app.domain_name, port, &app.vhost)
+                .await
+        {
+            // The proxy refused it; put back the vhost it was serving
+            app.vhost.redirects = previous;
+            let _ = self
+                .proxy_mgr
+                .create_vhost(&app.domain_name, port, &app.vhost)
+                .await;
+            return Err(Status::internal(format!(
+                "[SLA ERROR] Proxy wiring failed: {}",
+                e
+            )));
         }
+        put_record(self.state_store.as_ref(), NS_APPS, &app.app_id, &app)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State update failed: {}", e)))?;
 
         info!(
-            "🔒 Jail provisioned: {} (user: {}, mem: {}MB)",
-            service_name, app_user, transient_req.memory_limit_mb
+            "↪️ {} redirect(s) set for {}",
+            app.vhost.redirects.len(),
+            app.domain_name
         );
-
         Ok(Response::new(AgentResponse {
             success: true,
-            exit_code: 0,
             stdout: format!(
-                "Jail '{}' provisioned with {}MB memory limit",
-                service_name, transient_req.memory_limit_mb
+                "{} redirect(s) for {}",
+                app.vhost.redirects.len(),
+                app.domain_name
             ),
-            stderr: String::new(),
-            error_message: String::new(),
+            ..Default::default()
         }))
     }
 
     // =========================================================================
-    // 4. ⚙️ Service Management (systemd lifecycle)
+    // 46. 🌐 Vhost Inventory (Paged)
     // =========================================================================
-    async fn manage_service(
+    async fn list_vhosts(
         &self,
-        request: Request<ServiceRequest>,
-    ) -> Result<Response<AgentResponse>, Status> {
-        use kari_agent::ServiceAction;
-
+        request: Request<ListRequest>,
+    ) -> Result<Response<VhostList>, Status> {
         let req = request.into_inner();
-        Self::validate_identifier(&req.service_name, "service_name")?;
-
-        // 🛡️ Zero-Trust: Only allow management of kari-prefixed services
-        if !req.service_name.starts_with("kari-") {
-            return Err(Status::permission_denied(
-                "Zero-Trust: Refusing to manage non-Kari service",
-            ));
-        }
+        let fields = Self::field_selector(&req, &["domain_name", "app_id", "tls", "maintenance"])?;
+        let selector = validate_labels(req.label_selector.clone())?;
+        let apps: Vec<AppRecord> = list_records(self.state_store.as_ref(), NS_APPS)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+
+        // An app has a vhost once its ingress is wired
+        let apps = apps
+            .into_iter()
+            .filter(|app| {
+                app.port.is_some()
+                    && matches_labels(&app.labels, &selector)
+                    && fields.accepts("domain_name", &app.domain_name)
+                    && fields.accepts("app_id", &app.app_id)
+                    && fields.accepts("tls", &app.vhost.tls.is_some().to_string())
+                    && fields.accepts("maintenance", &app.vhost.maintenance.to_string())
+            })
+            .collect();
+        let (apps, next_page_token) = Self::page(apps, |app| app.domain_name.clone(), &req)?;
+
+        let vhosts = apps
+            .into_iter()
+            .map(|app| Vhost {
+                port: app.port.map(u32::from).unwrap_or(0),
+                tls: app.vhost.tls.is_some(),
+                maintenance: app.vhost.maintenance,
+                redirect_count: app.vhost.redirects.len() as u32,
+                request_tracing: app.vhost.request_tracing,
+                labels: app.labels.into_iter().collect(),
+                domain_name: app.domain_name,
+                app_id: app.app_id,
+            })
+            .collect();
+        Ok(Response::new(VhostList {
+            vhosts,
+            next_page_token,
+        }))
+    }
 
-        let action = ServiceAction::try_from(req.action)
-            .map_err(|_| Status::invalid_argument("Invalid service action"))?;
+    // =========================================================================
+    // 47. ⏪ Deployment Rollback
+    // =========================================================================
+    async fn rollback_deployment(
+        &self,
+        request: Request<RollbackDeploymentRequest>,
+    ) -> Result<Response<Self::RollbackDeploymentStream>, Status> {
+        let req = request.into_inner();
+        validate_domain_name(&req.domain_name)?;
 
-        let result = match action {
-            ServiceAction::Start => self.svc_mgr.start(&req.service_name).await,
-            ServiceAction::Stop => self.svc_mgr.stop(&req.service_name).await,
-            ServiceAction::Restart => self.svc_mgr.restart(&req.service_name).await,
-            ServiceAction::Reload => self.svc_mgr.reload_daemon().await,
-            ServiceAction::Enable => self.svc_mgr.enable_and_start(&req.service_name).await,
-            ServiceAction::Disable => self.svc_mgr.stop(&req.service_name).await,
+        let app_dir = Self::secure_join(&self.config.web_root, &req.domain_name)?;
+        let current = cleanup::current_release(&app_dir);
+        let releases: Vec<ReleaseRecord> = list_records(self.state_store.as_ref(), NS_RELEASES)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        let releases_dir = app_dir.join("releases");
+        let Some(record) =
+            cleanup::rollback_target(releases, &req.domain_name, current.as_deref(), |release| {
+                releases_dir.join(release).is_dir()
+            })
+        else {
+            return Err(Status::failed_precondition(format!(
+                "No earlier release of {} is left on disk to roll back to",
+                req.domain_name
+            )));
         };
 
-        match result {
-            Ok(()) => {
-                info!(
-                    "⚙️ Service {} action {:?} succeeded",
-                    req.service_name, action
-                );
-                Ok(Response::new(AgentResponse {
-                    success: true,
-                    exit_code: 0,
-                    stdout: format!("Service '{}' action completed", req.service_name),
-                    stderr: String::new(),
-                    error_message: String::new(),
-                }))
-            }
-            Err(e) => Ok(Response::new(AgentResponse {
-                success: false,
-                exit_code: 1,
-                stdout: String::new(),
-                stderr: e.clone(),
-                error_message: format!("[SLA ERROR] Service management failed: {}", e),
-            })),
-        }
+        info!(
+            "⏪ Rolling {} back from {} to {}",
+            req.domain_name,
+            current.as_deref().unwrap_or("nothing"),
+            record.release
+        );
+        Ok(Response::new(self.stream_switch(record, req.trace_id)))
     }
 
     // =========================================================================
-    // 5. 📡 Streaming Deployment (Hardened Blue-Green)
+    // 48. 🧹 Release Hygiene
     // =========================================================================
-    async fn stream_deployment(
+    async fn list_releases(
         &self,
-        request: Request<DeployRequest>,
-    ) -> Result<Response<Self::StreamDeploymentStream>, Status> {
+        request: Request<ListReleasesRequest>,
+    ) -> Result<Response<ReleaseList>, Status> {
         let req = request.into_inner();
+        validate_domain_name(&req.domain_name)?;
 
-        // 🛡️ Zero-Trust: Validate identifiers before processing
-        Self::validate_identifier(&req.app_id, "app_id")?;
-        Self::validate_domain_name(&req.domain_name)?;
-
-        let timestamp = chrono::Utc::now().format("%Y%m%d%H%M%S").to_string();
+        let app_dir = Self::secure_join(&self.config.web_root, &req.domain_name)?;
+        let records: HashMap<String, ReleaseRecord> =
+            list_records::<ReleaseRecord>(self.state_store.as_ref(), NS_RELEASES)
+                .await
+                .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?
+                .into_iter()
+                .filter(|r| r.domain_name == req.domain_name)
+                .map(|r| (r.release.clone(), r))
+                .collect();
+        let current = cleanup::current_release(&app_dir);
+
+        // Sizing walks every release's tree
+        let releases_dir = app_dir.join("releases");
+        let sized = tokio::task::spawn_blocking(move || {
+            cleanup::releases_on_disk(&releases_dir)
+                .into_iter()
+                .map(|release| {
+                    let size = capacity::dir_size_bytes(&releases_dir.join(&release));
+                    (release, size)
+                })
+                .collect::<Vec<_>>()
+        })
+        .await
+        .map_err(|e| Status::internal(format!("[SLA ERROR] Release scan failed: {}", e)))?;
+
+        let releases = sized
+            .into_iter()
+            .map(|(release, size_bytes)| ReleaseInfo {
+                created_at: chrono::NaiveDateTime::parse_from_str(&release, "%Y%m%d%H%M%S")
+                    .map(|t| t.and_utc().timestamp())
+                    .unwrap_or_default(),
+                size_bytes,
+                active: current.as_deref() == Some(release.as_str()),
+                activated_at: records
+                    .get(&release)
+                    .and_then(|r| r.activated_at)
+                    .unwrap_or_default(),
+                image: records
+                    .get(&release)
+                    .and_then(|r| r.image.clone())
+                    .unwrap_or_default(),
+                release,
+            })
+            .collect();
+        Ok(Response::new(ReleaseList { releases }))
+    }
 
-        let base_dir = Self::secure_join(&self.config.web_root, &req.domain_name)?;
-        let release_dir = base_dir.join("releases").join(&timestamp);
-        let app_user = format!("kari-app-{}", req.app_id);
+    async fn prune_releases(
+        &self,
+        request: Request<PruneReleasesRequest>,
+    ) -> Result<Response<PruneReleasesResponse>, Status> {
+        let req = request.into_inner();
+        validate_domain_name(&req.domain_name)?;
 
-        let (tx, rx) = mpsc::channel(512);
+        let keep = match req.keep {
+            0 => self.config.releases_keep,
+            keep => keep as usize,
+        };
+        let pruned = self
+            .prune_releases_of(&req.domain_name, keep)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Release pruning failed: {}", e)))?;
+        Ok(Response::new(PruneReleasesResponse {
+            pruned: pruned as u32,
+        }))
+    }
 
-        // 🛡️ Clone Arcs for the background task
-        let git = Arc::clone(&self.git_mgr);
-        let jail = Arc::clone(&self.jail_mgr);
-        let build = Arc::clone(&self.build_mgr);
-        let svc = Arc::clone(&self.svc_mgr);
-        let proxy = Arc::clone(&self.proxy_mgr);
+    // =========================================================================
+    // 49. 🛑 Deployment Cancellation
+    // =========================================================================
+    async fn cancel_deployment(
+        &self,
+        request: Request<CancelDeploymentRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let caller = Caller::of(&request);
+        let req = request.into_inner();
+        validate_identifier(&req.trace_id, "trace_id")?;
+
+        let Some(cancel) = self
+            .cancellable_deploys
+            .lock()
+            .unwrap()
+            .remove(&req.trace_id)
+        else {
+            return Err(Status::not_found(format!(
+                "No cancellable deployment under trace id {} (finished, or already switching live)",
+                req.trace_id
+            )));
+        };
+        if cancel.send(()).is_err() {
+            return Err(Status::failed_precondition(format!(
+                "Deployment {} finished before the cancellation arrived",
+                req.trace_id
+            )));
+        }
 
-        tokio::spawn(async move {
-            let t = req.trace_id.clone();
-            let log = |m: &str| LogChunk {
-                content: m.to_string(),
-                trace_id: t.clone(),
-            };
+        info!("🛑 Deployment {} cancelled by {}", req.trace_id, caller);
+        Ok(Response::new(AgentResponse {
+            success: true,
+            ..Default::default()
+        }))
+    }
 
-            // -- Step 1: Secure Git Clone --
-            let ssh_cred = req.ssh_key.map(ProviderCredential::from_string);
-            let _ = tx.send(Ok(log("📦 Pulling source...\n"))).await;
-            if let Err(e) = git
-                .clone_repo(&req.repo_url, &req.branch, &release_dir, ssh_cred)
+    // =========================================================================
+    // 50. 🛰️ Satellites (agentless hosts driven over SSH)
+    // =========================================================================
+    async fn list_satellites(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<SatelliteList>, Status> {
+        let configs = satellite::load(&self.config.agent_toml)
+            .map_err(|e| Status::failed_precondition(format!("Satellites unavailable: {}", e)))?;
+        let mut satellites = Vec::with_capacity(configs.len());
+        for config in configs.into_values() {
+            let key_stored = self
+                .secret_store
+                .get_secret(&satellite::key_secret(&config.name))
                 .await
-            {
-                let _ = tx.send(Ok(log(&format!("❌ Git Error: {}\n", e)))).await;
-                return;
-            }
-
-            // -- Step 2: Permissions Jailing --
-            // (ssh_cred ownership transferred to clone_repo; zeroized on drop)
-            let _ = tx.send(Ok(log("🔒 Securing directory...\n"))).await;
-            if let Err(e) = jail.secure_directory(&release_dir, &app_user).await {
-                let _ = tx
-                    .send(Ok(log(&format!("❌ Security Error: {}\n", e))))
-                    .await;
-                return;
-            }
+                .map_err(|e| Status::internal(format!("[SLA ERROR] Secret lookup failed: {}", e)))?
+                .map(ProviderCredential::destroy)
+                .is_some();
+            satellites.push(SatelliteInfo {
+                name: config.name,
+                host: config.host,
+                port: u32::from(config.port),
+                user: config.user,
+                proxy: config.proxy.name().to_string(),
+                key_stored,
+            });
+        }
+        Ok(Response::new(SatelliteList { satellites }))
+    }
 
-            // -- Step 3: Isolated Build --
-            let _ = tx.send(Ok(log("🏗️ Executing build...\n"))).await;
-            let mut envs: HashMap<String, String> = req.env_vars.into_iter().collect();
-            let build_res = build
-                .execute_build(
-                    &req.build_command,
-                    &release_dir,
-                    &app_user,
-                    &envs,
-                    tx.clone(),
-                    t.clone(),
-                )
-                .await;
+    async fn set_satellite_key(
+        &self,
+        request: Request<SatelliteKeyRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let caller = Caller::of(&request);
+        let req = request.into_inner();
+        self.satellite_config(&req.satellite)?;
+
+        // 🛡️ Privacy: The request buffer moves straight into the ProviderCredential.
+        let key = ProviderCredential::from_utf8(req.private_key)
+            .map_err(|e| Status::invalid_argument(format!("private_key is {}", e)))?;
+        if !key.use_secret(|k| {
+            k.trim_start().starts_with("-----BEGIN ") && k.contains("PRIVATE KEY-----")
+        }) {
+            return Err(Status::invalid_argument(
+                "Zero-Trust: private_key is not a PEM or OpenSSH private key",
+            ));
+        }
+        self.secret_store
+            .put_secret(&satellite::key_secret(&req.satellite), key)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Secret storage failed: {}", e)))?;
 
-            // 🛡️ Privacy: Clear the build environment variables from RAM
-            for (_, mut val) in envs.drain() {
-                val.zeroize();
-            }
+        info!(
+            "🛰️ SSH key stored for satellite {} by {}",
+            req.satellite, caller
+        );
+        Ok(Response::new(AgentResponse {
+            success: true,
+            stdout: format!("SSH key stored for satellite {}", req.satellite),
+            ..Default::default()
+        }))
+    }
 
-            if let Err(e) = build_res {
-                let _ = tx.send(Ok(log(&format!("❌ Build Error: {}\n", e)))).await;
-                return;
-            }
+    async fn apply_satellite_firewall_policy(
+        &self,
+        request: Request<SatelliteFirewallRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        let caller = Caller::of(&request);
+        let req = request.into_inner();
+        let policy = req
+            .policy
+            .ok_or_else(|| Status::invalid_argument("policy is required"))?;
+        let intent = firewall_intent(&policy, &caller)?;
+
+        let satellite = self.satellite(&req.satellite).await?;
+        satellite
+            .firewall
+            .apply_policy(&intent)
+            .await
+            .map_err(|e| {
+                Status::internal(format!(
+                    "[SLA ERROR] Satellite firewall policy failed: {}",
+                    e
+                ))
+            })?;
 
-            // -- Step 4: Proxy & Service Activation --
-            let service_name = format!("kari-{}", req.domain_name);
-            let _ = tx
-                .send(Ok(log("🌐 Updating Proxy & Restarting...\n")))
-                .await;
+        info!(
+            "🛰️ Firewall rule for port {} applied on satellite {} by {}",
+            policy.port, req.satellite, caller
+        );
+        Ok(Response::new(AgentResponse {
+            success: true,
+            stdout: format!(
+                "Firewall rule applied on {}: port {}",
+                req.satellite, policy.port
+            ),
+            ..Default::default()
+        }))
+    }
 
-            let port = req.port.unwrap_or(3000) as u16;
-            // 🛡️ Zero-Trust: ProxyManager implementation strictly validates domain_name to prevent Nginx/Apache injection.
-            // This is Defense-in-Depth as validate_identifier() also checks it upstream.
-            if let Err(e) = proxy.create_vhost(&req.domain_name, port).await {
-                let _ = tx.send(Ok(log(&format!("❌ Proxy Error: {}\n", e)))).await;
-                return;
-            }
+    async fn manage_satellite_service(
+        &self,
+        request: Request<SatelliteServiceRequest>,
+    ) -> Result<Response<AgentResponse>, Status> {
+        use kari_agent::ServiceAction;
 
-            if let Err(e) = svc.restart(&service_name).await {
-                let _ = tx
-                    .send(Ok(log(&format!("❌ Service Error: {}\n", e))))
-                    .await;
-                return;
-            }
+        let caller = Caller::of(&request);
+        let req = request.into_inner();
+        let service = req
+            .service
+            .ok_or_else(|| Status::invalid_argument("service is required"))?;
+        let action = ServiceAction::try_from(service.action)
+            .map_err(|_| Status::invalid_argument("Invalid service action"))?;
 
-            let _ = tx.send(Ok(log("✅ Deployment successful.\n"))).await;
-        });
+        let satellite = self.satellite(&req.satellite).await?;
+        // 🛡️ Zero-Trust: kari-* units, plus the ones agent.toml lists for this satellite
+        if !service.service_name.starts_with("kari-")
+            && !satellite.config.services.contains(&service.service_name)
+        {
+            return Err(Status::permission_denied(format!(
+                "Zero-Trust: '{}' is not among satellite {}'s services",
+                service.service_name, req.satellite
+            )));
+        }
 
-        Ok(Response::new(ReceiverStream::new(rx)))
+        let result = run_service_action(&satellite.services, &service.service_name, action).await;
+        match result {
+            Ok(()) => {
+                info!(
+                    "🛰️ Service {} action {:?} succeeded on satellite {} (by {})",
+                    service.service_name, action, req.satellite, caller
+                );
+                Ok(Response::new(AgentResponse {
+                    success: true,
+                    stdout: format!(
+                        "Service '{}' action completed on {}",
+                        service.service_name, req.satellite
+                    ),
+                    ..Default::default()
+                }))
+            }
+            Err(e) => Ok(Response::new(AgentResponse {
+                success: false,
+                exit_code: 1,
+                error_message: format!("[SLA ERROR] Satellite service management failed: {}", e),
+                stderr: e,
+                ..Default::default()
+            })),
+        }
     }
 
-    // =========================================================================
-    // 6. 🔥 Resource Teardown (Clean Hygiene)
-    // =========================================================================
-    async fn delete_deployment(
+    async fn set_satellite_vhost(
         &self,
-        request: Request<DeleteRequest>,
+        request: Request<SatelliteVhostRequest>,
     ) -> Result<Response<AgentResponse>, Status> {
+        let caller = Caller::of(&request);
         let req = request.into_inner();
-
-        // 🛡️ Zero-Trust: Validate inputs
-        Self::validate_identifier(&req.app_id, "app_id")?;
-        Self::validate_domain_name(&req.domain_name)?;
-
-        let app_dir = Self::secure_join(&self.config.web_root, &req.domain_name)?;
-        let app_user = format!("kari-app-{}", req.app_id);
-        let service_name = format!("kari-{}", req.domain_name);
-
-        // 🛡️ Deterministic Cleanup Order: Service → Proxy → User → Files
-        let _ = self.svc_mgr.stop(&service_name).await;
-        let _ = self.svc_mgr.remove_unit_file(&service_name).await;
-        let _ = self.proxy_mgr.remove_vhost(&req.domain_name).await;
-        let _ = self.jail_mgr.deprovision_app_user(&app_user).await;
-
-        if app_dir.exists() {
-            tokio::fs::remove_dir_all(&app_dir).await.map_err(|e| {
-                Status::internal(format!(
-                    "[SLA ERROR] Filesystem purge failed for {}: {}",
-                    req.domain_name, e
-                ))
-            })?;
-        }
+        validate_domain_name(&req.domain_name)?;
+
+        let satellite = self.satellite(&req.satellite).await?;
+        let (result, done) = if req.remove {
+            (
+                satellite.proxy.remove_vhost(&req.domain_name).await,
+                "removed",
+            )
+        } else {
+            let port = validate_port(req.target_port, "target_port")?;
+            let options = VhostOptions::default();
+            (
+                satellite
+                    .proxy
+                    .create_vhost(&req.domain_name, port, &options)
+                    .await,
+                "configured",
+            )
+        };
+        result
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Satellite proxy failed: {}", e)))?;
 
         info!(
-            "🔥 Deployment torn down: {} (user: {})",
-            service_name, app_user
+            "🛰️ Vhost {} {} on satellite {} by {}",
+            req.domain_name, done, req.satellite, caller
         );
-
         Ok(Response::new(AgentResponse {
             success: true,
+            stdout: format!("Vhost {} {} on {}", req.domain_name, done, req.satellite),
             ..Default::default()
         }))
     }
 
     // =========================================================================
-    // 6b. 🔥 Teardown Jail (Force-Stop PID Namespace)
+    // 51. 🐞 Debug Port Exposure (time-limited, revoked by a kari timer)
     // =========================================================================
-    async fn teardown_jail(
+    async fn expose_debug_port(
         &self,
-        request: Request<TeardownRequest>,
+        request: Request<ExposeDebugPortRequest>,
     ) -> Result<Response<AgentResponse>, Status> {
+        let caller = Caller::of(&request);
         let req = request.into_inner();
+        validate_domain_name(&req.domain_name)?;
+        let port = validate_port(req.port, "port")?;
+        let source_ip = req
+            .source_ip
+            .parse::<std::net::IpAddr>()
+            .map_err(|_| {
+                Status::invalid_argument(format!(
+                    "Zero-Trust: Invalid source IP: '{}'",
+                    req.source_ip
+                ))
+            })?
+            .to_string();
+        let duration_secs = match req.duration_secs {
+            0 => DEFAULT_DEBUG_PORT_SECS,
+            secs => secs,
+        };
 
-        // 🛡️ Zero-Trust: Validate input
-        Self::validate_identifier(&req.app_id, "app_id")?;
+        let apps: Vec<AppRecord> = list_records(self.state_store.as_ref(), NS_APPS)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        if !apps.iter().any(|app| app.domain_name == req.domain_name) {
+            return Err(Status::not_found(format!(
+                "No app serves {}",
+                req.domain_name
+            )));
+        }
 
-        let service_name = format!("kari-app-{}", req.app_id);
+        // Lapsed exposures go first: one lapsing now is not extended by this call
+        let now = chrono::Utc::now().timestamp();
+        firewall::revoke_expired(
+            self.state_store.as_ref(),
+            self.firewall_mgr.as_ref(),
+            self.job_scheduler.as_ref(),
+            now,
+        )
+        .await
+        .map_err(|e| {
+            Status::internal(format!("[SLA ERROR] Debug port revocation failed: {}", e))
+        })?;
 
-        // 🛡️ SIGKILL via systemctl stop — this tears down the entire cgroup scope,
-        // killing all child processes in the jail's PID namespace.
-        let output = tokio::process::Command::new("systemctl")
-            .args(["stop", "--no-block", &service_name])
-            .output()
+        let policy = TraitFirewallPolicy {
+            action: FirewallAction::Allow,
+            port,
+            protocol: Protocol::Tcp,
+            source_ip: Some(source_ip.clone()),
+            description: Some(format!("debug port for {}", req.domain_name)),
+            created_by: Some(caller.to_string()),
+            log_denied: false,
+        };
+        let key = policy_rule_key(&policy);
+        let existing =
+            get_record::<FirewallRuleRecord>(self.state_store.as_ref(), NS_FIREWALL_RULES, &key)
+                .await
+                .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        // 🛡️ Extending reuses the installed rule, comment and all, so revoking
+        // it later deletes exactly what is in the chain
+        let (policy, created_at) = match existing {
+            Some(rule) if rule.expires_at.is_none() => {
+                return Err(Status::already_exists(format!(
+                    "Conflict: a permanent rule already covers port {} from {}",
+                    port, source_ip
+                )));
+            }
+            Some(rule) => (
+                record_policy(&rule).map_err(|e| {
+                    Status::internal(format!("[SLA ERROR] Stored rule unreadable: {}", e))
+                })?,
+                Some(rule.created_at),
+            ),
+            None => (policy, None),
+        };
+        let expires_at = now + i64::from(duration_secs);
+
+        self.firewall_mgr
+            .apply_policy(&policy)
             .await
-            .map_err(|e| Status::internal(format!("[SLA ERROR] Teardown failed: {}", e)))?;
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Firewall policy failed: {}", e)))?;
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            // Not fatal — service may already be stopped
-            warn!("⚠️ Teardown warning for {}: {}", service_name, stderr);
+        // 🛡️ A hole nothing would close must not stay open
+        let job = firewall::debug_port_job(&key);
+        let scheduled = match std::env::current_exe() {
+            Ok(agent) => {
+                let lapse = chrono::DateTime::from_timestamp(expires_at, 0).unwrap_or_default();
+                self.job_scheduler
+                    .schedule_job(&TraitJobIntent {
+                        name: job.clone(),
+                        binary: agent.to_string_lossy().to_string(),
+                        args: vec![firewall::REVOKE_EXPIRED_FLAG.to_string()],
+                        schedule: lapse.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+                        run_as_user: "root".to_string(),
+                    })
+                    .await
+            }
+            Err(e) => Err(format!("Agent binary unknown: {}", e)),
+        };
+        let record = FirewallRuleRecord {
+            action: "allow".to_string(),
+            port,
+            protocol: protocol_name(policy.protocol).to_string(),
+            source_ip: policy.source_ip.clone(),
+            labels: [("debug-port-for".to_string(), req.domain_name.clone())].into(),
+            description: policy.description.clone(),
+            created_by: policy.created_by.clone(),
+            created_at: created_at.unwrap_or(now),
+            log_denied: false,
+            expires_at: Some(expires_at),
+        };
+        let stored = match scheduled {
+            Ok(()) => put_record(self.state_store.as_ref(), NS_FIREWALL_RULES, &key, &record).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = stored {
+            // An extension keeps its earlier timer and record
+            if created_at.is_none() {
+                let _ = self.firewall_mgr.remove_policy(&policy).await;
+                let _ = self.job_scheduler.unschedule_job(&job).await;
+            }
+            return Err(Self::unit_error("Debug port exposure failed", e));
         }
 
+        let until = chrono::DateTime::from_timestamp(expires_at, 0)
+            .unwrap_or_default()
+            .to_rfc3339();
         info!(
-            "🔥 Jail torn down: {} (trace: {})",
-            service_name, req.trace_id
+            "🐞 Port {} of {} open to {} until {} (by {})",
+            port, req.domain_name, source_ip, until, caller
         );
-
         Ok(Response::new(AgentResponse {
             success: true,
-            exit_code: 0,
-            stdout: format!("Jail '{}' teardown initiated", service_name),
-            stderr: String::new(),
-            error_message: String::new(),
+            stdout: format!("Port {} open to {} until {}", port, source_ip, until),
+            ..Default::default()
         }))
     }
 
     // =========================================================================
-    // 7. 📝 Filesystem Operations (Zero-Trust Path Validation)
+    // 52. 📦 Artifact Deploys (a verified tar.gz in place of the clone)
     // =========================================================================
-    async fn write_system_file(
+    async fn deploy_artifact(
         &self,
-        request: Request<FileWriteRequest>,
-    ) -> Result<Response<AgentResponse>, Status> {
-        let req = request.into_inner();
-
-        // 🛡️ Zero-Trust: Validate path is within allowed directories
-        let path = std::path::Path::new(&req.absolute_path);
-        let allowed_prefixes = [
-            self.config.web_root.as_path(),
-            self.config.ssl_storage_dir.as_path(),
-            self.config.proxy_conf_dir.as_path(),
-            self.config.systemd_dir.as_path(),
-        ];
-
-        let is_allowed = allowed_prefixes
-            .iter()
-            .any(|prefix| path.starts_with(prefix));
-        if !is_allowed {
-            return Err(Status::permission_denied(format!(
-                "Zero-Trust: Path '{}' is outside all allowed boundaries",
-                req.absolute_path
-            )));
+        request: Request<tonic::Streaming<DeployArtifactChunk>>,
+    ) -> Result<Response<Self::DeployArtifactStream>, Status> {
+        let mut chunks = request.into_inner();
+        let header = chunks
+            .message()
+            .await?
+            .ok_or_else(|| Status::invalid_argument("Zero-Trust: no archive was sent"))?;
+        header.validate()?;
+        let DeployArtifactChunk {
+            deploy,
+            sha256,
+            url,
+            strip_components,
+            data,
+        } = header;
+        let Some(deploy) = deploy else {
+            return Err(Status::invalid_argument(
+                "Zero-Trust: the first chunk must carry the deploy",
+            ));
+        };
+        if sha256.is_empty() {
+            return Err(Status::invalid_argument(
+                "Zero-Trust: the first chunk must name the archive's sha256",
+            ));
         }
-
-        // 🛡️ Zero-Trust: Prevent path traversal
-        if req.absolute_path.contains("..") {
+        let url = Some(url).filter(|url| !url.is_empty());
+        if url.is_some() && !data.is_empty() {
             return Err(Status::invalid_argument(
-                "Zero-Trust: Path traversal detected",
+                "Zero-Trust: an archive is either uploaded or fetched from url, not both",
             ));
         }
 
-        // Write the content
-        if let Some(parent) = path.parent() {
-            tokio::fs::create_dir_all(parent).await.map_err(|e| {
-                Status::internal(format!("[SLA ERROR] Directory creation failed: {}", e))
-            })?;
-        }
+        let mut job = self.prepare_deploy(deploy).await?;
+        job.source = DeploySource::Archive(Box::new(ArchiveSource {
+            sha256,
+            url,
+            strip_components: strip_components as usize,
+            first: data,
+            rest: chunks,
+        }));
+        self.run_deployment(job).map(Response::new)
+    }
 
-        tokio::fs::write(path, &req.content)
+    // =========================================================================
+    // 53. 🔐 Permission Audits (ownership and 0750 across an app's tree)
+    // =========================================================================
+    async fn verify_app_permissions(
+        &self,
+        request: Request<VerifyAppPermissionsRequest>,
+    ) -> Result<Response<AppPermissionsReport>, Status> {
+        let req = request.into_inner();
+        let apps: Vec<AppRecord> = list_records(self.state_store.as_ref(), NS_APPS)
             .await
-            .map_err(|e| Status::internal(format!("[SLA ERROR] File write failed: {}", e)))?;
-
-        // Apply file mode
-        if !req.file_mode.is_empty() {
-            let mode = u32::from_str_radix(&req.file_mode, 8)
-                .map_err(|_| Status::invalid_argument("Invalid octal file mode"))?;
-            let mut perms = tokio::fs::metadata(path)
-                .await
-                .map_err(|e| Status::internal(format!("[SLA ERROR] Metadata read failed: {}", e)))?
-                .permissions();
-            perms.set_mode(mode);
-            tokio::fs::set_permissions(path, perms).await.map_err(|e| {
-                Status::internal(format!("[SLA ERROR] Permission set failed: {}", e))
-            })?;
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        let app = apps
+            .into_iter()
+            .find(|app| app.domain_name == req.domain_name)
+            .ok_or_else(|| Status::not_found(format!("No app serves {}", req.domain_name)))?;
+        let app_dir = Self::secure_join(&self.config.web_root, &app.domain_name)?;
+        if !app_dir.is_dir() {
+            return Err(Status::failed_precondition(format!(
+                "{} has no app directory",
+                app.domain_name
+            )));
         }
 
-        // Apply ownership
-        if !req.owner.is_empty() {
-            let owner_arg = if !req.group.is_empty() {
-                format!("{}:{}", req.owner, req.group)
-            } else {
-                req.owner.clone()
-            };
-
-            let output = tokio::process::Command::new("chown")
-                .args(["-P", &owner_arg, &req.absolute_path])
-                .output()
+        let audit_failed =
+            |e: String| Status::internal(format!("[SLA ERROR] Permission audit failed: {}", e));
+        let mut audit = self
+            .jail_mgr
+            .audit_directory(&app_dir, &app.app_user)
+            .await
+            .map_err(audit_failed)?;
+        let mut repaired = 0;
+        if req.repair && audit.deviating > 0 {
+            self.jail_mgr
+                .secure_directory(&app_dir, &app.app_user)
                 .await
-                .map_err(|e| Status::internal(format!("[SLA ERROR] chown failed: {}", e)))?;
+                .map_err(|e| {
+                    Status::internal(format!("[SLA ERROR] Permission repair failed: {}", e))
+                })?;
+            let after = self
+                .jail_mgr
+                .audit_directory(&app_dir, &app.app_user)
+                .await
+                .map_err(audit_failed)?;
+            repaired = audit.deviating.saturating_sub(after.deviating);
+            info!(
+                "🔐 Repaired permissions of {}: {} path(s) fixed, {} still deviating",
+                app.domain_name, repaired, after.deviating
+            );
+            audit = after;
+        }
+        Ok(Response::new(Self::permissions_report(
+            &app.app_user,
+            audit,
+            repaired,
+        )))
+    }
 
-            if !output.status.success() {
-                return Err(Status::internal(format!(
-                    "[SLA ERROR] Ownership change failed: {}",
-                    String::from_utf8_lossy(&output.stderr)
-                )));
-            }
+    // =========================================================================
+    // 54. 🗑️ Log Erasure (everything kept for a deleted tenant's domains)
+    // =========================================================================
+    async fn purge_tenant_logs(
+        &self,
+        request: Request<PurgeTenantLogsRequest>,
+    ) -> Result<Response<LogPurgeReport>, Status> {
+        let req = request.into_inner();
+        let apps: Vec<AppRecord> = list_records(self.state_store.as_ref(), NS_APPS)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        // 🛡️ Erasure is for what is gone: a live app would only log again
+        if let Some(app) = apps.iter().find(|app| {
+            app.tenant_id.as_deref() == Some(req.tenant_id.as_str())
+                || req.domain_names.contains(&app.domain_name)
+        }) {
+            return Err(Status::failed_precondition(format!(
+                "{} is still deployed (tenant {}); delete it first",
+                app.domain_name,
+                app.tenant_id.as_deref().unwrap_or("none")
+            )));
         }
 
+        let policy = self.config.log_retention();
+        let domains = req.domain_names.clone();
+        let purged =
+            tokio::task::spawn_blocking(move || log_retention::purge_domains(&policy, &domains))
+                .await
+                .map_err(|e| Status::internal(format!("[SLA ERROR] Purge task failed: {}", e)))?
+                .map_err(|e| Status::internal(format!("[SLA ERROR] Log purge failed: {}", e)))?;
         info!(
-            "📝 File written: {} (trace: {})",
-            req.absolute_path, req.trace_id
+            "🗑️ Purged the logs of tenant {} ({} domain(s)): {} file(s)",
+            req.tenant_id,
+            req.domain_names.len(),
+            purged.iter().map(|purge| purge.files).sum::<u64>()
         );
+        Ok(Response::new(Self::log_purge_report(purged)))
+    }
 
-        Ok(Response::new(AgentResponse {
-            success: true,
-            exit_code: 0,
-            stdout: format!("File written to {}", req.absolute_path),
-            stderr: String::new(),
-            error_message: String::new(),
+    // =========================================================================
+    // 55. 🧭 Placement Hints (one load score per host for the control plane)
+    // =========================================================================
+    async fn get_placement_score(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<PlacementScore>, Status> {
+        let committed = capacity::committed_resources(self.state_store.as_ref(), None)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        let web_root = self.config.web_root.clone();
+        let (physical, pressure, disk_used_percent, disk_free_mb) =
+            tokio::task::spawn_blocking(move || {
+                (
+                    capacity::physical_resources(),
+                    placement::read_pressure(Path::new("/proc/pressure")),
+                    capacity::disk_usage_percent(&web_root),
+                    capacity::free_disk_mb(&web_root),
+                )
+            })
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] Host probe panicked: {}", e)))?;
+
+        // The same limits ProvisionAppJail enforces (see capacity::check_reservation)
+        let ratio = self.config.overcommit_ratio;
+        let scaled = |value: u64, ratio: f64| (value as f64 * ratio) as u64;
+        let limit = capacity::Reservations {
+            memory_mb: scaled(physical.memory_mb, ratio.unwrap_or(1.0)),
+            cpu_percent: ratio.map_or(physical.cpu_percent, |ratio| {
+                scaled(physical.cpu_percent, ratio)
+            }),
+        };
+        let deploys_in_flight = self.deploys_in_flight.load(Ordering::Relaxed);
+        let inputs = PlacementInputs {
+            committed,
+            limit,
+            pressure,
+            disk_used_percent,
+            disk_free_mb,
+            min_free_disk_mb: self.config.min_free_disk_mb,
+            deploys_in_flight,
+            cores: physical.cpu_percent / 100,
+        };
+        let placement = placement::score(&inputs);
+        Ok(Response::new(PlacementScore {
+            score: placement.score,
+            model: placement::MODEL,
+            accepting: placement.accepting,
+            committed: placement.committed,
+            pressure: placement.pressure,
+            disk: placement.disk,
+            queue: placement.queue,
+            committed_memory_mb: committed.memory_mb,
+            memory_limit_mb: limit.memory_mb,
+            committed_cpu_percent: committed.cpu_percent,
+            cpu_limit_percent: limit.cpu_percent,
+            cpu_pressure: pressure.cpu,
+            memory_pressure: pressure.memory,
+            io_pressure: pressure.io,
+            disk_free_mb: disk_free_mb.unwrap_or(0),
+            deploys_in_flight: deploys_in_flight as u32,
         }))
     }
 
     // =========================================================================
-    // 7. 🔐 SSL Certificate Installation (Privacy-First: Zeroize on Drop)
+    // 56. 📰 App Logs (the unit's journal, tailed or followed)
     // =========================================================================
-    async fn install_certificate(
+    async fn stream_service_logs(
         &self,
-        request: Request<SslPayload>,
-    ) -> Result<Response<AgentResponse>, Status> {
+        request: Request<ServiceLogsRequest>,
+    ) -> Result<Response<Self::StreamServiceLogsStream>, Status> {
         let req = request.into_inner();
-
-        // 🛡️ Zero-Trust: Validate domain
-        Self::validate_domain_name(&req.domain_name)?;
-
-        // 🛡️ Privacy: Wrap the private key in a Zeroizing buffer.
-        // When this drops, the memory is physically overwritten with 0x00.
-        let privkey_bytes = Zeroizing::new(req.privkey_pem);
-
-        // Convert protobuf payload to our trait's SslPayload
-        let trait_payload = TraitSslPayload {
-            domain_name: req.domain_name.clone(),
-            fullchain_pem: String::from_utf8(req.fullchain_pem)
-                .map_err(|_| Status::invalid_argument("fullchain_pem is not valid UTF-8"))?,
-            privkey_pem: ProviderCredential::from_string(
-                String::from_utf8(privkey_bytes.to_vec())
-                    .map_err(|_| Status::invalid_argument("privkey_pem is not valid UTF-8"))?,
-            ),
-        };
-
-        self.ssl_engine
-            .install_certificate(trait_payload)
+        let apps: Vec<AppRecord> = list_records(self.state_store.as_ref(), NS_APPS)
             .await
-            .map_err(|e| {
-                Status::internal(format!(
-                    "[SLA ERROR] Certificate installation failed: {}",
-                    e
-                ))
-            })?;
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        // 🛡️ Only a managed app's unit: the name is never taken from the caller
+        let app = apps
+            .into_iter()
+            .find(|app| app.domain_name == req.domain_name)
+            .ok_or_else(|| Status::not_found(format!("No app serves {}", req.domain_name)))?;
+
+        let query = JournalQuery {
+            unit: format!("{}.service", app.service_name),
+            since: (req.since_unix > 0).then_some(req.since_unix),
+            until: (req.until_unix > 0).then_some(req.until_unix),
+            max_priority: req
+                .max_priority
+                .map_or(journal::ALL_PRIORITIES, |priority| priority as u8),
+            lines: match req.lines {
+                0 => DEFAULT_SERVICE_LOG_LINES,
+                lines => lines,
+            },
+            follow: req.follow,
+        };
 
-        info!("🔐 Certificate installed for domain: {}", req.domain_name);
+        let (tx, rx) = mpsc::channel(64);
+        let (entry_tx, mut entry_rx) = mpsc::channel(256);
+        let reader = self.journal.clone();
+        tokio::spawn(async move {
+            let read = reader.read(&query, entry_tx);
+            tokio::pin!(read);
+            let mut outcome = None;
+            loop {
+                tokio::select! {
+                    _ = tx.closed() => return,
+                    result = &mut read, if outcome.is_none() => outcome = Some(result),
+                    entry = entry_rx.recv() => match entry {
+                        Some(entry) => {
+                            let message = ServiceLogEntry {
+                                timestamp_unix_us: entry.timestamp_us as i64,
+                                priority: u32::from(entry.priority),
+                                message: entry.message,
+                                pid: entry.pid,
+                            };
+                            if tx.send(Ok(message)).await.is_err() {
+                                return;
+                            }
+                        }
+                        // The reader is done and everything it sent is out
+                        None => break,
+                    },
+                }
+            }
+            let outcome = match outcome {
+                Some(outcome) => outcome,
+                None => read.await,
+            };
+            if let Err(e) = outcome {
+                let _ = tx
+                    .send(Err(Status::internal(format!(
+                        "[SLA ERROR] Journal read failed: {}",
+                        e
+                    ))))
+                    .await;
+            }
+        });
 
-        Ok(Response::new(AgentResponse {
-            success: true,
-            exit_code: 0,
-            stdout: format!("SSL certificate installed for {}", req.domain_name),
-            stderr: String::new(),
-            error_message: String::new(),
-        }))
+        Ok(Response::new(ReceiverStream::new(rx)))
     }
 
     // =========================================================================
-    // 8. 🛡️ Firewall Policy Enforcement
+    // 57. 📂 SFTP-Only Access (uploads without a deploy)
     // =========================================================================
-    async fn apply_firewall_policy(
+    async fn set_sftp_access(
         &self,
-        request: Request<FirewallPolicy>,
-    ) -> Result<Response<AgentResponse>, Status> {
-        use kari_agent::firewall_policy::{Action, Protocol as ProtoProtocol};
-
+        request: Request<SftpAccessRequest>,
+    ) -> Result<Response<SftpAccess>, Status> {
         let req = request.into_inner();
+        let apps: Vec<AppRecord> = list_records(self.state_store.as_ref(), NS_APPS)
+            .await
+            .map_err(|e| Status::internal(format!("[SLA ERROR] State read failed: {}", e)))?;
+        let app = apps
+            .into_iter()
+            .find(|app| app.domain_name == req.domain_name)
+            .ok_or_else(|| Status::not_found(format!("No app serves {}", req.domain_name)))?;
+        let keys = req
+            .authorized_keys
+            .iter()
+            .map(|key| sftp::parse_authorized_key(key))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Status::invalid_argument)?;
 
-        // 🛡️ Zero-Trust: Map proto enums to our strict trait types
-        let action = match Action::try_from(req.action) {
-            Ok(Action::Allow) => FirewallAction::Allow,
-            Ok(Action::Deny) => FirewallAction::Deny,
-            Ok(Action::Reject) => FirewallAction::Reject,
-            Err(_) => return Err(Status::invalid_argument("Invalid firewall action")),
-        };
-
-        let protocol = match ProtoProtocol::try_from(req.protocol) {
-            Ok(ProtoProtocol::Tcp) => Protocol::Tcp,
-            Ok(ProtoProtocol::Udp) => Protocol::Udp,
-            Ok(ProtoProtocol::Both) => Protocol::Both,
-            Err(_) => return Err(Status::invalid_argument("Invalid protocol")),
-        };
+        if keys.is_empty() {
+            self.sftp_mgr.revoke(&app.app_user).await.map_err(|e| {
+                Status::internal(format!("[SLA ERROR] SFTP revocation failed: {}", e))
+            })?;
+            info!("📂 SFTP access revoked for {}", app.domain_name);
+            return Ok(Response::new(SftpAccess {
+                enabled: false,
+                username: app.app_user,
+                ..Default::default()
+            }));
+        }
 
-        // 🛡️ Zero-Trust: Parse and validate source IP if provided
-        let source_ip = if let Some(ref ip_str) = req.source_ip {
-            if ip_str.is_empty() {
-                None
-            } else {
-                // Validate as IP address first
-                let _ = ip_str.parse::<std::net::IpAddr>().map_err(|_| {
-                    Status::invalid_argument(format!("Zero-Trust: Invalid source IP: '{}'", ip_str))
+        let shared_dir = Self::secure_join(&self.config.web_root, &app.domain_name)?.join("shared");
+        if !shared_dir.exists() {
+            self.jail_mgr
+                .secure_directory(&shared_dir, &app.app_user)
+                .await
+                .map_err(|e| {
+                    Status::internal(format!("[SLA ERROR] Directory jailing failed: {}", e))
                 })?;
-                Some(ip_str.clone())
-            }
-        } else {
-            None
-        };
-
-        let policy = TraitFirewallPolicy {
-            action,
-            port: req.port as u16,
-            protocol,
-            source_ip,
-        };
-
-        self.firewall_mgr
-            .apply_policy(&policy)
+        }
+        let key_fingerprints = keys.iter().map(sftp::AuthorizedKey::fingerprint).collect();
+        self.sftp_mgr
+            .grant(&SftpGrant {
+                app_user: app.app_user.clone(),
+                domain_name: app.domain_name.clone(),
+                shared_dir,
+                keys,
+            })
             .await
-            .map_err(|e| Status::internal(format!("[SLA ERROR] Firewall policy failed: {}", e)))?;
+            .map_err(|e| Status::internal(format!("[SLA ERROR] SFTP grant failed: {}", e)))?;
+        info!(
+            "📂 SFTP access granted for {} ({} keys)",
+            app.domain_name,
+            req.authorized_keys.len()
+        );
 
-        Ok(Response::new(AgentResponse {
-            success: true,
-            exit_code: 0,
-            stdout: format!("Firewall rule applied: port {}", req.port),
-            stderr: String::new(),
-            error_message: String::new(),
+        Ok(Response::new(SftpAccess {
+            enabled: true,
+            username: app.app_user,
+            directory: sftp::SFTP_HOME.to_string(),
+            key_fingerprints,
         }))
     }
 
     // =========================================================================
-    // 9. ⏰ Job Scheduling (Zero-Trust Cron via systemd timers)
+    // 58. 🩹 OS Patching (maintenance-window upgrades)
     // =========================================================================
-    async fn schedule_job(
+    async fn set_patch_policy(
         &self,
-        request: Request<JobIntent>,
-    ) -> Result<Response<AgentResponse>, Status> {
+        request: Request<PatchPolicyRequest>,
+    ) -> Result<Response<PatchPolicy>, Status> {
         let req = request.into_inner();
-
-        // 🛡️ Zero-Trust: Validate all fields
-        Self::validate_identifier(&req.job_name, "job_name")?;
-        Self::validate_identifier(&req.run_as_user, "run_as_user")?;
-
-        if req.binary.is_empty() {
-            return Err(Status::invalid_argument(
-                "Zero-Trust: Binary path cannot be empty",
-            ));
-        }
-
-        // 🛡️ Zero-Trust: Reject binaries with shell metacharacters
-        if req.binary.contains(';') || req.binary.contains('&') || req.binary.contains('|') {
-            return Err(Status::permission_denied(
-                "Zero-Trust: Shell metacharacters detected in binary path",
-            ));
+        let state = self.state_store.as_ref();
+        let internal =
+            |e: String| Status::internal(format!("[SLA ERROR] Patch policy update failed: {}", e));
+        let current = patching::policy(state).await.map_err(internal)?;
+
+        if !req.enabled {
+            if let Some(current) = current {
+                self.patcher
+                    .restore_distro_automation(&current.handed_over)
+                    .await
+                    .map_err(internal)?;
+                patching::clear_policy(state).await.map_err(internal)?;
+                info!(
+                    "🩹 Patch policy removed; handed back: {:?}",
+                    current.handed_over
+                );
+            }
+            return Ok(Response::new(patch_policy_message(None)));
         }
 
-        let intent = TraitJobIntent {
-            name: req.job_name.clone(),
-            binary: req.binary,
-            args: req.args,
-            schedule: req.schedule_expression,
-            run_as_user: req.run_as_user,
+        // The distro's automation is turned off once, by the first policy
+        let handed_over = match current {
+            Some(current) => current.handed_over,
+            None => self
+                .patcher
+                .disable_distro_automation()
+                .await
+                .map_err(internal)?,
         };
-
-        self.job_scheduler
-            .schedule_job(&intent)
+        let policy = patching::PatchPolicy {
+            window: req.window,
+            scope: match PatchScope::try_from(req.scope) {
+                Ok(PatchScope::Full) => patching::PatchScope::Full,
+                _ => patching::PatchScope::Security,
+            },
+            drain_secs: req.drain_secs,
+            handed_over,
+        };
+        patching::save_policy(state, &policy)
             .await
-            .map_err(|e| Status::internal(format!("[SLA ERROR] Job scheduling failed: {}", e)))?;
-
-        info!("⏰ Job scheduled: {}", req.job_name);
+            .map_err(internal)?;
+        info!(
+            "🩹 Patch policy set: {:?} upgrades in {}",
+            policy.scope, policy.window
+        );
+        Ok(Response::new(patch_policy_message(Some(&policy))))
+    }
 
-        Ok(Response::new(AgentResponse {
-            success: true,
-            exit_code: 0,
-            stdout: format!("Job '{}' scheduled successfully", req.job_name),
-            stderr: String::new(),
-            error_message: String::new(),
+    async fn get_patch_reports(
+        &self,
+        request: Request<PatchReportsRequest>,
+    ) -> Result<Response<PatchReports>, Status> {
+        let req = request.into_inner();
+        let state = self.state_store.as_ref();
+        let internal =
+            |e: String| Status::internal(format!("[SLA ERROR] State read failed: {}", e));
+        let policy = patching::policy(state).await.map_err(internal)?;
+        let mut runs = patching::runs(state).await.map_err(internal)?;
+        if req.limit > 0 {
+            runs.truncate(req.limit as usize);
+        }
+        Ok(Response::new(PatchReports {
+            policy: Some(patch_policy_message(policy.as_ref())),
+            runs: runs
+                .into_iter()
+                .map(|run| PatchRun {
+                    started_unix: run.started_at,
+                    finished_unix: run.finished_at,
+                    scope: patch_scope(run.scope),
+                    pending: run.pending,
+                    changes: run
+                        .changes
+                        .into_iter()
+                        .map(|change| PackageChange {
+                            name: change.name,
+                            from_version: change.from,
+                            to_version: change.to,
+                        })
+                        .collect(),
+                    apps_drained: run.drained,
+                    reboot_needed: run.reboot_needed,
+                    error: run.error,
+                })
+                .collect(),
         }))
     }
 }
@@ -844,31 +8036,6 @@ mod tests {
         assert!(KariAgentService::secure_join(base, "win\\path").is_err());
     }
 
-    #[test]
-    fn test_validate_identifier_valid() {
-        assert!(KariAgentService::validate_identifier("my-app", "app_id").is_ok());
-        assert!(KariAgentService::validate_identifier("app_v1", "app_id").is_ok());
-        assert!(KariAgentService::validate_identifier("site.com", "domain").is_ok());
-        assert!(KariAgentService::validate_identifier("12345", "id").is_ok());
-    }
-
-    #[test]
-    fn test_validate_identifier_invalid() {
-        // Empty
-        assert!(KariAgentService::validate_identifier("", "field").is_err());
-
-        // Spaces
-        assert!(KariAgentService::validate_identifier("my app", "field").is_err());
-
-        // Special characters
-        assert!(KariAgentService::validate_identifier("app!", "field").is_err());
-        assert!(KariAgentService::validate_identifier("app@domain", "field").is_err());
-
-        // Path characters
-        assert!(KariAgentService::validate_identifier("path/to", "field").is_err());
-        assert!(KariAgentService::validate_identifier("..", "field").is_err());
-    }
-
     #[test]
     fn test_secure_join_allows_nginx_injection_chars() {
         // This test documents the vulnerability: secure_join prevents traversal but allows
@@ -883,7 +8050,7 @@ mod tests {
         assert!(result.is_ok());
 
         // However, validate_identifier SHOULD reject it
-        assert!(KariAgentService::validate_identifier(malicious_input, "domain").is_err());
+        assert!(validate_identifier(malicious_input, "domain").is_err());
     }
 
     // This test simulates the logic used in stream_deployment to ensure
@@ -905,7 +8072,7 @@ mod tests {
 
         for domain in malicious_domains {
             // Check if validate_domain_name catches it (it should)
-            let result = KariAgentService::validate_domain_name(domain);
+            let result = validate_domain_name(domain);
             assert!(
                 result.is_err(),
                 "validate_domain_name failed to reject malicious domain: '{}'",
@@ -921,7 +8088,7 @@ mod tests {
             "example.com.",
         ];
         for domain in invalid_edge_cases {
-            let result = KariAgentService::validate_domain_name(domain);
+            let result = validate_domain_name(domain);
             assert!(
                 result.is_err(),
                 "validate_domain_name failed to reject invalid start/end chars: '{}'",
@@ -933,7 +8100,7 @@ mod tests {
         let valid_domains = vec!["example.com", "sub.example.com", "my-site.net", "v1.api.io"];
 
         for domain in valid_domains {
-            let result = KariAgentService::validate_domain_name(domain);
+            let result = validate_domain_name(domain);
             assert!(
                 result.is_ok(),
                 "validate_domain_name rejected valid domain: '{}'",
@@ -941,4 +8108,24 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_request_deadline_parsing() {
+        let deadline = |raw: &str| {
+            let mut md = MetadataMap::new();
+            md.insert("grpc-timeout", raw.parse().unwrap());
+            KariAgentService::request_deadline(&md)
+        };
+
+        assert_eq!(deadline("30S"), Some(Duration::from_secs(30)));
+        assert_eq!(deadline("2M"), Some(Duration::from_secs(120)));
+        assert_eq!(deadline("1500m"), Some(Duration::from_millis(1500)));
+        assert_eq!(deadline("S"), None);
+        assert_eq!(deadline("10X"), None);
+        assert_eq!(deadline("123456789S"), None);
+        assert_eq!(
+            KariAgentService::request_deadline(&MetadataMap::new()),
+            None
+        );
+    }
 }
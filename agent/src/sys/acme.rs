@@ -0,0 +1,601 @@
+// agent/src/sys/acme.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Certificates straight from an ACME CA.
+//
+// RFC 8555 over the host's curl, with the account key and every JWS signature
+// handled by the host's openssl (ES256 on P-256). Domains are proven with
+// HTTP-01: the ProxyManager serves each key authorization from port 80 for as
+// long as the CA needs it. The CSR comes from SslEngine::stage_next_key, so
+// the certificate's private key is generated here and never crosses the wire;
+// only the account key (0600, beside the agent's state) is long-lived.
+
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+use crate::sys::traits::{CertificateIssuer, ProxyManager};
+
+pub const LETS_ENCRYPT_DIRECTORY: &str = "https://acme-v02.api.letsencrypt.org/directory";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const POLL_ATTEMPTS: u32 = 45;
+
+pub struct AcmeEngine {
+    directory_url: String,
+    contact: Option<String>, // Mail address for expiry and policy notices
+    account_key: PathBuf,
+}
+
+impl AcmeEngine {
+    pub fn new(directory_url: String, contact: Option<String>, account_key: PathBuf) -> Self {
+        Self {
+            directory_url,
+            contact,
+            account_key,
+        }
+    }
+}
+
+// ==============================================================================
+// HTTP (curl)
+// ==============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HttpResponse {
+    status: u16,
+    headers: Vec<(String, String)>, // Names lowercased
+    body: Vec<u8>,
+}
+
+impl HttpResponse {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn json(&self) -> Result<Value, String> {
+        serde_json::from_slice(&self.body).map_err(|e| format!("CA sent invalid JSON: {}", e))
+    }
+}
+
+/// curl's `--include` output: interim (1xx) responses are skipped.
+fn parse_response(raw: &[u8]) -> Result<HttpResponse, String> {
+    let mut rest = raw;
+    loop {
+        let end = rest
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .ok_or("truncated HTTP response")?;
+        let head = String::from_utf8_lossy(&rest[..end]).into_owned();
+        rest = &rest[end + 4..];
+        let mut lines = head.lines();
+        let status: u16 = lines
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|code| code.parse().ok())
+            .ok_or("malformed HTTP status line")?;
+        if (100..200).contains(&status) {
+            continue;
+        }
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+            .collect();
+        return Ok(HttpResponse {
+            status,
+            headers,
+            body: rest.to_vec(),
+        });
+    }
+}
+
+/// One HTTPS exchange. `body` is a JWS for POSTs; None sends a HEAD or GET.
+async fn http(method: &str, url: &str, body: Option<&[u8]>) -> Result<HttpResponse, String> {
+    let mut command = Command::new("curl");
+    command
+        .args(["--silent", "--show-error", "--include", "--proto", "=https"])
+        .arg("--max-time")
+        .arg(REQUEST_TIMEOUT.as_secs().to_string());
+    match (method, body) {
+        ("HEAD", _) => command.arg("--head"),
+        (_, Some(_)) => command.args([
+            "--header",
+            "Content-Type: application/jose+json",
+            "--data-binary",
+            "@-",
+        ]),
+        _ => &mut command,
+    };
+    let mut child = command
+        .args(["--url", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run curl: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(body.unwrap_or_default())
+            .await
+            .map_err(|e| format!("Failed to feed curl: {}", e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|e| format!("Failed to run curl: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    parse_response(&output.stdout)
+}
+
+// ==============================================================================
+// Account key & JWS (openssl)
+// ==============================================================================
+
+fn b64(data: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(data)
+}
+
+/// ES256 wants r‖s, 32 bytes each; openssl signs in DER
+/// (SEQUENCE { INTEGER r, INTEGER s }).
+fn der_signature_to_raw(der: &[u8]) -> Option<[u8; 64]> {
+    let (&0x30, rest) = der.split_first()? else {
+        return None;
+    };
+    let (_, mut rest) = rest.split_first()?; // Signatures are always under 128 bytes
+    let mut raw = [0u8; 64];
+    for half in raw.chunks_mut(32) {
+        let (&0x02, after) = rest.split_first()? else {
+            return None;
+        };
+        let (&len, after) = after.split_first()?;
+        let int = after.get(..len as usize)?;
+        rest = &after[len as usize..];
+        let int = &int[int.iter().take_while(|b| **b == 0).count()..];
+        if int.len() > 32 {
+            return None;
+        }
+        half[32 - int.len()..].copy_from_slice(int);
+    }
+    Some(raw)
+}
+
+/// The certificate request's DER, from its PEM.
+fn csr_der(csr_pem: &str) -> Result<Vec<u8>, String> {
+    let body: String = csr_pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+    STANDARD
+        .decode(body.trim())
+        .map_err(|e| format!("CSR is not valid PEM: {}", e))
+}
+
+/// JWK thumbprint (RFC 7638): members in lexical order, no whitespace.
+fn thumbprint(jwk: &Value) -> String {
+    let canonical = format!(
+        r#"{{"crv":"{}","kty":"{}","x":"{}","y":"{}"}}"#,
+        jwk["crv"].as_str().unwrap_or_default(),
+        jwk["kty"].as_str().unwrap_or_default(),
+        jwk["x"].as_str().unwrap_or_default(),
+        jwk["y"].as_str().unwrap_or_default()
+    );
+    b64(&Sha256::digest(canonical.as_bytes()))
+}
+
+async fn openssl(args: &[&str], key: &Path, input: &[u8]) -> Result<Vec<u8>, String> {
+    let mut child = Command::new("openssl")
+        .args(args)
+        .arg(key)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run openssl: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input)
+            .await
+            .map_err(|e| format!("Failed to feed openssl: {}", e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|e| format!("Failed to run openssl: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "openssl {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// The account key, generated on first use. 🛡️ 0600 before openssl writes to it.
+async fn account_key(path: &Path) -> Result<(), String> {
+    if path.exists() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+        .map_err(|e| format!("Failed to create ACME account key: {}", e))?;
+    let generated = openssl(
+        &[
+            "genpkey",
+            "-algorithm",
+            "EC",
+            "-pkeyopt",
+            "ec_paramgen_curve:P-256",
+            "-out",
+        ],
+        path,
+        &[],
+    )
+    .await;
+    if let Err(e) = generated {
+        let _ = std::fs::remove_file(path);
+        return Err(e);
+    }
+    info!("🔏 Generated ACME account key at {}", path.display());
+    Ok(())
+}
+
+/// The account key's public half as a JWK. The DER SubjectPublicKeyInfo of a
+/// P-256 key ends in the uncompressed point 0x04‖x‖y.
+async fn account_jwk(path: &Path) -> Result<Value, String> {
+    let der = openssl(&["pkey", "-pubout", "-outform", "DER", "-in"], path, &[]).await?;
+    let point = der
+        .len()
+        .checked_sub(65)
+        .map(|start| &der[start..])
+        .filter(|point| point[0] == 0x04)
+        .ok_or("ACME account key is not a P-256 key")?;
+    Ok(json!({
+        "crv": "P-256",
+        "kty": "EC",
+        "x": b64(&point[1..33]),
+        "y": b64(&point[33..]),
+    }))
+}
+
+// ==============================================================================
+// RFC 8555 session
+// ==============================================================================
+
+struct Session<'a> {
+    key: &'a Path,
+    jwk: Value,
+    kid: Option<String>, // The account URL, once registered
+    nonce: Option<String>,
+    new_nonce: String,
+}
+
+/// The problem document's detail, for errors.
+fn problem(response: &HttpResponse) -> String {
+    let detail = response
+        .json()
+        .ok()
+        .and_then(|p| {
+            Some(format!(
+                "{} ({})",
+                p["detail"].as_str()?,
+                p["type"].as_str().unwrap_or("")
+            ))
+        })
+        .unwrap_or_else(|| String::from_utf8_lossy(&response.body).trim().to_string());
+    format!("HTTP {}: {}", response.status, detail)
+}
+
+impl Session<'_> {
+    async fn nonce(&mut self) -> Result<String, String> {
+        if let Some(nonce) = self.nonce.take() {
+            return Ok(nonce);
+        }
+        let response = http("HEAD", &self.new_nonce, None).await?;
+        response
+            .header("replay-nonce")
+            .map(str::to_string)
+            .ok_or_else(|| "CA sent no Replay-Nonce".to_string())
+    }
+
+    /// A signed POST; None is POST-as-GET. A rejected nonce is retried once,
+    /// as RFC 8555 §6.5 expects.
+    async fn post(&mut self, url: &str, payload: Option<&Value>) -> Result<HttpResponse, String> {
+        let payload = payload.map(|p| b64(p.to_string().as_bytes()));
+        for attempt in 0..2 {
+            let mut protected = json!({
+                "alg": "ES256",
+                "nonce": self.nonce().await?,
+                "url": url,
+            });
+            match &self.kid {
+                Some(kid) => protected["kid"] = json!(kid),
+                None => protected["jwk"] = self.jwk.clone(),
+            }
+            let protected = b64(protected.to_string().as_bytes());
+            let payload = payload.clone().unwrap_or_default();
+            let der = openssl(
+                &["dgst", "-sha256", "-sign"],
+                self.key,
+                format!("{}.{}", protected, payload).as_bytes(),
+            )
+            .await?;
+            let signature =
+                der_signature_to_raw(&der).ok_or("openssl produced an unreadable signature")?;
+            let body = json!({
+                "protected": protected,
+                "payload": payload,
+                "signature": b64(&signature),
+            });
+            let response = http("POST", url, Some(body.to_string().as_bytes())).await?;
+            self.nonce = response.header("replay-nonce").map(str::to_string);
+            if response.status < 400 {
+                return Ok(response);
+            }
+            let bad_nonce = response
+                .json()
+                .is_ok_and(|p| p["type"] == "urn:ietf:params:acme:error:badNonce");
+            if !bad_nonce || attempt == 1 {
+                return Err(format!("{}: {}", url, problem(&response)));
+            }
+        }
+        unreachable!("the second attempt always returns")
+    }
+
+    /// Polls `url` until its status leaves `pending`/`processing`.
+    async fn poll(&mut self, url: &str) -> Result<Value, String> {
+        for _ in 0..POLL_ATTEMPTS {
+            let object = self.post(url, None).await?.json()?;
+            if !matches!(object["status"].as_str(), Some("pending" | "processing")) {
+                return Ok(object);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        Err(format!("{} did not settle in time", url))
+    }
+}
+
+impl AcmeEngine {
+    async fn open(&self) -> Result<(Session<'_>, Value), String> {
+        account_key(&self.account_key).await?;
+        let directory = http("GET", &self.directory_url, None).await?;
+        if directory.status != 200 {
+            return Err(format!("{}: {}", self.directory_url, problem(&directory)));
+        }
+        let directory = directory.json()?;
+        let endpoint = |name: &str| {
+            directory[name]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| format!("ACME directory has no {}", name))
+        };
+        let mut session = Session {
+            key: &self.account_key,
+            jwk: account_jwk(&self.account_key).await?,
+            kid: None,
+            nonce: None,
+            new_nonce: endpoint("newNonce")?,
+        };
+        // Registering an existing key just returns its account URL
+        let mut account = json!({ "termsOfServiceAgreed": true });
+        if let Some(contact) = &self.contact {
+            account["contact"] = json!([format!("mailto:{}", contact)]);
+        }
+        let registered = session
+            .post(&endpoint("newAccount")?, Some(&account))
+            .await?;
+        session.kid = Some(
+            registered
+                .header("location")
+                .ok_or("CA sent no account URL")?
+                .to_string(),
+        );
+        Ok((session, directory))
+    }
+
+    /// Answers every pending authorization of the order over HTTP-01.
+    async fn authorize(
+        &self,
+        session: &mut Session<'_>,
+        authorizations: &[Value],
+        proxy: &dyn ProxyManager,
+        published: &mut Vec<(String, String)>,
+    ) -> Result<(), String> {
+        let account = thumbprint(&session.jwk);
+        for url in authorizations.iter().filter_map(Value::as_str) {
+            let authz = session.post(url, None).await?.json()?;
+            let domain = authz["identifier"]["value"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            if authz["status"] == "valid" {
+                continue;
+            }
+            let challenge = authz["challenges"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find(|c| c["type"] == "http-01")
+                .ok_or_else(|| format!("CA offered no HTTP-01 challenge for {}", domain))?;
+            let token = challenge["token"].as_str().unwrap_or_default().to_string();
+            let challenge_url = challenge["url"].as_str().unwrap_or_default().to_string();
+
+            proxy
+                .publish_challenge(&domain, &token, &format!("{}.{}", token, account))
+                .await?;
+            published.push((domain.clone(), token));
+            session.post(&challenge_url, Some(&json!({}))).await?;
+
+            let settled = session.poll(url).await?;
+            if settled["status"] != "valid" {
+                let detail = settled["challenges"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .find_map(|c| c["error"]["detail"].as_str())
+                    .unwrap_or("no detail");
+                return Err(format!(
+                    "{} failed validation ({}): {}",
+                    domain, settled["status"], detail
+                ));
+            }
+            info!("🔏 {} validated over HTTP-01", domain);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CertificateIssuer for AcmeEngine {
+    async fn issue(
+        &self,
+        domains: &[String],
+        csr_pem: &str,
+        proxy: &dyn ProxyManager,
+    ) -> Result<String, String> {
+        let csr = csr_der(csr_pem)?;
+        let (mut session, directory) = self.open().await?;
+        let new_order = directory["newOrder"]
+            .as_str()
+            .ok_or("ACME directory has no newOrder")?;
+
+        let identifiers: Vec<Value> = domains
+            .iter()
+            .map(|d| json!({ "type": "dns", "value": d }))
+            .collect();
+        let created = session
+            .post(new_order, Some(&json!({ "identifiers": identifiers })))
+            .await?;
+        let order_url = created
+            .header("location")
+            .ok_or("CA sent no order URL")?
+            .to_string();
+        let order = created.json()?;
+
+        let mut published = Vec::new();
+        let authorized = self
+            .authorize(
+                &mut session,
+                order["authorizations"]
+                    .as_array()
+                    .map_or(&[], Vec::as_slice),
+                proxy,
+                &mut published,
+            )
+            .await;
+        for (domain, token) in published {
+            if let Err(e) = proxy.withdraw_challenge(&domain, &token).await {
+                warn!("🔏 Could not withdraw challenge for {}: {}", domain, e);
+            }
+        }
+        authorized?;
+
+        let finalize = order["finalize"]
+            .as_str()
+            .ok_or("CA sent no finalize URL")?;
+        session
+            .post(finalize, Some(&json!({ "csr": b64(&csr) })))
+            .await?;
+        let order = session.poll(&order_url).await?;
+        if order["status"] != "valid" {
+            return Err(format!(
+                "Order ended {}: {}",
+                order["status"],
+                order["error"]["detail"].as_str().unwrap_or("no detail")
+            ));
+        }
+        let certificate = order["certificate"]
+            .as_str()
+            .ok_or("CA sent no certificate URL")?;
+        let chain = session.post(certificate, None).await?;
+        String::from_utf8(chain.body).map_err(|_| "CA sent a non-PEM certificate".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curl_output_parses_past_interim_responses() {
+        let raw = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/2 201 \r\nReplay-Nonce: abc_-1\r\nLocation: https://ca.test/acct/7\r\n\r\n{\"status\":\"valid\"}";
+        let response = parse_response(raw).unwrap();
+        assert_eq!(response.status, 201);
+        assert_eq!(response.header("replay-nonce"), Some("abc_-1"));
+        assert_eq!(response.header("location"), Some("https://ca.test/acct/7"));
+        assert_eq!(response.json().unwrap()["status"], "valid");
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\n").is_err());
+    }
+
+    #[test]
+    fn der_signatures_become_fixed_width() {
+        // r has a sign-padding zero, s is short and gets left-padded
+        let mut der = vec![0x30, 0x45, 0x02, 0x21, 0x00];
+        der.extend([0xAA; 32]);
+        der.extend([0x02, 0x20 - 1]);
+        der.extend([0xBB; 31]);
+        der[1] = (der.len() - 2) as u8;
+        let raw = der_signature_to_raw(&der).unwrap();
+        assert_eq!(&raw[..32], &[0xAA; 32]);
+        assert_eq!(raw[32], 0);
+        assert_eq!(&raw[33..], &[0xBB; 31]);
+        assert!(der_signature_to_raw(&[0x31, 0x00]).is_none());
+    }
+
+    #[tokio::test]
+    async fn account_keys_sign_what_their_jwk_verifies() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = dir.path().join("acme").join("account.key");
+        account_key(&key).await.unwrap();
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(
+            std::fs::metadata(&key).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        let jwk = account_jwk(&key).await.unwrap();
+        assert_eq!(
+            URL_SAFE_NO_PAD
+                .decode(jwk["x"].as_str().unwrap())
+                .unwrap()
+                .len(),
+            32
+        );
+        assert_eq!(thumbprint(&jwk).len(), 43);
+
+        let der = openssl(&["dgst", "-sha256", "-sign"], &key, b"header.payload")
+            .await
+            .unwrap();
+        assert!(der_signature_to_raw(&der).is_some());
+        // A second call keeps the key
+        account_key(&key).await.unwrap();
+        assert_eq!(account_jwk(&key).await.unwrap(), jwk);
+
+        let csr =
+            "-----BEGIN CERTIFICATE REQUEST-----\nMIIB\nAA==\n-----END CERTIFICATE REQUEST-----\n";
+        assert!(csr_der(csr).is_ok());
+    }
+}
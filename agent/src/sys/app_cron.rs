@@ -0,0 +1,82 @@
+// agent/src/sys/app_cron.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Reconciles an app's declared cron entries
+// with the kari job timers it owns. The state store remembers which timers
+// belong to which app, so a redeploy can retire entries the new spec dropped.
+
+use crate::sys::state::{JobRecord, NS_APP_JOBS, NS_JOBS, get_record, job_unit_holder, put_record};
+use crate::sys::traits::{JobIntent, JobScheduler, StateStore};
+
+/// Timer name for an app-owned cron entry: `{app_id}-{name}`.
+pub fn app_job_name(app_id: &str, entry_name: &str) -> String {
+    format!("{}-{}", app_id, entry_name)
+}
+
+/// Replaces every timer owned by `app_id` with `intents`.
+///
+/// 🛡️ SLA: Entries whose timer another owner holds are refused before anything
+/// is written. New timers are written first. If any of them fails, the timers this
+/// call created are rolled back and the previous set keeps running untouched;
+/// stale timers are only removed once the whole new set is in place.
+pub async fn sync_app_jobs(
+    scheduler: &dyn JobScheduler,
+    state: &dyn StateStore,
+    app_id: &str,
+    intents: &[JobIntent],
+) -> Result<(), String> {
+    let previous: Vec<String> = get_record(state, NS_APP_JOBS, app_id)
+        .await?
+        .unwrap_or_default();
+
+    // 🛡️ Refuse up-front when an entry's timer already belongs to someone else
+    for intent in intents {
+        if let Some(holder) = job_unit_holder(state, &intent.name, Some(app_id)).await? {
+            return Err(format!(
+                "Cron entry '{}' rejected: Conflict: its timer already belongs to {}",
+                intent.name, holder
+            ));
+        }
+    }
+
+    let mut created = Vec::new();
+    for intent in intents {
+        if let Err(e) = scheduler.schedule_job(intent).await {
+            for name in created.iter().filter(|n| !previous.contains(n)) {
+                let _ = scheduler.unschedule_job(name).await;
+            }
+            return Err(format!("Cron entry '{}' rejected: {}", intent.name, e));
+        }
+        created.push(intent.name.clone());
+    }
+
+    put_record(state, NS_APP_JOBS, app_id, &created).await?;
+    for intent in intents {
+        let record = JobRecord {
+            app_id: Some(app_id.to_string()),
+            ..JobRecord::from(intent)
+        };
+        put_record(state, NS_JOBS, &intent.name, &record).await?;
+    }
+
+    for stale in previous.iter().filter(|n| !created.contains(n)) {
+        scheduler.unschedule_job(stale).await?;
+        state.delete(NS_JOBS, stale).await?;
+    }
+    Ok(())
+}
+
+/// Removes every timer owned by `app_id` (teardown path).
+pub async fn remove_app_jobs(
+    scheduler: &dyn JobScheduler,
+    state: &dyn StateStore,
+    app_id: &str,
+) -> Result<(), String> {
+    let owned: Vec<String> = get_record(state, NS_APP_JOBS, app_id)
+        .await?
+        .unwrap_or_default();
+    for name in &owned {
+        scheduler.unschedule_job(name).await?;
+        state.delete(NS_JOBS, name).await?;
+    }
+    state.delete(NS_APP_JOBS, app_id).await
+}
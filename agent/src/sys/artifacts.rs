@@ -0,0 +1,651 @@
+// agent/src/sys/artifacts.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Built releases, kept by content.
+//
+// After a build, the release (minus `.git`) is archived as a gzip'd tarball
+// named after its own SHA-256. A deploy of the same commit with the same
+// fetch/build argv and environment finds it through its cache key and is
+// restored from the archive instead of being fetched and built again.
+//
+//   <root>/objects/<sha256>.tar.gz   the archives
+//   <root>/keys/<cache key>          the sha256 of a key's archive
+//
+// Nothing else is indexed, so copying both files to a sibling host's store
+// (rsync, scp, a shared bucket, or PullArtifact/PushArtifact through the
+// control plane) replicates a build. Every restore re-hashes the archive
+// first; one that no longer matches its name is discarded, and a received
+// archive is stored only once it hashes to the digest it was sent under.
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const CACHE_KEY_VERSION: &[u8] = b"kari-artifact-v1";
+const OBJECT_SUFFIX: &str = ".tar.gz";
+
+/// Archives move between hosts in pieces of this size.
+pub const TRANSFER_CHUNK_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    pub digest: String, // Hex SHA-256 of the archive
+    pub size_bytes: u64,
+}
+
+/// A git object id (SHA-1 or SHA-256) or one of our digests: lowercase hex.
+pub fn is_hex_id(s: &str) -> bool {
+    (s.len() == 40 || s.len() == 64) && s.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+}
+
+/// The commit a fresh clone has checked out, read from `.git` directly so no
+/// git process runs inside an untrusted checkout. Branches resolve through
+/// their loose ref or `packed-refs`; a tag clone has a detached HEAD.
+pub fn head_commit(checkout: &Path) -> Option<String> {
+    let git_dir = checkout.join(".git");
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    let commit = match head.strip_prefix("ref: ") {
+        None => head.to_string(),
+        Some(reference) => {
+            if !reference.starts_with("refs/") || reference.contains("..") {
+                return None;
+            }
+            match fs::read_to_string(git_dir.join(reference)) {
+                Ok(loose) => loose.trim().to_string(),
+                Err(_) => fs::read_to_string(git_dir.join("packed-refs"))
+                    .ok()?
+                    .lines()
+                    .find_map(|line| {
+                        let (id, name) = line.split_once(' ')?;
+                        (name == reference).then(|| id.to_string())
+                    })?,
+            }
+        }
+    };
+    is_hex_id(&commit).then_some(commit)
+}
+
+/// What a build's output depends on: the commit, both argvs, the network
+/// mode and the environment. Values are hashed, never stored.
+pub fn cache_key(
+    commit: &str,
+    fetch_argv: &[String],
+    build_argv: &[String],
+    offline_build: bool,
+    env_vars: &HashMap<String, String>,
+) -> String {
+    let mut hasher = Sha256::new();
+    // Length-prefixed, so no two different inputs concatenate alike
+    let mut field = |bytes: &[u8]| {
+        hasher.update((bytes.len() as u64).to_be_bytes());
+        hasher.update(bytes);
+    };
+    field(CACHE_KEY_VERSION);
+    field(commit.as_bytes());
+    for (name, argv) in [(b"fetch", fetch_argv), (b"build", build_argv)] {
+        field(name);
+        field(&(argv.len() as u64).to_be_bytes());
+        for arg in argv {
+            field(arg.as_bytes());
+        }
+    }
+    field(&[offline_build as u8]);
+    let sorted: BTreeMap<&String, &String> = env_vars.iter().collect();
+    for (key, value) in sorted {
+        field(key.as_bytes());
+        field(value.as_bytes());
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Hashes everything written through it.
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    written: u64,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            return Ok(format!("{:x}", hasher.finalize()));
+        }
+        hasher.update(&buf[..n]);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArtifactStore {
+    root: PathBuf,
+    max_bytes: u64, // Least recently used archives go first past this
+}
+
+impl ArtifactStore {
+    pub fn new(root: PathBuf, max_mb: u64) -> Self {
+        Self {
+            root,
+            max_bytes: max_mb * 1024 * 1024,
+        }
+    }
+
+    fn objects_dir(&self) -> PathBuf {
+        self.root.join("objects")
+    }
+
+    fn keys_dir(&self) -> PathBuf {
+        self.root.join("keys")
+    }
+
+    fn object_path(&self, digest: &str) -> PathBuf {
+        self.objects_dir()
+            .join(format!("{}{}", digest, OBJECT_SUFFIX))
+    }
+
+    /// 🛡️ Root-only: an archive is restored into a release as-is.
+    fn ensure_dirs(&self) -> Result<(), String> {
+        for dir in [self.objects_dir(), self.keys_dir()] {
+            fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(&dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        Ok(())
+    }
+
+    /// Archives `release_dir` under `key`. An identical archive already in the
+    /// store is kept and shared.
+    pub fn archive(&self, key: &str, release_dir: &Path) -> Result<Artifact, String> {
+        self.ensure_dirs()?;
+        let staged = tempfile::Builder::new()
+            .prefix(".artifact-")
+            .tempfile_in(self.objects_dir())
+            .map_err(|e| format!("Failed to stage artifact: {}", e))?;
+        let writer = HashingWriter {
+            inner: staged.as_file(),
+            hasher: Sha256::new(),
+            written: 0,
+        };
+        let mut builder = tar::Builder::new(GzEncoder::new(writer, Compression::fast()));
+        builder.follow_symlinks(false);
+        let entries =
+            fs::read_dir(release_dir).map_err(|e| format!("Failed to read release: {}", e))?;
+        let archived = (|| -> io::Result<HashingWriter<&File>> {
+            for entry in entries {
+                let entry = entry?;
+                let name = entry.file_name();
+                if name == ".git" {
+                    continue;
+                }
+                if entry.file_type()?.is_dir() {
+                    builder.append_dir_all(&name, entry.path())?;
+                } else {
+                    builder.append_path_with_name(entry.path(), &name)?;
+                }
+            }
+            builder.into_inner()?.finish()
+        })()
+        .map_err(|e| format!("Failed to archive release: {}", e))?;
+        let digest = format!("{:x}", archived.hasher.finalize());
+        let size_bytes = archived.written;
+
+        let object = self.object_path(&digest);
+        if !object.exists() {
+            staged
+                .persist(&object)
+                .map_err(|e| format!("Failed to store artifact: {}", e))?;
+        }
+        self.write_key(key, &digest)?;
+        Ok(Artifact { digest, size_bytes })
+    }
+
+    fn write_key(&self, key: &str, digest: &str) -> Result<(), String> {
+        let path = self.keys_dir().join(key);
+        let tmp_path = path.with_extension("tmp");
+        fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp_path)
+            .and_then(|mut file| file.write_all(digest.as_bytes()))
+            .and_then(|_| fs::rename(&tmp_path, &path))
+            .map_err(|e| format!("Failed to record artifact key: {}", e))
+    }
+
+    /// Unpacks the archive recorded for `key` over `dest`; None when there is
+    /// none. The archive is verified against its name before anything is
+    /// written, and counts as used (for pruning) once restored.
+    pub fn restore(&self, key: &str, dest: &Path) -> Result<Option<Artifact>, String> {
+        if !is_hex_id(key) {
+            return Err(format!("Invalid artifact key '{}'", key));
+        }
+        let key_path = self.keys_dir().join(key);
+        let Ok(digest) = fs::read_to_string(&key_path) else {
+            return Ok(None);
+        };
+        let digest = digest.trim().to_string();
+        if !is_hex_id(&digest) {
+            let _ = fs::remove_file(&key_path);
+            return Ok(None);
+        }
+        let object = self.object_path(&digest);
+        let Ok(meta) = fs::metadata(&object) else {
+            let _ = fs::remove_file(&key_path);
+            return Ok(None);
+        };
+        let actual = sha256_file(&object).map_err(|e| format!("Failed to read artifact: {}", e))?;
+        if actual != digest {
+            let _ = fs::remove_file(&object);
+            let _ = fs::remove_file(&key_path);
+            return Err(format!(
+                "Artifact sha256:{} is corrupt and was discarded",
+                digest
+            ));
+        }
+
+        let file = File::open(&object).map_err(|e| format!("Failed to open artifact: {}", e))?;
+        let mut archive = tar::Archive::new(GzDecoder::new(file));
+        archive.set_preserve_permissions(true);
+        archive.set_overwrite(true);
+        // `unpack` refuses entries that would land outside `dest`
+        archive
+            .unpack(dest)
+            .map_err(|e| format!("Failed to unpack artifact: {}", e))?;
+        if let Ok(file) = File::options().write(true).open(&object) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Ok(Some(Artifact {
+            digest,
+            size_bytes: meta.len(),
+        }))
+    }
+
+    /// The cache keys recorded for an archive.
+    pub fn keys_for(&self, digest: &str) -> Vec<String> {
+        let Ok(entries) = fs::read_dir(self.keys_dir()) else {
+            return Vec::new();
+        };
+        let mut keys: Vec<String> = entries
+            .flatten()
+            .filter_map(|e| {
+                let key = e.file_name().into_string().ok()?;
+                let recorded = fs::read_to_string(e.path()).ok()?;
+                (is_hex_id(&key) && recorded.trim() == digest).then_some(key)
+            })
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    /// Opens an archive to send to a sibling host, verified first so a
+    /// corrupt one is never replicated. None when the store does not have it.
+    pub fn open(&self, digest: &str) -> Result<Option<(File, Artifact)>, String> {
+        if !is_hex_id(digest) {
+            return Err(format!("Invalid artifact digest '{}'", digest));
+        }
+        let object = self.object_path(digest);
+        let Ok(meta) = fs::metadata(&object) else {
+            return Ok(None);
+        };
+        let actual = sha256_file(&object).map_err(|e| format!("Failed to read artifact: {}", e))?;
+        if actual != digest {
+            let _ = fs::remove_file(&object);
+            return Err(format!(
+                "Artifact sha256:{} is corrupt and was discarded",
+                digest
+            ));
+        }
+        let file = File::open(&object).map_err(|e| format!("Failed to open artifact: {}", e))?;
+        Ok(Some((
+            file,
+            Artifact {
+                digest: digest.to_string(),
+                size_bytes: meta.len(),
+            },
+        )))
+    }
+
+    /// Starts receiving an archive from a sibling host. Nothing is stored
+    /// until `ArtifactUpload::finish` has checked the digest.
+    pub fn receive(&self, digest: &str) -> Result<ArtifactUpload, String> {
+        if !is_hex_id(digest) || digest.len() != 64 {
+            return Err(format!("Invalid artifact digest '{}'", digest));
+        }
+        self.ensure_dirs()?;
+        let staged = tempfile::Builder::new()
+            .prefix(".artifact-")
+            .tempfile_in(self.objects_dir())
+            .map_err(|e| format!("Failed to stage artifact: {}", e))?;
+        Ok(ArtifactUpload {
+            store: self.clone(),
+            digest: digest.to_string(),
+            staged,
+            hasher: Sha256::new(),
+            written: 0,
+        })
+    }
+
+    /// Removes the least recently used archives until the store fits, then
+    /// any key left pointing at nothing. Returns how many archives went.
+    pub fn prune(&self) -> Result<usize, String> {
+        let Ok(entries) = fs::read_dir(self.objects_dir()) else {
+            return Ok(0);
+        };
+        let mut objects: Vec<(SystemTime, u64, PathBuf)> = entries
+            .flatten()
+            .filter(|e| e.file_name().to_string_lossy().ends_with(OBJECT_SUFFIX))
+            .filter_map(|e| {
+                let meta = e.metadata().ok()?;
+                Some((meta.modified().ok()?, meta.len(), e.path()))
+            })
+            .collect();
+        objects.sort_by_key(|(modified, _, _)| std::cmp::Reverse(*modified)); // Newest first
+
+        let mut total = 0u64;
+        let mut removed = 0;
+        for (_, size, path) in objects {
+            total += size;
+            if total > self.max_bytes {
+                fs::remove_file(&path)
+                    .map_err(|e| format!("Failed to prune {}: {}", path.display(), e))?;
+                removed += 1;
+            }
+        }
+        if removed > 0
+            && let Ok(keys) = fs::read_dir(self.keys_dir())
+        {
+            for key in keys.flatten() {
+                let orphaned = fs::read_to_string(key.path())
+                    .map(|digest| !self.object_path(digest.trim()).exists())
+                    .unwrap_or(true);
+                if orphaned {
+                    let _ = fs::remove_file(key.path());
+                }
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// An archive being received. Dropping it discards what was written.
+pub struct ArtifactUpload {
+    store: ArtifactStore,
+    digest: String,
+    staged: tempfile::NamedTempFile,
+    hasher: Sha256,
+    written: u64,
+}
+
+impl ArtifactUpload {
+    pub fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        self.written += data.len() as u64;
+        // 🛡️ A sender cannot fill the disk past what the store may hold
+        if self.written > self.store.max_bytes {
+            return Err(format!(
+                "Artifact is larger than the store ({} MiB)",
+                self.store.max_bytes / 1024 / 1024
+            ));
+        }
+        self.hasher.update(data);
+        self.staged
+            .write_all(data)
+            .map_err(|e| format!("Failed to stage artifact: {}", e))
+    }
+
+    /// Stores the archive under its digest, once it hashes to it, and records
+    /// `keys` for it so identical builds on this host restore it.
+    pub fn finish(self, keys: &[String]) -> Result<Artifact, String> {
+        let actual = format!("{:x}", self.hasher.finalize());
+        if actual != self.digest {
+            return Err(format!(
+                "Received artifact hashes to sha256:{}, not sha256:{}",
+                actual, self.digest
+            ));
+        }
+        if let Some(bad) = keys.iter().find(|key| !is_hex_id(key)) {
+            return Err(format!("Invalid artifact key '{}'", bad));
+        }
+        let object = self.store.object_path(&self.digest);
+        if !object.exists() {
+            self.staged
+                .persist(&object)
+                .map_err(|e| format!("Failed to store artifact: {}", e))?;
+        }
+        for key in keys {
+            self.store.write_key(key, &self.digest)?;
+        }
+        let _ = self.store.prune();
+        Ok(Artifact {
+            digest: self.digest,
+            size_bytes: self.written,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    const COMMIT: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+    #[test]
+    fn the_checked_out_commit_is_read_from_git_metadata() {
+        let checkout = tempfile::tempdir().unwrap();
+        let git = checkout.path().join(".git");
+        fs::create_dir_all(git.join("refs/heads")).unwrap();
+        assert_eq!(head_commit(checkout.path()), None);
+
+        fs::write(git.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(
+            git.join("packed-refs"),
+            format!("# pack-refs with: peeled\n{} refs/heads/main\n", COMMIT),
+        )
+        .unwrap();
+        assert_eq!(head_commit(checkout.path()).as_deref(), Some(COMMIT));
+
+        fs::write(git.join("HEAD"), "ref: ../../etc/passwd\n").unwrap();
+        assert_eq!(head_commit(checkout.path()), None);
+        fs::write(git.join("HEAD"), format!("{}\n", COMMIT)).unwrap();
+        assert_eq!(head_commit(checkout.path()).as_deref(), Some(COMMIT));
+    }
+
+    #[test]
+    fn any_input_that_shapes_the_build_changes_the_key() {
+        let argv = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        let env: HashMap<String, String> = [("NODE_ENV".into(), "production".into())].into();
+        let key = cache_key(
+            COMMIT,
+            &argv(&["npm", "ci"]),
+            &argv(&["npm", "run", "build"]),
+            false,
+            &env,
+        );
+        assert!(is_hex_id(&key));
+        assert_eq!(
+            key,
+            cache_key(
+                COMMIT,
+                &argv(&["npm", "ci"]),
+                &argv(&["npm", "run", "build"]),
+                false,
+                &env
+            )
+        );
+        let other_env: HashMap<String, String> = [("NODE_ENV".into(), "staging".into())].into();
+        for other in [
+            cache_key(
+                COMMIT,
+                &argv(&["npm", "ci"]),
+                &argv(&["npm", "run", "build"]),
+                true,
+                &env,
+            ),
+            cache_key(
+                COMMIT,
+                &argv(&["npm", "ci"]),
+                &argv(&["npm", "run", "build"]),
+                false,
+                &other_env,
+            ),
+            // The same words split differently between the two argvs
+            cache_key(
+                COMMIT,
+                &argv(&["npm", "ci", "npm"]),
+                &argv(&["run", "build"]),
+                false,
+                &env,
+            ),
+        ] {
+            assert_ne!(key, other);
+        }
+    }
+
+    #[test]
+    fn releases_round_trip_through_the_store_and_are_pruned_lru() {
+        let root = tempfile::tempdir().unwrap();
+        let store = ArtifactStore::new(root.path().join("store"), 1);
+        let release = root.path().join("release");
+        fs::create_dir_all(release.join("dist")).unwrap();
+        fs::create_dir_all(release.join(".git")).unwrap();
+        fs::write(release.join("dist/app.js"), "console.log(1)").unwrap();
+        fs::write(release.join("run.sh"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(release.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+        std::os::unix::fs::symlink("dist/app.js", release.join("main.js")).unwrap();
+        fs::write(release.join(".git/HEAD"), COMMIT).unwrap();
+
+        let key = "a".repeat(64);
+        let artifact = store.archive(&key, &release).unwrap();
+        assert_eq!(
+            sha256_file(&store.object_path(&artifact.digest)).unwrap(),
+            artifact.digest
+        );
+
+        let restored = root.path().join("restored");
+        fs::create_dir_all(&restored).unwrap();
+        assert_eq!(store.restore(&"b".repeat(64), &restored).unwrap(), None);
+        assert_eq!(
+            store.restore(&key, &restored).unwrap().unwrap().digest,
+            artifact.digest
+        );
+        assert_eq!(
+            fs::read_to_string(restored.join("dist/app.js")).unwrap(),
+            "console.log(1)"
+        );
+        assert_eq!(
+            fs::read_link(restored.join("main.js")).unwrap(),
+            Path::new("dist/app.js")
+        );
+        assert_eq!(
+            fs::metadata(restored.join("run.sh"))
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o777,
+            0o755
+        );
+        assert!(!restored.join(".git").exists());
+        assert!(store.restore("../../etc", &restored).is_err());
+
+        // A tampered archive no longer matches its name
+        fs::write(store.object_path(&artifact.digest), b"not the archive").unwrap();
+        assert!(store.restore(&key, &restored).is_err());
+        assert_eq!(store.restore(&key, &restored).unwrap(), None);
+
+        // Past 1 MiB, the least recently used archive goes along with its key
+        let big = root.path().join("big");
+        fs::create_dir_all(&big).unwrap();
+        // Incompressible, so each archive is about as large as the blob
+        let noise: Vec<u8> = (0..22_000u32)
+            .flat_map(|i| Sha256::digest(i.to_be_bytes()))
+            .collect();
+        fs::write(big.join("blob"), &noise).unwrap();
+        let first = store.archive(&"c".repeat(64), &big).unwrap();
+        fs::write(
+            big.join("blob"),
+            noise.iter().rev().copied().collect::<Vec<_>>(),
+        )
+        .unwrap();
+        let second = store.archive(&"d".repeat(64), &big).unwrap();
+        File::options()
+            .write(true)
+            .open(store.object_path(&first.digest))
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        assert_eq!(store.prune().unwrap(), 1);
+        assert!(!store.object_path(&first.digest).exists());
+        assert!(store.object_path(&second.digest).exists());
+        assert!(!store.keys_dir().join("c".repeat(64)).exists());
+    }
+
+    #[test]
+    fn received_archives_are_stored_only_under_their_own_digest() {
+        let root = tempfile::tempdir().unwrap();
+        let source = ArtifactStore::new(root.path().join("source"), 64);
+        let sibling = ArtifactStore::new(root.path().join("sibling"), 64);
+        let release = root.path().join("release");
+        fs::create_dir_all(&release).unwrap();
+        fs::write(release.join("server.js"), "listen(3000)").unwrap();
+        let key = "e".repeat(64);
+        let artifact = source.archive(&key, &release).unwrap();
+        assert_eq!(source.keys_for(&artifact.digest), [key.as_str()]);
+
+        let (mut file, opened) = source.open(&artifact.digest).unwrap().unwrap();
+        assert_eq!(opened, artifact);
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).unwrap();
+
+        // Truncated in transit: nothing is stored
+        let mut upload = sibling.receive(&artifact.digest).unwrap();
+        upload.write(&bytes[..bytes.len() / 2]).unwrap();
+        assert!(upload.finish(std::slice::from_ref(&key)).is_err());
+        assert!(sibling.open(&artifact.digest).unwrap().is_none());
+
+        let mut upload = sibling.receive(&artifact.digest).unwrap();
+        for chunk in bytes.chunks(7) {
+            upload.write(chunk).unwrap();
+        }
+        assert_eq!(upload.finish(std::slice::from_ref(&key)).unwrap(), artifact);
+        let restored = root.path().join("restored");
+        fs::create_dir_all(&restored).unwrap();
+        assert_eq!(
+            sibling.restore(&key, &restored).unwrap(),
+            Some(artifact.clone())
+        );
+        assert!(restored.join("server.js").is_file());
+
+        assert!(sibling.receive("../objects").is_err());
+        assert!(source.open(&"f".repeat(64)).unwrap().is_none());
+
+        // Corrupted on the source's disk: never sent, and gone afterwards
+        fs::write(source.object_path(&artifact.digest), &bytes[1..]).unwrap();
+        assert!(source.open(&artifact.digest).is_err());
+        assert!(source.open(&artifact.digest).unwrap().is_none());
+    }
+}
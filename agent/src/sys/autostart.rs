@@ -0,0 +1,167 @@
+// agent/src/sys/autostart.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Boot-survival checks for everything the
+// state store tracks. After a reboot every app unit and job timer must be
+// enabled, every routed app must still have its vhost, and every installed
+// certificate must still be on disk, and every firewall rule must be back in
+// the kernel. Units, timers, vhosts and firewall rules can be rebuilt from
+// state; a lost certificate cannot (the agent never keeps the key), so it is
+// only reported.
+
+use tracing::warn;
+
+use crate::sys::firewall;
+use crate::sys::state::{
+    AppRecord, FirewallRuleRecord, JobRecord, NS_APPS, NS_FIREWALL_RULES, NS_JOBS,
+    firewall_rule_key, list_records,
+};
+use crate::sys::systemd::ServiceManager;
+use crate::sys::traits::{
+    FirewallManager, JobIntent, JobScheduler, ProxyManager, SslEngine, StateStore,
+};
+
+/// The managers a reconciliation pass reads from (and repairs through).
+pub struct AutostartManagers<'a> {
+    pub state: &'a dyn StateStore,
+    pub services: &'a dyn ServiceManager, // Enables every app unit, container ones included
+    pub proxy: &'a dyn ProxyManager,
+    pub ssl: &'a dyn SslEngine,
+    pub scheduler: &'a dyn JobScheduler,
+    pub firewall: &'a dyn FirewallManager,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Discrepancy {
+    pub kind: &'static str, // "unit" | "timer" | "vhost" | "certificate" | "firewall"
+    pub target: String,     // Unit, job or domain name, or firewall rule key
+    pub app_id: String,     // Empty for standalone jobs
+    pub repaired: bool,
+    pub detail: String,
+}
+
+#[derive(Debug, Default)]
+pub struct AutostartReport {
+    pub checked: u32,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl AutostartReport {
+    /// True when nothing is (still) broken.
+    pub fn healthy(&self) -> bool {
+        self.discrepancies.iter().all(|d| d.repaired)
+    }
+
+    /// Records one check. `probe` says whether the resource is in place; when it
+    /// is not and `repair` is given, the repair is attempted once.
+    async fn check<R>(
+        &mut self,
+        kind: &'static str,
+        target: &str,
+        app_id: &str,
+        probe: Result<bool, String>,
+        repair: Option<R>,
+    ) where
+        R: std::future::Future<Output = Result<(), String>>,
+    {
+        self.checked += 1;
+        let detail = match probe {
+            Ok(true) => return,
+            Ok(false) if matches!(kind, "unit" | "timer") => format!("{} is not enabled", kind),
+            Ok(false) if kind == "firewall" => "rule is not in the INPUT chain".to_string(),
+            Ok(false) => format!("{} is missing", kind),
+            Err(e) => format!("{} check failed: {}", kind, e),
+        };
+        let (repaired, detail) = match repair {
+            Some(fix) => match fix.await {
+                Ok(()) => (true, format!("{}; repaired", detail)),
+                Err(e) => (false, format!("{}; repair failed: {}", detail, e)),
+            },
+            None => (false, detail),
+        };
+        if !repaired {
+            warn!("🔁 Autostart: {} {} — {}", kind, target, detail);
+        }
+        self.discrepancies.push(Discrepancy {
+            kind,
+            target: target.to_string(),
+            app_id: app_id.to_string(),
+            repaired,
+            detail,
+        });
+    }
+}
+
+/// Verifies every tracked app, job and firewall rule; with `repair`, re-enables
+/// units and timers, recreates vhosts and re-applies missing firewall rules in
+/// place. One broken app never stops the pass.
+pub async fn reconcile(m: &AutostartManagers<'_>, repair: bool) -> Result<AutostartReport, String> {
+    let apps: Vec<AppRecord> = list_records(m.state, NS_APPS).await?;
+    let jobs: Vec<JobRecord> = list_records(m.state, NS_JOBS).await?;
+    let rules: Vec<FirewallRuleRecord> = list_records(m.state, NS_FIREWALL_RULES).await?;
+    let mut report = AutostartReport::default();
+
+    for app in &apps {
+        let probe = m.services.is_enabled(&app.service_name).await;
+        let fix = repair.then(|| m.services.enable_and_start(&app.service_name));
+        report
+            .check("unit", &app.service_name, &app.app_id, probe, fix)
+            .await;
+
+        // Only routed apps (a deploy or container port was recorded) own a vhost
+        if let Some(port) = app.port {
+            let probe = m.proxy.vhost_exists(&app.domain_name).await;
+            let fix = repair.then(|| m.proxy.create_vhost(&app.domain_name, port, &app.vhost));
+            report
+                .check("vhost", &app.domain_name, &app.app_id, probe, fix)
+                .await;
+        }
+
+        if app.certificate_installed {
+            let probe = m.ssl.certificate_exists(app.certificate_name()).await;
+            let fix: Option<std::future::Ready<Result<(), String>>> = None;
+            report
+                .check("certificate", &app.domain_name, &app.app_id, probe, fix)
+                .await;
+        }
+    }
+
+    for job in &jobs {
+        let intent = JobIntent {
+            name: job.job_name.clone(),
+            binary: job.binary.clone(),
+            args: job.args.clone(),
+            schedule: job.schedule.clone(),
+            run_as_user: job.run_as_user.clone(),
+        };
+        let probe = m.scheduler.is_job_enabled(&job.job_name).await;
+        // Rewriting the timer and service is idempotent and enables with --now
+        let fix = repair.then(|| m.scheduler.schedule_job(&intent));
+        report
+            .check(
+                "timer",
+                &job.job_name,
+                job.app_id.as_deref().unwrap_or_default(),
+                probe,
+                fix,
+            )
+            .await;
+    }
+
+    for rule in &rules {
+        let key = firewall_rule_key(rule.port, &rule.protocol, rule.source_ip.as_deref());
+        let policy = match firewall::record_policy(rule) {
+            Ok(policy) => policy,
+            Err(e) => {
+                let probe = Err(format!("stored rule is unreadable: {}", e));
+                let fix: Option<std::future::Ready<Result<(), String>>> = None;
+                report.check("firewall", &key, "", probe, fix).await;
+                continue;
+            }
+        };
+        let probe = m.firewall.policy_applied(&policy).await;
+        let fix = repair.then(|| m.firewall.apply_policy(&policy));
+        report.check("firewall", &key, "", probe, fix).await;
+    }
+
+    Ok(report)
+}
@@ -1,27 +1,355 @@
 use crate::server::kari_agent::LogChunk;
-use crate::sys::traits::BuildManager;
+use crate::sys::mirrors::BuildMirrors;
+use crate::sys::privilege;
+use crate::sys::systemd::render_env_file;
+use crate::sys::traits::{BuildManager, BuildNetwork, BuildUsage};
 use async_trait::async_trait;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::net::IpAddr;
 use std::path::Path;
 use std::process::Stdio;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
 use tokio::sync::mpsc;
 use tonic::Status;
 
-pub struct SystemBuildManager;
+/// Lines `systemd-run --wait` prints about the transient unit itself. They share
+/// stderr with the build, so they are parsed for usage instead of forwarded.
+const RUN_SUMMARY_PREFIXES: &[&str] = &[
+    "Running as unit:",
+    "Finished with result:",
+    "Main processes terminated with:",
+    "Service runtime:",
+    "CPU time consumed:",
+    "Memory peak:",
+    "Memory swap peak:",
+    "IP traffic received:",
+    "IP traffic sent:",
+    "IO bytes read:",
+    "IO bytes written:",
+];
+
+/// Parses a systemd timespan such as `345ms`, `1.234s` or `2min 3.5s`.
+fn parse_timespan_usec(value: &str) -> Option<u64> {
+    let mut total = 0f64;
+    for word in value.split_whitespace() {
+        let split = word.find(|c: char| !(c.is_ascii_digit() || c == '.'))?;
+        let (number, unit) = word.split_at(split);
+        let scale = match unit {
+            "us" | "μs" => 1.0,
+            "ms" => 1e3,
+            "s" => 1e6,
+            "min" => 60e6,
+            "h" => 3600e6,
+            "d" => 86_400e6,
+            _ => return None,
+        };
+        total += number.parse::<f64>().ok()? * scale;
+    }
+    Some(total as u64)
+}
+
+/// Parses a systemd byte size such as `800B`, `50.3M` or `1.2G` (base 1024).
+fn parse_bytes(value: &str) -> Option<u64> {
+    let word = value.split_whitespace().next()?;
+    let split = word.find(|c: char| !(c.is_ascii_digit() || c == '.'))?;
+    let (number, unit) = word.split_at(split);
+    let exp = ["B", "K", "M", "G", "T", "P"]
+        .iter()
+        .position(|u| *u == unit)?;
+    Some((number.parse::<f64>().ok()? * 1024f64.powi(exp as i32)) as u64)
+}
+
+/// Folds one systemd-run summary line into `usage`. Later lines win, so build
+/// output imitating the summary is overridden by the real one printed at exit.
+fn record_summary_line(line: &str, usage: &mut BuildUsage) {
+    if let Some(v) = line.strip_prefix("CPU time consumed:") {
+        usage.cpu_usec = parse_timespan_usec(v).unwrap_or(usage.cpu_usec);
+    } else if let Some(v) = line.strip_prefix("Memory peak:") {
+        usage.peak_memory_bytes = parse_bytes(v).unwrap_or(usage.peak_memory_bytes);
+    }
+}
+
+/// A partial line longer than this is forwarded anyway, so a process that
+/// never prints a newline still shows up (and cannot grow the buffer unbounded).
+const MAX_LINE_BYTES: usize = 16 * 1024;
+
+#[derive(Debug, PartialEq, Eq)]
+struct OutputLine {
+    bytes: Vec<u8>,
+    progress: bool, // Ended by a bare '\r': the next one redraws it
+}
+
+/// Splits raw process output into lines without assuming it is UTF-8. A bare
+/// '\r' ends a progress update; of several updates read at once only the last
+/// is kept, since each one overwrites the previous on a terminal anyway.
+#[derive(Default)]
+struct LineSplitter {
+    pending: Vec<u8>,
+}
+
+impl LineSplitter {
+    fn push(&mut self, bytes: &[u8]) -> Vec<OutputLine> {
+        self.pending.extend_from_slice(bytes);
+        let mut lines: Vec<OutputLine> = Vec::new();
+        let mut start = 0;
+        let mut i = 0;
+        while i < self.pending.len() {
+            match self.pending[i] {
+                b'\n' => {
+                    // "\r\n" is an ordinary line end
+                    let end = if i > start && self.pending[i - 1] == b'\r' {
+                        i - 1
+                    } else {
+                        i
+                    };
+                    lines.push(OutputLine {
+                        bytes: self.pending[start..end].to_vec(),
+                        progress: false,
+                    });
+                    start = i + 1;
+                }
+                // A '\r' as the last byte may still be the first half of "\r\n"
+                b'\r' if i + 1 < self.pending.len() && self.pending[i + 1] != b'\n' => {
+                    if lines.last().is_some_and(|l| l.progress) {
+                        lines.pop();
+                    }
+                    lines.push(OutputLine {
+                        bytes: self.pending[start..i].to_vec(),
+                        progress: true,
+                    });
+                    start = i + 1;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        self.pending.drain(..start);
+        if self.pending.len() >= MAX_LINE_BYTES {
+            lines.push(OutputLine {
+                bytes: std::mem::take(&mut self.pending),
+                progress: false,
+            });
+        }
+        lines
+    }
+
+    /// Whatever is left once the process closed the pipe.
+    fn finish(&mut self) -> Option<OutputLine> {
+        let bytes = std::mem::take(&mut self.pending);
+        let bytes = bytes
+            .strip_suffix(b"\r")
+            .map(<[u8]>::to_vec)
+            .unwrap_or(bytes);
+        (!bytes.is_empty()).then_some(OutputLine {
+            bytes,
+            progress: false,
+        })
+    }
+}
+
+/// Renders one line as a chunk. Bytes that are not UTF-8 are replaced in
+/// `content` and kept verbatim in `raw_content`.
+fn line_chunk(tag: &str, line: &OutputLine, trace_id: &str) -> LogChunk {
+    let end = if line.progress { '\r' } else { '\n' };
+    let text = String::from_utf8_lossy(&line.bytes);
+    let raw_content = match text {
+        std::borrow::Cow::Borrowed(_) => Vec::new(),
+        std::borrow::Cow::Owned(_) => [tag.as_bytes(), b" ", &line.bytes, &[end as u8]].concat(),
+    };
+    LogChunk {
+        content: format!("{} {}{}", tag, text, end),
+        trace_id: trace_id.to_string(),
+        finding: None,
+        usage: None,
+        compressed_content: Vec::new(),
+        raw_content,
+        event: None,
+        queued: None,
+    }
+}
+
+/// Pulls lines off a pipe as bytes: a stray non-UTF-8 byte must not end the
+/// stream the way `AsyncBufReadExt::lines` does.
+struct LineReader<R> {
+    pipe: Option<R>,
+    splitter: LineSplitter,
+    ready: VecDeque<OutputLine>,
+    buf: Vec<u8>,
+}
+
+impl<R: AsyncRead + Unpin> LineReader<R> {
+    fn new(pipe: R) -> Self {
+        Self {
+            pipe: Some(pipe),
+            splitter: LineSplitter::default(),
+            ready: VecDeque::new(),
+            buf: vec![0u8; 8192],
+        }
+    }
+
+    async fn next_line(&mut self) -> Option<OutputLine> {
+        loop {
+            if let Some(line) = self.ready.pop_front() {
+                return Some(line);
+            }
+            let pipe = self.pipe.as_mut()?;
+            match pipe.read(&mut self.buf).await {
+                Ok(n) if n > 0 => self.ready.extend(self.splitter.push(&self.buf[..n])),
+                _ => {
+                    self.pipe = None;
+                    return self.splitter.finish();
+                }
+            }
+        }
+    }
+}
+
+/// Human form for log lines, e.g. `peak memory 48.0 MiB, CPU 1.23s`.
+pub fn describe_usage(usage: &BuildUsage) -> String {
+    format!(
+        "peak memory {:.1} MiB, CPU {:.2}s",
+        usage.peak_memory_bytes as f64 / (1024.0 * 1024.0),
+        usage.cpu_usec as f64 / 1e6
+    )
+}
+
+/// Names each build's transient unit, so it can be stopped as a whole.
+static BUILD_UNITS: AtomicU64 = AtomicU64::new(0);
+
+/// systemd keeps enforcing the limit itself this long after the agent would
+/// have, in case the agent is gone by then.
+const RUNTIME_MAX_GRACE_SECS: u64 = 30;
+
+/// `systemd-run` options for one build step, everything before the `--`.
+#[allow(clippy::too_many_arguments)]
+fn systemd_run_args(
+    unit: &str,
+    run_as_user: &str,
+    working_dir: &Path,
+    network: BuildNetwork,
+    env_file: Option<&Path>,
+    timeout: Duration,
+    tmp_max_mb: u64,
+    ip_allow: Option<&[IpAddr]>,
+) -> Vec<String> {
+    let mut args: Vec<String> = ["--pipe", "--wait", "--collect"]
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    args.extend([
+        format!("--unit={}", unit),
+        "--property=CPUAccounting=yes".to_string(),
+        "--property=MemoryAccounting=yes".to_string(),
+        // 🛡️ Stopping the unit takes every descendant with it, however deep
+        "--property=KillMode=control-group".to_string(),
+        format!(
+            "--property=RuntimeMaxSec={}",
+            timeout.as_secs() + RUNTIME_MAX_GRACE_SECS
+        ),
+        format!("--uid={}", run_as_user),
+        format!("--gid={}", run_as_user),
+        format!("--working-directory={}", working_dir.display()),
+    ]);
+    // 🛡️ Scratch space of its own: /tmp and /var/tmp are fresh, size-capped tmpfs
+    // mounts only this step sees, gone with its unit. A runaway build fills its
+    // own cap, never the host's /tmp, and nothing it leaves reaches another app.
+    for dir in ["/tmp", "/var/tmp"] {
+        args.push(format!(
+            "--property=TemporaryFileSystem={}:size={}M,mode=1777",
+            dir, tmp_max_mb
+        ));
+    }
+    args.push("--setenv=TMPDIR=/tmp".to_string());
+    // 🛡️ Isolated builds get PrivateNetwork=yes: loopback only
+    if network == BuildNetwork::Isolated {
+        args.push("--property=PrivateNetwork=yes".to_string());
+    }
+    // 🛡️ Mirrors-only builds: everything but the allowed addresses is dropped
+    if let Some(allowed) = ip_allow {
+        args.push("--property=IPAddressDeny=any".to_string());
+        let mut allow = String::from("--property=IPAddressAllow=localhost");
+        for address in allowed {
+            allow.push_str(&format!(" {}", address));
+        }
+        args.push(allow);
+    }
+    if let Some(env_file) = env_file {
+        args.push(format!("--property=EnvironmentFile={}", env_file.display()));
+    }
+    args
+}
+
+/// The step's environment as a private temporary file (0600, removed when
+/// dropped), in EnvironmentFile= syntax.
+fn write_env_file(env: &HashMap<String, String>) -> Result<tempfile::NamedTempFile, String> {
+    let mut file = tempfile::Builder::new()
+        .prefix("kari-build-env-")
+        .tempfile()
+        .map_err(|e| format!("Failed to create build env file: {}", e))?;
+    file.write_all(render_env_file(env).as_bytes())
+        .and_then(|()| file.flush())
+        .map_err(|e| format!("Failed to write build env file: {}", e))?;
+    Ok(file)
+}
+
+/// Stops a build's unit unless the build ran to completion. Killing the
+/// `systemd-run` client alone leaves npm, node and webpack running in the
+/// cgroup; stopping the unit kills the cgroup. Runs on drop, so a cancelled
+/// deploy future cleans up too.
+struct BuildUnitGuard {
+    unit: Option<String>,
+}
+
+impl BuildUnitGuard {
+    fn disarm(&mut self) {
+        self.unit = None;
+    }
+}
+
+impl Drop for BuildUnitGuard {
+    fn drop(&mut self) {
+        if let Some(unit) = self.unit.take() {
+            // Drop cannot await; a plain thread works with or without a runtime
+            std::thread::spawn(move || {
+                let _ = privilege::std_command("systemctl")
+                    .args(["stop", "--", &unit])
+                    .status();
+            });
+        }
+    }
+}
+
+pub struct SystemBuildManager {
+    timeout: Duration, // Per step; past it the whole process tree is stopped
+    tmp_max_mb: u64,   // Per step, for each of /tmp and /var/tmp
+    mirrors: BuildMirrors,
+}
+
+impl SystemBuildManager {
+    pub fn new(timeout_secs: u64, tmp_max_mb: u64, mirrors: BuildMirrors) -> Self {
+        Self {
+            timeout: Duration::from_secs(timeout_secs),
+            tmp_max_mb,
+            mirrors,
+        }
+    }
+}
 
 #[async_trait]
 impl BuildManager for SystemBuildManager {
     async fn execute_build(
         &self,
-        build_command: &str,
+        build_argv: &[String],
         working_dir: &Path, // 🛡️ SLA: Strict Type
         run_as_user: &str,
+        network: BuildNetwork,
         env_vars: &HashMap<String, String>,
         log_tx: mpsc::Sender<Result<LogChunk, Status>>,
         trace_id: String,
-    ) -> Result<(), String> {
+    ) -> Result<BuildUsage, String> {
         // 1. 🛡️ Identity Validation
         if run_as_user.is_empty()
             || !run_as_user
@@ -32,31 +360,70 @@ impl BuildManager for SystemBuildManager {
         }
 
         // 2. 🛡️ Shell Injection Mitigation
-        // We reject any commands containing shell metacharacters that allow chaining.
-        // For a more robust solution, we'd use a parser, but this is a Zero-Trust baseline.
-        if build_command.contains(';') || build_command.contains('&') || build_command.contains('|')
-        {
-            return Err("SECURITY VIOLATION: Command chaining detected in build command".into());
+        // The argv is executed directly; with no `sh -c` there is nothing to chain.
+        let Some((program, args)) = build_argv.split_first() else {
+            return Err("Build argv is empty".into());
+        };
+
+        // 3. 🛡️ cgroup Isolation & Accounting
+        // Every step runs as a transient unit: its own cgroup, so the whole process
+        // tree is accounted (and reported by systemd-run on exit) and contained.
+        // Env values go through a 0600 EnvironmentFile= read by systemd itself:
+        // never in the systemd-run argv, and not reliant on this process's
+        // environment, which `sudo` resets before systemd-run sees it.
+        let unit = format!(
+            "kari-build-{}-{}.service",
+            std::process::id(),
+            BUILD_UNITS.fetch_add(1, Ordering::Relaxed)
+        );
+        // 🪞 Networked steps fetch through the host's mirrors and proxy. The
+        // app's values are not copied (they may be secrets the caller zeroizes)
+        let mut mirror_env = Vec::new();
+        let mut ip_allow = None;
+        if network == BuildNetwork::Host {
+            mirror_env = self.mirrors.env();
+            self.mirrors.write_cargo_config(working_dir)?;
+            let resolv_conf = tokio::fs::read_to_string("/etc/resolv.conf")
+                .await
+                .unwrap_or_default();
+            ip_allow = self.mirrors.allowed_addresses(&resolv_conf).await?;
         }
+        let mut env = env_vars.clone();
+        env.extend(
+            mirror_env
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value)),
+        ); // Last, so they win
+        let env_file = if env.is_empty() {
+            None
+        } else {
+            Some(write_env_file(&env)?)
+        };
+        drop(env);
+        let mut command = privilege::command("systemd-run");
+        command
+            .args(systemd_run_args(
+                &unit,
+                run_as_user,
+                working_dir,
+                network,
+                env_file.as_ref().map(|file| file.path()),
+                self.timeout,
+                self.tmp_max_mb,
+                ip_allow.as_deref(),
+            ))
+            .arg("--");
 
-        // 3. 🛡️ Process Group Isolation
-        // We use a custom wrapper to ensure that if we kill the build,
-        // we kill the parent and ALL children (the entire process group).
-        let mut child = Command::new("runuser")
-            .arg("-u")
-            .arg(run_as_user)
-            .arg("--")
-            .arg("sh")
-            .arg("-c")
-            .arg(build_command)
+        let mut child = command
+            .arg(program)
+            .args(args)
             .current_dir(working_dir)
-            .envs(env_vars)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            // 🛡️ Zero-Trust: Kills the whole group on drop
             .kill_on_drop(true)
             .spawn()
             .map_err(|e| format!("Failed to initiate build process: {}", e))?;
+        let mut guard = BuildUnitGuard { unit: Some(unit) };
 
         let stdout = child.stdout.take().ok_or("STDOUT_UNAVAILABLE")?;
         let stderr = child.stderr.take().ok_or("STDERR_UNAVAILABLE")?;
@@ -65,14 +432,14 @@ impl BuildManager for SystemBuildManager {
         let t_out = trace_id.clone();
         let tx_out = log_tx.clone();
         let stdout_task = tokio::spawn(async move {
-            let mut reader = BufReader::new(stdout).lines();
-            while let Ok(Some(line)) = reader.next_line().await {
-                let chunk = LogChunk {
-                    content: format!("[OUT] {}\n", line),
-                    trace_id: t_out.clone(),
-                };
+            let mut reader = LineReader::new(stdout);
+            while let Some(line) = reader.next_line().await {
                 // 🛡️ SLA: Send with backpressure. If receiver is gone, stop the task.
-                if tx_out.send(Ok(chunk)).await.is_err() {
+                if tx_out
+                    .send(Ok(line_chunk("[OUT]", &line, &t_out)))
+                    .await
+                    .is_err()
+                {
                     break;
                 }
             }
@@ -81,23 +448,45 @@ impl BuildManager for SystemBuildManager {
         let t_err = trace_id.clone();
         let tx_err = log_tx.clone();
         let stderr_task = tokio::spawn(async move {
-            let mut reader = BufReader::new(stderr).lines();
-            while let Ok(Some(line)) = reader.next_line().await {
-                let chunk = LogChunk {
-                    content: format!("[ERR] {}\n", line),
-                    trace_id: t_err.clone(),
-                };
-                if tx_err.send(Ok(chunk)).await.is_err() {
+            let mut usage = BuildUsage::default();
+            let mut reader = LineReader::new(stderr);
+            while let Some(line) = reader.next_line().await {
+                let text = String::from_utf8_lossy(&line.bytes);
+                if !line.progress && RUN_SUMMARY_PREFIXES.iter().any(|p| text.starts_with(p)) {
+                    record_summary_line(&text, &mut usage);
+                    continue;
+                }
+                if tx_err
+                    .send(Ok(line_chunk("[ERR]", &line, &t_err)))
+                    .await
+                    .is_err()
+                {
                     break;
                 }
             }
+            usage
         });
 
         // 5. Lifecycle Synchronization
-        let status = child.wait().await.map_err(|e| e.to_string())?;
+        // 🛡️ A deploy nobody is watching any more, or one past its time, is
+        // stopped rather than left to finish unobserved
+        let status = tokio::select! {
+            status = child.wait() => status.map_err(|e| e.to_string())?,
+            _ = log_tx.closed() => {
+                return Err("Log stream consumer disconnected; build stopped".into());
+            }
+            _ = tokio::time::sleep(self.timeout) => {
+                return Err(format!(
+                    "Build exceeded {}s; its process tree was stopped",
+                    self.timeout.as_secs()
+                ));
+            }
+        };
+        guard.disarm();
 
         // Ensure all log buffers are flushed before returning control to server.rs
-        let _ = tokio::join!(stdout_task, stderr_task);
+        let (_, usage) = tokio::join!(stdout_task, stderr_task);
+        let usage = usage.unwrap_or_default();
 
         if !status.success() {
             let exit_desc = match status.code() {
@@ -105,9 +494,152 @@ impl BuildManager for SystemBuildManager {
                 // Handle cases where the process was killed by OOM Killer or a Signal
                 None => "Terminated by Signal (Likely OOM or Timeout)".to_string(),
             };
-            return Err(format!("Build process failed: {}", exit_desc));
+            // 📊 Runaway builds stay visible even though they produced nothing
+            return Err(format!(
+                "Build process failed: {} ({})",
+                exit_desc,
+                describe_usage(&usage)
+            ));
+        }
+
+        Ok(usage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn systemd_run_summary_is_parsed() {
+        let mut usage = BuildUsage::default();
+        for line in [
+            "Finished with result: success",
+            "CPU time consumed: 2min 3.5s",
+            "Memory peak: 50.5M (swap: 0B)",
+        ] {
+            record_summary_line(line, &mut usage);
+        }
+        assert_eq!(usage.cpu_usec, 123_500_000);
+        assert_eq!(usage.peak_memory_bytes, 52_953_088);
+
+        assert_eq!(parse_timespan_usec("345ms"), Some(345_000));
+        assert_eq!(parse_timespan_usec("12us"), Some(12));
+        assert_eq!(parse_timespan_usec("soon"), None);
+        assert_eq!(parse_bytes("800B"), Some(800));
+        assert_eq!(parse_bytes("1.5G"), Some(1_610_612_736));
+        assert_eq!(parse_bytes("lots"), None);
+    }
+
+    #[test]
+    fn build_steps_run_as_named_units_with_a_runtime_cap() {
+        let args = systemd_run_args(
+            "kari-build-1-0.service",
+            "kari-shop",
+            Path::new("/var/www/shop"),
+            BuildNetwork::Isolated,
+            Some(Path::new("/tmp/kari-build-env-x1")),
+            Duration::from_secs(600),
+            512,
+            None,
+        );
+        for expected in [
+            "--unit=kari-build-1-0.service",
+            "--property=KillMode=control-group",
+            "--property=RuntimeMaxSec=630",
+            "--property=TemporaryFileSystem=/tmp:size=512M,mode=1777",
+            "--property=TemporaryFileSystem=/var/tmp:size=512M,mode=1777",
+            "--setenv=TMPDIR=/tmp",
+            "--property=PrivateNetwork=yes",
+            "--property=EnvironmentFile=/tmp/kari-build-env-x1",
+        ] {
+            assert!(
+                args.iter().any(|a| a == expected),
+                "{} in {:?}",
+                expected,
+                args
+            );
         }
+        assert!(!args.iter().any(|a| a.contains("IPAddress")));
+
+        let allowed: [IpAddr; 2] = ["10.0.0.2".parse().unwrap(), "2001:db8::5".parse().unwrap()];
+        let args = systemd_run_args(
+            "kari-build-1-1.service",
+            "kari-shop",
+            Path::new("/var/www/shop"),
+            BuildNetwork::Host,
+            None,
+            Duration::from_secs(600),
+            512,
+            Some(&allowed),
+        );
+        assert!(args.ends_with(&[
+            "--property=IPAddressDeny=any".to_string(),
+            "--property=IPAddressAllow=localhost 10.0.0.2 2001:db8::5".to_string(),
+        ]));
+    }
+
+    #[test]
+    fn build_env_reaches_systemd_through_a_private_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let env = HashMap::from([
+            ("NODE_ENV".to_string(), "production".to_string()),
+            ("API_TOKEN".to_string(), "s3cr$t".to_string()),
+            ("NOT VALID".to_string(), "dropped".to_string()),
+        ]);
+        let file = write_env_file(&env).unwrap();
+        let path = file.path().to_path_buf();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "API_TOKEN=\"s3cr\\$t\"\nNODE_ENV=\"production\"\n"
+        );
+
+        let args = systemd_run_args(
+            "kari-build-1-2.service",
+            "kari-shop",
+            Path::new("/var/www/shop"),
+            BuildNetwork::Host,
+            Some(&path),
+            Duration::from_secs(600),
+            512,
+            None,
+        );
+        assert!(!args.iter().any(|a| a.contains("s3cr")));
+        drop(file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn progress_redraws_and_partial_lines_are_split_safely() {
+        let line = |bytes: &[u8], progress| OutputLine {
+            bytes: bytes.to_vec(),
+            progress,
+        };
+        let mut splitter = LineSplitter::default();
+
+        // Only the newest of several redraws survives; the partial tail waits
+        assert_eq!(
+            splitter.push(b"  10%\r  20%\r  30%\rdone\r\nnext"),
+            [line(b"  30%", true), line(b"done", false)]
+        );
+        // A '\r' at the end of a read may be half of "\r\n"
+        assert_eq!(splitter.push(b" line\r"), []);
+        assert_eq!(
+            splitter.push(b"\n\xffbad\n"),
+            [line(b"next line", false), line(b"\xffbad", false)]
+        );
+        assert_eq!(splitter.push(b"tail"), []);
+        assert_eq!(splitter.finish(), Some(line(b"tail", false)));
+        assert_eq!(splitter.finish(), None);
 
-        Ok(())
+        let chunk = line_chunk("[ERR]", &line(b"caf\xe9", false), "t");
+        assert_eq!(chunk.content, "[ERR] caf\u{fffd}\n");
+        assert_eq!(chunk.raw_content, b"[ERR] caf\xe9\n");
+        let chunk = line_chunk("[OUT]", &line(b" 42%", true), "t");
+        assert_eq!(chunk.content, "[OUT]  42%\r");
+        assert!(chunk.raw_content.is_empty());
     }
 }
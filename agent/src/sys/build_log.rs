@@ -0,0 +1,198 @@
+// agent/src/sys/build_log.rs
+//
+// 🛡️ SOLID: Single-Responsibility — On-disk copies of deploy streams.
+//
+// Every deploy's log is gzip'd to `{state_dir}/build-logs/{domain}/{release}.log.gz`
+// (0600: build output can echo secrets). A log over the cap keeps its head and
+// its tail, the parts that say what ran and why it failed, and only the newest
+// logs per domain are kept.
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::collections::VecDeque;
+use std::fs as std_fs;
+use std::io::{Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+const LOG_SUFFIX: &str = ".log.gz";
+
+/// Every domain's logs, one directory each.
+pub fn build_logs_root(state_dir: &Path) -> PathBuf {
+    state_dir.join("build-logs")
+}
+
+pub fn build_log_dir(state_dir: &Path, domain: &str) -> PathBuf {
+    build_logs_root(state_dir).join(domain)
+}
+
+pub fn build_log_path(state_dir: &Path, domain: &str, release: &str) -> PathBuf {
+    build_log_dir(state_dir, domain).join(format!("{}{}", release, LOG_SUFFIX))
+}
+
+/// Releases with a stored log for `domain`, oldest first (release ids are timestamps).
+pub fn list_build_logs(state_dir: &Path, domain: &str) -> Vec<String> {
+    let Ok(entries) = std_fs::read_dir(build_log_dir(state_dir, domain)) else {
+        return Vec::new();
+    };
+    let mut releases: Vec<String> = entries
+        .flatten()
+        .filter_map(|e| {
+            e.file_name()
+                .to_str()
+                .and_then(|name| name.strip_suffix(LOG_SUFFIX))
+                .map(str::to_string)
+        })
+        .collect();
+    releases.sort();
+    releases
+}
+
+/// Removes all but the newest `keep` logs for `domain`. Returns how many went.
+pub fn prune_build_logs(state_dir: &Path, domain: &str, keep: usize) -> Result<usize, String> {
+    let releases = list_build_logs(state_dir, domain);
+    let stale = releases.len().saturating_sub(keep);
+    for release in &releases[..stale] {
+        let path = build_log_path(state_dir, domain, release);
+        std_fs::remove_file(&path).map_err(|e| format!("Failed to remove {:?}: {}", path, e))?;
+    }
+    Ok(stale)
+}
+
+pub fn read_build_log(path: &Path) -> Result<String, String> {
+    let file = std_fs::File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    let mut text = String::new();
+    GzDecoder::new(file)
+        .read_to_string(&mut text)
+        .map_err(|e| format!("Failed to decompress {:?}: {}", path, e))?;
+    Ok(text)
+}
+
+/// Streams one deploy's log to disk. Up to half the cap is written as it
+/// arrives; past that, only the most recent half-cap of text is held in memory
+/// and written (after an omission marker) by `finish`.
+pub struct BuildLogWriter {
+    encoder: GzEncoder<std_fs::File>,
+    head_budget: usize,
+    tail: VecDeque<String>,
+    tail_bytes: usize,
+    tail_budget: usize,
+    omitted_bytes: u64,
+}
+
+impl BuildLogWriter {
+    pub fn create(path: &Path, max_bytes: usize) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            std_fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        let file = std_fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)
+            .map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+        Ok(Self {
+            encoder: GzEncoder::new(file, Compression::default()),
+            head_budget: max_bytes / 2,
+            tail: VecDeque::new(),
+            tail_bytes: 0,
+            tail_budget: max_bytes - max_bytes / 2,
+            omitted_bytes: 0,
+        })
+    }
+
+    pub fn append(&mut self, text: &str) -> Result<(), String> {
+        if text.len() <= self.head_budget {
+            self.head_budget -= text.len();
+            return self
+                .encoder
+                .write_all(text.as_bytes())
+                .map_err(|e| format!("Failed to write build log: {}", e));
+        }
+        self.head_budget = 0;
+        self.tail_bytes += text.len();
+        self.tail.push_back(text.to_string());
+        while self.tail_bytes > self.tail_budget {
+            let Some(dropped) = self.tail.pop_front() else {
+                break;
+            };
+            self.tail_bytes -= dropped.len();
+            self.omitted_bytes += dropped.len() as u64;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        let write = |encoder: &mut GzEncoder<std_fs::File>, text: &str| {
+            encoder
+                .write_all(text.as_bytes())
+                .map_err(|e| format!("Failed to write build log: {}", e))
+        };
+        if self.omitted_bytes > 0 {
+            let marker = format!(
+                "\n[kari] … {} bytes of build output omitted (log size cap) …\n\n",
+                self.omitted_bytes
+            );
+            write(&mut self.encoder, &marker)?;
+        }
+        for chunk in std::mem::take(&mut self.tail) {
+            write(&mut self.encoder, &chunk)?;
+        }
+        self.encoder
+            .finish()
+            .and_then(|file| file.sync_all())
+            .map_err(|e| format!("Failed to finish build log: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oversized_logs_keep_head_and_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = build_log_path(dir.path(), "shop.example.com", "20260101000000");
+        let mut writer = BuildLogWriter::create(&path, 40).unwrap();
+        for i in 0..10 {
+            writer.append(&format!("line {:02}\n", i)).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let text = read_build_log(&path).unwrap();
+        assert!(text.starts_with("line 00\nline 01\n"), "{}", text);
+        assert!(
+            text.contains("48 bytes of build output omitted"),
+            "{}",
+            text
+        );
+        assert!(
+            text.ends_with("omitted (log size cap) …\n\nline 08\nline 09\n"),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn pruning_keeps_the_newest_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        for release in ["20260101000000", "20260102000000", "20260103000000"] {
+            let path = build_log_path(dir.path(), "shop.example.com", release);
+            BuildLogWriter::create(&path, 1024)
+                .unwrap()
+                .finish()
+                .unwrap();
+        }
+        assert_eq!(
+            prune_build_logs(dir.path(), "shop.example.com", 2).unwrap(),
+            1
+        );
+        assert_eq!(
+            list_build_logs(dir.path(), "shop.example.com"),
+            ["20260102000000", "20260103000000"]
+        );
+    }
+}
@@ -0,0 +1,211 @@
+// agent/src/sys/build_queue.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Decides which deploy may build right now.
+//
+// Fetch and build steps are the memory-hungry part of a deploy: a bundler or a
+// compiler easily takes a gigabyte, and several tenants pushing at once used to
+// all build together until the OOM killer picked one. The host's build budget is
+// counted in slots of `SLOT_MEMORY_MB` and `SLOT_CPU_PERCENT`; each build takes
+// the slots of its class for as long as it runs, and builds that do not fit wait
+// in strict arrival order, so a heavy build is never starved by light ones.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+use crate::sys::capacity::Reservations;
+
+/// What one build slot stands for.
+pub const SLOT_MEMORY_MB: u64 = 512;
+pub const SLOT_CPU_PERCENT: u64 = 50;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BuildClass {
+    Light,
+    #[default]
+    Standard,
+    Heavy,
+}
+
+impl BuildClass {
+    pub fn slots(self) -> u32 {
+        match self {
+            BuildClass::Light => 1,
+            BuildClass::Standard => 2,
+            BuildClass::Heavy => 4,
+        }
+    }
+}
+
+/// Derives the budget from the hardware: half of the memory (apps keep running
+/// while their successors build) and every core, never less than one slot.
+pub fn host_slots(physical: Reservations) -> u32 {
+    let by_memory = physical.memory_mb / 2 / SLOT_MEMORY_MB;
+    let by_cpu = physical.cpu_percent / SLOT_CPU_PERCENT;
+    by_memory.min(by_cpu).clamp(1, u32::MAX as u64) as u32
+}
+
+#[derive(Default)]
+struct QueueState {
+    used: u32,
+    next_id: u64,
+    waiting: VecDeque<(u64, u32)>,
+}
+
+pub struct BuildQueue {
+    total: u32,
+    state: Mutex<QueueState>,
+    changed: watch::Sender<u64>,
+}
+
+impl BuildQueue {
+    pub fn new(total: u32) -> Self {
+        Self {
+            total: total.max(1),
+            state: Mutex::new(QueueState::default()),
+            changed: watch::channel(0).0,
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+
+    /// Joins the back of the queue. A class larger than the whole budget is
+    /// capped to it, i.e. it builds alone.
+    pub fn enqueue(self: &Arc<Self>, class: BuildClass) -> BuildTicket {
+        let slots = class.slots().min(self.total);
+        let id = {
+            let mut state = self.state.lock().unwrap();
+            state.next_id += 1;
+            let id = state.next_id;
+            state.waiting.push_back((id, slots));
+            id
+        };
+        BuildTicket {
+            queue: self.clone(),
+            id,
+            slots,
+            admitted: false,
+            rx: self.changed.subscribe(),
+        }
+    }
+
+    fn notify(&self) {
+        self.changed.send_modify(|n| *n = n.wrapping_add(1));
+    }
+}
+
+/// A place in the queue, and once admitted, the slots themselves. Dropping it
+/// (build done, deploy cancelled, client gone) gives either back.
+pub struct BuildTicket {
+    queue: Arc<BuildQueue>,
+    id: u64,
+    slots: u32,
+    admitted: bool,
+    rx: watch::Receiver<u64>,
+}
+
+impl BuildTicket {
+    pub fn slots(&self) -> u32 {
+        self.slots
+    }
+
+    /// `None` once the build may start, otherwise how many deploys wait before
+    /// this one. Admits the ticket when it is at the head and its slots are free.
+    pub fn ahead(&mut self) -> Option<usize> {
+        if self.admitted {
+            return None;
+        }
+        // Marked seen before looking, so a release after this point wakes `changed`.
+        self.rx.borrow_and_update();
+        let mut state = self.queue.state.lock().unwrap();
+        let position = state.waiting.iter().position(|(id, _)| *id == self.id)?;
+        if position > 0 || state.used + self.slots > self.queue.total {
+            return Some(position);
+        }
+        state.waiting.pop_front();
+        state.used += self.slots;
+        drop(state);
+        self.admitted = true;
+        // The next in line moved up, and may fit as well.
+        self.queue.notify();
+        None
+    }
+
+    /// Resolves whenever a build finishes or a waiting deploy leaves the queue.
+    pub async fn changed(&mut self) {
+        // The queue owns the sender and the ticket owns the queue, so this
+        // cannot fail.
+        let _ = self.rx.changed().await;
+    }
+}
+
+impl Drop for BuildTicket {
+    fn drop(&mut self) {
+        let mut state = self.queue.state.lock().unwrap();
+        if self.admitted {
+            state.used -= self.slots;
+        } else {
+            state.waiting.retain(|(id, _)| *id != self.id);
+        }
+        drop(state);
+        self.queue.notify();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_are_admitted_in_order_within_the_budget() {
+        let queue = Arc::new(BuildQueue::new(4));
+        let mut a = queue.enqueue(BuildClass::Standard);
+        let mut heavy = queue.enqueue(BuildClass::Heavy);
+        let mut light = queue.enqueue(BuildClass::Light);
+
+        assert_eq!(a.ahead(), None);
+        // Two slots are free, but the heavy build is first in line and the
+        // light one must not overtake it.
+        assert_eq!(heavy.ahead(), Some(0));
+        assert_eq!(light.ahead(), Some(1));
+
+        drop(a);
+        assert_eq!(heavy.ahead(), None);
+        assert_eq!(light.ahead(), Some(0));
+
+        drop(heavy);
+        assert_eq!(light.ahead(), None);
+    }
+
+    #[test]
+    fn leaving_the_queue_moves_the_rest_up() {
+        let queue = Arc::new(BuildQueue::new(1));
+        let mut running = queue.enqueue(BuildClass::Heavy);
+        assert_eq!(running.slots(), 1);
+        assert_eq!(running.ahead(), None);
+
+        let first = queue.enqueue(BuildClass::Light);
+        let mut second = queue.enqueue(BuildClass::Light);
+        assert_eq!(second.ahead(), Some(1));
+        drop(first);
+        assert_eq!(second.ahead(), Some(0));
+        drop(running);
+        assert_eq!(second.ahead(), None);
+    }
+
+    #[test]
+    fn small_hosts_get_at_least_one_slot() {
+        let tiny = Reservations {
+            memory_mb: 512,
+            cpu_percent: 100,
+        };
+        assert_eq!(host_slots(tiny), 1);
+        let big = Reservations {
+            memory_mb: 16384,
+            cpu_percent: 800,
+        };
+        assert_eq!(host_slots(big), 16);
+    }
+}
@@ -0,0 +1,349 @@
+// agent/src/sys/bundle.rs
+//
+// 🛡️ SOLID: Single-Responsibility — The portable archive format shared by state
+// snapshots and app migration.
+//
+// A bundle is a gzip'd tarball plus a detached `.sig` file holding an
+// HMAC-SHA256 over the whole archive. Sensitive entries (keys, secrets) are
+// sealed individually with AES-256-GCM and carry a `.sealed` suffix. Both keys
+// are derived from operator-supplied key material, so any host given the same
+// material can verify and open the bundle. Config bundles reuse the archive
+// layout but carry an Ed25519 signature instead (see config_bundle.rs).
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fs as std_fs;
+use std::io::Read;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const BUNDLE_VERSION: u32 = 1;
+const MANIFEST_ENTRY: &str = "manifest.json";
+const SEALED_SUFFIX: &str = ".sealed";
+const NONCE_LEN: usize = 12;
+const MIN_KEY_MATERIAL: usize = 32;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub version: u32,
+    pub kind: String, // "state" | "app"
+    pub created_at: i64,
+    pub source_host: String,
+}
+
+/// Keys derived from the operator's bundle key material; wiped on drop.
+pub struct BundleKey {
+    mac: Zeroizing<[u8; 32]>,
+    enc: Zeroizing<[u8; 32]>,
+}
+
+impl BundleKey {
+    /// 🛡️ Zero-Trust: Separate MAC and encryption keys, each derived with its own label.
+    /// Callers keep `material` in a zeroizing buffer; only the derived keys are retained.
+    pub fn derive(material: &[u8]) -> Result<Self, String> {
+        if material.len() < MIN_KEY_MATERIAL {
+            return Err(format!(
+                "Bundle key must be at least {} bytes",
+                MIN_KEY_MATERIAL
+            ));
+        }
+        let derive = |label: &[u8]| -> Result<Zeroizing<[u8; 32]>, String> {
+            let mut mac = <HmacSha256 as Mac>::new_from_slice(material)
+                .map_err(|e| format!("Key derivation failed: {}", e))?;
+            mac.update(label);
+            Ok(Zeroizing::new(mac.finalize().into_bytes().into()))
+        };
+        Ok(Self {
+            mac: derive(b"kari-bundle-mac-v1")?,
+            enc: derive(b"kari-bundle-enc-v1")?,
+        })
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        <HmacSha256 as Mac>::new_from_slice(self.mac.as_ref()).expect("HMAC accepts any key length")
+    }
+
+    fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let cipher = Aes256Gcm::new_from_slice(self.enc.as_ref())
+            .map_err(|e| format!("Cipher init failed: {}", e))?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let mut out = nonce.to_vec();
+        out.extend(
+            cipher
+                .encrypt(&nonce, plaintext)
+                .map_err(|_| "Encryption failed".to_string())?,
+        );
+        Ok(out)
+    }
+
+    fn open(&self, sealed: &[u8]) -> Result<Zeroizing<Vec<u8>>, String> {
+        if sealed.len() < NONCE_LEN {
+            return Err("Sealed entry is truncated".into());
+        }
+        let cipher = Aes256Gcm::new_from_slice(self.enc.as_ref())
+            .map_err(|e| format!("Cipher init failed: {}", e))?;
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map(Zeroizing::new)
+            .map_err(|_| "Sealed entry failed authentication".to_string())
+    }
+}
+
+pub(crate) fn signature_path(bundle_path: &Path) -> PathBuf {
+    let mut name = bundle_path.as_os_str().to_owned();
+    name.push(".sig");
+    PathBuf::from(name)
+}
+
+pub(crate) fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            text.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+        })
+        .collect()
+}
+
+/// Streams the archive through HMAC-SHA256 without loading it into memory.
+fn file_mac(path: &Path, key: &BundleKey) -> Result<HmacSha256, String> {
+    let mut file = std_fs::File::open(path).map_err(|e| format!("Failed to open bundle: {}", e))?;
+    let mut mac = key.mac();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .map_err(|e| format!("Failed to read bundle: {}", e))?;
+        if n == 0 {
+            break;
+        }
+        mac.update(&buf[..n]);
+    }
+    Ok(mac)
+}
+
+// ==============================================================================
+// Writing
+// ==============================================================================
+
+pub struct BundleWriter<'k> {
+    path: PathBuf,
+    key: &'k BundleKey,
+    tar: tar::Builder<GzEncoder<std_fs::File>>,
+}
+
+impl<'k> BundleWriter<'k> {
+    /// Creates the archive (0600) and writes its manifest as the first entry.
+    pub fn create(path: &Path, key: &'k BundleKey, kind: &str) -> Result<Self, String> {
+        let file = std_fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)
+            .map_err(|e| format!("Failed to create bundle {:?}: {}", path, e))?;
+
+        let mut writer = Self {
+            path: path.to_path_buf(),
+            key,
+            tar: tar::Builder::new(GzEncoder::new(file, Compression::default())),
+        };
+
+        let manifest = BundleManifest {
+            version: BUNDLE_VERSION,
+            kind: kind.to_string(),
+            created_at: chrono::Utc::now().timestamp(),
+            source_host: sysinfo::System::host_name().unwrap_or_default(),
+        };
+        let json = serde_json::to_vec_pretty(&manifest)
+            .map_err(|e| format!("Failed to encode manifest: {}", e))?;
+        writer.add_file(MANIFEST_ENTRY, &json, 0o644)?;
+        Ok(writer)
+    }
+
+    pub fn add_file(&mut self, name: &str, data: &[u8], mode: u32) -> Result<(), String> {
+        if name.ends_with(SEALED_SUFFIX) {
+            return Err(format!(
+                "Entry name '{}' uses the reserved sealed suffix",
+                name
+            ));
+        }
+        self.append(name, data, mode)
+    }
+
+    /// 🛡️ Privacy: The plaintext never reaches the archive; only the AES-GCM output does.
+    pub fn add_sealed(&mut self, name: &str, data: &[u8], mode: u32) -> Result<(), String> {
+        let sealed = self.key.seal(data)?;
+        self.append(&format!("{}{}", name, SEALED_SUFFIX), &sealed, mode)
+    }
+
+    fn append(&mut self, name: &str, data: &[u8], mode: u32) -> Result<(), String> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(mode);
+        header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+        header.set_entry_type(tar::EntryType::Regular);
+        self.tar
+            .append_data(&mut header, name, data)
+            .map_err(|e| format!("Failed to append '{}' to bundle: {}", name, e))
+    }
+
+    /// Flushes the archive, then writes the detached signature next to it.
+    pub fn finish(self) -> Result<PathBuf, String> {
+        let encoder = self
+            .tar
+            .into_inner()
+            .map_err(|e| format!("Failed to finalize bundle: {}", e))?;
+        let file = encoder
+            .finish()
+            .map_err(|e| format!("Failed to finalize bundle: {}", e))?;
+        file.sync_all()
+            .map_err(|e| format!("Failed to sync bundle: {}", e))?;
+
+        let signature: String = file_mac(&self.path, self.key)?
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        std_fs::write(signature_path(&self.path), signature)
+            .map_err(|e| format!("Failed to write bundle signature: {}", e))?;
+        Ok(self.path)
+    }
+}
+
+// ==============================================================================
+// Reading
+// ==============================================================================
+
+/// Verifies the detached signature, then hands every entry (sealed ones already
+/// opened, with the suffix stripped) to `visit` in archive order.
+/// 🛡️ Zero-Trust: Nothing is parsed until the whole archive has authenticated.
+pub fn read_bundle(
+    path: &Path,
+    key: &BundleKey,
+    visit: impl FnMut(&str, u32, &[u8]) -> Result<(), String>,
+) -> Result<BundleManifest, String> {
+    let signature = std_fs::read_to_string(signature_path(path))
+        .map_err(|e| format!("Bundle signature missing: {}", e))?;
+    let expected =
+        decode_hex(signature.trim()).ok_or_else(|| "Bundle signature is malformed".to_string())?;
+
+    // verify_slice compares in constant time
+    file_mac(path, key)?
+        .verify_slice(&expected)
+        .map_err(|_| "SECURITY VIOLATION: Bundle signature mismatch".to_string())?;
+
+    read_entries(path, Some(key), visit)
+}
+
+/// Walks an archive whose signature the caller has already verified. Without
+/// a key, a sealed entry is an error rather than something to skip.
+pub(crate) fn read_entries(
+    path: &Path,
+    key: Option<&BundleKey>,
+    mut visit: impl FnMut(&str, u32, &[u8]) -> Result<(), String>,
+) -> Result<BundleManifest, String> {
+    let file = std_fs::File::open(path).map_err(|e| format!("Failed to open bundle: {}", e))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    let mut manifest: Option<BundleManifest> = None;
+
+    for entry in archive
+        .entries()
+        .map_err(|e| format!("Failed to read bundle: {}", e))?
+    {
+        let mut entry = entry.map_err(|e| format!("Corrupt bundle entry: {}", e))?;
+        if !entry.header().entry_type().is_file() {
+            continue; // 🛡️ Links and devices are never materialized
+        }
+        let name = entry
+            .path()
+            .map_err(|e| format!("Corrupt bundle entry path: {}", e))?
+            .to_string_lossy()
+            .to_string();
+        let mode = entry.header().mode().unwrap_or(0o600);
+        let mut data = Zeroizing::new(Vec::new());
+        entry
+            .read_to_end(&mut data)
+            .map_err(|e| format!("Failed to read '{}': {}", name, e))?;
+
+        if name == MANIFEST_ENTRY {
+            let parsed: BundleManifest = serde_json::from_slice(&data)
+                .map_err(|e| format!("Corrupt bundle manifest: {}", e))?;
+            if parsed.version != BUNDLE_VERSION {
+                return Err(format!("Unsupported bundle version {}", parsed.version));
+            }
+            manifest = Some(parsed);
+            continue;
+        }
+        if manifest.is_none() {
+            return Err("Bundle manifest must be the first entry".into());
+        }
+
+        match (name.strip_suffix(SEALED_SUFFIX), key) {
+            (Some(plain_name), Some(key)) => visit(plain_name, mode, &key.open(&data)?)?,
+            (Some(plain_name), None) => {
+                return Err(format!(
+                    "Sealed entry '{}' cannot be opened here",
+                    plain_name
+                ));
+            }
+            (None, _) => visit(&name, mode, &data)?,
+        }
+    }
+
+    manifest.ok_or_else(|| "Bundle has no manifest".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_and_tamper_detection() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.kbundle");
+        let key = BundleKey::derive(&[7u8; 32]).unwrap();
+
+        let mut writer = BundleWriter::create(&path, &key, "state").unwrap();
+        writer.add_file("plain.txt", b"hello", 0o644).unwrap();
+        writer
+            .add_sealed("secret.pem", b"-----BEGIN KEY-----", 0o600)
+            .unwrap();
+        writer.finish().unwrap();
+
+        let mut seen = Vec::new();
+        let manifest = read_bundle(&path, &key, |name, mode, data| {
+            seen.push((name.to_string(), mode, data.to_vec()));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(manifest.kind, "state");
+        assert_eq!(seen[0], ("plain.txt".into(), 0o644, b"hello".to_vec()));
+        assert_eq!(
+            seen[1],
+            ("secret.pem".into(), 0o600, b"-----BEGIN KEY-----".to_vec())
+        );
+
+        // Wrong key: the signature no longer verifies
+        let other = BundleKey::derive(&[8u8; 32]).unwrap();
+        assert!(read_bundle(&path, &other, |_, _, _| Ok(())).is_err());
+
+        // Flipped byte: the signature no longer verifies
+        let mut bytes = std_fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        std_fs::write(&path, bytes).unwrap();
+        assert!(read_bundle(&path, &key, |_, _, _| Ok(())).is_err());
+    }
+}
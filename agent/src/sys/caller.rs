@@ -0,0 +1,108 @@
+// agent/src/sys/caller.rs
+//
+// 🛡️ Zero-Trust: Who is asking. The accept loop authenticates every connection
+// by its kernel credentials (SO_PEERCRED) and wraps it in an
+// AuthenticatedStream, so tonic attaches the Caller to each request on that
+// connection. ValidatingAgent refuses requests without one; handlers read it
+// with Caller::of, and the operations and events they start carry it, so an
+// action can always be traced to the peer that asked for it.
+
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tonic::Request;
+use tonic::transport::server::Connected;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Caller {
+    Peer { uid: u32, pid: Option<i32> }, // A local process, as the kernel reported it
+    Agent,                               // The agent itself: timers, watchers, bundles
+}
+
+impl Caller {
+    /// The identity attached at accept time, if the request came over a socket.
+    pub fn authenticated<T>(request: &Request<T>) -> Option<&Self> {
+        request.extensions().get::<Self>()
+    }
+
+    /// Who a handler is acting for. Requests the agent builds for itself carry
+    /// no identity and are its own.
+    pub fn of<T>(request: &Request<T>) -> Self {
+        Self::authenticated(request).cloned().unwrap_or(Self::Agent)
+    }
+}
+
+impl fmt::Display for Caller {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Peer {
+                uid,
+                pid: Some(pid),
+            } => write!(f, "uid {} (pid {})", uid, pid),
+            Self::Peer { uid, pid: None } => write!(f, "uid {}", uid),
+            Self::Agent => f.write_str("agent"),
+        }
+    }
+}
+
+/// A connection the accept loop has already authenticated.
+pub struct AuthenticatedStream<S> {
+    io: S,
+    caller: Caller,
+}
+
+impl<S> AuthenticatedStream<S> {
+    pub fn new(io: S, caller: Caller) -> Self {
+        Self { io, caller }
+    }
+}
+
+impl<S> Connected for AuthenticatedStream<S> {
+    type ConnectInfo = Caller;
+
+    fn connect_info(&self) -> Caller {
+        self.caller.clone()
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for AuthenticatedStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for AuthenticatedStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().io).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().io).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+    }
+}
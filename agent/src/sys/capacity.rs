@@ -0,0 +1,156 @@
+// agent/src/sys/capacity.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Host capacity probes used to fail fast
+// before work starts. A build that dies at 95% on ENOSPC wastes minutes and
+// leaves a half-written release behind; checking first costs milliseconds.
+
+use std::fs as std_fs;
+use std::path::Path;
+
+use crate::sys::state::{AppRecord, NS_APPS, list_records};
+use crate::sys::traits::StateStore;
+
+const MB: u64 = 1024 * 1024;
+
+/// The filesystem holding `path` (longest mount-point match).
+fn disk_for<'d>(disks: &'d sysinfo::Disks, path: &Path) -> Option<&'d sysinfo::Disk> {
+    disks
+        .list()
+        .iter()
+        .filter(|d| path.starts_with(d.mount_point()))
+        .max_by_key(|d| d.mount_point().as_os_str().len())
+        .filter(|d| d.total_space() > 0)
+}
+
+/// Used-space percentage of the filesystem holding `path`.
+pub fn disk_usage_percent(path: &Path) -> Option<f64> {
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    disk_for(&disks, path).map(|d| {
+        let used = d.total_space() - d.available_space();
+        used as f64 * 100.0 / d.total_space() as f64
+    })
+}
+
+/// Free space on the filesystem holding `path`, in MB.
+pub fn free_disk_mb(path: &Path) -> Option<u64> {
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    disk_for(&disks, path).map(|d| d.available_space() / MB)
+}
+
+/// Apparent size of a directory tree in bytes (symlinks are not followed).
+pub fn dir_size_bytes(path: &Path) -> u64 {
+    let Ok(entries) = std_fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.path().symlink_metadata() {
+            Ok(meta) if meta.is_dir() => dir_size_bytes(&entry.path()),
+            Ok(meta) if meta.is_file() => meta.len(),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Apparent size of a directory tree in MB (symlinks are not followed).
+pub fn dir_size_mb(path: &Path) -> u64 {
+    dir_size_bytes(path).div_ceil(MB)
+}
+
+/// Size of the newest release under `{app_dir}/releases`, the best predictor of
+/// what the next one will need. Zero for a first deploy.
+pub fn last_release_mb(app_dir: &Path) -> u64 {
+    let Ok(entries) = std_fs::read_dir(app_dir.join("releases")) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+        .map(|e| e.path())
+        .max()
+        .map(|newest| dir_size_mb(&newest))
+        .unwrap_or(0)
+}
+
+/// Fails when the filesystem holding `path` has less than `needed_mb` free.
+/// An undeterminable filesystem is not treated as a shortfall.
+pub fn check_disk(path: &Path, needed_mb: u64) -> Result<(), String> {
+    match free_disk_mb(path) {
+        Some(free) if free < needed_mb => Err(format!(
+            "disk: {} MB needed under {:?}, {} MB free",
+            needed_mb, path, free
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Resources promised to kari apps via MemoryMax / CPUQuota.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Reservations {
+    pub memory_mb: u64,
+    pub cpu_percent: u64, // 100 = one full core
+}
+
+/// Sums the reservations of every kari app except `exclude_app` (a re-provision
+/// replaces its own reservation rather than adding to it).
+pub async fn committed_resources(
+    state: &dyn StateStore,
+    exclude_app: Option<&str>,
+) -> Result<Reservations, String> {
+    let apps: Vec<AppRecord> = list_records(state, NS_APPS).await?;
+    Ok(apps
+        .iter()
+        .filter(|a| exclude_app.is_none_or(|id| a.app_id != id))
+        .fold(Reservations::default(), |acc, a| Reservations {
+            memory_mb: acc.memory_mb + a.memory_limit_mb as u64,
+            cpu_percent: acc.cpu_percent + a.cpu_limit_percent as u64,
+        }))
+}
+
+/// What the host physically has, in the same units as `Reservations`.
+pub fn physical_resources() -> Reservations {
+    let mut sys = sysinfo::System::new();
+    sys.refresh_memory();
+    sys.refresh_cpu();
+    Reservations {
+        memory_mb: sys.total_memory() / MB,
+        cpu_percent: sys.cpus().len() as u64 * 100,
+    }
+}
+
+/// Fails when `requested` does not fit under `physical * overcommit_ratio` once
+/// `committed` is accounted for. Memory defaults to no overcommit (ratio 1.0);
+/// CPU is only limited when an explicit ratio is configured.
+pub fn check_reservation(
+    requested: Reservations,
+    committed: Reservations,
+    overcommit_ratio: Option<f64>,
+) -> Result<(), String> {
+    let physical = physical_resources();
+    let limit = |physical: u64, ratio: f64| (physical as f64 * ratio) as u64;
+
+    let memory_limit = limit(physical.memory_mb, overcommit_ratio.unwrap_or(1.0));
+    let unreserved_mb = memory_limit.saturating_sub(committed.memory_mb);
+    if requested.memory_mb > unreserved_mb {
+        return Err(format!(
+            "memory: {} MB requested, {} MB unreserved ({} MB physical, {} MB committed)",
+            requested.memory_mb, unreserved_mb, physical.memory_mb, committed.memory_mb
+        ));
+    }
+
+    if let Some(ratio) = overcommit_ratio {
+        let cpu_limit = limit(physical.cpu_percent, ratio);
+        let unreserved_cpu = cpu_limit.saturating_sub(committed.cpu_percent);
+        if requested.cpu_percent > unreserved_cpu {
+            return Err(format!(
+                "cpu: {}% requested, {}% unreserved ({}% physical, {}% committed, ratio {})",
+                requested.cpu_percent,
+                unreserved_cpu,
+                physical.cpu_percent,
+                committed.cpu_percent,
+                ratio
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,140 @@
+// agent/src/sys/certificates.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Certificates shared by several domains.
+//
+// A certificate is stored once, under the name it was installed with (its
+// primary domain), however many SANs it covers. Each app's vhost points at
+// that directory (AppRecord::certificate_name), and the certificate's record
+// (NS_CERTIFICATES) lists the domains referencing it. Deleting an app drops
+// its domain's reference; the files go only with the last one, so removing
+// one domain never takes HTTPS away from another.
+
+use std::path::Path;
+use tracing::info;
+
+use crate::sys::state::{
+    AppRecord, CertificateRecord, NS_APPS, NS_CERT_KEYS, NS_CERTIFICATES, list_records, put_record,
+};
+use crate::sys::traits::StateStore;
+
+/// The installed certificate covering `domain`, if any.
+pub async fn covering(
+    state: &dyn StateStore,
+    domain: &str,
+) -> Result<Option<CertificateRecord>, String> {
+    let records: Vec<CertificateRecord> = list_records(state, NS_CERTIFICATES).await?;
+    Ok(records
+        .into_iter()
+        .find(|r| r.domains.iter().any(|d| d == domain)))
+}
+
+/// The records `installed` takes domains from: a domain is served by the
+/// certificate installed for it last.
+fn superseded(
+    records: Vec<CertificateRecord>,
+    installed: &CertificateRecord,
+) -> Vec<CertificateRecord> {
+    records
+        .into_iter()
+        .filter(|r| r.name != installed.name)
+        .filter_map(|mut r| {
+            let before = r.domains.len();
+            r.domains.retain(|d| !installed.domains.contains(d));
+            (r.domains.len() != before).then_some(r)
+        })
+        .collect()
+}
+
+/// Records a certificate InstallCertificate just wrote. Certificates left
+/// without a domain or an app are removed.
+pub async fn register(
+    state: &dyn StateStore,
+    ssl_storage_dir: &Path,
+    installed: &CertificateRecord,
+) -> Result<(), String> {
+    let records: Vec<CertificateRecord> = list_records(state, NS_CERTIFICATES).await?;
+    put_record(state, NS_CERTIFICATES, &installed.name, installed).await?;
+    for record in superseded(records, installed) {
+        put_record(state, NS_CERTIFICATES, &record.name, &record).await?;
+        prune(state, ssl_storage_dir, &record).await?;
+    }
+    Ok(())
+}
+
+/// Drops `domain`'s reference to its certificate, once its app is gone.
+/// Returns the certificate name when that was the last reference and its
+/// files were removed.
+pub async fn release(
+    state: &dyn StateStore,
+    ssl_storage_dir: &Path,
+    domain: &str,
+) -> Result<Option<String>, String> {
+    let Some(mut record) = covering(state, domain).await? else {
+        return Ok(None);
+    };
+    record.domains.retain(|d| d != domain);
+    put_record(state, NS_CERTIFICATES, &record.name, &record).await?;
+    prune(state, ssl_storage_dir, &record).await
+}
+
+/// Removes a certificate nothing references any more: no domain in its
+/// record, and no app whose vhost still points at it.
+async fn prune(
+    state: &dyn StateStore,
+    ssl_storage_dir: &Path,
+    record: &CertificateRecord,
+) -> Result<Option<String>, String> {
+    if !record.domains.is_empty() {
+        return Ok(None);
+    }
+    let apps: Vec<AppRecord> = list_records(state, NS_APPS).await?;
+    if apps.iter().any(|a| a.certificate_name() == record.name) {
+        return Ok(None);
+    }
+    if record.name.is_empty() || record.name.contains("..") || record.name.contains('/') {
+        return Err("SECURITY VIOLATION: Invalid certificate name".into());
+    }
+    match tokio::fs::remove_dir_all(ssl_storage_dir.join(&record.name)).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(format!(
+                "Failed to remove certificate {}: {}",
+                record.name, e
+            ));
+        }
+        _ => {}
+    }
+    state.delete(NS_CERTIFICATES, &record.name).await?;
+    state.delete(NS_CERT_KEYS, &record.name).await?;
+    info!("🔐 Certificate {} removed: no domain uses it", record.name);
+    Ok(Some(record.name.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cert(name: &str, domains: &[&str]) -> CertificateRecord {
+        CertificateRecord {
+            name: name.into(),
+            domains: domains.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn a_shared_certificate_takes_over_the_domains_it_covers() {
+        let records = vec![
+            cert("shop.example", &["shop.example"]),
+            cert("blog.example", &["blog.example", "www.blog.example"]),
+            cert("example.com", &["example.com"]),
+        ];
+        let installed = cert(
+            "example.com",
+            &["example.com", "shop.example", "www.blog.example"],
+        );
+        let changed = superseded(records, &installed);
+        assert_eq!(changed.len(), 2);
+        assert_eq!(changed[0].name, "shop.example");
+        assert!(changed[0].domains.is_empty());
+        assert_eq!(changed[1].domains, vec!["blog.example".to_string()]);
+    }
+}
@@ -0,0 +1,126 @@
+// agent/src/sys/circuit.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Takes crash-looping apps out of rotation.
+//
+// Detection is systemd's: every app unit carries StartLimitBurst and
+// StartLimitIntervalSec, so after too many failed starts systemd stops
+// restarting it (`Result=start-limit-hit`). The breaker notices that, points
+// the app's vhost at the maintenance page instead of a dead upstream, and
+// records it in `vhost.maintenance` so every later vhost rewrite keeps it.
+// Nothing restarts the unit until `reset` (ResetCircuitBreaker) closes the
+// breaker again.
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::sys::caller::Caller;
+use crate::sys::state::{AppRecord, NS_APPS, list_records, put_record};
+use crate::sys::systemd::ServiceManager;
+use crate::sys::traits::{ProxyManager, StateStore};
+
+/// A breaker that opened or closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitEvent {
+    pub app_id: String,
+    pub domain_name: String,
+    pub service_name: String,
+    pub open: bool, // false: closed again by ResetCircuitBreaker
+    pub detail: String,
+    pub at: i64,              // Unix seconds
+    pub initiated_by: Caller, // The agent when it opened; whoever reset it
+}
+
+impl CircuitEvent {
+    fn new(app: &AppRecord, open: bool, detail: String, initiated_by: Caller) -> Self {
+        Self {
+            app_id: app.app_id.clone(),
+            domain_name: app.domain_name.clone(),
+            service_name: app.service_name.clone(),
+            open,
+            detail,
+            at: chrono::Utc::now().timestamp(),
+            initiated_by,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    pub state: Arc<dyn StateStore>,
+    pub services: Arc<dyn ServiceManager>,
+    pub proxy: Arc<dyn ProxyManager>,
+}
+
+impl CircuitBreaker {
+    /// Checks every `interval` for as long as the agent runs.
+    pub async fn run(self, interval: Duration, events: broadcast::Sender<CircuitEvent>) {
+        let mut ticker = tokio::time::interval(interval.max(Duration::from_millis(10)));
+        loop {
+            ticker.tick().await;
+            match self.trip_crash_loops().await {
+                Ok(opened) => {
+                    for event in opened {
+                        // No subscribers is fine; the warn below is the record
+                        let _ = events.send(event);
+                    }
+                }
+                Err(e) => warn!("🔌 Circuit check failed: {}", e),
+            }
+        }
+    }
+
+    /// Opens the breaker of every app whose unit systemd gave up on.
+    pub async fn trip_crash_loops(&self) -> Result<Vec<CircuitEvent>, String> {
+        let apps: Vec<AppRecord> = list_records(self.state.as_ref(), NS_APPS).await?;
+        let mut opened = Vec::new();
+        for mut app in apps.into_iter().filter(|a| !a.vhost.maintenance) {
+            match self.services.start_limit_hit(&app.service_name).await {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    warn!("🔌 Could not read {} state: {}", app.service_name, e);
+                    continue;
+                }
+            }
+
+            app.vhost.maintenance = true;
+            put_record(self.state.as_ref(), NS_APPS, &app.app_id, &app).await?;
+            let mut detail = "crash loop: systemd stopped restarting the unit".to_string();
+            if let Some(port) = app.port
+                && let Err(e) = self
+                    .proxy
+                    .create_vhost(&app.domain_name, port, &app.vhost)
+                    .await
+            {
+                detail.push_str(&format!("; maintenance page not applied: {}", e));
+            }
+            warn!("🔌 Circuit opened for {}: {}", app.domain_name, detail);
+            opened.push(CircuitEvent::new(&app, true, detail, Caller::Agent));
+        }
+        Ok(opened)
+    }
+
+    /// Closes the breaker: clears systemd's start counter, starts the unit and
+    /// routes the vhost back to it.
+    pub async fn reset(&self, mut app: AppRecord, caller: Caller) -> Result<CircuitEvent, String> {
+        self.services.reset_failed(&app.service_name).await?;
+        self.services.start(&app.service_name).await?;
+
+        app.vhost.maintenance = false;
+        if let Some(port) = app.port {
+            self.proxy
+                .create_vhost(&app.domain_name, port, &app.vhost)
+                .await?;
+        }
+        put_record(self.state.as_ref(), NS_APPS, &app.app_id, &app).await?;
+        info!("🔌 Circuit closed for {} by {}", app.domain_name, caller);
+        Ok(CircuitEvent::new(
+            &app,
+            false,
+            "reset: unit restarted".to_string(),
+            caller,
+        ))
+    }
+}
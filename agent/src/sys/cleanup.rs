@@ -1,8 +1,67 @@
+use crate::sys::state::ReleaseRecord;
 use crate::sys::traits::ReleaseManager;
 use async_trait::async_trait;
 use std::path::{Path, PathBuf};
 use tokio::fs;
 
+/// The release `{app_dir}/current` points at, if any.
+pub fn current_release(app_dir: &Path) -> Option<String> {
+    std::fs::read_link(app_dir.join("current"))
+        .ok()
+        .and_then(|target| target.file_name().map(|n| n.to_string_lossy().to_string()))
+}
+
+/// Points `{app_dir}/current` at `releases/{release}`. The new link is renamed
+/// over the old one, so the app never sees a moment without `current`.
+pub fn switch_current_release(app_dir: &Path, release: &str) -> Result<(), String> {
+    let staged = app_dir.join(format!(".current-{}", release));
+    let _ = std::fs::remove_file(&staged);
+    std::os::unix::fs::symlink(Path::new("releases").join(release), &staged)
+        .map_err(|e| format!("Failed to link release {}: {}", release, e))?;
+    std::fs::rename(&staged, app_dir.join("current")).map_err(|e| {
+        let _ = std::fs::remove_file(&staged);
+        format!("Failed to switch current release: {}", e)
+    })
+}
+
+/// ⏪ The release a rollback returns to: of the domain's releases other than
+/// `current` that are still on disk, the one most recently live. Releases
+/// never activated (warm standbys) only qualify when no activation was ever
+/// recorded, and then only if older than `current`.
+pub fn rollback_target(
+    releases: Vec<ReleaseRecord>,
+    domain_name: &str,
+    current: Option<&str>,
+    on_disk: impl Fn(&str) -> bool,
+) -> Option<ReleaseRecord> {
+    releases
+        .into_iter()
+        .filter(|r| r.domain_name == domain_name && Some(r.release.as_str()) != current)
+        .filter(|r| r.activated_at.is_some() || current.is_some_and(|c| r.release.as_str() < c))
+        .filter(|r| on_disk(&r.release))
+        .max_by(|a, b| (a.activated_at, &a.release).cmp(&(b.activated_at, &b.release)))
+}
+
+/// Release directories are named for their UTC build time, `%Y%m%d%H%M%S`.
+pub fn is_release_name(name: &str) -> bool {
+    name.len() == 14 && name.chars().all(|c| c.is_ascii_digit())
+}
+
+/// The release directories under `releases_dir`, newest first.
+pub fn releases_on_disk(releases_dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(releases_dir) else {
+        return Vec::new();
+    };
+    let mut releases: Vec<String> = entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+        .filter(|name| is_release_name(name))
+        .collect();
+    releases.sort_by(|a, b| b.cmp(a));
+    releases
+}
+
 pub struct SystemReleaseManager;
 
 #[async_trait]
@@ -48,10 +107,7 @@ impl ReleaseManager for SystemReleaseManager {
             let name_str = file_name.to_string_lossy();
 
             // 3. Strict Timestamp Validation
-            let is_valid_timestamp =
-                name_str.len() == 14 && name_str.chars().all(|c| c.is_ascii_digit());
-
-            if file_type.is_dir() && is_valid_timestamp {
+            if file_type.is_dir() && is_release_name(&name_str) {
                 paths.push(path);
             }
         }
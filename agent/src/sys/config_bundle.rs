@@ -0,0 +1,165 @@
+// agent/src/sys/config_bundle.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Opens signed config bundles for hosts
+// that are provisioned without a live control plane.
+//
+// A config bundle is an ordinary bundle (see bundle.rs) of kind "config" whose
+// entries are `*.pb` files, each a protobuf-encoded `ConfigSpec`: the same
+// ProvisionJailRequest / FirewallPolicy / JobIntent messages the control plane
+// would send over gRPC. Unlike state bundles, the `.sig` is an Ed25519
+// signature over the archive: the operator keeps the signing key and hosts pin
+// only the public half, so neither a host nor whoever carries the bundle
+// across the air gap can sign one. Any Ed25519 signer works, e.g.
+// `openssl pkeyutl -sign -rawin -inkey operator.pem -in site.kbundle | xxd -p -c 64`.
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use prost::Message;
+use std::io::Write;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use crate::server::kari_agent::ConfigSpec;
+use crate::sys::bundle::{self, BundleManifest};
+
+pub const CONFIG_BUNDLE_KIND: &str = "config";
+const SPEC_SUFFIX: &str = ".pb";
+
+/// Reads the operator's pinned public key (64 hex characters).
+/// 🛡️ Zero-Trust: The key is public, but one anyone else could rewrite, or
+/// that this process does not own, may have been swapped; it is refused.
+pub fn pinned_key(path: &Path) -> Result<VerifyingKey, String> {
+    let meta = std::fs::metadata(path)
+        .map_err(|e| format!("No pinned config bundle key at {}: {}", path.display(), e))?;
+    if meta.mode() & 0o022 != 0 || meta.uid() != nix::unistd::geteuid().as_raw() {
+        return Err(format!(
+            "Pinned key {} must be owned by the agent and writable only by it",
+            path.display()
+        ));
+    }
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read pinned key: {}", e))?;
+    let bytes: [u8; 32] = bundle::decode_hex(text.trim())
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| "Pinned key must be a hex-encoded Ed25519 public key".to_string())?;
+    VerifyingKey::from_bytes(&bytes)
+        .map_err(|_| "Pinned key is not a valid Ed25519 public key".to_string())
+}
+
+/// Verifies `archive` against `signature` (hex, as in a `.sig` file) and
+/// decodes its specs, merged in archive order. The archive is staged in
+/// `scratch_dir` only for as long as it is read.
+pub fn open(
+    archive: &[u8],
+    signature: &str,
+    key: &VerifyingKey,
+    scratch_dir: &Path,
+) -> Result<(BundleManifest, ConfigSpec), String> {
+    let signature = bundle::decode_hex(signature.trim())
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+        .ok_or_else(|| "Bundle signature is malformed".to_string())?;
+    // 🛡️ Zero-Trust: verify_strict also rejects malleable and small-order signatures
+    key.verify_strict(archive, &signature)
+        .map_err(|_| "SECURITY VIOLATION: Bundle signature mismatch".to_string())?;
+
+    let staged = tempfile::Builder::new()
+        .prefix(".config-bundle-")
+        .tempfile_in(scratch_dir)
+        .map_err(|e| format!("Failed to stage bundle: {}", e))?;
+    staged
+        .as_file()
+        .write_all(archive)
+        .map_err(|e| format!("Failed to stage bundle: {}", e))?;
+
+    let mut spec = ConfigSpec::default();
+    // Config bundles carry no sealed entries, so no bundle key is needed
+    let manifest = bundle::read_entries(staged.path(), None, |name, _, data| {
+        if !name.ends_with(SPEC_SUFFIX) || name.contains('/') {
+            return Err(format!("Unexpected entry '{}' in config bundle", name));
+        }
+        let part = ConfigSpec::decode(data)
+            .map_err(|e| format!("'{}' is not a ConfigSpec: {}", name, e))?;
+        spec.apps.extend(part.apps);
+        spec.firewall_rules.extend(part.firewall_rules);
+        spec.jobs.extend(part.jobs);
+        Ok(())
+    })?;
+
+    if manifest.kind != CONFIG_BUNDLE_KIND {
+        return Err(format!(
+            "Bundle is a '{}' bundle, not a config bundle",
+            manifest.kind
+        ));
+    }
+    Ok((manifest, spec))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::kari_agent::JobIntent;
+    use crate::sys::bundle::{BundleKey, BundleWriter};
+    use ed25519_dalek::{Signer, SigningKey};
+    use std::os::unix::fs::PermissionsExt;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn specs_merge_only_from_authentic_config_bundles() {
+        let dir = tempfile::tempdir().unwrap();
+        let scratch = tempfile::tempdir().unwrap();
+        let operator = SigningKey::from_bytes(&[9u8; 32]);
+        let key_path = dir.path().join("config-bundle.pub");
+        std::fs::write(&key_path, hex(operator.verifying_key().as_bytes())).unwrap();
+        std::fs::set_permissions(&key_path, std::fs::Permissions::from_mode(0o666)).unwrap();
+        assert!(pinned_key(&key_path).is_err());
+        std::fs::set_permissions(&key_path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let key = pinned_key(&key_path).unwrap();
+
+        let job = |name: &str| ConfigSpec {
+            jobs: vec![JobIntent {
+                job_name: name.into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let write = |kind: &str, entries: &[(&str, Vec<u8>)]| {
+            let path = dir.path().join(format!("{}.kbundle", kind));
+            let archive_key = BundleKey::derive(&[1u8; 32]).unwrap();
+            let mut writer = BundleWriter::create(&path, &archive_key, kind).unwrap();
+            for (name, data) in entries {
+                writer.add_file(name, data, 0o644).unwrap();
+            }
+            writer.finish().unwrap();
+            let archive = std::fs::read(&path).unwrap();
+            let sig = hex(&operator.sign(&archive).to_bytes());
+            (archive, sig)
+        };
+
+        let (archive, sig) = write(
+            "config",
+            &[
+                ("10-base.pb", job("backup").encode_to_vec()),
+                ("20-extra.pb", job("report").encode_to_vec()),
+            ],
+        );
+        let (manifest, spec) = open(&archive, &sig, &key, scratch.path()).unwrap();
+        assert_eq!(manifest.kind, "config");
+        let names: Vec<&str> = spec.jobs.iter().map(|j| j.job_name.as_str()).collect();
+        assert_eq!(names, ["backup", "report"]);
+
+        // The HMAC a host could compute is no substitute for the operator's signature
+        let hmac_sig = std::fs::read_to_string(dir.path().join("config.kbundle.sig")).unwrap();
+        assert!(open(&archive, &hmac_sig, &key, scratch.path()).is_err());
+        let forged = hex(&SigningKey::from_bytes(&[8u8; 32]).sign(&archive).to_bytes());
+        let err = open(&archive, &forged, &key, scratch.path()).unwrap_err();
+        assert!(err.starts_with("SECURITY VIOLATION"));
+        let (state, sig) = write("state", &[]);
+        assert!(open(&state, &sig, &key, scratch.path()).is_err());
+        let (stray, sig) = write("config", &[("notes.txt", b"hi".to_vec())]);
+        assert!(open(&stray, &sig, &key, scratch.path()).is_err());
+        // Nothing is left behind in the scratch dir
+        assert_eq!(std::fs::read_dir(scratch.path()).unwrap().count(), 0);
+    }
+}
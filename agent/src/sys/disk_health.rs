@@ -0,0 +1,432 @@
+// agent/src/sys/disk_health.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Warns about a dying disk before it takes the host down.
+//
+// Two sources, because neither catches everything: the drive's own SMART data
+// (smartctl), which sees sectors being remapped long before reads fail, and
+// the kernel log, which sees the I/O and filesystem errors a drive without
+// SMART (virtual disks, some RAID controllers) never reports. The latest
+// assessment is served by GetSystemStatus; changes are pushed to
+// StreamDiskHealthEvents and, through agent.toml routes, to notifications.
+
+use async_trait::async_trait;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::sys::privilege;
+use crate::sys::traits::{DiskHealthProbe, KernelDiskError, SmartReport};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Share of rated NVMe endurance after which a drive is worth replacing.
+const ENDURANCE_WARN_PERCENT: u32 = 90;
+
+/// Kernel messages that mean data did not make it to or from a disk.
+static KERNEL_DISK_ERRORS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    [
+        // blk_update_request / print_req_error / Buffer I/O error
+        r"(?:I/O|medium|target) error(?:,| on) dev ([A-Za-z0-9]+)",
+        r"(?:EXT[234]-fs|BTRFS) (?:error|critical) \(device ([A-Za-z0-9-]+)\)",
+        r"XFS \(([A-Za-z0-9-]+)\): .*(?:[Ee]rror|[Cc]orruption)",
+    ]
+    .iter()
+    .map(|pattern| Regex::new(pattern).expect("Failed to compile kernel disk error rule"))
+    .collect()
+});
+
+/// The kernel device a disk error line is about, if it is one.
+pub fn classify_kernel_line(line: &str) -> Option<String> {
+    KERNEL_DISK_ERRORS
+        .iter()
+        .find_map(|rule| rule.captures(line))
+        .map(|captures| captures[1].to_string())
+}
+
+/// The whole-disk device node for a kernel partition name, in the form
+/// smartctl reports it: "sda1" → "/dev/sda", "nvme0n1p2" → "/dev/nvme0".
+pub fn disk_of(kernel_name: &str) -> String {
+    let name = if let Some(rest) = kernel_name.strip_prefix("nvme") {
+        let controller: String = rest.chars().take_while(char::is_ascii_digit).collect();
+        format!("nvme{}", controller)
+    } else if ["sd", "vd", "xvd", "hd"]
+        .iter()
+        .any(|prefix| kernel_name.starts_with(prefix))
+    {
+        kernel_name
+            .trim_end_matches(|c: char| c.is_ascii_digit())
+            .to_string()
+    } else {
+        kernel_name.to_string()
+    };
+    format!("/dev/{}", name)
+}
+
+/// One disk as GetSystemStatus reports it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiskStatus {
+    pub device: String,
+    pub smart: Option<SmartReport>, // None: smartctl could not read this disk
+    pub io_errors: u64,             // Kernel errors seen since the agent started
+    pub last_error: String,
+    pub problems: Vec<String>, // Empty when healthy
+}
+
+/// A disk whose problems changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskHealthEvent {
+    pub device: String,
+    pub failing: bool,
+    pub detail: String,
+    pub at: i64, // Unix seconds
+}
+
+/// What is wrong with a disk, most serious first.
+pub fn assess(smart: Option<&SmartReport>, io_errors: u64, last_error: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some(smart) = smart {
+        if !smart.passed {
+            problems.push("SMART overall health self-assessment failed".to_string());
+        }
+        for (count, what) in [
+            (smart.reallocated_sectors, "reallocated sectors"),
+            (smart.pending_sectors, "sectors pending reallocation"),
+            (smart.uncorrectable_sectors, "uncorrectable sectors"),
+            (smart.media_errors, "media errors"),
+        ] {
+            if count > 0 {
+                problems.push(format!("{} {}", count, what));
+            }
+        }
+        if smart.percent_used >= ENDURANCE_WARN_PERCENT {
+            problems.push(format!("{}% of rated endurance used", smart.percent_used));
+        }
+    }
+    if io_errors > 0 {
+        problems.push(format!(
+            "{} kernel I/O errors, last: {}",
+            io_errors, last_error
+        ));
+    }
+    problems
+}
+
+#[derive(Clone)]
+pub struct DiskHealthWatch {
+    pub probe: Arc<dyn DiskHealthProbe>,
+    pub status: Arc<Mutex<Vec<DiskStatus>>>, // Read by GetSystemStatus
+}
+
+impl DiskHealthWatch {
+    /// Checks every `interval` for as long as the agent runs. Kernel errors are
+    /// counted from `since` (Unix seconds), normally the boot.
+    pub async fn run(
+        self,
+        interval: Duration,
+        since: i64,
+        events: broadcast::Sender<DiskHealthEvent>,
+    ) {
+        let mut ticker = tokio::time::interval(interval.max(Duration::from_millis(10)));
+        let mut since = since;
+        let mut errors: BTreeMap<String, (u64, String)> = BTreeMap::new();
+        loop {
+            ticker.tick().await;
+            let smart = match self.probe.smart_reports().await {
+                Ok(reports) => reports,
+                Err(e) => {
+                    warn!("💽 SMART check failed: {}", e);
+                    Vec::new()
+                }
+            };
+            match self.probe.kernel_errors(since).await {
+                Ok(lines) => {
+                    for line in lines {
+                        since = since.max(line.at + 1);
+                        let entry = errors.entry(disk_of(&line.device)).or_default();
+                        entry.0 += 1;
+                        entry.1 = line.message;
+                    }
+                }
+                Err(e) => warn!("💽 Kernel log check failed: {}", e),
+            }
+
+            let mut devices: BTreeMap<String, Option<SmartReport>> =
+                errors.keys().map(|device| (device.clone(), None)).collect();
+            for report in smart {
+                devices.insert(report.device.clone(), Some(report));
+            }
+            let current: Vec<DiskStatus> = devices
+                .into_iter()
+                .map(|(device, smart)| {
+                    let (io_errors, last_error) = errors.get(&device).cloned().unwrap_or_default();
+                    DiskStatus {
+                        problems: assess(smart.as_ref(), io_errors, &last_error),
+                        device,
+                        smart,
+                        io_errors,
+                        last_error,
+                    }
+                })
+                .collect();
+
+            let previous = std::mem::replace(&mut *self.status.lock().unwrap(), current.clone());
+            for disk in current {
+                let before = previous.iter().find(|d| d.device == disk.device);
+                if before.map(|d| &d.problems) == Some(&disk.problems)
+                    || (before.is_none() && disk.problems.is_empty())
+                {
+                    continue;
+                }
+                let failing = !disk.problems.is_empty();
+                let detail = if failing {
+                    disk.problems.join("; ")
+                } else {
+                    "no problems reported".to_string()
+                };
+                if failing {
+                    warn!("💽 {}: {}", disk.device, detail);
+                } else {
+                    info!("💽 {}: {}", disk.device, detail);
+                }
+                let _ = events.send(DiskHealthEvent {
+                    device: disk.device,
+                    failing,
+                    detail,
+                    at: chrono::Utc::now().timestamp(),
+                });
+            }
+        }
+    }
+}
+
+// ==============================================================================
+// smartctl & journalctl
+// ==============================================================================
+
+/// Reads SMART data with smartctl and kernel errors from the journal.
+pub struct SmartctlDiskHealthProbe;
+
+/// Runs a probe command; None when the binary is not installed.
+async fn probe_output(
+    program: &str,
+    args: &[&str],
+) -> Result<Option<std::process::Output>, String> {
+    let run = privilege::command(program)
+        .args(args)
+        .kill_on_drop(true)
+        .output();
+    match tokio::time::timeout(PROBE_TIMEOUT, run).await {
+        Err(_) => Err(format!("{} timed out", program)),
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Ok(Err(e)) => Err(format!("Failed to run {}: {}", program, e)),
+        Ok(Ok(output)) => Ok(Some(output)),
+    }
+}
+
+/// 🛡️ Zero-Trust: Scan output becomes argv, so it must look like a device.
+fn valid_scan_entry(name: &str, kind: &str) -> bool {
+    name.starts_with("/dev/")
+        && !name.contains("..")
+        && name[5..]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '_' | '-'))
+        && !kind.is_empty()
+        && kind
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, ',' | '_' | '-'))
+}
+
+/// One device's `smartctl --json` output.
+pub fn parse_smartctl(device: &str, json: &serde_json::Value) -> SmartReport {
+    let number = |value: &serde_json::Value| value.as_u64().unwrap_or(0);
+    let attribute = |id: u64| {
+        json["ata_smart_attributes"]["table"]
+            .as_array()
+            .and_then(|table| table.iter().find(|a| a["id"].as_u64() == Some(id)))
+            .map(|a| number(&a["raw"]["value"]))
+            .unwrap_or(0)
+    };
+    let nvme = &json["nvme_smart_health_information_log"];
+    SmartReport {
+        device: device.to_string(),
+        model: json["model_name"].as_str().unwrap_or_default().to_string(),
+        passed: json["smart_status"]["passed"].as_bool().unwrap_or(true),
+        reallocated_sectors: attribute(5),
+        pending_sectors: attribute(197),
+        uncorrectable_sectors: attribute(198),
+        media_errors: number(&nvme["media_errors"]),
+        percent_used: number(&nvme["percentage_used"]) as u32,
+        temperature_celsius: number(&json["temperature"]["current"]) as u32,
+    }
+}
+
+#[async_trait]
+impl DiskHealthProbe for SmartctlDiskHealthProbe {
+    async fn smart_reports(&self) -> Result<Vec<SmartReport>, String> {
+        let Some(scan) = probe_output("smartctl", &["--scan", "--json"]).await? else {
+            return Ok(Vec::new());
+        };
+        let scan: serde_json::Value = serde_json::from_slice(&scan.stdout)
+            .map_err(|e| format!("Unreadable smartctl scan: {}", e))?;
+        let mut reports = Vec::new();
+        for entry in scan["devices"].as_array().into_iter().flatten() {
+            let name = entry["name"].as_str().unwrap_or_default();
+            let kind = entry["type"].as_str().unwrap_or_default();
+            if !valid_scan_entry(name, kind) {
+                warn!("💽 Ignoring smartctl device {:?} ({:?})", name, kind);
+                continue;
+            }
+            let Some(output) = probe_output(
+                "smartctl",
+                &[
+                    "--json",
+                    "--info",
+                    "--health",
+                    "--attributes",
+                    "--device",
+                    kind,
+                    name,
+                ],
+            )
+            .await?
+            else {
+                break;
+            };
+            // The exit status is a bitmask; only bits 0-1 mean nothing was read
+            if output.status.code().is_none_or(|code| code & 0b11 != 0) {
+                warn!("💽 smartctl could not read {}", name);
+                continue;
+            }
+            match serde_json::from_slice(&output.stdout) {
+                Ok(json) => reports.push(parse_smartctl(name, &json)),
+                Err(e) => warn!("💽 Unreadable smartctl output for {}: {}", name, e),
+            }
+        }
+        Ok(reports)
+    }
+
+    async fn kernel_errors(&self, since: i64) -> Result<Vec<KernelDiskError>, String> {
+        let since = format!("--since=@{}", since);
+        let Some(output) = probe_output(
+            "journalctl",
+            &["--dmesg", &since, "--output=json", "--no-pager", "--quiet"],
+        )
+        .await?
+        else {
+            return Ok(Vec::new());
+        };
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+            .filter_map(|entry| {
+                let message = entry["MESSAGE"].as_str()?.to_string();
+                let device = classify_kernel_line(&message)?;
+                let at = entry["__REALTIME_TIMESTAMP"]
+                    .as_str()
+                    .and_then(|usec| usec.parse::<i64>().ok())
+                    .map_or(0, |usec| usec / 1_000_000);
+                Some(KernelDiskError {
+                    device,
+                    message,
+                    at,
+                })
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernel_disk_errors_are_recognised_and_mapped_to_their_disk() {
+        let cases = [
+            (
+                "blk_update_request: I/O error, dev sda, sector 2048 op 0x0:(READ)",
+                Some("sda"),
+            ),
+            (
+                "Buffer I/O error on dev sdb1, logical block 0, async page read",
+                Some("sdb1"),
+            ),
+            (
+                "critical medium error, dev nvme0n1, sector 1234",
+                Some("nvme0n1"),
+            ),
+            (
+                "EXT4-fs error (device nvme0n1p2): ext4_find_entry:1455: inode #2",
+                Some("nvme0n1p2"),
+            ),
+            (
+                "BTRFS error (device dm-0): bdev /dev/mapper/root errs: wr 1",
+                Some("dm-0"),
+            ),
+            (
+                "XFS (vdb1): Metadata corruption detected at xfs_buf_ioend",
+                Some("vdb1"),
+            ),
+            ("XFS (vdb1): Mounting V5 Filesystem", None),
+            (
+                "EXT4-fs (sda1): mounted filesystem with ordered data mode",
+                None,
+            ),
+        ];
+        for (line, device) in cases {
+            assert_eq!(classify_kernel_line(line).as_deref(), device, "{}", line);
+        }
+        assert_eq!(disk_of("sdb1"), "/dev/sdb");
+        assert_eq!(disk_of("nvme0n1p2"), "/dev/nvme0");
+        assert_eq!(disk_of("dm-0"), "/dev/dm-0");
+    }
+
+    #[test]
+    fn smartctl_json_is_assessed() {
+        let ata = serde_json::json!({
+            "model_name": "WDC WD40EFRX",
+            "smart_status": {"passed": true},
+            "temperature": {"current": 41},
+            "ata_smart_attributes": {"table": [
+                {"id": 5, "raw": {"value": 8}},
+                {"id": 9, "raw": {"value": 40000}},
+                {"id": 197, "raw": {"value": 2}},
+            ]},
+        });
+        let report = parse_smartctl("/dev/sda", &ata);
+        assert_eq!(
+            (
+                report.reallocated_sectors,
+                report.pending_sectors,
+                report.temperature_celsius
+            ),
+            (8, 2, 41)
+        );
+        assert_eq!(
+            assess(Some(&report), 0, ""),
+            vec!["8 reallocated sectors", "2 sectors pending reallocation"]
+        );
+
+        let nvme = serde_json::json!({
+            "smart_status": {"passed": false},
+            "nvme_smart_health_information_log": {"media_errors": 0, "percentage_used": 97},
+        });
+        let report = parse_smartctl("/dev/nvme0", &nvme);
+        assert_eq!(
+            assess(Some(&report), 1, "I/O error, dev nvme0n1"),
+            vec![
+                "SMART overall health self-assessment failed",
+                "97% of rated endurance used",
+                "1 kernel I/O errors, last: I/O error, dev nvme0n1",
+            ]
+        );
+        assert!(assess(None, 0, "").is_empty());
+        assert!(!valid_scan_entry("/dev/sda; rm -rf /", "sat"));
+        assert!(valid_scan_entry("/dev/bus/0", "megaraid,0"));
+    }
+}
@@ -0,0 +1,380 @@
+// agent/src/sys/dns.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Asks the host's resolvers one question at a time.
+//
+// The issuance pre-check needs what a CA will see: A/AAAA for the challenge
+// and the CAA set, from the same recursive resolvers the host uses. libc's
+// resolver cannot ask for CAA, so this speaks just enough of RFC 1035 over
+// UDP (TCP when the answer is truncated) to the nameservers in resolv.conf.
+
+use async_trait::async_trait;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+use crate::sys::mirrors::parse_nameservers;
+use crate::sys::traits::{DnsAnswer, DnsRecord, DnsResolver, RecordType};
+
+const TYPE_CNAME: u16 = 5;
+const TYPE_OPT: u16 = 41;
+const CLASS_IN: u16 = 1;
+const UDP_PAYLOAD: u16 = 1232; // EDNS0 size that avoids fragmentation
+const RCODE_NXDOMAIN: u16 = 3;
+
+static NEXT_ID: AtomicU16 = AtomicU16::new(0);
+
+/// A recursion-desired query for `name`, with an EDNS0 OPT record.
+pub fn encode_query(id: u16, name: &str, record_type: RecordType) -> Result<Vec<u8>, String> {
+    let mut msg = Vec::with_capacity(64);
+    msg.extend_from_slice(&id.to_be_bytes());
+    msg.extend_from_slice(&0x0100u16.to_be_bytes()); // RD
+    for count in [1u16, 0, 0, 1] {
+        msg.extend_from_slice(&count.to_be_bytes());
+    }
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(format!("'{}' is not a valid DNS name", name));
+        }
+        msg.push(label.len() as u8);
+        msg.extend_from_slice(label.as_bytes());
+    }
+    msg.push(0);
+    msg.extend_from_slice(&record_type.code().to_be_bytes());
+    msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+    // OPT: root owner, UDP size in the class field, no options
+    msg.push(0);
+    msg.extend_from_slice(&TYPE_OPT.to_be_bytes());
+    msg.extend_from_slice(&UDP_PAYLOAD.to_be_bytes());
+    msg.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    Ok(msg)
+}
+
+struct Reader<'a> {
+    msg: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .msg
+            .get(self.pos..self.pos + n)
+            .ok_or("Truncated DNS message")?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    /// A possibly compressed name. Pointers may only go backwards, so a
+    /// crafted message cannot loop.
+    fn name(&mut self) -> Result<String, String> {
+        let mut labels = Vec::new();
+        let mut pos = self.pos;
+        let mut resume = None;
+        loop {
+            let len = *self.msg.get(pos).ok_or("Truncated DNS name")? as usize;
+            match len {
+                0 => {
+                    pos += 1;
+                    break;
+                }
+                l if l & 0xC0 == 0xC0 => {
+                    let low = *self.msg.get(pos + 1).ok_or("Truncated DNS name")? as usize;
+                    let target = ((l & 0x3F) << 8) | low;
+                    if target >= pos {
+                        return Err("Invalid DNS name compression".into());
+                    }
+                    resume.get_or_insert(pos + 2);
+                    pos = target;
+                }
+                l if l <= 63 => {
+                    let label = self
+                        .msg
+                        .get(pos + 1..pos + 1 + l)
+                        .ok_or("Truncated DNS name")?;
+                    labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+                    pos += 1 + l;
+                }
+                _ => return Err("Invalid DNS label".into()),
+            }
+        }
+        self.pos = resume.unwrap_or(pos);
+        Ok(labels.join("."))
+    }
+}
+
+/// Parses the answer to query `id`. Ok(None) when it was truncated and must
+/// be asked again over TCP.
+pub fn parse_response(msg: &[u8], id: u16) -> Result<Option<DnsAnswer>, String> {
+    let mut r = Reader { msg, pos: 0 };
+    if r.u16()? != id {
+        return Err("DNS answer does not match the query".into());
+    }
+    let flags = r.u16()?;
+    if flags & 0x8000 == 0 {
+        return Err("DNS message is not an answer".into());
+    }
+    if flags & 0x0200 != 0 {
+        return Ok(None);
+    }
+    let (qdcount, ancount) = (r.u16()?, r.u16()?);
+    r.take(4)?; // Authority and additional counts; neither is read
+    match flags & 0x000F {
+        0 => {}
+        RCODE_NXDOMAIN => {
+            return Ok(Some(DnsAnswer {
+                nxdomain: true,
+                records: Vec::new(),
+            }));
+        }
+        2 => return Err("SERVFAIL".into()),
+        5 => return Err("REFUSED".into()),
+        rcode => return Err(format!("DNS error (rcode {})", rcode)),
+    }
+    for _ in 0..qdcount {
+        r.name()?;
+        r.take(4)?;
+    }
+
+    let mut records = Vec::new();
+    for _ in 0..ancount {
+        r.name()?;
+        let (rtype, _class) = (r.u16()?, r.u16()?);
+        r.take(4)?; // TTL
+        let rdlength = r.u16()? as usize;
+        let end = r.pos + rdlength;
+        let record = match rtype {
+            1 if rdlength == 4 => {
+                let b = r.take(4)?;
+                Some(DnsRecord::Address(IpAddr::V4(Ipv4Addr::new(
+                    b[0], b[1], b[2], b[3],
+                ))))
+            }
+            28 if rdlength == 16 => {
+                let b: [u8; 16] = r.take(16)?.try_into().map_err(|_| "Bad AAAA record")?;
+                Some(DnsRecord::Address(IpAddr::V6(Ipv6Addr::from(b))))
+            }
+            TYPE_CNAME => Some(DnsRecord::Cname(r.name()?)),
+            257 if rdlength >= 2 => {
+                let flags = r.u8()?;
+                let tag_len = r.u8()? as usize;
+                let tag = r.take(tag_len)?;
+                let value = r.msg.get(r.pos..end).ok_or("Truncated CAA record")?;
+                Some(DnsRecord::Caa {
+                    critical: flags & 0x80 != 0,
+                    tag: String::from_utf8_lossy(tag).to_ascii_lowercase(),
+                    value: String::from_utf8_lossy(value).into_owned(),
+                })
+            }
+            _ => None, // RRSIGs and the like
+        };
+        if end > msg.len() {
+            return Err("Truncated DNS record".into());
+        }
+        r.pos = end;
+        records.extend(record);
+    }
+    Ok(Some(DnsAnswer {
+        nxdomain: false,
+        records,
+    }))
+}
+
+/// The host's recursive resolvers, as resolv.conf lists them at query time.
+pub struct StubDnsResolver {
+    resolv_conf: PathBuf,
+    timeout: Duration, // Per nameserver
+}
+
+impl StubDnsResolver {
+    pub fn new(resolv_conf: PathBuf, timeout: Duration) -> Self {
+        Self {
+            resolv_conf,
+            timeout,
+        }
+    }
+
+    async fn ask(&self, server: SocketAddr, query: &[u8], id: u16) -> Result<DnsAnswer, String> {
+        let bind: SocketAddr = if server.is_ipv4() {
+            "0.0.0.0:0".parse().unwrap()
+        } else {
+            "[::]:0".parse().unwrap()
+        };
+        let socket = UdpSocket::bind(bind)
+            .await
+            .map_err(|e| format!("Failed to open a DNS socket: {}", e))?;
+        socket
+            .connect(server)
+            .await
+            .map_err(|e| format!("{}: {}", server, e))?;
+        socket
+            .send(query)
+            .await
+            .map_err(|e| format!("{}: {}", server, e))?;
+        let mut buf = vec![0u8; UDP_PAYLOAD as usize];
+        let answer = loop {
+            let n = socket
+                .recv(&mut buf)
+                .await
+                .map_err(|e| format!("{}: {}", server, e))?;
+            // A stray datagram for another id is ignored, not trusted
+            if n >= 2 && buf[..2] == id.to_be_bytes() {
+                break parse_response(&buf[..n], id)?;
+            }
+        };
+        if let Some(answer) = answer {
+            return Ok(answer);
+        }
+
+        // Truncated: the same question over TCP, length-prefixed
+        let mut stream = TcpStream::connect(server)
+            .await
+            .map_err(|e| format!("{}: {}", server, e))?;
+        let mut framed = (query.len() as u16).to_be_bytes().to_vec();
+        framed.extend_from_slice(query);
+        stream
+            .write_all(&framed)
+            .await
+            .map_err(|e| format!("{}: {}", server, e))?;
+        let len = stream
+            .read_u16()
+            .await
+            .map_err(|e| format!("{}: {}", server, e))?;
+        let mut msg = vec![0u8; len as usize];
+        stream
+            .read_exact(&mut msg)
+            .await
+            .map_err(|e| format!("{}: {}", server, e))?;
+        parse_response(&msg, id)?.ok_or_else(|| format!("{}: truncated over TCP", server))
+    }
+}
+
+#[async_trait]
+impl DnsResolver for StubDnsResolver {
+    async fn query(&self, name: &str, record_type: RecordType) -> Result<DnsAnswer, String> {
+        let resolv_conf = tokio::fs::read_to_string(&self.resolv_conf)
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", self.resolv_conf.display(), e))?;
+        let nameservers = parse_nameservers(&resolv_conf);
+        if nameservers.is_empty() {
+            return Err(format!("No nameservers in {}", self.resolv_conf.display()));
+        }
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed)
+            ^ (chrono::Utc::now().timestamp_subsec_nanos() as u16);
+        let query = encode_query(id, name, record_type)?;
+        let mut errors = Vec::new();
+        for server in nameservers.into_iter().map(|ip| SocketAddr::new(ip, 53)) {
+            match tokio::time::timeout(self.timeout, self.ask(server, &query, id)).await {
+                Ok(Ok(answer)) => return Ok(answer),
+                Ok(Err(e)) => errors.push(e),
+                Err(_) => errors.push(format!("{}: timed out", server)),
+            }
+        }
+        Err(format!(
+            "{} lookup for {} failed: {}",
+            match record_type {
+                RecordType::A => "A",
+                RecordType::Aaaa => "AAAA",
+                RecordType::Caa => "CAA",
+            },
+            name,
+            errors.join("; ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An answer to `query`: its header and question, then `answers`.
+    fn respond(query: &[u8], rcode: u16, answers: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let question_end = query.len() - 11; // Minus the OPT record
+        let mut msg = query[..2].to_vec();
+        msg.extend_from_slice(&(0x8180u16 | rcode).to_be_bytes());
+        msg.extend_from_slice(&1u16.to_be_bytes());
+        msg.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+        msg.extend_from_slice(&[0, 0, 0, 0]);
+        msg.extend_from_slice(&query[12..question_end]);
+        for (rtype, rdata) in answers {
+            msg.extend_from_slice(&[0xC0, 12]); // The question's name
+            msg.extend_from_slice(&rtype.to_be_bytes());
+            msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+            msg.extend_from_slice(&300u32.to_be_bytes());
+            msg.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            msg.extend_from_slice(rdata);
+        }
+        msg
+    }
+
+    #[test]
+    fn answers_are_parsed_across_cnames_and_caa_sets() {
+        let query = encode_query(0x1234, "www.shop.example.com", RecordType::Caa).unwrap();
+        let mut cname = vec![4];
+        cname.extend_from_slice(b"edge");
+        cname.extend_from_slice(&[0xC0, 16]); // "shop.example.com" inside the question
+        let mut caa = vec![0x80, 5];
+        caa.extend_from_slice(b"issueletsencrypt.org; validationmethods=http-01");
+        let msg = respond(&query, 0, &[(TYPE_CNAME, cname), (257, caa)]);
+
+        let answer = parse_response(&msg, 0x1234).unwrap().unwrap();
+        assert_eq!(
+            answer.records,
+            [
+                DnsRecord::Cname("edge.shop.example.com".into()),
+                DnsRecord::Caa {
+                    critical: true,
+                    tag: "issue".into(),
+                    value: "letsencrypt.org; validationmethods=http-01".into(),
+                },
+            ]
+        );
+
+        let query = encode_query(7, "shop.example.com", RecordType::Aaaa).unwrap();
+        let v6: Ipv6Addr = "2001:db8::5".parse().unwrap();
+        let msg = respond(&query, 0, &[(28, v6.octets().to_vec())]);
+        assert_eq!(
+            parse_response(&msg, 7).unwrap().unwrap().records,
+            [DnsRecord::Address(IpAddr::V6(v6))]
+        );
+    }
+
+    #[test]
+    fn errors_truncation_and_forged_messages_are_told_apart() {
+        let query = encode_query(9, "gone.example.com", RecordType::A).unwrap();
+        assert!(
+            parse_response(&respond(&query, 3, &[]), 9)
+                .unwrap()
+                .unwrap()
+                .nxdomain
+        );
+        assert_eq!(
+            parse_response(&respond(&query, 2, &[]), 9).unwrap_err(),
+            "SERVFAIL"
+        );
+        assert!(parse_response(&respond(&query, 0, &[]), 10).is_err());
+
+        let mut truncated = respond(&query, 0, &[]);
+        truncated[2] |= 0x02;
+        assert_eq!(parse_response(&truncated, 9).unwrap(), None);
+
+        // A compression pointer to itself
+        let mut looped = respond(&query, 0, &[(1, vec![10, 0, 0, 1])]);
+        let at = looped.len() - 16;
+        looped[at..at + 2].copy_from_slice(&[0xC0, at as u8]);
+        assert!(parse_response(&looped, 9).is_err());
+
+        assert!(encode_query(1, "bad..example.com", RecordType::A).is_err());
+    }
+}
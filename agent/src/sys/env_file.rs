@@ -0,0 +1,138 @@
+// agent/src/sys/env_file.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Renders dotenv files for apps that read
+// their configuration from `.env` (PHP, static site generators, Node dotenv).
+// 🛡️ Privacy: The rendered file is built in a zeroizing buffer and written with
+// synchronous I/O, so secret plaintext never crosses an `.await`.
+
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::fs as std_fs;
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+use crate::sys::secrets::ProviderCredential;
+use crate::sys::systemd::is_valid_env_key;
+use crate::sys::traits::EnvFileManager;
+
+pub struct DotenvFileManager;
+
+/// Renders one `KEY="value"` line. Double quotes are understood by every common
+/// dotenv parser; `$` is escaped so values are never expanded as variable references.
+fn render_line(out: &mut String, key: &str, value: &str) {
+    out.push_str(key);
+    out.push_str("=\"");
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '$' => out.push_str("\\$"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            _ => out.push(c),
+        }
+    }
+    out.push_str("\"\n");
+}
+
+fn lookup_user(username: &str) -> Result<nix::unistd::User, String> {
+    nix::unistd::User::from_name(username)
+        .map_err(|e| format!("User lookup failed: {}", e))?
+        .ok_or_else(|| format!("Unknown app user '{}'", username))
+}
+
+#[async_trait]
+impl EnvFileManager for DotenvFileManager {
+    async fn write_env_file(
+        &self,
+        shared_dir: &Path,
+        owner: &str,
+        vars: &BTreeMap<String, String>,
+        secrets: BTreeMap<String, ProviderCredential>,
+    ) -> Result<(), String> {
+        if let Some(bad) = vars
+            .keys()
+            .chain(secrets.keys())
+            .find(|k| !is_valid_env_key(k))
+        {
+            return Err(format!("SECURITY VIOLATION: Invalid env key '{}'", bad));
+        }
+        let user = lookup_user(owner)?;
+        let (uid, gid) = (user.uid.as_raw(), user.gid.as_raw());
+
+        if !shared_dir.exists() {
+            std_fs::create_dir_all(shared_dir)
+                .map_err(|e| format!("Failed to create shared directory: {}", e))?;
+            std::os::unix::fs::chown(shared_dir, Some(uid), Some(gid))
+                .map_err(|e| format!("Failed to chown shared directory: {}", e))?;
+            std_fs::set_permissions(shared_dir, std_fs::Permissions::from_mode(0o750))
+                .map_err(|e| format!("Failed to secure shared directory: {}", e))?;
+        }
+
+        // 🛡️ Privacy: The only buffer holding the rendered secrets is wiped on drop.
+        let mut content = Zeroizing::new(String::from("# Managed by kari-agent. Do not edit.\n"));
+        for (key, value) in vars {
+            render_line(&mut content, key, value);
+        }
+        for (key, credential) in &secrets {
+            credential.use_secret(|value| render_line(&mut content, key, value));
+        }
+        for (_, credential) in secrets {
+            credential.destroy();
+        }
+
+        let env_path = shared_dir.join(".env");
+        let tmp_path = shared_dir.join(".env.tmp");
+        let backup_path = shared_dir.join(".env.bak");
+
+        // 1. Keep the previous contents recoverable, with the same narrow permissions.
+        match std_fs::copy(&env_path, &backup_path) {
+            Ok(_) => {
+                std::os::unix::fs::chown(&backup_path, Some(uid), Some(gid))
+                    .map_err(|e| format!("Failed to chown env backup: {}", e))?;
+                std_fs::set_permissions(&backup_path, std_fs::Permissions::from_mode(0o640))
+                    .map_err(|e| format!("Failed to secure env backup: {}", e))?;
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to back up existing .env: {}", e)),
+        }
+
+        // 2. Write + chown the replacement before it becomes visible, then swap atomically.
+        let write_result = (|| {
+            let mut file = std_fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o640) // rw-r----- (app user reads, nobody else)
+                .open(&tmp_path)
+                .map_err(|e| format!("Failed to open env file securely: {}", e))?;
+            std::os::unix::fs::fchown(&file, Some(uid), Some(gid))
+                .map_err(|e| format!("Failed to chown env file: {}", e))?;
+            file.write_all(content.as_bytes())
+                .map_err(|e| format!("Failed to write env file: {}", e))?;
+            file.sync_all()
+                .map_err(|e| format!("Failed to sync env file: {}", e))?;
+            std_fs::rename(&tmp_path, &env_path)
+                .map_err(|e| format!("Failed to commit env file: {}", e))
+        })();
+
+        if write_result.is_err() {
+            let _ = std_fs::remove_file(&tmp_path);
+        }
+        write_result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_quoted_and_never_expanded() {
+        let mut out = String::new();
+        render_line(&mut out, "DB_PASS", "p\"a$HOME\\x\ny");
+        assert_eq!(out, "DB_PASS=\"p\\\"a\\$HOME\\\\x\\ny\"\n");
+    }
+}
@@ -0,0 +1,479 @@
+// agent/src/sys/executor.rs
+//
+// 🧪 Seam between the managers and the processes they run. A manager builds a
+// `CommandSpec` (argv, environment) and decides what the exit code means; the
+// `Executor` only runs it. Swapping the executor lets a manager's argument
+// construction and error mapping be tested without root or real binaries, and
+// lets the same managers drive another host (see satellite.rs).
+//
+// The config files managers write go through the executor too. Its file
+// operations default to plain commands (`cat`, `tee`, `rm`, ...), which is all
+// a remote backend needs; `SystemExecutor` does them with the local filesystem.
+
+use async_trait::async_trait;
+use std::fmt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use zeroize::Zeroizing;
+
+use crate::sys::privilege;
+
+/// How long `kill_group` waits for a killed group to exit.
+const GROUP_EXIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The process group a command run `in_group` leads, filled in by the
+/// executor once it starts. Shared by clones of the spec, so whoever built
+/// the spec can still reach the group after the run future is gone.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessGroup(Arc<Mutex<Option<i32>>>);
+
+impl ProcessGroup {
+    pub fn id(&self) -> Option<i32> {
+        *self.0.lock().unwrap()
+    }
+
+    pub(crate) fn started(&self, pgid: i32) {
+        *self.0.lock().unwrap() = Some(pgid);
+    }
+}
+
+/// One command to run: a program, its arguments and extra environment.
+#[derive(Clone, Default)]
+pub struct CommandSpec {
+    pub program: String,
+    pub args: Vec<String>,
+    /// 🛡️ Values may be secrets (git credentials): wiped when the spec drops.
+    pub envs: Vec<(String, Zeroizing<String>)>,
+    /// Through `privilege::command`, i.e. via `sudo -n` when the agent is rootless.
+    pub privileged: bool,
+    /// Kill the process if the caller stops waiting for it.
+    pub kill_on_drop: bool,
+    /// Fed to the process, then closed. Without it stdin is empty.
+    pub stdin: Option<Zeroizing<Vec<u8>>>,
+    /// Leads a process group of its own, so `kill_on_drop` takes down the
+    /// children it spawned too, not only the process itself.
+    pub group: Option<ProcessGroup>,
+}
+
+impl CommandSpec {
+    /// A command run as the agent's own user.
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: program.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// A command that may need root.
+    pub fn privileged(program: &str) -> Self {
+        Self {
+            privileged: true,
+            ..Self::new(program)
+        }
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn env(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.envs
+            .push((key.to_string(), Zeroizing::new(value.into())));
+        self
+    }
+
+    pub fn kill_on_drop(mut self) -> Self {
+        self.kill_on_drop = true;
+        self
+    }
+
+    pub fn stdin(mut self, input: impl Into<Vec<u8>>) -> Self {
+        self.stdin = Some(Zeroizing::new(input.into()));
+        self
+    }
+
+    pub fn in_group(mut self, group: &ProcessGroup) -> Self {
+        self.group = Some(group.clone());
+        self
+    }
+
+    /// The value set for `key`, if any.
+    pub fn env_value(&self, key: &str) -> Option<&str> {
+        self.envs
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Program and arguments as one argv.
+    pub fn argv(&self) -> Vec<String> {
+        std::iter::once(self.program.clone())
+            .chain(self.args.iter().cloned())
+            .collect()
+    }
+}
+
+/// 🛡️ Environment values are never printed: some are credentials.
+impl fmt::Debug for CommandSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let env_keys: Vec<&str> = self.envs.iter().map(|(k, _)| k.as_str()).collect();
+        f.debug_struct("CommandSpec")
+            .field("program", &self.program)
+            .field("args", &self.args)
+            .field("env_keys", &env_keys)
+            .field("privileged", &self.privileged)
+            .field("kill_on_drop", &self.kill_on_drop)
+            .field("stdin_bytes", &self.stdin.as_ref().map(|input| input.len()))
+            .field("group", &self.group.as_ref().map(ProcessGroup::id))
+            .finish()
+    }
+}
+
+/// What a finished command left behind. `code` is None when it was killed by
+/// a signal.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandOutput {
+    pub code: Option<i32>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+/// A path as one argv word. Paths are built by the managers, never taken
+/// from requests, but a remote shell would mangle anything but UTF-8.
+fn path_arg(path: &Path) -> Result<String, String> {
+    path.to_str()
+        .map(str::to_string)
+        .ok_or_else(|| format!("Invalid UTF-8 in path {:?}", path))
+}
+
+/// Runs a file command; a non-zero exit becomes its stderr.
+async fn run_checked<E: Executor + ?Sized>(executor: &E, spec: CommandSpec) -> Result<(), String> {
+    let output = executor.run(&spec).await?;
+    if !output.success() {
+        return Err(format!(
+            "{} failed: {}",
+            spec.program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Runs a command and returns its stdout; a non-zero exit becomes its stderr.
+pub(crate) async fn run_stdout(
+    executor: &dyn Executor,
+    spec: CommandSpec,
+) -> Result<String, String> {
+    let output = executor.run(&spec).await?;
+    if !output.success() {
+        return Err(format!(
+            "{} failed: {}",
+            spec.argv().join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[async_trait]
+pub trait Executor: Send + Sync {
+    /// Runs the command to completion. `Err` means it never ran (spawn
+    /// failure); a non-zero exit is an `Ok` the caller interprets.
+    async fn run(&self, spec: &CommandSpec) -> Result<CommandOutput, String>;
+
+    /// Kills whatever is left of `group` and returns once none of it is
+    /// running. A group that never started is not an error; remote commands
+    /// have no local group and end with their connection.
+    async fn kill_group(&self, _group: &ProcessGroup) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Whether `path` exists, following symlinks (a dangling link does not).
+    async fn exists(&self, path: &Path) -> Result<bool, String> {
+        let output = self
+            .run(&CommandSpec::privileged("test").args(["-e", &path_arg(path)?]))
+            .await?;
+        Ok(output.success())
+    }
+
+    /// A text file's contents, or None when there is no such file.
+    async fn read_file(&self, path: &Path) -> Result<Option<String>, String> {
+        if !self.exists(path).await? {
+            return Ok(None);
+        }
+        let output = self
+            .run(&CommandSpec::privileged("cat").args(["--", &path_arg(path)?]))
+            .await?;
+        if !output.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()))
+    }
+
+    /// Replaces `path` with `contents` and gives it `mode`.
+    async fn write_file(&self, path: &Path, contents: &str, mode: u32) -> Result<(), String> {
+        let path = path_arg(path)?;
+        let tee = CommandSpec::privileged("tee")
+            .args(["--", &path])
+            .stdin(contents.as_bytes());
+        run_checked(self, tee).await?;
+        let chmod =
+            CommandSpec::privileged("chmod").args([format!("{:o}", mode), "--".into(), path]);
+        run_checked(self, chmod).await
+    }
+
+    /// Removes `path`; one that is already gone is not an error.
+    async fn remove_file(&self, path: &Path) -> Result<(), String> {
+        run_checked(
+            self,
+            CommandSpec::privileged("rm").args(["-f", "--", &path_arg(path)?]),
+        )
+        .await
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<(), String> {
+        run_checked(
+            self,
+            CommandSpec::privileged("mkdir").args(["-p", "--", &path_arg(path)?]),
+        )
+        .await
+    }
+
+    /// Creates `link` pointing at `target`.
+    async fn symlink(&self, target: &Path, link: &Path) -> Result<(), String> {
+        let ln =
+            CommandSpec::privileged("ln").args(["-s", "--", &path_arg(target)?, &path_arg(link)?]);
+        run_checked(self, ln).await
+    }
+}
+
+/// SIGKILLs a process group when dropped; forgotten once the leader exits.
+struct GroupKill(i32);
+
+impl Drop for GroupKill {
+    fn drop(&mut self) {
+        let _ = kill_group_now(self.0);
+    }
+}
+
+/// SIGKILL to every process in the group; an empty group is not an error.
+fn kill_group_now(pgid: i32) -> Result<(), nix::Error> {
+    use nix::sys::signal::{Signal, killpg};
+    match killpg(nix::unistd::Pid::from_raw(pgid), Signal::SIGKILL) {
+        Err(nix::Error::ESRCH) => Ok(()),
+        result => result,
+    }
+}
+
+/// Whether any process in the group is still alive. Zombies are not: they
+/// run no code and wait only for their parent (or init) to reap them.
+fn group_is_running(pgid: i32) -> bool {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        let Ok(stat) = std::fs::read_to_string(entry.path().join("stat")) else {
+            return false;
+        };
+        // "pid (comm) state ppid pgrp ...": comm may hold spaces and parens
+        let mut fields = stat
+            .rsplit_once(')')
+            .map_or("", |(_, rest)| rest)
+            .split_whitespace();
+        let state = fields.next();
+        let pgrp = fields.nth(1);
+        state.is_some_and(|s| s != "Z" && s != "X") && pgrp == Some(pgid.to_string().as_str())
+    })
+}
+
+/// Runs commands on this host.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemExecutor;
+
+#[async_trait]
+impl Executor for SystemExecutor {
+    async fn run(&self, spec: &CommandSpec) -> Result<CommandOutput, String> {
+        let mut command = if spec.privileged {
+            privilege::command(&spec.program)
+        } else {
+            Command::new(&spec.program)
+        };
+        command.args(&spec.args);
+        for (key, value) in &spec.envs {
+            command.env(key, value.as_str());
+        }
+        if spec.group.is_some() {
+            command.process_group(0);
+        }
+        let mut child = command
+            .kill_on_drop(spec.kill_on_drop)
+            .stdin(match spec.stdin {
+                Some(_) => Stdio::piped(),
+                None => Stdio::null(),
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| e.to_string())?;
+        // 🛡️ Dropped before the command finishes: its children go with it
+        let mut group_guard = None;
+        if let (Some(group), Some(pid)) = (&spec.group, child.id()) {
+            group.started(pid as i32);
+            group_guard = spec.kill_on_drop.then_some(GroupKill(pid as i32));
+        }
+        if let (Some(input), Some(mut stdin)) = (&spec.stdin, child.stdin.take()) {
+            // A process that exits without reading still reports its status
+            let _ = stdin.write_all(input).await;
+        }
+        let output = child.wait_with_output().await.map_err(|e| e.to_string())?;
+        std::mem::forget(group_guard);
+        Ok(CommandOutput {
+            code: output.status.code(),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        })
+    }
+
+    async fn kill_group(&self, group: &ProcessGroup) -> Result<(), String> {
+        let Some(pgid) = group.id() else {
+            return Ok(());
+        };
+        kill_group_now(pgid)
+            .map_err(|e| format!("Failed to kill process group {}: {}", pgid, e))?;
+        let deadline = tokio::time::Instant::now() + GROUP_EXIT_TIMEOUT;
+        while group_is_running(pgid) {
+            if tokio::time::Instant::now() >= deadline {
+                return Err(format!(
+                    "Process group {} still running {}s after SIGKILL",
+                    pgid,
+                    GROUP_EXIT_TIMEOUT.as_secs()
+                ));
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        Ok(())
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool, String> {
+        Ok(path.exists())
+    }
+
+    async fn read_file(&self, path: &Path) -> Result<Option<String>, String> {
+        match tokio::fs::read_to_string(path).await {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    async fn write_file(&self, path: &Path, contents: &str, mode: u32) -> Result<(), String> {
+        tokio::fs::write(path, contents)
+            .await
+            .map_err(|e| e.to_string())?;
+        tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn remove_file(&self, path: &Path) -> Result<(), String> {
+        match tokio::fs::remove_file(path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> Result<(), String> {
+        tokio::fs::create_dir_all(path)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn symlink(&self, target: &Path, link: &Path) -> Result<(), String> {
+        tokio::fs::symlink(target, link)
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_output_names_env_keys_but_never_values() {
+        let spec = CommandSpec::new("git")
+            .arg("clone")
+            .env("KARI_GIT_PASSWORD", "glpat-secret");
+        let printed = format!("{:?}", spec);
+        assert!(printed.contains("KARI_GIT_PASSWORD"));
+        assert!(!printed.contains("glpat-secret"));
+        assert_eq!(spec.env_value("KARI_GIT_PASSWORD"), Some("glpat-secret"));
+        assert_eq!(spec.argv(), ["git", "clone"]);
+    }
+
+    #[tokio::test]
+    async fn system_executor_reports_exit_codes_and_spawn_failures() {
+        let output = SystemExecutor
+            .run(&CommandSpec::new("sh").args(["-c", "echo out; echo err >&2; exit 3"]))
+            .await
+            .unwrap();
+        assert_eq!(output.code, Some(3));
+        assert!(!output.success());
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+
+        assert!(
+            SystemExecutor
+                .run(&CommandSpec::new("/nonexistent/kari-binary"))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn abandoned_commands_take_their_children_with_them() {
+        let group = ProcessGroup::default();
+        let spec = CommandSpec::new("sh")
+            .args(["-c", "sleep 30 & sleep 30"])
+            .in_group(&group)
+            .kill_on_drop();
+        let run = SystemExecutor.run(&spec);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(200), run)
+                .await
+                .is_err()
+        );
+        let pgid = group.id().unwrap();
+        SystemExecutor.kill_group(&group).await.unwrap();
+        assert!(!group_is_running(pgid));
+
+        // A group that never started, or already ended, is nothing to kill
+        SystemExecutor
+            .kill_group(&ProcessGroup::default())
+            .await
+            .unwrap();
+        SystemExecutor.kill_group(&group).await.unwrap();
+    }
+}
@@ -0,0 +1,186 @@
+// agent/src/sys/facts.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Static facts about the host for the
+// control plane's inventory: what it runs, on what hardware, with which disks,
+// and which parts of a web stack are installed. Everything is read locally
+// (os-release, sysinfo, systemd-detect-virt, well-known binary paths); nothing
+// is installed or started to find out.
+
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+const MB: u64 = 1024 * 1024;
+
+/// Where distribution packages put daemons and tools. The agent's own PATH
+/// is whatever systemd gave it, so it is not consulted.
+pub const BIN_DIRS: &[&str] = &[
+    "/usr/local/sbin",
+    "/usr/local/bin",
+    "/usr/sbin",
+    "/usr/bin",
+    "/sbin",
+    "/bin",
+];
+
+/// Stack components and the binaries that give each away (any one suffices).
+const STACK_COMPONENTS: &[(&str, &[&str])] = &[
+    ("nginx", &["nginx"]),
+    ("apache", &["apache2", "httpd"]),
+    ("podman", &["podman"]),
+    ("docker", &["dockerd"]),
+    ("postgresql", &["pg_ctlcluster", "postgres"]),
+    ("mysql", &["mariadbd", "mysqld"]),
+    ("redis", &["redis-server"]),
+    ("memcached", &["memcached"]),
+    ("node", &["node"]),
+    ("python", &["python3"]),
+    ("php", &["php", "php-fpm"]),
+    ("ruby", &["ruby"]),
+    ("go", &["go"]),
+    ("java", &["java"]),
+    ("postfix", &["postfix"]),
+    ("chrony", &["chronyd"]),
+    ("nftables", &["nft"]),
+    ("git", &["git"]),
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OsRelease {
+    pub id: String,      // e.g. "ubuntu", "rhel"
+    pub name: String,    // PRETTY_NAME, e.g. "Ubuntu 24.04.1 LTS"
+    pub version: String, // VERSION_ID, e.g. "24.04"
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskFacts {
+    pub device: String,
+    pub mount_point: String,
+    pub filesystem: String,
+    pub total_mb: u64,
+    pub available_mb: u64,
+    pub removable: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackComponent {
+    pub name: String,
+    pub path: PathBuf, // The first binary found
+}
+
+/// Parses os-release(5): `KEY=value` lines, values optionally quoted.
+pub fn parse_os_release(content: &str) -> OsRelease {
+    let mut release = OsRelease::default();
+    for line in content.lines() {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        let value = value.trim_matches(|c| c == '"' || c == '\'').to_string();
+        match key {
+            "ID" => release.id = value,
+            "PRETTY_NAME" => release.name = value,
+            "VERSION_ID" => release.version = value,
+            _ => {}
+        }
+    }
+    release
+}
+
+/// /etc/os-release, falling back to /usr/lib/os-release as the spec asks.
+pub fn read_os_release() -> OsRelease {
+    ["/etc/os-release", "/usr/lib/os-release"]
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .map(|content| parse_os_release(&content))
+        .unwrap_or_default()
+}
+
+/// Components with at least one binary in `dirs`, in STACK_COMPONENTS order.
+pub fn detect_components(dirs: &[&Path]) -> Vec<StackComponent> {
+    STACK_COMPONENTS
+        .iter()
+        .filter_map(|(name, binaries)| {
+            let path = binaries
+                .iter()
+                .flat_map(|binary| dirs.iter().map(move |dir| dir.join(binary)))
+                .find(|path| path.is_file())?;
+            Some(StackComponent {
+                name: name.to_string(),
+                path,
+            })
+        })
+        .collect()
+}
+
+/// Mounted block-device filesystems. Pseudo filesystems never make the list.
+pub fn read_disks() -> Vec<DiskFacts> {
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    let mut facts: Vec<DiskFacts> = disks
+        .list()
+        .iter()
+        .map(|d| DiskFacts {
+            device: d.name().to_string_lossy().into_owned(),
+            mount_point: d.mount_point().display().to_string(),
+            filesystem: d.file_system().to_string_lossy().into_owned(),
+            total_mb: d.total_space() / MB,
+            available_mb: d.available_space() / MB,
+            removable: d.is_removable(),
+        })
+        .collect();
+    facts.sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
+    facts
+}
+
+/// systemd-detect-virt's verdict: "none" on bare metal, otherwise the
+/// hypervisor or container technology ("kvm", "lxc", ...). "unknown" when it
+/// cannot be asked.
+pub async fn detect_virtualization() -> String {
+    // Exits 1 when it finds nothing, still printing "none"
+    match Command::new("systemd-detect-virt").output().await {
+        Ok(output) => {
+            let verdict = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if verdict.is_empty() {
+                "unknown".to_string()
+            } else {
+                verdict
+            }
+        }
+        Err(_) => "unknown".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn os_release_values_lose_their_quotes() {
+        let release = parse_os_release(
+            "PRETTY_NAME=\"Ubuntu 24.04.1 LTS\"\nNAME=\"Ubuntu\"\nVERSION_ID=\"24.04\"\n\
+             ID=ubuntu\nID_LIKE=debian\n# comment\n",
+        );
+        assert_eq!(
+            release,
+            OsRelease {
+                id: "ubuntu".into(),
+                name: "Ubuntu 24.04.1 LTS".into(),
+                version: "24.04".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn components_are_found_by_any_of_their_binaries() {
+        let root = tempfile::tempdir().unwrap();
+        let (sbin, bin) = (root.path().join("sbin"), root.path().join("bin"));
+        std::fs::create_dir_all(&sbin).unwrap();
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::write(sbin.join("httpd"), "").unwrap();
+        std::fs::write(bin.join("node"), "").unwrap();
+        std::fs::create_dir(bin.join("nginx")).unwrap(); // A directory is not a binary
+
+        let found = detect_components(&[&sbin, &bin]);
+        let names: Vec<&str> = found.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["apache", "node"]);
+        assert_eq!(found[0].path, sbin.join("httpd"));
+    }
+}
@@ -4,18 +4,312 @@
 // 🛡️ Zero-Trust: All inputs validated before kernel interaction.
 
 use async_trait::async_trait;
-use tokio::process::Command;
-use tracing::info;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::{info, warn};
 
-use crate::sys::traits::{FirewallAction, FirewallManager, FirewallPolicy, Protocol};
+use crate::sys::executor::{CommandSpec, Executor, SystemExecutor};
+use crate::sys::firewall_log::{self, LOG_PREFIX};
+use crate::sys::state::{FirewallRuleRecord, NS_FIREWALL_RULES, firewall_rule_key, list_records};
+use crate::sys::traits::{
+    FirewallAction, FirewallManager, FirewallPolicy, JobScheduler, Protocol, StateStore,
+};
+
+/// Every rule Kari installs carries this comment prefix, so its rules can be
+/// told apart from the host's own when reading `iptables -S`.
+pub const RULE_COMMENT_PREFIX: &str = "kari:";
+
+/// 🐞 Timer jobs revoking debug port exposures are named with this prefix;
+/// ScheduleJob refuses it.
+pub const DEBUG_PORT_JOB_PREFIX: &str = "debug-port-";
+
+/// Agent flag for the oneshot those timers run.
+pub const REVOKE_EXPIRED_FLAG: &str = "--revoke-expired-firewall-rules";
+
+/// xt_comment's limit is 256 bytes including the terminating NUL.
+const MAX_COMMENT_BYTES: usize = 255;
+
+/// `kari: <description> (by <created_by>)`, truncated on a char boundary.
+/// Inputs are validated upstream; quotes and control characters are dropped
+/// here as defense-in-depth, since the comment is shown verbatim by every tool.
+pub fn rule_comment(policy: &FirewallPolicy) -> String {
+    let mut comment = String::from(RULE_COMMENT_PREFIX);
+    if let Some(description) = policy.description.as_deref().filter(|d| !d.is_empty()) {
+        comment.push(' ');
+        comment.push_str(description);
+    }
+    if let Some(created_by) = policy.created_by.as_deref().filter(|c| !c.is_empty()) {
+        comment.push_str(&format!(" (by {})", created_by));
+    }
+
+    let mut clean: String = comment
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"' && *c != '\\')
+        .collect();
+    while clean.len() > MAX_COMMENT_BYTES {
+        clean.pop();
+    }
+    clean
+}
+
+pub fn protocol_name(protocol: Protocol) -> &'static str {
+    match protocol {
+        Protocol::Tcp => "tcp",
+        Protocol::Udp => "udp",
+        Protocol::Both => "both",
+    }
+}
+
+/// The rule's key in the firewall_rules state namespace.
+pub fn policy_rule_key(policy: &FirewallPolicy) -> String {
+    firewall_rule_key(
+        policy.port,
+        protocol_name(policy.protocol),
+        policy.source_ip.as_deref(),
+    )
+}
+
+/// Stable short id for a rule, derived from its state key. LOG prefixes are
+/// capped at 29 bytes, too short for the key itself once IPv6 is involved.
+pub fn rule_log_id(rule_key: &str) -> String {
+    let digest = Sha256::digest(rule_key.as_bytes());
+    digest[..4].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The timer job that revokes the debug port exposure stored under `rule_key`.
+pub fn debug_port_job(rule_key: &str) -> String {
+    format!("{}{}", DEBUG_PORT_JOB_PREFIX, rule_log_id(rule_key))
+}
+
+/// The stored rule as a policy again, for re-applying it after a reboot.
+pub fn record_policy(record: &FirewallRuleRecord) -> Result<FirewallPolicy, String> {
+    let action = match record.action.as_str() {
+        "allow" => FirewallAction::Allow,
+        "deny" => FirewallAction::Deny,
+        "reject" => FirewallAction::Reject,
+        other => return Err(format!("unknown action '{}'", other)),
+    };
+    let protocol = match record.protocol.as_str() {
+        "tcp" => Protocol::Tcp,
+        "udp" => Protocol::Udp,
+        "both" => Protocol::Both,
+        other => return Err(format!("unknown protocol '{}'", other)),
+    };
+    Ok(FirewallPolicy {
+        action,
+        port: record.port,
+        protocol,
+        source_ip: record.source_ip.clone(),
+        description: record.description.clone(),
+        created_by: record.created_by.clone(),
+        log_denied: record.log_denied,
+    })
+}
+
+/// Applies `policy` in place of `previous`, the rule stored under the same
+/// key. The kernel rules match on action and comment as well, so a changed
+/// action or description would otherwise leave the old rule in the chain
+/// next to the new one. The old rules go first, by their recorded spec; if
+/// the new ones then fail, the old ones are put back.
+pub async fn replace_policy(
+    firewall: &dyn FirewallManager,
+    previous: Option<&FirewallRuleRecord>,
+    policy: &FirewallPolicy,
+) -> Result<(), String> {
+    let previous = match previous {
+        Some(record) => Some(record_policy(record)?),
+        None => None,
+    };
+    let Some(previous) = previous.filter(|old| {
+        protocols(old)
+            .iter()
+            .any(|proto| policy_rules(old, proto) != policy_rules(policy, proto))
+    }) else {
+        return firewall.apply_policy(policy).await;
+    };
+
+    firewall.remove_policy(&previous).await?;
+    if let Err(e) = firewall.apply_policy(policy).await {
+        if let Err(restore) = firewall.apply_policy(&previous).await {
+            warn!(
+                "🛡️ Firewall rule {} not put back: {}",
+                policy_rule_key(&previous),
+                restore
+            );
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// 🔁 Boot survival: iptables rules live only in the kernel, so every rule in
+/// the state store that is not in the INPUT chain is applied again. A rule
+/// that fails is logged and skipped; the others still go in. Returns how many
+/// were re-applied. Lapsed debug port exposures are never put back.
+pub async fn restore(
+    state: &dyn StateStore,
+    firewall: &dyn FirewallManager,
+) -> Result<usize, String> {
+    let records: Vec<FirewallRuleRecord> = list_records(state, NS_FIREWALL_RULES).await?;
+    let now = chrono::Utc::now().timestamp();
+    let mut restored = 0;
+    for record in records.iter().filter(|record| !record.expired(now)) {
+        let key = firewall_rule_key(record.port, &record.protocol, record.source_ip.as_deref());
+        let result = match record_policy(record) {
+            Ok(policy) => match firewall.policy_applied(&policy).await {
+                Ok(true) => continue,
+                Ok(false) => firewall.apply_policy(&policy).await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => restored += 1,
+            Err(e) => warn!("🛡️ Firewall rule {} not restored: {}", key, e),
+        }
+    }
+    Ok(restored)
+}
+
+/// 🐞 Revokes every debug port exposure lapsed by `now`: its kernel rules,
+/// its record, then its timer. Run by that timer (`REVOKE_EXPIRED_FLAG`) and
+/// by the agent itself, so a lapse while either was down is still caught.
+/// A timer that cannot be removed is only logged: it has fired for good.
+/// Returns how many exposures were revoked.
+pub async fn revoke_expired(
+    state: &dyn StateStore,
+    firewall: &dyn FirewallManager,
+    scheduler: &dyn JobScheduler,
+    now: i64,
+) -> Result<usize, String> {
+    let records: Vec<FirewallRuleRecord> = list_records(state, NS_FIREWALL_RULES).await?;
+    let mut revoked = 0;
+    for record in records.iter().filter(|record| record.expired(now)) {
+        let key = firewall_rule_key(record.port, &record.protocol, record.source_ip.as_deref());
+        firewall.remove_policy(&record_policy(record)?).await?;
+        state.delete(NS_FIREWALL_RULES, &key).await?;
+        if let Err(e) = scheduler.unschedule_job(&debug_port_job(&key)).await {
+            warn!("🐞 Timer for debug port {} not removed: {}", key, e);
+        }
+        info!("🐞 Debug port exposure {} lapsed and was revoked", key);
+        revoked += 1;
+    }
+    Ok(revoked)
+}
+
+/// `-p proto --dport port [-s source] -m comment --comment ...`: what both the
+/// LOG and the verdict rule match on.
+fn match_args(policy: &FirewallPolicy, proto: &str) -> Vec<String> {
+    let mut args = vec![
+        "-p".to_string(),
+        proto.to_string(),
+        "--dport".to_string(),
+        policy.port.to_string(),
+    ];
+
+    // 🛡️ Zero-Trust: Source IP filtering (optional)
+    if let Some(ref source_ip) = policy.source_ip {
+        args.push("-s".to_string());
+        args.push(source_ip.to_string());
+    }
+
+    // 📝 Ownership and intent travel with the rule itself
+    args.push("-m".to_string());
+    args.push("comment".to_string());
+    args.push("--comment".to_string());
+    args.push(rule_comment(policy));
+    args
+}
+
+/// The INPUT rules one policy installs for `proto`, in order: the rate-limited
+/// LOG rule (deny/reject with log_denied only), then the verdict.
+fn policy_rules(policy: &FirewallPolicy, proto: &str) -> Vec<Vec<String>> {
+    let mut rules = Vec::with_capacity(2);
+    if policy.log_denied && policy.action != FirewallAction::Allow {
+        // Rate-limited so a flood cannot flood the journal as well
+        let mut log_args = match_args(policy, proto);
+        log_args.extend(
+            [
+                "-m",
+                "limit",
+                "--limit",
+                "10/second",
+                "--limit-burst",
+                "50",
+                "-j",
+                "LOG",
+                "--log-prefix",
+            ]
+            .map(String::from),
+        );
+        log_args.push(format!(
+            "{}{} ",
+            LOG_PREFIX,
+            rule_log_id(&policy_rule_key(policy))
+        ));
+        rules.push(log_args);
+    }
+
+    let mut args = match_args(policy, proto);
+    args.push("-j".to_string());
+    args.push(verdict(policy.action).to_string());
+    rules.push(args);
+    rules
+}
+
+fn verdict(action: FirewallAction) -> &'static str {
+    match action {
+        FirewallAction::Allow => "ACCEPT",
+        FirewallAction::Deny => "DROP",
+        FirewallAction::Reject => "REJECT",
+    }
+}
+
+fn protocols(policy: &FirewallPolicy) -> &'static [&'static str] {
+    match policy.protocol {
+        Protocol::Tcp => &["tcp"],
+        Protocol::Udp => &["udp"],
+        Protocol::Both => &["tcp", "udp"],
+    }
+}
+
+/// `iptables -C`: whether the rule is already in the INPUT chain.
+async fn rule_present(executor: &dyn Executor, rule: &[String]) -> Result<bool, String> {
+    let check = CommandSpec::privileged("iptables")
+        .args(["-C", "INPUT"])
+        .args(rule.iter().cloned());
+    let output = executor
+        .run(&check)
+        .await
+        .map_err(|e| format!("[SLA ERROR] iptables spawn failed: {}", e))?;
+    match output.code {
+        Some(0) => Ok(true),
+        Some(1) => Ok(false), // No such rule
+        _ => Err(format!(
+            "[SLA ERROR] iptables check failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )),
+    }
+}
 
 /// LinuxFirewallManager implements firewall policy via `nftables` (2026 standard).
 /// Falls back to `iptables` if nftables is unavailable.
-pub struct LinuxFirewallManager;
+pub struct LinuxFirewallManager {
+    systemd_dir: PathBuf, // Where the log forwarder unit is installed
+    executor: Arc<dyn Executor>,
+}
 
 impl LinuxFirewallManager {
-    pub fn new() -> Self {
-        Self
+    pub fn new(systemd_dir: PathBuf) -> Self {
+        Self::with_executor(systemd_dir, Arc::new(SystemExecutor))
+    }
+
+    pub fn with_executor(systemd_dir: PathBuf, executor: Arc<dyn Executor>) -> Self {
+        Self {
+            systemd_dir,
+            executor,
+        }
     }
 }
 
@@ -28,54 +322,38 @@ impl FirewallManager for LinuxFirewallManager {
             return Err("Zero-Trust: Port 0 is reserved and cannot be used".into());
         }
 
-        let action_str = match policy.action {
-            FirewallAction::Allow => "ACCEPT",
-            FirewallAction::Deny => "DROP",
-            FirewallAction::Reject => "REJECT",
-        };
-
-        let protocols: Vec<&str> = match policy.protocol {
-            Protocol::Tcp => vec!["tcp"],
-            Protocol::Udp => vec!["udp"],
-            Protocol::Both => vec!["tcp", "udp"],
-        };
+        // 📝 Denied-traffic logging: the LOG rule sits just ahead of the verdict rule
+        if policy.log_denied && policy.action != FirewallAction::Allow {
+            firewall_log::ensure_forwarder(self.executor.as_ref(), &self.systemd_dir).await?;
+        }
 
-        for proto in &protocols {
-            let mut args = vec![
-                "-A".to_string(),
-                "INPUT".to_string(),
-                "-p".to_string(),
-                proto.to_string(),
-                "--dport".to_string(),
-                policy.port.to_string(),
-            ];
-
-            // 🛡️ Zero-Trust: Source IP filtering (optional)
-            if let Some(ref source_ip) = policy.source_ip {
-                args.push("-s".to_string());
-                args.push(source_ip.to_string());
-            }
+        for proto in protocols(policy) {
+            for rule in policy_rules(policy, proto) {
+                // Idempotent: re-applying after a restart adds no duplicates
+                if rule_present(self.executor.as_ref(), &rule).await? {
+                    continue;
+                }
+                let append = CommandSpec::privileged("iptables")
+                    .args(["-A", "INPUT"])
+                    .args(rule);
+                let output = self
+                    .executor
+                    .run(&append)
+                    .await
+                    .map_err(|e| format!("[SLA ERROR] iptables spawn failed: {}", e))?;
 
-            args.push("-j".to_string());
-            args.push(action_str.to_string());
-
-            let output = Command::new("iptables")
-                .args(&args)
-                .output()
-                .await
-                .map_err(|e| format!("[SLA ERROR] iptables spawn failed: {}", e))?;
-
-            if !output.status.success() {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                return Err(format!(
-                    "[SLA ERROR] iptables rule application failed for port {}/{}: {}",
-                    policy.port, proto, stderr
-                ));
+                if !output.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    return Err(format!(
+                        "[SLA ERROR] iptables rule application failed for port {}/{}: {}",
+                        policy.port, proto, stderr
+                    ));
+                }
             }
 
             info!(
                 "🛡️ Firewall: {} {} port {}/{}",
-                action_str,
+                verdict(policy.action),
                 policy
                     .source_ip
                     .as_ref()
@@ -88,6 +366,51 @@ impl FirewallManager for LinuxFirewallManager {
 
         Ok(())
     }
+
+    async fn policy_applied(&self, policy: &FirewallPolicy) -> Result<bool, String> {
+        for proto in protocols(policy) {
+            for rule in policy_rules(policy, proto) {
+                if !rule_present(self.executor.as_ref(), &rule).await? {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    async fn remove_policy(&self, policy: &FirewallPolicy) -> Result<(), String> {
+        for proto in protocols(policy) {
+            for rule in policy_rules(policy, proto) {
+                // `-D` of a missing rule is an error; a half-removed policy is not
+                if !rule_present(self.executor.as_ref(), &rule).await? {
+                    continue;
+                }
+                let delete = CommandSpec::privileged("iptables")
+                    .args(["-D", "INPUT"])
+                    .args(rule);
+                let output = self
+                    .executor
+                    .run(&delete)
+                    .await
+                    .map_err(|e| format!("[SLA ERROR] iptables spawn failed: {}", e))?;
+                if !output.success() {
+                    return Err(format!(
+                        "[SLA ERROR] iptables rule removal failed for port {}/{}: {}",
+                        policy.port,
+                        proto,
+                        String::from_utf8_lossy(&output.stderr)
+                    ));
+                }
+            }
+            info!(
+                "🛡️ Firewall: removed {} port {}/{}",
+                verdict(policy.action),
+                policy.port,
+                proto
+            );
+        }
+        Ok(())
+    }
 }
 
 // ==============================================================================
@@ -96,8 +419,253 @@ impl FirewallManager for LinuxFirewallManager {
 
 #[cfg(test)]
 mod tests {
-    use super::*;
-    use crate::sys::traits::{FirewallAction, FirewallPolicy, Protocol};
+    use super::{
+        LinuxFirewallManager, policy_rule_key, policy_rules, record_policy, replace_policy,
+        rule_comment, rule_log_id,
+    };
+    use crate::sys::state::FirewallRuleRecord;
+    use crate::sys::traits::{FirewallAction, FirewallManager, FirewallPolicy, Protocol};
+    use crate::testkit::FakeExecutor;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn rules_already_in_the_chain_are_not_appended_again() {
+        let executor = Arc::new(FakeExecutor::default());
+        let firewall =
+            LinuxFirewallManager::with_executor(PathBuf::from("/unused"), executor.clone());
+        let policy = FirewallPolicy {
+            port: 443,
+            action: FirewallAction::Allow,
+            protocol: Protocol::Tcp,
+            source_ip: None,
+            description: None,
+            created_by: None,
+            log_denied: false,
+        };
+        let rule = &policy_rules(&policy, "tcp")[0];
+
+        executor.answer(&["iptables", "-C"], 1, "", "Bad rule");
+        firewall.apply_policy(&policy).await.unwrap();
+        let argvs = executor.argvs();
+        assert_eq!(argvs.len(), 2);
+        assert_eq!(argvs[1][..3], ["iptables", "-A", "INPUT"]);
+        assert_eq!(argvs[1][3..], rule[..]);
+        assert!(!firewall.policy_applied(&policy).await.unwrap());
+
+        executor.answer(&["iptables", "-C"], 0, "", "");
+        firewall.apply_policy(&policy).await.unwrap();
+        assert!(firewall.policy_applied(&policy).await.unwrap());
+        assert_eq!(executor.argvs().len(), 5, "check only, no second append");
+
+        executor.answer(
+            &["iptables", "-C"],
+            4,
+            "",
+            "Permission denied (you must be root)",
+        );
+        let err = firewall.apply_policy(&policy).await.unwrap_err();
+        assert!(err.contains("you must be root"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn only_rules_in_the_chain_are_deleted() {
+        let executor = Arc::new(FakeExecutor::default());
+        let firewall =
+            LinuxFirewallManager::with_executor(PathBuf::from("/unused"), executor.clone());
+        let policy = FirewallPolicy {
+            port: 9229,
+            action: FirewallAction::Allow,
+            protocol: Protocol::Both,
+            source_ip: Some("203.0.113.9".into()),
+            description: Some("debug port for shop.example.com".into()),
+            created_by: None,
+            log_denied: false,
+        };
+
+        // The udp rule is already gone
+        executor.answer(&["iptables", "-C", "INPUT", "-p", "udp"], 1, "", "Bad rule");
+        firewall.remove_policy(&policy).await.unwrap();
+        let deletes: Vec<Vec<String>> = executor
+            .argvs()
+            .into_iter()
+            .filter(|argv| argv[1] == "-D")
+            .collect();
+        assert_eq!(deletes.len(), 1);
+        assert_eq!(deletes[0][3..], policy_rules(&policy, "tcp")[0][..]);
+
+        executor.answer(&["iptables", "-D"], 1, "", "Permission denied");
+        let err = firewall.remove_policy(&policy).await.unwrap_err();
+        assert!(err.contains("9229/tcp"), "{}", err);
+    }
+
+    fn iptables(op: &str, rule: &[String]) -> Vec<String> {
+        let mut argv = vec!["iptables".to_string(), op.to_string(), "INPUT".to_string()];
+        argv.extend(rule.iter().cloned());
+        argv
+    }
+
+    /// Only `old`'s rules are in the chain; returns the -D and -A argvs run
+    /// while replacing it with `new`.
+    async fn replace(old: &FirewallRuleRecord, new: &FirewallPolicy) -> Vec<Vec<String>> {
+        let executor = Arc::new(FakeExecutor::default());
+        let firewall =
+            LinuxFirewallManager::with_executor(PathBuf::from("/unused"), executor.clone());
+        executor.answer(&["iptables", "-C"], 1, "", "Bad rule");
+        let installed = record_policy(old).unwrap();
+        for rule in policy_rules(&installed, "tcp") {
+            let check = iptables("-C", &rule);
+            let check: Vec<&str> = check.iter().map(String::as_str).collect();
+            executor.answer(&check, 0, "", "");
+        }
+        replace_policy(&firewall, Some(old), new).await.unwrap();
+        executor
+            .argvs()
+            .into_iter()
+            .filter(|argv| argv[1] != "-C")
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn a_flipped_action_replaces_the_old_rule() {
+        let allow = FirewallRuleRecord {
+            action: "allow".to_string(),
+            port: 8080,
+            protocol: "tcp".to_string(),
+            description: Some("staging".to_string()),
+            ..Default::default()
+        };
+        let deny = FirewallPolicy {
+            action: FirewallAction::Deny,
+            ..record_policy(&allow).unwrap()
+        };
+
+        let changes = replace(&allow, &deny).await;
+        let old = &policy_rules(&record_policy(&allow).unwrap(), "tcp")[0];
+        let new = &policy_rules(&deny, "tcp")[0];
+        assert_eq!(changes, [iptables("-D", old), iptables("-A", new)]);
+        assert_eq!(old.last().unwrap(), "ACCEPT");
+        assert_eq!(new.last().unwrap(), "DROP");
+    }
+
+    #[tokio::test]
+    async fn a_new_description_does_not_duplicate_the_rule() {
+        let record = FirewallRuleRecord {
+            action: "allow".to_string(),
+            port: 443,
+            protocol: "tcp".to_string(),
+            description: Some("ingress".to_string()),
+            ..Default::default()
+        };
+        let renamed = FirewallPolicy {
+            description: Some("public ingress".to_string()),
+            ..record_policy(&record).unwrap()
+        };
+
+        let changes = replace(&record, &renamed).await;
+        let old = &policy_rules(&record_policy(&record).unwrap(), "tcp")[0];
+        let new = &policy_rules(&renamed, "tcp")[0];
+        assert_eq!(changes, [iptables("-D", old), iptables("-A", new)]);
+
+        // Re-applying the same rule leaves the chain alone
+        let same = record_policy(&record).unwrap();
+        assert!(replace(&record, &same).await.is_empty());
+    }
+
+    #[test]
+    fn stored_rules_restore_to_the_same_iptables_rules() {
+        let record = FirewallRuleRecord {
+            action: "deny".to_string(),
+            port: 5432,
+            protocol: "tcp".to_string(),
+            source_ip: Some("10.0.0.0/8".to_string()),
+            description: Some("Postgres".to_string()),
+            log_denied: true,
+            ..Default::default()
+        };
+        let policy = record_policy(&record).unwrap();
+        assert_eq!(policy_rule_key(&policy), "5432/tcp/10.0.0.0/8");
+
+        let rules = policy_rules(&policy, "tcp");
+        assert_eq!(rules.len(), 2, "LOG rule ahead of the verdict");
+        assert!(rules[0].contains(&"LOG".to_string()));
+        assert_eq!(
+            rules[1],
+            [
+                "-p",
+                "tcp",
+                "--dport",
+                "5432",
+                "-s",
+                "10.0.0.0/8",
+                "-m",
+                "comment",
+                "--comment",
+                "kari: Postgres",
+                "-j",
+                "DROP"
+            ]
+        );
+
+        let allow = FirewallRuleRecord {
+            action: "allow".to_string(),
+            log_denied: true,
+            ..record.clone()
+        };
+        assert_eq!(
+            policy_rules(&record_policy(&allow).unwrap(), "tcp").len(),
+            1
+        );
+        let bad = FirewallRuleRecord {
+            protocol: "sctp".to_string(),
+            ..record
+        };
+        assert!(record_policy(&bad).is_err());
+    }
+
+    #[test]
+    fn rule_comment_names_owner_and_reason() {
+        let mut policy = FirewallPolicy {
+            port: 443,
+            action: FirewallAction::Allow,
+            protocol: Protocol::Tcp,
+            source_ip: None,
+            description: Some("Public HTTPS \"ingress\"".to_string()),
+            created_by: Some("alice@example.com".to_string()),
+            log_denied: false,
+        };
+        assert_eq!(
+            rule_comment(&policy),
+            "kari: Public HTTPS ingress (by alice@example.com)"
+        );
+
+        policy.description = Some("é".repeat(200));
+        policy.created_by = None;
+        let comment = rule_comment(&policy);
+        assert!(comment.len() <= 255);
+        assert!(comment.starts_with("kari: é"));
+
+        policy.description = None;
+        assert_eq!(rule_comment(&policy), "kari:");
+    }
+
+    #[test]
+    fn rule_log_id_fits_the_log_prefix() {
+        let mut policy = FirewallPolicy {
+            port: 22,
+            action: FirewallAction::Deny,
+            protocol: Protocol::Tcp,
+            source_ip: Some("2001:db8:ffff:ffff:ffff:ffff:ffff:ffff".to_string()),
+            description: None,
+            created_by: None,
+            log_denied: true,
+        };
+        let id = rule_log_id(&policy_rule_key(&policy));
+        assert_eq!(id.len(), 8);
+        assert!(format!("kari-fw:{} ", id).len() <= 29);
+        policy.port = 23;
+        assert_ne!(rule_log_id(&policy_rule_key(&policy)), id);
+    }
 
     #[test]
     fn policy_allow_tcp_constructs_correctly() {
@@ -106,6 +674,9 @@ mod tests {
             action: FirewallAction::Allow,
             protocol: Protocol::Tcp,
             source_ip: None,
+            description: None,
+            created_by: None,
+            log_denied: false,
         };
         assert_eq!(policy.port, 443);
         assert!(matches!(policy.action, FirewallAction::Allow));
@@ -120,6 +691,9 @@ mod tests {
             action: FirewallAction::Deny,
             protocol: Protocol::Udp,
             source_ip: Some("10.0.0.0/8".to_string()),
+            description: None,
+            created_by: None,
+            log_denied: false,
         };
         assert_eq!(policy.port, 53);
         assert!(matches!(policy.action, FirewallAction::Deny));
@@ -133,6 +707,9 @@ mod tests {
             action: FirewallAction::Reject,
             protocol: Protocol::Both,
             source_ip: None,
+            description: None,
+            created_by: None,
+            log_denied: false,
         };
         assert!(matches!(policy.protocol, Protocol::Both));
         assert!(matches!(policy.action, FirewallAction::Reject));
@@ -145,6 +722,9 @@ mod tests {
             action: FirewallAction::Allow,
             protocol: Protocol::Tcp,
             source_ip: None,
+            description: None,
+            created_by: None,
+            log_denied: false,
         };
         assert_eq!(policy.port, 0);
     }
@@ -156,12 +736,18 @@ mod tests {
             action: FirewallAction::Allow,
             protocol: Protocol::Tcp,
             source_ip: None,
+            description: None,
+            created_by: None,
+            log_denied: false,
         };
         let high = FirewallPolicy {
             port: 65535,
             action: FirewallAction::Allow,
             protocol: Protocol::Tcp,
             source_ip: None,
+            description: None,
+            created_by: None,
+            log_denied: false,
         };
         assert_eq!(low.port, 1);
         assert_eq!(high.port, 65535);
@@ -196,6 +782,9 @@ mod tests {
             action: FirewallAction::Allow,
             protocol: Protocol::Tcp,
             source_ip: Some("192.168.1.100".to_string()),
+            description: None,
+            created_by: None,
+            log_denied: false,
         };
         let mut args = vec!["-A", "INPUT", "-p", "tcp", "--dport", "443"];
         if let Some(ref ip) = policy.source_ip {
@@ -226,8 +815,11 @@ mod tests {
             action: FirewallAction::Deny,
             protocol: Protocol::Udp,
             source_ip: None,
+            description: None,
+            created_by: None,
+            log_denied: false,
         };
-        let mut args: Vec<String> = vec!["-A", "INPUT", "-p", "udp", "--dport", "80"]
+        let mut args: Vec<String> = ["-A", "INPUT", "-p", "udp", "--dport", "80"]
             .iter()
             .map(|s| s.to_string())
             .collect();
@@ -249,6 +841,9 @@ mod tests {
                 action: FirewallAction::Allow,
                 protocol: Protocol::Tcp,
                 source_ip: Some(cidr.to_string()),
+                description: None,
+                created_by: None,
+                log_denied: false,
             };
             assert_eq!(p.source_ip.as_deref(), Some(*cidr));
         }
@@ -0,0 +1,157 @@
+// agent/src/sys/firewall_log.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Denied-traffic logging for kari rules.
+//
+// Kernel LOG lines cannot be written into a journal namespace directly, so a
+// small forwarder unit (LogNamespace=kari-firewall) copies every `kari-fw:`
+// kernel line into it. Firewall events are then read from that namespace only,
+// keeping packet noise out of the system journal's consumers and vice versa.
+
+use async_trait::async_trait;
+use std::path::Path;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc;
+
+use crate::sys::executor::{CommandSpec, Executor};
+use crate::sys::privilege;
+use crate::sys::traits::{FirewallEventSource, FirewallHit};
+
+pub const LOG_NAMESPACE: &str = "kari-firewall";
+pub const LOG_PREFIX: &str = "kari-fw:";
+const FORWARDER_UNIT: &str = "kari-firewall-log.service";
+
+fn render_forwarder_unit() -> String {
+    format!(
+        r#"[Unit]
+Description=Kari firewall log forwarder (kernel {prefix} lines -> journal namespace {ns})
+After=systemd-journald.service
+
+[Service]
+Type=simple
+ExecStart=/usr/bin/journalctl --dmesg --follow --lines=0 --output=cat --grep=^{prefix}
+LogNamespace={ns}
+Restart=always
+RestartSec=5
+
+# --- 🛡️ Kari Ironclad Security Directives ---
+DynamicUser=yes
+SupplementaryGroups=systemd-journal
+NoNewPrivileges=true
+ProtectSystem=strict
+ProtectHome=true
+PrivateTmp=true
+
+[Install]
+WantedBy=multi-user.target
+"#,
+        prefix = LOG_PREFIX,
+        ns = LOG_NAMESPACE,
+    )
+}
+
+async fn systemctl(executor: &dyn Executor, args: &[&str]) -> Result<(), String> {
+    let output = executor
+        .run(&CommandSpec::privileged("systemctl").args(args.iter().copied()))
+        .await
+        .map_err(|e| format!("Failed to execute systemctl: {}", e))?;
+    if !output.success() {
+        return Err(format!(
+            "systemctl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Installs and starts the forwarder unit. Idempotent: an unchanged unit is
+/// only (re-)enabled, never rewritten.
+pub async fn ensure_forwarder(executor: &dyn Executor, systemd_dir: &Path) -> Result<(), String> {
+    let path = systemd_dir.join(FORWARDER_UNIT);
+    let content = render_forwarder_unit();
+    if executor.read_file(&path).await.ok().flatten().as_deref() != Some(content.as_str()) {
+        executor
+            .write_file(&path, &content, 0o644)
+            .await
+            .map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+        systemctl(executor, &["daemon-reload"]).await?;
+    }
+    systemctl(executor, &["enable", "--now", FORWARDER_UNIT]).await
+}
+
+/// Parses `kari-fw:<rule_id> IN=eth0 ... SRC=203.0.113.9 DST=...`.
+pub fn parse_hit(line: &str) -> Option<FirewallHit> {
+    let rest = &line[line.find(LOG_PREFIX)? + LOG_PREFIX.len()..];
+    let rule_id = rest.split_whitespace().next()?;
+    if rule_id.is_empty() || !rule_id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let source_ip = rest
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("SRC="))
+        .unwrap_or_default();
+    Some(FirewallHit {
+        rule_id: rule_id.to_string(),
+        source_ip: source_ip.to_string(),
+    })
+}
+
+/// Follows the `kari-firewall` journal namespace.
+pub struct JournalFirewallEventSource;
+
+#[async_trait]
+impl FirewallEventSource for JournalFirewallEventSource {
+    async fn follow(&self, tx: mpsc::Sender<FirewallHit>) -> Result<(), String> {
+        let mut child = privilege::command("journalctl")
+            .arg(format!("--namespace={}", LOG_NAMESPACE))
+            .args(["--follow", "--lines=0", "--output=cat"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true) // 🛡️ A disconnected caller never leaves journalctl behind
+            .spawn()
+            .map_err(|e| format!("Failed to follow firewall journal: {}", e))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| "journalctl produced no stdout".to_string())?;
+
+        let mut lines = BufReader::new(stdout).lines();
+        loop {
+            tokio::select! {
+                _ = tx.closed() => return Ok(()),
+                line = lines.next_line() => match line {
+                    Ok(Some(line)) => {
+                        if let Some(hit) = parse_hit(&line)
+                            && tx.send(hit).await.is_err()
+                        {
+                            return Ok(());
+                        }
+                    }
+                    Ok(None) => return Err("Firewall journal stream ended".into()),
+                    Err(e) => return Err(format!("Failed to read firewall journal: {}", e)),
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernel_log_lines_are_parsed() {
+        let line = "kari-fw:1a2b3c4d IN=eth0 OUT= MAC=00:11 SRC=203.0.113.9 DST=10.0.0.5 \
+                    LEN=60 PROTO=TCP SPT=51000 DPT=22";
+        assert_eq!(
+            parse_hit(line),
+            Some(FirewallHit {
+                rule_id: "1a2b3c4d".into(),
+                source_ip: "203.0.113.9".into(),
+            })
+        );
+        assert_eq!(parse_hit("kari-fw:zz IN=eth0 SRC=1.2.3.4"), None);
+        assert_eq!(parse_hit("UFW BLOCK IN=eth0 SRC=1.2.3.4"), None);
+    }
+}
@@ -0,0 +1,199 @@
+// agent/src/sys/heartbeat.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Tells the control plane this host is alive.
+//
+// Opt-in (KARI_HEARTBEAT_URL). Every interval the agent POSTs who it is, what
+// it can do and a health summary, so a host that goes quiet is noticed without
+// the API dialling every socket. The body is signed with HMAC-SHA256 under a
+// pinned key shared with the control plane; `sent_at` and `sequence` are inside
+// the signed body, so a captured heartbeat cannot be replayed as a fresh one.
+// Nothing comes back: the agent still takes no orders over the network.
+
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use sysinfo::System;
+use tracing::{debug, warn};
+use zeroize::Zeroizing;
+
+use crate::sys::notify::https_post;
+use crate::sys::state::{AppRecord, NS_APPS, list_records};
+use crate::sys::systemd::ServiceManager;
+use crate::sys::traits::{HeartbeatTransport, StateStore};
+
+/// Carries `sha256=<hex HMAC of the body>`.
+pub const SIGNATURE_HEADER: &str = "X-Kari-Signature";
+
+const MIN_KEY_BYTES: usize = 32;
+
+/// Who is beating. Gathered once at startup; none of it changes while the
+/// agent runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct HostIdentity {
+    pub host_id: String, // /etc/machine-id
+    pub hostname: String,
+    pub agent_version: String,
+    pub capabilities: Vec<String>, // "api:kari.agent.v1", then detected stack components
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct HealthSummary {
+    pub healthy: bool, // No app down and no circuit open
+    pub uptime_secs: u64,
+    pub cpu_usage_percent: f32,
+    pub memory_used_mb: u64,
+    pub memory_total_mb: u64,
+    pub apps: u32,
+    pub apps_down: Vec<String>,     // app_ids whose unit is not active
+    pub circuits_open: Vec<String>, // app_ids serving the maintenance page
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HeartbeatPayload {
+    #[serde(flatten)]
+    pub identity: HostIdentity,
+    pub health: HealthSummary,
+    pub sequence: u64, // From 1 at every agent start
+    pub sent_at: i64,  // Unix seconds
+}
+
+/// /etc/machine-id, falling back to the D-Bus copy. Empty when neither exists.
+pub fn read_host_id() -> String {
+    ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .map(|id| id.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// 🛡️ Zero-Trust: A key readable by anyone else, or not owned by this
+/// process, may have been leaked or swapped.
+fn load_key(path: &Path) -> Result<Zeroizing<Vec<u8>>, String> {
+    let meta = std::fs::metadata(path)
+        .map_err(|e| format!("No heartbeat key at {}: {}", path.display(), e))?;
+    if meta.mode() & 0o077 != 0 || meta.uid() != nix::unistd::geteuid().as_raw() {
+        return Err(format!(
+            "Heartbeat key {} must be owned by the agent and mode 0600",
+            path.display()
+        ));
+    }
+    let key = Zeroizing::new(
+        std::fs::read(path).map_err(|e| format!("Failed to read heartbeat key: {}", e))?,
+    );
+    if key.len() < MIN_KEY_BYTES {
+        return Err(format!(
+            "Heartbeat key must be at least {} bytes",
+            MIN_KEY_BYTES
+        ));
+    }
+    Ok(key)
+}
+
+/// The `SIGNATURE_HEADER` value for `body`.
+pub fn sign(key: &[u8], body: &[u8]) -> String {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(body);
+    let digest: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("sha256={}", digest)
+}
+
+#[derive(Clone)]
+pub struct Heartbeat {
+    pub transport: Arc<dyn HeartbeatTransport>,
+    pub state: Arc<dyn StateStore>,
+    pub services: Arc<dyn ServiceManager>,
+    pub url: String,
+    pub key_path: PathBuf, // Read at every beat, so it can be rotated in place
+    pub identity: HostIdentity,
+}
+
+impl Heartbeat {
+    /// Beats every `interval` for as long as the agent runs. A failed beat is
+    /// logged and the next one goes out on schedule; the control plane's
+    /// dead-host timeout is what should absorb a flaky link.
+    pub async fn run(self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval.max(Duration::from_millis(10)));
+        let mut system = System::new();
+        let mut sequence = 0;
+        loop {
+            ticker.tick().await;
+            sequence += 1;
+            match self.beat(&mut system, sequence).await {
+                Ok(()) => debug!("💓 Heartbeat {} delivered", sequence),
+                Err(e) => warn!("💓 Heartbeat {} failed: {}", sequence, e),
+            }
+        }
+    }
+
+    async fn beat(&self, system: &mut System, sequence: u64) -> Result<(), String> {
+        let key = load_key(&self.key_path)?;
+        let payload = HeartbeatPayload {
+            identity: self.identity.clone(),
+            health: self.health(system).await?,
+            sequence,
+            sent_at: chrono::Utc::now().timestamp(),
+        };
+        let body = serde_json::to_vec(&payload)
+            .map_err(|e| format!("Failed to encode heartbeat: {}", e))?;
+        self.transport
+            .send(&self.url, &body, &sign(&key, &body))
+            .await
+    }
+
+    async fn health(&self, system: &mut System) -> Result<HealthSummary, String> {
+        let apps: Vec<AppRecord> = list_records(self.state.as_ref(), NS_APPS).await?;
+        let mut apps_down = Vec::new();
+        for app in &apps {
+            if !self
+                .services
+                .is_active(&app.service_name)
+                .await
+                .unwrap_or(false)
+            {
+                apps_down.push(app.app_id.clone());
+            }
+        }
+        let circuits_open: Vec<String> = apps
+            .iter()
+            .filter(|app| app.vhost.maintenance)
+            .map(|app| app.app_id.clone())
+            .collect();
+
+        system.refresh_cpu();
+        system.refresh_memory();
+        Ok(HealthSummary {
+            healthy: apps_down.is_empty() && circuits_open.is_empty(),
+            uptime_secs: System::uptime(),
+            cpu_usage_percent: system.global_cpu_info().cpu_usage(),
+            memory_used_mb: system.used_memory() / 1_048_576,
+            memory_total_mb: system.total_memory() / 1_048_576,
+            apps: apps.len() as u32,
+            apps_down,
+            circuits_open,
+        })
+    }
+}
+
+/// Delivers heartbeats with the host's curl (see `https_post`), which brings the system trust
+/// store and proxy settings the agent would otherwise have to reimplement.
+pub struct CurlHeartbeatTransport {
+    pub timeout: Duration,
+}
+
+#[async_trait]
+impl HeartbeatTransport for CurlHeartbeatTransport {
+    async fn send(&self, url: &str, body: &[u8], signature: &str) -> Result<(), String> {
+        let header = format!("{}: {}", SIGNATURE_HEADER, signature);
+        https_post(url, body, &[header], self.timeout).await
+    }
+}
@@ -0,0 +1,356 @@
+// agent/src/sys/host_ops.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Named host operations with fixed argv.
+//
+// The package-command pattern, generalised: callers name an operation and
+// fill its parameters; the argv comes from a template here or in agent.toml.
+// Parameters can only land in the `{slot}`s their template declares, and
+// every slot has a kind whose values cannot start with '-', contain spaces
+// or reach a shell, so no option or extra argument is reachable.
+//
+//   [operations.certbot-renew-one]
+//   description = "Renew one certbot lineage"
+//   argv = ["/usr/bin/certbot", "renew", "--non-interactive", "--cert-name", "{domain}"]
+//
+//   [operations.certbot-renew-one.params]
+//   domain = "domain"                    # domain | identifier | unit | number | one_of:a|b|c
+//
+// Built-in operations cannot be redefined. agent.toml is re-read for every
+// call, so edits apply without restarting the agent.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::config::AgentToml;
+use crate::sys::systemd::is_valid_unit_name;
+
+const MAX_PARAM_LEN: usize = 253;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamKind {
+    Domain,             // A DNS name (RFC 1123 labels)
+    Identifier,         // App ids, cert names: [A-Za-z0-9._-], alphanumeric first
+    Unit,               // A systemd unit name
+    Number,             // Decimal digits only
+    OneOf(Vec<String>), // One of a fixed set of words
+}
+
+impl ParamKind {
+    fn parse(value: &str) -> Option<Self> {
+        Some(match value {
+            "domain" => Self::Domain,
+            "identifier" => Self::Identifier,
+            "unit" => Self::Unit,
+            "number" => Self::Number,
+            other => {
+                let choices: Vec<String> = other
+                    .strip_prefix("one_of:")?
+                    .split('|')
+                    .map(str::to_string)
+                    .collect();
+                if !choices.iter().all(|c| is_identifier(c)) {
+                    return None;
+                }
+                Self::OneOf(choices)
+            }
+        })
+    }
+
+    fn accepts(&self, value: &str) -> bool {
+        value.len() <= MAX_PARAM_LEN
+            && match self {
+                Self::Domain => is_domain(value),
+                Self::Identifier => is_identifier(value),
+                Self::Unit => is_valid_unit_name(value) && !value.starts_with('-'),
+                Self::Number => {
+                    !value.is_empty()
+                        && value.len() <= 10
+                        && value.bytes().all(|b| b.is_ascii_digit())
+                }
+                Self::OneOf(choices) => choices.iter().any(|c| c == value),
+            }
+    }
+}
+
+fn is_domain(value: &str) -> bool {
+    !value.is_empty()
+        && value.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+fn is_identifier(value: &str) -> bool {
+    value.len() <= 64
+        && value.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && !value.contains("..")
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostOperation {
+    pub name: String,
+    pub description: String,
+    pub argv: Vec<String>,
+    pub params: BTreeMap<String, ParamKind>,
+}
+
+/// The `{slot}` names in one argv word.
+fn slots(word: &str) -> Result<Vec<&str>, String> {
+    let mut found = Vec::new();
+    let mut rest = word;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unterminated slot in '{}'", word))?;
+        found.push(&rest[start + 1..start + end]);
+        rest = &rest[start + end + 1..];
+    }
+    if rest.contains('}') {
+        return Err(format!("unmatched '}}' in '{}'", word));
+    }
+    Ok(found)
+}
+
+impl HostOperation {
+    /// The argv with each slot filled. Every declared parameter must be given
+    /// and accepted by its kind; nothing else may be given.
+    pub fn render(&self, params: &BTreeMap<String, String>) -> Result<Vec<String>, String> {
+        if let Some(unknown) = params.keys().find(|k| !self.params.contains_key(*k)) {
+            return Err(format!("{} takes no parameter '{}'", self.name, unknown));
+        }
+        for (name, kind) in &self.params {
+            let value = params
+                .get(name)
+                .ok_or_else(|| format!("{} needs parameter '{}'", self.name, name))?;
+            if !kind.accepts(value) {
+                return Err(format!(
+                    "{}: '{}' is not a valid {:?} for '{}'",
+                    self.name, value, kind, name
+                ));
+            }
+        }
+        Ok(self
+            .argv
+            .iter()
+            .map(|word| {
+                params.iter().fold(word.clone(), |word, (name, value)| {
+                    word.replace(&format!("{{{}}}", name), value)
+                })
+            })
+            .collect())
+    }
+
+    fn check(&self) -> Result<(), String> {
+        let at = |e: String| format!("operations.{}: {}", self.name, e);
+        if !is_identifier(&self.name) {
+            return Err(at("the name must be an identifier".to_string()));
+        }
+        let program = self.argv.first().map(String::as_str).unwrap_or_default();
+        if !program.starts_with('/') || program.contains(['{', '}']) {
+            return Err(at(
+                "argv must start with an absolute program path".to_string()
+            ));
+        }
+        if self.argv.iter().any(|w| w.chars().any(|c| c.is_control())) {
+            return Err(at("argv may not contain control characters".to_string()));
+        }
+        let mut used = BTreeSet::new();
+        for word in &self.argv {
+            for slot in slots(word).map_err(at)? {
+                if !self.params.contains_key(slot) {
+                    return Err(at(format!("slot '{{{}}}' has no parameter", slot)));
+                }
+                used.insert(slot);
+            }
+        }
+        if let Some(unused) = self.params.keys().find(|p| !used.contains(p.as_str())) {
+            return Err(at(format!("parameter '{}' fills no slot", unused)));
+        }
+        Ok(())
+    }
+}
+
+/// Built-ins take no parameters.
+fn builtin(name: &str, description: &str, argv: &[&str]) -> HostOperation {
+    HostOperation {
+        name: name.to_string(),
+        description: description.to_string(),
+        argv: argv.iter().map(|w| w.to_string()).collect(),
+        params: BTreeMap::new(),
+    }
+}
+
+/// What every agent can run, before anything agent.toml adds.
+pub fn builtins() -> Vec<HostOperation> {
+    vec![
+        builtin(
+            "nginx-test",
+            "Check the Nginx configuration (nginx -t)",
+            &["/usr/sbin/nginx", "-t"],
+        ),
+        builtin(
+            "nginx-dump",
+            "Print the effective Nginx configuration (nginx -T)",
+            &["/usr/sbin/nginx", "-T"],
+        ),
+        builtin(
+            "apache-test",
+            "Check the Apache configuration (apachectl configtest)",
+            &["/usr/sbin/apachectl", "configtest"],
+        ),
+        builtin(
+            "certbot-renew",
+            "Renew certbot-managed certificates that are due",
+            &["/usr/bin/certbot", "renew", "--non-interactive"],
+        ),
+        builtin(
+            "certbot-certificates",
+            "List certbot-managed certificates",
+            &["/usr/bin/certbot", "certificates"],
+        ),
+    ]
+}
+
+/// Built-ins plus the [operations.*] tables of agent.toml, by name.
+pub fn load(agent_toml: &Path) -> Result<BTreeMap<String, HostOperation>, String> {
+    let mut operations: BTreeMap<String, HostOperation> = builtins()
+        .into_iter()
+        .map(|op| (op.name.clone(), op))
+        .collect();
+    let declared = AgentToml::load(agent_toml)?.unwrap_or_default().operations;
+
+    for (name, settings) in declared {
+        let at = |e: &str| format!("operations.{}: {}", name, e);
+        if operations.contains_key(&name) {
+            return Err(at("redefines a built-in operation"));
+        }
+        let mut kinds = BTreeMap::new();
+        for (param, kind) in settings.params {
+            let kind = ParamKind::parse(&kind)
+                .ok_or_else(|| at(&format!("parameter '{}' has an unknown kind", param)))?;
+            kinds.insert(param, kind);
+        }
+        let operation = HostOperation {
+            name: name.clone(),
+            description: settings.description,
+            argv: settings.argv,
+            params: kinds,
+        };
+        operation.check()?;
+        operations.insert(name, operation);
+    }
+    Ok(operations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn renew_one() -> HostOperation {
+        HostOperation {
+            name: "certbot-renew-one".into(),
+            description: String::new(),
+            argv: [
+                "/usr/bin/certbot",
+                "renew",
+                "--cert-name",
+                "{domain}",
+                "--deploy-hook={hook}",
+            ]
+            .map(String::from)
+            .to_vec(),
+            params: BTreeMap::from([
+                ("domain".to_string(), ParamKind::Domain),
+                (
+                    "hook".to_string(),
+                    ParamKind::parse("one_of:reload|none").unwrap(),
+                ),
+            ]),
+        }
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parameters_fill_only_their_slots() {
+        let op = renew_one();
+        op.check().unwrap();
+        assert_eq!(
+            op.render(&params(&[
+                ("domain", "shop.example.com"),
+                ("hook", "reload")
+            ]))
+            .unwrap(),
+            [
+                "/usr/bin/certbot",
+                "renew",
+                "--cert-name",
+                "shop.example.com",
+                "--deploy-hook=reload"
+            ]
+        );
+        for bad in [
+            "--config=/tmp/x",
+            "shop.example.com --dry-run",
+            "a;b",
+            "",
+            "-x.example.com",
+        ] {
+            assert!(
+                op.render(&params(&[("domain", bad), ("hook", "reload")]))
+                    .is_err(),
+                "{bad}"
+            );
+        }
+        assert!(
+            op.render(&params(&[("domain", "shop.example.com"), ("hook", "sh")]))
+                .is_err()
+        );
+        assert!(
+            op.render(&params(&[("domain", "shop.example.com")]))
+                .is_err()
+        );
+        assert!(
+            op.render(&params(&[
+                ("domain", "a.b"),
+                ("hook", "none"),
+                ("extra", "1")
+            ]))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn templates_must_be_closed() {
+        let mut op = renew_one();
+        op.argv.push("{undeclared}".into());
+        assert!(op.check().is_err());
+
+        let mut op = renew_one();
+        op.params.insert("unused".into(), ParamKind::Number);
+        assert!(op.check().is_err());
+
+        let mut op = renew_one();
+        op.argv[0] = "certbot".into();
+        assert!(op.check().is_err());
+
+        let mut op = renew_one();
+        op.argv[0] = "/usr/bin/{domain}".into();
+        assert!(op.check().is_err());
+
+        assert!(ParamKind::parse("one_of:a|-rf").is_none());
+        assert!(ParamKind::parse("path").is_none());
+        builtins().iter().for_each(|op| op.check().unwrap());
+    }
+}
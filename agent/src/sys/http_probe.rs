@@ -0,0 +1,132 @@
+// agent/src/sys/http_probe.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Plain HTTP/1.0 GETs against loopback
+// listeners: the self-test's ingress probe and the deploy health gate. No TLS,
+// no redirects, a capped response; all the agent ever needs to ask is "does
+// this port answer, and with what".
+
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const MAX_PROBE_RESPONSE_BYTES: u64 = 64 * 1024;
+
+/// Retry policy for the deploy health gate: `GET path` on the release's port
+/// until it answers 2xx/3xx, at most `1 + retries` attempts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthCheck {
+    pub path: String,
+    pub timeout: Duration, // Per attempt
+    pub retries: u32,
+    pub interval: Duration, // Between attempts
+}
+
+/// One GET of `path`; returns the status code and body.
+pub async fn fetch(
+    addr: &str,
+    host: &str,
+    path: &str,
+    timeout: Duration,
+) -> Result<(u16, String), String> {
+    let mut stream = tokio::time::timeout(timeout, TcpStream::connect(addr))
+        .await
+        .map_err(|_| format!("Timed out connecting to {}", addr))?
+        .map_err(|e| format!("Failed to connect to {}: {}", addr, e))?;
+
+    let request = format!(
+        "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        path, host
+    );
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| format!("Failed to send probe: {}", e))?;
+
+    let mut raw = Vec::new();
+    tokio::time::timeout(
+        timeout,
+        stream.take(MAX_PROBE_RESPONSE_BYTES).read_to_end(&mut raw),
+    )
+    .await
+    .map_err(|_| "Timed out reading probe response".to_string())?
+    .map_err(|e| format!("Failed to read probe response: {}", e))?;
+
+    let text = String::from_utf8_lossy(&raw);
+    let status = text
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| "Malformed HTTP response".to_string())?;
+    let body = text
+        .split_once("\r\n\r\n")
+        .map(|(_, body)| body.to_string())
+        .unwrap_or_default();
+    Ok((status, body))
+}
+
+/// 🩺 Runs `check` against `127.0.0.1:port`. Returns the attempts it took, or
+/// the last failure once every attempt is spent.
+pub async fn wait_healthy(port: u16, host: &str, check: &HealthCheck) -> Result<u32, String> {
+    let addr = format!("127.0.0.1:{}", port);
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let failure = match fetch(&addr, host, &check.path, check.timeout).await {
+            Ok((status, _)) if (200..400).contains(&status) => return Ok(attempt),
+            Ok((status, _)) => format!("GET {} answered HTTP {}", check.path, status),
+            Err(e) => e,
+        };
+        if attempt > check.retries {
+            return Err(format!("{} (after {} attempts)", failure, attempt));
+        }
+        tokio::time::sleep(check.interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn health_gate_retries_until_the_app_answers() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            // Still booting for the first request, healthy from the second
+            let mut served = 0;
+            while let Ok((mut conn, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let n = conn.read(&mut buf).await.unwrap_or(0);
+                let req = String::from_utf8_lossy(&buf[..n]).to_string();
+                served += 1;
+                let resp = match (served, req.starts_with("GET /healthz ")) {
+                    (1, _) => "HTTP/1.0 503 Service Unavailable\r\n\r\n",
+                    (_, true) => "HTTP/1.0 204 No Content\r\n\r\n",
+                    (_, false) => "HTTP/1.0 404 Not Found\r\n\r\n",
+                };
+                let _ = conn.write_all(resp.as_bytes()).await;
+            }
+        });
+
+        let check = |path: &str, retries| HealthCheck {
+            path: path.into(),
+            timeout: Duration::from_secs(1),
+            retries,
+            interval: Duration::ZERO,
+        };
+        assert_eq!(
+            wait_healthy(port, "shop.example.com", &check("/healthz", 3)).await,
+            Ok(2)
+        );
+        let err = wait_healthy(port, "shop.example.com", &check("/missing", 1))
+            .await
+            .unwrap_err();
+        assert!(
+            err.contains("HTTP 404") && err.contains("2 attempts"),
+            "{}",
+            err
+        );
+    }
+}
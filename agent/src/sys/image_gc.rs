@@ -0,0 +1,112 @@
+// agent/src/sys/image_gc.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Container image garbage collection policy.
+// The state store is the source of truth for which image each app runs, so an
+// image referenced by a still-deployed app, or by a release still on disk to
+// roll back to, is never removed.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::sys::capacity::disk_usage_percent;
+use crate::sys::notify::{Notice, NoticeKind};
+use crate::sys::state::{AppRecord, NS_APPS, NS_RELEASES, ReleaseRecord, list_records};
+use crate::sys::traits::{ImageManager, StateStore};
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GcReport {
+    pub apps_scanned: u32,
+    pub images_removed: u32,
+}
+
+/// Prunes every container app's storage (or only `app_filter`), keeping deployed images.
+pub async fn collect_images(
+    state: &dyn StateStore,
+    images: &dyn ImageManager,
+    web_root: &Path,
+    app_filter: Option<&str>,
+) -> Result<GcReport, String> {
+    let apps: Vec<AppRecord> = list_records(state, NS_APPS).await?;
+    let releases: Vec<ReleaseRecord> = list_records(state, NS_RELEASES).await?;
+    let mut report = GcReport::default();
+
+    for app in apps
+        .iter()
+        .filter(|a| a.runtime == "container")
+        .filter(|a| app_filter.is_none_or(|id| a.app_id == id))
+    {
+        let app_dir = web_root.join(&app.domain_name);
+        let mut keep: Vec<String> = app.image.iter().cloned().collect();
+        keep.extend(
+            releases
+                .iter()
+                .filter(|r| r.app_id == app.app_id && r.domain_name == app.domain_name)
+                .filter(|r| app_dir.join("releases").join(&r.release).is_dir())
+                .filter_map(|r| r.image.clone()),
+        );
+        keep.sort();
+        keep.dedup();
+
+        match images.prune_images(&app.app_user, &app_dir, &keep).await {
+            Ok(removed) => report.images_removed += removed as u32,
+            // 🛡️ SLA: One tenant's broken storage must not block GC for the others.
+            Err(e) => warn!("Image GC failed for {}: {}", app.app_id, e),
+        }
+        report.apps_scanned += 1;
+    }
+
+    Ok(report)
+}
+
+/// 🛡️ SLA: Disk-pressure driven GC. Runs only when the web root's filesystem
+/// crosses `threshold_percent`, so healthy hosts keep their warm image cache.
+/// Crossing it raises a DiskPressure notice, once until usage drops back.
+pub async fn run_disk_pressure_gc(
+    state: Arc<dyn StateStore>,
+    images: Arc<dyn ImageManager>,
+    web_root: std::path::PathBuf,
+    interval: Duration,
+    threshold_percent: f64,
+    notices: broadcast::Sender<Notice>,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    let mut under_pressure = false;
+    loop {
+        ticker.tick().await;
+
+        let Some(usage) = disk_usage_percent(&web_root) else {
+            continue;
+        };
+        if usage < threshold_percent {
+            under_pressure = false;
+            continue;
+        }
+        if !under_pressure {
+            under_pressure = true;
+            let _ = notices.send(Notice::new(
+                NoticeKind::DiskPressure,
+                &web_root.display().to_string(),
+                format!("Disk at {:.0}% on {}", usage, web_root.display()),
+                format!(
+                    "Usage crossed the {:.0}% threshold; collecting container images",
+                    threshold_percent
+                ),
+            ));
+        }
+
+        info!(
+            "🧹 Disk at {:.1}% (threshold {:.0}%): collecting container images",
+            usage, threshold_percent
+        );
+        match collect_images(state.as_ref(), images.as_ref(), &web_root, None).await {
+            Ok(report) => info!(
+                "🧹 Image GC removed {} images across {} apps",
+                report.images_removed, report.apps_scanned
+            ),
+            Err(e) => warn!("Image GC aborted: {}", e),
+        }
+    }
+}
@@ -0,0 +1,309 @@
+// agent/src/sys/issuance.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Why a CA would refuse a domain, asked first.
+//
+// Most failed issuances are DNS: the name does not exist yet, points at the
+// old host, or a CAA record names a different CA. A CA reports all of these as
+// a generic challenge failure. This looks up the same records from the host's
+// resolvers and names the problem before the CA is asked.
+
+use std::net::IpAddr;
+
+use crate::sys::traits::{DnsRecord, DnsResolver, NetworkInterface, RecordType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Diagnosis {
+    Ready,
+    Nxdomain,        // The name does not exist
+    NoAddress,       // It exists without A or AAAA records
+    PointsElsewhere, // Some address is not this host's
+    CaaForbids,      // The relevant CAA set does not authorise the CA
+    DnsError,        // No answer (SERVFAIL, timeouts); CAs treat this as a refusal too
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IssuanceCheck {
+    pub diagnosis: Diagnosis,
+    pub detail: String,
+    pub addresses: Vec<IpAddr>,
+    pub caa_domain: Option<String>, // Where the relevant CAA set was found
+    pub caa_records: Vec<String>,   // As "flags tag value"
+}
+
+impl IssuanceCheck {
+    fn new(diagnosis: Diagnosis, detail: String) -> Self {
+        Self {
+            diagnosis,
+            detail,
+            addresses: Vec::new(),
+            caa_domain: None,
+            caa_records: Vec::new(),
+        }
+    }
+}
+
+/// The issuer domain of a CAA `issue` value: "letsencrypt.org; accounturi=..."
+/// -> "letsencrypt.org". An empty one (";") authorises nobody.
+fn issuer_domain(value: &str) -> String {
+    value
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// Whether a CAA set lets `ca_domain` issue for a (non-wildcard) name. A set
+/// without `issue` tags restricts nothing but an unknown critical tag, which
+/// a CA must treat as a refusal (RFC 8659).
+pub fn caa_permits(records: &[DnsRecord], ca_domain: &str) -> bool {
+    let mut issuers = Vec::new();
+    for record in records {
+        if let DnsRecord::Caa {
+            critical,
+            tag,
+            value,
+        } = record
+        {
+            match tag.as_str() {
+                "issue" => issuers.push(issuer_domain(value)),
+                "issuewild" | "iodef" => {}
+                _ if *critical => return false,
+                _ => {}
+            }
+        }
+    }
+    issuers.is_empty() || issuers.iter().any(|issuer| issuer == ca_domain)
+}
+
+/// The addresses on this host's interfaces that a CA could reach. Behind NAT
+/// there are none, and the caller cannot tell where the name should point.
+pub fn public_addresses(interfaces: &[NetworkInterface]) -> Vec<IpAddr> {
+    let public = |ip: &IpAddr| match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || (a == 100 && (64..128).contains(&b))) // CGNAT
+        }
+        IpAddr::V6(v6) => {
+            let first = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || first & 0xffc0 == 0xfe80 // Link-local
+                || first & 0xfe00 == 0xfc00) // Unique local
+        }
+    };
+    interfaces
+        .iter()
+        .filter(|iface| iface.up)
+        .flat_map(|iface| &iface.addresses)
+        .filter_map(|cidr| cidr.split('/').next()?.parse().ok())
+        .filter(public)
+        .collect()
+}
+
+/// `name` and every parent up to the TLD, the order CAA is looked up in.
+fn caa_search_path(name: &str) -> Vec<String> {
+    let labels: Vec<&str> = name.trim_end_matches('.').split('.').collect();
+    (0..labels.len()).map(|i| labels[i..].join(".")).collect()
+}
+
+/// Checks `domain` the way `ca_domain` would before issuing: it must resolve,
+/// every address must be one of `expected` (when any are given), and the
+/// closest CAA set must authorise the CA.
+pub async fn check(
+    resolver: &dyn DnsResolver,
+    domain: &str,
+    ca_domain: &str,
+    expected: &[IpAddr],
+) -> IssuanceCheck {
+    let mut addresses = Vec::new();
+    for record_type in [RecordType::A, RecordType::Aaaa] {
+        match resolver.query(domain, record_type).await {
+            Ok(answer) if answer.nxdomain => {
+                return IssuanceCheck::new(
+                    Diagnosis::Nxdomain,
+                    format!(
+                        "{} does not exist; create an A or AAAA record pointing at this host",
+                        domain
+                    ),
+                );
+            }
+            Ok(answer) => addresses.extend(answer.records.into_iter().filter_map(|r| match r {
+                DnsRecord::Address(ip) => Some(ip),
+                _ => None,
+            })),
+            Err(e) => return IssuanceCheck::new(Diagnosis::DnsError, e),
+        }
+    }
+    let found = |diagnosis, detail| IssuanceCheck {
+        addresses: addresses.clone(),
+        ..IssuanceCheck::new(diagnosis, detail)
+    };
+    if addresses.is_empty() {
+        return found(
+            Diagnosis::NoAddress,
+            format!("{} has no A or AAAA record", domain),
+        );
+    }
+    let strays: Vec<String> = addresses
+        .iter()
+        .filter(|ip| !expected.is_empty() && !expected.contains(ip))
+        .map(IpAddr::to_string)
+        .collect();
+    if !strays.is_empty() {
+        // The CA may pick any of them (and prefers IPv6), so one is enough to fail
+        return found(
+            Diagnosis::PointsElsewhere,
+            format!(
+                "{} resolves to {}, which is not this host",
+                domain,
+                strays.join(", ")
+            ),
+        );
+    }
+
+    for name in caa_search_path(domain) {
+        let answer = match resolver.query(&name, RecordType::Caa).await {
+            Ok(answer) => answer,
+            Err(e) => {
+                return found(
+                    Diagnosis::DnsError,
+                    format!("{}; a CA will not issue while CAA cannot be read", e),
+                );
+            }
+        };
+        let caa: Vec<DnsRecord> = answer
+            .records
+            .into_iter()
+            .filter(|r| matches!(r, DnsRecord::Caa { .. }))
+            .collect();
+        if caa.is_empty() {
+            continue;
+        }
+        let caa_records = caa
+            .iter()
+            .filter_map(|r| match r {
+                DnsRecord::Caa {
+                    critical,
+                    tag,
+                    value,
+                } => Some(format!(
+                    "{} {} \"{}\"",
+                    if *critical { 128 } else { 0 },
+                    tag,
+                    value
+                )),
+                _ => None,
+            })
+            .collect();
+        let (diagnosis, detail) = if caa_permits(&caa, ca_domain) {
+            (
+                Diagnosis::Ready,
+                format!("CAA at {} authorises {}", name, ca_domain),
+            )
+        } else {
+            (
+                Diagnosis::CaaForbids,
+                format!(
+                    "CAA at {} does not authorise {}; add: {} CAA 0 issue \"{}\"",
+                    name, ca_domain, name, ca_domain
+                ),
+            )
+        };
+        return IssuanceCheck {
+            caa_domain: Some(name),
+            caa_records,
+            ..found(diagnosis, detail)
+        };
+    }
+    found(
+        Diagnosis::Ready,
+        format!(
+            "{} resolves here and no CAA record restricts issuance",
+            domain
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caa(critical: bool, tag: &str, value: &str) -> DnsRecord {
+        DnsRecord::Caa {
+            critical,
+            tag: tag.into(),
+            value: value.into(),
+        }
+    }
+
+    #[test]
+    fn caa_sets_authorise_only_the_issuers_they_name() {
+        let le = "letsencrypt.org";
+        assert!(caa_permits(&[], le));
+        assert!(caa_permits(
+            &[caa(
+                false,
+                "issue",
+                "letsencrypt.org; validationmethods=http-01"
+            )],
+            le
+        ));
+        assert!(!caa_permits(&[caa(false, "issue", "digicert.com")], le));
+        assert!(!caa_permits(&[caa(false, "issue", ";")], le));
+        // issuewild alone does not restrict non-wildcard names
+        assert!(caa_permits(&[caa(false, "issuewild", "digicert.com")], le));
+        assert!(caa_permits(
+            &[caa(false, "iodef", "mailto:ops@example.com")],
+            le
+        ));
+        assert!(!caa_permits(&[caa(true, "future-tag", "x")], le));
+
+        assert_eq!(
+            caa_search_path("www.shop.example.com"),
+            [
+                "www.shop.example.com",
+                "shop.example.com",
+                "example.com",
+                "com"
+            ]
+        );
+    }
+
+    #[test]
+    fn only_globally_reachable_addresses_are_expected() {
+        let interfaces = [
+            NetworkInterface {
+                name: "lo".into(),
+                up: true,
+                addresses: vec!["127.0.0.1/8".into(), "::1/128".into()],
+            },
+            NetworkInterface {
+                name: "eth0".into(),
+                up: true,
+                addresses: vec![
+                    "203.0.113.7/24".into(),
+                    "10.0.0.5/8".into(),
+                    "100.64.1.1/10".into(),
+                    "fe80::1/64".into(),
+                    "fd00::5/64".into(),
+                    "2001:db8::7/64".into(),
+                ],
+            },
+            NetworkInterface {
+                name: "eth1".into(),
+                up: false,
+                addresses: vec!["198.51.100.9/24".into()],
+            },
+        ];
+        let expected: Vec<IpAddr> = vec![
+            "203.0.113.7".parse().unwrap(),
+            "2001:db8::7".parse().unwrap(),
+        ];
+        assert_eq!(public_addresses(&interfaces), expected);
+    }
+}
@@ -1,6 +1,7 @@
+use crate::sys::executor::{CommandSpec, Executor, SystemExecutor};
 use async_trait::async_trait;
 use std::path::Path;
-use tokio::process::Command;
+use std::sync::Arc;
 
 #[async_trait]
 pub trait JailManager: Send + Sync {
@@ -12,9 +13,146 @@ pub trait JailManager: Send + Sync {
 
     /// Locks down a directory safely, avoiding TOCTOU symlink races
     async fn secure_directory(&self, path: &Path, username: &str) -> Result<(), String>;
+
+    /// 🔐 What under `path` departs from the policy `secure_directory`
+    /// applies for `username`. Symlinks are not followed or judged.
+    async fn audit_directory(&self, path: &Path, username: &str) -> Result<DirectoryAudit, String>;
+}
+
+/// Deviations reported in full; the rest are only counted.
+pub const MAX_LISTED_DEVIATIONS: usize = 200;
+
+/// One way a path departs from the jail's ownership and 0750 policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionProblem {
+    Owner,       // Not owned by the app user
+    Group,       // Not in the app user's group
+    Writable,    // Group or world writable
+    OtherAccess, // The app directory itself is open to other users
+    SpecialBits, // setuid, setgid or sticky
+    Unreadable,  // The app user cannot read it (or enter it)
+}
+
+/// A file or directory as the audit sees it. `path` is relative to the
+/// audited directory, "." for the directory itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    pub path: String,
+    pub is_dir: bool,
+    pub uid: u32,
+    pub gid: u32,
+    pub mode: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionDeviation {
+    pub entry: TreeEntry,
+    pub problems: Vec<PermissionProblem>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirectoryAudit {
+    pub checked: u64,
+    pub deviating: u64,
+    pub deviations: Vec<PermissionDeviation>, // The first MAX_LISTED_DEVIATIONS
+}
+
+impl DirectoryAudit {
+    /// Judges each entry against ownership by `uid`:`gid` and 0750.
+    pub fn of(entries: impl IntoIterator<Item = TreeEntry>, uid: u32, gid: u32) -> Self {
+        let mut audit = Self::default();
+        for entry in entries {
+            audit.checked += 1;
+            let mut problems = Vec::new();
+            if entry.uid != uid {
+                problems.push(PermissionProblem::Owner);
+            }
+            if entry.gid != gid {
+                problems.push(PermissionProblem::Group);
+            }
+            if entry.mode & 0o022 != 0 {
+                problems.push(PermissionProblem::Writable);
+            }
+            // Inside a 0750 app directory, other users cannot reach what a
+            // file's own "other" bits would grant
+            if entry.path == "." && entry.mode & 0o007 != 0 {
+                problems.push(PermissionProblem::OtherAccess);
+            }
+            if entry.mode & 0o7000 != 0 {
+                problems.push(PermissionProblem::SpecialBits);
+            }
+            let needed = if entry.is_dir { 0o500 } else { 0o400 };
+            if entry.mode & needed != needed {
+                problems.push(PermissionProblem::Unreadable);
+            }
+            if problems.is_empty() {
+                continue;
+            }
+            audit.deviating += 1;
+            if audit.deviations.len() < MAX_LISTED_DEVIATIONS {
+                audit
+                    .deviations
+                    .push(PermissionDeviation { entry, problems });
+            }
+        }
+        audit
+    }
+}
+
+/// Parses `find -printf '%y %U %G %m %P\0'`, dropping symlinks and anything
+/// that is neither a file nor a directory.
+fn parse_find_output(stdout: &[u8]) -> Result<Vec<TreeEntry>, String> {
+    let mut entries = Vec::new();
+    for record in stdout.split(|b| *b == 0).filter(|r| !r.is_empty()) {
+        let record = String::from_utf8_lossy(record);
+        let mut fields = record.splitn(5, ' ');
+        let (Some(kind), Some(uid), Some(gid), Some(mode), Some(path)) = (
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+        ) else {
+            return Err(format!("Unexpected find output: {}", record));
+        };
+        let is_dir = match kind {
+            "d" => true,
+            "f" => false,
+            _ => continue,
+        };
+        let number = |s: &str, radix| {
+            u32::from_str_radix(s, radix).map_err(|_| format!("Unexpected find output: {}", record))
+        };
+        entries.push(TreeEntry {
+            path: if path.is_empty() { "." } else { path }.to_string(),
+            is_dir,
+            uid: number(uid, 10)?,
+            gid: number(gid, 10)?,
+            mode: number(mode, 8)?,
+        });
+    }
+    Ok(entries)
+}
+
+pub struct LinuxJailManager {
+    executor: Arc<dyn Executor>,
+}
+
+impl LinuxJailManager {
+    pub fn new() -> Self {
+        Self::with_executor(Arc::new(SystemExecutor))
+    }
+
+    pub fn with_executor(executor: Arc<dyn Executor>) -> Self {
+        Self { executor }
+    }
 }
 
-pub struct LinuxJailManager;
+impl Default for LinuxJailManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 #[async_trait]
 impl JailManager for LinuxJailManager {
@@ -32,17 +170,21 @@ impl JailManager for LinuxJailManager {
         }
 
         // Idempotency check: Does the user already exist?
-        let check = Command::new("id").arg("-u").arg(username).output().await;
+        let check = self
+            .executor
+            .run(&CommandSpec::new("id").args(["-u", username]))
+            .await;
         if let Ok(output) = check
-            && output.status.success()
+            && output.success()
         {
             return Ok(());
         }
 
         // 2. 🛡️ Deterministic Jailing
         // We force the specific UID passed from the Go API using `-u`.
-        let output = Command::new("useradd")
-            .args([
+        let output = self
+            .executor
+            .run(&CommandSpec::privileged("useradd").args([
                 "--system",
                 "--no-create-home",
                 "--shell",
@@ -50,12 +192,11 @@ impl JailManager for LinuxJailManager {
                 "-u",
                 &uid.to_string(),
                 username,
-            ])
-            .output()
+            ]))
             .await
             .map_err(|e| format!("SLA Failure: useradd spawn error: {}", e))?;
 
-        if !output.status.success() {
+        if !output.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
             return Err(format!("Failed to provision user {}: {}", username, stderr));
         }
@@ -70,22 +211,22 @@ impl JailManager for LinuxJailManager {
 
         // 1. 🛡️ Hygiene: forcefully kill all lingering processes owned by this user
         // so `userdel` doesn't hang or fail.
-        let _ = Command::new("killall")
-            .args(["-u", username])
-            .output()
+        let _ = self
+            .executor
+            .run(&CommandSpec::privileged("killall").args(["-u", username]))
             .await;
 
         // 2. Deterministic deletion
-        let output = Command::new("userdel")
-            .arg(username)
-            .output()
+        let output = self
+            .executor
+            .run(&CommandSpec::privileged("userdel").arg(username))
             .await
             .map_err(|e| format!("Failed to execute userdel: {}", e))?;
 
-        if !output.status.success() {
+        if !output.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
             // userdel returns exit code 6 if the user doesn't exist. We treat that as success.
-            if output.status.code() != Some(6) {
+            if output.code != Some(6) {
                 return Err(format!(
                     "Failed to deprovision user {}: {}",
                     username, stderr
@@ -114,13 +255,17 @@ impl JailManager for LinuxJailManager {
         // `-P` prevents traversing symlinks that are encountered.
         let path_str = path.to_str().ok_or("Path contains invalid UTF-8")?;
 
-        let chown_out = Command::new("chown")
-            .args(["-RP", &format!("{}:{}", username, username), path_str])
-            .output()
+        let chown_out = self
+            .executor
+            .run(&CommandSpec::privileged("chown").args([
+                "-RP",
+                &format!("{}:{}", username, username),
+                path_str,
+            ]))
             .await
             .map_err(|e| format!("Failed to spawn chown: {}", e))?;
 
-        if !chown_out.status.success() {
+        if !chown_out.success() {
             return Err(format!(
                 "Failed to secure directory ownership: {}",
                 String::from_utf8_lossy(&chown_out.stderr)
@@ -128,13 +273,13 @@ impl JailManager for LinuxJailManager {
         }
 
         // Apply strict 0750 permissions recursively
-        let chmod_out = Command::new("chmod")
-            .args(["-R", "0750", path_str])
-            .output()
+        let chmod_out = self
+            .executor
+            .run(&CommandSpec::privileged("chmod").args(["-R", "0750", path_str]))
             .await
             .map_err(|e| format!("Failed to spawn chmod: {}", e))?;
 
-        if !chmod_out.status.success() {
+        if !chmod_out.success() {
             return Err(format!(
                 "Failed to secure directory permissions: {}",
                 String::from_utf8_lossy(&chmod_out.stderr)
@@ -143,4 +288,150 @@ impl JailManager for LinuxJailManager {
 
         Ok(())
     }
+
+    async fn audit_directory(&self, path: &Path, username: &str) -> Result<DirectoryAudit, String> {
+        let path_str = path.to_str().ok_or("Path contains invalid UTF-8")?;
+        let mut ids = [0u32; 2];
+        for (flag, id) in ["-u", "-g"].into_iter().zip(ids.iter_mut()) {
+            let output = self
+                .executor
+                .run(&CommandSpec::new("id").args([flag, username]))
+                .await
+                .map_err(|e| format!("Failed to spawn id: {}", e))?;
+            if !output.success() {
+                return Err(format!("No such user {}", username));
+            }
+            *id = String::from_utf8_lossy(&output.stdout)
+                .trim()
+                .parse()
+                .map_err(|_| format!("Unexpected id output for {}", username))?;
+        }
+
+        // `-P` reports symlinks as themselves instead of what they point at
+        let output = self
+            .executor
+            .run(&CommandSpec::privileged("find").args([
+                "-P",
+                path_str,
+                "-printf",
+                "%y %U %G %m %P\\0",
+            ]))
+            .await
+            .map_err(|e| format!("Failed to spawn find: {}", e))?;
+        if !output.success() {
+            return Err(format!(
+                "Failed to audit directory: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(DirectoryAudit::of(
+            parse_find_output(&output.stdout)?,
+            ids[0],
+            ids[1],
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::FakeExecutor;
+
+    #[tokio::test]
+    async fn users_are_created_once_and_userdel_misses_are_not_errors() {
+        let executor = Arc::new(FakeExecutor::default());
+        let jail = LinuxJailManager::with_executor(executor.clone());
+
+        executor.answer(&["id"], 1, "", "id: 'kari-shop': no such user");
+        jail.provision_app_user("kari-shop", 2001).await.unwrap();
+        let runs = executor.runs();
+        assert!(!runs[0].privileged, "id needs no root");
+        assert_eq!(
+            runs[1].argv(),
+            [
+                "useradd",
+                "--system",
+                "--no-create-home",
+                "--shell",
+                "/bin/false",
+                "-u",
+                "2001",
+                "kari-shop"
+            ]
+        );
+
+        executor.answer(&["id"], 0, "2001\n", "");
+        jail.provision_app_user("kari-shop", 2001).await.unwrap();
+        assert_eq!(executor.runs().len(), 3, "an existing user is left alone");
+
+        executor.answer(&["userdel"], 6, "", "user 'kari-shop' does not exist");
+        jail.deprovision_app_user("kari-shop").await.unwrap();
+        executor.answer(&["userdel"], 8, "", "user kari-shop is currently used");
+        let err = jail.deprovision_app_user("kari-shop").await.unwrap_err();
+        assert!(err.contains("currently used"), "{}", err);
+
+        executor.missing(&["chown"]);
+        let dir = tempfile::tempdir().unwrap();
+        let err = jail
+            .secure_directory(dir.path(), "kari-shop")
+            .await
+            .unwrap_err();
+        assert!(err.starts_with("Failed to spawn chown"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn audits_judge_find_output_against_the_app_user() {
+        let executor = Arc::new(FakeExecutor::default());
+        let jail = LinuxJailManager::with_executor(executor.clone());
+        executor.answer(&["id", "-u"], 0, "2001\n", "");
+        executor.answer(&["id", "-g"], 0, "2001\n", "");
+        executor.answer(
+            &["find"],
+            0,
+            "d 2001 2001 750 \0\
+             d 2001 2001 750 releases\0\
+             f 0 0 4755 releases/1/run me\0\
+             l 0 0 777 current\0\
+             f 2001 2001 640 shared/.env\0\
+             f 2001 33 220 shared/log\0",
+            "",
+        );
+        let audit = jail
+            .audit_directory(Path::new("/var/www/shop.example.com"), "kari-shop")
+            .await
+            .unwrap();
+        let find = &executor.runs()[2];
+        assert!(find.privileged);
+        assert_eq!(
+            find.argv()[..3],
+            ["find", "-P", "/var/www/shop.example.com"]
+        );
+        assert_eq!((audit.checked, audit.deviating), (5, 2));
+        let run_me = &audit.deviations[0];
+        assert_eq!(run_me.entry.path, "releases/1/run me");
+        assert_eq!(run_me.entry.mode, 0o4755);
+        assert_eq!(
+            run_me.problems,
+            [
+                PermissionProblem::Owner,
+                PermissionProblem::Group,
+                PermissionProblem::SpecialBits
+            ]
+        );
+        assert_eq!(
+            audit.deviations[1].problems,
+            [
+                PermissionProblem::Group,
+                PermissionProblem::Writable,
+                PermissionProblem::Unreadable
+            ]
+        );
+
+        executor.answer(&["id", "-u"], 1, "", "id: 'kari-gone': no such user");
+        let err = jail
+            .audit_directory(Path::new("/var/www/shop.example.com"), "kari-gone")
+            .await
+            .unwrap_err();
+        assert_eq!(err, "No such user kari-gone");
+    }
 }
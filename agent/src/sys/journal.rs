@@ -0,0 +1,162 @@
+// agent/src/sys/journal.rs
+//
+// 📰 An app's own output, read from the systemd journal: the kari-<domain>
+// unit writes stdout and stderr there, so the dashboard can show them without
+// a shell on the host.
+//
+// journalctl is asked for JSON, one entry per line, and only for the one unit;
+// the unit name comes from the app record, never from the caller.
+
+use async_trait::async_trait;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc;
+
+use crate::sys::privilege;
+use crate::sys::traits::{JournalEntry, JournalQuery, JournalReader};
+
+/// syslog's least severe level, debug: a query at it filters nothing out.
+pub const ALL_PRIORITIES: u8 = 7;
+
+fn journalctl_args(query: &JournalQuery) -> Vec<String> {
+    let mut args = vec![
+        format!("--unit={}", query.unit),
+        "--output=json".to_string(),
+        "--output-fields=MESSAGE,PRIORITY,_PID".to_string(),
+        "--no-pager".to_string(),
+        format!("--lines={}", query.lines),
+        format!("--priority=0..{}", query.max_priority.min(ALL_PRIORITIES)),
+    ];
+    if let Some(since) = query.since {
+        args.push(format!("--since=@{}", since));
+    }
+    if let Some(until) = query.until {
+        args.push(format!("--until=@{}", until));
+    }
+    if query.follow {
+        args.push("--follow".to_string());
+    }
+    args
+}
+
+/// Journal fields are strings, except that a MESSAGE which is not valid UTF-8
+/// comes as an array of bytes.
+fn field(entry: &serde_json::Value, name: &str) -> Option<String> {
+    match entry.get(name)? {
+        serde_json::Value::String(value) => Some(value.clone()),
+        serde_json::Value::Array(bytes) => {
+            let bytes: Vec<u8> = bytes
+                .iter()
+                .filter_map(|b| b.as_u64().and_then(|b| u8::try_from(b).ok()))
+                .collect();
+            Some(String::from_utf8_lossy(&bytes).into_owned())
+        }
+        _ => None,
+    }
+}
+
+/// One line of `journalctl --output=json`.
+pub fn parse_entry(line: &str) -> Option<JournalEntry> {
+    let entry: serde_json::Value = serde_json::from_str(line).ok()?;
+    Some(JournalEntry {
+        timestamp_us: field(&entry, "__REALTIME_TIMESTAMP")?.parse().ok()?,
+        priority: field(&entry, "PRIORITY")
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(6), // journald's default for stdout, info
+        message: field(&entry, "MESSAGE").unwrap_or_default(),
+        pid: field(&entry, "_PID")
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(0),
+    })
+}
+
+/// Reads the system journal through journalctl.
+pub struct JournalctlReader;
+
+#[async_trait]
+impl JournalReader for JournalctlReader {
+    async fn read(
+        &self,
+        query: &JournalQuery,
+        tx: mpsc::Sender<JournalEntry>,
+    ) -> Result<(), String> {
+        let mut child = privilege::command("journalctl")
+            .args(journalctl_args(query))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true) // 🛡️ A disconnected caller never leaves journalctl behind
+            .spawn()
+            .map_err(|e| format!("Failed to run journalctl: {}", e))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| "journalctl produced no stdout".to_string())?;
+
+        let mut lines = BufReader::new(stdout).lines();
+        loop {
+            tokio::select! {
+                _ = tx.closed() => return Ok(()),
+                line = lines.next_line() => match line {
+                    Ok(Some(line)) => {
+                        if let Some(entry) = parse_entry(&line)
+                            && tx.send(entry).await.is_err()
+                        {
+                            return Ok(());
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(e) => return Err(format!("Failed to read the journal: {}", e)),
+                },
+            }
+        }
+        let status = child
+            .wait()
+            .await
+            .map_err(|e| format!("Failed to wait for journalctl: {}", e))?;
+        if !status.success() {
+            return Err(format!("journalctl exited with {}", status));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_lines_become_entries() {
+        let line = r#"{"__REALTIME_TIMESTAMP":"1760500000123456","PRIORITY":"3","_PID":"4242","MESSAGE":"listening on :3000","__CURSOR":"s=1"}"#;
+        assert_eq!(
+            parse_entry(line),
+            Some(JournalEntry {
+                timestamp_us: 1_760_500_000_123_456,
+                priority: 3,
+                message: "listening on :3000".into(),
+                pid: 4242,
+            })
+        );
+        // Binary messages come as byte arrays
+        let binary = r#"{"__REALTIME_TIMESTAMP":"1","MESSAGE":[104,105,255]}"#;
+        let entry = parse_entry(binary).unwrap();
+        assert_eq!((entry.message.as_str(), entry.priority), ("hi\u{fffd}", 6));
+        assert_eq!(parse_entry("-- No entries --"), None);
+    }
+
+    #[test]
+    fn queries_map_onto_journalctl_flags() {
+        let args = journalctl_args(&JournalQuery {
+            unit: "kari-shop.example.com.service".into(),
+            since: Some(1_760_000_000),
+            until: None,
+            max_priority: 4,
+            lines: 100,
+            follow: true,
+        });
+        assert_eq!(args[0], "--unit=kari-shop.example.com.service");
+        assert!(args.contains(&"--priority=0..4".to_string()));
+        assert!(args.contains(&"--since=@1760000000".to_string()));
+        assert!(args.contains(&"--follow".to_string()));
+        assert!(!args.iter().any(|arg| arg.starts_with("--until")));
+    }
+}
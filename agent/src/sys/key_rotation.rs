@@ -0,0 +1,204 @@
+// agent/src/sys/key_rotation.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Retires certificate keys past their maximum age.
+//
+// Certificates are issued off-host and pushed in, and a renewal that reuses its
+// key keeps that key in service indefinitely. So every key InstallCertificate
+// writes is recorded by fingerprint with the time it first appeared
+// (NS_CERT_KEYS); a renewal with the same key keeps its age. Once a key is older
+// than the policy allows, its successor is generated on this host and staged
+// beside it, and a KeyRotationEvent carries the CSR to the control plane. That
+// gets it reissued (ACME takes a CSR) and pushes the certificate back with no
+// key, which promotes the staged one. The new key never leaves the host.
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::sys::state::{
+    CertKeyRecord, CertificateRecord, NS_CERT_KEYS, NS_CERTIFICATES, get_record, list_records,
+    put_record,
+};
+use crate::sys::traits::{SslEngine, StateStore};
+
+/// A request the control plane did not answer with a certificate is repeated
+/// (with a fresh key) after this long.
+const REQUEST_RETRY_SECS: i64 = 24 * 60 * 60;
+
+/// The longest maximum age honoured: ten years. KARI_KEY_MAX_AGE_DAYS past it
+/// is clamped rather than overflowing into an age every key is older than.
+pub const MAX_KEY_AGE_DAYS: u64 = 3650;
+
+/// KARI_KEY_MAX_AGE_DAYS in seconds, at most MAX_KEY_AGE_DAYS.
+pub fn max_key_age_secs(days: u64) -> u64 {
+    let ceiling = MAX_KEY_AGE_DAYS * 86_400;
+    days.checked_mul(86_400)
+        .map_or(ceiling, |secs| secs.min(ceiling))
+}
+
+/// A key that needs a new certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyRotationEvent {
+    pub domain_name: String,
+    pub key_created_at: i64, // Unix seconds, of the key being retired
+    pub csr_pem: String,     // For the staged successor; empty when staging failed
+    pub detail: String,
+    pub at: i64, // Unix seconds
+}
+
+/// Records the key InstallCertificate just wrote. Returns whether it is a new
+/// key (a renewal that kept the old key keeps its age).
+pub async fn record_installed_key(
+    state: &dyn StateStore,
+    ssl: &dyn SslEngine,
+    domain_name: &str,
+    rotation_opt_out: bool,
+) -> Result<bool, String> {
+    let Some(fingerprint) = ssl.key_fingerprint(domain_name).await? else {
+        return Ok(false);
+    };
+    let previous: Option<CertKeyRecord> = get_record(state, NS_CERT_KEYS, domain_name).await?;
+    let (record, rotated) = match previous {
+        Some(mut record) if record.key_fingerprint == fingerprint => {
+            record.rotation_opt_out = rotation_opt_out;
+            (record, false)
+        }
+        _ => (
+            CertKeyRecord {
+                domain_name: domain_name.to_string(),
+                key_fingerprint: fingerprint,
+                key_created_at: chrono::Utc::now().timestamp(),
+                rotation_opt_out,
+                rotation_requested_at: None,
+            },
+            true,
+        ),
+    };
+    put_record(state, NS_CERT_KEYS, domain_name, &record).await?;
+    Ok(rotated)
+}
+
+#[derive(Clone)]
+pub struct KeyRotation {
+    pub state: Arc<dyn StateStore>,
+    pub ssl: Arc<dyn SslEngine>,
+    pub max_key_age: Duration,
+}
+
+impl KeyRotation {
+    /// Checks every `interval` for as long as the agent runs.
+    pub async fn run(self, interval: Duration, events: broadcast::Sender<KeyRotationEvent>) {
+        let mut ticker = tokio::time::interval(interval.max(Duration::from_millis(10)));
+        loop {
+            ticker.tick().await;
+            match self.stage_aged_keys().await {
+                Ok(due) => {
+                    for event in due {
+                        let _ = events.send(event);
+                    }
+                }
+                Err(e) => warn!("🔄 Key rotation check failed: {}", e),
+            }
+        }
+    }
+
+    /// Stages a successor for every key past its maximum age, unless the
+    /// domain opted out or a request for it is still outstanding.
+    pub async fn stage_aged_keys(&self) -> Result<Vec<KeyRotationEvent>, String> {
+        let now = chrono::Utc::now().timestamp();
+        let max_age = i64::try_from(self.max_key_age.as_secs()).unwrap_or(i64::MAX);
+        let records: Vec<CertKeyRecord> = list_records(self.state.as_ref(), NS_CERT_KEYS).await?;
+        let mut due = Vec::new();
+        for mut record in records {
+            let outstanding = record
+                .rotation_requested_at
+                .is_some_and(|at| now - at < REQUEST_RETRY_SECS);
+            if record.rotation_opt_out || outstanding || now - record.key_created_at < max_age {
+                continue;
+            }
+
+            let age_days = (now - record.key_created_at) / 86_400;
+            // 🌐 The successor covers every domain the certificate does
+            let covered: Option<CertificateRecord> =
+                get_record(self.state.as_ref(), NS_CERTIFICATES, &record.domain_name).await?;
+            let san_domains = covered.map(|c| c.domains).unwrap_or_default();
+            let (csr_pem, detail) = match self
+                .ssl
+                .stage_next_key(&record.domain_name, &san_domains)
+                .await
+            {
+                Ok(csr) => {
+                    record.rotation_requested_at = Some(now);
+                    put_record(
+                        self.state.as_ref(),
+                        NS_CERT_KEYS,
+                        &record.domain_name,
+                        &record,
+                    )
+                    .await?;
+                    info!(
+                        "🔄 Key for {} is {} days old; successor staged",
+                        record.domain_name, age_days
+                    );
+                    (
+                        csr,
+                        format!(
+                            "key is {} days old; reissue for the CSR and install with no key",
+                            age_days
+                        ),
+                    )
+                }
+                Err(e) => {
+                    warn!("🔄 Could not stage a key for {}: {}", record.domain_name, e);
+                    (
+                        String::new(),
+                        format!("key is {} days old; staging failed: {}", age_days, e),
+                    )
+                }
+            };
+            due.push(KeyRotationEvent {
+                domain_name: record.domain_name,
+                key_created_at: record.key_created_at,
+                csr_pem,
+                detail,
+                at: now,
+            });
+        }
+        Ok(due)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sys::state::JsonStateStore;
+    use crate::testkit::FakeSslEngine;
+
+    #[tokio::test]
+    async fn huge_maximum_ages_never_make_every_key_due() {
+        assert_eq!(max_key_age_secs(90), 90 * 86_400);
+        assert_eq!(max_key_age_secs(u64::MAX), MAX_KEY_AGE_DAYS * 86_400);
+        assert_eq!(max_key_age_secs(u64::MAX / 1000), MAX_KEY_AGE_DAYS * 86_400);
+
+        let dir = tempfile::tempdir().unwrap();
+        let state = Arc::new(JsonStateStore::open(dir.path().to_path_buf()).unwrap());
+        let record = CertKeyRecord {
+            domain_name: "shop.example.com".into(),
+            key_fingerprint: "ab".into(),
+            key_created_at: chrono::Utc::now().timestamp() - 86_400,
+            rotation_opt_out: false,
+            rotation_requested_at: None,
+        };
+        put_record(state.as_ref(), NS_CERT_KEYS, &record.domain_name, &record)
+            .await
+            .unwrap();
+        // Past i64::MAX seconds, as a Duration built straight from the config could be
+        let rotation = KeyRotation {
+            state,
+            ssl: Arc::new(FakeSslEngine::default()),
+            max_key_age: Duration::from_secs(u64::MAX),
+        };
+        assert!(rotation.stage_aged_keys().await.unwrap().is_empty());
+    }
+}
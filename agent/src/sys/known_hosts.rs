@@ -0,0 +1,173 @@
+// agent/src/sys/known_hosts.rs
+//
+// 🛡️ SOLID: Single-Responsibility — The agent's own known_hosts file.
+//
+// SSH clones run with StrictHostKeyChecking=yes against this file alone, so a
+// host key is trusted only once the control plane has pinned it (from GitHub's
+// meta API, GitLab's documented fingerprints, or a self-hosted server's admin).
+// Nothing is learned on first connection, which is what closes the MITM window
+// `accept-new` left open.
+
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// Key types OpenSSH accepts as host keys (DSA is long gone).
+const HOST_KEY_TYPES: &[&str] = &[
+    "ssh-ed25519",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "ssh-rsa",
+    "sk-ssh-ed25519@openssh.com",
+    "sk-ecdsa-sha2-nistp256@openssh.com",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostKey {
+    pub key_type: String,
+    pub blob: Vec<u8>,
+}
+
+impl HostKey {
+    /// As `ssh-keygen -l` prints it: `SHA256:` and unpadded base64.
+    pub fn fingerprint(&self) -> String {
+        format!(
+            "SHA256:{}",
+            STANDARD_NO_PAD.encode(Sha256::digest(&self.blob))
+        )
+    }
+
+    fn to_line(&self, pattern: &str) -> String {
+        format!(
+            "{} {} {}\n",
+            pattern,
+            self.key_type,
+            STANDARD.encode(&self.blob)
+        )
+    }
+}
+
+/// Parses `type base64 [comment]` (an `ssh-keyscan` line minus the host, or a
+/// `.pub` file). The type must be a host key type and match the one encoded in
+/// the blob, so a truncated or mislabelled key is refused rather than pinned.
+pub fn parse_host_key(line: &str) -> Result<HostKey, String> {
+    let mut fields = line.split_whitespace();
+    let (Some(key_type), Some(encoded)) = (fields.next(), fields.next()) else {
+        return Err(format!("'{}' is not a 'type base64' public key", line));
+    };
+    if !HOST_KEY_TYPES.contains(&key_type) {
+        return Err(format!("'{}' is not an SSH public key type", key_type));
+    }
+    let blob = STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("{} key is not valid base64: {}", key_type, e))?;
+    // The blob opens with its own type as a length-prefixed string
+    let embedded = blob
+        .get(..4)
+        .map(|len| u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize)
+        .and_then(|len| blob.get(4..4 + len));
+    if embedded != Some(key_type.as_bytes()) {
+        return Err(format!("Key data does not hold a {} key", key_type));
+    }
+    Ok(HostKey {
+        key_type: key_type.to_string(),
+        blob,
+    })
+}
+
+/// The known_hosts name for a host: bare on port 22, `[host]:port` otherwise.
+pub fn host_pattern(host: &str, port: u16) -> String {
+    if port == 22 {
+        host.to_string()
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
+/// Replaces every key pinned for `pattern` with `keys`; no keys unpins it.
+/// Entries for other hosts are kept as they are.
+pub fn pin(path: &Path, pattern: &str, keys: &[HostKey]) -> Result<(), String> {
+    let existing = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let mut content: String = existing
+        .lines()
+        .filter(|line| {
+            let hosts = line.split_whitespace().next().unwrap_or("");
+            !hosts.split(',').any(|host| host == pattern)
+        })
+        .map(|line| format!("{}\n", line))
+        .collect();
+    for key in keys {
+        content.push_str(&key.to_line(pattern));
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    // Write-then-rename, so a concurrent clone never reads half a file
+    let tmp_path = path.with_extension("kari-tmp");
+    std::fs::write(&tmp_path, content)
+        .and_then(|_| std::fs::rename(&tmp_path, path))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // GitHub's published ed25519 host key
+    const GITHUB_ED25519: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl";
+
+    #[test]
+    fn host_keys_are_checked_before_they_are_pinned() {
+        let key = parse_host_key(&format!("{} github.com", GITHUB_ED25519)).unwrap();
+        assert_eq!(
+            key.fingerprint(),
+            "SHA256:+DiY3wvvV6TuJJhbpZisF/zLDA0zPMSvHdkr4UvCOqU"
+        );
+
+        let blob = GITHUB_ED25519.split_whitespace().nth(1).unwrap();
+        assert!(parse_host_key(&format!("ssh-rsa {}", blob)).is_err());
+        assert!(parse_host_key(&format!("ssh-dss {}", blob)).is_err());
+        assert!(parse_host_key("ssh-ed25519 not-base64!").is_err());
+        assert!(parse_host_key("ssh-ed25519").is_err());
+    }
+
+    #[test]
+    fn pinning_replaces_only_that_hosts_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ssh_known_hosts");
+        let key = parse_host_key(GITHUB_ED25519).unwrap();
+
+        std::fs::write(
+            &path,
+            "github.com,140.82.112.3 ssh-rsa AAAA\ngit.example.com ssh-rsa BBBB\n",
+        )
+        .unwrap();
+        pin(&path, "github.com", std::slice::from_ref(&key)).unwrap();
+        pin(&path, &host_pattern("git.internal", 2222), &[key]).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            content.lines().collect::<Vec<_>>(),
+            [
+                "git.example.com ssh-rsa BBBB",
+                &format!("github.com {}", GITHUB_ED25519),
+                &format!("[git.internal]:2222 {}", GITHUB_ED25519),
+            ]
+        );
+
+        pin(&path, "[git.internal]:2222", &[]).unwrap();
+        assert!(
+            !std::fs::read_to_string(&path)
+                .unwrap()
+                .contains("git.internal")
+        );
+    }
+}
@@ -0,0 +1,485 @@
+// agent/src/sys/log_forward.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Ships every app's journal to a central log store.
+//
+// Opt-in (KARI_LOG_FORWARD_URL): an https:// URL is a Grafana Loki push
+// endpoint, syslog+tls://host[:port] a syslog receiver speaking RFC 5425
+// (RFC 5424 messages, octet-counted, over TLS). Plain-text transports are
+// refused; app output routinely carries customer data.
+//
+// One journal tail per app unit feeds a single bounded queue. Lines leave in
+// batches, once `batch_lines` have gathered or the oldest has waited the batch
+// window. While a batch is being retried nothing else is read, so a slow or
+// unreachable endpoint backs up into the journal (which keeps the entries)
+// instead of into agent memory. What each app has shipped is recorded under
+// NS_LOG_FORWARD, so an agent restart resumes where it stopped.
+
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use crate::sys::journal::ALL_PRIORITIES;
+use crate::sys::notify::https_post;
+use crate::sys::state::{AppRecord, NS_APPS, get_record, list_records, put_record};
+use crate::sys::traits::{JournalEntry, JournalQuery, JournalReader, LogShipper, StateStore};
+
+/// Namespace of `ForwardCursor`s, keyed by app_id.
+pub const NS_LOG_FORWARD: &str = "log_forward";
+/// RFC 5425's registered port.
+pub const SYSLOG_TLS_PORT: u16 = 6514;
+/// A batch the endpoint keeps refusing is dropped after this many attempts,
+/// rather than holding every app's logs back behind it.
+const SHIP_ATTEMPTS: u32 = 10;
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogTarget {
+    Loki { url: String },
+    Syslog { host: String, port: u16 },
+}
+
+impl LogTarget {
+    pub fn parse(url: &str) -> Result<Self, String> {
+        if url.starts_with("https://") {
+            return Ok(LogTarget::Loki {
+                url: url.to_string(),
+            });
+        }
+        let Some(authority) = url.strip_prefix("syslog+tls://") else {
+            return Err("must be https:// (Loki) or syslog+tls://host[:port]".to_string());
+        };
+        let authority = authority.trim_end_matches('/');
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(bracketed) => bracketed
+                .split_once(']')
+                .map(|(host, rest)| (host, rest.strip_prefix(':')))
+                .ok_or_else(|| format!("invalid syslog host '{}'", authority))?,
+            None => match authority.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            },
+        };
+        let port = match port {
+            Some(port) => port
+                .parse()
+                .map_err(|_| format!("invalid syslog port '{}'", port))?,
+            None => SYSLOG_TLS_PORT,
+        };
+        let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':');
+        if host.is_empty() || !host.chars().all(valid) {
+            return Err(format!("invalid syslog host '{}'", host));
+        }
+        Ok(LogTarget::Syslog {
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+/// One journal entry on its way out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwardedLine {
+    pub app_id: String,
+    pub tenant_id: Option<String>,
+    pub entry: JournalEntry,
+}
+
+/// The newest entry of an app that reached the endpoint.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForwardCursor {
+    pub timestamp_us: u64,
+}
+
+/// Grafana's level names for syslog priorities.
+fn level(priority: u8) -> &'static str {
+    match priority {
+        0..=2 => "critical",
+        3 => "error",
+        4 => "warning",
+        5 | 6 => "info",
+        _ => "debug",
+    }
+}
+
+/// The batch as `target` expects it: a Loki push body with one stream per app
+/// and level, or RFC 5425 frames of RFC 5424 messages (facility user).
+pub fn encode(target: &LogTarget, host: &str, batch: &[ForwardedLine]) -> Vec<u8> {
+    match target {
+        LogTarget::Loki { .. } => {
+            // (app, level) → (tenant, [timestamp in ns, line])
+            type Streams<'a> = BTreeMap<(&'a str, &'a str), (Option<&'a str>, Vec<[String; 2]>)>;
+            let mut streams = Streams::new();
+            for line in batch {
+                let stream = streams
+                    .entry((&line.app_id, level(line.entry.priority)))
+                    .or_insert_with(|| (line.tenant_id.as_deref(), Vec::new()));
+                stream.1.push([
+                    (line.entry.timestamp_us * 1000).to_string(),
+                    line.entry.message.clone(),
+                ]);
+            }
+            let streams: Vec<serde_json::Value> = streams
+                .into_iter()
+                .map(|((app, level), (tenant, values))| {
+                    let mut labels = serde_json::json!({
+                        "job": "kari",
+                        "host": host,
+                        "app": app,
+                        "level": level,
+                    });
+                    if let Some(tenant) = tenant {
+                        labels["tenant"] = tenant.into();
+                    }
+                    serde_json::json!({ "stream": labels, "values": values })
+                })
+                .collect();
+            serde_json::to_vec(&serde_json::json!({ "streams": streams })).unwrap_or_default()
+        }
+        LogTarget::Syslog { .. } => {
+            let host = if host.is_empty() { "-" } else { host };
+            let mut out = Vec::new();
+            for line in batch {
+                let timestamp = DateTime::from_timestamp_micros(line.entry.timestamp_us as i64)
+                    .map_or("-".to_string(), |t| {
+                        t.to_rfc3339_opts(SecondsFormat::Micros, true)
+                    });
+                let app: String = line.app_id.chars().take(48).collect();
+                let pid = match line.entry.pid {
+                    0 => "-".to_string(),
+                    pid => pid.to_string(),
+                };
+                let message = format!(
+                    "<{}>1 {} {} {} {} - - {}",
+                    8 + u32::from(line.entry.priority.min(ALL_PRIORITIES)),
+                    timestamp,
+                    host,
+                    app,
+                    pid,
+                    line.entry.message.trim_end_matches('\n')
+                );
+                out.extend_from_slice(format!("{} ", message.len()).as_bytes());
+                out.extend_from_slice(message.as_bytes());
+            }
+            out
+        }
+    }
+}
+
+pub struct LogForwarder {
+    pub state: Arc<dyn StateStore>,
+    pub journal: Arc<dyn JournalReader>,
+    pub shipper: Arc<dyn LogShipper>,
+    pub target: LogTarget,
+    pub host: String,
+    pub batch_lines: usize,
+    pub batch_window: Duration,
+}
+
+impl LogForwarder {
+    /// Picks up new and deleted apps every `poll`, and ships until the agent stops.
+    pub async fn run(self, poll: Duration) {
+        let batch_lines = self.batch_lines.max(1);
+        let (tx, mut rx) = mpsc::channel(batch_lines * 2);
+        let mut tails: HashMap<String, JoinHandle<()>> = HashMap::new();
+        let mut ticker = tokio::time::interval(poll.max(Duration::from_millis(10)));
+        let mut batch = Vec::new();
+        let mut deadline = None;
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => self.reconcile(&mut tails, &tx).await,
+                Some(line) = rx.recv() => {
+                    batch.push(line);
+                    deadline.get_or_insert(Instant::now() + self.batch_window);
+                    if batch.len() >= batch_lines {
+                        self.flush(&mut batch).await;
+                        deadline = None;
+                    }
+                }
+                _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    self.flush(&mut batch).await;
+                    deadline = None;
+                }
+            }
+        }
+    }
+
+    /// One tail per app unit: started for new apps, restarted if journalctl
+    /// ended, stopped for deleted apps.
+    async fn reconcile(
+        &self,
+        tails: &mut HashMap<String, JoinHandle<()>>,
+        tx: &mpsc::Sender<ForwardedLine>,
+    ) {
+        let apps: Vec<AppRecord> = match list_records(self.state.as_ref(), NS_APPS).await {
+            Ok(apps) => apps,
+            Err(e) => {
+                warn!("📤 Log forwarding could not list apps: {}", e);
+                return;
+            }
+        };
+        tails.retain(|app_id, tail| {
+            let keep = apps.iter().any(|app| &app.app_id == app_id) && !tail.is_finished();
+            if !keep {
+                tail.abort();
+            }
+            keep
+        });
+        for app in apps {
+            if tails.contains_key(&app.app_id) {
+                continue;
+            }
+            let after =
+                get_record::<ForwardCursor>(self.state.as_ref(), NS_LOG_FORWARD, &app.app_id)
+                    .await
+                    .ok()
+                    .flatten();
+            let tail = tail(self.journal.clone(), app.clone(), after, tx.clone());
+            tails.insert(app.app_id, tokio::spawn(tail));
+        }
+    }
+
+    /// Ships the batch, backing off between attempts, then records how far each
+    /// app got. Empties `batch` either way.
+    async fn flush(&self, batch: &mut Vec<ForwardedLine>) {
+        if batch.is_empty() {
+            return;
+        }
+        let payload = encode(&self.target, &self.host, batch);
+        let mut backoff = self.batch_window.max(Duration::from_millis(10));
+        let mut attempt = 1;
+        loop {
+            match self.shipper.ship(&self.target, &payload).await {
+                Ok(()) => {
+                    debug!("📤 Shipped {} log lines", batch.len());
+                    break;
+                }
+                Err(e) if attempt >= SHIP_ATTEMPTS => {
+                    warn!(
+                        "📤 Dropped {} log lines after {} attempts: {}",
+                        batch.len(),
+                        attempt,
+                        e
+                    );
+                    break;
+                }
+                Err(e) => {
+                    warn!("📤 Log shipping failed (attempt {}): {}", attempt, e);
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    attempt += 1;
+                }
+            }
+        }
+        let mut newest: BTreeMap<&str, u64> = BTreeMap::new();
+        for line in batch.iter() {
+            let at = newest.entry(&line.app_id).or_default();
+            *at = (*at).max(line.entry.timestamp_us);
+        }
+        for (app_id, timestamp_us) in newest {
+            let cursor = ForwardCursor { timestamp_us };
+            if let Err(e) = put_record(self.state.as_ref(), NS_LOG_FORWARD, app_id, &cursor).await {
+                warn!("📤 Log forwarding cursor for {} not saved: {}", app_id, e);
+            }
+        }
+        batch.clear();
+    }
+}
+
+/// Follows one app's unit from just after `after` (or from now, the first
+/// time), sending each entry into `tx`. Waits while `tx` is full.
+async fn tail(
+    journal: Arc<dyn JournalReader>,
+    app: AppRecord,
+    after: Option<ForwardCursor>,
+    tx: mpsc::Sender<ForwardedLine>,
+) {
+    let after_us = after.map_or(0, |cursor| cursor.timestamp_us);
+    let query = JournalQuery {
+        unit: format!("{}.service", app.service_name),
+        since: Some(match after {
+            Some(cursor) => (cursor.timestamp_us / 1_000_000) as i64,
+            None => chrono::Utc::now().timestamp(),
+        }),
+        until: None,
+        max_priority: ALL_PRIORITIES,
+        lines: u32::MAX,
+        follow: true,
+    };
+    let (entry_tx, mut entry_rx) = mpsc::channel(64);
+    let read = journal.read(&query, entry_tx);
+    tokio::pin!(read);
+    let mut outcome = None;
+    loop {
+        tokio::select! {
+            result = &mut read, if outcome.is_none() => outcome = Some(result),
+            entry = entry_rx.recv() => match entry {
+                // --since has second precision; what was shipped is skipped here
+                Some(entry) if entry.timestamp_us <= after_us => {}
+                Some(entry) => {
+                    let line = ForwardedLine {
+                        app_id: app.app_id.clone(),
+                        tenant_id: app.tenant_id.clone(),
+                        entry,
+                    };
+                    if tx.send(line).await.is_err() {
+                        return;
+                    }
+                }
+                None => break,
+            },
+        }
+    }
+    let outcome = match outcome {
+        Some(outcome) => outcome,
+        None => read.await,
+    };
+    if let Err(e) = outcome {
+        warn!("📤 Journal tail of {} ended: {}", app.app_id, e);
+    }
+}
+
+/// Ships with the host's tools: curl for Loki (see `https_post`), and one
+/// `openssl s_client` connection per batch for syslog, so the system trust
+/// store verifies the receiver either way.
+pub struct HostLogShipper {
+    pub timeout: Duration,
+    /// Extra HTTP headers for Loki, one per line (e.g. Authorization,
+    /// X-Scope-OrgID); curl reads them itself, so no credential is on a command line.
+    pub headers_file: Option<PathBuf>,
+}
+
+#[async_trait]
+impl LogShipper for HostLogShipper {
+    async fn ship(&self, target: &LogTarget, payload: &[u8]) -> Result<(), String> {
+        match target {
+            LogTarget::Loki { url } => {
+                let headers: Vec<String> = self
+                    .headers_file
+                    .iter()
+                    .map(|path| format!("@{}", path.display()))
+                    .collect();
+                https_post(url, payload, &headers, self.timeout).await
+            }
+            LogTarget::Syslog { host, port } => {
+                let mut child = Command::new("openssl")
+                    .args(["s_client", "-quiet", "-no_ign_eof", "-verify_return_error"])
+                    .arg("-connect")
+                    .arg(if host.contains(':') {
+                        format!("[{}]:{}", host, port)
+                    } else {
+                        format!("{}:{}", host, port)
+                    })
+                    .arg("-servername")
+                    .arg(host)
+                    .arg("-verify_hostname")
+                    .arg(host)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::piped())
+                    .kill_on_drop(true)
+                    .spawn()
+                    .map_err(|e| format!("Failed to run openssl: {}", e))?;
+                let mut stdin = child
+                    .stdin
+                    .take()
+                    .ok_or_else(|| "openssl has no stdin".to_string())?;
+                let delivered = async {
+                    stdin
+                        .write_all(payload)
+                        .await
+                        .map_err(|e| format!("Failed to feed openssl: {}", e))?;
+                    drop(stdin);
+                    child
+                        .wait_with_output()
+                        .await
+                        .map_err(|e| format!("Failed to run openssl: {}", e))
+                };
+                let output = tokio::time::timeout(self.timeout, delivered)
+                    .await
+                    .map_err(|_| format!("syslog delivery to {} timed out", host))??;
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    return Err(format!(
+                        "syslog delivery to {} failed: {}",
+                        host,
+                        stderr.lines().last().unwrap_or("").trim()
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_are_loki_or_syslog_over_tls() {
+        assert_eq!(
+            LogTarget::parse("https://logs.example.com/loki/api/v1/push"),
+            Ok(LogTarget::Loki {
+                url: "https://logs.example.com/loki/api/v1/push".into()
+            })
+        );
+        assert_eq!(
+            LogTarget::parse("syslog+tls://logs.example.com"),
+            Ok(LogTarget::Syslog {
+                host: "logs.example.com".into(),
+                port: SYSLOG_TLS_PORT
+            })
+        );
+        assert_eq!(
+            LogTarget::parse("syslog+tls://[2001:db8::1]:10514"),
+            Ok(LogTarget::Syslog {
+                host: "2001:db8::1".into(),
+                port: 10514
+            })
+        );
+        for refused in [
+            "http://logs.example.com/loki/api/v1/push",
+            "syslog://logs.example.com:514",
+            "syslog+tls://logs.example.com:tls",
+            "syslog+tls://logs example.com",
+        ] {
+            assert!(LogTarget::parse(refused).is_err(), "{}", refused);
+        }
+    }
+
+    #[test]
+    fn syslog_batches_are_octet_counted_rfc5424() {
+        let line = |priority, message: &str| ForwardedLine {
+            app_id: "shop".into(),
+            tenant_id: None,
+            entry: JournalEntry {
+                timestamp_us: 1_760_500_000_123_456,
+                priority,
+                message: message.into(),
+                pid: 4242,
+            },
+        };
+        let target = LogTarget::Syslog {
+            host: "logs.example.com".into(),
+            port: SYSLOG_TLS_PORT,
+        };
+        let out = encode(&target, "web-1", &[line(3, "db down\n"), line(6, "ok")]);
+        let first = "<11>1 2025-10-15T03:46:40.123456Z web-1 shop 4242 - - db down";
+        let second = "<14>1 2025-10-15T03:46:40.123456Z web-1 shop 4242 - - ok";
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{} {}{} {}", first.len(), first, second.len(), second)
+        );
+    }
+}
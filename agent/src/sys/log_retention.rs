@@ -0,0 +1,297 @@
+// agent/src/sys/log_retention.rs
+//
+// 🗑️ How long each class of log is kept, and erasure on request.
+//
+// Three classes, each a directory of per-domain files: deploy logs (kari's own,
+// under state_dir/build-logs/<domain>/), audit trails and proxy access logs
+// (wherever the operator points them). A domain's files are a `<domain>/`
+// subdirectory or `<domain>.log` and its rotations (`.1`, `-20260101.gz`, ...).
+//
+// A daily kari timer (`ENFORCE_FLAG`) removes files older than their class's
+// retention. Sensitive classes are overwritten with zeros and synced before
+// they are unlinked; on copy-on-write filesystems and SSDs that is best effort,
+// the old blocks may survive until they are reused.
+//
+// The systemd journal is not touched: it cannot be purged per unit, and its
+// own MaxRetentionSec= applies.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Runs `enforce` and exits (kari-job-log-retention).
+pub const ENFORCE_FLAG: &str = "--enforce-log-retention";
+pub const RETENTION_JOB: &str = "log-retention";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogClass {
+    Deploy,
+    Audit,
+    Access,
+}
+
+impl LogClass {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Deploy => "deploy",
+            Self::Audit => "audit",
+            Self::Access => "access",
+        }
+    }
+
+    /// 🛡️ Build output can echo secrets and audit trails name people: both are
+    /// erased, not just unlinked. Access logs are bulk and merely unlinked.
+    pub fn sensitive(self) -> bool {
+        matches!(self, Self::Deploy | Self::Audit)
+    }
+}
+
+/// One class: where it lives and how long it is kept (0 keeps it forever).
+#[derive(Debug, Clone)]
+pub struct ClassPolicy {
+    pub class: LogClass,
+    pub dir: PathBuf,
+    pub retention_days: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    pub classes: Vec<ClassPolicy>,
+}
+
+impl RetentionPolicy {
+    /// Whether any class expires at all, i.e. whether the timer has work.
+    pub fn enabled(&self) -> bool {
+        self.classes.iter().any(|policy| policy.retention_days > 0)
+    }
+}
+
+/// What was removed from one class.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassPurge {
+    pub class: LogClass,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Removes every file older than its class's retention. Emptied per-domain
+/// directories go too.
+pub fn enforce(policy: &RetentionPolicy, now: SystemTime) -> Result<Vec<ClassPurge>, String> {
+    let mut purged = Vec::new();
+    for class in &policy.classes {
+        let mut tally = ClassPurge {
+            class: class.class,
+            files: 0,
+            bytes: 0,
+        };
+        if class.retention_days > 0 && class.dir.is_dir() {
+            let max_age = Duration::from_secs(u64::from(class.retention_days) * 86_400);
+            let cutoff = now.checked_sub(max_age).unwrap_or(SystemTime::UNIX_EPOCH);
+            let expired = |meta: &fs::Metadata| meta.modified().is_ok_and(|at| at < cutoff);
+            sweep(&class.dir, class.class, &expired, &mut tally)?;
+        }
+        purged.push(tally);
+    }
+    Ok(purged)
+}
+
+/// Removes the files under `dir` that are `expired`, and the subdirectories
+/// that leaves empty.
+fn sweep(
+    dir: &Path,
+    class: LogClass,
+    expired: &dyn Fn(&fs::Metadata) -> bool,
+    tally: &mut ClassPurge,
+) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read {:?}: {}", dir, e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(meta) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if meta.is_dir() {
+            sweep(&path, class, expired, tally)?;
+            // Only succeeds once nothing is left in it
+            let _ = fs::remove_dir(&path);
+        } else if meta.is_file() && expired(&meta) {
+            remove(&path, class, meta.len(), tally)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether `name` in a class directory belongs to `domain`.
+fn belongs_to(name: &str, domain: &str) -> bool {
+    name == domain
+        || name
+            .strip_prefix(domain)
+            .and_then(|rest| rest.strip_prefix(".log"))
+            .is_some_and(|rotation| {
+                rotation.is_empty() || rotation.starts_with('.') || rotation.starts_with('-')
+            })
+}
+
+/// 🛡️ Erasure: removes everything any class holds for `domains`, whatever its
+/// age. Domains must already be validated; they are used as file names.
+pub fn purge_domains(
+    policy: &RetentionPolicy,
+    domains: &[String],
+) -> Result<Vec<ClassPurge>, String> {
+    let mut purged = Vec::new();
+    for class in &policy.classes {
+        let mut tally = ClassPurge {
+            class: class.class,
+            files: 0,
+            bytes: 0,
+        };
+        let entries = match fs::read_dir(&class.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                purged.push(tally);
+                continue;
+            }
+            Err(e) => return Err(format!("Failed to read {:?}: {}", class.dir, e)),
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            if !domains.iter().any(|domain| belongs_to(name, domain)) {
+                continue;
+            }
+            let path = entry.path();
+            let meta = fs::symlink_metadata(&path)
+                .map_err(|e| format!("Failed to inspect {:?}: {}", path, e))?;
+            if meta.is_dir() {
+                sweep(&path, class.class, &|_| true, &mut tally)?;
+                fs::remove_dir_all(&path)
+                    .map_err(|e| format!("Failed to remove {:?}: {}", path, e))?;
+            } else {
+                remove(
+                    &path,
+                    class.class,
+                    if meta.is_file() { meta.len() } else { 0 },
+                    &mut tally,
+                )?;
+            }
+        }
+        purged.push(tally);
+    }
+    Ok(purged)
+}
+
+/// Unlinks one file, erasing it first when its class is sensitive. Symlinks
+/// are unlinked, never written through.
+fn remove(path: &Path, class: LogClass, len: u64, tally: &mut ClassPurge) -> Result<(), String> {
+    if class.sensitive() && fs::symlink_metadata(path).is_ok_and(|meta| meta.is_file()) {
+        let file = OpenOptions::new()
+            .write(true)
+            .custom_flags(nix::libc::O_NOFOLLOW)
+            .open(path)
+            .map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+        overwrite(file, len).map_err(|e| format!("Failed to erase {:?}: {}", path, e))?;
+    }
+    fs::remove_file(path).map_err(|e| format!("Failed to remove {:?}: {}", path, e))?;
+    tally.files += 1;
+    tally.bytes += len;
+    Ok(())
+}
+
+fn overwrite(mut file: File, len: u64) -> std::io::Result<()> {
+    let zeros = [0u8; 64 * 1024];
+    let mut left = len;
+    while left > 0 {
+        let n = left.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..n])?;
+        left -= n as u64;
+    }
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(root: &Path, days: u32) -> RetentionPolicy {
+        RetentionPolicy {
+            classes: [LogClass::Deploy, LogClass::Audit, LogClass::Access]
+                .into_iter()
+                .map(|class| ClassPolicy {
+                    class,
+                    dir: root.join(class.name()),
+                    retention_days: days,
+                })
+                .collect(),
+        }
+    }
+
+    fn write(path: &Path, data: &[u8], age_days: u64) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, data).unwrap();
+        let modified = SystemTime::now() - Duration::from_secs(age_days * 86_400);
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    #[test]
+    fn retention_removes_only_what_has_aged_out() {
+        let root = tempfile::tempdir().unwrap();
+        let old = root.path().join("deploy/shop.example/20250101.log.gz");
+        let fresh = root.path().join("deploy/shop.example/20261001.log.gz");
+        write(&old, b"npm ERR! token=abc", 40);
+        write(&fresh, b"ok", 1);
+        write(&root.path().join("access/shop.example.log.1"), b"GET /", 40);
+
+        // Off unless a class has a retention
+        assert!(!policy(root.path(), 0).enabled());
+        let untouched = enforce(&policy(root.path(), 0), SystemTime::now()).unwrap();
+        assert!(untouched.iter().all(|purge| purge.files == 0));
+
+        let purged = enforce(&policy(root.path(), 30), SystemTime::now()).unwrap();
+        assert_eq!(
+            purged[0],
+            ClassPurge {
+                class: LogClass::Deploy,
+                files: 1,
+                bytes: 18
+            }
+        );
+        assert_eq!((purged[1].files, purged[2].files), (0, 1));
+        assert!(!old.exists() && fresh.exists());
+    }
+
+    #[test]
+    fn purges_take_every_file_of_the_domains_and_nothing_else() {
+        let root = tempfile::tempdir().unwrap();
+        let outside = root.path().join("outside.log");
+        write(&outside, b"keep", 0);
+        for path in [
+            "deploy/shop.example/20261001.log.gz",
+            "audit/shop.example.log",
+            "access/shop.example.log-20261014.gz",
+            "access/shop.example.log.2",
+        ] {
+            write(&root.path().join(path), b"x", 0);
+        }
+        for kept in ["access/shop.example.com.log", "access/blog.example.log"] {
+            write(&root.path().join(kept), b"x", 0);
+        }
+        std::os::unix::fs::symlink(&outside, root.path().join("audit/shop.example")).unwrap();
+
+        let purged = purge_domains(&policy(root.path(), 0), &["shop.example".to_string()]).unwrap();
+        let files: Vec<u64> = purged.iter().map(|purge| purge.files).collect();
+        assert_eq!(files, [1, 2, 2]);
+        assert!(!root.path().join("deploy/shop.example").exists());
+        assert!(root.path().join("access/shop.example.com.log").exists());
+        assert!(root.path().join("access/blog.example.log").exists());
+        // The symlink went, not what it pointed at
+        assert_eq!(fs::read(&outside).unwrap(), b"keep");
+    }
+}
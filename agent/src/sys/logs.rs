@@ -1,24 +1,29 @@
 // agent/src/sys/logs.rs
+//
+// 🗞️ Rotation of the logs an app writes itself, under <app_dir>/logs (the
+// unit's working directory is the app directory, so `logs/app.log` lands
+// there). Output on stdout/stderr goes to the journal and is not rotated here.
 
 use crate::sys::traits::LogManager;
 use async_trait::async_trait;
 use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 use tokio::fs as tokio_fs;
 
+/// Rotated logs kept per file (one a day).
+const ROTATIONS: u32 = 14;
+
 pub struct LinuxLogManager {
-    logrotate_dir: String, // Injected path (e.g., "/etc/logrotate.d")
+    logrotate_dir: PathBuf, // Injected path (e.g., "/etc/logrotate.d")
 }
 
 impl LinuxLogManager {
-    pub fn new(logrotate_dir: String) -> Self {
+    pub fn new(logrotate_dir: PathBuf) -> Self {
         Self { logrotate_dir }
     }
-}
 
-#[async_trait]
-impl LogManager for LinuxLogManager {
-    async fn configure_logrotate(&self, domain_name: &str, log_dir: &str) -> Result<(), String> {
-        // 🛡️ 1. Zero-Trust Path Traversal Shield
+    fn config_path(&self, domain_name: &str) -> Result<PathBuf, String> {
+        // 🛡️ Zero-Trust Path Traversal Shield
         // Enforce strict domain name characters to prevent directory escape.
         if domain_name.is_empty() || domain_name.contains("..") || domain_name.contains('/') {
             return Err("SECURITY VIOLATION: Invalid domain name format".into());
@@ -29,58 +34,122 @@ impl LogManager for LinuxLogManager {
         {
             return Err("SECURITY VIOLATION: Domain contains illegal characters".into());
         }
+        Ok(self.logrotate_dir.join(format!("kari-{}", domain_name)))
+    }
+}
 
-        // 🛡️ 2. Prevent Config Injection
-        // Ensure the log_dir doesn't contain characters that could break the config structure
-        // or inject arbitrary scripts into the `postrotate` block.
-        if log_dir.contains('\n')
-            || log_dir.contains('{')
-            || log_dir.contains('}')
-            || log_dir.contains(';')
-        {
-            return Err("SECURITY VIOLATION: log_dir contains illegal characters".into());
-        }
-
-        let config_path = format!("{}/kari-{}", self.logrotate_dir, domain_name);
-
-        // 🛡️ 3. Platform-Agnostic Process Signaling
-        // We use `systemctl reload nginx` instead of a hardcoded `/var/run/nginx.pid` path.
-        // This lets systemd locate the correct PID natively, regardless of the Linux distribution.
-        let logrotate_config = format!(
-            r#"{log_dir}/*.log {{
+fn render_logrotate(log_dir: &str, app_user: &str) -> String {
+    // 🛡️ `su`: logrotate runs as root, and the directory is the app's; rotating as
+    // the app user keeps a planted symlink from turning into a root write.
+    // The app keeps its file open, so the file is copied and truncated rather
+    // than moved (lines written in between can be lost).
+    format!(
+        r#"{log_dir}/*.log {{
+    su {user} {user}
     daily
     missingok
-    rotate 14
+    rotate {rotations}
     compress
     delaycompress
     notifempty
-    create 640 root root
-    sharedscripts
-    postrotate
-        systemctl reload nginx > /dev/null 2>/dev/null || true
-    endscript
+    copytruncate
 }}
 "#,
-            log_dir = log_dir
-        );
+        log_dir = log_dir,
+        user = app_user,
+        rotations = ROTATIONS,
+    )
+}
+
+#[async_trait]
+impl LogManager for LinuxLogManager {
+    async fn configure_logrotate(
+        &self,
+        domain_name: &str,
+        app_user: &str,
+        log_dir: &Path,
+    ) -> Result<(), String> {
+        let config_path = self.config_path(domain_name)?;
+        if app_user.is_empty()
+            || !app_user
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            return Err("SECURITY VIOLATION: Invalid username format".into());
+        }
+
+        // 🛡️ Prevent Config Injection
+        // The path is a bare word in the config: nothing that could end it, open a
+        // block or start a directive.
+        let log_dir = log_dir.to_str().ok_or("log_dir contains invalid UTF-8")?;
+        if !log_dir.starts_with('/')
+            || log_dir
+                .chars()
+                .any(|c| c.is_whitespace() || c.is_control() || "{}\"';#*?".contains(c))
+        {
+            return Err("SECURITY VIOLATION: log_dir contains illegal characters".into());
+        }
 
-        tokio_fs::write(&config_path, logrotate_config)
+        tokio_fs::create_dir_all(&self.logrotate_dir)
+            .await
+            .map_err(|e| format!("Failed to create {:?}: {}", self.logrotate_dir, e))?;
+        tokio_fs::write(&config_path, render_logrotate(log_dir, app_user))
             .await
             .map_err(|e| format!("Failed to write logrotate config: {}", e))?;
 
-        // 🛡️ 4. Native Kernel Syscalls (No `chmod` subprocess)
+        // 🛡️ Native Kernel Syscalls (No `chmod` subprocess)
         // Logrotate daemon is highly strict. If the file is writable by group/world (e.g. 666),
         // it will refuse to execute it. We enforce 644 strictly via the kernel.
-        let mut perms = tokio_fs::metadata(&config_path)
-            .await
-            .map_err(|e| format!("Failed to read logrotate config metadata: {}", e))?
-            .permissions();
-
-        perms.set_mode(0o644);
-        tokio_fs::set_permissions(&config_path, perms)
+        tokio_fs::set_permissions(&config_path, std::fs::Permissions::from_mode(0o644))
             .await
             .map_err(|e| format!("Failed to secure logrotate config permissions: {}", e))?;
 
         Ok(())
     }
+
+    async fn remove_logrotate(&self, domain_name: &str) -> Result<(), String> {
+        match tokio_fs::remove_file(self.config_path(domain_name)?).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("Failed to remove logrotate config: {}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn configs_rotate_as_the_app_user_and_refuse_injection() {
+        let root = tempfile::tempdir().unwrap();
+        let logs = LinuxLogManager::new(root.path().join("logrotate.d"));
+        let log_dir = Path::new("/var/www/kari/shop.example.com/logs");
+        logs.configure_logrotate("shop.example.com", "kari-app-shop", log_dir)
+            .await
+            .unwrap();
+
+        let path = root.path().join("logrotate.d/kari-shop.example.com");
+        let config = std::fs::read_to_string(&path).unwrap();
+        assert!(config.starts_with("/var/www/kari/shop.example.com/logs/*.log {\n"));
+        assert!(config.contains("    su kari-app-shop kari-app-shop\n"));
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o644);
+
+        for (domain, dir) in [
+            ("../etc", log_dir),
+            ("shop.example.com", Path::new("/tmp/x {\n postrotate")),
+            ("shop.example.com", Path::new("relative/logs")),
+        ] {
+            assert!(
+                logs.configure_logrotate(domain, "kari-app-shop", dir)
+                    .await
+                    .is_err()
+            );
+        }
+
+        logs.remove_logrotate("shop.example.com").await.unwrap();
+        assert!(!path.exists());
+        logs.remove_logrotate("shop.example.com").await.unwrap();
+    }
 }
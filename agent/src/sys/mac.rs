@@ -0,0 +1,445 @@
+// agent/src/sys/mac.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Mandatory access control for each app.
+//
+// systemd's sandbox (ProtectSystem, ReadWritePaths) is enforced by the unit
+// alone; auditors also want the kernel's MAC framework to say what an app may
+// touch. On AppArmor hosts each app gets a profile allowing its releases
+// (read and execute only), shared/ and logs/, plus the runtime it needs from
+// /usr. On SELinux hosts the app runs as container_t with an MCS level of its
+// own, and its directory is labelled with that level, so no two apps can
+// reach each other's files. Either way the profile is attached through a unit
+// drop-in, not the unit file, so a migrated unit stays valid on a host that
+// uses the other framework.
+
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::sys::executor::{CommandSpec, Executor, SystemExecutor, run_stdout};
+use crate::sys::traits::{MacManager, MacStatus, MacTarget};
+
+const DROP_IN_FILE: &str = "kari-mac.conf";
+const APPARMOR_ENABLED: &str = "/sys/module/apparmor/parameters/enabled";
+const APPARMOR_PROFILES: &str = "/sys/kernel/security/apparmor/profiles";
+const SELINUX_ENFORCE: &str = "/sys/fs/selinux/enforce";
+const MCS_CATEGORIES: u32 = 1024;
+
+/// Which framework confines apps (KARI_MAC).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MacMode {
+    /// Whichever the kernel enforces, AppArmor first; none is not an error.
+    #[default]
+    Auto,
+    AppArmor,
+    SeLinux,
+    Off,
+}
+
+impl MacMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "auto" => Some(Self::Auto),
+            "apparmor" => Some(Self::AppArmor),
+            "selinux" => Some(Self::SeLinux),
+            "off" => Some(Self::Off),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Framework {
+    AppArmor,
+    SeLinux,
+}
+
+impl Framework {
+    fn name(self) -> &'static str {
+        match self {
+            Framework::AppArmor => "apparmor",
+            Framework::SeLinux => "selinux",
+        }
+    }
+}
+
+fn apparmor_enabled() -> bool {
+    std::fs::read_to_string(APPARMOR_ENABLED).is_ok_and(|v| v.trim() == "Y")
+}
+
+fn selinux_enabled() -> bool {
+    Path::new(SELINUX_ENFORCE).exists()
+}
+
+/// 🛡️ Zero-Trust: Profile names and paths end up in policy text; anything
+/// that could close a quoted rule or start a new one is refused.
+fn check_target(target: &MacTarget) -> Result<(), String> {
+    if target.app_user.is_empty()
+        || !target
+            .app_user
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "SECURITY VIOLATION: Invalid profile name '{}'",
+            target.app_user
+        ));
+    }
+    let dir = target.app_dir.to_string_lossy();
+    if !target.app_dir.is_absolute() || dir.chars().any(|c| c.is_control() || c == '"') {
+        return Err(format!(
+            "SECURITY VIOLATION: Invalid app directory {:?}",
+            dir
+        ));
+    }
+    Ok(())
+}
+
+/// The AppArmor profile for one app. Everything not allowed here is denied.
+fn render_apparmor_profile(name: &str, app_dir: &Path) -> String {
+    let dir = app_dir.to_string_lossy();
+    let dir = dir.trim_end_matches('/');
+    format!(
+        r#"# Managed by Kari; regenerated on provisioning, so local edits are lost.
+abi <abi/3.0>,
+include <tunables/global>
+
+profile {name} flags=(attach_disconnected) {{
+  include <abstractions/base>
+  include <abstractions/nameservice>
+  include <abstractions/ssl_certs>
+
+  network inet stream,
+  network inet6 stream,
+  network inet dgram,
+  network inet6 dgram,
+  network unix,
+
+  # Interpreters and the libraries they load
+  /usr/** mrix,
+  /{{,usr/}}lib{{,32,64}}/** mr,
+  @{{PROC}}/@{{pid}}/** r,
+  /sys/fs/cgroup/** r,
+  /sys/devices/system/cpu/** r,
+  owner /tmp/** rwk,
+  owner /var/tmp/** rwk,
+
+  # The app: its code read-only, its data and logs writable
+  "{dir}/" r,
+  "{dir}/current" r,
+  "{dir}/releases/" r,
+  "{dir}/releases/**" mrix,
+  "{dir}/shared/" r,
+  "{dir}/shared/**" rwk,
+  "{dir}/logs/" r,
+  "{dir}/logs/**" rwk,
+}}
+"#
+    )
+}
+
+/// A per-app MCS level: two distinct categories derived from the name, so
+/// the same app gets the same level on every host.
+fn mcs_level(name: &str) -> String {
+    let digest = Sha256::digest(name.as_bytes());
+    let a = u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) % MCS_CATEGORIES;
+    let mut b = u32::from_be_bytes([digest[4], digest[5], digest[6], digest[7]]) % MCS_CATEGORIES;
+    if b == a {
+        b = (a + 1) % MCS_CATEGORIES;
+    }
+    format!("s0:c{},c{}", a.min(b), a.max(b))
+}
+
+fn selinux_context(name: &str) -> String {
+    format!("system_u:system_r:container_t:{}", mcs_level(name))
+}
+
+pub struct LinuxMacManager {
+    mode: MacMode,
+    apparmor_dir: PathBuf, // e.g. /etc/apparmor.d; loaded from there again at boot
+    systemd_dir: PathBuf,
+    executor: Arc<dyn Executor>,
+}
+
+impl LinuxMacManager {
+    pub fn new(mode: MacMode, apparmor_dir: PathBuf, systemd_dir: PathBuf) -> Self {
+        Self::with_executor(mode, apparmor_dir, systemd_dir, Arc::new(SystemExecutor))
+    }
+
+    pub fn with_executor(
+        mode: MacMode,
+        apparmor_dir: PathBuf,
+        systemd_dir: PathBuf,
+        executor: Arc<dyn Executor>,
+    ) -> Self {
+        Self {
+            mode,
+            apparmor_dir,
+            systemd_dir,
+            executor,
+        }
+    }
+
+    /// Runs a command as root and returns its stdout.
+    async fn run(&self, program: &str, args: &[&str]) -> Result<String, String> {
+        run_stdout(
+            self.executor.as_ref(),
+            CommandSpec::privileged(program).args(args.iter().copied()),
+        )
+        .await
+    }
+
+    /// The framework apps are confined with. A framework asked for by name
+    /// but not enforced by the kernel is an error, never a silent downgrade.
+    fn framework(&self) -> Result<Option<Framework>, String> {
+        match self.mode {
+            MacMode::Off => Ok(None),
+            MacMode::Auto if apparmor_enabled() => Ok(Some(Framework::AppArmor)),
+            MacMode::Auto if selinux_enabled() => Ok(Some(Framework::SeLinux)),
+            MacMode::Auto => Ok(None),
+            MacMode::AppArmor if apparmor_enabled() => Ok(Some(Framework::AppArmor)),
+            MacMode::SeLinux if selinux_enabled() => Ok(Some(Framework::SeLinux)),
+            MacMode::AppArmor => Err("KARI_MAC=apparmor but AppArmor is not enabled".into()),
+            MacMode::SeLinux => Err("KARI_MAC=selinux but SELinux is not enabled".into()),
+        }
+    }
+
+    fn drop_in_path(&self, target: &MacTarget) -> PathBuf {
+        self.systemd_dir
+            .join(format!("{}.service.d", target.service_name))
+            .join(DROP_IN_FILE)
+    }
+
+    fn profile_path(&self, target: &MacTarget) -> PathBuf {
+        self.apparmor_dir.join(&target.app_user)
+    }
+
+    fn write_drop_in(&self, target: &MacTarget, directive: &str) -> Result<(), String> {
+        let path = self.drop_in_path(target);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let content = format!(
+            "# Managed by Kari: MAC confinement\n[Service]\n{}\n",
+            directive
+        );
+        std::fs::write(&path, content)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    fn remove_drop_in(&self, target: &MacTarget) -> Result<(), String> {
+        let path = self.drop_in_path(target);
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(format!("Failed to remove {}: {}", path.display(), e));
+            }
+            _ => {}
+        }
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::remove_dir(dir); // Only if nothing else is in it
+        }
+        Ok(())
+    }
+
+    /// `semanage fcontext` specs for the app's directory: everything at the
+    /// app's level, releases read-only.
+    fn selinux_specs(target: &MacTarget) -> [(String, &'static str); 2] {
+        let dir = regex::escape(target.app_dir.to_string_lossy().trim_end_matches('/'));
+        [
+            (format!("{}(/.*)?", dir), "container_file_t"),
+            (format!("{}/releases(/.*)?", dir), "container_ro_file_t"),
+        ]
+    }
+}
+
+#[async_trait]
+impl MacManager for LinuxMacManager {
+    async fn confine(&self, target: &MacTarget) -> Result<(), String> {
+        check_target(target)?;
+        let Some(framework) = self.framework()? else {
+            return self.remove_drop_in(target);
+        };
+        let directive = match framework {
+            Framework::AppArmor => {
+                let path = self.profile_path(target);
+                let profile = render_apparmor_profile(&target.app_user, &target.app_dir);
+                std::fs::write(&path, profile)
+                    .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                self.run(
+                    "apparmor_parser",
+                    &["--replace", "--write-cache", &path.to_string_lossy()],
+                )
+                .await?;
+                format!("AppArmorProfile={}", target.app_user)
+            }
+            Framework::SeLinux => {
+                let level = mcs_level(&target.app_user);
+                for (spec, file_type) in Self::selinux_specs(target) {
+                    // -a fails for a spec that already exists; -m then updates it
+                    let add = ["fcontext", "-a", "-t", file_type, "-r", &level, &spec];
+                    if self.run("semanage", &add).await.is_err() {
+                        self.run(
+                            "semanage",
+                            &["fcontext", "-m", "-t", file_type, "-r", &level, &spec],
+                        )
+                        .await?;
+                    }
+                }
+                self.run(
+                    "restorecon",
+                    &["-R", "-F", &target.app_dir.to_string_lossy()],
+                )
+                .await?;
+                format!("SELinuxContext={}", selinux_context(&target.app_user))
+            }
+        };
+        self.write_drop_in(target, &directive)?;
+        info!(
+            "🛡️ {} confined by {} ({})",
+            target.service_name,
+            framework.name(),
+            directive
+        );
+        Ok(())
+    }
+
+    async fn release(&self, target: &MacTarget) -> Result<(), String> {
+        check_target(target)?;
+        self.remove_drop_in(target)?;
+        // Whatever was loaded is removed, even if the host's framework changed since
+        let profile = self.profile_path(target);
+        if profile.exists() {
+            self.run("apparmor_parser", &["--remove", &profile.to_string_lossy()])
+                .await?;
+            std::fs::remove_file(&profile)
+                .map_err(|e| format!("Failed to remove {}: {}", profile.display(), e))?;
+        }
+        if selinux_enabled() {
+            for (spec, _) in Self::selinux_specs(target) {
+                let _ = self.run("semanage", &["fcontext", "-d", &spec]).await; // Absent is fine
+            }
+        }
+        Ok(())
+    }
+
+    async fn status(&self, target: &MacTarget) -> Result<MacStatus, String> {
+        check_target(target)?;
+        let Some(framework) = self.framework()? else {
+            return Ok(MacStatus::default());
+        };
+        let attached = self.drop_in_path(target).exists();
+        let mut status = MacStatus {
+            framework: framework.name().to_string(),
+            ..Default::default()
+        };
+        match framework {
+            Framework::AppArmor => {
+                status.profile = target.app_user.clone();
+                // Lines read "<name> (<mode>)"
+                let loaded = std::fs::read_to_string(APPARMOR_PROFILES).unwrap_or_default();
+                if let Some(mode) = loaded.lines().find_map(|line| {
+                    line.strip_prefix(&format!("{} (", target.app_user))
+                        .and_then(|rest| rest.strip_suffix(')'))
+                }) {
+                    status.mode = mode.to_string();
+                    status.loaded = attached;
+                }
+            }
+            Framework::SeLinux => {
+                status.profile = selinux_context(&target.app_user);
+                let label = self
+                    .run("stat", &["-c", "%C", &target.app_dir.to_string_lossy()])
+                    .await
+                    .unwrap_or_default();
+                if label.trim().ends_with(&mcs_level(&target.app_user)) {
+                    let enforce = std::fs::read_to_string(SELINUX_ENFORCE).unwrap_or_default();
+                    status.mode = match enforce.trim() {
+                        "1" => "enforce",
+                        _ => "permissive",
+                    }
+                    .to_string();
+                    status.loaded = attached;
+                }
+            }
+        }
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::FakeExecutor;
+
+    #[tokio::test]
+    async fn released_profiles_are_unloaded_as_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let executor = Arc::new(FakeExecutor::default());
+        let manager = LinuxMacManager::with_executor(
+            MacMode::Off,
+            dir.path().to_path_buf(),
+            dir.path().to_path_buf(),
+            executor.clone(),
+        );
+        let target = MacTarget {
+            app_user: "kari-app-shop".into(),
+            service_name: "kari-shop.example".into(),
+            app_dir: PathBuf::from("/var/www/shop.example"),
+        };
+        let profile = dir.path().join("kari-app-shop");
+
+        std::fs::write(&profile, "profile kari-app-shop {}").unwrap();
+        executor.answer(&["apparmor_parser"], 1, "", "Permission denied");
+        let err = manager.release(&target).await.unwrap_err();
+        assert!(err.ends_with("failed: Permission denied"), "{}", err);
+        assert!(profile.exists(), "a profile still loaded is kept");
+
+        executor.answer(&["apparmor_parser"], 0, "", "");
+        manager.release(&target).await.unwrap();
+        assert!(!profile.exists());
+        let unload = executor
+            .runs()
+            .into_iter()
+            .rfind(|spec| spec.program == "apparmor_parser")
+            .unwrap();
+        assert!(unload.privileged);
+        assert_eq!(
+            unload.argv(),
+            ["apparmor_parser", "--remove", &profile.to_string_lossy()]
+        );
+    }
+
+    #[test]
+    fn profiles_allow_only_the_apps_own_paths() {
+        let profile = render_apparmor_profile("kari-app-shop", Path::new("/var/www/shop.example"));
+        assert!(profile.contains("profile kari-app-shop flags=(attach_disconnected) {"));
+        assert!(profile.contains("\"/var/www/shop.example/releases/**\" mrix,"));
+        assert!(profile.contains("\"/var/www/shop.example/shared/**\" rwk,"));
+        assert!(profile.contains("\"/var/www/shop.example/logs/**\" rwk,"));
+        assert!(!profile.contains("/var/www/shop.example/** "));
+        assert!(profile.contains("@{PROC}/@{pid}/** r,"));
+
+        let bad = MacTarget {
+            app_user: "kari-app-shop".into(),
+            service_name: "kari-shop.example".into(),
+            app_dir: PathBuf::from("/var/www/x\" rw,\n  /** rwk,"),
+        };
+        assert!(check_target(&bad).is_err());
+    }
+
+    #[test]
+    fn each_app_gets_a_stable_level_of_two_categories() {
+        let level = mcs_level("kari-app-shop");
+        assert_eq!(level, mcs_level("kari-app-shop"));
+        assert_ne!(level, mcs_level("kari-app-blog"));
+        let (a, b) = level
+            .strip_prefix("s0:c")
+            .and_then(|rest| rest.split_once(",c"))
+            .unwrap();
+        let (a, b): (u32, u32) = (a.parse().unwrap(), b.parse().unwrap());
+        assert!(a < b && b < MCS_CATEGORIES);
+    }
+}
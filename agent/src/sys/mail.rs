@@ -0,0 +1,236 @@
+// agent/src/sys/mail.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Local null-client relay configuration only.
+// 🛡️ Zero-Trust: Tenants submit to localhost; the relay credential lives only in a
+// root-owned, 0600 postmap database that jailed users cannot read.
+
+use async_trait::async_trait;
+use std::fs as std_fs;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::sys::executor::{CommandSpec, Executor, SystemExecutor};
+use crate::sys::traits::{MailRelayConfig, MailRelayManager};
+
+/// PostfixRelayManager configures Postfix as a loopback-only null client that
+/// forwards everything to an authenticated submission relay.
+pub struct PostfixRelayManager {
+    postfix_dir: PathBuf, // Injected via AgentConfig, e.g., "/etc/postfix"
+    executor: Arc<dyn Executor>,
+}
+
+impl PostfixRelayManager {
+    pub fn new(postfix_dir: PathBuf) -> Self {
+        Self::with_executor(postfix_dir, Arc::new(SystemExecutor))
+    }
+
+    pub fn with_executor(postfix_dir: PathBuf, executor: Arc<dyn Executor>) -> Self {
+        Self {
+            postfix_dir,
+            executor,
+        }
+    }
+
+    async fn postconf(&self, setting: &str) -> Result<(), String> {
+        let dir = self
+            .postfix_dir
+            .to_str()
+            .ok_or("Invalid UTF-8 in postfix dir")?;
+        let postconf = CommandSpec::privileged("postconf").args(["-c", dir, "-e", setting]);
+        let output = self
+            .executor
+            .run(&postconf)
+            .await
+            .map_err(|e| format!("SLA Failure: postconf spawn error: {}", e))?;
+
+        if !output.success() {
+            return Err(format!(
+                "postconf rejected setting: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// 🛡️ Zero-Trust: Relay hosts end up inside main.cf, so only hostname/IP characters pass.
+fn validate_relay_host(host: &str) -> Result<(), String> {
+    if host.is_empty()
+        || host.starts_with('-')
+        || host.starts_with('.')
+        || !host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == ':')
+    {
+        return Err(format!("Zero-Trust: Invalid relay host: '{}'", host));
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl MailRelayManager for PostfixRelayManager {
+    async fn configure_relay(&self, config: MailRelayConfig) -> Result<(), String> {
+        // 1. 🛡️ Zero-Trust Input Validation
+        validate_relay_host(&config.relay_host)?;
+        if config.relay_port == 0 {
+            return Err("Zero-Trust: Port 0 is reserved and cannot be used".into());
+        }
+        // Postfix splits the map value on the first ':', and whitespace ends the key.
+        if config.username.is_empty()
+            || config
+                .username
+                .chars()
+                .any(|c| c.is_whitespace() || c == ':')
+        {
+            return Err("SECURITY VIOLATION: Invalid relay username format".into());
+        }
+        if config.origin_domain.is_empty()
+            || !config
+                .origin_domain
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+        {
+            return Err("SECURITY VIOLATION: Invalid origin domain format".into());
+        }
+
+        let relay = format!("[{}]:{}", config.relay_host, config.relay_port);
+        let sasl_path = self.postfix_dir.join("sasl_passwd");
+
+        // 2. 🚨 CRITICAL SECURITY BOUNDARY 🚨
+        // Same trade-off as the SSL engine: synchronous I/O inside the closure so the
+        // plaintext never crosses an `.await` into the task's state machine.
+        let write_result = config.password.use_secret(|secret_str| {
+            if secret_str.contains('\n') || secret_str.contains('\r') {
+                return Err("SECURITY VIOLATION: Relay password contains line breaks".to_string());
+            }
+
+            let mut file = std_fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600) // rw------- (root only)
+                .open(&sasl_path)
+                .map_err(|e| format!("Failed to open sasl_passwd securely: {}", e))?;
+
+            writeln!(file, "{} {}:{}", relay, config.username, secret_str)
+                .map_err(|e| format!("Failed to write relay credentials: {}", e))?;
+            file.sync_all()
+                .map_err(|e| format!("Failed to sync sasl_passwd to disk: {}", e))?;
+
+            Ok::<(), String>(())
+        });
+
+        // 🛡️ Proactive Scrubbing
+        config.password.destroy();
+        write_result?;
+
+        // 3. Compile the lookup table, then remove the plaintext source.
+        // Postfix only ever reads the hashed .db file (which postmap creates as 0600 too).
+        let sasl_str = sasl_path.to_str().ok_or("Invalid UTF-8 in sasl path")?;
+        let postmap = self
+            .executor
+            .run(&CommandSpec::privileged("postmap").arg(format!("hash:{}", sasl_str)))
+            .await
+            .map_err(|e| format!("SLA Failure: postmap spawn error: {}", e))?;
+
+        let _ = std_fs::OpenOptions::new()
+            .write(true)
+            .open(&sasl_path)
+            .and_then(|mut f| f.write_all(&[0u8; 4096]).and_then(|_| f.sync_all()));
+        let _ = std_fs::remove_file(&sasl_path);
+
+        if !postmap.success() {
+            return Err(format!(
+                "postmap failed: {}",
+                String::from_utf8_lossy(&postmap.stderr)
+            ));
+        }
+
+        // 4. 🛡️ Null-Client Posture: loopback only, no local delivery, TLS mandatory.
+        let settings = [
+            format!("relayhost = {}", relay),
+            "inet_interfaces = loopback-only".to_string(),
+            "mydestination =".to_string(),
+            "local_transport = error:local delivery is disabled".to_string(),
+            format!("myorigin = {}", config.origin_domain),
+            "smtp_sasl_auth_enable = yes".to_string(),
+            format!("smtp_sasl_password_maps = hash:{}", sasl_str),
+            "smtp_sasl_security_options = noanonymous".to_string(),
+            "smtp_tls_security_level = encrypt".to_string(),
+        ];
+        for setting in &settings {
+            self.postconf(setting).await?;
+        }
+
+        // 5. Apply (restart covers the inet_interfaces change, which reload ignores)
+        let restart = self
+            .executor
+            .run(&CommandSpec::privileged("systemctl").args(["restart", "postfix"]))
+            .await
+            .map_err(|e| format!("Failed to execute systemctl: {}", e))?;
+
+        if !restart.success() {
+            return Err(format!(
+                "Failed to restart postfix: {}",
+                String::from_utf8_lossy(&restart.stderr)
+            ));
+        }
+
+        info!("✉️ Mail relay configured via {}", relay);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sys::secrets::ProviderCredential;
+    use crate::testkit::FakeExecutor;
+
+    fn relay() -> MailRelayConfig {
+        MailRelayConfig {
+            relay_host: "smtp.example.com".into(),
+            relay_port: 587,
+            username: "mailer".into(),
+            password: ProviderCredential::from_string("s3cret".into()),
+            origin_domain: "example.com".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn relays_are_compiled_configured_and_applied_as_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let executor = Arc::new(FakeExecutor::default());
+        let postfix =
+            PostfixRelayManager::with_executor(dir.path().to_path_buf(), executor.clone());
+
+        postfix.configure_relay(relay()).await.unwrap();
+        let runs = executor.runs();
+        assert!(runs.iter().all(|run| run.privileged));
+        let sasl = dir.path().join("sasl_passwd");
+        assert_eq!(
+            runs[0].argv(),
+            ["postmap", &format!("hash:{}", sasl.display())]
+        );
+        assert!(runs[1..10].iter().all(|run| run.program == "postconf"));
+        assert_eq!(
+            runs[1].args.last().unwrap(),
+            "relayhost = [smtp.example.com]:587"
+        );
+        assert_eq!(runs[10].argv(), ["systemctl", "restart", "postfix"]);
+        assert!(!sasl.exists(), "the plaintext map source is removed");
+
+        executor.answer(&["postmap"], 1, "", "fatal: open database");
+        let err = postfix.configure_relay(relay()).await.unwrap_err();
+        assert_eq!(err, "postmap failed: fatal: open database");
+        assert!(!sasl.exists());
+        assert_eq!(
+            executor.runs().len(),
+            12,
+            "nothing is configured after a failed postmap"
+        );
+    }
+}
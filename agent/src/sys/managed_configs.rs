@@ -0,0 +1,278 @@
+// agent/src/sys/managed_configs.rs
+//
+// 🛡️ SOLID: Single-Responsibility — A read-only copy of everything the agent generated.
+//
+// Auditors want to see the files kari wrote (kari-* units and timers, app
+// vhosts, logrotate configs) next to the records that produced them, without
+// the bundle key a state snapshot needs and without the secrets it seals. So
+// each file is hashed as it is on disk, environment values are redacted from
+// the copy, and the archive carries a manifest of hashes and generating specs.
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use serde::Serialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::fs as std_fs;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::sys::state::{
+    AppRecord, JobRecord, NS_APPS, NS_JOBS, NS_TENANTS, get_record, list_records,
+};
+use crate::sys::systemd::is_valid_env_key;
+use crate::sys::traits::StateStore;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+
+/// Host directories the generated files live in.
+pub struct ManagedConfigPaths<'a> {
+    pub systemd_dir: &'a Path,
+    pub proxy_conf_dir: &'a Path,
+    pub logrotate_dir: &'a Path,
+}
+
+/// One generated file, as the manifest describes it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ManagedConfig {
+    pub kind: String, // "unit" | "timer" | "slice" | "vhost" | "logrotate"
+    pub name: String, // File name
+    pub path: PathBuf,
+    pub sha256: String, // Of the file on disk, before redaction
+    pub size: u64,
+    pub owner: String, // app_id, job name or tenant_id; empty if none is recorded
+    pub spec: Option<Value>, // The record the file was generated from
+    pub redacted: bool, // Environment values were blanked in the archived copy
+}
+
+/// Blanks every value of a unit's env file.
+fn redact_env_file(data: &[u8]) -> (Vec<u8>, bool) {
+    let text = String::from_utf8_lossy(data);
+    let mut out = String::with_capacity(text.len());
+    let mut quoted = false; // Inside a double-quoted value that spans lines
+    for line in text.lines() {
+        let value = if quoted {
+            line
+        } else {
+            match line.split_once('=') {
+                Some((key, value)) if is_valid_env_key(key) => {
+                    out.push_str(&format!("{}=<redacted>\n", key));
+                    value
+                }
+                _ => continue,
+            }
+        };
+        let mut escaped = false;
+        for c in value.chars() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => quoted = !quoted,
+                _ => {}
+            }
+        }
+    }
+    let redacted = !out.is_empty();
+    (out.into_bytes(), redacted)
+}
+
+/// Blanks the value of every `Environment=` line. Returns the copy and whether
+/// anything was blanked.
+fn redact_unit(data: &[u8]) -> (Vec<u8>, bool) {
+    let text = String::from_utf8_lossy(data);
+    let mut redacted = false;
+    let mut out = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let body = line.trim_end_matches('\n');
+        if let Some(assignment) = body.strip_prefix("Environment=") {
+            let key = assignment
+                .trim_start_matches('"')
+                .split('=')
+                .next()
+                .unwrap_or_default();
+            out.push_str(&format!("Environment=\"{}=<redacted>\"", key));
+            out.push_str(&line[body.len()..]);
+            redacted = true;
+        } else {
+            out.push_str(line);
+        }
+    }
+    (out.into_bytes(), redacted)
+}
+
+fn read_managed(dir: &Path, keep: impl Fn(&str) -> bool) -> Result<Vec<(String, PathBuf)>, String> {
+    let entries = match std_fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to list {:?}: {}", dir, e)),
+    };
+    let mut files: Vec<(String, PathBuf)> = entries
+        .flatten()
+        .filter(|entry| {
+            // 🛡️ symlink_metadata: never follow a link out of the managed directory
+            entry.path().symlink_metadata().is_ok_and(|m| m.is_file())
+        })
+        .map(|entry| {
+            (
+                entry.file_name().to_string_lossy().to_string(),
+                entry.path(),
+            )
+        })
+        .filter(|(name, _)| keep(name))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn to_spec<T: Serialize>(record: &T) -> Option<Value> {
+    serde_json::to_value(record).ok()
+}
+
+/// Every file the agent manages, with its archived (redacted) contents.
+pub async fn collect(
+    state: &dyn StateStore,
+    paths: &ManagedConfigPaths<'_>,
+) -> Result<Vec<(ManagedConfig, Vec<u8>)>, String> {
+    let apps: Vec<AppRecord> = list_records(state, NS_APPS).await?;
+    let jobs: Vec<JobRecord> = list_records(state, NS_JOBS).await?;
+    let mut configs = Vec::new();
+    let mut push = |kind: &str, name: String, path: PathBuf, owner: String, spec: Option<Value>| {
+        let data = std_fs::read(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        let (copy, redacted) = if kind == "unit" {
+            redact_unit(&data)
+        } else if kind == "env" {
+            redact_env_file(&data)
+        } else {
+            (data.clone(), false)
+        };
+        configs.push((
+            ManagedConfig {
+                kind: kind.to_string(),
+                name,
+                path,
+                sha256: format!("{:x}", Sha256::digest(&data)),
+                size: data.len() as u64,
+                owner,
+                spec,
+                redacted,
+            },
+            copy,
+        ));
+        Ok::<(), String>(())
+    };
+
+    for (name, path) in read_managed(paths.systemd_dir, |n| n.starts_with("kari-"))? {
+        let (stem, ext) = name.rsplit_once('.').unwrap_or((&name, ""));
+        let kind = match ext {
+            "timer" => "timer",
+            "slice" => "slice",
+            "env" => "env",
+            _ => "unit",
+        };
+        let (owner, spec) = if let Some(job) = stem
+            .strip_prefix("kari-job-")
+            .and_then(|n| jobs.iter().find(|j| j.job_name == n))
+        {
+            (job.job_name.clone(), to_spec(job))
+        } else if let Some(tenant_id) = stem.strip_prefix("kari-tenant-") {
+            let quota: Option<Value> = get_record(state, NS_TENANTS, tenant_id).await?;
+            (tenant_id.to_string(), quota)
+        } else if let Some(app) = apps.iter().find(|a| a.service_name == stem) {
+            (app.app_id.clone(), to_spec(app))
+        } else {
+            (String::new(), None)
+        };
+        push(kind, name, path, owner, spec)?;
+    }
+
+    // Only vhosts belonging to kari apps; operator-managed sites are not ours to report.
+    for (name, path) in read_managed(paths.proxy_conf_dir, |_| true)? {
+        let Some(app) = apps
+            .iter()
+            .find(|a| name == a.domain_name || name == format!("{}.conf", a.domain_name))
+        else {
+            continue;
+        };
+        let spec = json!({ "domain_name": app.domain_name, "port": app.port, "vhost": app.vhost });
+        push("vhost", name, path, app.app_id.clone(), Some(spec))?;
+    }
+
+    for (name, path) in read_managed(paths.logrotate_dir, |n| n.starts_with("kari-"))? {
+        let app = apps
+            .iter()
+            .find(|a| name.strip_prefix("kari-") == Some(a.domain_name.as_str()));
+        let owner = app.map(|a| a.app_id.clone()).unwrap_or_default();
+        let spec = app.map(|a| json!({ "domain_name": a.domain_name }));
+        push("logrotate", name, path, owner, spec)?;
+    }
+    Ok(configs)
+}
+
+/// Writes the files and a manifest of their hashes and specs as a gzip'd
+/// tarball (0600): `manifest.json`, then `<kind>/<name>` per file.
+pub fn write_archive(path: &Path, configs: &[(ManagedConfig, Vec<u8>)]) -> Result<(), String> {
+    let file = std_fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .map_err(|e| format!("Failed to create archive {:?}: {}", path, e))?;
+    let mut tar = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    let now = chrono::Utc::now().timestamp().max(0) as u64;
+    let mut append = |name: &str, data: &[u8]| {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o600);
+        header.set_mtime(now);
+        header.set_entry_type(tar::EntryType::Regular);
+        tar.append_data(&mut header, name, data)
+            .map_err(|e| format!("Failed to append '{}' to archive: {}", name, e))
+    };
+
+    let manifest = json!({
+        "created_at": now,
+        "source_host": sysinfo::System::host_name().unwrap_or_default(),
+        "configs": configs.iter().map(|(config, _)| config).collect::<Vec<_>>(),
+    });
+    let json = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| format!("Failed to encode manifest: {}", e))?;
+    append(MANIFEST_ENTRY, &json)?;
+    for (config, data) in configs {
+        append(&format!("{}/{}", config.kind, config.name), data)?;
+    }
+
+    let file = tar
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .map_err(|e| format!("Failed to finalize archive: {}", e))?;
+    file.sync_all()
+        .map_err(|e| format!("Failed to sync archive: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn environment_values_never_reach_the_archive() {
+        let unit = "[Service]\nEnvironment=\"DATABASE_URL=postgres://u:hunter2@db/app\"\nExecStart=/usr/bin/node server.js\nEnvironment=\"PORT=3000\"";
+        let (copy, redacted) = redact_unit(unit.as_bytes());
+        let copy = String::from_utf8(copy).unwrap();
+        assert!(redacted);
+        assert!(!copy.contains("hunter2"));
+        assert_eq!(
+            copy,
+            "[Service]\nEnvironment=\"DATABASE_URL=<redacted>\"\nExecStart=/usr/bin/node server.js\nEnvironment=\"PORT=<redacted>\""
+        );
+        assert!(!redact_unit(b"[Timer]\nOnCalendar=daily\n").1);
+
+        let env = "API_TOKEN=\"s3cr%t\"\nPEM=\"-----BEGIN KEY-----\nMIIB=x\n-----END KEY-----\"\n";
+        let (copy, redacted) = redact_env_file(env.as_bytes());
+        assert!(redacted);
+        assert_eq!(
+            String::from_utf8(copy).unwrap(),
+            "API_TOKEN=<redacted>\nPEM=<redacted>\n"
+        );
+    }
+}
@@ -0,0 +1,311 @@
+// agent/src/sys/migration.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Packs one app into a portable bundle and
+// unpacks it on another host. Re-provisioning (user, units, ingress) is the
+// caller's job; this module only moves bytes and records.
+//
+// Bundle layout:
+//   spec/app.json, spec/jobs.json   AppRecord + owned job names (always first)
+//   meta/current                    Name of the active release, if any
+//   files/...                       Active release + shared/ (dotenv files sealed)
+//   units/...                       App service and job units (sealed)
+//   ssl/...                         Certificates (sealed)
+//   secrets/registry-auth           Podman auth.json (sealed)
+
+use std::fs as std_fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+use zeroize::Zeroizing;
+
+use crate::sys::bundle::{BundleKey, BundleWriter, read_bundle};
+use crate::sys::secrets::ProviderCredential;
+use crate::sys::state::AppRecord;
+
+const SPEC_APP: &str = "spec/app.json";
+const SPEC_JOBS: &str = "spec/jobs.json";
+const META_CURRENT: &str = "meta/current";
+const FILES_PREFIX: &str = "files/";
+const UNITS_PREFIX: &str = "units/";
+const SSL_PREFIX: &str = "ssl/";
+const REGISTRY_AUTH: &str = "secrets/registry-auth";
+
+/// Host directories an app is exported from and imported into.
+pub struct MigrationPaths<'a> {
+    pub web_root: &'a Path,
+    pub systemd_dir: &'a Path,
+    pub ssl_storage_dir: &'a Path,
+}
+
+/// What `export_app` needs beyond the files on disk.
+pub struct AppExport {
+    pub record: AppRecord,
+    pub job_names: Vec<String>,
+    pub registry_auth: Option<ProviderCredential>,
+}
+
+/// What `unpack_app` restored; the caller re-provisions from this.
+pub struct ImportedApp {
+    pub record: AppRecord,
+    pub job_names: Vec<String>,
+    pub registry_auth: Option<ProviderCredential>,
+    pub entries: u32,
+}
+
+/// Dotenv files hold rendered secrets, so they travel sealed.
+fn is_sensitive(rel: &Path) -> bool {
+    rel.file_name()
+        .map(|n| n.to_string_lossy().starts_with(".env"))
+        .unwrap_or(false)
+}
+
+fn walk_files(root: &Path, dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = match std_fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("Failed to list {:?}: {}", dir, e)),
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        // 🛡️ symlink_metadata: links are skipped, never followed out of the app tree
+        let Ok(meta) = path.symlink_metadata() else {
+            continue;
+        };
+        if meta.is_dir() {
+            walk_files(root, &path, out)?;
+        } else if meta.is_file() {
+            out.push(path.strip_prefix(root).unwrap_or(&path).to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+fn unit_names(record: &AppRecord, job_names: &[String]) -> Vec<String> {
+    let mut names = vec![
+        format!("{}.service", record.service_name),
+        format!("{}.env", record.service_name),
+    ];
+    for job in job_names {
+        names.push(format!("kari-job-{}.service", job));
+        names.push(format!("kari-job-{}.timer", job));
+    }
+    names
+}
+
+/// Env files hold resolved secrets and stay root-only; units are world-readable.
+fn unit_mode(name: &str) -> u32 {
+    if name.ends_with(".env") { 0o600 } else { 0o644 }
+}
+
+pub fn export_app(
+    app: AppExport,
+    paths: &MigrationPaths<'_>,
+    key: &BundleKey,
+    bundle_path: &Path,
+) -> Result<u32, String> {
+    let app_dir = paths.web_root.join(&app.record.domain_name);
+    let mut writer = BundleWriter::create(bundle_path, key, "app")?;
+    let mut entries = 0u32;
+
+    let spec = serde_json::to_vec_pretty(&app.record)
+        .map_err(|e| format!("Failed to encode app spec: {}", e))?;
+    writer.add_file(SPEC_APP, &spec, 0o600)?;
+    let jobs = serde_json::to_vec(&app.job_names)
+        .map_err(|e| format!("Failed to encode job list: {}", e))?;
+    writer.add_file(SPEC_JOBS, &jobs, 0o600)?;
+    entries += 2;
+
+    // 1. Active release only (older releases are rollback history, not state)
+    let mut roots = vec![PathBuf::from("shared")];
+    if let Ok(target) = std_fs::read_link(app_dir.join("current"))
+        && let Some(release) = target.file_name()
+    {
+        let release = release.to_string_lossy().to_string();
+        writer.add_file(META_CURRENT, release.as_bytes(), 0o644)?;
+        roots.push(Path::new("releases").join(&release));
+        entries += 1;
+    }
+
+    let mut files = Vec::new();
+    for root in &roots {
+        walk_files(&app_dir, &app_dir.join(root), &mut files)?;
+    }
+    for rel in files {
+        let path = app_dir.join(&rel);
+        let mode = std_fs::metadata(&path)
+            .map(|m| m.permissions().mode() & 0o777)
+            .unwrap_or(0o640);
+        let data = Zeroizing::new(
+            std_fs::read(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?,
+        );
+        let name = format!("{}{}", FILES_PREFIX, rel.to_string_lossy());
+        if is_sensitive(&rel) {
+            writer.add_sealed(&name, &data, mode)?;
+        } else {
+            writer.add_file(&name, &data, mode)?;
+        }
+        entries += 1;
+    }
+
+    // 2. Units and their env files (resolved secrets) are sealed like secrets
+    for unit in unit_names(&app.record, &app.job_names) {
+        let path = paths.systemd_dir.join(&unit);
+        if let Ok(data) = std_fs::read(&path) {
+            writer.add_sealed(
+                &format!("{}{}", UNITS_PREFIX, unit),
+                &data,
+                unit_mode(&unit),
+            )?;
+            entries += 1;
+        }
+    }
+
+    // 3. Certificates
+    let ssl_dir = paths.ssl_storage_dir.join(app.record.certificate_name());
+    let mut certs = Vec::new();
+    walk_files(&ssl_dir, &ssl_dir, &mut certs)?;
+    for rel in certs {
+        let path = ssl_dir.join(&rel);
+        let mode = std_fs::metadata(&path)
+            .map(|m| m.permissions().mode() & 0o777)
+            .unwrap_or(0o600);
+        let data = Zeroizing::new(
+            std_fs::read(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?,
+        );
+        writer.add_sealed(
+            &format!("{}{}", SSL_PREFIX, rel.to_string_lossy()),
+            &data,
+            mode,
+        )?;
+        entries += 1;
+    }
+
+    // 4. Registry credentials
+    if let Some(auth) = app.registry_auth {
+        auth.use_secret(|secret| writer.add_sealed(REGISTRY_AUTH, secret.as_bytes(), 0o600))?;
+        auth.destroy();
+        entries += 1;
+    }
+
+    writer.finish()?;
+    Ok(entries)
+}
+
+/// 🛡️ Zero-Trust Path Traversal Shield: only plain components below `base`.
+fn restore_target(base: &Path, rel: &str) -> Result<PathBuf, String> {
+    let rel_path = Path::new(rel);
+    if rel.is_empty()
+        || !rel_path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return Err(format!(
+            "SECURITY VIOLATION: Invalid bundle entry '{}'",
+            rel
+        ));
+    }
+    Ok(base.join(rel_path))
+}
+
+fn write_restored(path: &Path, data: &[u8], mode: u32) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std_fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+    std_fs::write(path, data).map_err(|e| format!("Failed to restore {:?}: {}", path, e))?;
+    std_fs::set_permissions(path, std_fs::Permissions::from_mode(mode & 0o777))
+        .map_err(|e| format!("Failed to set mode on {:?}: {}", path, e))
+}
+
+/// Verifies the bundle and writes its files, units and certificates into place.
+/// `validate` vets the spec (identifiers, conflicts) before any file is written.
+pub fn unpack_app(
+    paths: &MigrationPaths<'_>,
+    key: &BundleKey,
+    bundle_path: &Path,
+    validate: impl Fn(&AppRecord) -> Result<(), String>,
+) -> Result<ImportedApp, String> {
+    let mut record: Option<AppRecord> = None;
+    let mut job_names: Vec<String> = Vec::new();
+    let mut registry_auth = None;
+    let mut current: Option<String> = None;
+    let mut entries = 0u32;
+
+    let manifest = read_bundle(bundle_path, key, |name, mode, data| {
+        entries += 1;
+        if name == SPEC_APP {
+            let spec: AppRecord =
+                serde_json::from_slice(data).map_err(|e| format!("Corrupt app spec: {}", e))?;
+            validate(&spec)?;
+            record = Some(spec);
+            return Ok(());
+        }
+        let Some(app) = record.as_ref() else {
+            return Err("App spec must precede every other bundle entry".into());
+        };
+
+        if name == SPEC_JOBS {
+            job_names =
+                serde_json::from_slice(data).map_err(|e| format!("Corrupt job list: {}", e))?;
+            // 🛡️ A bundle may only carry jobs in its own app's namespace
+            let prefix = format!("{}-", app.app_id);
+            if let Some(foreign) = job_names.iter().find(|j| !j.starts_with(&prefix)) {
+                return Err(format!(
+                    "SECURITY VIOLATION: Foreign job '{}' in bundle",
+                    foreign
+                ));
+            }
+        } else if name == META_CURRENT {
+            let release = String::from_utf8_lossy(data).to_string();
+            restore_target(Path::new("/"), &release)?;
+            current = Some(release);
+        } else if let Some(rel) = name.strip_prefix(FILES_PREFIX) {
+            let app_dir = paths.web_root.join(&app.domain_name);
+            write_restored(&restore_target(&app_dir, rel)?, data, mode)?;
+        } else if let Some(unit) = name.strip_prefix(UNITS_PREFIX) {
+            if !unit_names(app, &job_names).iter().any(|u| u == unit) {
+                return Err(format!(
+                    "SECURITY VIOLATION: Foreign unit '{}' in bundle",
+                    unit
+                ));
+            }
+            write_restored(
+                &restore_target(paths.systemd_dir, unit)?,
+                data,
+                unit_mode(unit),
+            )?;
+        } else if let Some(rel) = name.strip_prefix(SSL_PREFIX) {
+            let ssl_dir = paths.ssl_storage_dir.join(&app.domain_name);
+            write_restored(&restore_target(&ssl_dir, rel)?, data, mode)?;
+        } else if name == REGISTRY_AUTH {
+            let auth = String::from_utf8(data.to_vec())
+                .map_err(|_| "Registry auth in bundle is not UTF-8".to_string())?;
+            registry_auth = Some(ProviderCredential::from_string(auth));
+        } else {
+            return Err(format!("Unexpected bundle entry '{}'", name));
+        }
+        Ok(())
+    })?;
+
+    if manifest.kind != "app" {
+        return Err(format!(
+            "Bundle is a '{}' bundle, not an app",
+            manifest.kind
+        ));
+    }
+    let record = record.ok_or_else(|| "Bundle has no app spec".to_string())?;
+
+    if let Some(release) = current {
+        let app_dir = paths.web_root.join(&record.domain_name);
+        let link = app_dir.join("current");
+        let _ = std_fs::remove_file(&link);
+        std::os::unix::fs::symlink(Path::new("releases").join(release), &link)
+            .map_err(|e| format!("Failed to restore current release link: {}", e))?;
+    }
+
+    Ok(ImportedApp {
+        record,
+        job_names,
+        registry_auth,
+        entries,
+    })
+}
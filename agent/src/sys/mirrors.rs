@@ -0,0 +1,274 @@
+// agent/src/sys/mirrors.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Where build steps fetch dependencies from.
+//
+// Hosts behind an artifact proxy configure their npm, pip and cargo mirrors
+// and an HTTP(S) proxy once; every build step gets them in its environment
+// (cargo, which takes no source replacement from the environment, gets a
+// `.cargo/config.toml` in the checkout). With `mirrors_only`, the step's unit
+// may reach nothing but those hosts and the DNS resolvers: the addresses are
+// resolved when the step starts and enforced by systemd (IPAddressAllow).
+
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::Path;
+
+const CARGO_MIRROR_SOURCE: &str = "kari-mirror";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildMirrors {
+    pub npm_registry: Option<String>,
+    pub pip_index: Option<String>,
+    pub cargo_registry: Option<String>, // A sparse index, e.g. https://mirror/api/cargo/crates-io/index/
+    pub proxy: Option<String>,          // Used for both http and https
+    pub no_proxy: Option<String>,
+    pub mirrors_only: bool,
+}
+
+/// `scheme://[user@]host[:port]/...` -> (host, port), the port defaulting
+/// by scheme. IPv6 literals keep no brackets.
+pub fn url_host(url: &str) -> Option<(String, u16)> {
+    let (scheme, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, h)| h);
+    let default_port = match scheme.to_ascii_lowercase().as_str() {
+        "https" | "sparse+https" => 443,
+        "http" | "sparse+http" => 80,
+        _ => return None,
+    };
+    let (host, port) = if let Some(v6) = host_port.strip_prefix('[') {
+        let (host, rest) = v6.split_once(']')?;
+        (host, rest.strip_prefix(':'))
+    } else {
+        match host_port.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (host_port, None),
+        }
+    };
+    let port = match port {
+        Some(port) => port.parse().ok()?,
+        None => default_port,
+    };
+    (!host.is_empty()).then(|| (host.to_string(), port))
+}
+
+/// The `nameserver` lines of a resolv.conf.
+pub fn parse_nameservers(resolv_conf: &str) -> Vec<IpAddr> {
+    resolv_conf
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            (words.next() == Some("nameserver"))
+                .then(|| words.next()?.split('%').next()?.parse().ok())
+                .flatten()
+        })
+        .collect()
+}
+
+impl BuildMirrors {
+    /// Variables every build step gets. The host's settings win over the app's,
+    /// since they are policy rather than preference.
+    pub fn env(&self) -> Vec<(&'static str, String)> {
+        let mut env = Vec::new();
+        if let Some(registry) = &self.npm_registry {
+            // npm and pnpm, Yarn 1, Yarn 2+
+            for key in [
+                "npm_config_registry",
+                "YARN_REGISTRY",
+                "YARN_NPM_REGISTRY_SERVER",
+            ] {
+                env.push((key, registry.clone()));
+            }
+        }
+        if let Some(index) = &self.pip_index {
+            for key in ["PIP_INDEX_URL", "UV_DEFAULT_INDEX"] {
+                env.push((key, index.clone()));
+            }
+        }
+        if let Some(proxy) = &self.proxy {
+            // Tools disagree on the case; cargo also has its own key
+            for key in [
+                "HTTP_PROXY",
+                "HTTPS_PROXY",
+                "http_proxy",
+                "https_proxy",
+                "CARGO_HTTP_PROXY",
+            ] {
+                env.push((key, proxy.clone()));
+            }
+        }
+        if let Some(no_proxy) = &self.no_proxy {
+            env.push(("NO_PROXY", no_proxy.clone()));
+            env.push(("no_proxy", no_proxy.clone()));
+        }
+        env
+    }
+
+    /// Replaces crates.io with the cargo mirror.
+    pub fn cargo_config(&self) -> Option<String> {
+        let registry = self.cargo_registry.as_ref()?;
+        let registry = if registry.starts_with("sparse+") {
+            registry.clone()
+        } else {
+            format!("sparse+{}", registry)
+        };
+        Some(format!(
+            "# Written by Kari for this host's cargo mirror\n\
+             [source.crates-io]\nreplace-with = \"{0}\"\n\n\
+             [source.{0}]\nregistry = \"{1}\"\n",
+            CARGO_MIRROR_SOURCE, registry
+        ))
+    }
+
+    /// Writes `cargo_config` into a checkout, unless the repo brings its own
+    /// cargo config, which is then left to take effect. Returns whether the
+    /// mirror applies. 🛡️ Nothing is written through a symlink: the checkout
+    /// is untrusted and this runs as root.
+    pub fn write_cargo_config(&self, checkout: &Path) -> Result<bool, String> {
+        let Some(content) = self.cargo_config() else {
+            return Ok(false);
+        };
+        let dir = checkout.join(".cargo");
+        match std::fs::symlink_metadata(&dir) {
+            Ok(meta) if !meta.is_dir() => return Ok(false),
+            Ok(_) => {}
+            Err(_) => std::fs::create_dir(&dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?,
+        }
+        let path = dir.join("config.toml");
+        for existing in [dir.join("config"), path.clone()] {
+            if let Ok(meta) = std::fs::symlink_metadata(&existing) {
+                // Ours from an earlier step of this build, or the repo's
+                return Ok(meta.is_file()
+                    && std::fs::read_to_string(&existing).is_ok_and(|c| c == content));
+            }
+        }
+        // create_new refuses anything that appeared in the meantime, links included
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(true)
+    }
+
+    /// Every (host, port) a build may reach under `mirrors_only`.
+    fn allowed_endpoints(&self) -> Vec<(String, u16)> {
+        [
+            &self.npm_registry,
+            &self.pip_index,
+            &self.cargo_registry,
+            &self.proxy,
+        ]
+        .into_iter()
+        .flatten()
+        .filter_map(|url| url_host(url))
+        .collect()
+    }
+
+    /// The addresses a `mirrors_only` build unit may reach: the mirrors and
+    /// proxy as they resolve right now, plus the DNS resolvers. None when
+    /// builds are not restricted.
+    pub async fn allowed_addresses(
+        &self,
+        resolv_conf: &str,
+    ) -> Result<Option<Vec<IpAddr>>, String> {
+        if !self.mirrors_only {
+            return Ok(None);
+        }
+        let mut addresses: BTreeSet<IpAddr> = parse_nameservers(resolv_conf).into_iter().collect();
+        for (host, port) in self.allowed_endpoints() {
+            let resolved = tokio::net::lookup_host((host.as_str(), port))
+                .await
+                .map_err(|e| format!("Cannot resolve build mirror {}: {}", host, e))?;
+            addresses.extend(resolved.map(|addr| addr.ip()));
+        }
+        Ok(Some(addresses.into_iter().collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mirrors() -> BuildMirrors {
+        BuildMirrors {
+            npm_registry: Some("https://artifacts.corp/api/npm/npm/".into()),
+            pip_index: Some("https://artifacts.corp/api/pypi/pypi/simple".into()),
+            cargo_registry: Some("https://artifacts.corp/api/cargo/crates-io/index/".into()),
+            proxy: Some("http://proxy.corp:3128".into()),
+            no_proxy: Some("localhost,.corp".into()),
+            mirrors_only: true,
+        }
+    }
+
+    #[test]
+    fn mirror_hosts_and_resolvers_are_what_a_build_may_reach() {
+        assert_eq!(
+            url_host("https://user:pw@artifacts.corp/api/npm/"),
+            Some(("artifacts.corp".into(), 443))
+        );
+        assert_eq!(
+            url_host("http://proxy.corp:3128"),
+            Some(("proxy.corp".into(), 3128))
+        );
+        assert_eq!(
+            url_host("sparse+https://[2001:db8::5]:8443/index/"),
+            Some(("2001:db8::5".into(), 8443))
+        );
+        assert_eq!(url_host("ftp://files.corp/"), None);
+
+        assert_eq!(
+            mirrors().allowed_endpoints(),
+            [
+                ("artifacts.corp".to_string(), 443),
+                ("artifacts.corp".to_string(), 443),
+                ("artifacts.corp".to_string(), 443),
+                ("proxy.corp".to_string(), 3128),
+            ]
+        );
+        assert_eq!(
+            parse_nameservers(
+                "# generated\nnameserver 10.0.0.2\nnameserver fe80::1%eth0\nsearch corp\n"
+            ),
+            [
+                "10.0.0.2".parse::<IpAddr>().unwrap(),
+                "fe80::1".parse().unwrap()
+            ]
+        );
+
+        let env = mirrors().env();
+        assert!(env.contains(&(
+            "npm_config_registry",
+            "https://artifacts.corp/api/npm/npm/".into()
+        )));
+        assert!(env.contains(&("https_proxy", "http://proxy.corp:3128".into())));
+        assert!(BuildMirrors::default().env().is_empty());
+    }
+
+    #[test]
+    fn cargo_mirrors_never_override_a_repos_own_config() {
+        let checkout = tempfile::tempdir().unwrap();
+        assert!(mirrors().write_cargo_config(checkout.path()).unwrap());
+        let written = std::fs::read_to_string(checkout.path().join(".cargo/config.toml")).unwrap();
+        assert!(
+            written.contains(
+                "registry = \"sparse+https://artifacts.corp/api/cargo/crates-io/index/\""
+            )
+        );
+        // Later steps of the same build find their own file
+        assert!(mirrors().write_cargo_config(checkout.path()).unwrap());
+
+        let own = tempfile::tempdir().unwrap();
+        std::fs::create_dir(own.path().join(".cargo")).unwrap();
+        std::fs::write(own.path().join(".cargo/config"), "[build]\njobs = 2\n").unwrap();
+        assert!(!mirrors().write_cargo_config(own.path()).unwrap());
+
+        let linked = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink("/etc", linked.path().join(".cargo")).unwrap();
+        assert!(!mirrors().write_cargo_config(linked.path()).unwrap());
+        assert!(!Path::new("/etc/config.toml").exists());
+    }
+}
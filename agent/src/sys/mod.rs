@@ -1,16 +1,74 @@
 // 🛡️ Zero-Trust Architecture: Modules are private, traits and managers are public.
 
+pub mod acme; // Let's Encrypt / ACME issuance over HTTP-01
+pub mod app_cron; // App-owned job timers
+pub mod artifacts; // Content-addressed built releases
+pub mod autostart; // Boot-survival checks & repair
 pub mod build; // Build orchestration
+pub mod build_log; // Persisted, size-capped deploy logs
+pub mod build_queue; // Host-wide build slot budget & FIFO queue
+pub mod bundle; // Signed, sealed portable archives
+pub mod caller; // Authenticated peer identity, per connection
+pub mod capacity; // Pre-flight disk & memory checks
+pub mod certificates; // Shared SAN certificates & their references
+pub mod circuit; // Crash-loop circuit breaking
 pub mod cleanup; // Resource hygiene
+pub mod config_bundle; // Signed offline config specs
+pub mod disk_health; // SMART & kernel I/O error monitoring
+pub mod dns; // Stub resolver for issuance pre-checks
+pub mod env_file; // Dotenv rendering for apps
+pub mod executor; // Command execution seam (mockable in tests)
+pub mod facts; // OS, hardware & stack inventory
 pub mod firewall; // Network policy enforcement
+pub mod firewall_log; // Denied-traffic logging (kari-firewall journal)
 pub mod git; // Source control
+pub mod heartbeat; // Signed outbound liveness reports
+pub mod host_ops; // Allowlisted named host commands (fixed argv)
+pub mod http_probe; // Loopback HTTP GETs (self-test, deploy health gate)
+pub mod image_gc; // Container image hygiene
+pub mod issuance; // DNS & CAA checks before certificate issuance
 pub mod jail; // User namespacing
-pub mod logs; // Log management
+pub mod journal; // App output from the systemd journal
+pub mod key_rotation; // Maximum certificate key age
+pub mod known_hosts; // Pinned SSH host keys for clones
+pub mod log_forward; // Per-app journals shipped to syslog or Loki
+pub mod log_retention; // Per-class log retention & erasure
+pub mod logs; // logrotate for the log files apps write
+pub mod mac; // Per-app AppArmor profiles / SELinux contexts
+pub mod mail; // Transactional mail relay
+pub mod managed_configs; // Read-only audit export of generated files
+pub mod migration; // App export/import between hosts
+pub mod mirrors; // Dependency mirrors & proxy for builds
+pub mod network; // Interfaces & listening sockets
+pub mod notify; // Crash, job, cert & disk alerts to Slack, PagerDuty, mail
+pub mod operations; // Long-running operation tracking
+pub mod packages;
+pub mod paging; // Cursor pages & field filters for list RPCs // Host package manager argv (intents only)
+pub mod patching; // Unattended OS upgrades in a maintenance window
+pub mod pipeline; // Concurrent stages within one deploy
+pub mod placement; // Load scores for multi-host placement
+pub mod podman; // Rootless container units
+pub mod privilege; // Rootless operation: sudo/polkit grants, RPC gating
+pub mod processes; // Process trees inside app cgroups
 pub mod proxy; // Ingress (Nginx/Apache)
+pub mod reboot; // Kernel advisory & drained reboots
+pub mod satellite; // Agentless hosts driven over SSH
+pub mod sbom; // CycloneDX SBOMs from lockfiles
+pub mod scan; // Post-clone secret & malware scanning
 pub mod scheduler; // Cron/Timer scheduling
+pub mod secret_store; // At-rest secrets (0600, root-only)
 pub mod secrets; // Memory hygiene (ProviderCredential)
+pub mod selftest; // Disposable-app host self-test
+pub mod sftp; // SFTP-only logins into an app's shared directory
+pub mod snapshot; // Control-layer backup & restore
+pub mod source_archive; // Tarball sources for DeployArtifact
 pub mod ssl; // Certificate management
+pub mod state; // Durable agent records
 pub mod systemd; // Process jailing
+pub mod tamper; // Watch on kari-owned config files
+pub mod tenant; // Per-tenant quota slices
+pub mod timesync; // NTP via chrony or systemd-timesyncd
+pub mod tls_policy; // Fleet-wide TLS presets for HTTPS vhosts
 pub mod traits; // Global contracts
 
 // 🏗️ SLA Re-exports
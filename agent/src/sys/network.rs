@@ -0,0 +1,296 @@
+// agent/src/sys/network.rs
+//
+// 🛡️ SOLID: Single-Responsibility — What the host's network looks like from
+// the inside: interfaces with their addresses, and every listening socket
+// with the process and systemd unit holding it.
+//
+// Sockets come from /proc/net/{tcp,tcp6,udp,udp6}; their inodes are matched
+// against /proc/<pid>/fd links, and the unit is read from the holder's cgroup.
+// Both are plain procfs reads, so no `ss` output format has to be trusted.
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+
+use crate::sys::traits::{Listener, NetworkInspector, NetworkInterface, Protocol};
+
+/// TCP_LISTEN and TCP_CLOSE (an unconnected, bound UDP socket) in /proc/net.
+const TCP_LISTEN: &str = "0A";
+const UDP_UNCONNECTED: &str = "07";
+
+/// One socket line from /proc/net/*, before its owner is known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ProcSocket {
+    address: IpAddr,
+    port: u16,
+    inode: u64,
+}
+
+/// Decodes `0100007F:1F90` (IPv4) or the 32-digit IPv6 form. The kernel prints
+/// each 32-bit word in host byte order.
+fn parse_endpoint(field: &str) -> Option<(IpAddr, u16)> {
+    let (addr, port) = field.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let mut bytes = Vec::with_capacity(16);
+    for i in (0..addr.len()).step_by(8) {
+        let word = u32::from_str_radix(addr.get(i..i + 8)?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+    let address = match bytes.len() {
+        4 => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?)),
+        16 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?)),
+        _ => return None,
+    };
+    Some((address, port))
+}
+
+/// Listening sockets in one /proc/net table. Malformed lines are skipped.
+fn parse_proc_net(content: &str, protocol: Protocol) -> Vec<ProcSocket> {
+    let wanted = match protocol {
+        Protocol::Udp => UDP_UNCONNECTED,
+        _ => TCP_LISTEN,
+    };
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (address, port) = parse_endpoint(fields.get(1)?)?;
+            let (_, remote_port) = parse_endpoint(fields.get(2)?)?;
+            if *fields.get(3)? != wanted || remote_port != 0 {
+                return None;
+            }
+            Some(ProcSocket {
+                address,
+                port,
+                inode: fields.get(9)?.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// The innermost service in a `/proc/<pid>/cgroup` file, so a container
+/// payload nested under `kari-shop.example.com.service` belongs to that unit.
+/// Processes outside any service (login sessions) report their scope.
+pub(crate) fn unit_from_cgroup(content: &str) -> Option<String> {
+    let path = content
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .or_else(|| content.lines().next()?.splitn(3, ':').nth(2))?;
+    let innermost = |suffix: &str| {
+        path.split('/')
+            .rev()
+            .find(|part| part.ends_with(suffix))
+            .map(str::to_string)
+    };
+    innermost(".service").or_else(|| innermost(".scope"))
+}
+
+/// Socket inode -> pid, from every readable /proc/<pid>/fd.
+fn socket_owners(proc_root: &Path) -> HashMap<u64, u32> {
+    let mut owners = HashMap::new();
+    let Ok(entries) = std::fs::read_dir(proc_root) else {
+        return owners;
+    };
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|n| n.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        // Processes exit mid-scan; whatever cannot be read is skipped
+        let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            let Ok(target) = std::fs::read_link(fd.path()) else {
+                continue;
+            };
+            if let Some(inode) = target
+                .to_str()
+                .and_then(|t| t.strip_prefix("socket:["))
+                .and_then(|t| t.strip_suffix(']'))
+                .and_then(|t| t.parse().ok())
+            {
+                owners.entry(inode).or_insert(pid);
+            }
+        }
+    }
+    owners
+}
+
+fn read_listeners(proc_root: &Path) -> Result<Vec<Listener>, String> {
+    let mut sockets = Vec::new();
+    for (table, protocol) in [
+        ("tcp", Protocol::Tcp),
+        ("tcp6", Protocol::Tcp),
+        ("udp", Protocol::Udp),
+        ("udp6", Protocol::Udp),
+    ] {
+        // A host without IPv6 has no tcp6/udp6 tables
+        let Ok(content) = std::fs::read_to_string(proc_root.join("net").join(table)) else {
+            continue;
+        };
+        sockets.extend(
+            parse_proc_net(&content, protocol)
+                .into_iter()
+                .map(|s| (protocol, s)),
+        );
+    }
+    if sockets.is_empty() && !proc_root.join("net/tcp").exists() {
+        return Err(format!("{}/net is not readable", proc_root.display()));
+    }
+
+    let owners = socket_owners(proc_root);
+    let mut listeners: Vec<Listener> = sockets
+        .into_iter()
+        .map(|(protocol, socket)| {
+            let pid = owners.get(&socket.inode).copied();
+            let read = |file: &str| {
+                pid.and_then(|pid| {
+                    std::fs::read_to_string(proc_root.join(pid.to_string()).join(file)).ok()
+                })
+            };
+            Listener {
+                protocol,
+                address: socket.address.to_string(),
+                port: socket.port,
+                pid,
+                process: read("comm").map(|c| c.trim().to_string()),
+                unit: read("cgroup").as_deref().and_then(unit_from_cgroup),
+            }
+        })
+        .collect();
+    listeners.sort_by(|a, b| {
+        (a.port, a.protocol == Protocol::Udp, &a.address).cmp(&(
+            b.port,
+            b.protocol == Protocol::Udp,
+            &b.address,
+        ))
+    });
+    listeners.dedup();
+    Ok(listeners)
+}
+
+fn read_interfaces() -> Result<Vec<NetworkInterface>, String> {
+    use nix::ifaddrs::getifaddrs;
+    use nix::net::if_::InterfaceFlags;
+
+    let mut by_name: Vec<NetworkInterface> = Vec::new();
+    for ifaddr in getifaddrs().map_err(|e| format!("getifaddrs failed: {}", e))? {
+        let index = match by_name.iter().position(|i| i.name == ifaddr.interface_name) {
+            Some(index) => index,
+            None => {
+                by_name.push(NetworkInterface {
+                    name: ifaddr.interface_name.clone(),
+                    up: ifaddr.flags.contains(InterfaceFlags::IFF_UP),
+                    addresses: Vec::new(),
+                });
+                by_name.len() - 1
+            }
+        };
+        let Some(address) = ifaddr.address else {
+            continue;
+        };
+        let prefix = |mask: Option<&[u8]>| -> u32 {
+            mask.map(|m| m.iter().map(|b| b.count_ones()).sum())
+                .unwrap_or(0)
+        };
+        let cidr = if let Some(v4) = address.as_sockaddr_in() {
+            let mask = ifaddr
+                .netmask
+                .as_ref()
+                .and_then(|m| m.as_sockaddr_in())
+                .map(|m| m.ip().octets());
+            format!("{}/{}", v4.ip(), prefix(mask.as_ref().map(|m| &m[..])))
+        } else if let Some(v6) = address.as_sockaddr_in6() {
+            let mask = ifaddr
+                .netmask
+                .as_ref()
+                .and_then(|m| m.as_sockaddr_in6())
+                .map(|m| m.ip().octets());
+            format!("{}/{}", v6.ip(), prefix(mask.as_ref().map(|m| &m[..])))
+        } else {
+            // Link-layer (AF_PACKET) entries carry no IP address
+            continue;
+        };
+        by_name[index].addresses.push(cidr);
+    }
+    Ok(by_name)
+}
+
+pub struct ProcNetworkInspector;
+
+#[async_trait]
+impl NetworkInspector for ProcNetworkInspector {
+    async fn interfaces(&self) -> Result<Vec<NetworkInterface>, String> {
+        tokio::task::spawn_blocking(read_interfaces)
+            .await
+            .map_err(|e| format!("Interface scan panicked: {}", e))?
+    }
+
+    async fn listeners(&self) -> Result<Vec<Listener>, String> {
+        tokio::task::spawn_blocking(|| read_listeners(Path::new("/proc")))
+            .await
+            .map_err(|e| format!("Socket scan panicked: {}", e))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proc_net_tables_yield_listening_sockets_only() {
+        let tcp = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n\
+   0: 0100007F:0BB8 00000000:0000 0A 00000000:00000000 00:00000000 00000000   998        0 41234 1 0000000000000000 100 0 0 10 0\n\
+   1: 0100007F:0BB8 0100007F:D2F0 01 00000000:00000000 00:00000000 00000000   998        0 41999 1 0000000000000000 20 4 30 10 -1\n\
+   2: 00000000:0016 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1777 1 0000000000000000 100 0 0 10 0\n\
+   3: garbage\n";
+        let sockets = parse_proc_net(tcp, Protocol::Tcp);
+        assert_eq!(
+            sockets,
+            [
+                ProcSocket {
+                    address: "127.0.0.1".parse().unwrap(),
+                    port: 3000,
+                    inode: 41234,
+                },
+                ProcSocket {
+                    address: "0.0.0.0".parse().unwrap(),
+                    port: 22,
+                    inode: 1777,
+                },
+            ]
+        );
+
+        let tcp6 = "  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n\
+   0: 00000000000000000000000001000000:0050 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 2020 1 0000000000000000 100 0 0 10 0\n";
+        let sockets = parse_proc_net(tcp6, Protocol::Tcp);
+        assert_eq!(sockets[0].address, "::1".parse::<IpAddr>().unwrap());
+        assert_eq!(sockets[0].port, 80);
+    }
+
+    #[test]
+    fn the_innermost_service_owns_a_process() {
+        assert_eq!(
+            unit_from_cgroup("0::/system.slice/kari-shop.example.com.service\n").as_deref(),
+            Some("kari-shop.example.com.service")
+        );
+        assert_eq!(
+            unit_from_cgroup(
+                "0::/system.slice/kari-api.example.com.service/libpod-payload-1234/init.scope\n"
+            )
+            .as_deref(),
+            Some("kari-api.example.com.service")
+        );
+        assert_eq!(
+            unit_from_cgroup("0::/user.slice/user-1000.slice/session-4.scope\n").as_deref(),
+            Some("session-4.scope")
+        );
+        assert_eq!(unit_from_cgroup("0::/\n"), None);
+    }
+}
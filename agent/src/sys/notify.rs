@@ -0,0 +1,732 @@
+// agent/src/sys/notify.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Tells people when something broke.
+//
+// Crash loops, failed jobs, failed key rotations, disk pressure and failing
+// disks become a `Notice`, and the routes in agent.toml decide which channels
+// each kind goes to. Channels are `Notifier`s: webhooks (Slack, PagerDuty Events v2, plain
+// JSON) over HTTPS, and mail submitted over SMTP to the host's MTA (the null
+// client ConfigureMailRelay sets up). A channel that fails is logged and the
+// others still get the notice.
+//
+//   [notify.channels.ops]
+//   type = "slack"                       # slack | webhook | pagerduty | smtp
+//   url = "https://hooks.slack.com/services/..."
+//
+//   [notify.channels.mail]
+//   type = "smtp"
+//   from = "kari@web-1.example.com"
+//   to = ["ops@example.com"]
+//
+//   [notify.routes]
+//   crash = ["ops", "mail"]              # crash | job_failed | cert_renewal_failed | disk_pressure | disk_health
+//
+// agent.toml holds webhook URLs and routing keys, so it must be owned by the
+// agent and mode 0600 (see `AgentToml` in config.rs). Only the [notify.*]
+// tables are read from it here. Webhook URLs and headers reach curl on its
+// stdin, never its argv, which every user on the host can read.
+
+use async_trait::async_trait;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::process::Command;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+use crate::config::{AgentToml, ChannelToml, Strings};
+
+use crate::sys::circuit::CircuitEvent;
+use crate::sys::disk_health::DiskHealthEvent;
+use crate::sys::key_rotation::KeyRotationEvent;
+use crate::sys::state::{JobRecord, NS_JOBS, list_records};
+use crate::sys::traits::{JobScheduler, StateStore};
+
+const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+const DEFAULT_SMTP_SERVER: &str = "127.0.0.1:25";
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoticeKind {
+    Crash,             // A unit hit its start limit; the circuit breaker opened
+    JobFailed,         // A scheduled job's last run failed
+    CertRenewalFailed, // A key past its maximum age could not get a successor
+    DiskPressure,      // The web root's filesystem crossed the GC threshold
+    DiskHealth,        // SMART or the kernel log reported a disk going bad
+}
+
+impl NoticeKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "crash" => Some(Self::Crash),
+            "job_failed" => Some(Self::JobFailed),
+            "cert_renewal_failed" => Some(Self::CertRenewalFailed),
+            "disk_pressure" => Some(Self::DiskPressure),
+            "disk_health" => Some(Self::DiskHealth),
+            _ => None,
+        }
+    }
+
+    /// PagerDuty's severity scale.
+    fn severity(self) -> &'static str {
+        match self {
+            Self::Crash | Self::DiskHealth => "critical",
+            Self::JobFailed | Self::CertRenewalFailed => "error",
+            Self::DiskPressure => "warning",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Notice {
+    pub kind: NoticeKind,
+    pub subject: String, // The app, job, domain or path it is about
+    pub summary: String, // One line, plain ASCII
+    pub detail: String,
+    pub host: String, // Filled in on dispatch
+    pub at: i64,      // Unix seconds
+}
+
+impl Notice {
+    pub fn new(kind: NoticeKind, subject: &str, summary: String, detail: String) -> Self {
+        Self {
+            kind,
+            subject: subject.to_string(),
+            summary,
+            detail,
+            host: String::new(),
+            at: chrono::Utc::now().timestamp(),
+        }
+    }
+
+    /// Only a breaker opening is news; closing it was somebody's decision.
+    pub fn from_circuit(event: &CircuitEvent) -> Option<Self> {
+        event.open.then(|| {
+            Self::new(
+                NoticeKind::Crash,
+                &event.app_id,
+                format!(
+                    "{} crash-looped and is serving the maintenance page",
+                    event.domain_name
+                ),
+                event.detail.clone(),
+            )
+        })
+    }
+
+    /// Events with a CSR are the rotation working; only a failed staging is news.
+    pub fn from_key_rotation(event: &KeyRotationEvent) -> Option<Self> {
+        event.csr_pem.is_empty().then(|| {
+            Self::new(
+                NoticeKind::CertRenewalFailed,
+                &event.domain_name,
+                format!("Key rotation for {} failed", event.domain_name),
+                event.detail.clone(),
+            )
+        })
+    }
+
+    /// A disk that recovered (SMART counters only ever grow) is not news.
+    pub fn from_disk_health(event: &DiskHealthEvent) -> Option<Self> {
+        event.failing.then(|| {
+            Self::new(
+                NoticeKind::DiskHealth,
+                &event.device,
+                format!("Disk {} is failing", event.device),
+                event.detail.clone(),
+            )
+        })
+    }
+}
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, notice: &Notice) -> Result<(), String>;
+}
+
+// ==============================================================================
+// Webhooks
+// ==============================================================================
+
+/// Appends one curl config file value: double-quoted, with the escapes curl's
+/// config parser undoes.
+fn curl_quote(quoted: &mut Vec<u8>, value: &[u8]) {
+    quoted.push(b'"');
+    for &byte in value {
+        match byte {
+            b'"' | b'\\' => quoted.extend([b'\\', byte]),
+            b'\n' => quoted.extend(b"\\n"),
+            b'\r' => quoted.extend(b"\\r"),
+            b'\t' => quoted.extend(b"\\t"),
+            0x0b => quoted.extend(b"\\v"),
+            byte => quoted.push(byte),
+        }
+    }
+    quoted.push(b'"');
+}
+
+/// The `--config -` curl reads for one POST: URL, headers and body.
+fn curl_config(url: &str, body: &[u8], headers: &[String]) -> Zeroizing<Vec<u8>> {
+    let mut config = Zeroizing::new(Vec::new());
+    let mut option = |name: &str, value: &[u8]| {
+        config.extend(name.as_bytes());
+        config.extend(b" = ");
+        curl_quote(&mut config, value);
+        config.push(b'\n');
+    };
+    option("url", url.as_bytes());
+    option("header", b"Content-Type: application/json");
+    for header in headers {
+        option("header", header.as_bytes());
+    }
+    option("data-binary", body);
+    config
+}
+
+/// POSTs `body` with the host's curl, HTTPS only. Err for anything but a 2xx.
+pub async fn https_post(
+    url: &str,
+    body: &[u8],
+    headers: &[String],
+    timeout: Duration,
+) -> Result<(), String> {
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--proto", "=https"])
+        .arg("--max-time")
+        .arg(timeout.as_secs().max(1).to_string())
+        .args(["--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run curl: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(&curl_config(url, body, headers))
+            .await
+            .map_err(|e| format!("Failed to feed curl: {}", e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|e| format!("Failed to run curl: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebhookFormat {
+    Slack,                             // Incoming webhook: {"text": ...}
+    PagerDuty { routing_key: String }, // Events API v2, deduplicated per kind and subject
+    Json,                              // The Notice itself
+}
+
+pub struct WebhookNotifier {
+    pub url: String,
+    pub format: WebhookFormat,
+}
+
+impl WebhookNotifier {
+    pub fn body(&self, notice: &Notice) -> serde_json::Value {
+        match &self.format {
+            WebhookFormat::Slack => serde_json::json!({
+                "text": format!("*[{}] {}*\n{}", notice.host, notice.summary, notice.detail),
+            }),
+            WebhookFormat::PagerDuty { routing_key } => serde_json::json!({
+                "routing_key": routing_key,
+                "event_action": "trigger",
+                "dedup_key": format!(
+                    "kari/{}/{}/{}",
+                    notice.host,
+                    serde_json::to_value(notice.kind).unwrap_or_default().as_str().unwrap_or(""),
+                    notice.subject
+                ),
+                "payload": {
+                    "summary": notice.summary,
+                    "source": notice.host,
+                    "severity": notice.kind.severity(),
+                    "custom_details": { "detail": notice.detail },
+                },
+            }),
+            WebhookFormat::Json => serde_json::to_value(notice).unwrap_or_default(),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, notice: &Notice) -> Result<(), String> {
+        let body = serde_json::to_vec(&self.body(notice)).map_err(|e| e.to_string())?;
+        https_post(&self.url, &body, &[], SEND_TIMEOUT).await
+    }
+}
+
+// ==============================================================================
+// SMTP
+// ==============================================================================
+
+/// Mail submitted over plain SMTP to `server`, by default the local MTA. It
+/// relays from there, so no credential or TLS setup lives here.
+pub struct SmtpNotifier {
+    pub server: String, // host:port
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+/// 🛡️ Zero-Trust: Addresses go into SMTP commands and headers verbatim.
+fn validate_address(address: &str) -> Result<(), String> {
+    let valid = address
+        .split_once('@')
+        .is_some_and(|(local, domain)| !local.is_empty() && !domain.is_empty())
+        && address
+            .chars()
+            .all(|c| c.is_ascii_graphic() && !matches!(c, '<' | '>' | ',' | '"' | '\\'));
+    if !valid {
+        return Err(format!("Invalid mail address: '{}'", address));
+    }
+    Ok(())
+}
+
+/// Reads one (possibly multi-line) reply and checks its code class.
+async fn smtp_reply(
+    reader: &mut BufReader<tokio::net::tcp::OwnedReadHalf>,
+    expect: char,
+) -> Result<(), String> {
+    loop {
+        let mut line = String::new();
+        if reader
+            .read_line(&mut line)
+            .await
+            .map_err(|e| format!("SMTP read failed: {}", e))?
+            == 0
+        {
+            return Err("SMTP server closed the connection".to_string());
+        }
+        if !line.starts_with(expect) {
+            return Err(format!("SMTP server said: {}", line.trim_end()));
+        }
+        // "250-..." continues, "250 ..." ends
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok(());
+        }
+    }
+}
+
+impl SmtpNotifier {
+    pub fn message(&self, notice: &Notice) -> String {
+        let mut body = String::new();
+        for line in format!("{}\r\n\r\n{}", notice.summary, notice.detail).lines() {
+            // Dot-stuffing (RFC 5321 4.5.2)
+            if line.starts_with('.') {
+                body.push('.');
+            }
+            body.push_str(line);
+            body.push_str("\r\n");
+        }
+        format!(
+            "From: {}\r\nTo: {}\r\nSubject: [kari] {}: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}",
+            self.from,
+            self.to.join(", "),
+            notice.host,
+            notice.summary.replace(['\r', '\n'], " "),
+            chrono::Utc::now().to_rfc2822(),
+            body
+        )
+    }
+
+    async fn send(&self, notice: &Notice) -> Result<(), String> {
+        let stream = TcpStream::connect(&self.server)
+            .await
+            .map_err(|e| format!("SMTP connect to {} failed: {}", self.server, e))?;
+        let (read, mut write) = stream.into_split();
+        let mut reader = BufReader::new(read);
+        smtp_reply(&mut reader, '2').await?;
+
+        let helo = if notice.host.is_empty() {
+            "localhost"
+        } else {
+            notice.host.as_str()
+        };
+        let mut commands = vec![
+            (format!("EHLO {}\r\n", helo), '2'),
+            (format!("MAIL FROM:<{}>\r\n", self.from), '2'),
+        ];
+        for to in &self.to {
+            commands.push((format!("RCPT TO:<{}>\r\n", to), '2'));
+        }
+        commands.push(("DATA\r\n".to_string(), '3'));
+        commands.push((format!("{}.\r\n", self.message(notice)), '2'));
+        for (command, expect) in commands {
+            write
+                .write_all(command.as_bytes())
+                .await
+                .map_err(|e| format!("SMTP write failed: {}", e))?;
+            smtp_reply(&mut reader, expect).await?;
+        }
+        let _ = write.write_all(b"QUIT\r\n").await;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for SmtpNotifier {
+    async fn notify(&self, notice: &Notice) -> Result<(), String> {
+        tokio::time::timeout(SEND_TIMEOUT, self.send(notice))
+            .await
+            .map_err(|_| format!("SMTP delivery to {} timed out", self.server))?
+    }
+}
+
+// ==============================================================================
+// agent.toml
+// ==============================================================================
+
+/// The channel named `name`, from its table.
+fn build_channel(name: &str, table: &ChannelToml) -> Result<Arc<dyn Notifier>, String> {
+    let required = |key: &str, value: &Option<String>| {
+        value
+            .clone()
+            .ok_or_else(|| format!("channel {}: {} is required", name, key))
+    };
+    let https = |url: String| {
+        if url.starts_with("https://") {
+            Ok(url)
+        } else {
+            Err(format!("channel {}: url must be https://", name))
+        }
+    };
+    let kind = table.kind.as_str();
+    Ok(match kind {
+        "" => return Err(format!("channel {}: type is required", name)),
+        "slack" | "webhook" => Arc::new(WebhookNotifier {
+            url: https(required("url", &table.url)?)?,
+            format: if kind == "slack" {
+                WebhookFormat::Slack
+            } else {
+                WebhookFormat::Json
+            },
+        }),
+        "pagerduty" => Arc::new(WebhookNotifier {
+            url: https(
+                table
+                    .url
+                    .clone()
+                    .unwrap_or_else(|| PAGERDUTY_EVENTS_URL.to_string()),
+            )?,
+            format: WebhookFormat::PagerDuty {
+                routing_key: required("routing_key", &table.routing_key)?,
+            },
+        }),
+        "smtp" => {
+            let from = required("from", &table.from)?;
+            let to = table.to.clone().map(Strings::into_vec).unwrap_or_default();
+            if to.is_empty() {
+                return Err(format!("channel {}: to is required", name));
+            }
+            for address in std::iter::once(&from).chain(&to) {
+                validate_address(address).map_err(|e| format!("channel {}: {}", name, e))?;
+            }
+            Arc::new(SmtpNotifier {
+                server: table
+                    .server
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_SMTP_SERVER.to_string()),
+                from,
+                to,
+            })
+        }
+        other => return Err(format!("channel {}: unknown type '{}'", name, other)),
+    })
+}
+
+// ==============================================================================
+// Routing
+// ==============================================================================
+
+pub struct Notifications {
+    host: String,
+    channels: BTreeMap<String, Arc<dyn Notifier>>,
+    routes: BTreeMap<NoticeKind, Vec<String>>,
+}
+
+impl Notifications {
+    /// Reads the [notify.*] tables of agent.toml. `extra` channels may be
+    /// routed to as if they were declared there. Ok(None) when the file does
+    /// not exist or routes nothing.
+    pub fn load(
+        path: &Path,
+        host: String,
+        extra: &BTreeMap<String, Arc<dyn Notifier>>,
+    ) -> Result<Option<Self>, String> {
+        let Some(toml) = AgentToml::load(path)? else {
+            return Ok(None);
+        };
+
+        let mut channels = extra.clone();
+        for (name, settings) in &toml.notify.channels {
+            channels.insert(name.clone(), build_channel(name, settings)?);
+        }
+        let mut routes = BTreeMap::new();
+        for (kind, targets) in toml.notify.routes {
+            let kind = NoticeKind::parse(&kind)
+                .ok_or_else(|| format!("notify.routes: unknown event type '{}'", kind))?;
+            let targets = targets.into_vec();
+            if let Some(missing) = targets.iter().find(|t| !channels.contains_key(*t)) {
+                return Err(format!("notify.routes: no channel named '{}'", missing));
+            }
+            routes.insert(kind, targets);
+        }
+        if routes.values().all(Vec::is_empty) {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            host,
+            channels,
+            routes,
+        }))
+    }
+
+    /// Sends `notice` to every channel its kind is routed to.
+    pub async fn dispatch(&self, mut notice: Notice) {
+        let Some(targets) = self.routes.get(&notice.kind) else {
+            return;
+        };
+        notice.host = self.host.clone();
+        for name in targets {
+            if let Some(channel) = self.channels.get(name)
+                && let Err(e) = channel.notify(&notice).await
+            {
+                warn!("📣 Notification to {} failed: {}", name, e);
+            }
+        }
+    }
+
+    /// Routes notices as the sources raise them, for as long as the agent runs.
+    pub async fn run(
+        self,
+        mut circuit: broadcast::Receiver<CircuitEvent>,
+        mut key_rotation: broadcast::Receiver<KeyRotationEvent>,
+        mut disk_health: broadcast::Receiver<DiskHealthEvent>,
+        mut notices: broadcast::Receiver<Notice>,
+    ) {
+        info!(
+            "📣 Notifications routed for {:?}",
+            self.routes.keys().collect::<Vec<_>>()
+        );
+        loop {
+            let notice = tokio::select! {
+                event = circuit.recv() => match event {
+                    Ok(event) => Notice::from_circuit(&event),
+                    Err(broadcast::error::RecvError::Lagged(_)) => None,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                event = key_rotation.recv() => match event {
+                    Ok(event) => Notice::from_key_rotation(&event),
+                    Err(broadcast::error::RecvError::Lagged(_)) => None,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                event = disk_health.recv() => match event {
+                    Ok(event) => Notice::from_disk_health(&event),
+                    Err(broadcast::error::RecvError::Lagged(_)) => None,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                notice = notices.recv() => match notice {
+                    Ok(notice) => Some(notice),
+                    Err(broadcast::error::RecvError::Lagged(_)) => None,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            };
+            if let Some(notice) = notice {
+                self.dispatch(notice).await;
+            }
+        }
+    }
+}
+
+/// Raises a JobFailed notice when a job's last run turns from passing to
+/// failed; a job that keeps failing is reported once.
+pub async fn watch_failed_jobs(
+    state: Arc<dyn StateStore>,
+    scheduler: Arc<dyn JobScheduler>,
+    interval: Duration,
+    notices: broadcast::Sender<Notice>,
+) {
+    let mut ticker = tokio::time::interval(interval.max(Duration::from_millis(10)));
+    let mut failed = BTreeSet::new();
+    loop {
+        ticker.tick().await;
+        let jobs: Vec<JobRecord> = match list_records(state.as_ref(), NS_JOBS).await {
+            Ok(jobs) => jobs,
+            Err(e) => {
+                warn!("📣 Job failure check failed: {}", e);
+                continue;
+            }
+        };
+        let mut now_failed = BTreeSet::new();
+        for job in jobs {
+            if !scheduler.job_failed(&job.job_name).await.unwrap_or(false) {
+                continue;
+            }
+            if !failed.contains(&job.job_name) {
+                let _ = notices.send(Notice::new(
+                    NoticeKind::JobFailed,
+                    &job.job_name,
+                    format!("Job {} failed", job.job_name),
+                    format!(
+                        "Last run of {} ({}) exited unsuccessfully; see journalctl -u kari-job-{}.service",
+                        job.binary, job.schedule, job.job_name
+                    ),
+                ));
+            }
+            now_failed.insert(job.job_name);
+        }
+        failed = now_failed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notify_tables_parse_and_refuse_what_they_cannot_use() {
+        let toml = AgentToml::parse(
+            r#"
+# Fleet defaults
+[notify.channels.ops]
+type = "slack"   # comment
+url = "https://hooks.slack.com/services/T0/B0/x\"y"
+
+[notify.routes]
+crash = [ "ops", 'mail' ]
+disk_pressure = []
+job_failed = "ops"
+"#,
+        )
+        .unwrap();
+        let ops = &toml.notify.channels["ops"];
+        assert_eq!(
+            ops.url.as_deref(),
+            Some("https://hooks.slack.com/services/T0/B0/x\"y")
+        );
+        assert!(build_channel("ops", ops).is_ok());
+        let routes = &toml.notify.routes;
+        assert_eq!(
+            routes["crash"].clone().into_vec(),
+            ["ops".to_string(), "mail".to_string()]
+        );
+        assert!(routes["disk_pressure"].clone().into_vec().is_empty());
+        assert_eq!(routes["job_failed"].clone().into_vec(), ["ops".to_string()]);
+
+        for bad in [
+            "timeout = 10",
+            "[notify.channels.ops]\ntype = \"slack\"\ntype = \"smtp\"",
+            "[notify.channels.ops]\nurl = \"https://x\" trailing",
+            "[notify.channels.ops]\nurls = \"https://x\"",
+            "[[notify.channels]]",
+        ] {
+            assert!(AgentToml::parse(bad).is_err(), "{}", bad);
+        }
+
+        let smtp = ChannelToml {
+            kind: "smtp".into(),
+            from: Some("kari@example.com".into()),
+            to: Some(Strings::One(
+                "ops@example.com>\r\nRCPT TO:<x@evil.test".into(),
+            )),
+            ..Default::default()
+        };
+        assert!(build_channel("mail", &smtp).is_err());
+        let plain = ChannelToml {
+            kind: "webhook".into(),
+            url: Some("http://hooks.example.com/x".into()),
+            ..Default::default()
+        };
+        assert!(build_channel("hook", &plain).is_err());
+    }
+
+    #[test]
+    fn webhook_urls_and_headers_reach_curl_on_stdin() {
+        let config = curl_config(
+            "https://hooks.slack.com/services/T0/B0/s3cret",
+            b"{\"text\":\"C:\\\\tmp\"}\n",
+            &["Authorization: Bearer t0ken".to_string()],
+        );
+        assert_eq!(
+            String::from_utf8(config.to_vec()).unwrap(),
+            r#"url = "https://hooks.slack.com/services/T0/B0/s3cret"
+header = "Content-Type: application/json"
+header = "Authorization: Bearer t0ken"
+data-binary = "{\"text\":\"C:\\\\tmp\"}\n"
+"#
+        );
+    }
+
+    #[tokio::test]
+    async fn smtp_notices_are_submitted_with_dot_stuffing() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = listener.local_addr().unwrap().to_string();
+        let mta = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read, mut write) = stream.into_split();
+            let mut lines = BufReader::new(read).lines();
+            write.write_all(b"220 mta ESMTP\r\n").await.unwrap();
+            let mut transcript = Vec::new();
+            let mut in_data = false;
+            while let Some(line) = lines.next_line().await.unwrap() {
+                transcript.push(line.clone());
+                let reply: &[u8] = if in_data {
+                    if line != "." {
+                        continue;
+                    }
+                    in_data = false;
+                    b"250 queued\r\n"
+                } else if line.starts_with("EHLO") {
+                    b"250-mta\r\n250 8BITMIME\r\n"
+                } else if line == "DATA" {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                } else if line == "QUIT" {
+                    break;
+                } else {
+                    b"250 ok\r\n"
+                };
+                write.write_all(reply).await.unwrap();
+            }
+            transcript
+        });
+
+        let notifier = SmtpNotifier {
+            server,
+            from: "kari@web-1.example.com".into(),
+            to: vec!["ops@example.com".into(), "oncall@example.com".into()],
+        };
+        let mut notice = Notice::new(
+            NoticeKind::JobFailed,
+            "nightly-report",
+            "Job nightly-report failed".into(),
+            "exit status 1\n.hidden line".into(),
+        );
+        notice.host = "web-1".into();
+        notifier.notify(&notice).await.unwrap();
+
+        let transcript = mta.await.unwrap();
+        assert_eq!(transcript[0], "EHLO web-1");
+        assert_eq!(transcript[1], "MAIL FROM:<kari@web-1.example.com>");
+        assert_eq!(transcript[3], "RCPT TO:<oncall@example.com>");
+        assert!(
+            transcript.contains(&"Subject: [kari] web-1: Job nightly-report failed".to_string())
+        );
+        assert!(transcript.contains(&"..hidden line".to_string()));
+        assert_eq!(transcript.last().unwrap(), "QUIT");
+    }
+}
@@ -0,0 +1,146 @@
+// agent/src/sys/operations.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Tracks work that outlives the RPC that started it.
+// Every operation is persisted in the state store, so a caller whose connection
+// dropped can always ask what happened, even across an agent restart.
+
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::sync::Arc;
+
+use crate::sys::caller::Caller;
+use crate::sys::state::{get_record, list_records, put_record};
+use crate::sys::traits::StateStore;
+
+/// Namespace for long-running operations, keyed by operation id.
+pub const NS_OPERATIONS: &str = "operations";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationState {
+    #[default]
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// Mirrors `AgentResponse`, so a finished operation reports exactly what the
+/// synchronous RPC would have returned.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OperationResult {
+    pub success: bool,
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
+    pub error_message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OperationRecord {
+    pub id: String,
+    pub kind: String,
+    pub state: OperationState,
+    pub progress_percent: u32,
+    pub message: String,
+    pub result: Option<OperationResult>,
+    pub created_at: i64, // Unix seconds
+    pub updated_at: i64,
+    /// 🛡️ The peer whose request started it.
+    #[serde(default)]
+    pub initiated_by: String,
+}
+
+pub struct OperationTracker {
+    state: Arc<dyn StateStore>,
+}
+
+impl OperationTracker {
+    pub fn new(state: Arc<dyn StateStore>) -> Self {
+        Self { state }
+    }
+
+    /// 128 bits from the kernel CSPRNG: ids are unguessable, not just unique.
+    fn new_id() -> Result<String, String> {
+        let mut bytes = [0u8; 16];
+        std::fs::File::open("/dev/urandom")
+            .and_then(|mut f| f.read_exact(&mut bytes))
+            .map_err(|e| format!("Failed to generate operation id: {}", e))?;
+        Ok(format!(
+            "op-{}",
+            bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        ))
+    }
+
+    pub async fn start(&self, kind: &str, caller: &Caller) -> Result<OperationRecord, String> {
+        let now = chrono::Utc::now().timestamp();
+        let op = OperationRecord {
+            id: Self::new_id()?,
+            kind: kind.to_string(),
+            initiated_by: caller.to_string(),
+            created_at: now,
+            updated_at: now,
+            ..Default::default()
+        };
+        put_record(self.state.as_ref(), NS_OPERATIONS, &op.id, &op).await?;
+        Ok(op)
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<OperationRecord>, String> {
+        get_record(self.state.as_ref(), NS_OPERATIONS, id).await
+    }
+
+    /// Records a progress step. Progress updates are advisory, so a failed write is
+    /// only reported to the caller, never allowed to abort the work itself.
+    pub async fn progress(&self, id: &str, percent: u32, message: &str) -> Result<(), String> {
+        let Some(mut op) = self.get(id).await? else {
+            return Err(format!("Unknown operation '{}'", id));
+        };
+        op.progress_percent = percent.min(100);
+        op.message = message.to_string();
+        op.updated_at = chrono::Utc::now().timestamp();
+        put_record(self.state.as_ref(), NS_OPERATIONS, id, &op).await
+    }
+
+    pub async fn finish(&self, id: &str, result: OperationResult) -> Result<(), String> {
+        let Some(mut op) = self.get(id).await? else {
+            return Err(format!("Unknown operation '{}'", id));
+        };
+        op.state = if result.success {
+            OperationState::Succeeded
+        } else {
+            OperationState::Failed
+        };
+        op.progress_percent = 100;
+        op.result = Some(result);
+        op.updated_at = chrono::Utc::now().timestamp();
+        put_record(self.state.as_ref(), NS_OPERATIONS, id, &op).await
+    }
+
+    /// 🛡️ SLA: Boot-time hygiene. Operations still `Running` belonged to a previous
+    /// agent process and will never finish, so they are failed explicitly; finished
+    /// operations older than `retention_secs` are dropped.
+    pub async fn recover(&self, retention_secs: i64) -> Result<(), String> {
+        let now = chrono::Utc::now().timestamp();
+        let ops: Vec<OperationRecord> = list_records(self.state.as_ref(), NS_OPERATIONS).await?;
+
+        for mut op in ops {
+            if op.state == OperationState::Running {
+                op.state = OperationState::Failed;
+                op.message = "Interrupted by agent restart".into();
+                op.result = Some(OperationResult {
+                    exit_code: -1,
+                    error_message: "Interrupted by agent restart".into(),
+                    ..Default::default()
+                });
+                op.updated_at = now;
+                put_record(self.state.as_ref(), NS_OPERATIONS, &op.id, &op).await?;
+            } else if now - op.updated_at > retention_secs {
+                self.state.delete(NS_OPERATIONS, &op.id).await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+// agent/src/sys/packages.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Host package manager argv construction.
+//
+// Callers say what they want (install, remove, refresh, upgrade) and which
+// packages; the argv is built here from fixed subcommands and flags. Nothing
+// from the request lands anywhere but the package-name positions, and names
+// cannot start with '-', so no option (`-o`, `--setopt`) or subcommand
+// (`source`, `shell`) is reachable.
+
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageAction {
+    Install,
+    Remove,
+    Update, // Refresh the package index
+    Upgrade,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageManagerKind {
+    Apt,
+    Dnf,
+    Yum,
+    Zypper,
+}
+
+impl PackageManagerKind {
+    pub fn binary(self) -> &'static str {
+        match self {
+            Self::Apt => "/usr/bin/apt-get",
+            Self::Dnf => "/usr/bin/dnf",
+            Self::Yum => "/usr/bin/yum",
+            Self::Zypper => "/usr/bin/zypper",
+        }
+    }
+}
+
+/// The host's package manager, in order of preference (dnf over yum).
+pub fn detect() -> Option<PackageManagerKind> {
+    use PackageManagerKind::*;
+    [Apt, Dnf, Yum, Zypper]
+        .into_iter()
+        .find(|kind| Path::new(kind.binary()).exists())
+}
+
+/// Debian/RPM package names, optionally with an arch (`:amd64`) or a pinned
+/// version (`=1.2-3`). The leading alphanumeric keeps names from reading as options.
+pub fn is_valid_package_name(name: &str) -> bool {
+    name.len() <= 128
+        && name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name.chars().all(|c| {
+            c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-' | '_' | ':' | '~' | '=')
+        })
+}
+
+/// Non-interactive argv for `action`. Packages are assumed valid; an empty list
+/// means "everything" for Upgrade and is refused for Install and Remove.
+pub fn build_argv(
+    kind: PackageManagerKind,
+    action: PackageAction,
+    packages: &[String],
+) -> Result<Vec<String>, String> {
+    use PackageAction::*;
+    use PackageManagerKind::*;
+
+    if matches!(action, Install | Remove) && packages.is_empty() {
+        return Err("install and remove need at least one package".to_string());
+    }
+    if action == Update && !packages.is_empty() {
+        return Err("update refreshes the package index and takes no packages".to_string());
+    }
+
+    let fixed: &[&str] = match (kind, action) {
+        (Apt, Install) => &["install", "-y", "--no-install-recommends"],
+        (Apt, Remove) => &["remove", "-y"],
+        (Apt, Update) => &["update"],
+        (Apt, Upgrade) if packages.is_empty() => &["upgrade", "-y"],
+        (Apt, Upgrade) => &["install", "-y", "--only-upgrade"],
+        (Dnf | Yum, Install) => &["install", "-y"],
+        (Dnf | Yum, Remove) => &["remove", "-y"],
+        (Dnf | Yum, Update) => &["makecache"],
+        (Dnf | Yum, Upgrade) => &["upgrade", "-y"],
+        (Zypper, Install) => &["--non-interactive", "install"],
+        (Zypper, Remove) => &["--non-interactive", "remove"],
+        (Zypper, Update) => &["--non-interactive", "refresh"],
+        (Zypper, Upgrade) => &["--non-interactive", "update"],
+    };
+
+    Ok(std::iter::once(kind.binary())
+        .chain(fixed.iter().copied())
+        .map(str::to_string)
+        .chain(packages.iter().cloned())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn argv_is_fixed_apart_from_package_names() {
+        let pkgs = vec!["nginx".to_string(), "libssl3:amd64".to_string()];
+        assert_eq!(
+            build_argv(PackageManagerKind::Apt, PackageAction::Install, &pkgs).unwrap(),
+            [
+                "/usr/bin/apt-get",
+                "install",
+                "-y",
+                "--no-install-recommends",
+                "nginx",
+                "libssl3:amd64"
+            ]
+        );
+        assert_eq!(
+            build_argv(PackageManagerKind::Dnf, PackageAction::Upgrade, &[]).unwrap(),
+            ["/usr/bin/dnf", "upgrade", "-y"]
+        );
+        assert!(build_argv(PackageManagerKind::Apt, PackageAction::Remove, &[]).is_err());
+        assert!(build_argv(PackageManagerKind::Zypper, PackageAction::Update, &pkgs).is_err());
+    }
+
+    #[test]
+    fn package_names_cannot_smuggle_options() {
+        assert!(is_valid_package_name("python3-venv"));
+        assert!(is_valid_package_name("nginx=1.24.0-1"));
+        assert!(is_valid_package_name("g++"));
+        assert!(!is_valid_package_name("-oAPT::Update::Pre-Invoke::=sh"));
+        assert!(!is_valid_package_name("--setopt=tsflags=noscripts"));
+        assert!(!is_valid_package_name("nginx; rm -rf /"));
+        assert!(!is_valid_package_name(""));
+    }
+}
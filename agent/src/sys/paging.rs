@@ -0,0 +1,142 @@
+// agent/src/sys/paging.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Cursor pages and field filters for list RPCs.
+//
+// A page token is the sort key of the last item returned, base64url-encoded so
+// callers treat it as opaque. The next page starts strictly after that key, so
+// records added or removed between calls never shift or repeat the rest of
+// the listing the way an offset would. Field selectors are exact matches on the
+// handful of fields each list declares; anything else is refused rather than
+// silently matching nothing.
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use std::collections::{BTreeMap, HashMap};
+
+/// Items per page when the caller leaves page_size at 0.
+pub const DEFAULT_PAGE_SIZE: u32 = 100;
+pub const MAX_PAGE_SIZE: u32 = 1000;
+const MAX_TOKEN_LEN: usize = 1024;
+
+/// Checks a page request before any records are read.
+pub fn validate_page(page_size: u32, page_token: &str) -> Result<(), String> {
+    if page_size > MAX_PAGE_SIZE {
+        return Err(format!("page_size is at most {}", MAX_PAGE_SIZE));
+    }
+    decode_token(page_token).map(|_| ())
+}
+
+/// The key a token resumes after; None for the first page.
+fn decode_token(token: &str) -> Result<Option<String>, String> {
+    if token.is_empty() {
+        return Ok(None);
+    }
+    if token.len() > MAX_TOKEN_LEN {
+        return Err("page_token is too long".to_string());
+    }
+    URL_SAFE_NO_PAD
+        .decode(token)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .map(Some)
+        .ok_or_else(|| "page_token is not one this agent issued".to_string())
+}
+
+/// One page of `items`, in key order, and the token for the next (empty on
+/// the last page). Keys must be unique within the listing.
+pub fn paginate<T>(
+    items: Vec<T>,
+    key: impl Fn(&T) -> String,
+    page_size: u32,
+    page_token: &str,
+) -> Result<(Vec<T>, String), String> {
+    let after = decode_token(page_token)?;
+    let size = match page_size {
+        0 => DEFAULT_PAGE_SIZE,
+        size => size.min(MAX_PAGE_SIZE),
+    } as usize;
+
+    let mut keyed: Vec<(String, T)> = items.into_iter().map(|item| (key(&item), item)).collect();
+    keyed.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut rest = keyed
+        .into_iter()
+        .skip_while(|(k, _)| after.as_ref().is_some_and(|after| k <= after))
+        .peekable();
+
+    let mut page = Vec::with_capacity(size.min(64));
+    let mut last = None;
+    while page.len() < size {
+        let Some((k, item)) = rest.next() else {
+            break;
+        };
+        last = Some(k);
+        page.push(item);
+    }
+    let next = match (rest.peek(), last) {
+        (Some(_), Some(last)) => URL_SAFE_NO_PAD.encode(last.as_bytes()),
+        _ => String::new(),
+    };
+    Ok((page, next))
+}
+
+/// Exact-match filters on the fields a list declares.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldSelector(BTreeMap<String, String>);
+
+impl FieldSelector {
+    /// Refuses fields the list does not offer, naming the ones it does.
+    pub fn parse(selector: &HashMap<String, String>, fields: &[&str]) -> Result<Self, String> {
+        if let Some(unknown) = selector.keys().find(|f| !fields.contains(&f.as_str())) {
+            return Err(format!(
+                "Cannot filter on '{}' (fields: {})",
+                unknown,
+                fields.join(", ")
+            ));
+        }
+        Ok(Self(selector.clone().into_iter().collect()))
+    }
+
+    /// Whether `value` satisfies the filter on `field`, if there is one.
+    pub fn accepts(&self, field: &str, value: &str) -> bool {
+        self.0.get(field).is_none_or(|wanted| wanted == value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_resume_after_their_cursor_whatever_changed_in_between() {
+        let names = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let key = |s: &String| s.clone();
+
+        let (first, token) = paginate(names(&["c", "a", "d", "b"]), key, 2, "").unwrap();
+        assert_eq!(first, ["a", "b"]);
+        assert!(!token.is_empty());
+
+        // "a" went away and "bb" arrived: the second page neither skips nor repeats
+        let (second, token) = paginate(names(&["b", "bb", "c", "d"]), key, 2, &token).unwrap();
+        assert_eq!(second, ["bb", "c"]);
+        let (last, token) = paginate(names(&["b", "bb", "c", "d"]), key, 2, &token).unwrap();
+        assert_eq!(last, ["d"]);
+        assert!(token.is_empty());
+
+        let (all, token) = paginate(names(&["a", "b"]), key, 0, "").unwrap();
+        assert_eq!((all.len(), token.as_str()), (2, ""));
+        assert!(paginate(names(&["a"]), key, 1, "!!not-base64").is_err());
+        assert!(validate_page(MAX_PAGE_SIZE + 1, "").is_err());
+    }
+
+    #[test]
+    fn selectors_only_name_declared_fields() {
+        let selector = HashMap::from([("runtime".to_string(), "container".to_string())]);
+        let fields = FieldSelector::parse(&selector, &["app_id", "runtime"]).unwrap();
+        assert!(fields.accepts("runtime", "container"));
+        assert!(!fields.accepts("runtime", "source"));
+        assert!(fields.accepts("app_id", "anything"));
+
+        let err = FieldSelector::parse(&selector, &["app_id"]).unwrap_err();
+        assert!(err.contains("fields: app_id"), "{}", err);
+    }
+}
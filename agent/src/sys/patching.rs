@@ -0,0 +1,660 @@
+// agent/src/sys/patching.rs
+//
+// 🩹 Unattended OS patching in a maintenance window. SetPatchPolicy stores a
+// daily window and a scope (security fixes only, or every upgrade). Once a day,
+// when the window opens, the index is refreshed and, if anything is pending,
+// routed apps are parked on the maintenance page, their units stopped, the
+// host upgraded, and the units started again on the new libraries. Every run
+// leaves a report: what was pending, which versions changed, and whether the
+// host now wants a reboot (which stays ScheduleReboot's call).
+//
+// The distro's own automatic upgrades (unattended-upgrades, dnf-automatic)
+// restart services behind the agent's back, so setting a policy turns them off
+// and removing it turns back on what it turned off.
+//
+// The window is watched from inside the agent rather than by a kari timer:
+// timer jobs run under ProtectSystem=full, where /usr cannot be written. The
+// drain is recorded like a reboot's (NS_REBOOT, without an operation), so an
+// agent that dies mid-run puts the apps back on its next start, and a reboot
+// that is already scheduled holds patching off.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::sys::executor::{CommandOutput, CommandSpec, Executor, SystemExecutor};
+use crate::sys::packages::{self, PackageAction, PackageManagerKind};
+use crate::sys::reboot::{self, RebootRecord, RebootWindow};
+use crate::sys::state::{get_record, list_records, put_record};
+use crate::sys::systemd::ServiceManager;
+use crate::sys::traits::{Patcher, ProxyManager, RebootManager, StateStore};
+
+/// Namespace for the policy; it only ever holds `POLICY_KEY`.
+pub const NS_PATCHING: &str = "patching";
+const POLICY_KEY: &str = "policy";
+/// Namespace for run reports, keyed by start time.
+pub const NS_PATCH_RUNS: &str = "patch_runs";
+/// Reports kept; older ones are dropped as new runs finish.
+pub const RUNS_KEPT: usize = 30;
+
+/// The apt.conf.d drop-in that turns unattended-upgrades off.
+const APT_DROP_IN: &str = "99kari-patching";
+const UNATTENDED_UPGRADES: &str = "unattended-upgrades";
+/// The timers of dnf-automatic and yum-cron; only the enabled ones are turned off.
+const RPM_AUTOMATION: &[&str] = &[
+    "dnf-automatic.timer",
+    "dnf-automatic-install.timer",
+    "dnf-automatic-download.timer",
+    "dnf-automatic-notifyonly.timer",
+    "yum-cron.service",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatchScope {
+    #[default]
+    Security,
+    Full,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatchPolicy {
+    pub window: String, // RebootWindow syntax
+    pub scope: PatchScope,
+    pub drain_secs: u32,
+    pub handed_over: Vec<String>, // Distro automation turned off when the policy was set
+}
+
+/// One package whose installed version a run changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageChange {
+    pub name: String,
+    pub from: String, // Empty if newly installed
+    pub to: String,   // Empty if removed
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatchRun {
+    pub started_at: i64, // Unix seconds
+    pub finished_at: i64,
+    pub scope: PatchScope,
+    pub pending: Vec<String>,
+    pub changes: Vec<PackageChange>,
+    pub drained: u32, // Apps put on the maintenance page
+    pub reboot_needed: bool,
+    pub error: String, // Empty on success
+}
+
+pub async fn policy(state: &dyn StateStore) -> Result<Option<PatchPolicy>, String> {
+    get_record(state, NS_PATCHING, POLICY_KEY).await
+}
+
+pub async fn save_policy(state: &dyn StateStore, policy: &PatchPolicy) -> Result<(), String> {
+    put_record(state, NS_PATCHING, POLICY_KEY, policy).await
+}
+
+pub async fn clear_policy(state: &dyn StateStore) -> Result<(), String> {
+    state.delete(NS_PATCHING, POLICY_KEY).await
+}
+
+/// Run reports, newest first.
+pub async fn runs(state: &dyn StateStore) -> Result<Vec<PatchRun>, String> {
+    let mut runs: Vec<PatchRun> = list_records(state, NS_PATCH_RUNS).await?;
+    runs.sort_by_key(|run| std::cmp::Reverse(run.started_at));
+    Ok(runs)
+}
+
+async fn record_run(state: &dyn StateStore, run: &PatchRun) -> Result<(), String> {
+    put_record(state, NS_PATCH_RUNS, &run.started_at.to_string(), run).await?;
+    for old in runs(state).await?.iter().skip(RUNS_KEPT) {
+        state
+            .delete(NS_PATCH_RUNS, &old.started_at.to_string())
+            .await?;
+    }
+    Ok(())
+}
+
+/// Packages whose version differs between two snapshots, by name.
+pub fn diff(
+    before: &BTreeMap<String, String>,
+    after: &BTreeMap<String, String>,
+) -> Vec<PackageChange> {
+    let mut names: Vec<&String> = before.keys().chain(after.keys()).collect();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .filter(|name| before.get(*name) != after.get(*name))
+        .map(|name| PackageChange {
+            name: name.clone(),
+            from: before.get(name).cloned().unwrap_or_default(),
+            to: after.get(name).cloned().unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Whether a run is due: the window is open and none started in it yet. A run
+/// from an earlier window started at least a window's length before now.
+pub fn due(
+    window: &RebootWindow,
+    seconds_since_midnight: u32,
+    now: i64,
+    last: Option<i64>,
+) -> bool {
+    window.wait_from(seconds_since_midnight).is_zero()
+        && last.is_none_or(|last| now - last >= window.length().as_secs() as i64)
+}
+
+/// Drains, upgrades and restores once a day, in the policy's window.
+pub struct Patching {
+    pub state: Arc<dyn StateStore>,
+    pub services: Arc<dyn ServiceManager>,
+    pub proxy: Arc<dyn ProxyManager>,
+    pub patcher: Arc<dyn Patcher>,
+    pub reboots: Arc<dyn RebootManager>,
+}
+
+impl Patching {
+    pub async fn run(self, poll: Duration) {
+        use chrono::Timelike;
+        let mut interval = tokio::time::interval(poll);
+        loop {
+            interval.tick().await;
+            let now = chrono::Local::now();
+            match self
+                .tick(now.num_seconds_from_midnight(), now.timestamp())
+                .await
+            {
+                Ok(Some(run)) if run.error.is_empty() => info!(
+                    "🩹 Patching: {} pending, {} packages changed{}",
+                    run.pending.len(),
+                    run.changes.len(),
+                    if run.reboot_needed {
+                        "; a reboot is needed"
+                    } else {
+                        ""
+                    }
+                ),
+                Ok(Some(run)) => warn!("🩹 Patching failed: {}", run.error),
+                Ok(None) => {}
+                Err(e) => warn!("🩹 Patch window check failed: {}", e),
+            }
+        }
+    }
+
+    /// Runs the policy if its window is open and it has not run in it yet.
+    pub async fn tick(
+        &self,
+        seconds_since_midnight: u32,
+        now: i64,
+    ) -> Result<Option<PatchRun>, String> {
+        let state = self.state.as_ref();
+        let Some(policy) = policy(state).await? else {
+            return Ok(None);
+        };
+        let window = RebootWindow::parse(&policy.window)?;
+        let last = runs(state).await?.first().map(|run| run.started_at);
+        if !due(&window, seconds_since_midnight, now, last) {
+            return Ok(None);
+        }
+        // The reboot's drain owns the apps until it is done
+        if reboot::pending(state).await?.is_some() {
+            return Ok(None);
+        }
+        let mut run = PatchRun {
+            started_at: now,
+            scope: policy.scope,
+            ..Default::default()
+        };
+        if let Err(e) = self.patch(&policy, &mut run).await {
+            run.error = e;
+        }
+        run.finished_at = chrono::Utc::now().timestamp();
+        record_run(state, &run).await?;
+        Ok(Some(run))
+    }
+
+    async fn patch(&self, policy: &PatchPolicy, run: &mut PatchRun) -> Result<(), String> {
+        let state = self.state.as_ref();
+        run.pending = self.patcher.pending(policy.scope).await?;
+        if run.pending.is_empty() {
+            return Ok(());
+        }
+        let before = self.patcher.installed().await?;
+
+        let mut record = RebootRecord {
+            window: policy.window.clone(),
+            ..Default::default()
+        };
+        reboot::save(state, &record).await?;
+        let drained = reboot::drain(
+            state,
+            self.services.as_ref(),
+            self.proxy.as_ref(),
+            &mut record,
+            Duration::from_secs(policy.drain_secs.into()),
+        )
+        .await;
+        let upgraded = match drained {
+            Ok(()) => self.patcher.upgrade(policy.scope, &run.pending).await,
+            Err(e) => Err(format!("Drain failed: {}", e)),
+        };
+        run.drained = record.drained.len() as u32;
+        let restored =
+            reboot::restore(state, self.services.as_ref(), self.proxy.as_ref(), &record).await;
+
+        // A failed upgrade may still have changed some packages
+        match self.patcher.installed().await {
+            Ok(after) => run.changes = diff(&before, &after),
+            Err(e) => warn!("🩹 Installed packages unreadable after patching: {}", e),
+        }
+        run.reboot_needed = self
+            .reboots
+            .kernel_state()
+            .await
+            .is_ok_and(|kernel| reboot::advise(&kernel).needs_reboot);
+        upgraded?;
+        restored.map_err(|e| format!("Restore failed: {}", e))
+    }
+}
+
+fn stdout(output: &CommandOutput) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// `dpkg-query -W -f='${db:Status-Abbrev} ${Package} ${Version}\n'`: only
+/// installed ("ii") packages, not those removed with their config kept.
+pub fn parse_dpkg(text: &str) -> BTreeMap<String, String> {
+    text.lines()
+        .filter_map(
+            |line| match line.split_whitespace().collect::<Vec<_>>()[..] {
+                ["ii", name, version] => Some((name.to_string(), version.to_string())),
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+/// `rpm -qa --qf '%{NAME} %{VERSION}-%{RELEASE}\n'`.
+pub fn parse_rpm(text: &str) -> BTreeMap<String, String> {
+    text.lines()
+        .filter_map(|line| {
+            let (name, version) = line.trim().split_once(' ')?;
+            Some((name.to_string(), version.to_string()))
+        })
+        .collect()
+}
+
+/// `apt-get -s upgrade`: "Inst <name> [<old>] (<new> <origins> [<arch>])".
+/// Security fixes come from a "-security" suite (jammy-security, bookworm-security).
+pub fn parse_apt_simulation(text: &str, scope: PatchScope) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| line.strip_prefix("Inst "))
+        .filter(|line| scope == PatchScope::Full || line.contains("-security"))
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_string)
+        .collect()
+}
+
+/// `dnf check-update`: "<name>.<arch> <version> <repo>" rows, then an
+/// "Obsoleting Packages" section that is not an upgrade list.
+pub fn parse_check_update(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = text
+        .lines()
+        .take_while(|line| !line.starts_with("Obsoleting"))
+        .filter(|line| !line.starts_with(char::is_whitespace))
+        .filter_map(
+            |line| match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [package, _, _] => package.rsplit_once('.').map(|(name, _)| name.to_string()),
+                _ => None,
+            },
+        )
+        .collect();
+    names.dedup();
+    names
+}
+
+/// zypper's tables: `list-updates` rows start with "v", `list-patches` rows
+/// are "needed" in their status column.
+pub fn parse_zypper_table(text: &str, scope: PatchScope) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| {
+            let cells: Vec<&str> = line.split('|').map(str::trim).collect();
+            match scope {
+                PatchScope::Full if cells.len() > 2 && cells[0] == "v" => Some(cells[2]),
+                PatchScope::Security if cells.len() > 1 && cells.contains(&"needed") => {
+                    Some(cells[1])
+                }
+                _ => None,
+            }
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// The host's package manager, driven non-interactively.
+pub struct HostPatcher {
+    kind: Option<PackageManagerKind>,
+    apt_conf_dir: PathBuf,
+    timeout: Duration, // Per package manager command
+    executor: Arc<dyn Executor>,
+}
+
+impl HostPatcher {
+    pub fn new(apt_conf_dir: PathBuf, timeout: Duration) -> Self {
+        Self::with_executor(
+            packages::detect(),
+            apt_conf_dir,
+            timeout,
+            Arc::new(SystemExecutor),
+        )
+    }
+
+    pub fn with_executor(
+        kind: Option<PackageManagerKind>,
+        apt_conf_dir: PathBuf,
+        timeout: Duration,
+        executor: Arc<dyn Executor>,
+    ) -> Self {
+        Self {
+            kind,
+            apt_conf_dir,
+            timeout,
+            executor,
+        }
+    }
+
+    fn kind(&self) -> Result<PackageManagerKind, String> {
+        self.kind
+            .ok_or_else(|| "No supported package manager found on this host".to_string())
+    }
+
+    /// Runs `argv`; exit codes outside `ok` are failures.
+    async fn run(&self, argv: Vec<String>, ok: &[i32]) -> Result<CommandOutput, String> {
+        let (program, args) = argv.split_first().ok_or("Empty command")?;
+        let spec = CommandSpec::privileged(program)
+            .args(args.iter().cloned())
+            .env("DEBIAN_FRONTEND", "noninteractive")
+            // 🛡️ needrestart would restart services mid-upgrade; the drain restarts apps instead
+            .env("NEEDRESTART_MODE", "l")
+            .kill_on_drop();
+        let command = argv.join(" ");
+        let output = tokio::time::timeout(self.timeout, self.executor.run(&spec))
+            .await
+            .map_err(|_| format!("{} timed out after {:?}", command, self.timeout))?
+            .map_err(|e| format!("Failed to execute {}: {}", command, e))?;
+        if !output.code.is_some_and(|code| ok.contains(&code)) {
+            return Err(format!(
+                "{} failed: {}",
+                command,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(output)
+    }
+
+    fn argv(kind: PackageManagerKind, args: &[&str]) -> Vec<String> {
+        std::iter::once(kind.binary())
+            .chain(args.iter().copied())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Patcher for HostPatcher {
+    async fn installed(&self) -> Result<BTreeMap<String, String>, String> {
+        let (argv, parse): (&[&str], fn(&str) -> BTreeMap<String, String>) = match self.kind()? {
+            PackageManagerKind::Apt => (
+                &[
+                    "dpkg-query",
+                    "-W",
+                    "-f=${db:Status-Abbrev} ${Package} ${Version}\\n",
+                ],
+                parse_dpkg,
+            ),
+            _ => (
+                &["rpm", "-qa", "--qf", "%{NAME} %{VERSION}-%{RELEASE}\\n"],
+                parse_rpm,
+            ),
+        };
+        let output = self
+            .run(argv.iter().map(|arg| arg.to_string()).collect(), &[0])
+            .await?;
+        Ok(parse(&stdout(&output)))
+    }
+
+    async fn pending(&self, scope: PatchScope) -> Result<Vec<String>, String> {
+        use PackageManagerKind::*;
+        let kind = self.kind()?;
+        self.run(
+            packages::build_argv(kind, PackageAction::Update, &[])?,
+            &[0],
+        )
+        .await?;
+        Ok(match (kind, scope) {
+            (Apt, _) => {
+                let output = self.run(Self::argv(kind, &["-s", "upgrade"]), &[0]).await?;
+                parse_apt_simulation(&stdout(&output), scope)
+            }
+            (Dnf | Yum, _) => {
+                let mut args = vec!["-q", "check-update"];
+                if scope == PatchScope::Security {
+                    args.push("--security");
+                }
+                // 100: updates are available
+                let output = self.run(Self::argv(kind, &args), &[0, 100]).await?;
+                parse_check_update(&stdout(&output))
+            }
+            (Zypper, PatchScope::Security) => {
+                let args = [
+                    "--non-interactive",
+                    "--quiet",
+                    "list-patches",
+                    "--category",
+                    "security",
+                ];
+                let output = self.run(Self::argv(kind, &args), &[0]).await?;
+                parse_zypper_table(&stdout(&output), scope)
+            }
+            (Zypper, PatchScope::Full) => {
+                let args = ["--non-interactive", "--quiet", "list-updates"];
+                let output = self.run(Self::argv(kind, &args), &[0]).await?;
+                parse_zypper_table(&stdout(&output), scope)
+            }
+        })
+    }
+
+    async fn upgrade(&self, scope: PatchScope, pending: &[String]) -> Result<(), String> {
+        use PackageManagerKind::*;
+        let kind = self.kind()?;
+        let (argv, ok): (Vec<String>, &[i32]) = match (kind, scope) {
+            // apt has no security switch: the listed packages, upgraded only
+            (Apt, PatchScope::Security) => (
+                packages::build_argv(kind, PackageAction::Upgrade, pending)?,
+                &[0],
+            ),
+            (Dnf | Yum, PatchScope::Security) => {
+                (Self::argv(kind, &["upgrade", "-y", "--security"]), &[0])
+            }
+            // 102/103: done, and a reboot or a restart of zypper is advised
+            (Zypper, PatchScope::Security) => (
+                Self::argv(
+                    kind,
+                    &["--non-interactive", "patch", "--category", "security"],
+                ),
+                &[0, 102, 103],
+            ),
+            (Zypper, PatchScope::Full) => (
+                packages::build_argv(kind, PackageAction::Upgrade, &[])?,
+                &[0, 102, 103],
+            ),
+            (_, PatchScope::Full) => (
+                packages::build_argv(kind, PackageAction::Upgrade, &[])?,
+                &[0],
+            ),
+        };
+        let mut argv = argv;
+        if kind == Apt {
+            // 🛡️ Changed config files keep the local version instead of prompting
+            argv.extend(
+                ["-o", "Dpkg::Options::=--force-confold"]
+                    .iter()
+                    .map(|arg| arg.to_string()),
+            );
+        }
+        self.run(argv, ok).await.map(|_| ())
+    }
+
+    async fn disable_distro_automation(&self) -> Result<Vec<String>, String> {
+        let mut disabled = Vec::new();
+        match self.kind()? {
+            PackageManagerKind::Apt => {
+                if self
+                    .executor
+                    .exists(Path::new("/usr/bin/unattended-upgrade"))
+                    .await?
+                {
+                    let drop_in = self.apt_conf_dir.join(APT_DROP_IN);
+                    let content = "// Written by kari: this host is patched by its SetPatchPolicy window\n\
+                                   APT::Periodic::Unattended-Upgrade \"0\";\n";
+                    self.executor
+                        .write_file(&drop_in, content, 0o644)
+                        .await
+                        .map_err(|e| format!("Failed to write {:?}: {}", drop_in, e))?;
+                    disabled.push(UNATTENDED_UPGRADES.to_string());
+                }
+            }
+            PackageManagerKind::Dnf | PackageManagerKind::Yum => {
+                for unit in RPM_AUTOMATION {
+                    let state = self
+                        .executor
+                        .run(&CommandSpec::new("systemctl").args(["is-enabled", unit]))
+                        .await?;
+                    if stdout(&state).trim() != "enabled" {
+                        continue;
+                    }
+                    self.run(
+                        vec![
+                            "systemctl".into(),
+                            "disable".into(),
+                            "--now".into(),
+                            unit.to_string(),
+                        ],
+                        &[0],
+                    )
+                    .await?;
+                    disabled.push(unit.to_string());
+                }
+            }
+            PackageManagerKind::Zypper => {}
+        }
+        Ok(disabled)
+    }
+
+    async fn restore_distro_automation(&self, disabled: &[String]) -> Result<(), String> {
+        for name in disabled {
+            if name == UNATTENDED_UPGRADES {
+                self.executor
+                    .remove_file(&self.apt_conf_dir.join(APT_DROP_IN))
+                    .await?;
+            } else if RPM_AUTOMATION.contains(&name.as_str()) {
+                self.run(
+                    vec![
+                        "systemctl".into(),
+                        "enable".into(),
+                        "--now".into(),
+                        name.clone(),
+                    ],
+                    &[0],
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn package_manager_output_parses() {
+        let dpkg = "ii  openssl 3.0.2-0ubuntu1.12\nrc  oldpkg 1.0\nii  libssl3 3.0.2-0ubuntu1.12\n";
+        assert_eq!(
+            parse_dpkg(dpkg).into_iter().collect::<Vec<_>>(),
+            [
+                ("libssl3".to_string(), "3.0.2-0ubuntu1.12".to_string()),
+                ("openssl".to_string(), "3.0.2-0ubuntu1.12".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_rpm("openssl 3.0.7-27.el9\n")["openssl"],
+            "3.0.7-27.el9"
+        );
+
+        let apt = "Reading package lists...\n\
+                   Inst libssl3 [3.0.2-0ubuntu1.10] (3.0.2-0ubuntu1.12 Ubuntu:22.04/jammy-updates, Ubuntu:22.04/jammy-security [amd64])\n\
+                   Inst tzdata [2024a-0ubuntu0.22.04] (2024a-0ubuntu0.22.04.1 Ubuntu:22.04/jammy-updates [all])\n\
+                   Conf libssl3 (3.0.2-0ubuntu1.12 Ubuntu:22.04/jammy-updates [amd64])\n";
+        assert_eq!(parse_apt_simulation(apt, PatchScope::Security), ["libssl3"]);
+        assert_eq!(
+            parse_apt_simulation(apt, PatchScope::Full),
+            ["libssl3", "tzdata"]
+        );
+
+        let dnf = "\nopenssl.x86_64      1:3.0.7-27.el9     baseos\n\
+                   openssl-libs.x86_64 1:3.0.7-27.el9     baseos\n\
+                   Obsoleting Packages\n\
+                   grub2-tools.x86_64  1:2.06-80.el9      baseos\n";
+        assert_eq!(parse_check_update(dnf), ["openssl", "openssl-libs"]);
+
+        let patches = "Repository | Name | Category | Severity | Interactive | Status | Summary\n\
+                       -----------+------+----------+----------+-------------+--------+--------\n\
+                       Update     | SUSE-2024-1 | security | important | --- | needed | openssl\n\
+                       Update     | SUSE-2024-2 | security | moderate | --- | applied | curl\n";
+        assert_eq!(
+            parse_zypper_table(patches, PatchScope::Security),
+            ["SUSE-2024-1"]
+        );
+        let updates = "S | Repository | Name | Current Version | Available Version | Arch\n\
+                       v | Update     | curl | 8.0.1-1 | 8.0.1-2 | x86_64\n";
+        assert_eq!(parse_zypper_table(updates, PatchScope::Full), ["curl"]);
+    }
+
+    #[test]
+    fn runs_are_due_once_per_window() {
+        let window = RebootWindow::parse("02:00-04:00").unwrap();
+        let day = 86_400;
+        let opened = 10 * day + 2 * 3600; // 02:00 on some day, as Unix seconds
+        assert!(due(&window, 2 * 3600, opened, None));
+        assert!(!due(&window, 3600, opened - 3600, None));
+        // Not again in the same window...
+        assert!(!due(&window, 3 * 3600, opened + 3600, Some(opened)));
+        // ...but in the next one, even after a late start the day before
+        assert!(due(&window, 2 * 3600, opened + day, Some(opened + 7000)));
+
+        let before = BTreeMap::from([
+            ("openssl".to_string(), "1".to_string()),
+            ("gone".to_string(), "1".to_string()),
+        ]);
+        let after = BTreeMap::from([
+            ("openssl".to_string(), "2".to_string()),
+            ("new".to_string(), "1".to_string()),
+        ]);
+        let changes = diff(&before, &after);
+        let summary: Vec<(&str, &str, &str)> = changes
+            .iter()
+            .map(|c| (c.name.as_str(), c.from.as_str(), c.to.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [("gone", "1", ""), ("new", "", "1"), ("openssl", "1", "2")]
+        );
+    }
+}
@@ -0,0 +1,110 @@
+// agent/src/sys/pipeline.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Runs the independent phases of one deploy
+// side by side.
+//
+// A deploy is a sequence of stages. Phases inside a stage must not depend on
+// each other (scanning and jailing a fresh clone, say); a stage only starts
+// once every phase of the previous one has finished, which is what keeps the
+// ordering constraints (nothing is fetched before it was scanned, nothing goes
+// live before it built). Phases run on the caller's task, so they may borrow
+// from it; anything CPU- or disk-bound belongs in `spawn_blocking` inside the
+// phase.
+
+use std::collections::VecDeque;
+use std::future::{Future, poll_fn};
+use std::pin::Pin;
+use std::task::Poll;
+
+pub type Phase<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Boxes a phase future for `run_stage`.
+pub fn phase<'a, T, F>(future: F) -> Phase<'a, T>
+where
+    F: Future<Output = T> + Send + 'a,
+{
+    Box::pin(future)
+}
+
+/// Runs one stage with at most `limit` phases in flight and returns their
+/// outputs in phase order. A limit of 1 is exactly a sequence of awaits.
+pub async fn run_stage<T>(phases: Vec<Phase<'_, T>>, limit: usize) -> Vec<T> {
+    let limit = limit.max(1);
+    let mut outputs: Vec<Option<T>> = phases.iter().map(|_| None).collect();
+    let mut queued: VecDeque<(usize, Phase<'_, T>)> = phases.into_iter().enumerate().collect();
+    let mut running: Vec<(usize, Phase<'_, T>)> = Vec::with_capacity(limit);
+
+    poll_fn(|cx| {
+        while running.len() < limit {
+            match queued.pop_front() {
+                Some(next) => running.push(next),
+                None => break,
+            }
+        }
+        let mut i = 0;
+        while i < running.len() {
+            match running[i].1.as_mut().poll(cx) {
+                Poll::Ready(output) => {
+                    let (index, _) = running.swap_remove(i);
+                    outputs[index] = Some(output);
+                    // The freed slot is taken straight away; whatever lands at
+                    // `i` (moved or queued) is polled next
+                    if let Some(next) = queued.pop_front() {
+                        running.push(next);
+                    }
+                }
+                Poll::Pending => i += 1,
+            }
+        }
+        if running.is_empty() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await;
+
+    outputs
+        .into_iter()
+        .map(|output| output.expect("every phase ran to completion"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn phases_overlap_up_to_the_limit_and_keep_their_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let stage = |limit: usize| {
+            let phases: Vec<Phase<'_, u32>> = (0..3u32)
+                .map(|n| {
+                    let log = Arc::clone(&log);
+                    phase(async move {
+                        log.lock().unwrap().push(format!("start {}", n));
+                        // The first phase is the slowest
+                        tokio::time::sleep(Duration::from_millis(30 - 10 * u64::from(n))).await;
+                        log.lock().unwrap().push(format!("end {}", n));
+                        n * 10
+                    })
+                })
+                .collect();
+            run_stage(phases, limit)
+        };
+
+        assert_eq!(stage(3).await, [0, 10, 20]);
+        assert_eq!(
+            std::mem::take(&mut *log.lock().unwrap()),
+            ["start 0", "start 1", "start 2", "end 2", "end 1", "end 0"]
+        );
+
+        assert_eq!(stage(1).await, [0, 10, 20]);
+        assert_eq!(
+            std::mem::take(&mut *log.lock().unwrap()),
+            ["start 0", "end 0", "start 1", "end 1", "start 2", "end 2"]
+        );
+    }
+}
@@ -0,0 +1,206 @@
+// agent/src/sys/placement.rs
+//
+// 🧭 One number a multi-host control plane can rank agents by when it places a
+// new tenant. The agent computes it, so every control plane ranks the same way
+// and the formula can change with the agent; `MODEL` says which one produced a
+// score, and scores of different models must not be compared.
+//
+// Four loads, each 0 (idle) to 1 (saturated), are weighed into the score:
+//
+//   committed  MemoryMax/CPUQuota promised to apps, against what may be promised
+//   pressure   PSI "some" avg60 of cpu, memory and io, the worst of the three
+//   disk       used share of the web root's filesystem
+//   queue      deploys in flight per core
+//
+// A host past a hard limit (all memory promised, or the web root's filesystem
+// under KARI_MIN_FREE_DISK_MB) is not accepting and scores 0, whatever else.
+
+use std::path::Path;
+
+use crate::sys::capacity::Reservations;
+
+/// The formula below. Bump it whenever the weights or a load's meaning change.
+pub const MODEL: u32 = 1;
+
+const WEIGHT_COMMITTED: f64 = 0.35;
+const WEIGHT_PRESSURE: f64 = 0.25;
+const WEIGHT_DISK: f64 = 0.2;
+const WEIGHT_QUEUE: f64 = 0.2;
+
+/// PSI "some" avg60 per resource, in percent of wall time stalled.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Pressure {
+    pub cpu: f64,
+    pub memory: f64,
+    pub io: f64,
+}
+
+/// The `some ... avg60=` figure of a /proc/pressure file.
+pub fn parse_pressure(text: &str) -> Option<f64> {
+    text.lines()
+        .find(|line| line.starts_with("some "))?
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("avg60="))?
+        .parse()
+        .ok()
+}
+
+/// The host's pressure. A kernel without PSI reads as no pressure at all.
+pub fn read_pressure(proc_pressure: &Path) -> Pressure {
+    let read = |resource: &str| {
+        std::fs::read_to_string(proc_pressure.join(resource))
+            .ok()
+            .and_then(|text| parse_pressure(&text))
+            .unwrap_or(0.0)
+    };
+    Pressure {
+        cpu: read("cpu"),
+        memory: read("memory"),
+        io: read("io"),
+    }
+}
+
+/// What the score is computed from.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PlacementInputs {
+    pub committed: Reservations,
+    /// What may be promised: physical resources times the overcommit ratio.
+    pub limit: Reservations,
+    pub pressure: Pressure,
+    pub disk_used_percent: Option<f64>,
+    pub disk_free_mb: Option<u64>,
+    pub min_free_disk_mb: u64,
+    pub deploys_in_flight: u64,
+    pub cores: u64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Placement {
+    pub score: f64, // 0 (full) to 100 (idle)
+    pub accepting: bool,
+    pub committed: f64,
+    pub pressure: f64,
+    pub disk: f64,
+    pub queue: f64,
+}
+
+fn share(used: u64, limit: u64) -> f64 {
+    if limit == 0 {
+        return 1.0;
+    }
+    (used as f64 / limit as f64).min(1.0)
+}
+
+pub fn score(inputs: &PlacementInputs) -> Placement {
+    let memory = share(inputs.committed.memory_mb, inputs.limit.memory_mb);
+    let committed = memory.max(share(
+        inputs.committed.cpu_percent,
+        inputs.limit.cpu_percent,
+    ));
+    let pressure = inputs
+        .pressure
+        .cpu
+        .max(inputs.pressure.memory)
+        .max(inputs.pressure.io)
+        .clamp(0.0, 100.0)
+        / 100.0;
+    // An undeterminable filesystem is not held against the host, as in check_disk
+    let disk = inputs
+        .disk_used_percent
+        .map_or(0.0, |percent| percent.clamp(0.0, 100.0) / 100.0);
+    let queue = share(inputs.deploys_in_flight, inputs.cores.max(1));
+
+    let accepting = memory < 1.0
+        && inputs
+            .disk_free_mb
+            .is_none_or(|free| free >= inputs.min_free_disk_mb);
+    let load = WEIGHT_COMMITTED * committed
+        + WEIGHT_PRESSURE * pressure
+        + WEIGHT_DISK * disk
+        + WEIGHT_QUEUE * queue;
+    Placement {
+        score: if accepting {
+            ((1.0 - load) * 100.0).clamp(0.0, 100.0)
+        } else {
+            0.0
+        },
+        accepting,
+        committed,
+        pressure,
+        disk,
+        queue,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pressure_files_yield_their_some_avg60() {
+        let text = "some avg10=0.50 avg60=12.25 avg300=3.00 total=123456\n\
+                    full avg10=0.00 avg60=40.00 avg300=0.00 total=42\n";
+        assert_eq!(parse_pressure(text), Some(12.25));
+        assert_eq!(parse_pressure("full avg60=1.00"), None);
+    }
+
+    #[test]
+    fn scores_fall_with_load_and_hit_zero_past_hard_limits() {
+        let idle = PlacementInputs {
+            limit: Reservations {
+                memory_mb: 8192,
+                cpu_percent: 400,
+            },
+            disk_used_percent: Some(0.0),
+            disk_free_mb: Some(100_000),
+            min_free_disk_mb: 1024,
+            cores: 4,
+            ..Default::default()
+        };
+        let placement = score(&idle);
+        assert!(placement.accepting);
+        assert_eq!(placement.score, 100.0);
+
+        let busy = PlacementInputs {
+            committed: Reservations {
+                memory_mb: 4096,
+                cpu_percent: 100,
+            },
+            pressure: Pressure {
+                cpu: 10.0,
+                memory: 20.0,
+                io: 5.0,
+            },
+            disk_used_percent: Some(50.0),
+            deploys_in_flight: 2,
+            ..idle
+        };
+        let placement = score(&busy);
+        assert_eq!(
+            (
+                placement.committed,
+                placement.pressure,
+                placement.disk,
+                placement.queue
+            ),
+            (0.5, 0.2, 0.5, 0.5)
+        );
+        // 100 * (1 - (0.35*0.5 + 0.25*0.2 + 0.2*0.5 + 0.2*0.5))
+        assert!((placement.score - 57.5).abs() < 1e-9, "{}", placement.score);
+
+        for full in [
+            PlacementInputs {
+                committed: idle.limit,
+                ..idle
+            },
+            PlacementInputs {
+                disk_free_mb: Some(512),
+                ..idle
+            },
+        ] {
+            let placement = score(&full);
+            assert!(!placement.accepting);
+            assert_eq!(placement.score, 0.0);
+        }
+    }
+}
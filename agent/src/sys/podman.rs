@@ -0,0 +1,548 @@
+// agent/src/sys/podman.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Rootless container unit generation only.
+// Lifecycle (start/stop/restart/remove) is delegated to the systemd manager, since
+// container units live in the same directory under the same `kari-*` names.
+
+use async_trait::async_trait;
+use std::io::{Seek, SeekFrom, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::NamedTempFile;
+use tokio::fs;
+
+use crate::sys::executor::{CommandOutput, CommandSpec, Executor, SystemExecutor};
+use crate::sys::secrets::ProviderCredential;
+use crate::sys::systemd::{
+    LinuxSystemdManager, ServiceConfig, ServiceManager, ensure_unit_not_foreign, escape_specifiers,
+    is_valid_env_key, render_dependency_lines, render_slice_line, render_start_limit_lines,
+};
+use crate::sys::traits::ImageManager;
+
+/// Size of the subordinate UID/GID range handed to each app user for rootless podman.
+const SUBID_RANGE: u64 = 65_536;
+const SUBID_BASE: u64 = 100_000;
+
+/// PodmanServiceManager generates `podman-systemd` units that run an OCI image
+/// rootless as the per-app user, with the same cgroup limits as source deploys.
+pub struct PodmanServiceManager {
+    systemd: LinuxSystemdManager,
+}
+
+impl PodmanServiceManager {
+    pub fn new(systemd_dir: PathBuf) -> Self {
+        Self::with_executor(systemd_dir, Arc::new(SystemExecutor))
+    }
+
+    pub fn with_executor(systemd_dir: PathBuf, executor: Arc<dyn Executor>) -> Self {
+        Self {
+            systemd: LinuxSystemdManager::with_executor(systemd_dir, executor),
+        }
+    }
+
+    /// 🛡️ Rootless podman needs a private subordinate ID range per user.
+    /// Ranges are allocated above the highest existing entry so tenants never overlap.
+    async fn ensure_subids(&self, username: &str) -> Result<(), String> {
+        for file in ["/etc/subuid", "/etc/subgid"] {
+            let content = fs::read_to_string(file).await.unwrap_or_default();
+            if content
+                .lines()
+                .any(|l| l.split(':').next() == Some(username))
+            {
+                continue;
+            }
+
+            let next_start = content
+                .lines()
+                .filter_map(|l| {
+                    let mut parts = l.split(':');
+                    let _ = parts.next()?;
+                    let start = parts.next()?.parse::<u64>().ok()?;
+                    let count = parts.next()?.parse::<u64>().ok()?;
+                    Some(start + count)
+                })
+                .max()
+                .unwrap_or(SUBID_BASE)
+                .max(SUBID_BASE);
+
+            let range = format!("{}-{}", next_start, next_start + SUBID_RANGE - 1);
+            let flag = if file == "/etc/subuid" {
+                "--add-subuids"
+            } else {
+                "--add-subgids"
+            };
+
+            let usermod = CommandSpec::privileged("usermod").args([flag, &range, username]);
+            let output = self
+                .systemd
+                .executor()
+                .run(&usermod)
+                .await
+                .map_err(|e| format!("SLA Failure: usermod spawn error: {}", e))?;
+
+            if !output.success() {
+                return Err(format!(
+                    "Failed to allocate subordinate IDs for {}: {}",
+                    username,
+                    String::from_utf8_lossy(&output.stderr)
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rootless image storage lives inside the app directory, so teardown removes it too.
+fn storage_root(working_dir: &Path) -> PathBuf {
+    working_dir.join(".containers").join("storage")
+}
+
+/// 🛡️ Zero-Trust: Image references end up in ExecStart, so only registry/repo/tag/digest
+/// characters pass and nothing can be parsed as a podman flag.
+pub(crate) fn validate_image_ref(image: &str) -> Result<(), String> {
+    if image.is_empty()
+        || image.starts_with('-')
+        || image.len() > 512
+        || !image
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '/' | ':' | '@' | '_' | '-'))
+    {
+        return Err(format!("Zero-Trust: Invalid image reference: '{}'", image));
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl ServiceManager for PodmanServiceManager {
+    async fn write_unit_file(&self, config: &ServiceConfig) -> Result<(), String> {
+        let path = self.systemd.get_unit_path(&config.service_name)?;
+        ensure_unit_not_foreign(self.systemd.executor(), &path).await?;
+
+        let image = config
+            .image
+            .as_deref()
+            .ok_or("Container runtime requires an image reference")?;
+        validate_image_ref(image)?;
+        let port = config
+            .port
+            .filter(|p| *p != 0)
+            .ok_or("Container runtime requires a non-zero port")?;
+
+        self.ensure_subids(&config.username).await?;
+
+        // 1. 🛡️ Env values stay in the unit's 0600 env file; podman only receives
+        // key names, so secrets never appear in the unit or the container's argv.
+        let env_block = self.systemd.write_env_file(config).await?;
+        let dependency_lines = render_dependency_lines(&config.dependencies)?;
+        let env_flags: String = config
+            .env_vars
+            .keys()
+            .filter(|k| is_valid_env_key(k))
+            .map(|k| format!(" --env {}", k))
+            .collect();
+
+        let workdir = config.working_directory.to_string_lossy();
+        let storage = storage_root(&config.working_directory);
+        let storage = storage.to_string_lossy();
+        let cpus = f64::from(config.cpu_limit_percent.max(1)) / 100.0;
+
+        // NOTE: Rootless podman relies on the setuid newuidmap/newgidmap helpers and on
+        // writing its own cgroup subtree, so NoNewPrivileges/RestrictSUIDSGID and
+        // ProtectControlGroups cannot be applied here. The container itself runs in a
+        // user namespace with no host capabilities instead.
+        let unit_content = format!(
+            r#"[Unit]
+Description=Kari Managed Container: {service_name}
+Wants=network-online.target
+After=network-online.target
+{start_limit_lines}{dependency_lines}
+[Service]
+Type=notify
+NotifyAccess=all
+User={username}
+Group={username}
+WorkingDirectory={workdir}
+Environment="HOME={home}"
+{env_block}
+RuntimeDirectory={service_name}
+ExecStart=/usr/bin/podman --root {storage} --runroot %t/{service_name} run --rm --replace --name {service_name} --cgroups=split --sdnotify=conmon --pull=missing --publish 127.0.0.1:{port}:{port} --memory {mem_limit}m --cpus {cpus}{env_flags} {image}
+ExecStop=/usr/bin/podman --root {storage} --runroot %t/{service_name} stop --ignore --time 10 {service_name}
+Delegate=yes
+Restart=always
+RestartSec=5
+
+# --- ⚖️ Dynamic Resource Jailing ---
+{slice_line}CPUAccounting=true
+CPUQuota={cpu_limit}%
+MemoryAccounting=true
+MemoryMax={mem_limit}M
+TasksMax=512
+
+# --- 🛡️ Sandbox (rootless-compatible subset) ---
+PrivateTmp=true
+ProtectHome=true
+ProtectKernelModules=true
+RestrictRealtime=true
+
+[Install]
+WantedBy=multi-user.target
+"#,
+            service_name = config.service_name,
+            start_limit_lines = render_start_limit_lines(config.start_limit),
+            dependency_lines = dependency_lines,
+            username = config.username,
+            workdir = workdir,
+            home = escape_specifiers(&workdir),
+            storage = storage,
+            env_block = env_block,
+            env_flags = env_flags,
+            image = image,
+            port = port,
+            cpus = cpus,
+            slice_line = render_slice_line(config.slice.as_deref()),
+            cpu_limit = config.cpu_limit_percent,
+            mem_limit = config.memory_limit_mb
+        );
+
+        fs::write(&path, unit_content)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        // 2. 🛡️ Ensure standard 644 permissions (rw-r--r--)
+        let mut perms = fs::metadata(&path)
+            .await
+            .map_err(|e| e.to_string())?
+            .permissions();
+        perms.set_mode(0o644);
+        fs::set_permissions(&path, perms)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    async fn remove_unit_file(&self, service_name: &str) -> Result<(), String> {
+        self.systemd.remove_unit_file(service_name).await
+    }
+
+    async fn reload_daemon(&self) -> Result<(), String> {
+        self.systemd.reload_daemon().await
+    }
+
+    async fn enable_and_start(&self, service_name: &str) -> Result<(), String> {
+        self.systemd.enable_and_start(service_name).await
+    }
+
+    async fn start(&self, service_name: &str) -> Result<(), String> {
+        self.systemd.start(service_name).await
+    }
+
+    async fn stop(&self, service_name: &str) -> Result<(), String> {
+        self.systemd.stop(service_name).await
+    }
+
+    async fn restart(&self, service_name: &str) -> Result<(), String> {
+        self.systemd.restart(service_name).await
+    }
+
+    async fn is_enabled(&self, service_name: &str) -> Result<bool, String> {
+        self.systemd.is_enabled(service_name).await
+    }
+
+    async fn is_active(&self, service_name: &str) -> Result<bool, String> {
+        self.systemd.is_active(service_name).await
+    }
+
+    async fn start_limit_hit(&self, service_name: &str) -> Result<bool, String> {
+        self.systemd.start_limit_hit(service_name).await
+    }
+
+    async fn reset_failed(&self, service_name: &str) -> Result<(), String> {
+        self.systemd.reset_failed(service_name).await
+    }
+}
+
+/// A path as one argv word.
+fn path_arg(path: &Path) -> Result<&str, String> {
+    path.to_str()
+        .ok_or_else(|| format!("Invalid UTF-8 in path {:?}", path))
+}
+
+impl PodmanServiceManager {
+    /// Runs `podman` as the app user against its private storage, with a transient
+    /// runroot owned by that user (only lock/state files live there).
+    async fn run_podman_as(
+        &self,
+        user: &nix::unistd::User,
+        working_dir: &Path,
+        args: &[&str],
+    ) -> Result<CommandOutput, String> {
+        let runroot = tempfile::Builder::new()
+            .prefix("kari-podman-")
+            .tempdir()
+            .map_err(|e| format!("Temp dir error: {}", e))?;
+        nix::unistd::chown(runroot.path(), Some(user.uid), Some(user.gid))
+            .map_err(|e| format!("Failed to chown runroot: {}", e))?;
+
+        let podman = CommandSpec::privileged("runuser")
+            .args(["-u", &user.name, "--", "podman", "--root"])
+            .arg(path_arg(&storage_root(working_dir))?)
+            .args(["--runroot", path_arg(runroot.path())?])
+            .args(args.iter().copied())
+            .env("HOME", path_arg(working_dir)?)
+            .kill_on_drop();
+        self.systemd
+            .executor()
+            .run(&podman)
+            .await
+            .map_err(|e| format!("SLA Failure: podman spawn error: {}", e))
+    }
+}
+
+fn lookup_user(username: &str) -> Result<nix::unistd::User, String> {
+    nix::unistd::User::from_name(username)
+        .map_err(|e| format!("User lookup failed: {}", e))?
+        .ok_or_else(|| format!("Unknown app user '{}'", username))
+}
+
+#[async_trait]
+impl ImageManager for PodmanServiceManager {
+    async fn pull_image(
+        &self,
+        image: &str,
+        run_as_user: &str,
+        working_dir: &Path,
+        auth: Option<ProviderCredential>,
+    ) -> Result<(), String> {
+        validate_image_ref(image)?;
+        let user = lookup_user(run_as_user)?;
+
+        // 1. 🛡️ Ephemeral auth.json: 0600, owned by the app user, scrubbed after the pull
+        let mut auth_guard = None;
+        if let Some(cred) = auth {
+            let mut temp = NamedTempFile::new().map_err(|e| format!("Temp file error: {}", e))?;
+            std::fs::set_permissions(temp.path(), std::fs::Permissions::from_mode(0o600))
+                .map_err(|e| e.to_string())?;
+
+            cred.use_secret(|secret_str| temp.write_all(secret_str.as_bytes()))
+                .map_err(|e| format!("Failed to write registry auth: {}", e))?;
+            temp.as_file().sync_all().map_err(|e| e.to_string())?;
+            cred.destroy();
+
+            nix::unistd::chown(temp.path(), Some(user.uid), Some(user.gid))
+                .map_err(|e| format!("Failed to chown auth file: {}", e))?;
+            auth_guard = Some(temp);
+        }
+
+        // 2. Pull into the app's rootless storage
+        let mut args = vec!["pull", "--quiet"];
+        if let Some(ref temp) = auth_guard {
+            args.push("--authfile");
+            args.push(path_arg(temp.path())?);
+        }
+        args.push("--");
+        args.push(image);
+        let output = self.run_podman_as(&user, working_dir, &args).await;
+
+        // 3. 🛡️ Disk Residue Scrubbing (regardless of pull success)
+        if let Some(mut temp) = auth_guard {
+            let _ = temp.seek(SeekFrom::Start(0));
+            let _ = temp.write_all(&[0u8; 4096]);
+            let _ = temp.as_file().sync_all();
+        }
+
+        let output = output?;
+        if !output.success() {
+            return Err(format!(
+                "Image pull failed for {}: {}",
+                image,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        Ok(())
+    }
+
+    async fn prune_images(
+        &self,
+        run_as_user: &str,
+        working_dir: &Path,
+        keep: &[String],
+    ) -> Result<usize, String> {
+        if !storage_root(working_dir).exists() {
+            return Ok(0); // Nothing was ever pulled for this app
+        }
+        let user = lookup_user(run_as_user)?;
+
+        // 1. Resolve the image IDs that must survive (the deployed references)
+        let mut keep_ids = Vec::new();
+        for image in keep {
+            validate_image_ref(image)?;
+            let out = self
+                .run_podman_as(
+                    &user,
+                    working_dir,
+                    &["image", "inspect", "--format", "{{.Id}}", "--", image],
+                )
+                .await?;
+            if out.success() {
+                keep_ids.push(String::from_utf8_lossy(&out.stdout).trim().to_string());
+            }
+        }
+
+        // 2. Remove every other image in this user's storage
+        let listed = self
+            .run_podman_as(&user, working_dir, &["images", "--quiet", "--no-trunc"])
+            .await?;
+        if !listed.success() {
+            return Err(format!(
+                "Failed to list images: {}",
+                String::from_utf8_lossy(&listed.stderr)
+            ));
+        }
+
+        let mut removed = 0;
+        let listed = String::from_utf8_lossy(&listed.stdout);
+        for id in listed.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let bare = id.trim_start_matches("sha256:");
+            if keep_ids
+                .iter()
+                .any(|k| k.trim_start_matches("sha256:") == bare)
+            {
+                continue;
+            }
+            // No --force: an image still backing a container is left alone.
+            let rmi = self.run_podman_as(&user, working_dir, &["rmi", id]).await?;
+            if rmi.success() {
+                removed += 1;
+            } else {
+                tracing::warn!(
+                    "Skipping image {} for {}: {}",
+                    id,
+                    run_as_user,
+                    String::from_utf8_lossy(&rmi.stderr).trim()
+                );
+            }
+        }
+
+        // 3. Drop dangling layers left behind by the removals
+        let _ = self
+            .run_podman_as(&user, working_dir, &["image", "prune", "--force"])
+            .await;
+
+        Ok(removed)
+    }
+
+    async fn set_unit_image(&self, service_name: &str, image: &str) -> Result<(), String> {
+        validate_image_ref(image)?;
+        let path = self.systemd.get_unit_path(service_name)?;
+        let unit = fs::read_to_string(&path)
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let unit = retarget_exec_start(&unit, image)
+            .ok_or_else(|| format!("{}.service is not a Kari container unit", service_name))?;
+        fs::write(&path, unit).await.map_err(|e| e.to_string())
+    }
+}
+
+/// The unit with the image, always the last word of the `podman run`
+/// ExecStart, replaced. None when there is no such line.
+fn retarget_exec_start(unit: &str, image: &str) -> Option<String> {
+    let mut found = false;
+    let mut retargeted = String::with_capacity(unit.len());
+    for line in unit.lines() {
+        match line.rsplit_once(' ') {
+            Some((head, _)) if line.starts_with("ExecStart=/usr/bin/podman ") => {
+                found = true;
+                retargeted.push_str(head);
+                retargeted.push(' ');
+                retargeted.push_str(image);
+            }
+            _ => retargeted.push_str(line),
+        }
+        retargeted.push('\n');
+    }
+    found.then_some(retargeted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::FakeExecutor;
+
+    #[tokio::test]
+    async fn pulls_run_podman_as_the_app_user_with_its_own_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let executor = Arc::new(FakeExecutor::default());
+        let podman =
+            PodmanServiceManager::with_executor(dir.path().join("systemd"), executor.clone());
+        let user = nix::unistd::User::from_uid(nix::unistd::getuid())
+            .unwrap()
+            .unwrap();
+
+        podman
+            .pull_image("nginx:1.27", &user.name, dir.path(), None)
+            .await
+            .unwrap();
+        let pull = executor.runs().pop().unwrap();
+        assert!(pull.privileged && pull.kill_on_drop);
+        let storage = storage_root(dir.path());
+        assert_eq!(
+            pull.args[..6],
+            [
+                "-u",
+                &user.name,
+                "--",
+                "podman",
+                "--root",
+                storage.to_str().unwrap()
+            ]
+        );
+        assert!(pull.args.ends_with(&[
+            "pull".to_string(),
+            "--quiet".to_string(),
+            "--".to_string(),
+            "nginx:1.27".to_string(),
+        ]));
+        assert_eq!(pull.env_value("HOME"), dir.path().to_str());
+
+        executor.answer(&["runuser"], 125, "", "Error: manifest unknown");
+        let err = podman
+            .pull_image("nginx:1.27", &user.name, dir.path(), None)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            "Image pull failed for nginx:1.27: Error: manifest unknown"
+        );
+    }
+
+    #[test]
+    fn test_validate_image_ref_valid() {
+        assert!(validate_image_ref("nginx").is_ok());
+        assert!(validate_image_ref("docker.io/library/nginx:1.27").is_ok());
+        assert!(validate_image_ref("ghcr.io/acme/api@sha256:abc123").is_ok());
+        assert!(validate_image_ref("registry.local:5000/team/app_v2").is_ok());
+    }
+
+    #[test]
+    fn test_validate_image_ref_invalid() {
+        assert!(validate_image_ref("").is_err());
+        assert!(validate_image_ref("--privileged").is_err());
+        assert!(validate_image_ref("nginx --net=host").is_err());
+        assert!(validate_image_ref("nginx\nExecStartPre=/bin/sh").is_err());
+        assert!(validate_image_ref("nginx;reboot").is_err());
+    }
+
+    #[test]
+    fn only_the_image_of_the_run_line_is_retargeted() {
+        let unit = "[Service]\n\
+            ExecStart=/usr/bin/podman --root /srv/.containers/storage run --rm --publish 127.0.0.1:8080:8080 ghcr.io/acme/api:1.0\n\
+            ExecStop=/usr/bin/podman --root /srv/.containers/storage stop --ignore --time 10 kari-api.example.com\n";
+        let retargeted = retarget_exec_start(unit, "ghcr.io/acme/api:1.1").unwrap();
+        assert!(retargeted.contains("127.0.0.1:8080:8080 ghcr.io/acme/api:1.1\n"));
+        assert!(!retargeted.contains("api:1.0"));
+        assert!(retargeted.contains("--time 10 kari-api.example.com\n"));
+        assert!(retarget_exec_start("[Service]\nExecStart=/usr/bin/node app.js\n", "x").is_none());
+    }
+}
@@ -0,0 +1,473 @@
+// agent/src/sys/privilege.rs
+//
+// 🛡️ SOLID: Single-Responsibility — What a rootless agent is allowed to do.
+//
+// As root the agent can do everything it manages. Run as an ordinary user it
+// holds only what the host grants: sudo rules for specific commands (useradd,
+// systemctl, chown, ...), polkit for managing units, and write access to the
+// directories it generates files in. Those grants are probed once at startup
+// and grouped into capabilities; every privileged command is spawned through
+// `command`, which adds `sudo -n` where a rule covers it, and an RPC whose
+// capabilities are not all granted is refused before its handler runs.
+//
+// The grouping describes what the agent uses each grant for, not how far the
+// grant reaches. Several are root-equivalent on their own: a sudo rule for
+// `systemd-run` or `systemctl` (units) starts any program as any user, and
+// `chown`, `useradd` and `iptables` are little better. Treat every sudo rule
+// handed to the agent as handing it root; rootless mode limits what the agent
+// attempts and shrinks the exposure of its own process, nothing more.
+//
+// `sudo` resets the environment, so nothing a privileged command needs may
+// travel in it: builds hand their env to systemd-run as an EnvironmentFile=.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use tokio::process::Command;
+
+/// A group of privileged actions RPCs depend on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Capability {
+    Users,        // App users and their processes
+    Units,        // systemd units, timers and slices; root-equivalent through sudo
+    Ownership,    // chown/chmod of app files
+    Proxy,        // Vhost files and proxy reloads
+    Certificates, // The SSL store
+    Firewall,
+    Packages,
+    Mail,
+    TimeSync,
+    Disks, // SMART data
+    Mac,   // AppArmor profiles, SELinux file contexts
+    Sftp,  // sshd drop-ins and SFTP chroots
+    Store, // Secrets, artifacts, pinned host keys and Kari's own logs
+}
+
+impl Capability {
+    pub const ALL: [Capability; 13] = [
+        Self::Users,
+        Self::Units,
+        Self::Ownership,
+        Self::Proxy,
+        Self::Certificates,
+        Self::Firewall,
+        Self::Packages,
+        Self::Mail,
+        Self::TimeSync,
+        Self::Disks,
+        Self::Mac,
+        Self::Sftp,
+        Self::Store,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Users => "users",
+            Self::Units => "units",
+            Self::Ownership => "ownership",
+            Self::Proxy => "proxy",
+            Self::Certificates => "certificates",
+            Self::Firewall => "firewall",
+            Self::Packages => "packages",
+            Self::Mail => "mail",
+            Self::TimeSync => "time_sync",
+            Self::Disks => "disks",
+            Self::Mac => "mac",
+            Self::Sftp => "sftp",
+            Self::Store => "store",
+        }
+    }
+
+    /// Commands the capability runs that need root. Only the installed ones
+    /// are probed; a missing binary is the host's gap, not a privilege.
+    fn programs(self) -> &'static [&'static str] {
+        match self {
+            Self::Users => &["useradd", "usermod", "userdel", "killall", "runuser"],
+            Self::Units => &["systemctl", "systemd-run"],
+            Self::Ownership => &["chown", "chmod"],
+            Self::Proxy => &["nginx", "apache2ctl"],
+            Self::Certificates => &[],
+            Self::Firewall => &["iptables"],
+            Self::Packages => &["apt-get", "dnf", "yum", "zypper"],
+            Self::Mail => &["postconf", "postmap"],
+            Self::TimeSync => &["timedatectl"],
+            Self::Disks => &["smartctl"],
+            Self::Mac => &["apparmor_parser", "semanage", "restorecon"],
+            Self::Sftp => &["sshd", "pkill"],
+            Self::Store => &[],
+        }
+    }
+}
+
+/// 🛡️ The capabilities each RPC needs, for every RPC: one missing here is
+/// refused, whatever was granted. An empty list means it only reads.
+const RPC_REQUIREMENTS: &[(&str, &[Capability])] = {
+    use Capability::*;
+    &[
+        ("get_system_status", &[]),
+        ("execute_package_command", &[Packages]),
+        ("provision_app_jail", &[Users, Ownership, Units, Proxy, Mac]),
+        ("manage_service", &[Units]),
+        ("stream_deployment", &[Users, Ownership, Units, Proxy]),
+        ("delete_deployment", &[Users, Units, Proxy, Mac]),
+        ("teardown_jail", &[Users, Units]),
+        ("write_system_file", &[Ownership]),
+        ("install_certificate", &[Certificates, Proxy]),
+        ("issue_certificate", &[Certificates, Proxy]),
+        ("set_redirects", &[Proxy]),
+        ("apply_firewall_policy", &[Firewall]),
+        ("schedule_job", &[Units]),
+        ("configure_mail_relay", &[Mail, Units]),
+        ("prune_images", &[Users]),
+        ("write_app_env_file", &[Ownership]),
+        ("import_state", &[Units, Proxy, Certificates]),
+        (
+            "import_app",
+            &[Users, Ownership, Units, Proxy, Certificates, Mac],
+        ),
+        ("set_tenant_quota", &[Units]),
+        ("run_self_test", &[Users, Ownership, Units, Proxy]),
+        ("prebuild_release", &[Users, Ownership, Units]),
+        ("activate_release", &[Units, Proxy]),
+        ("rollback_deployment", &[Units, Proxy]),
+        ("prune_releases", &[Ownership]),
+        ("confirm_deployment", &[Units, Proxy]),
+        ("cancel_deployment", &[Units]),
+        ("reset_circuit_breaker", &[Units, Proxy]),
+        ("configure_time_sync", &[TimeSync, Units]),
+        (
+            "apply_config_bundle",
+            &[Users, Ownership, Units, Proxy, Firewall, Mac],
+        ),
+        ("schedule_reboot", &[Units]),
+        ("expose_debug_port", &[Firewall, Units]),
+        ("deploy_artifact", &[Users, Ownership, Units, Proxy]),
+        ("verify_app_permissions", &[Ownership]),
+        ("set_sftp_access", &[Sftp, Units]),
+        ("set_patch_policy", &[Packages, Units, Proxy]),
+        // Built-in operations run nginx and certbot; agent.toml's own run
+        // with whatever the agent holds for their programs
+        ("run_host_operation", &[Proxy, Certificates]),
+        ("purge_tenant_logs", &[Store]),
+        // repair re-enables units, recreates vhosts and re-applies rules
+        ("verify_autostart", &[Units, Proxy, Firewall]),
+        ("pin_ssh_host_keys", &[Store]),
+        ("push_artifact", &[Store]),
+        ("put_secret", &[Store]),
+        ("set_registry_credential", &[Store]),
+        ("set_satellite_key", &[Store]),
+        ("get_operation", &[]),
+        ("export_state", &[]),
+        ("export_app", &[]),
+        ("get_deploy_sbom", &[]),
+        ("get_build_log", &[]),
+        ("list_deployments", &[]),
+        ("list_jobs", &[]),
+        ("list_firewall_rules", &[]),
+        ("stream_firewall_events", &[]),
+        ("stream_tamper_events", &[]),
+        ("stream_circuit_events", &[]),
+        ("get_network_inventory", &[]),
+        ("get_time_sync_status", &[]),
+        ("get_host_facts", &[]),
+        ("pull_artifact", &[]),
+        ("stream_key_rotation_events", &[]),
+        ("check_issuance", &[]),
+        ("stream_disk_health_events", &[]),
+        ("get_certificate_info", &[]),
+        ("export_managed_configs", &[]),
+        ("get_privileges", &[]),
+        ("get_app_health", &[]),
+        ("list_jail_processes", &[]),
+        ("list_vhosts", &[]),
+        ("list_releases", &[]),
+        // Satellites are driven over their own connections, not through this host
+        ("list_satellites", &[]),
+        ("apply_satellite_firewall_policy", &[]),
+        ("manage_satellite_service", &[]),
+        ("set_satellite_vhost", &[]),
+        ("get_placement_score", &[]),
+        ("stream_service_logs", &[]),
+        ("get_patch_reports", &[]),
+    ]
+};
+
+/// How a capability is held, and what it lacks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Grant {
+    pub via: String, // "root" | "sudo" | "polkit" | "direct" (writable dirs, nothing to run as root)
+    pub missing: Vec<String>, // Commands without a rule, directories not writable
+}
+
+impl Grant {
+    pub fn granted(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Privileges {
+    pub rootless: bool,
+    pub user: String,
+    /// Commands spawned as `sudo -n -- <program>`.
+    pub sudo: BTreeSet<String>,
+    pub grants: BTreeMap<Capability, Grant>,
+}
+
+impl Privileges {
+    /// Running as root: everything is granted, nothing goes through sudo.
+    pub fn root() -> Self {
+        Self {
+            rootless: false,
+            user: "root".into(),
+            sudo: BTreeSet::new(),
+            grants: Capability::ALL
+                .into_iter()
+                .map(|cap| {
+                    let grant = Grant {
+                        via: "root".into(),
+                        missing: Vec::new(),
+                    };
+                    (cap, grant)
+                })
+                .collect(),
+        }
+    }
+
+    /// What this process may do. `writable` names the directories each
+    /// capability writes into.
+    pub async fn probe(writable: &[(Capability, &Path)]) -> Self {
+        let euid = nix::unistd::geteuid();
+        if euid.is_root() {
+            return Self::root();
+        }
+        let user = nix::unistd::User::from_uid(euid)
+            .ok()
+            .flatten()
+            .map(|u| u.name)
+            .unwrap_or_else(|| euid.to_string());
+        let polkit_units = polkit_allows("org.freedesktop.systemd1.manage-units").await;
+
+        let mut sudo = BTreeSet::new();
+        let mut grants = BTreeMap::new();
+        for cap in Capability::ALL {
+            let mut grant = Grant {
+                via: "direct".into(),
+                missing: Vec::new(),
+            };
+            for program in cap.programs().iter().filter(|p| installed(p)) {
+                if cap == Capability::Units && polkit_units {
+                    grant.via = "polkit".into();
+                } else if sudo_allows(program).await {
+                    sudo.insert(program.to_string());
+                    grant.via = "sudo".into();
+                } else {
+                    grant.missing.push(program.to_string());
+                }
+            }
+            for (_, dir) in writable.iter().filter(|(c, _)| *c == cap) {
+                if !is_writable(dir) {
+                    grant.missing.push(dir.display().to_string());
+                }
+            }
+            grants.insert(cap, grant);
+        }
+        Self {
+            rootless: true,
+            user,
+            sudo,
+            grants,
+        }
+    }
+
+    /// Refuses an RPC (by handler name) that needs a capability not granted.
+    pub fn check(&self, rpc: &str) -> Result<(), String> {
+        let Some(required) = required(rpc) else {
+            return Err(format!(
+                "{} has no privilege requirements on record and is refused",
+                method_name(rpc)
+            ));
+        };
+        let missing: Vec<&str> = required
+            .iter()
+            .filter(|cap| !self.grants.get(cap).is_some_and(Grant::granted))
+            .map(|cap| cap.name())
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        Err(format!(
+            "Rootless: {} needs {}, which {} was not granted (see GetPrivileges)",
+            method_name(rpc),
+            missing.join(", "),
+            self.user
+        ))
+    }
+
+    /// gRPC method names of the RPCs `check` refuses.
+    pub fn unavailable_rpcs(&self) -> Vec<String> {
+        RPC_REQUIREMENTS
+            .iter()
+            .map(|(rpc, _)| *rpc)
+            .filter(|rpc| self.check(rpc).is_err())
+            .map(method_name)
+            .collect()
+    }
+}
+
+fn required(rpc: &str) -> Option<&'static [Capability]> {
+    RPC_REQUIREMENTS
+        .iter()
+        .find(|(name, _)| *name == rpc)
+        .map(|(_, caps)| *caps)
+}
+
+/// `provision_app_jail` → `ProvisionAppJail`
+fn method_name(rpc: &str) -> String {
+    rpc.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+fn installed(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+fn is_writable(dir: &Path) -> bool {
+    nix::unistd::access(dir, nix::unistd::AccessFlags::W_OK).is_ok()
+}
+
+/// Whether a sudo rule lets this user run `program` without a password.
+async fn sudo_allows(program: &str) -> bool {
+    Command::new("sudo")
+        .args(["-n", "-l", program])
+        .output()
+        .await
+        .is_ok_and(|o| o.status.success())
+}
+
+async fn polkit_allows(action: &str) -> bool {
+    Command::new("pkcheck")
+        .args(["--action-id", action, "--process"])
+        .arg(std::process::id().to_string())
+        .output()
+        .await
+        .is_ok_and(|o| o.status.success())
+}
+
+static PROCESS_PRIVILEGES: OnceLock<Arc<Privileges>> = OnceLock::new();
+
+/// Records what this process was granted, for `command` and `current`.
+/// Only the first call takes effect.
+pub fn install(privileges: Arc<Privileges>) {
+    let _ = PROCESS_PRIVILEGES.set(privileges);
+}
+
+/// What this process was granted; root's grants until `install` is called.
+pub fn current() -> Arc<Privileges> {
+    PROCESS_PRIVILEGES
+        .get()
+        .cloned()
+        .unwrap_or_else(|| Arc::new(Privileges::root()))
+}
+
+fn via_sudo(program: &str) -> bool {
+    PROCESS_PRIVILEGES
+        .get()
+        .is_some_and(|p| p.sudo.contains(program))
+}
+
+/// A command for a program that may need root: run directly, or through
+/// `sudo -n` when a rootless agent holds a rule for it.
+pub fn command(program: &str) -> Command {
+    if via_sudo(program) {
+        let mut command = Command::new("sudo");
+        command.args(["-n", "--", program]);
+        command
+    } else {
+        Command::new(program)
+    }
+}
+
+/// `command`, for the few places that must block.
+pub fn std_command(program: &str) -> std::process::Command {
+    if via_sudo(program) {
+        let mut command = std::process::Command::new("sudo");
+        command.args(["-n", "--", program]);
+        command
+    } else {
+        std::process::Command::new(program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rpcs_are_refused_for_capabilities_not_granted() {
+        let mut privileges = Privileges::root();
+        privileges.rootless = true;
+        privileges.user = "kari".into();
+        assert!(privileges.check("provision_app_jail").is_ok());
+
+        privileges.grants.insert(
+            Capability::Firewall,
+            Grant {
+                via: "direct".into(),
+                missing: vec!["iptables".into()],
+            },
+        );
+        let refused = privileges.check("apply_config_bundle").unwrap_err();
+        assert!(
+            refused.contains("ApplyConfigBundle needs firewall"),
+            "{}",
+            refused
+        );
+        assert!(privileges.check("list_deployments").is_ok());
+        assert_eq!(
+            privileges.unavailable_rpcs(),
+            vec![
+                "ApplyFirewallPolicy",
+                "ApplyConfigBundle",
+                "ExposeDebugPort",
+                "VerifyAutostart"
+            ]
+        );
+
+        let unknown = Privileges::root().check("drop_all_tables").unwrap_err();
+        assert!(
+            unknown.contains("DropAllTables has no privilege"),
+            "{}",
+            unknown
+        );
+    }
+
+    #[test]
+    fn every_rpc_has_its_requirements_on_record() {
+        let missing: Vec<&str> = crate::validation::VALIDATED_RPCS
+            .iter()
+            .copied()
+            .filter(|rpc| required(rpc).is_none())
+            .collect();
+        assert!(
+            missing.is_empty(),
+            "no RPC_REQUIREMENTS entry: {:?}",
+            missing
+        );
+        let stale: Vec<&str> = RPC_REQUIREMENTS
+            .iter()
+            .map(|(rpc, _)| *rpc)
+            .filter(|rpc| !crate::validation::VALIDATED_RPCS.contains(rpc))
+            .collect();
+        assert!(stale.is_empty(), "not an RPC: {:?}", stale);
+    }
+}
@@ -0,0 +1,204 @@
+// agent/src/sys/processes.rs
+//
+// 🛡️ SOLID: Single-Responsibility — What is running inside an app's jail.
+//
+// A process belongs to the app when its cgroup (/proc/<pid>/cgroup) lies
+// under the app's service unit, which also catches container payloads and
+// anything a compromised app forked off. CPU is sampled over a short window
+// from /proc/<pid>/stat, so a cryptominer shows up as what it is using now,
+// not averaged over a long uptime.
+
+use async_trait::async_trait;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::sys::network::unit_from_cgroup;
+use crate::sys::traits::{ProcessInspector, UnitProcess};
+
+/// Kernel-reported CPU times are in USER_HZ, which Linux fixes at 100.
+const CLOCK_TICKS: f64 = 100.0;
+const CPU_SAMPLE: Duration = Duration::from_millis(250);
+
+/// From /proc/<pid>/stat: the parent, and user plus system CPU in ticks.
+/// comm may contain spaces and parentheses, so fields are counted from its
+/// last ')'.
+fn parse_stat(content: &str) -> Option<(u32, u64)> {
+    let rest = &content[content.rfind(')')? + 1..];
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    // fields[0] is the state (field 3 of stat(5))
+    let ppid = fields.get(1)?.parse().ok()?;
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some((ppid, utime + stime))
+}
+
+/// VmRSS (bytes), the real uid and the thread count from /proc/<pid>/status.
+fn parse_status(content: &str) -> (u64, Option<u32>, u32) {
+    let field = |name: &str| {
+        content
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|value| value.split_whitespace().next())
+    };
+    let rss_kb: u64 = field("VmRSS:").and_then(|v| v.parse().ok()).unwrap_or(0);
+    let uid = field("Uid:").and_then(|v| v.parse().ok());
+    let threads = field("Threads:").and_then(|v| v.parse().ok()).unwrap_or(0);
+    (rss_kb * 1024, uid, threads)
+}
+
+fn read_pids(proc_root: &Path, unit: &str) -> Result<Vec<u32>, String> {
+    let entries = std::fs::read_dir(proc_root)
+        .map_err(|e| format!("{} is not readable: {}", proc_root.display(), e))?;
+    Ok(entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|pid| {
+            std::fs::read_to_string(proc_root.join(pid.to_string()).join("cgroup"))
+                .ok()
+                .as_deref()
+                .and_then(unit_from_cgroup)
+                .is_some_and(|u| u == unit)
+        })
+        .collect())
+}
+
+fn cpu_ticks(proc_root: &Path, pids: &[u32]) -> HashMap<u32, u64> {
+    pids.iter()
+        .filter_map(|pid| {
+            let stat =
+                std::fs::read_to_string(proc_root.join(pid.to_string()).join("stat")).ok()?;
+            Some((*pid, parse_stat(&stat)?.1))
+        })
+        .collect()
+}
+
+/// One process, with CPU measured against `before`. Processes that exited
+/// since the scan are None.
+fn read_process(proc_root: &Path, pid: u32, before: Option<u64>) -> Option<UnitProcess> {
+    let dir = proc_root.join(pid.to_string());
+    let (ppid, ticks) = parse_stat(&std::fs::read_to_string(dir.join("stat")).ok()?)?;
+    let (rss_bytes, uid, threads) =
+        parse_status(&std::fs::read_to_string(dir.join("status")).ok()?);
+    let cmdline = std::fs::read(dir.join("cmdline")).unwrap_or_default();
+    let command = cmdline
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let command = if command.is_empty() {
+        let comm = std::fs::read_to_string(dir.join("comm")).unwrap_or_default();
+        format!("[{}]", comm.trim())
+    } else {
+        command
+    };
+    let user = uid
+        .map(|uid| {
+            nix::unistd::User::from_uid(nix::unistd::Uid::from_raw(uid))
+                .ok()
+                .flatten()
+                .map(|u| u.name)
+                .unwrap_or_else(|| uid.to_string())
+        })
+        .unwrap_or_default();
+    let used = ticks.saturating_sub(before.unwrap_or(ticks)) as f64;
+    Some(UnitProcess {
+        pid,
+        ppid,
+        user,
+        command,
+        cpu_percent: used / CLOCK_TICKS / CPU_SAMPLE.as_secs_f64() * 100.0,
+        rss_bytes,
+        threads,
+    })
+}
+
+/// Orders processes depth-first from the unit's main process(es), children by
+/// pid, pairing each with its depth. A process whose parent is outside the
+/// set (reparented, or the unit's own main process) is a root.
+pub fn tree(processes: Vec<UnitProcess>) -> Vec<(u32, UnitProcess)> {
+    let pids: Vec<u32> = processes.iter().map(|p| p.pid).collect();
+    let mut children: BTreeMap<u32, Vec<UnitProcess>> = BTreeMap::new();
+    let mut roots = Vec::new();
+    for process in processes {
+        if process.ppid != process.pid && pids.contains(&process.ppid) {
+            children.entry(process.ppid).or_default().push(process);
+        } else {
+            roots.push(process);
+        }
+    }
+    roots.sort_by_key(|p| p.pid);
+    let mut ordered = Vec::new();
+    let mut stack: Vec<(u32, UnitProcess)> = roots.into_iter().rev().map(|p| (0, p)).collect();
+    while let Some((depth, process)) = stack.pop() {
+        let mut kids = children.remove(&process.pid).unwrap_or_default();
+        kids.sort_by_key(|p| std::cmp::Reverse(p.pid));
+        stack.extend(kids.into_iter().map(|p| (depth + 1, p)));
+        ordered.push((depth, process));
+    }
+    ordered
+}
+
+pub struct ProcProcessInspector;
+
+#[async_trait]
+impl ProcessInspector for ProcProcessInspector {
+    async fn unit_processes(&self, unit: &str) -> Result<Vec<UnitProcess>, String> {
+        let proc_root = Path::new("/proc");
+        let unit = unit.to_string();
+        let (pids, before) = tokio::task::spawn_blocking(move || {
+            let pids = read_pids(proc_root, &unit)?;
+            let before = cpu_ticks(proc_root, &pids);
+            Ok::<_, String>((pids, before))
+        })
+        .await
+        .map_err(|e| format!("Process scan panicked: {}", e))??;
+        tokio::time::sleep(CPU_SAMPLE).await;
+        tokio::task::spawn_blocking(move || {
+            pids.into_iter()
+                .filter_map(|pid| read_process(proc_root, pid, before.get(&pid).copied()))
+                .collect()
+        })
+        .await
+        .map_err(|e| format!("Process scan panicked: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, ppid: u32) -> UnitProcess {
+        UnitProcess {
+            pid,
+            ppid,
+            user: "kari-app-shop".into(),
+            command: format!("cmd-{}", pid),
+            cpu_percent: 0.0,
+            rss_bytes: 0,
+            threads: 1,
+        }
+    }
+
+    #[test]
+    fn stat_and_status_survive_odd_command_names() {
+        let stat = "4242 (node (worker) 1) S 4200 4242 4242 0 -1 4194560 1790 0 0 0 350 25 0 0 20 0 11 0 123456 1000000 5000";
+        assert_eq!(parse_stat(stat), Some((4200, 375)));
+        let status = "Name:\tnode\nUid:\t998\t998\t998\t998\nVmRSS:\t  51200 kB\nThreads:\t11\n";
+        assert_eq!(parse_status(status), (51200 * 1024, Some(998), 11));
+    }
+
+    #[test]
+    fn processes_are_ordered_as_a_tree() {
+        let ordered = tree(vec![
+            process(30, 10),
+            process(10, 1),
+            process(20, 10),
+            process(40, 20),
+            process(50, 777), // Parent outside the unit
+        ]);
+        let shape: Vec<(u32, u32)> = ordered.iter().map(|(d, p)| (*d, p.pid)).collect();
+        assert_eq!(shape, vec![(0, 10), (1, 20), (2, 40), (1, 30), (0, 50)]);
+    }
+}
@@ -1,8 +1,13 @@
-use crate::sys::traits::ProxyManager;
+use crate::sys::executor::{CommandSpec, Executor, SystemExecutor};
+use crate::sys::tls_policy::preset;
+use crate::sys::traits::{
+    ClientLimits, ProxyManager, RateLimit, RealIp, RedirectRule, VhostOptions, VhostTls,
+};
 use async_trait::async_trait;
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::fs;
-use tokio::process::Command;
 
 /// 🛡️ Zero-Trust: Strictly validates domain names to prevent config injection
 fn validate_domain_format(domain: &str) -> Result<(), String> {
@@ -45,45 +50,422 @@ fn validate_domain_format(domain: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// 🛡️ Zero-Trust: Header names and CIDRs are written into the config verbatim,
+/// so they are held to the characters they can legitimately contain.
+fn validate_real_ip(real_ip: &RealIp) -> Result<(), String> {
+    if real_ip.header.is_empty()
+        || !real_ip
+            .header
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Err(format!(
+            "Zero-Trust: Invalid real IP header: '{}'",
+            real_ip.header
+        ));
+    }
+    if real_ip.trusted_proxies.is_empty() {
+        return Err("Real IP handling needs at least one trusted proxy".to_string());
+    }
+    for cidr in &real_ip.trusted_proxies {
+        if cidr.is_empty()
+            || !cidr
+                .chars()
+                .all(|c| c.is_ascii_hexdigit() || matches!(c, '.' | ':' | '/'))
+        {
+            return Err(format!("Zero-Trust: Invalid trusted proxy: '{}'", cidr));
+        }
+    }
+    Ok(())
+}
+
+pub const MAX_REDIRECTS: usize = 200;
+const ACME_CHALLENGE_PATH: &str = "/.well-known/acme-challenge/";
+
+/// 🛡️ Zero-Trust: Redirect paths and targets are written into the config
+/// verbatim (paths into regexes too), so both are held to plain URL
+/// characters: no whitespace, quotes, `$`, `;`, `#`, braces or backslashes.
+pub fn validate_redirects(rules: &[RedirectRule]) -> Result<(), String> {
+    if rules.len() > MAX_REDIRECTS {
+        return Err(format!("At most {} redirects per domain", MAX_REDIRECTS));
+    }
+    let mut seen = HashSet::new();
+    for rule in rules {
+        let path = rule.path.strip_suffix('*').unwrap_or(&rule.path);
+        let path_ok = path.starts_with('/')
+            && path.len() <= 256
+            && !path.contains("//")
+            && !path.split('/').any(|segment| segment == "..")
+            && (path.len() == rule.path.len() || path.ends_with('/'))
+            && path
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '.' | '_' | '~' | '-'));
+        if !path_ok {
+            return Err(format!(
+                "Zero-Trust: Invalid redirect path: '{}'",
+                rule.path
+            ));
+        }
+        // 🔏 Certificates keep renewing whatever is redirected
+        if path.starts_with(ACME_CHALLENGE_PATH) {
+            return Err(format!(
+                "Redirect path '{}' would hide ACME challenges",
+                rule.path
+            ));
+        }
+        if !seen.insert(rule.path.as_str()) {
+            return Err(format!("Duplicate redirect for '{}'", rule.path));
+        }
+
+        let target = &rule.target;
+        let target_ok = target.len() <= 2048
+            && target.chars().all(|c| {
+                c.is_ascii_alphanumeric()
+                    || matches!(
+                        c,
+                        '/' | '.' | '_' | '~' | '-' | ':' | '?' | '=' | '&' | '%' | '+' | ',' | '@'
+                    )
+            })
+            && match target
+                .strip_prefix("https://")
+                .or_else(|| target.strip_prefix("http://"))
+            {
+                Some(rest) => {
+                    let host = rest.split('/').next().unwrap_or_default();
+                    validate_domain_format(host).is_ok()
+                }
+                None => target.starts_with('/') && !target.starts_with("//"),
+            };
+        if !target_ok {
+            return Err(format!(
+                "Zero-Trust: Invalid redirect target: '{}'",
+                rule.target
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Both servers take the first redirect that matches, so exact paths go
+/// first, then prefixes from the most specific down.
+fn redirect_order(rules: &[RedirectRule]) -> Vec<&RedirectRule> {
+    let mut ordered: Vec<&RedirectRule> = rules.iter().collect();
+    ordered.sort_by_key(|rule| (rule.path.ends_with('*'), std::cmp::Reverse(rule.path.len())));
+    ordered
+}
+
+/// The regex a redirect's path compiles to; a prefix captures its remainder,
+/// minus the ACME challenges when it covers them. Paths are plain URL
+/// characters, so '.' is the only one to escape.
+fn redirect_regex(rule: &RedirectRule) -> String {
+    let escape = |path: &str| path.replace('.', "\\.");
+    match rule.path.strip_suffix('*') {
+        Some(prefix) => match ACME_CHALLENGE_PATH.strip_prefix(prefix) {
+            Some(challenges) => format!("^{}(?!{})(.*)$", escape(prefix), escape(challenges)),
+            None => format!("^{}(.*)$", escape(prefix)),
+        },
+        None => format!("^{}$", escape(&rule.path)),
+    }
+}
+
+/// Where a redirect sends the client: its target, a prefix's remainder, then
+/// `query` unless the target brings its own.
+fn redirect_target(rule: &RedirectRule, query: &str) -> String {
+    let remainder = if rule.path.ends_with('*') { "$1" } else { "" };
+    let query = if rule.target.contains('?') { "" } else { query };
+    format!("{}{}{}", rule.target, remainder, query)
+}
+
+/// The vhost's `limit_req` / `limit_conn` settings: its rate limit, with the
+/// default per-client connection cap wherever that sets none.
+fn ingress_limit(options: &VhostOptions) -> RateLimit {
+    let mut limit = options.rate_limit.unwrap_or_default();
+    if limit.max_connections == 0 {
+        limit.max_connections = options.client_limits.max_connections;
+    }
+    limit
+}
+
 // ==============================================================================
 // 1. Apache Implementation
 // ==============================================================================
 pub struct ApacheManager {
     base_path: PathBuf,
+    executor: Arc<dyn Executor>,
 }
 
 impl ApacheManager {
     pub fn new(base_path: PathBuf) -> Self {
-        Self { base_path }
+        Self::with_executor(base_path, Arc::new(SystemExecutor))
+    }
+
+    pub fn with_executor(base_path: PathBuf, executor: Arc<dyn Executor>) -> Self {
+        Self {
+            base_path,
+            executor,
+        }
     }
 
     async fn test_and_reload(&self) -> Result<(), String> {
-        let check = Command::new("apache2ctl")
-            .arg("configtest")
-            .output()
+        let check = self
+            .executor
+            .run(&CommandSpec::privileged("apache2ctl").arg("configtest"))
             .await
             .map_err(|e| format!("Apache check failed: {}", e))?;
 
-        if !check.status.success() {
+        if !check.success() {
             return Err(format!(
                 "Apache config error: {}",
                 String::from_utf8_lossy(&check.stderr)
             ));
         }
 
-        Command::new("systemctl")
-            .args(["reload", "apache2"])
-            .output()
+        self.executor
+            .run(&CommandSpec::privileged("systemctl").args(["reload", "apache2"]))
             .await
             .map_err(|e| format!("Systemd reload failed: {}", e))?;
         Ok(())
     }
 }
 
+/// 🔏 ACME HTTP-01 responses, one directory per domain. Port 80 serves them
+/// ahead of the app (Apache vhosts in maintenance answer 503 instead).
+const ACME_CHALLENGE_ROOT: &str = "/var/lib/kari/acme-challenge";
+
+/// 🛡️ Zero-Trust: Tokens become file names; RFC 8555 makes them base64url.
+fn validate_challenge_token(token: &str) -> Result<(), String> {
+    if token.is_empty()
+        || token.len() > 128
+        || !token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!("Zero-Trust: Invalid challenge token: {:?}", token));
+    }
+    Ok(())
+}
+
+async fn write_challenge(domain: &str, token: &str, key_authorization: &str) -> Result<(), String> {
+    validate_domain_format(domain)?;
+    validate_challenge_token(token)?;
+    let dir = Path::new(ACME_CHALLENGE_ROOT).join(domain);
+    fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+    fs::write(dir.join(token), key_authorization)
+        .await
+        .map_err(|e| format!("Failed to write challenge response: {}", e))
+}
+
+async fn remove_challenge(domain: &str, token: &str) -> Result<(), String> {
+    validate_domain_format(domain)?;
+    validate_challenge_token(token)?;
+    match fs::remove_file(Path::new(ACME_CHALLENGE_ROOT).join(domain).join(token)).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(format!("Failed to remove challenge response: {}", e))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Body of the 503 served while a vhost is in maintenance. Free of quotes so it
+/// can sit inside either server's quoted directive as is.
+/// 🩺 Apache keeps stapled OCSP responses in one server-wide cache.
+const APACHE_STAPLING_CACHE: &str = "shmcb:/run/kari-ocsp-stapling(128000)";
+
+const MAINTENANCE_PAGE: &str = "<!doctype html><title>Temporarily unavailable</title><h1>Temporarily unavailable</h1><p>This site is down for maintenance. Please try again shortly.</p>";
+
+fn render_apache_vhost(domain: &str, target_port: u16, options: &VhostOptions) -> String {
+    // mod_remoteip must be enabled; configtest rejects the directives otherwise
+    let real_ip = match &options.real_ip {
+        Some(real_ip) => format!(
+            "    RemoteIPHeader {}\n    RemoteIPTrustedProxy {}\n",
+            real_ip.header,
+            real_ip.trusted_proxies.join(" ")
+        ),
+        None => String::new(),
+    };
+
+    // mod_reqtimeout must be enabled too. Stock Apache has no per-client
+    // connection cap, so max_connections is nginx only.
+    let client = render_apache_client_limits(&options.client_limits);
+
+    let routing = if options.maintenance {
+        format!(
+            "    Redirect 503 /\n    ErrorDocument 503 \"{}\"\n    Header always set Retry-After \"120\"\n",
+            MAINTENANCE_PAGE
+        )
+    } else {
+        format!(
+            "{tracing}    ProxyPreserveHost On\n    ProxyPass / http://127.0.0.1:{port}/\n    ProxyPassReverse / http://127.0.0.1:{port}/\n",
+            tracing = if options.request_tracing {
+                APACHE_REQUEST_TRACING
+            } else {
+                ""
+            },
+            port = target_port
+        )
+    };
+
+    // mod_alias runs Redirect before Alias, so maintenance still wins
+    let challenge = format!(
+        "    Alias /.well-known/acme-challenge/ {root}/{domain}/\n    <Directory {root}/{domain}/>\n        Require all granted\n    </Directory>\n{exclude}",
+        root = ACME_CHALLENGE_ROOT,
+        domain = domain,
+        exclude = if options.maintenance {
+            ""
+        } else {
+            "    ProxyPass /.well-known/acme-challenge/ !\n"
+        }
+    );
+
+    // Ahead of the routing: mod_alias takes the first match, and mod_proxy
+    // would otherwise claim the paths before mod_alias sees them
+    let mut redirects = String::new();
+    for rule in redirect_order(&options.redirects) {
+        let regex = redirect_regex(rule);
+        if !options.maintenance {
+            redirects.push_str(&format!("    ProxyPassMatch {} !\n", regex));
+        }
+        redirects.push_str(&format!(
+            "    RedirectMatch {} {} {}\n",
+            if rule.permanent { 301 } else { 302 },
+            regex,
+            redirect_target(rule, "")
+        ));
+    }
+
+    let http = format!(
+        r#"<VirtualHost *:80>
+    ServerName {domain}
+{client}{real_ip}{challenge}{redirects}{routing}    Header always set X-Content-Type-Options "nosniff"
+</VirtualHost>"#,
+        domain = domain,
+        client = client,
+        real_ip = real_ip,
+        challenge = challenge,
+        redirects = redirects,
+        routing = routing
+    );
+    match &options.tls {
+        // mod_ssl must be enabled, as mod_remoteip above
+        Some(tls) => format!(
+            r#"{http}
+
+{stapling_cache}<VirtualHost *:443>
+    ServerName {domain}
+{ssl}{client}{real_ip}{redirects}{routing}    Header always set X-Content-Type-Options "nosniff"
+</VirtualHost>"#,
+            http = http,
+            stapling_cache = if tls.ocsp_stapling.is_some() {
+                // Server-wide; every kari vhost names the same one
+                format!("SSLStaplingCache {}\n", APACHE_STAPLING_CACHE)
+            } else {
+                String::new()
+            },
+            domain = domain,
+            ssl = render_apache_tls(tls),
+            client = client,
+            real_ip = real_ip,
+            redirects = redirects,
+            routing = routing
+        ),
+        None => http,
+    }
+}
+
+/// 🔎 The traceparent's trace id when there is a valid one, else mod_unique_id's
+/// id (mod_unique_id and mod_headers must be enabled). traceparent itself
+/// reaches the app untouched.
+const APACHE_REQUEST_TRACING: &str = r#"    SetEnvIf traceparent "^[0-9a-f]{2}-([0-9a-f]{32})-[0-9a-f]{16}-[0-9a-f]{2}$" KARI_TRACE_ID=$1
+    RequestHeader set X-Request-ID "%{KARI_TRACE_ID}e" env=KARI_TRACE_ID
+    RequestHeader set X-Request-ID "%{UNIQUE_ID}e" env=!KARI_TRACE_ID
+    Header always set X-Request-ID "%{KARI_TRACE_ID}e" env=KARI_TRACE_ID
+    Header always set X-Request-ID "%{UNIQUE_ID}e" env=!KARI_TRACE_ID
+"#;
+
+/// Headers and bodies that trickle in below 500 bytes/s past their timeout
+/// get a 408, so a slow client cannot hold a worker indefinitely.
+fn render_apache_client_limits(limits: &ClientLimits) -> String {
+    format!(
+        "    RequestReadTimeout header={} body={},MinRate=500\n    Timeout {}\n    KeepAliveTimeout {}\n    LimitRequestBody {}\n",
+        limits.header_timeout_secs,
+        limits.body_timeout_secs,
+        limits.send_timeout_secs,
+        limits.keepalive_timeout_secs,
+        u64::from(limits.max_body_mb) * 1024 * 1024
+    )
+}
+
+fn render_apache_tls(tls: &VhostTls) -> String {
+    let preset = preset(tls.policy);
+    let mut ssl = format!(
+        "    SSLEngine on\n    SSLCertificateFile {}\n    SSLCertificateKeyFile {}\n    SSLProtocol -all {}\n",
+        tls.cert_path.display(),
+        tls.key_path.display(),
+        preset
+            .protocols
+            .iter()
+            .map(|p| format!("+{}", p))
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    if !preset.ciphers.is_empty() {
+        ssl.push_str(&format!("    SSLCipherSuite {}\n", preset.ciphers));
+    }
+    ssl.push_str(&format!(
+        "    SSLOpenSSLConfCmd Curves {}\n    SSLHonorCipherOrder {}\n    SSLSessionTickets off\n",
+        preset.curves,
+        if preset.prefer_server_ciphers {
+            "on"
+        } else {
+            "off"
+        }
+    ));
+    if tls.ocsp_stapling.is_some() {
+        // A responder outage must not become a handshake failure
+        ssl.push_str("    SSLUseStapling on\n    SSLStaplingReturnResponderErrors off\n");
+    }
+    ssl
+}
+
+/// 🛡️ Zero-Trust: Certificate paths and resolvers are written into the config verbatim.
+fn validate_tls(tls: &VhostTls) -> Result<(), String> {
+    for path in [&tls.cert_path, &tls.key_path] {
+        let printable = path.to_str().is_some_and(|p| {
+            p.starts_with('/')
+                && p.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '.' | '-' | '_'))
+        });
+        if !printable {
+            return Err(format!("Zero-Trust: Invalid certificate path: {:?}", path));
+        }
+    }
+    for resolver in tls.ocsp_stapling.iter().flat_map(|s| &s.resolvers) {
+        let ip = resolver.trim_start_matches('[').trim_end_matches(']');
+        if ip.parse::<std::net::IpAddr>().is_err() {
+            return Err(format!("Zero-Trust: Invalid OCSP resolver: {:?}", resolver));
+        }
+    }
+    Ok(())
+}
+
 #[async_trait]
 impl ProxyManager for ApacheManager {
-    async fn create_vhost(&self, domain: &str, target_port: u16) -> Result<(), String> {
+    async fn create_vhost(
+        &self,
+        domain: &str,
+        target_port: u16,
+        options: &VhostOptions,
+    ) -> Result<(), String> {
         validate_domain_format(domain)?;
+        // Apache has no per-client request-rate equivalent (mod_ratelimit caps bandwidth)
+        if options.rate_limit.is_some() {
+            return Err(format!(
+                "Rate limiting for {} requires the nginx proxy",
+                domain
+            ));
+        }
 
         let config_path = self
             .base_path
@@ -94,25 +476,20 @@ impl ProxyManager for ApacheManager {
             .join("sites-enabled")
             .join(format!("{}.conf", domain));
 
-        let content = format!(
-            r#"<VirtualHost *:80>
-    ServerName {domain}
-    ProxyPreserveHost On
-    ProxyPass / http://127.0.0.1:{target_port}/
-    ProxyPassReverse / http://127.0.0.1:{target_port}/
-    Header always set X-Content-Type-Options "nosniff"
-</VirtualHost>"#,
-            domain = domain,
-            target_port = target_port
-        );
+        if let Some(real_ip) = &options.real_ip {
+            validate_real_ip(real_ip)?;
+        }
+        if let Some(tls) = &options.tls {
+            validate_tls(tls)?;
+        }
+        validate_redirects(&options.redirects)?;
+        let content = render_apache_vhost(domain, target_port, options);
 
-        fs::write(&config_path, content)
-            .await
-            .map_err(|e| e.to_string())?;
-        if !enabled_link.exists() {
-            fs::symlink(&config_path, &enabled_link)
-                .await
-                .map_err(|e| e.to_string())?;
+        self.executor
+            .write_file(&config_path, &content, 0o644)
+            .await?;
+        if !self.executor.exists(&enabled_link).await? {
+            self.executor.symlink(&config_path, &enabled_link).await?;
         }
         self.test_and_reload().await
     }
@@ -128,10 +505,51 @@ impl ProxyManager for ApacheManager {
             .base_path
             .join("sites-enabled")
             .join(format!("{}.conf", domain));
-        let _ = fs::remove_file(enabled_link).await;
-        let _ = fs::remove_file(config_path).await;
+        let _ = self.executor.remove_file(&enabled_link).await;
+        let _ = self.executor.remove_file(&config_path).await;
         self.test_and_reload().await
     }
+
+    async fn vhost_exists(&self, domain: &str) -> Result<bool, String> {
+        validate_domain_format(domain)?;
+
+        let enabled_link = self
+            .base_path
+            .join("sites-enabled")
+            .join(format!("{}.conf", domain));
+        // exists() follows the link, so a dangling one counts as missing
+        self.executor.exists(&enabled_link).await
+    }
+
+    async fn vhost_is_current(
+        &self,
+        domain: &str,
+        target_port: u16,
+        options: &VhostOptions,
+    ) -> Result<bool, String> {
+        if !self.vhost_exists(domain).await? {
+            return Ok(false);
+        }
+        let config_path = self
+            .base_path
+            .join("sites-available")
+            .join(format!("{}.conf", domain));
+        Ok(self.executor.read_file(&config_path).await.ok().flatten()
+            == Some(render_apache_vhost(domain, target_port, options)))
+    }
+
+    async fn publish_challenge(
+        &self,
+        domain: &str,
+        token: &str,
+        key_authorization: &str,
+    ) -> Result<(), String> {
+        write_challenge(domain, token, key_authorization).await
+    }
+
+    async fn withdraw_challenge(&self, domain: &str, token: &str) -> Result<(), String> {
+        remove_challenge(domain, token).await
+    }
 }
 
 // ==============================================================================
@@ -139,67 +557,313 @@ impl ProxyManager for ApacheManager {
 // ==============================================================================
 pub struct NginxManager {
     base_path: PathBuf,
+    executor: Arc<dyn Executor>,
 }
 
 impl NginxManager {
     pub fn new(base_path: PathBuf) -> Self {
-        Self { base_path }
+        Self::with_executor(base_path, Arc::new(SystemExecutor))
+    }
+
+    pub fn with_executor(base_path: PathBuf, executor: Arc<dyn Executor>) -> Self {
+        Self {
+            base_path,
+            executor,
+        }
     }
 
     async fn test_and_reload(&self) -> Result<(), String> {
-        let check = Command::new("nginx")
-            .arg("-t")
-            .output()
+        let check = self
+            .executor
+            .run(&CommandSpec::privileged("nginx").arg("-t"))
             .await
             .map_err(|e| format!("Nginx check failed: {}", e))?;
 
-        if !check.status.success() {
+        if !check.success() {
             return Err(format!(
                 "Nginx config error: {}",
                 String::from_utf8_lossy(&check.stderr)
             ));
         }
 
-        Command::new("systemctl")
-            .args(["reload", "nginx"])
-            .output()
+        self.executor
+            .run(&CommandSpec::privileged("systemctl").args(["reload", "nginx"]))
             .await
             .map_err(|e| format!("Systemd reload failed: {}", e))?;
         Ok(())
     }
+
+    fn limit_zone_path(&self, domain: &str) -> PathBuf {
+        self.base_path
+            .join("kari-limits.d")
+            .join(format!("{}.conf", domain))
+    }
+
+    /// Writes (or clears) the domain's zones and makes sure the kari include,
+    /// which pulls every zone file into the http context, is in place.
+    async fn write_limit_zones(&self, domain: &str, options: &VhostOptions) -> Result<(), String> {
+        let zone_path = self.limit_zone_path(domain);
+        let limit = ingress_limit(options);
+        if limit == RateLimit::default() {
+            let _ = self.executor.remove_file(&zone_path).await;
+            return Ok(());
+        }
+
+        let zone_dir = self.base_path.join("kari-limits.d");
+        let include_path = self.base_path.join("conf.d").join("kari-limits.conf");
+        self.executor
+            .create_dir_all(&zone_dir)
+            .await
+            .map_err(|e| format!("Failed to create {:?}: {}", zone_dir, e))?;
+        if let Some(parent) = include_path.parent() {
+            self.executor
+                .create_dir_all(parent)
+                .await
+                .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        self.executor
+            .write_file(&include_path, &render_nginx_limit_include(&zone_dir), 0o644)
+            .await?;
+        self.executor
+            .write_file(&zone_path, &render_nginx_limit_zones(domain, &limit), 0o644)
+            .await
+    }
+
+    /// Puts the `$kari_request_id` map in place. It stays once written: other
+    /// vhosts may use it, and an unused map costs nothing.
+    async fn write_request_id_map(&self) -> Result<(), String> {
+        let conf_dir = self.base_path.join("conf.d");
+        let map_path = conf_dir.join("kari-request-id.conf");
+        if self
+            .executor
+            .read_file(&map_path)
+            .await
+            .ok()
+            .flatten()
+            .as_deref()
+            == Some(NGINX_REQUEST_ID_MAP)
+        {
+            return Ok(());
+        }
+        self.executor
+            .create_dir_all(&conf_dir)
+            .await
+            .map_err(|e| format!("Failed to create {:?}: {}", conf_dir, e))?;
+        self.executor
+            .write_file(&map_path, NGINX_REQUEST_ID_MAP, 0o644)
+            .await
+    }
 }
 
-#[async_trait]
-impl ProxyManager for NginxManager {
-    async fn create_vhost(&self, domain: &str, target_port: u16) -> Result<(), String> {
-        validate_domain_format(domain)?;
+/// Shared-memory zones for one vhost's limits; they must live in the http
+/// context, so they go in the kari include rather than the server block.
+fn render_nginx_limit_zones(domain: &str, limit: &RateLimit) -> String {
+    let mut zones = String::new();
+    if limit.requests_per_second > 0 {
+        zones.push_str(&format!(
+            "limit_req_zone $binary_remote_addr zone=kari_req_{}:10m rate={}r/s;\n",
+            domain, limit.requests_per_second
+        ));
+    }
+    if limit.max_connections > 0 {
+        zones.push_str(&format!(
+            "limit_conn_zone $binary_remote_addr zone=kari_conn_{}:10m;\n",
+            domain
+        ));
+    }
+    zones
+}
 
-        let config_path = self.base_path.join("sites-available").join(domain);
-        let enabled_link = self.base_path.join("sites-enabled").join(domain);
+fn render_nginx_vhost(domain: &str, target_port: u16, options: &VhostOptions) -> String {
+    // realip runs before limit_req, so limits key on the real client address too
+    let mut real_ip = String::new();
+    if let Some(ip) = &options.real_ip {
+        for cidr in &ip.trusted_proxies {
+            real_ip.push_str(&format!("    set_real_ip_from {};\n", cidr));
+        }
+        real_ip.push_str(&format!(
+            "    real_ip_header {};\n    real_ip_recursive on;\n",
+            ip.header
+        ));
+    }
+
+    let client = &options.client_limits;
+    let mut limits = format!(
+        "    client_header_timeout {}s;\n    client_body_timeout {}s;\n    send_timeout {}s;\n    keepalive_timeout {}s;\n    client_max_body_size {}m;\n",
+        client.header_timeout_secs,
+        client.body_timeout_secs,
+        client.send_timeout_secs,
+        client.keepalive_timeout_secs,
+        client.max_body_mb
+    );
+    let limit = ingress_limit(options);
+    if limit.requests_per_second > 0 {
+        limits.push_str(&format!(
+            "    limit_req zone=kari_req_{} burst={} nodelay;\n    limit_req_status 429;\n",
+            domain, limit.burst
+        ));
+    }
+    if limit.max_connections > 0 {
+        limits.push_str(&format!(
+            "    limit_conn kari_conn_{} {};\n    limit_conn_status 429;\n",
+            domain, limit.max_connections
+        ));
+    }
+
+    let location = if options.maintenance {
+        format!(
+            "        default_type text/html;\n        add_header Retry-After 120 always;\n        add_header X-Content-Type-Options \"nosniff\" always;\n        return 503 '{}';\n",
+            MAINTENANCE_PAGE
+        )
+    } else {
+        let tracing = if options.request_tracing {
+            "        proxy_set_header X-Request-ID $kari_request_id;\n        add_header X-Request-ID $kari_request_id always;\n"
+        } else {
+            ""
+        };
+        format!(
+            "        proxy_pass http://127.0.0.1:{};\n        proxy_set_header Host $host;\n        proxy_set_header X-Real-IP $remote_addr;\n{}        add_header X-Content-Type-Options \"nosniff\" always;\n",
+            target_port, tracing
+        )
+    };
 
-        let content = format!(
+    // ^~ keeps the challenge ahead of `location /`, maintenance included
+    let challenge = format!(
+        "\n    location ^~ /.well-known/acme-challenge/ {{\n        alias {}/{}/;\n        default_type text/plain;\n    }}\n",
+        ACME_CHALLENGE_ROOT, domain
+    );
+
+    // Exact and regex locations both beat `location /`; regexes match in order
+    let mut redirects = String::new();
+    for rule in redirect_order(&options.redirects) {
+        let location = if rule.path.ends_with('*') {
+            format!("~ {}", redirect_regex(rule))
+        } else {
+            format!("= {}", rule.path)
+        };
+        redirects.push_str(&format!(
+            "\n    location {} {{\n        return {} {};\n    }}\n",
+            location,
+            if rule.permanent { 301 } else { 302 },
+            redirect_target(rule, "$is_args$args")
+        ));
+    }
+
+    let server = |listen: &str, ssl: &str, challenge: &str| {
+        format!(
             r#"server {{
-    listen 80;
+    listen {listen};
     server_name {domain};
-
+{ssl}{real_ip}{limits}{challenge}{redirects}
     location / {{
-        proxy_pass http://127.0.0.1:{target_port};
-        proxy_set_header Host $host;
-        proxy_set_header X-Real-IP $remote_addr;
-        add_header X-Content-Type-Options "nosniff" always;
-    }}
+{location}    }}
 }}"#,
+            listen = listen,
             domain = domain,
-            target_port = target_port
-        );
+            ssl = ssl,
+            real_ip = real_ip,
+            limits = limits,
+            challenge = challenge,
+            redirects = redirects,
+            location = location
+        )
+    };
+    match &options.tls {
+        Some(tls) => format!(
+            "{}\n\n{}",
+            server("80", "", &challenge),
+            server("443 ssl", &render_nginx_tls(tls), "")
+        ),
+        None => server("80", "", &challenge),
+    }
+}
 
-        fs::write(&config_path, content)
-            .await
-            .map_err(|e| e.to_string())?;
-        if !enabled_link.exists() {
-            fs::symlink(&config_path, &enabled_link)
-                .await
-                .map_err(|e| e.to_string())?;
+fn render_nginx_tls(tls: &VhostTls) -> String {
+    let preset = preset(tls.policy);
+    let mut ssl = format!(
+        "    ssl_certificate {};\n    ssl_certificate_key {};\n    ssl_protocols {};\n",
+        tls.cert_path.display(),
+        tls.key_path.display(),
+        preset.protocols.join(" ")
+    );
+    if !preset.ciphers.is_empty() {
+        ssl.push_str(&format!("    ssl_ciphers {};\n", preset.ciphers));
+    }
+    ssl.push_str(&format!(
+        "    ssl_ecdh_curve {};\n    ssl_prefer_server_ciphers {};\n    ssl_session_tickets off;\n",
+        preset.curves,
+        if preset.prefer_server_ciphers {
+            "on"
+        } else {
+            "off"
+        }
+    ));
+    if let Some(stapling) = &tls.ocsp_stapling {
+        ssl.push_str(&format!(
+            "    ssl_stapling on;\n    ssl_stapling_verify on;\n    ssl_trusted_certificate {};\n",
+            tls.cert_path.display()
+        ));
+        if !stapling.resolvers.is_empty() {
+            ssl.push_str(&format!(
+                "    resolver {} valid=300s;\n    resolver_timeout 5s;\n",
+                stapling.resolvers.join(" ")
+            ));
+        }
+    }
+    ssl
+}
+
+/// 🔎 `$kari_request_id`: the traceparent's trace id when there is a valid
+/// one, else nginx's own random id. Maps live in the http context, so this is
+/// a kari include of its own, shared by every tracing vhost; traceparent
+/// itself reaches the app untouched.
+const NGINX_REQUEST_ID_MAP: &str = r#"# Managed by kari: request ids for vhosts with request tracing
+map $http_traceparent $kari_request_id {
+    "~^[0-9a-f]{2}-(?<kari_trace_id>[0-9a-f]{32})-[0-9a-f]{16}-[0-9a-f]{2}$" $kari_trace_id;
+    default $request_id;
+}
+"#;
+
+fn render_nginx_limit_include(zone_dir: &Path) -> String {
+    format!(
+        "# Managed by kari: per-vhost rate-limit zones\ninclude {}/*.conf;\n",
+        zone_dir.display()
+    )
+}
+
+#[async_trait]
+impl ProxyManager for NginxManager {
+    async fn create_vhost(
+        &self,
+        domain: &str,
+        target_port: u16,
+        options: &VhostOptions,
+    ) -> Result<(), String> {
+        validate_domain_format(domain)?;
+        if let Some(real_ip) = &options.real_ip {
+            validate_real_ip(real_ip)?;
+        }
+        if let Some(tls) = &options.tls {
+            validate_tls(tls)?;
+        }
+        validate_redirects(&options.redirects)?;
+
+        let config_path = self.base_path.join("sites-available").join(domain);
+        let enabled_link = self.base_path.join("sites-enabled").join(domain);
+
+        self.write_limit_zones(domain, options).await?;
+        if options.request_tracing {
+            self.write_request_id_map().await?;
+        }
+        self.executor
+            .write_file(
+                &config_path,
+                &render_nginx_vhost(domain, target_port, options),
+                0o644,
+            )
+            .await?;
+        if !self.executor.exists(&enabled_link).await? {
+            self.executor.symlink(&config_path, &enabled_link).await?;
         }
         self.test_and_reload().await
     }
@@ -209,15 +873,73 @@ impl ProxyManager for NginxManager {
 
         let config_path = self.base_path.join("sites-available").join(domain);
         let enabled_link = self.base_path.join("sites-enabled").join(domain);
-        let _ = fs::remove_file(enabled_link).await;
-        let _ = fs::remove_file(config_path).await;
+        let _ = self.executor.remove_file(&enabled_link).await;
+        let _ = self.executor.remove_file(&config_path).await;
+        let _ = self
+            .executor
+            .remove_file(&self.limit_zone_path(domain))
+            .await;
         self.test_and_reload().await
     }
+
+    async fn vhost_exists(&self, domain: &str) -> Result<bool, String> {
+        validate_domain_format(domain)?;
+
+        // exists() follows the link, so a dangling one counts as missing
+        self.executor
+            .exists(&self.base_path.join("sites-enabled").join(domain))
+            .await
+    }
+
+    async fn vhost_is_current(
+        &self,
+        domain: &str,
+        target_port: u16,
+        options: &VhostOptions,
+    ) -> Result<bool, String> {
+        if !self.vhost_exists(domain).await? {
+            return Ok(false);
+        }
+        let config_path = self.base_path.join("sites-available").join(domain);
+        Ok(self.executor.read_file(&config_path).await.ok().flatten()
+            == Some(render_nginx_vhost(domain, target_port, options)))
+    }
+
+    async fn publish_challenge(
+        &self,
+        domain: &str,
+        token: &str,
+        key_authorization: &str,
+    ) -> Result<(), String> {
+        write_challenge(domain, token, key_authorization).await
+    }
+
+    async fn withdraw_challenge(&self, domain: &str, token: &str) -> Result<(), String> {
+        remove_challenge(domain, token).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testkit::FakeExecutor;
+
+    #[tokio::test]
+    async fn a_config_that_fails_its_test_is_never_reloaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let executor = Arc::new(FakeExecutor::default());
+        let nginx = NginxManager::with_executor(dir.path().to_path_buf(), executor.clone());
+
+        executor.answer(&["nginx", "-t"], 1, "", "unknown directive \"limit_reqq\"");
+        let err = nginx.remove_vhost("shop.example.com").await.unwrap_err();
+        assert_eq!(err, "Nginx config error: unknown directive \"limit_reqq\"");
+        assert_eq!(executor.argvs(), [["nginx", "-t"]]);
+
+        executor.answer(&["nginx", "-t"], 0, "", "");
+        nginx.remove_vhost("shop.example.com").await.unwrap();
+        assert_eq!(executor.argvs()[2], ["systemctl", "reload", "nginx"]);
+        assert!(executor.runs().iter().all(|run| run.privileged));
+    }
 
     #[test]
     fn test_validate_domain_format_valid() {
@@ -252,4 +974,311 @@ mod tests {
         // Empty
         assert!(validate_domain_format("").is_err());
     }
+
+    #[test]
+    fn nginx_rate_limits_render_zones_and_directives() {
+        let limit = RateLimit {
+            requests_per_second: 20,
+            burst: 40,
+            max_connections: 0,
+        };
+        let options = VhostOptions {
+            rate_limit: Some(limit),
+            ..Default::default()
+        };
+
+        let zones = render_nginx_limit_zones("shop.example.com", &limit);
+        assert_eq!(
+            zones,
+            "limit_req_zone $binary_remote_addr zone=kari_req_shop.example.com:10m rate=20r/s;\n"
+        );
+
+        let vhost = render_nginx_vhost("shop.example.com", 3000, &options);
+        assert!(vhost.contains("limit_req zone=kari_req_shop.example.com burst=40 nodelay;"));
+        // No cap of its own, so the default per-client cap applies
+        assert!(vhost.contains("limit_conn kari_conn_shop.example.com 64;"));
+
+        let plain = render_nginx_vhost("shop.example.com", 3000, &VhostOptions::default());
+        assert!(!plain.contains("limit_req"));
+    }
+
+    #[test]
+    fn every_vhost_gets_slow_client_limits() {
+        let plain = VhostOptions::default();
+        let nginx = render_nginx_vhost("shop.example.com", 3000, &plain);
+        assert!(nginx.contains("    client_header_timeout 10s;\n"));
+        assert!(nginx.contains("    client_max_body_size 16m;\n"));
+        assert!(nginx.contains("    limit_conn kari_conn_shop.example.com 64;\n"));
+        assert_eq!(
+            render_nginx_limit_zones("shop.example.com", &ingress_limit(&plain)),
+            "limit_conn_zone $binary_remote_addr zone=kari_conn_shop.example.com:10m;\n"
+        );
+
+        // The app's own connection cap wins over the default
+        let capped = VhostOptions {
+            rate_limit: Some(RateLimit {
+                max_connections: 10,
+                ..Default::default()
+            }),
+            client_limits: ClientLimits {
+                header_timeout_secs: 5,
+                max_body_mb: 512,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let nginx = render_nginx_vhost("shop.example.com", 3000, &capped);
+        assert!(nginx.contains("    client_header_timeout 5s;\n"));
+        assert!(nginx.contains("    client_max_body_size 512m;\n"));
+        assert!(nginx.contains("    limit_conn kari_conn_shop.example.com 10;\n"));
+
+        let apache = render_apache_vhost("shop.example.com", 3000, &capped);
+        assert!(apache.contains("    RequestReadTimeout header=5 body=15,MinRate=500\n"));
+        assert!(apache.contains("    LimitRequestBody 536870912\n"));
+        assert!(apache.find("RequestReadTimeout").unwrap() < apache.find("ProxyPass / ").unwrap());
+    }
+
+    #[test]
+    fn traced_vhosts_send_a_request_id_both_ways() {
+        let traced = VhostOptions {
+            request_tracing: true,
+            ..Default::default()
+        };
+        let nginx = render_nginx_vhost("shop.example.com", 3000, &traced);
+        assert!(nginx.contains("        proxy_set_header X-Request-ID $kari_request_id;\n"));
+        assert!(nginx.contains("        add_header X-Request-ID $kari_request_id always;\n"));
+        assert!(NGINX_REQUEST_ID_MAP.contains("map $http_traceparent $kari_request_id {"));
+        let apache = render_apache_vhost("shop.example.com", 3000, &traced);
+        assert!(
+            apache.contains("RequestHeader set X-Request-ID \"%{UNIQUE_ID}e\" env=!KARI_TRACE_ID")
+        );
+        assert!(
+            apache.find("SetEnvIf traceparent").unwrap() < apache.find("ProxyPass / ").unwrap()
+        );
+
+        // Off by default, and a maintenance page has nothing to correlate
+        let plain = render_nginx_vhost("shop.example.com", 3000, &VhostOptions::default());
+        assert!(!plain.contains("X-Request-ID"));
+        let parked = VhostOptions {
+            maintenance: true,
+            ..traced
+        };
+        assert!(!render_nginx_vhost("shop.example.com", 3000, &parked).contains("X-Request-ID"));
+        assert!(!render_apache_vhost("shop.example.com", 3000, &parked).contains("X-Request-ID"));
+    }
+
+    #[test]
+    fn maintenance_vhosts_answer_503_without_proxying() {
+        let options = VhostOptions {
+            maintenance: true,
+            ..Default::default()
+        };
+        let nginx = render_nginx_vhost("shop.example.com", 3000, &options);
+        assert!(nginx.contains("return 503 '<!doctype html>"));
+        assert!(!nginx.contains("proxy_pass"));
+        let apache = render_apache_vhost("shop.example.com", 3000, &options);
+        assert!(apache.contains("Redirect 503 /"));
+        assert!(!apache.contains("ProxyPass"));
+
+        let live = render_nginx_vhost("shop.example.com", 3000, &VhostOptions::default());
+        assert!(live.contains("proxy_pass http://127.0.0.1:3000;"));
+        assert!(!MAINTENANCE_PAGE.contains(['"', '\'']));
+    }
+
+    #[test]
+    fn acme_challenges_are_served_over_plain_http_only() {
+        let tls = VhostOptions {
+            tls: Some(VhostTls {
+                cert_path: "/etc/kari/ssl/shop.example.com/fullchain.pem".into(),
+                key_path: "/etc/kari/ssl/shop.example.com/privkey.pem".into(),
+                policy: Default::default(),
+                ocsp_stapling: None,
+            }),
+            ..Default::default()
+        };
+        let nginx = render_nginx_vhost("shop.example.com", 3000, &tls);
+        assert_eq!(
+            nginx
+                .matches("alias /var/lib/kari/acme-challenge/shop.example.com/;")
+                .count(),
+            1
+        );
+        assert!(nginx.find("acme-challenge").unwrap() < nginx.find("listen 443").unwrap());
+
+        let apache = render_apache_vhost("shop.example.com", 3000, &tls);
+        let alias = apache
+            .find(
+                "Alias /.well-known/acme-challenge/ /var/lib/kari/acme-challenge/shop.example.com/",
+            )
+            .unwrap();
+        let exclude = apache
+            .find("ProxyPass /.well-known/acme-challenge/ !")
+            .unwrap();
+        assert!(alias < exclude && exclude < apache.find("ProxyPass / ").unwrap());
+        assert_eq!(apache.matches("ProxyPass /.well-known").count(), 1);
+
+        assert!(validate_challenge_token("LoqXcYV8q5ONbJQxbmR7SCTNo3tiAXDfowyjxAjEuX0").is_ok());
+        assert!(validate_challenge_token("../../etc/passwd").is_err());
+        assert!(validate_challenge_token("").is_err());
+    }
+
+    #[test]
+    fn redirects_are_answered_by_the_proxy_most_specific_first() {
+        let rule = |path: &str, target: &str, permanent: bool| RedirectRule {
+            path: path.into(),
+            target: target.into(),
+            permanent,
+        };
+        let options = VhostOptions {
+            redirects: vec![
+                rule("/*", "https://www.example.com/", true),
+                rule("/blog/*", "https://blog.example.com/", true),
+                rule("/promo", "/sale?utm_source=promo", false),
+            ],
+            ..Default::default()
+        };
+        validate_redirects(&options.redirects).unwrap();
+
+        let nginx = render_nginx_vhost("shop.example.com", 3000, &options);
+        assert!(
+            nginx
+                .contains("location = /promo {\n        return 302 /sale?utm_source=promo;\n    }")
+        );
+        let blog = nginx
+            .find("location ~ ^/blog/(.*)$ {\n        return 301 https://blog.example.com/$1$is_args$args;")
+            .unwrap();
+        // The catch-all spares the ACME challenges
+        let all = nginx
+            .find("location ~ ^/(?!\\.well-known/acme-challenge/)(.*)$ {")
+            .unwrap();
+        assert!(nginx.find("location = /promo").unwrap() < blog && blog < all);
+
+        let apache = render_apache_vhost("shop.example.com", 3000, &options);
+        assert!(apache.contains("ProxyPassMatch ^/blog/(.*)$ !"));
+        let redirect = apache
+            .find("RedirectMatch 301 ^/blog/(.*)$ https://blog.example.com/$1\n")
+            .unwrap();
+        assert!(redirect < apache.find("ProxyPass / ").unwrap());
+        assert!(apache.contains("RedirectMatch 302 ^/promo$ /sale?utm_source=promo\n"));
+
+        for bad in [
+            rule("/a b", "/b", true),
+            rule("/old", "/new;return 200", true),
+            rule("/old", "https://evil.example.com\"/", true),
+            rule("/old", "//evil.example.com/", true),
+            rule("old", "/new", true),
+            rule("/blog*", "/new", true),
+            rule("/../etc", "/new", true),
+            rule("/.well-known/acme-challenge/*", "/new", true),
+            rule("/x", "javascript:alert(1)", true),
+        ] {
+            assert!(
+                validate_redirects(std::slice::from_ref(&bad)).is_err(),
+                "{:?}",
+                bad
+            );
+        }
+        let twice = [rule("/a", "/b", true), rule("/a", "/c", false)];
+        assert!(validate_redirects(&twice).is_err());
+    }
+
+    #[test]
+    fn real_ip_trusts_only_listed_proxies() {
+        let options = VhostOptions {
+            real_ip: Some(RealIp {
+                header: "CF-Connecting-IP".into(),
+                trusted_proxies: vec!["173.245.48.0/20".into(), "2400:cb00::/32".into()],
+            }),
+            ..Default::default()
+        };
+        let nginx = render_nginx_vhost("shop.example.com", 3000, &options);
+        assert!(nginx.contains("    set_real_ip_from 173.245.48.0/20;\n"));
+        assert!(nginx.contains("    set_real_ip_from 2400:cb00::/32;\n"));
+        assert!(nginx.contains("    real_ip_header CF-Connecting-IP;\n"));
+
+        let apache = render_apache_vhost("shop.example.com", 3000, &options);
+        assert!(apache.contains("    RemoteIPHeader CF-Connecting-IP\n"));
+        assert!(apache.contains("    RemoteIPTrustedProxy 173.245.48.0/20 2400:cb00::/32\n"));
+
+        let injected = RealIp {
+            header: "X-Real-IP;\n".into(),
+            trusted_proxies: vec!["10.0.0.0/8".into()],
+        };
+        assert!(validate_real_ip(&injected).is_err());
+        let injected = RealIp {
+            header: "X-Forwarded-For".into(),
+            trusted_proxies: vec!["10.0.0.0/8; allow all".into()],
+        };
+        assert!(validate_real_ip(&injected).is_err());
+    }
+
+    #[test]
+    fn tls_vhosts_serve_443_under_the_configured_policy() {
+        use crate::sys::traits::{OcspStapling, TlsPolicy};
+        let tls = |policy| VhostOptions {
+            tls: Some(VhostTls {
+                cert_path: "/etc/kari/ssl/shop.example.com/fullchain.pem".into(),
+                key_path: "/etc/kari/ssl/shop.example.com/privkey.pem".into(),
+                policy,
+                ocsp_stapling: None,
+            }),
+            ..Default::default()
+        };
+
+        let plain = render_nginx_vhost("shop.example.com", 3000, &VhostOptions::default());
+        assert!(!plain.contains("443"));
+        let modern = render_nginx_vhost("shop.example.com", 3000, &tls(TlsPolicy::Modern));
+        assert!(modern.contains("listen 80;"));
+        assert!(modern.contains("listen 443 ssl;"));
+        assert!(modern.contains("ssl_protocols TLSv1.3;\n"));
+        assert!(!modern.contains("ssl_ciphers"));
+        assert_eq!(
+            modern.matches("proxy_pass http://127.0.0.1:3000;").count(),
+            2
+        );
+        let intermediate =
+            render_nginx_vhost("shop.example.com", 3000, &tls(TlsPolicy::Intermediate));
+        assert!(intermediate.contains("ssl_protocols TLSv1.2 TLSv1.3;"));
+        assert!(intermediate.contains("ssl_ciphers ECDHE-ECDSA-AES128-GCM-SHA256:"));
+        assert!(!intermediate.contains("-SHA:"));
+
+        let apache = render_apache_vhost("shop.example.com", 3000, &tls(TlsPolicy::Old));
+        assert!(apache.contains("<VirtualHost *:443>"));
+        assert!(apache.contains("SSLProtocol -all +TLSv1 +TLSv1.1 +TLSv1.2 +TLSv1.3\n"));
+        assert!(apache.contains("SSLHonorCipherOrder on"));
+        assert!(
+            apache.contains("SSLCertificateKeyFile /etc/kari/ssl/shop.example.com/privkey.pem\n")
+        );
+
+        assert!(!modern.contains("ssl_stapling"));
+
+        let mut stapled = tls(TlsPolicy::Modern);
+        if let Some(t) = stapled.tls.as_mut() {
+            t.ocsp_stapling = Some(OcspStapling {
+                resolvers: vec!["127.0.0.53".into(), "[2001:db8::53]".into()],
+            });
+        }
+        let nginx = render_nginx_vhost("shop.example.com", 3000, &stapled);
+        assert!(nginx.contains("ssl_stapling on;\n    ssl_stapling_verify on;\n"));
+        assert!(nginx.contains("resolver 127.0.0.53 [2001:db8::53] valid=300s;"));
+        let apache = render_apache_vhost("shop.example.com", 3000, &stapled);
+        assert!(apache.contains(
+            "SSLStaplingCache shmcb:/run/kari-ocsp-stapling(128000)\n<VirtualHost *:443>"
+        ));
+        assert!(apache.contains("SSLUseStapling on"));
+        assert!(validate_tls(stapled.tls.as_ref().unwrap()).is_ok());
+        if let Some(t) = stapled.tls.as_mut() {
+            t.ocsp_stapling = Some(OcspStapling {
+                resolvers: vec!["1.1.1.1; include /etc/passwd".into()],
+            });
+        }
+        assert!(validate_tls(stapled.tls.as_ref().unwrap()).is_err());
+
+        let mut injected = tls(TlsPolicy::Modern);
+        if let Some(t) = injected.tls.as_mut() {
+            t.cert_path = "/etc/ssl/x.pem;\n}\nserver {".into();
+        }
+        assert!(validate_tls(injected.tls.as_ref().unwrap()).is_err());
+        assert!(validate_tls(tls(TlsPolicy::Modern).tls.as_ref().unwrap()).is_ok());
+    }
 }
@@ -0,0 +1,456 @@
+// agent/src/sys/reboot.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Knows when the host needs a reboot, and takes it down gently.
+//
+// The advisory compares the running kernel with the newest one installed and
+// honours the distro's reboot-required flag. A newer kernel on a stable
+// distro branch is, in practice, a security fix, so it counts as a pending
+// security patch unless a livepatch is covering the running kernel.
+//
+// ScheduleReboot waits for its window, parks every routed app on the
+// maintenance page, stops app units dependents-first, and only then asks
+// systemd to reboot. What it did is recorded under NS_REBOOT before it is done,
+// so the next agent start can put every vhost back and finish the operation.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::path::Path;
+use std::time::Duration;
+use sysinfo::System;
+use tracing::{info, warn};
+
+use crate::sys::privilege;
+use crate::sys::state::{AppRecord, NS_APPS, get_record, list_records, put_record};
+use crate::sys::systemd::ServiceManager;
+use crate::sys::traits::{KernelState, ProxyManager, RebootManager, StateStore};
+
+/// Namespace for the reboot in progress; it only ever holds `PENDING_KEY`.
+pub const NS_REBOOT: &str = "reboot";
+const PENDING_KEY: &str = "pending";
+
+#[derive(Debug, PartialEq, Eq)]
+enum VersionPart<'a> {
+    Number(u64),
+    Text(&'a str),
+}
+
+fn version_parts(version: &str) -> Vec<VersionPart<'_>> {
+    let mut parts = Vec::new();
+    let mut rest = version;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphanumeric()) {
+        rest = &rest[start..];
+        let digits = rest.starts_with(|c: char| c.is_ascii_digit());
+        let end = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() || c.is_ascii_digit() != digits)
+            .unwrap_or(rest.len());
+        parts.push(if digits {
+            VersionPart::Number(rest[..end].parse().unwrap_or(u64::MAX))
+        } else {
+            VersionPart::Text(&rest[..end])
+        });
+        rest = &rest[end..];
+    }
+    parts
+}
+
+/// Orders kernel releases the way rpm and dpkg broadly agree on: numeric runs
+/// compare as numbers, and a number sorts after text in the same position.
+pub fn compare_kernel_versions(a: &str, b: &str) -> Ordering {
+    let (a, b) = (version_parts(a), version_parts(b));
+    for (x, y) in a.iter().zip(&b) {
+        let order = match (x, y) {
+            (VersionPart::Number(x), VersionPart::Number(y)) => x.cmp(y),
+            (VersionPart::Text(x), VersionPart::Text(y)) => x.cmp(y),
+            (VersionPart::Number(_), VersionPart::Text(_)) => Ordering::Greater,
+            (VersionPart::Text(_), VersionPart::Number(_)) => Ordering::Less,
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KernelAdvisory {
+    pub running: String,
+    pub latest_installed: String,
+    pub livepatches: Vec<String>,
+    pub needs_reboot: bool,
+    pub security_patch_pending: bool,
+}
+
+pub fn advise(state: &KernelState) -> KernelAdvisory {
+    let latest = state
+        .installed
+        .iter()
+        .max_by(|a, b| compare_kernel_versions(a, b))
+        .cloned()
+        .unwrap_or_else(|| state.running.clone());
+    let newer_kernel = compare_kernel_versions(&latest, &state.running) == Ordering::Greater;
+    let kernel_package = state
+        .reboot_packages
+        .iter()
+        .any(|pkg| pkg.starts_with("linux-") || pkg.starts_with("kernel"));
+    KernelAdvisory {
+        needs_reboot: newer_kernel || state.reboot_required,
+        security_patch_pending: (newer_kernel || kernel_package) && state.livepatches.is_empty(),
+        running: state.running.clone(),
+        latest_installed: latest,
+        livepatches: state.livepatches.clone(),
+    }
+}
+
+/// A daily window in host local time, e.g. "02:00-04:00"; it may wrap midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RebootWindow {
+    start: u32, // Minutes after midnight
+    end: u32,
+}
+
+impl RebootWindow {
+    pub fn parse(window: &str) -> Result<Self, String> {
+        let minutes = |time: &str| {
+            let (h, m) = time.trim().split_once(':')?;
+            let (h, m): (u32, u32) = (h.parse().ok()?, m.parse().ok()?);
+            (h < 24 && m < 60 && time.trim().len() == 5).then_some(h * 60 + m)
+        };
+        let invalid = || format!("Reboot window must be HH:MM-HH:MM, got '{}'", window);
+        let (start, end) = window.split_once('-').ok_or_else(invalid)?;
+        let (start, end) = (
+            minutes(start).ok_or_else(invalid)?,
+            minutes(end).ok_or_else(invalid)?,
+        );
+        if start == end {
+            return Err(invalid());
+        }
+        Ok(Self { start, end })
+    }
+
+    fn contains(&self, minute: u32) -> bool {
+        if self.start < self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+
+    /// How long the window stays open.
+    pub fn length(&self) -> Duration {
+        Duration::from_secs(((self.end + 1440 - self.start) % 1440) as u64 * 60)
+    }
+
+    /// How long after `seconds_since_midnight` the window opens; zero while it is open.
+    pub fn wait_from(&self, seconds_since_midnight: u32) -> Duration {
+        if self.contains(seconds_since_midnight / 60) {
+            return Duration::ZERO;
+        }
+        let until = (self.start * 60 + 86_400 - seconds_since_midnight) % 86_400;
+        Duration::from_secs(until as u64)
+    }
+}
+
+/// Dependents before what they depend on, so no app loses a backend it is
+/// still using. A dependency cycle falls back to the recorded order.
+pub fn stop_order(mut apps: Vec<AppRecord>) -> Vec<AppRecord> {
+    let mut ordered = Vec::with_capacity(apps.len());
+    while !apps.is_empty() {
+        let next = apps
+            .iter()
+            .position(|app| {
+                let unit = format!("{}.service", app.service_name);
+                !apps
+                    .iter()
+                    .any(|other| other.dependencies.iter().any(|dep| dep.unit == unit))
+            })
+            .unwrap_or(0);
+        ordered.push(apps.remove(next));
+    }
+    ordered
+}
+
+/// The reboot ScheduleReboot is carrying out.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RebootRecord {
+    pub operation_id: String,
+    pub window: String,
+    pub from_kernel: String,
+    pub drained: Vec<String>, // app_ids put on the maintenance page for the reboot
+    pub stopped: Vec<String>, // Units stopped, in order
+    pub rebooting_at: Option<i64>, // Unix seconds; set once systemd was asked
+}
+
+pub async fn pending(state: &dyn StateStore) -> Result<Option<RebootRecord>, String> {
+    get_record(state, NS_REBOOT, PENDING_KEY).await
+}
+
+pub async fn save(state: &dyn StateStore, record: &RebootRecord) -> Result<(), String> {
+    put_record(state, NS_REBOOT, PENDING_KEY, record).await
+}
+
+/// Parks every routed app on the maintenance page, waits `grace` for
+/// in-flight requests, then stops app units in `stop_order`. Each step is
+/// recorded before it is taken, so `restore` undoes at least what was done.
+pub async fn drain(
+    state: &dyn StateStore,
+    services: &dyn ServiceManager,
+    proxy: &dyn ProxyManager,
+    record: &mut RebootRecord,
+    grace: Duration,
+) -> Result<(), String> {
+    let apps: Vec<AppRecord> = list_records(state, NS_APPS).await?;
+    for app in &apps {
+        // An open circuit already serves the maintenance page
+        let Some(port) = app.port.filter(|_| !app.vhost.maintenance) else {
+            continue;
+        };
+        record.drained.push(app.app_id.clone());
+        save(state, record).await?;
+        let mut vhost = app.vhost.clone();
+        vhost.maintenance = true;
+        proxy
+            .create_vhost(&app.domain_name, port, &vhost)
+            .await
+            .map_err(|e| format!("{}: {}", app.domain_name, e))?;
+    }
+    tokio::time::sleep(grace).await;
+
+    for app in stop_order(apps) {
+        record.stopped.push(app.service_name.clone());
+        save(state, record).await?;
+        services
+            .stop(&app.service_name)
+            .await
+            .map_err(|e| format!("{}: {}", app.service_name, e))?;
+    }
+    Ok(())
+}
+
+/// Undoes `drain`: restarts the stopped units (a no-op after a reboot, which
+/// already started them) and rewrites the drained vhosts from their records.
+/// Forgets the record. Failures are logged; the rest is still restored.
+pub async fn restore(
+    state: &dyn StateStore,
+    services: &dyn ServiceManager,
+    proxy: &dyn ProxyManager,
+    record: &RebootRecord,
+) -> Result<(), String> {
+    for service in record.stopped.iter().rev() {
+        if let Err(e) = services.start(service).await {
+            warn!("🔁 Could not start {} after reboot: {}", service, e);
+        }
+    }
+    for app_id in &record.drained {
+        let Some(app) = get_record::<AppRecord>(state, NS_APPS, app_id).await? else {
+            continue;
+        };
+        // A circuit that opened meanwhile keeps its maintenance page
+        if let Some(port) = app.port
+            && let Err(e) = proxy.create_vhost(&app.domain_name, port, &app.vhost).await
+        {
+            warn!(
+                "🔁 Could not restore the vhost of {}: {}",
+                app.domain_name, e
+            );
+        }
+    }
+    forget(state).await
+}
+
+/// Drops the record without undoing anything.
+pub async fn forget(state: &dyn StateStore) -> Result<(), String> {
+    state.delete(NS_REBOOT, PENDING_KEY).await
+}
+
+/// Kernel releases with a bootable image: /boot/vmlinuz-<release> (Debian,
+/// Ubuntu, RHEL) or <modules>/<release>/vmlinuz (Fedora, Arch).
+pub fn installed_kernels(boot: &Path, modules: &Path) -> Vec<String> {
+    let mut installed: Vec<String> = std::fs::read_dir(boot)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            name.strip_prefix("vmlinuz-").map(str::to_string)
+        })
+        .chain(
+            std::fs::read_dir(modules)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter(|entry| entry.path().join("vmlinuz").is_file())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned()),
+        )
+        .filter(|release| release.starts_with(|c: char| c.is_ascii_digit()))
+        .collect();
+    installed.sort_by(|a, b| compare_kernel_versions(a, b));
+    installed.dedup();
+    installed
+}
+
+/// Reads the kernel state from /proc, /boot and /sys; reboots through systemd.
+pub struct SystemRebootManager;
+
+fn read_kernel_state() -> KernelState {
+    let livepatches = std::fs::read_dir("/sys/kernel/livepatch")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            std::fs::read_to_string(entry.path().join("enabled")).is_ok_and(|v| v.trim() == "1")
+        })
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    KernelState {
+        running: std::fs::read_to_string("/proc/sys/kernel/osrelease")
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+        installed: installed_kernels(Path::new("/boot"), Path::new("/lib/modules")),
+        livepatches,
+        reboot_required: Path::new("/var/run/reboot-required").exists(),
+        reboot_packages: std::fs::read_to_string("/var/run/reboot-required.pkgs")
+            .unwrap_or_default()
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect(),
+        booted_at: System::boot_time() as i64,
+    }
+}
+
+#[async_trait]
+impl RebootManager for SystemRebootManager {
+    async fn kernel_state(&self) -> Result<KernelState, String> {
+        tokio::task::spawn_blocking(read_kernel_state)
+            .await
+            .map_err(|e| format!("Kernel state read failed: {}", e))
+    }
+
+    async fn reboot(&self) -> Result<(), String> {
+        info!("🔁 Rebooting the host");
+        let output = privilege::command("systemctl")
+            .arg("reboot")
+            .output()
+            .await
+            .map_err(|e| format!("Failed to run systemctl: {}", e))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sys::systemd::{DependencyKind, UnitDependency};
+
+    #[test]
+    fn kernel_versions_order_and_advise() {
+        let cases = [
+            ("6.8.0-49-generic", "6.8.0-45-generic", Ordering::Greater),
+            ("6.8.0-100-generic", "6.8.0-99-generic", Ordering::Greater),
+            (
+                "5.14.0-503.el9.x86_64",
+                "5.14.0-427.13.1.el9_4.x86_64",
+                Ordering::Greater,
+            ),
+            ("6.1.0-rc1", "6.1.0-1", Ordering::Less),
+            ("6.8.0-45-generic", "6.8.0-45-generic", Ordering::Equal),
+        ];
+        for (a, b, order) in cases {
+            assert_eq!(compare_kernel_versions(a, b), order, "{} vs {}", a, b);
+        }
+
+        let mut state = KernelState {
+            running: "6.8.0-45-generic".into(),
+            installed: vec!["6.8.0-49-generic".into(), "6.8.0-45-generic".into()],
+            ..Default::default()
+        };
+        let advice = advise(&state);
+        assert_eq!(advice.latest_installed, "6.8.0-49-generic");
+        assert!(advice.needs_reboot && advice.security_patch_pending);
+
+        state.livepatches = vec!["livepatch_Ubuntu_6_8_0_45_1".into()];
+        let advice = advise(&state);
+        assert!(advice.needs_reboot && !advice.security_patch_pending);
+
+        let current = KernelState {
+            running: "6.8.0-49-generic".into(),
+            installed: vec!["6.8.0-49-generic".into()],
+            reboot_required: true,
+            reboot_packages: vec!["dbus".into()],
+            ..Default::default()
+        };
+        let advice = advise(&current);
+        assert!(advice.needs_reboot && !advice.security_patch_pending);
+    }
+
+    #[test]
+    fn windows_open_daily_and_may_wrap_midnight() {
+        let night = RebootWindow::parse("02:00-04:00").unwrap();
+        assert_eq!(night.wait_from(3 * 3600), Duration::ZERO);
+        assert_eq!(night.wait_from(3600 + 1800), Duration::from_secs(1800));
+        assert_eq!(night.wait_from(5 * 3600), Duration::from_secs(21 * 3600));
+
+        let late = RebootWindow::parse("23:30-00:30").unwrap();
+        assert_eq!(late.wait_from(15 * 60), Duration::ZERO);
+        assert_eq!(late.length(), Duration::from_secs(3600));
+        assert_eq!(late.wait_from(23 * 3600), Duration::from_secs(1800));
+
+        for bad in [
+            "02:00",
+            "2:00-04:00",
+            "02:00-02:00",
+            "25:00-01:00",
+            "02:00-04:60",
+        ] {
+            assert!(RebootWindow::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn dependents_stop_before_their_backends() {
+        let app = |id: &str, deps: &[&str]| AppRecord {
+            app_id: id.into(),
+            service_name: format!("kari-{}", id),
+            dependencies: deps
+                .iter()
+                .map(|unit| UnitDependency {
+                    unit: unit.to_string(),
+                    kind: DependencyKind::Requires,
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let order: Vec<String> = stop_order(vec![
+            app("db", &[]),
+            app("api", &["kari-db.service"]),
+            app("web", &["kari-api.service", "postgresql.service"]),
+        ])
+        .into_iter()
+        .map(|app| app.app_id)
+        .collect();
+        assert_eq!(order, ["web", "api", "db"]);
+    }
+
+    #[test]
+    fn installed_kernels_come_from_boot_and_modules() {
+        let root = tempfile::tempdir().unwrap();
+        let (boot, modules) = (root.path().join("boot"), root.path().join("modules"));
+        for dir in ["6.8.0-49-generic", "6.5.0-1-generic"] {
+            std::fs::create_dir_all(modules.join(dir)).unwrap();
+        }
+        std::fs::create_dir_all(&boot).unwrap();
+        std::fs::write(boot.join("vmlinuz-6.8.0-45-generic"), "").unwrap();
+        std::fs::write(boot.join("vmlinuz-linux"), "").unwrap();
+        std::fs::write(modules.join("6.8.0-49-generic/vmlinuz"), "").unwrap();
+        // 6.5.0-1 left only a modules directory behind
+        assert_eq!(
+            installed_kernels(&boot, &modules),
+            ["6.8.0-45-generic", "6.8.0-49-generic"]
+        );
+    }
+}
@@ -0,0 +1,257 @@
+// agent/src/sys/sbom.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Builds a CycloneDX SBOM for a release by
+// parsing the lockfiles it was built from. No external scanner is involved, so
+// the inventory is exactly what the package manager pinned.
+//
+// 🛡️ Zero-Trust: Lockfiles live in the tenant-writable release directory and
+// are read by root. Only regular files are parsed (symlinks are refused, so a
+// tenant cannot point `Cargo.lock` at /etc/shadow) and each is size-capped.
+
+use serde_json::{Value, json};
+use std::fs as std_fs;
+use std::path::{Path, PathBuf};
+
+const MAX_LOCKFILE_BYTES: u64 = 32 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Component {
+    pub ecosystem: &'static str, // purl type: npm, cargo, composer, pypi
+    pub name: String,
+    pub version: String,
+}
+
+impl Component {
+    fn purl(&self) -> String {
+        format!("pkg:{}/{}@{}", self.ecosystem, self.name, self.version)
+    }
+}
+
+/// Reads a lockfile if it is a regular file; `None` when absent or not a plain file.
+fn read_lockfile(path: &Path) -> Result<Option<String>, String> {
+    let meta = match std_fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to stat {:?}: {}", path, e)),
+    };
+    if !meta.is_file() {
+        return Ok(None);
+    }
+    if meta.len() > MAX_LOCKFILE_BYTES {
+        return Err(format!("{:?} exceeds the lockfile size limit", path));
+    }
+    std_fs::read_to_string(path)
+        .map(Some)
+        .map_err(|e| format!("Failed to read {:?}: {}", path, e))
+}
+
+/// package-lock.json v2/v3 (`packages`), falling back to v1 (`dependencies`).
+fn parse_npm(content: &str) -> Result<Vec<Component>, String> {
+    let doc: Value = serde_json::from_str(content)
+        .map_err(|e| format!("package-lock.json is not valid JSON: {}", e))?;
+    let mut out = Vec::new();
+
+    if let Some(packages) = doc.get("packages").and_then(Value::as_object) {
+        for (path, pkg) in packages {
+            // "" is the root project; nested entries are "node_modules/a/node_modules/b"
+            let Some(name) = path
+                .rsplit("node_modules/")
+                .next()
+                .filter(|n| !n.is_empty())
+            else {
+                continue;
+            };
+            if let Some(version) = pkg.get("version").and_then(Value::as_str) {
+                out.push(Component {
+                    ecosystem: "npm",
+                    name: name.to_string(),
+                    version: version.to_string(),
+                });
+            }
+        }
+    } else if let Some(deps) = doc.get("dependencies").and_then(Value::as_object) {
+        for (name, pkg) in deps {
+            if let Some(version) = pkg.get("version").and_then(Value::as_str) {
+                out.push(Component {
+                    ecosystem: "npm",
+                    name: name.clone(),
+                    version: version.to_string(),
+                });
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Cargo.lock: `name`/`version` pairs inside each `[[package]]` table.
+fn parse_cargo(content: &str) -> Vec<Component> {
+    let mut out = Vec::new();
+    let mut name: Option<String> = None;
+    let mut version: Option<String> = None;
+
+    let mut flush = |name: &mut Option<String>, version: &mut Option<String>| {
+        if let (Some(n), Some(v)) = (name.take(), version.take()) {
+            out.push(Component {
+                ecosystem: "cargo",
+                name: n,
+                version: v,
+            });
+        }
+    };
+
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            flush(&mut name, &mut version);
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches('"').to_string();
+        match key.trim() {
+            "name" => name = Some(value),
+            "version" => version = Some(value),
+            _ => {}
+        }
+    }
+    flush(&mut name, &mut version);
+    out
+}
+
+/// composer.lock: `packages` and `packages-dev`.
+fn parse_composer(content: &str) -> Result<Vec<Component>, String> {
+    let doc: Value = serde_json::from_str(content)
+        .map_err(|e| format!("composer.lock is not valid JSON: {}", e))?;
+    Ok(["packages", "packages-dev"]
+        .iter()
+        .filter_map(|section| doc.get(section).and_then(Value::as_array))
+        .flatten()
+        .filter_map(|pkg| {
+            Some(Component {
+                ecosystem: "composer",
+                name: pkg.get("name")?.as_str()?.to_string(),
+                version: pkg.get("version")?.as_str()?.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// requirements.txt: only exact pins (`name==version`) identify a component.
+fn parse_requirements(content: &str) -> Vec<Component> {
+    content
+        .lines()
+        .map(|l| l.split('#').next().unwrap_or("").trim())
+        .filter_map(|l| l.split_once("=="))
+        .map(|(name, version)| {
+            let name = name.split('[').next().unwrap_or(name).trim();
+            let version = version.split(';').next().unwrap_or(version).trim();
+            Component {
+                ecosystem: "pypi",
+                name: name.to_lowercase(),
+                version: version.to_string(),
+            }
+        })
+        .filter(|c| !c.name.is_empty() && !c.version.is_empty())
+        .collect()
+}
+
+/// Collects every component pinned by a lockfile at the root of `release_dir`.
+pub fn scan_release(release_dir: &Path) -> Result<Vec<Component>, String> {
+    let mut components = Vec::new();
+
+    if let Some(c) = read_lockfile(&release_dir.join("package-lock.json"))? {
+        components.extend(parse_npm(&c)?);
+    }
+    if let Some(c) = read_lockfile(&release_dir.join("Cargo.lock"))? {
+        components.extend(parse_cargo(&c));
+    }
+    if let Some(c) = read_lockfile(&release_dir.join("composer.lock"))? {
+        components.extend(parse_composer(&c)?);
+    }
+    if let Some(c) = read_lockfile(&release_dir.join("requirements.txt"))? {
+        components.extend(parse_requirements(&c));
+    }
+
+    components.sort();
+    components.dedup();
+    Ok(components)
+}
+
+/// Renders a CycloneDX 1.5 JSON document describing `release` of `app_name`.
+pub fn render_cyclonedx(app_name: &str, release: &str, components: &[Component]) -> Vec<u8> {
+    let doc = json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "version": 1,
+        "metadata": {
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "tools": [{ "vendor": "kari", "name": "kari-agent", "version": env!("CARGO_PKG_VERSION") }],
+            "component": { "type": "application", "name": app_name, "version": release },
+        },
+        "components": components
+            .iter()
+            .map(|c| json!({
+                "type": "library",
+                "name": c.name,
+                "version": c.version,
+                "purl": c.purl(),
+                "bom-ref": c.purl(),
+            }))
+            .collect::<Vec<_>>(),
+    });
+    serde_json::to_vec_pretty(&doc).unwrap_or_default()
+}
+
+/// Root-owned location of a release's SBOM, outside the tenant-writable tree.
+pub fn sbom_path(state_dir: &Path, domain: &str, release: &str) -> PathBuf {
+    state_dir
+        .join("sboms")
+        .join(domain)
+        .join(format!("{}.cdx.json", release))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lockfiles_are_parsed_and_symlinks_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        std_fs::write(
+            dir.path().join("package-lock.json"),
+            r#"{"lockfileVersion":3,"packages":{
+                "":{"name":"app","version":"1.0.0"},
+                "node_modules/lodash":{"version":"4.17.20"},
+                "node_modules/a/node_modules/@scope/b":{"version":"2.0.0"}}}"#,
+        )
+        .unwrap();
+        std_fs::write(
+            dir.path().join("Cargo.lock"),
+            "version = 3\n\n[[package]]\nname = \"serde\"\nversion = \"1.0.200\"\nsource = \"registry\"\n",
+        )
+        .unwrap();
+        std_fs::write(
+            dir.path().join("requirements.txt"),
+            "Django==4.2.1 ; python_version >= '3.8'\nrequests>=2\n",
+        )
+        .unwrap();
+        std::os::unix::fs::symlink("/etc/passwd", dir.path().join("composer.lock")).unwrap();
+
+        let components = scan_release(dir.path()).unwrap();
+        let purls: Vec<String> = components.iter().map(Component::purl).collect();
+        assert_eq!(
+            purls,
+            vec![
+                "pkg:cargo/serde@1.0.200",
+                "pkg:npm/@scope/b@2.0.0",
+                "pkg:npm/lodash@4.17.20",
+                "pkg:pypi/django@4.2.1",
+            ]
+        );
+
+        let bom: Value =
+            serde_json::from_slice(&render_cyclonedx("app", "r1", &components)).unwrap();
+        assert_eq!(bom["bomFormat"], "CycloneDX");
+        assert_eq!(bom["components"].as_array().unwrap().len(), 4);
+    }
+}
@@ -1,10 +1,13 @@
 // agent/src/sys/scheduler.rs
 
+use crate::sys::executor::{CommandSpec, Executor, SystemExecutor};
+use crate::sys::systemd::{ensure_unit_not_foreign, is_unit_enabled, is_unit_failed};
 use crate::sys::traits::{JobIntent, JobScheduler};
 use async_trait::async_trait;
 use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::Arc;
 use tokio::fs;
-use tokio::process::Command;
 
 // ==============================================================================
 // 1. Concrete Implementation (Systemd)
@@ -12,11 +15,40 @@ use tokio::process::Command;
 
 pub struct SystemdTimerManager {
     systemd_dir: String, // Injected via AgentConfig, e.g., "/etc/systemd/system"
+    executor: Arc<dyn Executor>,
 }
 
 impl SystemdTimerManager {
     pub fn new(systemd_dir: String) -> Self {
-        Self { systemd_dir }
+        Self::with_executor(systemd_dir, Arc::new(SystemExecutor))
+    }
+
+    pub fn with_executor(systemd_dir: String, executor: Arc<dyn Executor>) -> Self {
+        Self {
+            systemd_dir,
+            executor,
+        }
+    }
+
+    async fn daemon_reload(&self) -> Result<(), String> {
+        let reload_out = self
+            .executor
+            .run(&CommandSpec::privileged("systemctl").arg("daemon-reload"))
+            .await
+            .map_err(|e| format!("Failed to execute daemon-reload: {}", e))?;
+
+        if !reload_out.success() {
+            return Err("systemctl daemon-reload failed".into());
+        }
+        Ok(())
+    }
+
+    /// 🛡️ Zero-Trust Path Traversal Shield
+    fn validate_job_name(name: &str) -> Result<(), String> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err("SECURITY VIOLATION: Invalid job name format".into());
+        }
+        Ok(())
     }
 }
 
@@ -24,14 +56,7 @@ impl SystemdTimerManager {
 impl JobScheduler for SystemdTimerManager {
     async fn schedule_job(&self, intent: &JobIntent) -> Result<(), String> {
         // 🛡️ 1. Zero-Trust Path Traversal Shield
-        if intent.name.is_empty()
-            || !intent
-                .name
-                .chars()
-                .all(|c| c.is_ascii_alphanumeric() || c == '-')
-        {
-            return Err("SECURITY VIOLATION: Invalid job name format".into());
-        }
+        Self::validate_job_name(&intent.name)?;
 
         // 🛡️ 2. Directive Injection Prevention
         if intent.schedule.contains('\n') || intent.schedule.contains('=') {
@@ -42,6 +67,11 @@ impl JobScheduler for SystemdTimerManager {
         let service_path = format!("{}/{}.service", self.systemd_dir, service_name);
         let timer_path = format!("{}/{}.timer", self.systemd_dir, service_name);
 
+        // 🛡️ A job named "nginx" must not replace someone else's kari-job-nginx units
+        for path in [&service_path, &timer_path] {
+            ensure_unit_not_foreign(self.executor.as_ref(), Path::new(path)).await?;
+        }
+
         // 🛡️ 3. SLA Trait Compliance (Anti-Injection Construction)
         // We iterate through the discrete arguments and safely quote them for systemd parsing,
         // completely avoiding raw string execution.
@@ -116,25 +146,17 @@ WantedBy=timers.target
         }
 
         // 5. Reload daemon to recognize the new files
-        let reload_out = Command::new("systemctl")
-            .arg("daemon-reload")
-            .output()
-            .await
-            .map_err(|e| format!("Failed to execute daemon-reload: {}", e))?;
-
-        if !reload_out.status.success() {
-            return Err("systemctl daemon-reload failed".into());
-        }
+        self.daemon_reload().await?;
 
         // 6. Enable and Start the Timer (Not the service!)
         let timer_name = format!("{}.timer", service_name);
-        let enable_out = Command::new("systemctl")
-            .args(["enable", "--now", &timer_name])
-            .output()
+        let enable_out = self
+            .executor
+            .run(&CommandSpec::privileged("systemctl").args(["enable", "--now", &timer_name]))
             .await
             .map_err(|e| format!("Failed to enable timer: {}", e))?;
 
-        if !enable_out.status.success() {
+        if !enable_out.success() {
             let stderr = String::from_utf8_lossy(&enable_out.stderr);
             return Err(format!(
                 "Failed to activate timer {}: {}",
@@ -144,4 +166,43 @@ WantedBy=timers.target
 
         Ok(())
     }
+    async fn unschedule_job(&self, name: &str) -> Result<(), String> {
+        Self::validate_job_name(name)?;
+
+        let service_name = format!("kari-job-{}", name);
+        let timer_name = format!("{}.timer", service_name);
+
+        // Best-effort: the timer may never have been enabled
+        let _ = self
+            .executor
+            .run(&CommandSpec::privileged("systemctl").args(["disable", "--now", &timer_name]))
+            .await;
+
+        for ext in ["timer", "service"] {
+            let path = format!("{}/{}.{}", self.systemd_dir, service_name, ext);
+            match fs::remove_file(&path).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("Failed to remove {}: {}", path, e)),
+            }
+        }
+
+        self.daemon_reload().await?;
+
+        Ok(())
+    }
+
+    async fn is_job_enabled(&self, name: &str) -> Result<bool, String> {
+        Self::validate_job_name(name)?;
+        is_unit_enabled(self.executor.as_ref(), &format!("kari-job-{}.timer", name)).await
+    }
+
+    async fn job_failed(&self, name: &str) -> Result<bool, String> {
+        Self::validate_job_name(name)?;
+        is_unit_failed(
+            self.executor.as_ref(),
+            &format!("kari-job-{}.service", name),
+        )
+        .await
+    }
 }
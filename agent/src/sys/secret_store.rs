@@ -0,0 +1,121 @@
+// agent/src/sys/secret_store.rs
+//
+// 🛡️ SOLID: Single-Responsibility — At-rest storage of named secrets only.
+// 🛡️ Zero-Trust: One 0600 root-owned file per secret inside a 0700 directory.
+// Values enter and leave exclusively as ProviderCredential.
+
+use async_trait::async_trait;
+use std::fs as std_fs;
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::PathBuf;
+
+use crate::sys::secrets::ProviderCredential;
+use crate::sys::traits::SecretStore;
+
+/// Env values of the form `secret://name` stand for the named secret and are
+/// resolved from the store at deploy time.
+pub const SECRET_REF_SCHEME: &str = "secret://";
+
+/// The secret name an env value refers to, if it is a reference.
+pub fn secret_ref(value: &str) -> Option<&str> {
+    value.strip_prefix(SECRET_REF_SCHEME)
+}
+
+pub struct FileSecretStore {
+    secrets_dir: PathBuf, // Injected via AgentConfig, e.g., "/etc/kari/secrets"
+}
+
+impl FileSecretStore {
+    pub fn new(secrets_dir: PathBuf) -> Self {
+        Self { secrets_dir }
+    }
+
+    /// 🛡️ Zero-Trust Path Traversal Shield
+    fn secret_path(&self, name: &str) -> Result<PathBuf, String> {
+        if name.is_empty()
+            || name.starts_with('.')
+            || name.contains("..")
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        {
+            return Err(format!(
+                "SECURITY VIOLATION: Invalid secret name '{}'",
+                name
+            ));
+        }
+        Ok(self.secrets_dir.join(name))
+    }
+
+    fn ensure_dir(&self) -> Result<(), String> {
+        std_fs::create_dir_all(&self.secrets_dir)
+            .map_err(|e| format!("Failed to create secrets directory: {}", e))?;
+        std_fs::set_permissions(&self.secrets_dir, std_fs::Permissions::from_mode(0o700))
+            .map_err(|e| format!("Failed to secure secrets directory: {}", e))
+    }
+}
+
+#[async_trait]
+impl SecretStore for FileSecretStore {
+    async fn put_secret(&self, name: &str, value: ProviderCredential) -> Result<(), String> {
+        let path = self.secret_path(name)?;
+        let tmp_path = self.secrets_dir.join(format!(".{}.tmp", name));
+        self.ensure_dir()?;
+
+        // 🚨 CRITICAL SECURITY BOUNDARY 🚨
+        // Synchronous I/O inside the closure: the plaintext never crosses an `.await`.
+        let write_result = value.use_secret(|secret_str| {
+            let mut file = std_fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600) // rw------- (Strictly locked down from inception)
+                .open(&tmp_path)
+                .map_err(|e| format!("Failed to open secret file securely: {}", e))?;
+
+            file.write_all(secret_str.as_bytes())
+                .map_err(|e| format!("Failed to write secret bytes: {}", e))?;
+            file.sync_all()
+                .map_err(|e| format!("Failed to sync secret to disk: {}", e))?;
+
+            // Atomic replace: readers never observe a half-written secret
+            std_fs::rename(&tmp_path, &path)
+                .map_err(|e| format!("Failed to commit secret file: {}", e))
+        });
+
+        value.destroy();
+
+        if write_result.is_err() {
+            let _ = std_fs::remove_file(&tmp_path);
+        }
+        write_result
+    }
+
+    async fn get_secret(&self, name: &str) -> Result<Option<ProviderCredential>, String> {
+        let path = self.secret_path(name)?;
+        match std_fs::read_to_string(&path) {
+            // The String's heap allocation moves straight into the SecretString.
+            Ok(content) => Ok(Some(ProviderCredential::from_string(content))),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Failed to read secret '{}': {}", name, e)),
+        }
+    }
+
+    async fn delete_secret(&self, name: &str) -> Result<(), String> {
+        let path = self.secret_path(name)?;
+
+        // 🛡️ Disk Residue Scrubbing: overwrite before unlinking
+        let len = match std_fs::metadata(&path) {
+            Ok(meta) => meta.len() as usize,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(format!("Failed to stat secret '{}': {}", name, e)),
+        };
+        if let Ok(mut file) = std_fs::OpenOptions::new().write(true).open(&path) {
+            let _ = file.write_all(&vec![0u8; len]);
+            let _ = file.sync_all();
+        }
+
+        std_fs::remove_file(&path).map_err(|e| format!("Failed to delete secret '{}': {}", name, e))
+    }
+}
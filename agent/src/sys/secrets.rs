@@ -22,6 +22,19 @@ impl ProviderCredential {
         }
     }
 
+    /// 🛡️ Hardened constructor for raw request bytes (protobuf `bytes` fields).
+    /// Valid UTF-8 keeps the request's own allocation, so the secret is never
+    /// copied; invalid input is wiped before the error is returned.
+    pub fn from_utf8(bytes: Vec<u8>) -> Result<Self, String> {
+        match String::from_utf8(bytes) {
+            Ok(s) => Ok(Self::from_string(s)),
+            Err(e) => {
+                zeroize::Zeroize::zeroize(&mut e.into_bytes());
+                Err("not valid UTF-8".to_string())
+            }
+        }
+    }
+
     /// Safely exposes the secret for a fleeting moment.
     /// 🛡️ Lexical Scope Confinement: The secret cannot escape this closure.
     pub fn use_secret<F, R>(&self, action: F) -> R
@@ -0,0 +1,139 @@
+// agent/src/sys/selftest.rs
+//
+// 🛡️ SOLID: Single-Responsibility — The host-level pieces of RunSelfTest: a
+// throwaway hello-world server, a free loopback port, a short-lived self-signed
+// certificate and an HTTP probe. Orchestration (user, unit, vhost, teardown)
+// stays in the handler, which drives the same managers a real app would use.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+use crate::sys::http_probe::fetch;
+use crate::sys::secrets::ProviderCredential;
+
+const PROBE_ATTEMPT_TIMEOUT: Duration = Duration::from_secs(2);
+const PROBE_RETRY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Short random-enough suffix so concurrent self-tests never share names.
+pub fn run_suffix() -> String {
+    let nanos = chrono::Utc::now().timestamp_subsec_nanos();
+    format!("{:08x}", nanos ^ std::process::id().rotate_left(16))
+}
+
+/// Asks the kernel for an unused loopback port. The port is released before it
+/// is returned, so a racing process could take it; the probe would then fail.
+pub fn free_loopback_port() -> Result<u16, String> {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .map(|addr| addr.port())
+        .map_err(|e| format!("Failed to find a free loopback port: {}", e))
+}
+
+/// Static file server for `dir`, bound to loopback only.
+pub fn hello_world_argv(port: u16, dir: &Path) -> Vec<String> {
+    vec![
+        "/usr/bin/python3".into(),
+        "-m".into(),
+        "http.server".into(),
+        port.to_string(),
+        "--bind".into(),
+        "127.0.0.1".into(),
+        "--directory".into(),
+        dir.to_string_lossy().into_owned(),
+    ]
+}
+
+/// A one-day self-signed certificate for `domain`: (fullchain PEM, private key).
+pub async fn self_signed_certificate(domain: &str) -> Result<(String, ProviderCredential), String> {
+    // 🛡️ The key only ever touches a private (0700) temp dir that is removed on return
+    let dir = tempfile::tempdir().map_err(|e| format!("Failed to create temp dir: {}", e))?;
+    let key_path = dir.path().join("key.pem");
+    let cert_path = dir.path().join("cert.pem");
+
+    let output = Command::new("openssl")
+        .args(["req", "-x509", "-nodes", "-days", "1"])
+        .args(["-newkey", "ec", "-pkeyopt", "ec_paramgen_curve:prime256v1"])
+        .arg("-subj")
+        .arg(format!("/CN={}", domain))
+        .arg("-keyout")
+        .arg(&key_path)
+        .arg("-out")
+        .arg(&cert_path)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run openssl: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "openssl failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let fullchain = tokio::fs::read_to_string(&cert_path)
+        .await
+        .map_err(|e| format!("Failed to read certificate: {}", e))?;
+    let key = tokio::fs::read_to_string(&key_path)
+        .await
+        .map_err(|e| format!("Failed to read private key: {}", e))?;
+    Ok((fullchain, ProviderCredential::from_string(key)))
+}
+
+/// Retries a GET against `addr` (with `Host: host`) until it returns 200 with
+/// `expect` in the body, or `timeout` elapses.
+pub async fn probe_http(
+    addr: &str,
+    host: &str,
+    expect: &str,
+    timeout: Duration,
+) -> Result<(), String> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let failure = match fetch(addr, host, "/", PROBE_ATTEMPT_TIMEOUT).await {
+            Ok((200, body)) if body.contains(expect) => return Ok(()),
+            Ok((status, _)) => format!("HTTP {} without the expected page", status),
+            Err(e) => e,
+        };
+        if Instant::now() >= deadline {
+            return Err(failure);
+        }
+        tokio::time::sleep(PROBE_RETRY_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn probe_checks_host_header_and_body() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            while let Ok((mut conn, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let n = conn.read(&mut buf).await.unwrap_or(0);
+                let req = String::from_utf8_lossy(&buf[..n]).to_string();
+                let resp = if req.contains("Host: selftest.example\r\n") {
+                    "HTTP/1.0 200 OK\r\n\r\nhello kari-token\n"
+                } else {
+                    "HTTP/1.0 404 Not Found\r\n\r\n"
+                };
+                let _ = conn.write_all(resp.as_bytes()).await;
+            }
+        });
+
+        let short = Duration::from_millis(100);
+        assert!(
+            probe_http(&addr, "selftest.example", "kari-token", short)
+                .await
+                .is_ok()
+        );
+        let err = probe_http(&addr, "other.example", "kari-token", short)
+            .await
+            .unwrap_err();
+        assert!(err.contains("HTTP 404"), "{}", err);
+    }
+}
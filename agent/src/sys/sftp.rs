@@ -0,0 +1,437 @@
+// agent/src/sys/sftp.rs
+//
+// 📂 SFTP-only file access to an app's shared directory, for tenants whose
+// workflow is uploading assets rather than deploying.
+//
+// The app user logs in with the keys granted here and nothing else: a
+// per-user sshd drop-in forces internal-sftp (no shell, no forwarding, no
+// password) and chroots it into <sftp_root>/<app_user>. sshd only chroots into
+// root-owned directories, and the app directory belongs to the app user, so the
+// shared directory is bind-mounted into the chroot by a kari mount unit
+// instead. Uploads land as the app user, readable by the app and nobody else.
+//
+// The keys file sits next to the drop-in, root-owned, so neither the tenant nor
+// the app can add a key. sshd must include sshd_config.d/*.conf (every current
+// distribution does) and, for the locked app account to log in with a key,
+// run with UsePAM yes (likewise the packaged default).
+
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::sys::executor::{CommandSpec, Executor, SystemExecutor};
+use crate::sys::known_hosts::{self, HostKey};
+use crate::sys::traits::{SftpAccessManager, SftpGrant};
+
+/// Where a session starts, inside the chroot.
+pub const SFTP_HOME: &str = "/shared";
+
+/// A public key as an authorized_keys line takes it. Options are never taken
+/// from the caller; every key is written with `restrict`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthorizedKey {
+    pub key: HostKey,
+    pub comment: String,
+}
+
+/// Parses `type base64 [comment]`, as in a `.pub` file.
+pub fn parse_authorized_key(line: &str) -> Result<AuthorizedKey, String> {
+    if line.contains(['\n', '\r', '\0']) {
+        return Err("A public key is a single line".into());
+    }
+    let key = known_hosts::parse_host_key(line)?;
+    let comment = line
+        .split_whitespace()
+        .skip(2)
+        .collect::<Vec<_>>()
+        .join(" ");
+    Ok(AuthorizedKey { key, comment })
+}
+
+impl AuthorizedKey {
+    pub fn fingerprint(&self) -> String {
+        self.key.fingerprint()
+    }
+}
+
+/// `systemd-escape --path --suffix=mount`: the unit name systemd requires for
+/// a mount at `path`.
+pub fn mount_unit_name(path: &Path) -> String {
+    let trimmed = path.to_string_lossy().trim_matches('/').to_string();
+    let mut name = String::new();
+    for (i, byte) in trimmed.bytes().enumerate() {
+        match byte {
+            b'/' => name.push('-'),
+            b'.' if i == 0 => name.push_str("\\x2e"),
+            b if b.is_ascii_alphanumeric() || b == b'_' || b == b'.' => name.push(b as char),
+            b => name.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    if name.is_empty() {
+        name.push('-');
+    }
+    name + ".mount"
+}
+
+fn render_drop_in(user: &str, chroot: &Path, keys_file: &Path) -> String {
+    format!(
+        r#"# Managed by kari: SFTP-only access for {user}
+Match User {user}
+    AuthorizedKeysFile {keys}
+    AuthenticationMethods publickey
+    PasswordAuthentication no
+    KbdInteractiveAuthentication no
+    ChrootDirectory {chroot}
+    ForceCommand internal-sftp -d {home} -u 0027
+    PermitTTY no
+    PermitTunnel no
+    AllowAgentForwarding no
+    AllowTcpForwarding no
+    AllowStreamLocalForwarding no
+    X11Forwarding no
+"#,
+        user = user,
+        keys = keys_file.display(),
+        chroot = chroot.display(),
+        home = SFTP_HOME,
+    )
+}
+
+fn render_mount_unit(domain: &str, what: &Path, at: &Path) -> String {
+    format!(
+        r#"[Unit]
+Description=Kari SFTP view of {domain}'s shared directory
+
+[Mount]
+What={what}
+Where={at}
+Type=none
+Options=bind,nosuid,nodev,noexec
+
+[Install]
+WantedBy=multi-user.target
+"#,
+        domain = domain,
+        what = what.display(),
+        at = at.display(),
+    )
+}
+
+fn render_keys(keys: &[AuthorizedKey]) -> String {
+    keys.iter()
+        .map(|key| {
+            let line = format!(
+                "restrict {} {}",
+                key.key.key_type,
+                base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &key.key.blob)
+            );
+            if key.comment.is_empty() {
+                format!("{}\n", line)
+            } else {
+                format!("{} {}\n", line, key.comment)
+            }
+        })
+        .collect()
+}
+
+/// sshd drop-ins, chroots under `sftp_root`, and bind mounts as systemd units.
+pub struct OpensshSftpManager {
+    sshd_config_dir: PathBuf,
+    sftp_root: PathBuf,
+    systemd_dir: PathBuf,
+    executor: Arc<dyn Executor>,
+}
+
+impl OpensshSftpManager {
+    pub fn new(sshd_config_dir: PathBuf, sftp_root: PathBuf, systemd_dir: PathBuf) -> Self {
+        Self::with_executor(
+            sshd_config_dir,
+            sftp_root,
+            systemd_dir,
+            Arc::new(SystemExecutor),
+        )
+    }
+
+    pub fn with_executor(
+        sshd_config_dir: PathBuf,
+        sftp_root: PathBuf,
+        systemd_dir: PathBuf,
+        executor: Arc<dyn Executor>,
+    ) -> Self {
+        Self {
+            sshd_config_dir,
+            sftp_root,
+            systemd_dir,
+            executor,
+        }
+    }
+
+    fn drop_in(&self, user: &str) -> PathBuf {
+        self.sshd_config_dir
+            .join(format!("kari-sftp-{}.conf", user))
+    }
+
+    fn keys_file(&self, user: &str) -> PathBuf {
+        self.sshd_config_dir
+            .join(format!("kari-sftp-{}.keys", user))
+    }
+
+    fn chroot(&self, user: &str) -> PathBuf {
+        self.sftp_root.join(user)
+    }
+
+    fn mount_point(&self, user: &str) -> PathBuf {
+        self.chroot(user).join(SFTP_HOME.trim_start_matches('/'))
+    }
+
+    async fn run(&self, spec: CommandSpec) -> Result<(), String> {
+        let argv = spec.argv().join(" ");
+        let output = self
+            .executor
+            .run(&spec)
+            .await
+            .map_err(|e| format!("Failed to execute {}: {}", argv, e))?;
+        if !output.success() {
+            return Err(format!(
+                "{} failed: {}",
+                argv,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Writes `path` unless it already holds `content`; true if it was written.
+    async fn write_if_changed(&self, path: &Path, content: &str) -> Result<bool, String> {
+        if self
+            .executor
+            .read_file(path)
+            .await
+            .ok()
+            .flatten()
+            .as_deref()
+            == Some(content)
+        {
+            return Ok(false);
+        }
+        self.executor
+            .write_file(path, content, 0o644)
+            .await
+            .map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+        Ok(true)
+    }
+}
+
+fn valid_user(user: &str) -> Result<(), String> {
+    if !user.starts_with("kari-") || !user.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(format!("SECURITY VIOLATION: Invalid app user '{}'", user));
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl SftpAccessManager for OpensshSftpManager {
+    async fn grant(&self, grant: &SftpGrant) -> Result<(), String> {
+        valid_user(&grant.app_user)?;
+        let user = grant.app_user.as_str();
+
+        // 1. The chroot (root-owned, 0755 by the umask) and its mount point
+        let mount_point = self.mount_point(user);
+        self.executor
+            .create_dir_all(&mount_point)
+            .await
+            .map_err(|e| format!("Failed to create {:?}: {}", mount_point, e))?;
+
+        // 2. The shared directory, bound into it
+        let unit = mount_unit_name(&mount_point);
+        let unit_content = render_mount_unit(&grant.domain_name, &grant.shared_dir, &mount_point);
+        if self
+            .write_if_changed(&self.systemd_dir.join(&unit), &unit_content)
+            .await?
+        {
+            self.run(CommandSpec::privileged("systemctl").arg("daemon-reload"))
+                .await?;
+        }
+        self.run(CommandSpec::privileged("systemctl").args(["enable", "--now", &unit]))
+            .await?;
+
+        // 3. The keys: read on every login, so a new set needs no reload
+        let keys_file = self.keys_file(user);
+        self.executor
+            .write_file(&keys_file, &render_keys(&grant.keys), 0o644)
+            .await
+            .map_err(|e| format!("Failed to write {:?}: {}", keys_file, e))?;
+
+        // 4. The drop-in, checked before sshd loads it
+        let drop_in = self.drop_in(user);
+        let content = render_drop_in(user, &self.chroot(user), &keys_file);
+        if self.write_if_changed(&drop_in, &content).await? {
+            if let Err(e) = self.run(CommandSpec::privileged("sshd").arg("-t")).await {
+                // 🛡️ A drop-in sshd refuses must not stop it from starting next boot
+                let _ = self.executor.remove_file(&drop_in).await;
+                let _ = self.executor.remove_file(&keys_file).await;
+                return Err(e);
+            }
+            self.run(CommandSpec::privileged("systemctl").args(["reload", "sshd"]))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn revoke(&self, app_user: &str) -> Result<(), String> {
+        valid_user(app_user)?;
+        let drop_in = self.drop_in(app_user);
+        let mount_point = self.mount_point(app_user);
+        let unit_path = self.systemd_dir.join(mount_unit_name(&mount_point));
+        if !self.executor.exists(&drop_in).await? && !self.executor.exists(&unit_path).await? {
+            return Ok(()); // Never granted
+        }
+
+        // 1. No new logins
+        self.executor
+            .remove_file(&drop_in)
+            .await
+            .map_err(|e| format!("Failed to remove {:?}: {}", drop_in, e))?;
+        self.executor
+            .remove_file(&self.keys_file(app_user))
+            .await
+            .map_err(|e| format!("Failed to remove keys: {}", e))?;
+        self.run(CommandSpec::privileged("systemctl").args(["reload", "sshd"]))
+            .await?;
+
+        // 2. Nor open sessions; pkill exits 1 when there were none
+        let _ = self
+            .executor
+            .run(&CommandSpec::privileged("pkill").args([
+                "-u",
+                app_user,
+                "-x",
+                "sshd|sshd-session",
+            ]))
+            .await;
+
+        // 3. The bind mount, then the empty chroot. Only ever rmdir here: the
+        //    mount point holds the tenant's files for as long as it is mounted.
+        let unit = mount_unit_name(&mount_point);
+        self.run(CommandSpec::privileged("systemctl").args(["disable", "--now", &unit]))
+            .await?;
+        self.executor
+            .remove_file(&unit_path)
+            .await
+            .map_err(|e| format!("Failed to remove {:?}: {}", unit_path, e))?;
+        self.run(CommandSpec::privileged("systemctl").arg("daemon-reload"))
+            .await?;
+        for dir in [mount_point, self.chroot(app_user)] {
+            let _ = self
+                .executor
+                .run(&CommandSpec::privileged("rmdir").arg(dir.to_string_lossy()))
+                .await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::FakeExecutor;
+
+    const KEY: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl";
+
+    fn manager(root: &Path, executor: Arc<FakeExecutor>) -> OpensshSftpManager {
+        for dir in ["sshd_config.d", "systemd"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        OpensshSftpManager::with_executor(
+            root.join("sshd_config.d"),
+            root.join("sftp"),
+            root.join("systemd"),
+            executor,
+        )
+    }
+
+    #[test]
+    fn keys_are_parsed_and_mount_units_named_like_systemd_does() {
+        let key = parse_authorized_key(&format!("{} alice@laptop", KEY)).unwrap();
+        assert_eq!(key.comment, "alice@laptop");
+        assert!(render_keys(&[key]).starts_with("restrict ssh-ed25519 AAAA"));
+        let with_options = format!("command=\"/bin/sh\" {}", KEY);
+        assert!(parse_authorized_key(&with_options).is_err());
+        assert!(parse_authorized_key(&format!("{}\nssh-rsa AAAA", KEY)).is_err());
+
+        assert_eq!(
+            mount_unit_name(Path::new("/srv/kari-sftp/kari-app-shop/shared")),
+            "srv-kari\\x2dsftp-kari\\x2dapp\\x2dshop-shared.mount"
+        );
+    }
+
+    #[tokio::test]
+    async fn grants_chroot_into_a_bind_mount_and_revokes_undo_them() {
+        let root = tempfile::tempdir().unwrap();
+        let executor = Arc::new(FakeExecutor::default());
+        let sftp = manager(root.path(), executor.clone());
+        let grant = SftpGrant {
+            app_user: "kari-app-shop".into(),
+            domain_name: "shop.example.com".into(),
+            shared_dir: root.path().join("www/shop.example.com/shared"),
+            keys: vec![parse_authorized_key(KEY).unwrap()],
+        };
+        sftp.grant(&grant).await.unwrap();
+
+        let drop_in = std::fs::read_to_string(
+            root.path()
+                .join("sshd_config.d/kari-sftp-kari-app-shop.conf"),
+        )
+        .unwrap();
+        assert!(drop_in.contains("Match User kari-app-shop\n"));
+        assert!(drop_in.contains("ForceCommand internal-sftp -d /shared"));
+        let argvs = executor.argvs();
+        assert!(argvs.contains(&vec!["sshd".into(), "-t".into()]));
+        assert_eq!(argvs.last().unwrap(), &["systemctl", "reload", "sshd"]);
+        assert!(root.path().join("sftp/kari-app-shop/shared").is_dir());
+
+        // A new key set alone needs no reload
+        let runs = executor.runs().len();
+        sftp.grant(&grant).await.unwrap();
+        assert!(!executor.argvs()[runs..].contains(&vec![
+            "systemctl".to_string(),
+            "reload".into(),
+            "sshd".into()
+        ]));
+
+        sftp.revoke("kari-app-shop").await.unwrap();
+        assert!(
+            !root
+                .path()
+                .join("sshd_config.d/kari-sftp-kari-app-shop.conf")
+                .exists()
+        );
+        assert!(
+            !root
+                .path()
+                .join("sshd_config.d/kari-sftp-kari-app-shop.keys")
+                .exists()
+        );
+        assert_eq!(
+            std::fs::read_dir(root.path().join("systemd"))
+                .unwrap()
+                .count(),
+            0
+        );
+        let runs = executor.runs().len();
+        sftp.revoke("kari-app-shop").await.unwrap();
+        assert_eq!(executor.runs().len(), runs, "nothing left to revoke");
+
+        // sshd refusing the drop-in leaves none behind
+        executor.answer(&["sshd", "-t"], 255, "", "Bad configuration option");
+        let err = sftp.grant(&grant).await.unwrap_err();
+        assert!(err.contains("Bad configuration option"), "{}", err);
+        assert!(
+            !root
+                .path()
+                .join("sshd_config.d/kari-sftp-kari-app-shop.conf")
+                .exists()
+        );
+    }
+}
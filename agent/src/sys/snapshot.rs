@@ -0,0 +1,200 @@
+// agent/src/sys/snapshot.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Disaster recovery for the control layer.
+// A state snapshot captures everything the agent generated on this host (state
+// store, kari-* systemd units and job timers, vhosts, certificates) as a signed
+// bundle, so a replacement host can be rebuilt without replaying every RPC.
+
+use std::collections::BTreeSet;
+use std::fs as std_fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+
+use crate::sys::bundle::{BundleKey, BundleWriter, read_bundle};
+use crate::sys::state::{AppRecord, NS_APPS, list_records};
+use crate::sys::traits::StateStore;
+
+const STATE_ENTRY: &str = "state.json";
+const UNITS_PREFIX: &str = "systemd/";
+const PROXY_PREFIX: &str = "proxy/";
+const SSL_PREFIX: &str = "ssl/";
+
+/// Host directories a snapshot is taken from and restored into.
+pub struct SnapshotPaths<'a> {
+    pub systemd_dir: &'a Path,
+    pub proxy_conf_dir: &'a Path,
+    pub ssl_storage_dir: &'a Path,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SnapshotReport {
+    pub entries: u32,
+}
+
+fn read_dir_files(dir: &Path) -> Result<Vec<(String, PathBuf)>, String> {
+    let entries = match std_fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to list {:?}: {}", dir, e)),
+    };
+    let mut files = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        // 🛡️ symlink_metadata: never follow a link out of the managed directory
+        if path
+            .symlink_metadata()
+            .map(|m| m.is_file())
+            .unwrap_or(false)
+        {
+            files.push((entry.file_name().to_string_lossy().to_string(), path));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn file_mode(path: &Path) -> u32 {
+    std_fs::metadata(path)
+        .map(|m| m.permissions().mode() & 0o777)
+        .unwrap_or(0o600)
+}
+
+pub async fn export_state(
+    state: &dyn StateStore,
+    paths: &SnapshotPaths<'_>,
+    key: &BundleKey,
+    bundle_path: &Path,
+) -> Result<SnapshotReport, String> {
+    let snapshot = state.snapshot().await?;
+    let apps: Vec<AppRecord> = list_records(state, NS_APPS).await?;
+
+    let mut writer = BundleWriter::create(bundle_path, key, "state")?;
+    let mut report = SnapshotReport::default();
+
+    let json = serde_json::to_vec_pretty(&snapshot)
+        .map_err(|e| format!("Failed to encode state: {}", e))?;
+    writer.add_file(STATE_ENTRY, &json, 0o600)?;
+    report.entries += 1;
+
+    // Units and their env files carry app environment, so they are sealed like secrets.
+    for (name, path) in read_dir_files(paths.systemd_dir)? {
+        if !name.starts_with("kari-") {
+            continue;
+        }
+        let data = std_fs::read(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        writer.add_sealed(
+            &format!("{}{}", UNITS_PREFIX, name),
+            &data,
+            file_mode(&path),
+        )?;
+        report.entries += 1;
+    }
+
+    // Only vhosts belonging to kari apps; operator-managed sites stay put.
+    for (name, path) in read_dir_files(paths.proxy_conf_dir)? {
+        let owned = apps
+            .iter()
+            .any(|a| name == a.domain_name || name == format!("{}.conf", a.domain_name));
+        if !owned {
+            continue;
+        }
+        let data = std_fs::read(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        writer.add_file(
+            &format!("{}{}", PROXY_PREFIX, name),
+            &data,
+            file_mode(&path),
+        )?;
+        report.entries += 1;
+    }
+
+    // 🌐 A certificate shared by several apps is captured once
+    let certs: BTreeSet<&str> = apps.iter().map(|a| a.certificate_name()).collect();
+    for cert in certs {
+        let cert_dir = paths.ssl_storage_dir.join(cert);
+        for (name, path) in read_dir_files(&cert_dir)? {
+            let data = zeroize::Zeroizing::new(
+                std_fs::read(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?,
+            );
+            writer.add_sealed(
+                &format!("{}{}/{}", SSL_PREFIX, cert, name),
+                &data,
+                file_mode(&path),
+            )?;
+            report.entries += 1;
+        }
+    }
+
+    writer.finish()?;
+    Ok(report)
+}
+
+/// 🛡️ Zero-Trust Path Traversal Shield: an entry may only name plain components
+/// below its prefix (one for units/vhosts, `domain/file` for certificates).
+fn restore_target(base: &Path, rel: &str, depth: usize) -> Result<PathBuf, String> {
+    let rel_path = Path::new(rel);
+    let components: Vec<_> = rel_path.components().collect();
+    if components.len() != depth || !components.iter().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(format!(
+            "SECURITY VIOLATION: Invalid bundle entry '{}'",
+            rel
+        ));
+    }
+    Ok(base.join(rel_path))
+}
+
+fn write_restored(path: &Path, data: &[u8], mode: u32) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std_fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+    std_fs::write(path, data).map_err(|e| format!("Failed to restore {:?}: {}", path, e))?;
+    std_fs::set_permissions(path, std_fs::Permissions::from_mode(mode & 0o777))
+        .map_err(|e| format!("Failed to set mode on {:?}: {}", path, e))
+}
+
+/// Verifies and restores a snapshot. Files are written in place; the state store
+/// is replaced wholesale only after every file entry has been restored.
+pub async fn import_state(
+    state: &dyn StateStore,
+    paths: &SnapshotPaths<'_>,
+    key: &BundleKey,
+    bundle_path: &Path,
+) -> Result<SnapshotReport, String> {
+    let mut report = SnapshotReport::default();
+    let mut restored_state: Option<serde_json::Value> = None;
+
+    let manifest = read_bundle(bundle_path, key, |name, mode, data| {
+        if name == STATE_ENTRY {
+            restored_state = Some(
+                serde_json::from_slice(data)
+                    .map_err(|e| format!("Corrupt state in bundle: {}", e))?,
+            );
+        } else if let Some(rel) = name.strip_prefix(UNITS_PREFIX) {
+            if !rel.starts_with("kari-") {
+                return Err(format!(
+                    "SECURITY VIOLATION: Foreign unit '{}' in bundle",
+                    rel
+                ));
+            }
+            write_restored(&restore_target(paths.systemd_dir, rel, 1)?, data, mode)?;
+        } else if let Some(rel) = name.strip_prefix(PROXY_PREFIX) {
+            write_restored(&restore_target(paths.proxy_conf_dir, rel, 1)?, data, mode)?;
+        } else if let Some(rel) = name.strip_prefix(SSL_PREFIX) {
+            write_restored(&restore_target(paths.ssl_storage_dir, rel, 2)?, data, mode)?;
+        } else {
+            return Err(format!("Unexpected bundle entry '{}'", name));
+        }
+        report.entries += 1;
+        Ok(())
+    })?;
+
+    if manifest.kind != "state" {
+        return Err(format!(
+            "Bundle is a '{}' bundle, not a state snapshot",
+            manifest.kind
+        ));
+    }
+    let snapshot = restored_state.ok_or_else(|| "Bundle has no state entry".to_string())?;
+    state.restore(snapshot).await?;
+    Ok(report)
+}
@@ -0,0 +1,340 @@
+// agent/src/sys/source_archive.rs
+//
+// 📦 Sources shipped as a gzip'd tarball instead of a git repository
+// (DeployArtifact), uploaded on the request stream or fetched from a URL.
+//
+// The archive is staged whole and hashed as it arrives; nothing is unpacked
+// until it matches the SHA-256 the deploy named. Unpacking runs as root, so it
+// keeps to the release directory: only files, directories and symlinks are
+// taken, no write goes through a symlink or outside the directory, set-id bits
+// and owners are dropped, and the unpacked size is capped. The jail then hands
+// the tree to the app user, exactly as after a clone.
+
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Longest a URL fetch may take.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// An archive being received. Dropping it discards what was written.
+pub struct StagedArchive {
+    staged: tempfile::NamedTempFile,
+    hasher: Sha256,
+    written: u64,
+    max_bytes: u64,
+}
+
+impl StagedArchive {
+    /// Stages an archive of at most `max_bytes`, which also caps what it may
+    /// unpack to.
+    pub fn new(max_bytes: u64) -> Result<Self, String> {
+        let staged = tempfile::Builder::new()
+            .prefix(".kari-source-")
+            .tempfile()
+            .map_err(|e| format!("Failed to stage archive: {}", e))?;
+        Ok(Self {
+            staged,
+            hasher: Sha256::new(),
+            written: 0,
+            max_bytes,
+        })
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        self.count(data.len())?;
+        self.hasher.update(data);
+        self.staged
+            .write_all(data)
+            .map_err(|e| format!("Failed to stage archive: {}", e))
+    }
+
+    fn count(&mut self, len: usize) -> Result<(), String> {
+        self.written += len as u64;
+        // 🛡️ A sender cannot fill the disk past the configured cap
+        if self.written > self.max_bytes {
+            return Err(format!(
+                "Archive is larger than {} MiB",
+                self.max_bytes / 1024 / 1024
+            ));
+        }
+        Ok(())
+    }
+
+    /// Hashes what a download left in the staged file.
+    fn absorb(&mut self) -> Result<(), String> {
+        let mut file =
+            File::open(self.staged.path()).map_err(|e| format!("Failed to read archive: {}", e))?;
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = file
+                .read(&mut buf)
+                .map_err(|e| format!("Failed to read archive: {}", e))?;
+            if n == 0 {
+                return Ok(());
+            }
+            self.count(n)?;
+            self.hasher.update(&buf[..n]);
+        }
+    }
+
+    /// The archive, once it hashes to `sha256`.
+    pub fn finish(self, sha256: &str) -> Result<VerifiedArchive, String> {
+        let actual = format!("{:x}", self.hasher.finalize());
+        if actual != sha256 {
+            return Err(format!(
+                "Archive hashes to sha256:{}, not sha256:{}",
+                actual, sha256
+            ));
+        }
+        Ok(VerifiedArchive {
+            staged: self.staged,
+            size_bytes: self.written,
+            max_bytes: self.max_bytes,
+        })
+    }
+}
+
+/// Fetches an archive over HTTPS with the host's curl. The URL may carry a
+/// token, so it is never repeated in errors.
+pub async fn download(url: &str, max_bytes: u64) -> Result<StagedArchive, String> {
+    let mut staged = StagedArchive::new(max_bytes)?;
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location"])
+        .args([
+            "--proto",
+            "=https",
+            "--proto-redir",
+            "=https",
+            "--max-redirs",
+            "5",
+        ])
+        .arg("--max-time")
+        .arg(DOWNLOAD_TIMEOUT.as_secs().to_string())
+        .arg("--max-filesize")
+        .arg(max_bytes.to_string())
+        .arg("--output")
+        .arg(staged.staged.path())
+        .args(["--url", url])
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run curl: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Download failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    tokio::task::spawn_blocking(move || staged.absorb().map(|_| staged))
+        .await
+        .map_err(|e| format!("Archive task failed: {}", e))?
+}
+
+/// A staged archive that matched its digest.
+pub struct VerifiedArchive {
+    staged: tempfile::NamedTempFile,
+    pub size_bytes: u64,
+    max_bytes: u64,
+}
+
+impl VerifiedArchive {
+    /// Unpacks into `dest` (created if missing), dropping the first
+    /// `strip_components` of every path. Returns the bytes unpacked.
+    pub fn unpack(&self, dest: &Path, strip_components: usize) -> Result<u64, String> {
+        fs::create_dir_all(dest).map_err(|e| format!("Failed to create release: {}", e))?;
+        let root = dest
+            .canonicalize()
+            .map_err(|e| format!("Failed to resolve release: {}", e))?;
+        let file =
+            File::open(self.staged.path()).map_err(|e| format!("Failed to open archive: {}", e))?;
+        let mut archive = tar::Archive::new(GzDecoder::new(file));
+        // Owners and set-id bits are never taken from the archive
+        archive.set_preserve_ownerships(false);
+        archive.set_preserve_permissions(false);
+        archive.set_mask(0o022);
+
+        let mut unpacked = 0u64;
+        let entries = archive.entries().map_err(|e| unreadable(&e))?;
+        for entry in entries {
+            let mut entry = entry.map_err(|e| unreadable(&e))?;
+            let kind = entry.header().entry_type();
+            if kind == tar::EntryType::XGlobalHeader {
+                continue; // pax comment (GitHub archives put the commit here)
+            }
+            let path = entry.path().map_err(|e| unreadable(&e))?.into_owned();
+            if !(kind.is_file() || kind.is_dir() || kind.is_symlink()) {
+                return Err(format!(
+                    "{}: {:?} entries are not accepted",
+                    path.display(),
+                    kind
+                ));
+            }
+            // 🛡️ Zero-Trust: no absolute paths, no `..`
+            if path
+                .components()
+                .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+            {
+                return Err(format!("{}: path leaves the archive", path.display()));
+            }
+            let relative: PathBuf = path
+                .components()
+                .filter(|c| matches!(c, Component::Normal(_)))
+                .skip(strip_components)
+                .collect();
+            if relative.as_os_str().is_empty() {
+                continue;
+            }
+            unpacked = unpacked.saturating_add(entry.size());
+            if unpacked > self.max_bytes {
+                return Err(format!(
+                    "Archive unpacks to more than {} MiB",
+                    self.max_bytes / 1024 / 1024
+                ));
+            }
+            // A symlink may point anywhere, as in a checkout; it is never written through
+            let target = root.join(&relative);
+            if let Some(parent) = target.parent() {
+                ensure_inside(&root, parent)?;
+                fs::create_dir_all(parent).map_err(|e| format!("{}: {}", relative.display(), e))?;
+            }
+            entry
+                .unpack(&target)
+                .map_err(|e| format!("{}: {}", relative.display(), e))?;
+        }
+        Ok(unpacked)
+    }
+}
+
+fn unreadable(e: &io::Error) -> String {
+    format!("Archive is not a readable tar.gz: {}", e)
+}
+
+/// 🛡️ The deepest existing ancestor of `dir`, symlinks resolved, must lie
+/// under `root`; whatever is then created below it does too.
+fn ensure_inside(root: &Path, dir: &Path) -> Result<(), String> {
+    let mut existing = dir;
+    while !existing.exists() {
+        existing = existing
+            .parent()
+            .ok_or_else(|| format!("{}: outside the release", dir.display()))?;
+    }
+    let real = existing
+        .canonicalize()
+        .map_err(|e| format!("{}: {}", dir.display(), e))?;
+    if !real.starts_with(root) {
+        return Err(format!(
+            "{}: path goes through a symlink out of the release",
+            dir.strip_prefix(root).unwrap_or(dir).display()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::os::unix::fs::PermissionsExt;
+
+    enum Entry<'a> {
+        File(&'a str, &'a [u8], u32),
+        Symlink(&'a str, &'a str),
+        Fifo(&'a str),
+    }
+
+    fn tarball(entries: &[Entry<'_>]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::fast()));
+        for entry in entries {
+            let mut header = tar::Header::new_gnu();
+            match *entry {
+                Entry::File(path, data, mode) => {
+                    header.set_size(data.len() as u64);
+                    header.set_mode(mode);
+                    builder.append_data(&mut header, path, data).unwrap();
+                }
+                Entry::Symlink(path, target) => {
+                    header.set_entry_type(tar::EntryType::Symlink);
+                    header.set_size(0);
+                    builder.append_link(&mut header, path, target).unwrap();
+                }
+                Entry::Fifo(path) => {
+                    header.set_entry_type(tar::EntryType::Fifo);
+                    header.set_size(0);
+                    builder.append_data(&mut header, path, io::empty()).unwrap();
+                }
+            }
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn verified(bytes: &[u8], max_bytes: u64) -> VerifiedArchive {
+        let mut staged = StagedArchive::new(max_bytes).unwrap();
+        for chunk in bytes.chunks(7) {
+            staged.write(chunk).unwrap();
+        }
+        staged
+            .finish(&format!("{:x}", Sha256::digest(bytes)))
+            .unwrap()
+    }
+
+    #[test]
+    fn archives_unpack_only_once_they_match_their_digest() {
+        let bytes = tarball(&[
+            Entry::File("shop-main/package.json", b"{}", 0o644),
+            Entry::File("shop-main/bin/start", b"#!/bin/sh\n", 0o4755),
+            Entry::Symlink("shop-main/current", "bin"),
+        ]);
+        let mut staged = StagedArchive::new(1 << 20).unwrap();
+        staged.write(&bytes).unwrap();
+        let err = staged.finish(&"0".repeat(64)).err().unwrap();
+        assert!(err.contains("not sha256:000"), "{}", err);
+
+        let dest = tempfile::tempdir().unwrap();
+        let release = dest.path().join("releases/1");
+        let archive = verified(&bytes, 1 << 20);
+        assert_eq!(archive.size_bytes, bytes.len() as u64);
+        assert_eq!(archive.unpack(&release, 1).unwrap(), 12);
+        assert_eq!(fs::read(release.join("package.json")).unwrap(), b"{}");
+        let mode = fs::metadata(release.join("bin/start"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o7777, 0o755);
+        assert_eq!(
+            fs::read_link(release.join("current")).unwrap(),
+            Path::new("bin")
+        );
+
+        let too_small = StagedArchive::new(8).unwrap().write(&bytes).err().unwrap();
+        assert!(too_small.contains("larger than"), "{}", too_small);
+    }
+
+    #[test]
+    fn nothing_is_written_through_symlinks_or_special_files() {
+        let outside = tempfile::tempdir().unwrap();
+        let escape = outside.path().to_str().unwrap();
+        for entries in [
+            vec![
+                Entry::Symlink("app/out", escape),
+                Entry::File("app/out/passwd", b"x", 0o644),
+            ],
+            vec![Entry::Fifo("app/pipe")],
+        ] {
+            let dest = tempfile::tempdir().unwrap();
+            let archive = verified(&tarball(&entries), 1 << 20);
+            assert!(archive.unpack(dest.path(), 0).is_err());
+        }
+        assert_eq!(fs::read_dir(outside.path()).unwrap().count(), 0);
+
+        let dest = tempfile::tempdir().unwrap();
+        let archive = verified(&tarball(&[Entry::File("big", &[0; 4096], 0o644)]), 4000);
+        let err = archive.unpack(dest.path(), 0).err().unwrap();
+        assert!(err.contains("unpacks to more than"), "{}", err);
+    }
+}
@@ -1,11 +1,16 @@
 use async_trait::async_trait;
+use sha2::{Digest, Sha256};
 use std::fs as std_fs;
 use std::io::Write;
 use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
 use tokio::fs as tokio_fs;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use zeroize::Zeroizing;
 
-use crate::sys::traits::{SslEngine, SslPayload};
+use crate::sys::traits::{CertificateDetails, OcspStaple, SslEngine, SslPayload};
 
 // ==============================================================================
 // 1. Concrete Implementation (Linux Filesystem)
@@ -14,12 +19,119 @@ use crate::sys::traits::{SslEngine, SslPayload};
 pub struct LinuxSslEngine {
     // 🛡️ SLA: Strict Type to prevent path traversal
     ssl_storage_dir: PathBuf,
+    must_staple: bool, // 🩺 Staged keys' CSRs ask for the TLS Feature extension
 }
 
+/// 🔄 A successor key waiting for its certificate (see `stage_next_key`).
+const STAGED_KEY: &str = "privkey.next.pem";
+
 impl LinuxSslEngine {
-    pub fn new(ssl_storage_dir: PathBuf) -> Self {
-        Self { ssl_storage_dir }
+    pub fn new(ssl_storage_dir: PathBuf, must_staple: bool) -> Self {
+        Self {
+            ssl_storage_dir,
+            must_staple,
+        }
+    }
+
+    fn domain_dir(&self, domain_name: &str) -> Result<PathBuf, String> {
+        if domain_name.is_empty() || domain_name.contains("..") || domain_name.contains('/') {
+            return Err("SECURITY VIOLATION: Invalid domain name format".into());
+        }
+        Ok(self.ssl_storage_dir.join(domain_name))
+    }
+}
+
+/// What openssl prints reading `input` with `args`, e.g. the public key (PEM)
+/// of the first certificate of a chain with `x509 -noout -pubkey`.
+async fn openssl_output(args: &[&str], input: &[u8]) -> Result<String, String> {
+    let mut child = Command::new("openssl")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run openssl: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input)
+            .await
+            .map_err(|e| format!("Failed to feed openssl: {}", e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|e| format!("Failed to run openssl: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "openssl {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// openssl's dates, e.g. `Oct 17 08:21:32 2026 GMT`, as Unix seconds.
+fn parse_openssl_time(value: &str) -> Option<i64> {
+    chrono::NaiveDateTime::parse_from_str(value.trim(), "%b %e %H:%M:%S %Y GMT")
+        .ok()
+        .map(|t| t.and_utc().timestamp())
+}
+
+/// The PEM blocks of a chain, leaf first.
+fn pem_blocks(chain: &str) -> Vec<String> {
+    const END: &str = "-----END CERTIFICATE-----";
+    chain
+        .split_inclusive(END)
+        .filter_map(|block| {
+            let start = block.find("-----BEGIN CERTIFICATE-----")?;
+            block
+                .ends_with(END)
+                .then(|| format!("{}\n", &block[start..]))
+        })
+        .collect()
+}
+
+/// What `openssl ocsp` printed for one certificate.
+fn parse_ocsp_output(text: &str) -> OcspStaple {
+    let mut staple = OcspStaple {
+        status: "unavailable".into(),
+        detail: "responder gave no status for the certificate".into(),
+        ..Default::default()
+    };
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(value) = line.strip_prefix("This Update:") {
+            staple.this_update = parse_openssl_time(value);
+        } else if let Some(value) = line.strip_prefix("Next Update:") {
+            staple.next_update = parse_openssl_time(value);
+        } else if let Some(value) = line.strip_prefix("Reason:") {
+            staple.detail = format!("revoked: {}", value.trim());
+        } else if let Some((_, status)) = line.rsplit_once(": ")
+            && matches!(status, "good" | "revoked" | "unknown")
+        {
+            staple.status = status.to_string();
+            staple.detail.clear();
+        }
+    }
+    staple
+}
+
+/// The staged key's public half, read by openssl straight from the file.
+async fn staged_public_key(staged: &Path) -> Result<String, String> {
+    let output = Command::new("openssl")
+        .args(["pkey", "-pubout", "-in"])
+        .arg(staged)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run openssl: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Staged key is unreadable: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
     }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
 }
 
 #[async_trait]
@@ -58,6 +170,25 @@ impl SslEngine for LinuxSslEngine {
             .await
             .map_err(|e| format!("Failed to secure SSL directory permissions: {}", e))?;
 
+        // 🔄 No key sent: the certificate must certify the staged successor
+        let staged = payload
+            .privkey_pem
+            .use_secret(str::is_empty)
+            .then(|| domain_path.join(STAGED_KEY));
+        if let Some(staged) = &staged {
+            if !staged.exists() {
+                return Err("No private key was sent and none is staged".into());
+            }
+            let certified = openssl_output(
+                &["x509", "-noout", "-pubkey"],
+                payload.fullchain_pem.as_bytes(),
+            )
+            .await?;
+            if certified != staged_public_key(staged).await? {
+                return Err("Certificate was not issued for the staged key".into());
+            }
+        }
+
         // 3. 🛡️ Write the Public Certificate (Eliminate TOCTOU via OpenOptions)
         let fullchain_path = domain_path.join("fullchain.pem");
 
@@ -76,6 +207,12 @@ impl SslEngine for LinuxSslEngine {
 
         // 4. Securely Write the Private Key (Zero-Copy + Zero-Race Boundary)
         let privkey_path = domain_path.join("privkey.pem");
+        if let Some(staged) = staged {
+            payload.privkey_pem.destroy();
+            return tokio_fs::rename(&staged, &privkey_path)
+                .await
+                .map_err(|e| format!("Failed to promote the staged key: {}", e));
+        }
 
         // 🚨 CRITICAL SECURITY BOUNDARY 🚨
         // Trade-off: We INTENTIONALLY use synchronous std::fs I/O inside this closure.
@@ -114,4 +251,323 @@ impl SslEngine for LinuxSslEngine {
 
         Ok(())
     }
+
+    async fn certificate_exists(&self, domain_name: &str) -> Result<bool, String> {
+        if domain_name.is_empty() || domain_name.contains("..") || domain_name.contains('/') {
+            return Err("SECURITY VIOLATION: Invalid domain name format".into());
+        }
+        let domain_path = self.ssl_storage_dir.join(domain_name);
+        for file in ["fullchain.pem", "privkey.pem"] {
+            match tokio_fs::metadata(domain_path.join(file)).await {
+                Ok(meta) if meta.len() > 0 => {}
+                Ok(_) => return Ok(false),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+                Err(e) => return Err(format!("Failed to inspect {}: {}", file, e)),
+            }
+        }
+        Ok(true)
+    }
+
+    async fn key_fingerprint(&self, domain_name: &str) -> Result<Option<String>, String> {
+        let privkey_path = self.domain_dir(domain_name)?.join("privkey.pem");
+        match tokio_fs::read(&privkey_path).await {
+            Ok(key) => {
+                let key = Zeroizing::new(key);
+                Ok(Some(format!("{:x}", Sha256::digest(key.as_slice()))))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Failed to read private key: {}", e)),
+        }
+    }
+
+    async fn stage_next_key(
+        &self,
+        domain_name: &str,
+        san_domains: &[String],
+    ) -> Result<String, String> {
+        let domain_path = self.domain_dir(domain_name)?;
+        // 🛡️ Names are joined into one -addext value, where ',' starts another entry
+        let names: Vec<&str> = std::iter::once(domain_name)
+            .chain(
+                san_domains
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|d| *d != domain_name),
+            )
+            .collect();
+        if names.iter().any(|name| {
+            name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+        }) {
+            return Err("SECURITY VIOLATION: Domain contains illegal characters".into());
+        }
+        // 🔏 ACME issues a domain's first certificate through a staged key too
+        if !domain_path.is_dir() {
+            std_fs::create_dir_all(&domain_path)
+                .map_err(|e| format!("Failed to create SSL directory: {}", e))?;
+            std_fs::set_permissions(&domain_path, std_fs::Permissions::from_mode(0o750))
+                .map_err(|e| format!("Failed to secure SSL directory permissions: {}", e))?;
+        }
+
+        // 🛡️ The key file is 0600 before openssl writes a byte into it
+        let staged = domain_path.join(STAGED_KEY);
+        let _ = std_fs::remove_file(&staged);
+        std_fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&staged)
+            .map_err(|e| format!("Failed to create staged key: {}", e))?;
+
+        let output = Command::new("openssl")
+            .args(["req", "-new", "-nodes"])
+            .args(["-newkey", "ec", "-pkeyopt", "ec_paramgen_curve:prime256v1"])
+            .arg("-subj")
+            .arg(format!("/CN={}", domain_name))
+            .arg("-addext")
+            .arg(format!(
+                "subjectAltName={}",
+                names
+                    .iter()
+                    .map(|name| format!("DNS:{}", name))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ))
+            .args(if self.must_staple {
+                &["-addext", "tlsfeature=status_request"][..]
+            } else {
+                &[]
+            })
+            .arg("-keyout")
+            .arg(&staged)
+            .output()
+            .await
+            .map_err(|e| format!("Failed to run openssl: {}", e));
+        match output {
+            Ok(output) if output.status.success() => {
+                Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+            }
+            Ok(output) => {
+                let _ = std_fs::remove_file(&staged);
+                Err(format!(
+                    "openssl failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ))
+            }
+            Err(e) => {
+                let _ = std_fs::remove_file(&staged);
+                Err(e)
+            }
+        }
+    }
+
+    async fn certificate_details(
+        &self,
+        domain_name: &str,
+    ) -> Result<Option<CertificateDetails>, String> {
+        let fullchain_path = self.domain_dir(domain_name)?.join("fullchain.pem");
+        let fullchain = match tokio_fs::read(&fullchain_path).await {
+            Ok(fullchain) => fullchain,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Failed to read certificate: {}", e)),
+        };
+        let dates =
+            openssl_output(&["x509", "-noout", "-enddate", "-ocsp_uri"], &fullchain).await?;
+        let mut details = CertificateDetails::default();
+        for line in dates.lines().map(str::trim) {
+            if let Some(value) = line.strip_prefix("notAfter=") {
+                details.not_after = parse_openssl_time(value).unwrap_or_default();
+            } else if line.starts_with("http://") || line.starts_with("https://") {
+                details.ocsp_url = Some(line.to_string());
+            }
+        }
+        let features =
+            openssl_output(&["x509", "-noout", "-ext", "tlsfeature"], &fullchain).await?;
+        details.must_staple = features.lines().any(|l| l.trim() == "status_request");
+        Ok(Some(details))
+    }
+
+    async fn ocsp_staple(&self, domain_name: &str) -> Result<OcspStaple, String> {
+        let unavailable = |detail: &str| OcspStaple {
+            status: "unavailable".into(),
+            detail: detail.into(),
+            ..Default::default()
+        };
+        let Some(details) = self.certificate_details(domain_name).await? else {
+            return Ok(unavailable("no certificate is installed"));
+        };
+        let Some(url) = details.ocsp_url else {
+            return Ok(unavailable("the certificate names no OCSP responder"));
+        };
+        let fullchain =
+            tokio_fs::read_to_string(self.domain_dir(domain_name)?.join("fullchain.pem"))
+                .await
+                .map_err(|e| format!("Failed to read certificate: {}", e))?;
+        let blocks = pem_blocks(&fullchain);
+        let [leaf, issuer, ..] = blocks.as_slice() else {
+            return Ok(unavailable("the chain has no issuer certificate"));
+        };
+
+        let dir = tempfile::tempdir().map_err(|e| format!("Failed to create temp dir: {}", e))?;
+        let (leaf_path, issuer_path) = (dir.path().join("leaf.pem"), dir.path().join("issuer.pem"));
+        std_fs::write(&leaf_path, leaf).map_err(|e| format!("Failed to stage leaf: {}", e))?;
+        std_fs::write(&issuer_path, issuer)
+            .map_err(|e| format!("Failed to stage issuer: {}", e))?;
+        // The status is reported, not trusted: the proxy verifies what it staples
+        let output = Command::new("openssl")
+            .args([
+                "ocsp",
+                "-no_nonce",
+                "-noverify",
+                "-timeout",
+                "10",
+                "-url",
+                &url,
+            ])
+            .arg("-issuer")
+            .arg(&issuer_path)
+            .arg("-cert")
+            .arg(&leaf_path)
+            .output()
+            .await
+            .map_err(|e| format!("Failed to run openssl: {}", e))?;
+        if !output.status.success() {
+            return Ok(unavailable(&format!(
+                "{}: {}",
+                url,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(parse_ocsp_output(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sys::secrets::ProviderCredential;
+
+    /// Signs `csr` with its own key, as a CA that issued for it would.
+    async fn issue(csr: &str, key: &Path) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        let csr_path = dir.path().join("next.csr");
+        std_fs::write(&csr_path, csr).unwrap();
+        let output = Command::new("openssl")
+            .args(["x509", "-req", "-days", "1", "-in"])
+            .arg(&csr_path)
+            .arg("-signkey")
+            .arg(key)
+            .output()
+            .await
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn ocsp_responses_are_read_per_certificate() {
+        let good = parse_ocsp_output(
+            "Response verify OK\n/tmp/x/leaf.pem: good\n\tThis Update: Oct 15 00:00:00 2026 GMT\n\tNext Update: Oct 22 00:00:00 2026 GMT\n",
+        );
+        assert_eq!(good.status, "good");
+        assert_eq!(good.this_update, Some(1_792_022_400));
+        assert_eq!(good.next_update, Some(1_792_627_200));
+        let revoked = parse_ocsp_output(
+            "leaf.pem: revoked\n\tThis Update: Oct  5 00:00:00 2026 GMT\n\tReason: keyCompromise\n\tRevocation Time: Oct  4 10:00:00 2026 GMT\n",
+        );
+        assert_eq!(revoked.status, "revoked");
+        assert_eq!(revoked.detail, "revoked: keyCompromise");
+        assert_eq!(
+            parse_ocsp_output("Error querying OCSP responder\n").status,
+            "unavailable"
+        );
+
+        let chain = "-----BEGIN CERTIFICATE-----\nAA==\n-----END CERTIFICATE-----\n\
+                     -----BEGIN CERTIFICATE-----\nBB==\n-----END CERTIFICATE-----\n";
+        let blocks = pem_blocks(chain);
+        assert_eq!(blocks.len(), 2);
+        assert!(blocks[1].contains("BB=="));
+    }
+
+    #[tokio::test]
+    async fn staged_keys_go_live_only_with_a_certificate_issued_for_them() {
+        let root = tempfile::tempdir().unwrap();
+        let engine = LinuxSslEngine::new(root.path().to_path_buf(), false);
+        let domain = "shop.example.com";
+        let payload = |fullchain: &str, key: &str| SslPayload {
+            domain_name: domain.into(),
+            fullchain_pem: fullchain.into(),
+            privkey_pem: ProviderCredential::from_string(key.into()),
+        };
+        let (fullchain, key) = crate::sys::selftest::self_signed_certificate(domain)
+            .await
+            .unwrap();
+        let key = key.use_secret(str::to_string);
+        engine
+            .install_certificate(payload(&fullchain, &key))
+            .await
+            .unwrap();
+        let live = engine.key_fingerprint(domain).await.unwrap().unwrap();
+
+        // Nothing staged yet
+        assert!(
+            engine
+                .install_certificate(payload(&fullchain, ""))
+                .await
+                .is_err()
+        );
+
+        let details = engine.certificate_details(domain).await.unwrap().unwrap();
+        assert!(details.not_after > chrono::Utc::now().timestamp());
+        assert!(!details.must_staple);
+        assert_eq!(details.ocsp_url, None);
+        assert_eq!(
+            engine.ocsp_staple(domain).await.unwrap().status,
+            "unavailable"
+        );
+
+        let csr = engine
+            .stage_next_key(domain, &["www.shop.example.com".to_string()])
+            .await
+            .unwrap();
+        let requested = openssl_output(&["req", "-noout", "-text"], csr.as_bytes())
+            .await
+            .unwrap();
+        assert!(requested.contains("DNS:shop.example.com, DNS:www.shop.example.com"));
+        assert!(
+            engine
+                .stage_next_key(domain, &["a.example.com,DNS:evil.test".to_string()])
+                .await
+                .is_err()
+        );
+        assert!(csr.starts_with("-----BEGIN CERTIFICATE REQUEST-----"));
+        let staged = root.path().join(domain).join(STAGED_KEY);
+        assert_eq!(
+            std_fs::metadata(&staged).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+
+        // A certificate for any other key leaves the live key alone
+        assert!(
+            engine
+                .install_certificate(payload(&fullchain, ""))
+                .await
+                .is_err()
+        );
+        assert_eq!(engine.key_fingerprint(domain).await.unwrap().unwrap(), live);
+
+        let reissued = issue(&csr, &staged).await;
+        engine
+            .install_certificate(payload(&reissued, ""))
+            .await
+            .unwrap();
+        assert_ne!(engine.key_fingerprint(domain).await.unwrap().unwrap(), live);
+        assert!(!staged.exists());
+        assert_eq!(
+            std_fs::read_to_string(root.path().join(domain).join("fullchain.pem")).unwrap(),
+            reissued
+        );
+    }
 }
@@ -0,0 +1,454 @@
+// agent/src/sys/state.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Durable records of what the agent manages.
+// The store is a namespaced key/value map persisted as one JSON document,
+// replaced atomically (write temp + rename) on every mutation.
+
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs as std_fs;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use tokio::sync::Mutex;
+
+use crate::sys::systemd::UnitDependency;
+use crate::sys::traits::{JobIntent, StateStore, VhostOptions};
+
+/// Namespace for per-app records, keyed by app_id.
+pub const NS_APPS: &str = "apps";
+/// Namespace for the job timers each app owns (a list of job names), keyed by app_id.
+pub const NS_APP_JOBS: &str = "app_jobs";
+/// Namespace for every scheduled job timer, keyed by job name.
+pub const NS_JOBS: &str = "jobs";
+/// Namespace for deployed releases (ReleaseRecord), keyed by `release_key`.
+pub const NS_RELEASES: &str = "releases";
+/// Namespace for tenant quota groups (TenantQuota), keyed by tenant_id.
+pub const NS_TENANTS: &str = "tenants";
+/// Namespace for applied firewall rules, keyed by `firewall_rule_key`.
+pub const NS_FIREWALL_RULES: &str = "firewall_rules";
+/// Namespace for installed certificate keys (CertKeyRecord), keyed by domain.
+pub const NS_CERT_KEYS: &str = "cert_keys";
+/// Namespace for installed certificates and the domains they cover
+/// (CertificateRecord), keyed by certificate name.
+pub const NS_CERTIFICATES: &str = "certificates";
+
+/// Operator-defined metadata (customer, environment, cost center, ...).
+pub type Labels = BTreeMap<String, String>;
+
+type Namespaces = BTreeMap<String, BTreeMap<String, Value>>;
+
+/// 📦 What the agent provisioned for an app, independent of how it was deployed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AppRecord {
+    pub app_id: String,
+    pub domain_name: String,
+    pub service_name: String,
+    pub app_user: String,
+    pub runtime: String, // "source" | "container"
+    pub image: Option<String>,
+    pub port: Option<u16>,
+    pub memory_limit_mb: u32,
+    pub cpu_limit_percent: u32,
+    #[serde(default)]
+    pub labels: Labels,
+    #[serde(default)]
+    pub tenant_id: Option<String>,
+    /// 🛡️ Builds run with PrivateNetwork=yes; only the fetch phase may reach the network.
+    #[serde(default)]
+    pub offline_build: bool,
+    /// 🔗 Resolved unit dependencies, as written into the app's unit.
+    #[serde(default)]
+    pub dependencies: Vec<UnitDependency>,
+    /// 🔐 Set once InstallCertificate succeeds for the app's domain.
+    #[serde(default)]
+    pub certificate_installed: bool,
+    /// 🚦 Ingress options reapplied whenever the app's vhost is rewritten.
+    #[serde(default)]
+    pub vhost: VhostOptions,
+}
+
+impl AppRecord {
+    /// 🔐 The certificate directory (under `ssl_storage_dir`) the app's vhost
+    /// serves: its own domain's, or a shared certificate listing it as a SAN.
+    pub fn certificate_name(&self) -> &str {
+        self.vhost
+            .tls
+            .as_ref()
+            .and_then(|tls| tls.cert_path.parent()?.file_name()?.to_str())
+            .unwrap_or(&self.domain_name)
+    }
+}
+
+/// ⏰ A scheduled job timer. App-owned jobs carry their app_id and inherit the
+/// app's labels when listed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub job_name: String,
+    pub binary: String,
+    pub args: Vec<String>,
+    pub schedule: String,
+    pub run_as_user: String,
+    pub app_id: Option<String>,
+    #[serde(default)]
+    pub labels: Labels,
+}
+
+impl From<&JobIntent> for JobRecord {
+    fn from(intent: &JobIntent) -> Self {
+        Self {
+            job_name: intent.name.clone(),
+            binary: intent.binary.clone(),
+            args: intent.args.clone(),
+            schedule: intent.schedule.clone(),
+            run_as_user: intent.run_as_user.clone(),
+            app_id: None,
+            labels: Labels::new(),
+        }
+    }
+}
+
+/// 📡 One successfully built release of an app.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReleaseRecord {
+    pub app_id: String,
+    pub domain_name: String,
+    pub release: String,
+    pub created_at: i64, // Unix seconds
+    /// Component count of the release's CycloneDX SBOM, when one was generated.
+    pub sbom_components: Option<u32>,
+    /// Ingress port and app timers to switch to when the release is activated.
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub cron_jobs: Vec<JobRecord>,
+    /// When the release last went live and passed its health gate.
+    #[serde(default)]
+    pub activated_at: Option<i64>,
+    /// HTTP check the release must pass before traffic moves to it.
+    #[serde(default)]
+    pub health_check: Option<HealthCheckRecord>,
+    /// 📦 The OCI image a container release runs; None for source releases.
+    #[serde(default)]
+    pub image: Option<String>,
+}
+
+/// 🩺 DeployRequest.health_check as sent; zeros take the agent's defaults
+/// when the release is activated.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthCheckRecord {
+    pub path: String,
+    pub timeout_secs: u32,
+    pub retries: u32,
+    pub interval_secs: u32,
+}
+
+pub fn release_key(domain_name: &str, release: &str) -> String {
+    format!("{}/{}", domain_name, release)
+}
+
+/// 🛡️ An applied firewall rule. Re-applying the same port/protocol/source
+/// replaces the record, mirroring how the firewall backend treats it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FirewallRuleRecord {
+    pub action: String, // "allow" | "deny" | "reject"
+    pub port: u16,
+    pub protocol: String, // "tcp" | "udp" | "both"
+    pub source_ip: Option<String>,
+    #[serde(default)]
+    pub labels: Labels,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub created_by: Option<String>,
+    #[serde(default)]
+    pub created_at: i64,
+    #[serde(default)]
+    pub log_denied: bool,
+    /// 🐞 Unix seconds a debug port exposure lapses; None for permanent rules.
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+}
+
+impl FirewallRuleRecord {
+    pub fn expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+pub fn firewall_rule_key(port: u16, protocol: &str, source_ip: Option<&str>) -> String {
+    format!("{}/{}/{}", port, protocol, source_ip.unwrap_or("any"))
+}
+
+/// 🔄 The private key serving a domain, for the key rotation policy.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CertKeyRecord {
+    pub domain_name: String,
+    pub key_fingerprint: String,
+    pub key_created_at: i64, // Unix seconds; first install of this key
+    #[serde(default)]
+    pub rotation_opt_out: bool,
+    /// Set once a successor key is staged and its CSR sent out.
+    #[serde(default)]
+    pub rotation_requested_at: Option<i64>,
+}
+
+/// 🔐 A certificate stored once under `ssl_storage_dir/<name>` and served for
+/// every domain it covers. The domains are its references: the files go when
+/// the last one is removed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CertificateRecord {
+    pub name: String,         // Primary domain, as installed
+    pub domains: Vec<String>, // Primary domain first, then the SANs
+}
+
+/// True when every selector pair is present in `labels` (an empty selector matches all).
+pub fn matches_labels(labels: &Labels, selector: &Labels) -> bool {
+    selector.iter().all(|(k, v)| labels.get(k) == Some(v))
+}
+
+pub struct JsonStateStore {
+    path: PathBuf,
+    data: Mutex<Namespaces>,
+}
+
+impl JsonStateStore {
+    /// Loads the existing state file, or starts empty if none exists yet.
+    /// 🛡️ SLA: A corrupt state file is a hard boot failure, never silently discarded.
+    pub fn open(state_dir: PathBuf) -> Result<Self, String> {
+        std_fs::create_dir_all(&state_dir)
+            .map_err(|e| format!("Failed to create state directory: {}", e))?;
+        let path = state_dir.join("state.json");
+
+        let data = match std_fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| format!("State file {:?} is corrupt: {}", path, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Namespaces::new(),
+            Err(e) => return Err(format!("Failed to read state file: {}", e)),
+        };
+
+        Ok(Self {
+            path,
+            data: Mutex::new(data),
+        })
+    }
+
+    fn persist(&self, data: &Namespaces) -> Result<(), String> {
+        let tmp_path = self.path.with_extension("json.tmp");
+        let serialized = serde_json::to_vec_pretty(data)
+            .map_err(|e| format!("Failed to serialize state: {}", e))?;
+
+        let mut file = std_fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp_path)
+            .map_err(|e| format!("Failed to open state file: {}", e))?;
+        file.write_all(&serialized)
+            .map_err(|e| format!("Failed to write state file: {}", e))?;
+        file.sync_all()
+            .map_err(|e| format!("Failed to sync state file: {}", e))?;
+
+        std_fs::rename(&tmp_path, &self.path)
+            .map_err(|e| format!("Failed to commit state file: {}", e))
+    }
+}
+
+#[async_trait]
+impl StateStore for JsonStateStore {
+    async fn put(&self, namespace: &str, key: &str, value: Value) -> Result<(), String> {
+        let mut data = self.data.lock().await;
+        data.entry(namespace.to_string())
+            .or_default()
+            .insert(key.to_string(), value);
+        self.persist(&data)
+    }
+
+    async fn get(&self, namespace: &str, key: &str) -> Result<Option<Value>, String> {
+        let data = self.data.lock().await;
+        Ok(data.get(namespace).and_then(|ns| ns.get(key)).cloned())
+    }
+
+    async fn list(&self, namespace: &str) -> Result<Vec<(String, Value)>, String> {
+        let data = self.data.lock().await;
+        Ok(data
+            .get(namespace)
+            .map(|ns| ns.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+            .unwrap_or_default())
+    }
+
+    async fn delete(&self, namespace: &str, key: &str) -> Result<(), String> {
+        let mut data = self.data.lock().await;
+        let removed = data
+            .get_mut(namespace)
+            .and_then(|ns| ns.remove(key))
+            .is_some();
+        if removed {
+            self.persist(&data)?;
+        }
+        Ok(())
+    }
+
+    async fn snapshot(&self) -> Result<Value, String> {
+        let data = self.data.lock().await;
+        serde_json::to_value(&*data).map_err(|e| format!("Failed to snapshot state: {}", e))
+    }
+
+    async fn restore(&self, snapshot: Value) -> Result<(), String> {
+        let restored: Namespaces = serde_json::from_value(snapshot)
+            .map_err(|e| format!("Snapshot is not a valid state document: {}", e))?;
+        let mut data = self.data.lock().await;
+        self.persist(&restored)?;
+        *data = restored;
+        Ok(())
+    }
+}
+
+// ==============================================================================
+// Typed helpers (generic methods cannot live on the object-safe trait)
+// ==============================================================================
+
+pub async fn put_record<T: Serialize + Sync>(
+    store: &dyn StateStore,
+    namespace: &str,
+    key: &str,
+    record: &T,
+) -> Result<(), String> {
+    let value = serde_json::to_value(record)
+        .map_err(|e| format!("Failed to encode {} record: {}", namespace, e))?;
+    store.put(namespace, key, value).await
+}
+
+pub async fn get_record<T: DeserializeOwned>(
+    store: &dyn StateStore,
+    namespace: &str,
+    key: &str,
+) -> Result<Option<T>, String> {
+    match store.get(namespace, key).await? {
+        Some(value) => serde_json::from_value(value)
+            .map(Some)
+            .map_err(|e| format!("Corrupt {} record '{}': {}", namespace, key, e)),
+        None => Ok(None),
+    }
+}
+
+pub async fn list_records<T: DeserializeOwned>(
+    store: &dyn StateStore,
+    namespace: &str,
+) -> Result<Vec<T>, String> {
+    store
+        .list(namespace)
+        .await?
+        .into_iter()
+        .map(|(key, value)| {
+            serde_json::from_value(value)
+                .map_err(|e| format!("Corrupt {} record '{}': {}", namespace, key, e))
+        })
+        .collect()
+}
+
+// ==============================================================================
+// Unit name ownership
+// ==============================================================================
+//
+// 🛡️ App units are `kari-{domain}` and job units `kari-job-{name}`, so an app on
+// the domain `job-nginx` and a job named `nginx` would share one unit file, and
+// app cron entries (`{app_id}-{entry}`) share the job namespace with standalone
+// jobs. Both files carry kari's marker, so only the records can tell them apart.
+
+/// Who already holds the unit for job `job_name`, if that is not `owner_app`
+/// (None: a standalone job). Rescheduling one's own job is not a conflict.
+pub async fn job_unit_holder(
+    store: &dyn StateStore,
+    job_name: &str,
+    owner_app: Option<&str>,
+) -> Result<Option<String>, String> {
+    let job: Option<JobRecord> = get_record(store, NS_JOBS, job_name).await?;
+    if let Some(job) = job.filter(|j| j.app_id.as_deref() != owner_app) {
+        return Ok(Some(match job.app_id {
+            Some(app_id) => format!("a cron entry of app '{}'", app_id),
+            None => format!("standalone job '{}'", job_name),
+        }));
+    }
+    let service_name = format!("kari-job-{}", job_name);
+    Ok(list_records::<AppRecord>(store, NS_APPS)
+        .await?
+        .into_iter()
+        .find(|app| app.service_name == service_name)
+        .map(|app| format!("the service of app '{}'", app.app_id)))
+}
+
+/// Which job already holds `service_name` (an app's `kari-{domain}` unit), if any.
+pub async fn app_unit_holder(
+    store: &dyn StateStore,
+    service_name: &str,
+) -> Result<Option<String>, String> {
+    let Some(job_name) = service_name.strip_prefix("kari-job-") else {
+        return Ok(None);
+    };
+    Ok(get_record::<JobRecord>(store, NS_JOBS, job_name)
+        .await?
+        .map(|_| format!("job '{}'", job_name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn label_selector_requires_every_pair() {
+        let labels: Labels = [("env", "prod"), ("customer", "acme")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let select = |pairs: &[(&str, &str)]| -> Labels {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        assert!(matches_labels(&labels, &Labels::new()));
+        assert!(matches_labels(&labels, &select(&[("env", "prod")])));
+        assert!(!matches_labels(
+            &labels,
+            &select(&[("env", "prod"), ("customer", "globex")])
+        ));
+        assert!(!matches_labels(&labels, &select(&[("team", "core")])));
+    }
+
+    #[tokio::test]
+    async fn records_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let record = AppRecord {
+            app_id: "app1".into(),
+            runtime: "container".into(),
+            image: Some("docker.io/library/nginx:1.25".into()),
+            ..Default::default()
+        };
+
+        let store = JsonStateStore::open(dir.path().to_path_buf()).unwrap();
+        put_record(&store, NS_APPS, "app1", &record).await.unwrap();
+        drop(store);
+
+        let store = JsonStateStore::open(dir.path().to_path_buf()).unwrap();
+        let apps: Vec<AppRecord> = list_records(&store, NS_APPS).await.unwrap();
+        assert_eq!(apps.len(), 1);
+        assert_eq!(
+            apps[0].image.as_deref(),
+            Some("docker.io/library/nginx:1.25")
+        );
+
+        store.delete(NS_APPS, "app1").await.unwrap();
+        assert!(
+            list_records::<AppRecord>(&store, NS_APPS)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -1,19 +1,279 @@
+use crate::sys::executor::{CommandSpec, Executor, SystemExecutor};
 use async_trait::async_trait;
-use std::collections::HashMap;
-use std::os::unix::fs::PermissionsExt;
-use std::path::PathBuf;
-use tokio::fs;
-use tokio::process::Command;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use zeroize::Zeroizing;
 
 // 🛡️ SLA: Domain Intent mapped to Rust Execution
 pub struct ServiceConfig {
     pub service_name: String,
     pub username: String,
     pub working_directory: PathBuf, // 🛡️ SLA: Strict Type
-    pub start_command: String,
+    pub start_argv: Vec<String>,
     pub env_vars: HashMap<String, String>,
     pub memory_limit_mb: i32,
     pub cpu_limit_percent: i32,
+    // 📦 Container runtime only: OCI image and the loopback port it publishes
+    pub image: Option<String>,
+    pub port: Option<u16>,
+    // 🏢 Tenant quota group (e.g. kari-tenant-acme.slice); None runs in system.slice
+    pub slice: Option<String>,
+    // 🔗 Units this one is ordered after and bound to
+    pub dependencies: Vec<UnitDependency>,
+    // 🔌 Crash-loop threshold after which systemd stops restarting the unit
+    pub start_limit: StartLimit,
+}
+
+/// More than `burst` starts within `interval_secs` and systemd gives up on the
+/// unit (`Result=start-limit-hit`) instead of restarting it forever.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StartLimit {
+    pub burst: u32,
+    pub interval_secs: u64,
+}
+
+impl Default for StartLimit {
+    fn default() -> Self {
+        Self {
+            burst: 5,
+            interval_secs: 300,
+        }
+    }
+}
+
+/// `[Unit]` lines for the crash-loop threshold.
+pub(crate) fn render_start_limit_lines(limit: StartLimit) -> String {
+    format!(
+        "StartLimitIntervalSec={}\nStartLimitBurst={}\n",
+        limit.interval_secs, limit.burst
+    )
+}
+
+/// How a unit is tied to a dependency. Both kinds also order it `After=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyKind {
+    Requires,
+    BindsTo,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnitDependency {
+    pub unit: String, // Full unit name, e.g. "postgresql.service"
+    pub kind: DependencyKind,
+}
+
+/// 🛡️ Secure Environment File Generation (Strict POSIX Validation)
+/// Shared by every unit generator so the escaping rules cannot drift apart.
+/// systemd expands no specifiers in an EnvironmentFile=, and inside double
+/// quotes only `\`, `"`, `$` and `` ` `` are escapes, so values arrive byte for byte.
+pub(crate) fn render_env_file(env_vars: &HashMap<String, String>) -> Zeroizing<String> {
+    let mut out = Zeroizing::new(String::new());
+    let sorted: BTreeMap<&String, &String> = env_vars.iter().collect();
+    for (k, v) in sorted {
+        // Keys MUST be strictly alphanumeric and underscores.
+        // This prevents line injection via malicious keys.
+        if !is_valid_env_key(k) {
+            tracing::warn!("Dropping invalid environment variable key: {}", k);
+            continue;
+        }
+        out.push_str(k);
+        out.push_str("=\"");
+        for c in v.chars() {
+            if matches!(c, '\\' | '"' | '$' | '`') {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+        out.push_str("\"\n");
+    }
+    out
+}
+
+/// Doubles `%` so systemd does not read it as a specifier in a unit line.
+pub(crate) fn escape_specifiers(value: &str) -> String {
+    value.replace('%', "%%")
+}
+
+/// `Slice=` line placing the unit in its tenant's quota group, if it has one.
+pub(crate) fn render_slice_line(slice: Option<&str>) -> String {
+    slice.map(|s| format!("Slice={}\n", s)).unwrap_or_default()
+}
+
+/// `After=` plus `Requires=`/`BindsTo=` lines for the unit's dependencies.
+/// 🛡️ Names are re-checked here: a newline in one would inject a directive.
+pub(crate) fn render_dependency_lines(dependencies: &[UnitDependency]) -> Result<String, String> {
+    let mut lines = String::new();
+    for dep in dependencies {
+        if !is_valid_unit_name(&dep.unit) {
+            return Err(format!(
+                "SECURITY VIOLATION: Invalid dependency unit name: '{}'",
+                dep.unit
+            ));
+        }
+        let directive = match dep.kind {
+            DependencyKind::Requires => "Requires",
+            DependencyKind::BindsTo => "BindsTo",
+        };
+        lines.push_str(&format!("After={}\n{}={}\n", dep.unit, directive, dep.unit));
+    }
+    Ok(lines)
+}
+
+/// 🛡️ Zero-Trust: Renders argv as an `ExecStart=` command line. Every word is
+/// double-quoted, with `\` and `"` escaped and `%`/`$` doubled so systemd never
+/// expands specifiers or variables. Control characters (newlines would start a
+/// new directive) are refused outright, and so is an executable starting with
+/// one of systemd's prefix characters: `+/bin/sh` would run with full privileges.
+pub(crate) fn quote_exec_argv(argv: &[String]) -> Result<String, String> {
+    let Some(exe) = argv.first() else {
+        return Err("ExecStart requires a non-empty argv".into());
+    };
+    if !exe.starts_with(|c: char| c == '/' || c.is_ascii_alphanumeric()) {
+        return Err(format!(
+            "SECURITY VIOLATION: Executable must be a path or plain name: '{}'",
+            exe
+        ));
+    }
+    let mut words = Vec::with_capacity(argv.len());
+    for arg in argv {
+        if arg.chars().any(|c| c.is_control()) {
+            return Err("SECURITY VIOLATION: Control character in argv".into());
+        }
+        let escaped = arg
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('%', "%%")
+            .replace('$', "$$");
+        words.push(format!("\"{}\"", escaped));
+    }
+    Ok(words.join(" "))
+}
+
+/// A concrete (non-template) unit name with a type suffix kari may depend on.
+pub(crate) fn is_valid_unit_name(name: &str) -> bool {
+    let Some((stem, suffix)) = name.rsplit_once('.') else {
+        return false;
+    };
+    name.len() <= 255
+        && matches!(suffix, "service" | "socket" | "target" | "mount")
+        && !stem.is_empty()
+        && !stem.starts_with(['-', '.'])
+        && stem
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '@' | ':'))
+        && !stem.ends_with('@')
+}
+
+/// `systemctl is-enabled`: exit status 0 means enabled (including `static`
+/// and `alias`); disabled, masked and missing units all exit non-zero.
+pub(crate) async fn is_unit_enabled(executor: &dyn Executor, unit: &str) -> Result<bool, String> {
+    let output = executor
+        .run(&CommandSpec::privileged("systemctl").args(["is-enabled", "--quiet", unit]))
+        .await
+        .map_err(|e| format!("SLA Failure: systemctl execution error: {}", e))?;
+    Ok(output.success())
+}
+
+/// `systemctl is-active`: exit status 0 only while the unit is running.
+pub(crate) async fn is_unit_active(executor: &dyn Executor, unit: &str) -> Result<bool, String> {
+    let output = executor
+        .run(&CommandSpec::privileged("systemctl").args(["is-active", "--quiet", unit]))
+        .await
+        .map_err(|e| format!("SLA Failure: systemctl execution error: {}", e))?;
+    Ok(output.success())
+}
+
+/// `systemctl is-failed`: exit status 0 only while the unit's last run failed.
+pub(crate) async fn is_unit_failed(executor: &dyn Executor, unit: &str) -> Result<bool, String> {
+    let output = executor
+        .run(&CommandSpec::privileged("systemctl").args(["is-failed", "--quiet", unit]))
+        .await
+        .map_err(|e| format!("SLA Failure: systemctl execution error: {}", e))?;
+    Ok(output.success())
+}
+
+/// Whether systemd stopped restarting the unit because it hit its start limit.
+pub(crate) async fn unit_start_limit_hit(
+    executor: &dyn Executor,
+    unit: &str,
+) -> Result<bool, String> {
+    let output = executor
+        .run(&CommandSpec::privileged("systemctl").args([
+            "show",
+            "--property=Result",
+            "--value",
+            unit,
+        ]))
+        .await
+        .map_err(|e| format!("SLA Failure: systemctl execution error: {}", e))?;
+    if !output.success() {
+        return Err(format!("SLA Failure: systemctl show {} failed", unit));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim() == "start-limit-hit")
+}
+
+/// Every unit kari writes (apps, containers, job timers, tenant slices) carries a
+/// `Description=Kari …` line; a unit without one belongs to someone else.
+pub(crate) fn is_kari_unit(content: &str) -> bool {
+    content
+        .lines()
+        .any(|line| line.starts_with("Description=Kari "))
+}
+
+/// 🛡️ Zero-Trust: Refuses to write `path` unless any unit already there is
+/// kari's own. A file without kari's marker is an operator's unit; with no file,
+/// a unit systemd already loads from elsewhere (a vendor unit under
+/// /usr/lib/systemd/system) would be silently shadowed by ours. Both are
+/// reported as `Conflict:` errors.
+pub(crate) async fn ensure_unit_not_foreign(
+    executor: &dyn Executor,
+    path: &Path,
+) -> Result<(), String> {
+    let unit = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| format!("Invalid unit path: {}", path.display()))?;
+
+    match executor.read_file(path).await {
+        Ok(Some(content)) if is_kari_unit(&content) => return Ok(()),
+        Ok(Some(_)) => {
+            return Err(format!(
+                "Conflict: {} already exists and is not managed by Kari",
+                unit
+            ));
+        }
+        Ok(None) => {}
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+
+    let output = executor
+        .run(&CommandSpec::privileged("systemctl").args([
+            "show",
+            "--property=FragmentPath",
+            "--value",
+            unit,
+        ]))
+        .await
+        .map_err(|e| format!("SLA Failure: systemctl execution error: {}", e))?;
+    if !output.success() {
+        return Err(format!("SLA Failure: systemctl show {} failed", unit));
+    }
+    let fragment = String::from_utf8_lossy(&output.stdout);
+    let fragment = fragment.trim();
+    if !fragment.is_empty() && Path::new(fragment) != path {
+        return Err(format!(
+            "Conflict: {} is already defined by {}",
+            unit, fragment
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn is_valid_env_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
 #[async_trait]
@@ -25,19 +285,39 @@ pub trait ServiceManager: Send + Sync {
     async fn start(&self, service_name: &str) -> Result<(), String>;
     async fn stop(&self, service_name: &str) -> Result<(), String>;
     async fn restart(&self, service_name: &str) -> Result<(), String>;
+    /// Whether the unit will be started at boot.
+    async fn is_enabled(&self, service_name: &str) -> Result<bool, String>;
+    /// Whether the unit is running right now.
+    async fn is_active(&self, service_name: &str) -> Result<bool, String>;
+    /// Whether systemd gave up restarting the unit after a crash loop.
+    async fn start_limit_hit(&self, service_name: &str) -> Result<bool, String>;
+    /// Clears the unit's failed state and start counter so it may start again.
+    async fn reset_failed(&self, service_name: &str) -> Result<(), String>;
 }
 
 pub struct LinuxSystemdManager {
     systemd_dir: PathBuf,
+    executor: Arc<dyn Executor>,
 }
 
 impl LinuxSystemdManager {
     pub fn new(systemd_dir: PathBuf) -> Self {
-        Self { systemd_dir }
+        Self::with_executor(systemd_dir, Arc::new(SystemExecutor))
+    }
+
+    pub fn with_executor(systemd_dir: PathBuf, executor: Arc<dyn Executor>) -> Self {
+        Self {
+            systemd_dir,
+            executor,
+        }
+    }
+
+    pub(crate) fn executor(&self) -> &dyn Executor {
+        self.executor.as_ref()
     }
 
     /// 🛡️ Zero-Trust: Safely joins paths to prevent unit file hijacking
-    fn get_unit_path(&self, service_name: &str) -> Result<PathBuf, String> {
+    pub(crate) fn get_unit_path(&self, service_name: &str) -> Result<PathBuf, String> {
         // Prevent path traversal attacks (e.g. "../../../etc/shadow")
         if service_name.contains("..") || service_name.contains('/') {
             return Err("SECURITY VIOLATION: Path traversal in service name".into());
@@ -46,14 +326,35 @@ impl LinuxSystemdManager {
         Ok(self.systemd_dir.join(format!("{}.service", service_name)))
     }
 
+    /// The unit's EnvironmentFile=, beside it. Unit files are world-readable
+    /// (and `systemctl show` prints their Environment= lines to anyone), while
+    /// env values may be resolved secrets, so they only ever live here.
+    pub(crate) fn get_env_path(&self, service_name: &str) -> Result<PathBuf, String> {
+        Ok(self.get_unit_path(service_name)?.with_extension("env"))
+    }
+
+    /// 🛡️ Writes the unit's env file, root-owned and 0600, and returns the
+    /// `EnvironmentFile=` line that loads it.
+    pub(crate) async fn write_env_file(&self, config: &ServiceConfig) -> Result<String, String> {
+        let path = self.get_env_path(&config.service_name)?;
+        let content = render_env_file(&config.env_vars);
+        // Created empty first: the values never sit in a file that is not yet 0600
+        self.executor.write_file(&path, "", 0o600).await?;
+        self.executor.write_file(&path, &content, 0o600).await?;
+        Ok(format!(
+            "EnvironmentFile={}\n",
+            escape_specifiers(&path.to_string_lossy())
+        ))
+    }
+
     async fn execute_systemctl(&self, args: &[&str]) -> Result<(), String> {
-        let output = Command::new("systemctl")
-            .args(args)
-            .output()
+        let output = self
+            .executor
+            .run(&CommandSpec::privileged("systemctl").args(args.iter().copied()))
             .await
             .map_err(|e| format!("SLA Failure: systemctl execution error: {}", e))?;
 
-        if !output.status.success() {
+        if !output.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
             return Err(format!("systemctl {} failed: {}", args[0], stderr));
         }
@@ -65,27 +366,18 @@ impl LinuxSystemdManager {
 impl ServiceManager for LinuxSystemdManager {
     async fn write_unit_file(&self, config: &ServiceConfig) -> Result<(), String> {
         let path = self.get_unit_path(&config.service_name)?;
+        ensure_unit_not_foreign(self.executor(), &path).await?;
 
-        // 1. 🛡️ Secure Environment Block Generation (Strict POSIX Validation)
-        let mut env_block = String::new();
-        for (k, v) in &config.env_vars {
-            // Keys MUST be strictly alphanumeric and underscores.
-            // This prevents systemd directive injection via malicious keys.
-            if !k.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
-                tracing::warn!("Dropping invalid environment variable key: {}", k);
-                continue;
-            }
-
-            // Values: Escape double quotes and backslashes for safe systemd parsing
-            let safe_v = v.replace('\\', "\\\\").replace('"', "\\\"");
-            env_block.push_str(&format!("Environment=\"{}={}\"\n", k, safe_v));
-        }
+        // 1. 🛡️ Env values go to the 0600 env file, never into the unit
+        let env_block = self.write_env_file(config).await?;
+        let exec_start = quote_exec_argv(&config.start_argv)?;
+        let dependency_lines = render_dependency_lines(&config.dependencies)?;
 
         let unit_content = format!(
             r#"[Unit]
 Description=Kari Managed App: {service_name}
 After=network.target
-
+{start_limit_lines}{dependency_lines}
 [Service]
 Type=simple
 User={username}
@@ -97,7 +389,7 @@ Restart=always
 RestartSec=5
 
 # --- ⚖️ Dynamic Resource Jailing ---
-CPUAccounting=true
+{slice_line}CPUAccounting=true
 CPUQuota={cpu_limit}%
 MemoryAccounting=true
 MemoryMax={mem_limit}M
@@ -124,34 +416,26 @@ WantedBy=multi-user.target
             service_name = config.service_name,
             username = config.username,
             workdir = config.working_directory.to_string_lossy(),
-            exec_start = config.start_command, // Trusted via upstream validation
+            exec_start = exec_start,
+            start_limit_lines = render_start_limit_lines(config.start_limit),
+            dependency_lines = dependency_lines,
             env_block = env_block,
+            slice_line = render_slice_line(config.slice.as_deref()),
             cpu_limit = config.cpu_limit_percent,
             mem_limit = config.memory_limit_mb
         );
 
-        // Write the file to disk
-        fs::write(&path, unit_content)
-            .await
-            .map_err(|e| e.to_string())?;
-
-        // 2. 🛡️ Ensure standard 644 permissions (rw-r--r--)
-        let mut perms = fs::metadata(&path)
-            .await
-            .map_err(|e| e.to_string())?
-            .permissions();
-        perms.set_mode(0o644);
-        fs::set_permissions(&path, perms)
-            .await
-            .map_err(|e| e.to_string())?;
-
-        Ok(())
+        // 2. 🛡️ Written with standard 644 permissions (rw-r--r--)
+        self.executor.write_file(&path, &unit_content, 0o644).await
     }
 
     async fn remove_unit_file(&self, service_name: &str) -> Result<(), String> {
-        let path = self.get_unit_path(service_name)?;
-        if path.exists() {
-            fs::remove_file(&path)
+        for path in [
+            self.get_unit_path(service_name)?,
+            self.get_env_path(service_name)?,
+        ] {
+            self.executor
+                .remove_file(&path)
                 .await
                 .map_err(|e| format!("Cleanup failed: {}", e))?;
         }
@@ -178,4 +462,194 @@ WantedBy=multi-user.target
     async fn restart(&self, service_name: &str) -> Result<(), String> {
         self.execute_systemctl(&["restart", service_name]).await
     }
+
+    async fn is_enabled(&self, service_name: &str) -> Result<bool, String> {
+        is_unit_enabled(self.executor(), service_name).await
+    }
+
+    async fn is_active(&self, service_name: &str) -> Result<bool, String> {
+        is_unit_active(self.executor(), service_name).await
+    }
+
+    async fn start_limit_hit(&self, service_name: &str) -> Result<bool, String> {
+        unit_start_limit_hit(self.executor(), service_name).await
+    }
+
+    async fn reset_failed(&self, service_name: &str) -> Result<(), String> {
+        self.execute_systemctl(&["reset-failed", service_name])
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::FakeExecutor;
+
+    #[tokio::test]
+    async fn systemctl_exit_codes_become_answers_and_errors() {
+        let executor = Arc::new(FakeExecutor::default());
+        let systemd =
+            LinuxSystemdManager::with_executor(PathBuf::from("/unused"), executor.clone());
+
+        assert!(systemd.is_active("kari-shop").await.unwrap());
+        executor.answer(&["systemctl", "is-active"], 3, "", "");
+        assert!(!systemd.is_active("kari-shop").await.unwrap());
+
+        executor.answer(&["systemctl", "show"], 0, "start-limit-hit\n", "");
+        assert!(systemd.start_limit_hit("kari-shop").await.unwrap());
+
+        executor.answer(
+            &["systemctl", "restart"],
+            1,
+            "",
+            "Unit kari-shop.service not found.",
+        );
+        let err = systemd.restart("kari-shop").await.unwrap_err();
+        assert_eq!(
+            err,
+            "systemctl restart failed: Unit kari-shop.service not found."
+        );
+        assert_eq!(
+            executor.argvs().last().unwrap(),
+            &["systemctl", "restart", "kari-shop"]
+        );
+
+        executor.missing(&["systemctl"]);
+        let err = systemd.reload_daemon().await.unwrap_err();
+        assert!(
+            err.starts_with("SLA Failure: systemctl execution error"),
+            "{}",
+            err
+        );
+    }
+
+    fn argv(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn exec_argv_is_quoted_and_prefixes_refused() {
+        assert_eq!(
+            quote_exec_argv(&argv(&[
+                "/usr/bin/node",
+                "server.js",
+                "--name=a \"b\" $HOME 50%"
+            ]))
+            .unwrap(),
+            r#""/usr/bin/node" "server.js" "--name=a \"b\" $$HOME 50%%""#
+        );
+        assert!(quote_exec_argv(&argv(&["npm", "start"])).is_ok());
+        assert!(quote_exec_argv(&[]).is_err());
+        assert!(quote_exec_argv(&argv(&["+/bin/sh"])).is_err());
+        assert!(quote_exec_argv(&argv(&["-/bin/true"])).is_err());
+        assert!(quote_exec_argv(&argv(&["/bin/app", "x\nUser=root"])).is_err());
+    }
+
+    #[test]
+    fn dependency_lines_order_and_bind() {
+        let deps = vec![
+            UnitDependency {
+                unit: "postgresql.service".into(),
+                kind: DependencyKind::Requires,
+            },
+            UnitDependency {
+                unit: "kari-cache.example.com.service".into(),
+                kind: DependencyKind::BindsTo,
+            },
+        ];
+        assert_eq!(
+            render_dependency_lines(&deps).unwrap(),
+            "After=postgresql.service\nRequires=postgresql.service\n\
+             After=kari-cache.example.com.service\nBindsTo=kari-cache.example.com.service\n"
+        );
+        assert_eq!(render_dependency_lines(&[]).unwrap(), "");
+
+        for bad in [
+            "postgresql",
+            "redis.service\nUser=root",
+            "getty@.service",
+            "-.mount",
+            "evil.timer",
+            "a b.service",
+        ] {
+            let dep = UnitDependency {
+                unit: bad.into(),
+                kind: DependencyKind::Requires,
+            };
+            assert!(render_dependency_lines(&[dep]).is_err(), "{:?}", bad);
+        }
+        assert!(is_valid_unit_name("postgresql@16-main.service"));
+    }
+
+    #[tokio::test]
+    async fn operator_units_are_never_overwritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kari-job-nginx.service");
+
+        std::fs::write(&path, "[Unit]\nDescription=Kari Scheduled Job: nginx\n").unwrap();
+        assert!(
+            ensure_unit_not_foreign(&SystemExecutor, &path)
+                .await
+                .is_ok()
+        );
+
+        std::fs::write(&path, "[Unit]\nDescription=nginx, hand-rolled by ops\n").unwrap();
+        let err = ensure_unit_not_foreign(&SystemExecutor, &path)
+            .await
+            .unwrap_err();
+        assert!(err.starts_with("Conflict:"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn env_values_live_in_a_private_env_file_not_the_unit() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let systemd = LinuxSystemdManager::with_executor(
+            dir.path().to_path_buf(),
+            Arc::new(FakeExecutor::default()),
+        );
+        let config = ServiceConfig {
+            service_name: "kari-shop.example.com".into(),
+            username: "shop".into(),
+            working_directory: PathBuf::from("/var/www/shop.example.com"),
+            start_argv: vec!["/usr/bin/node".into(), "server.js".into()],
+            env_vars: [
+                ("API_TOKEN".to_string(), "s3cr%t\"$HOME`x\\".to_string()),
+                ("PORT".to_string(), "3000".to_string()),
+            ]
+            .into(),
+            memory_limit_mb: 64,
+            cpu_limit_percent: 50,
+            image: None,
+            port: None,
+            slice: None,
+            dependencies: Vec::new(),
+            start_limit: StartLimit::default(),
+        };
+        systemd.write_unit_file(&config).await.unwrap();
+
+        let unit =
+            std::fs::read_to_string(dir.path().join("kari-shop.example.com.service")).unwrap();
+        let env_path = dir.path().join("kari-shop.example.com.env");
+        assert!(!unit.contains("s3cr"), "{}", unit);
+        assert!(!unit.contains("Environment=\"PORT"), "{}", unit);
+        assert!(unit.contains(&format!("EnvironmentFile={}\n", env_path.display())));
+
+        let env = std::fs::read_to_string(&env_path).unwrap();
+        assert_eq!(
+            env,
+            "API_TOKEN=\"s3cr%t\\\"\\$HOME\\`x\\\\\"\nPORT=\"3000\"\n"
+        );
+        let mode = std::fs::metadata(&env_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        systemd
+            .remove_unit_file("kari-shop.example.com")
+            .await
+            .unwrap();
+        assert!(!env_path.exists());
+        assert_eq!(escape_specifiers("/srv/100%/app"), "/srv/100%%/app");
+    }
 }
@@ -0,0 +1,414 @@
+// agent/src/sys/tamper.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Notices when another actor (a config
+// management run, a hand edit) deletes or rewrites a file kari owns.
+//
+// Changes in the systemd, proxy and certificate directories are collected,
+// left to settle (kari's own writes update the state store straight after the
+// file), and then checked against the state store. Only files a record says
+// kari owns are judged:
+//   - units (`kari-*`) must exist and still carry kari's Description marker;
+//   - vhosts must match what `create_vhost` would write from the app record;
+//   - installed certificates must still be on disk.
+// With self-heal on, job timers and vhosts are rewritten from their records.
+// App units and certificates cannot be rebuilt from state and are only reported.
+
+use async_trait::async_trait;
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{info, warn};
+
+use crate::sys::state::{AppRecord, JobRecord, NS_APPS, NS_JOBS, get_record, list_records};
+use crate::sys::systemd::is_kari_unit;
+use crate::sys::traits::{
+    ConfigChangeSource, JobIntent, JobScheduler, ProxyManager, SslEngine, StateStore,
+};
+
+/// The directories kari writes into and the watch covers.
+#[derive(Debug, Clone)]
+pub struct WatchedDirs {
+    pub systemd_dir: PathBuf,
+    pub proxy_conf_dir: PathBuf,
+    pub ssl_storage_dir: PathBuf,
+}
+
+impl WatchedDirs {
+    pub fn all(&self) -> Vec<PathBuf> {
+        vec![
+            self.systemd_dir.clone(),
+            self.proxy_conf_dir.clone(),
+            self.ssl_storage_dir.clone(),
+        ]
+    }
+}
+
+/// What a changed path is, as far as kari is concerned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Watched {
+    Unit(String),        // Full unit file name, e.g. "kari-job-backup.timer"
+    Vhost(String),       // Domain
+    Certificate(String), // Domain
+}
+
+/// Maps a changed path to the kind of file it is. Anything kari never writes
+/// there (operator units, editor swap files, other sites) is None.
+pub fn classify(dirs: &WatchedDirs, path: &Path) -> Option<Watched> {
+    let name = path.file_name()?.to_str()?;
+    let parent = path.parent()?;
+    if parent == dirs.systemd_dir {
+        return (name.starts_with("kari-") && name.ends_with(".service")
+            || name.starts_with("kari-job-") && name.ends_with(".timer"))
+        .then(|| Watched::Unit(name.to_string()));
+    }
+    if parent == dirs.proxy_conf_dir {
+        // Nginx files are named after the domain, Apache ones add ".conf"
+        let domain = name.strip_suffix(".conf").unwrap_or(name);
+        return (!domain.starts_with('.')).then(|| Watched::Vhost(domain.to_string()));
+    }
+    if parent.parent()? == dirs.ssl_storage_dir && matches!(name, "fullchain.pem" | "privkey.pem") {
+        let domain = parent.file_name()?.to_str()?;
+        return Some(Watched::Certificate(domain.to_string()));
+    }
+    None
+}
+
+/// One file kari owns that no longer is what kari wrote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TamperEvent {
+    pub kind: &'static str, // "unit" | "timer" | "vhost" | "certificate"
+    pub target: String,     // Unit, job or domain name
+    pub app_id: String,     // Empty for standalone jobs
+    pub path: PathBuf,
+    pub detail: String,
+    pub repaired: bool,
+    pub detected_at: i64, // Unix seconds
+}
+
+/// The records and managers a tamper check reads from (and heals through).
+#[derive(Clone)]
+pub struct TamperWatch {
+    pub dirs: WatchedDirs,
+    pub state: Arc<dyn StateStore>,
+    pub proxy: Arc<dyn ProxyManager>,
+    pub ssl: Arc<dyn SslEngine>,
+    pub scheduler: Arc<dyn JobScheduler>,
+    pub self_heal: bool,
+    pub settle: Duration, // Quiet time before a changed path is judged
+}
+
+impl TamperWatch {
+    /// Follows `source` for as long as it runs, publishing every finding.
+    pub async fn run(
+        self,
+        source: Arc<dyn ConfigChangeSource>,
+        events: broadcast::Sender<TamperEvent>,
+    ) {
+        let (tx, mut rx) = mpsc::channel(256);
+        let dirs = self.dirs.all();
+        let follower = tokio::spawn(async move {
+            if let Err(e) = source.follow(dirs, tx).await {
+                warn!("🕵️ Tamper watch stopped: {}", e);
+            }
+        });
+
+        // Path -> when it last changed; judged once quiet for `settle`
+        let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+        let mut ticker = tokio::time::interval(self.settle.max(Duration::from_millis(10)) / 2);
+        loop {
+            tokio::select! {
+                change = rx.recv() => match change {
+                    Some(path) => {
+                        if classify(&self.dirs, &path).is_some() {
+                            pending.insert(path, Instant::now());
+                        }
+                    }
+                    None => break,
+                },
+                _ = ticker.tick() => {
+                    let settled: Vec<PathBuf> = pending
+                        .iter()
+                        .filter(|(_, changed)| changed.elapsed() >= self.settle)
+                        .map(|(path, _)| path.clone())
+                        .collect();
+                    for path in settled {
+                        pending.remove(&path);
+                        match self.inspect(&path).await {
+                            Ok(Some(event)) => {
+                                warn!(
+                                    "🕵️ Tamper: {} {} ({}) — {}",
+                                    event.kind,
+                                    event.target,
+                                    event.path.display(),
+                                    event.detail
+                                );
+                                // No subscribers is fine; the log line above remains
+                                let _ = events.send(event);
+                            }
+                            Ok(None) => {}
+                            Err(e) => warn!("🕵️ Tamper check of {} failed: {}", path.display(), e),
+                        }
+                    }
+                }
+            }
+        }
+        follower.abort();
+    }
+
+    /// Judges one settled path against the state store.
+    pub async fn inspect(&self, path: &Path) -> Result<Option<TamperEvent>, String> {
+        let Some(watched) = classify(&self.dirs, path) else {
+            return Ok(None);
+        };
+        let finding = match watched {
+            Watched::Unit(unit) => self.inspect_unit(path, &unit).await?,
+            Watched::Vhost(domain) => self.inspect_vhost(&domain).await?,
+            Watched::Certificate(domain) => self.inspect_certificate(&domain).await?,
+        };
+        Ok(
+            finding.map(|(kind, target, app_id, detail, repaired)| TamperEvent {
+                kind,
+                target,
+                app_id,
+                path: path.to_path_buf(),
+                detail,
+                repaired,
+                detected_at: chrono::Utc::now().timestamp(),
+            }),
+        )
+    }
+
+    async fn inspect_unit(&self, path: &Path, unit: &str) -> Result<Option<Finding>, String> {
+        let problem = match tokio::fs::read_to_string(path).await {
+            Ok(content) if is_kari_unit(&content) => return Ok(None),
+            Ok(_) => "overwritten without kari's marker",
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => "deleted",
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+
+        // kari-job-{name}.service / .timer belong to a job record...
+        let job_name = unit
+            .strip_prefix("kari-job-")
+            .and_then(|rest| rest.rsplit_once('.'))
+            .map(|(name, _)| name);
+        if let Some(job_name) = job_name {
+            let job: Option<JobRecord> = get_record(self.state.as_ref(), NS_JOBS, job_name).await?;
+            if let Some(job) = job {
+                let app_id = job.app_id.clone().unwrap_or_default();
+                let heal = self.self_heal.then(|| self.rewrite_job(&job));
+                return Ok(Some(
+                    conclude("timer", job_name, app_id, problem.to_string(), heal).await,
+                ));
+            }
+        }
+
+        // ...and kari-{domain}.service to an app record
+        let service_name = unit.strip_suffix(".service").unwrap_or(unit);
+        let apps: Vec<AppRecord> = list_records(self.state.as_ref(), NS_APPS).await?;
+        Ok(
+            match apps.into_iter().find(|a| a.service_name == service_name) {
+                Some(app) => {
+                    let detail = format!("{}; app units cannot be rebuilt from state", problem);
+                    let heal: Option<std::future::Ready<Result<(), String>>> = None;
+                    Some(conclude("unit", service_name, app.app_id, detail, heal).await)
+                }
+                None => None, // Not (or no longer) kari's: teardown or an unrelated unit
+            },
+        )
+    }
+
+    async fn inspect_vhost(&self, domain: &str) -> Result<Option<Finding>, String> {
+        let apps: Vec<AppRecord> = list_records(self.state.as_ref(), NS_APPS).await?;
+        // Only routed apps (a port was recorded) own a vhost
+        let Some((app, port)) = apps
+            .into_iter()
+            .filter(|a| a.domain_name == domain)
+            .find_map(|a| a.port.map(|port| (a, port)))
+        else {
+            return Ok(None);
+        };
+        if self
+            .proxy
+            .vhost_is_current(domain, port, &app.vhost)
+            .await?
+        {
+            return Ok(None);
+        }
+        let detail = "deleted or edited outside kari".to_string();
+        let heal = self
+            .self_heal
+            .then(|| self.proxy.create_vhost(domain, port, &app.vhost));
+        Ok(Some(
+            conclude("vhost", domain, app.app_id, detail, heal).await,
+        ))
+    }
+
+    async fn inspect_certificate(&self, domain: &str) -> Result<Option<Finding>, String> {
+        let apps: Vec<AppRecord> = list_records(self.state.as_ref(), NS_APPS).await?;
+        let Some(app) = apps
+            .into_iter()
+            .find(|a| a.certificate_name() == domain && a.certificate_installed)
+        else {
+            return Ok(None);
+        };
+        if self.ssl.certificate_exists(domain).await? {
+            return Ok(None);
+        }
+        // The agent never keeps the key, so a lost certificate must be reinstalled
+        let detail = "certificate files missing or empty; reinstall required".to_string();
+        let heal: Option<std::future::Ready<Result<(), String>>> = None;
+        Ok(Some(
+            conclude("certificate", domain, app.app_id, detail, heal).await,
+        ))
+    }
+
+    /// Rewrites both job units from the record. The clobbered files are removed
+    /// first: the scheduler refuses to overwrite a unit without kari's marker.
+    async fn rewrite_job(&self, job: &JobRecord) -> Result<(), String> {
+        let intent = JobIntent {
+            name: job.job_name.clone(),
+            binary: job.binary.clone(),
+            args: job.args.clone(),
+            schedule: job.schedule.clone(),
+            run_as_user: job.run_as_user.clone(),
+        };
+        self.scheduler.unschedule_job(&job.job_name).await?;
+        self.scheduler.schedule_job(&intent).await
+    }
+}
+
+/// (kind, target, app_id, detail, repaired)
+type Finding = (&'static str, String, String, String, bool);
+
+/// Runs the heal, if any, and folds its outcome into the finding.
+async fn conclude<R>(
+    kind: &'static str,
+    target: &str,
+    app_id: String,
+    detail: String,
+    heal: Option<R>,
+) -> Finding
+where
+    R: std::future::Future<Output = Result<(), String>>,
+{
+    let (repaired, detail) = match heal {
+        Some(fix) => match fix.await {
+            Ok(()) => {
+                info!("🕵️ Tamper: {} {} rewritten from state", kind, target);
+                (true, format!("{}; rewritten from state", detail))
+            }
+            Err(e) => (false, format!("{}; rewrite failed: {}", detail, e)),
+        },
+        None => (false, detail),
+    };
+    (kind, target.to_string(), app_id, detail, repaired)
+}
+
+// ==============================================================================
+// inotify source
+// ==============================================================================
+
+pub struct InotifyChangeSource;
+
+const WATCH_MASK: AddWatchFlags = AddWatchFlags::IN_CLOSE_WRITE
+    .union(AddWatchFlags::IN_DELETE)
+    .union(AddWatchFlags::IN_MOVED_FROM)
+    .union(AddWatchFlags::IN_MOVED_TO)
+    .union(AddWatchFlags::IN_ATTRIB)
+    .union(AddWatchFlags::IN_CREATE);
+
+#[async_trait]
+impl ConfigChangeSource for InotifyChangeSource {
+    async fn follow(&self, dirs: Vec<PathBuf>, tx: mpsc::Sender<PathBuf>) -> Result<(), String> {
+        // inotify reads block; the watch gets a thread of its own for the agent's lifetime
+        tokio::task::spawn_blocking(move || follow_blocking(&dirs, &tx))
+            .await
+            .map_err(|e| format!("Tamper watch thread failed: {}", e))?
+    }
+}
+
+fn add_watch(inotify: &Inotify, watches: &mut HashMap<WatchDescriptor, PathBuf>, dir: &Path) {
+    match inotify.add_watch(dir, WATCH_MASK) {
+        Ok(wd) => {
+            watches.insert(wd, dir.to_path_buf());
+        }
+        Err(e) => warn!("🕵️ Cannot watch {}: {}", dir.display(), e),
+    }
+}
+
+fn follow_blocking(dirs: &[PathBuf], tx: &mpsc::Sender<PathBuf>) -> Result<(), String> {
+    let inotify =
+        Inotify::init(InitFlags::IN_CLOEXEC).map_err(|e| format!("inotify_init: {}", e))?;
+    let mut watches: HashMap<WatchDescriptor, PathBuf> = HashMap::new();
+
+    for dir in dirs {
+        add_watch(&inotify, &mut watches, dir);
+        // One level down covers per-domain certificate directories
+        if let Ok(entries) = std::fs::read_dir(dir) {
+            for entry in entries.flatten() {
+                if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                    add_watch(&inotify, &mut watches, &entry.path());
+                }
+            }
+        }
+    }
+
+    loop {
+        let events = inotify
+            .read_events()
+            .map_err(|e| format!("inotify read: {}", e))?;
+        for event in events {
+            let (Some(dir), Some(name)) = (watches.get(&event.wd), event.name) else {
+                continue;
+            };
+            let path = dir.join(name);
+            if event.mask.contains(AddWatchFlags::IN_ISDIR) {
+                // A new domain directory under a top-level dir gets watched too
+                if event.mask.contains(AddWatchFlags::IN_CREATE) && dirs.contains(dir) {
+                    add_watch(&inotify, &mut watches, &path);
+                }
+                continue;
+            }
+            if tx.blocking_send(path).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_files_kari_writes_are_watched() {
+        let dirs = WatchedDirs {
+            systemd_dir: "/etc/systemd/system".into(),
+            proxy_conf_dir: "/etc/nginx/sites-available".into(),
+            ssl_storage_dir: "/etc/kari/ssl".into(),
+        };
+        let at = |p: &str| classify(&dirs, Path::new(p));
+
+        assert_eq!(
+            at("/etc/systemd/system/kari-job-backup.timer"),
+            Some(Watched::Unit("kari-job-backup.timer".into()))
+        );
+        assert_eq!(
+            at("/etc/systemd/system/kari-shop.example.com.service"),
+            Some(Watched::Unit("kari-shop.example.com.service".into()))
+        );
+        assert_eq!(at("/etc/systemd/system/nginx.service"), None);
+        assert_eq!(
+            at("/etc/nginx/sites-available/shop.example.com"),
+            Some(Watched::Vhost("shop.example.com".into()))
+        );
+        assert_eq!(
+            at("/etc/kari/ssl/shop.example.com/privkey.pem"),
+            Some(Watched::Certificate("shop.example.com".into()))
+        );
+        assert_eq!(at("/etc/kari/ssl/shop.example.com/notes.txt"), None);
+    }
+}
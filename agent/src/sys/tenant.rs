@@ -0,0 +1,113 @@
+// agent/src/sys/tenant.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Tenant quota groups. Every app of a tenant
+// runs inside `kari-tenant-<id>.slice`, so the slice's MemoryMax/CPUQuota caps
+// the tenant as a whole no matter how many small apps it spreads across.
+
+use async_trait::async_trait;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use tokio::fs;
+
+use crate::sys::executor::SystemExecutor;
+use crate::sys::systemd::ensure_unit_not_foreign;
+use crate::sys::traits::{TenantQuota, TenantSliceManager};
+
+/// 🛡️ Zero-Trust: systemd treats '-' in a slice name as nesting, so a tenant
+/// "acme-eu" would land *inside* tenant "acme" and share its quota. Tenant ids
+/// are therefore restricted to ASCII alphanumerics and '_'.
+pub fn is_valid_tenant_id(tenant_id: &str) -> bool {
+    !tenant_id.is_empty()
+        && tenant_id.len() <= 64
+        && tenant_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+pub fn tenant_slice_name(tenant_id: &str) -> String {
+    format!("kari-tenant-{}.slice", tenant_id)
+}
+
+fn render_slice(quota: &TenantQuota) -> String {
+    // An empty CPUQuota= and MemoryMax=infinity reset a previously set limit
+    let cpu_quota = quota
+        .cpu_quota_percent
+        .map(|p| format!("{}%", p))
+        .unwrap_or_default();
+    let memory_max = quota
+        .memory_max_mb
+        .map(|mb| format!("{}M", mb))
+        .unwrap_or_else(|| "infinity".to_string());
+
+    format!(
+        r#"[Unit]
+Description=Kari Tenant Quota Group: {tenant_id}
+Before=slices.target
+
+[Slice]
+CPUAccounting=true
+CPUQuota={cpu_quota}
+MemoryAccounting=true
+MemoryMax={memory_max}
+TasksAccounting=true
+"#,
+        tenant_id = quota.tenant_id,
+        cpu_quota = cpu_quota,
+        memory_max = memory_max
+    )
+}
+
+pub struct SystemdSliceManager {
+    systemd_dir: PathBuf,
+}
+
+impl SystemdSliceManager {
+    pub fn new(systemd_dir: PathBuf) -> Self {
+        Self { systemd_dir }
+    }
+}
+
+#[async_trait]
+impl TenantSliceManager for SystemdSliceManager {
+    async fn write_slice(&self, quota: &TenantQuota) -> Result<(), String> {
+        if !is_valid_tenant_id(&quota.tenant_id) {
+            return Err(format!(
+                "SECURITY VIOLATION: Invalid tenant id '{}'",
+                quota.tenant_id
+            ));
+        }
+        let path = self.systemd_dir.join(tenant_slice_name(&quota.tenant_id));
+        ensure_unit_not_foreign(&SystemExecutor, &path).await?;
+
+        fs::write(&path, render_slice(quota))
+            .await
+            .map_err(|e| format!("Failed to write tenant slice: {}", e))?;
+        fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644))
+            .await
+            .map_err(|e| format!("Failed to set tenant slice mode: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tenant_ids_cannot_nest_slices() {
+        assert!(is_valid_tenant_id("acme_eu"));
+        assert!(!is_valid_tenant_id("acme-eu"));
+        assert!(!is_valid_tenant_id("acme.eu"));
+        assert!(!is_valid_tenant_id(""));
+    }
+
+    #[test]
+    fn unset_limits_render_as_unlimited() {
+        let unit = render_slice(&TenantQuota {
+            tenant_id: "acme".into(),
+            memory_max_mb: None,
+            cpu_quota_percent: Some(200),
+        });
+        assert!(unit.contains("CPUQuota=200%\n"));
+        assert!(unit.contains("MemoryMax=infinity\n"));
+    }
+}
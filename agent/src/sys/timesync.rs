@@ -0,0 +1,373 @@
+// agent/src/sys/timesync.rs
+//
+// 🛡️ SOLID: Single-Responsibility — Keeps the host clock disciplined.
+//
+// Certificates (notBefore/notAfter), OnCalendar timers and release timestamps
+// all assume a correct clock. Whichever daemon the host runs is used: chrony
+// when its unit exists, systemd-timesyncd otherwise. Kari's servers go into a
+// file of its own (a chrony sources file, a timesyncd drop-in), so the distro
+// configuration is never rewritten.
+
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::sys::executor::{CommandSpec, Executor, SystemExecutor, run_stdout};
+use crate::sys::traits::{TimeSyncDaemon, TimeSyncManager, TimeSyncStatus};
+
+const SOURCES_FILE: &str = "kari.sources";
+const DROP_IN_FILE: &str = "kari.conf";
+
+pub struct SystemTimeSyncManager {
+    chrony_conf: PathBuf,        // e.g. /etc/chrony/chrony.conf
+    chrony_sources_dir: PathBuf, // Read through chrony's `sourcedir`
+    timesyncd_dir: PathBuf,      // e.g. /etc/systemd/timesyncd.conf.d
+    executor: Arc<dyn Executor>,
+}
+
+impl SystemTimeSyncManager {
+    pub fn new(chrony_conf: PathBuf, chrony_sources_dir: PathBuf, timesyncd_dir: PathBuf) -> Self {
+        Self::with_executor(
+            chrony_conf,
+            chrony_sources_dir,
+            timesyncd_dir,
+            Arc::new(SystemExecutor),
+        )
+    }
+
+    pub fn with_executor(
+        chrony_conf: PathBuf,
+        chrony_sources_dir: PathBuf,
+        timesyncd_dir: PathBuf,
+        executor: Arc<dyn Executor>,
+    ) -> Self {
+        Self {
+            chrony_conf,
+            chrony_sources_dir,
+            timesyncd_dir,
+            executor,
+        }
+    }
+
+    /// Runs a command and returns its stdout. Only changes need root.
+    async fn run(&self, spec: CommandSpec) -> Result<String, String> {
+        run_stdout(self.executor.as_ref(), spec).await
+    }
+
+    /// chrony when `chronyd.service` (an alias of `chrony.service` on Debian)
+    /// is installed; systemd-timesyncd otherwise.
+    async fn daemon(&self) -> Result<TimeSyncDaemon, String> {
+        let load_state = self
+            .run(CommandSpec::new("systemctl").args([
+                "show",
+                "-p",
+                "LoadState",
+                "--value",
+                "chronyd.service",
+            ]))
+            .await?;
+        Ok(if load_state.trim() == "loaded" {
+            TimeSyncDaemon::Chrony
+        } else {
+            TimeSyncDaemon::Timesyncd
+        })
+    }
+
+    fn configure_chrony(&self, servers: &[String]) -> Result<(), String> {
+        std::fs::create_dir_all(&self.chrony_sources_dir)
+            .map_err(|e| format!("Failed to create chrony sources dir: {}", e))?;
+        write_config(
+            &self.chrony_sources_dir.join(SOURCES_FILE),
+            &render_chrony_sources(servers),
+        )?;
+
+        // Debian's chrony.conf already reads /etc/chrony/sources.d; other
+        // distributions need to be told about the directory once
+        let conf = std::fs::read_to_string(&self.chrony_conf)
+            .map_err(|e| format!("Failed to read {}: {}", self.chrony_conf.display(), e))?;
+        let dir = self.chrony_sources_dir.display().to_string();
+        let declared = conf.lines().any(|line| {
+            let mut words = line.split_whitespace();
+            words.next() == Some("sourcedir") && words.next() == Some(dir.as_str())
+        });
+        if !declared {
+            let mut conf = conf;
+            if !conf.is_empty() && !conf.ends_with('\n') {
+                conf.push('\n');
+            }
+            conf.push_str(&format!("# Added by Kari\nsourcedir {}\n", dir));
+            write_config(&self.chrony_conf, &conf)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write-then-rename, so the daemon never reads half a file.
+fn write_config(path: &Path, content: &str) -> Result<(), String> {
+    let tmp_path = path.with_extension("kari-tmp");
+    std::fs::write(&tmp_path, content)
+        .and_then(|_| std::fs::rename(&tmp_path, path))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn render_chrony_sources(servers: &[String]) -> String {
+    let mut out = String::from("# Managed by Kari (ConfigureTimeSync); changes are overwritten\n");
+    for server in servers {
+        out.push_str(&format!("server {} iburst\n", server));
+    }
+    out
+}
+
+fn render_timesyncd_drop_in(servers: &[String]) -> String {
+    format!(
+        "# Managed by Kari (ConfigureTimeSync); changes are overwritten\n[Time]\nNTP={}\n",
+        servers.join(" ")
+    )
+}
+
+/// `chronyc -c tracking`: (current source, offset). chrony reports the
+/// correction still to apply, so a slow clock has a positive one; the offset
+/// is its negation. An unsynchronised chrony has no source.
+fn parse_chrony_tracking(csv: &str) -> (Option<String>, Option<f64>) {
+    let fields: Vec<&str> = csv.trim().split(',').collect();
+    let synchronised = fields
+        .last()
+        .is_some_and(|leap| *leap != "Not synchronised");
+    let source = fields
+        .get(1)
+        .filter(|name| synchronised && !name.is_empty())
+        .map(|name| name.to_string());
+    let offset = fields
+        .get(4)
+        .and_then(|c| c.parse::<f64>().ok())
+        .map(|correction| -correction);
+    (source, offset)
+}
+
+/// `chronyc -c sources`: the names of configured servers and pool members.
+fn parse_chrony_sources(csv: &str) -> Vec<String> {
+    csv.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').collect();
+            // '^' is a server; '=' and '#' are peers and reference clocks
+            if fields.first() != Some(&"^") {
+                return None;
+            }
+            fields.get(2).map(|name| name.to_string())
+        })
+        .collect()
+}
+
+/// `timedatectl timesync-status`: (current source, offset). The offset is
+/// printed like `-1.180ms` or `+25us`.
+fn parse_timesync_status(text: &str) -> (Option<String>, Option<f64>) {
+    let field = |name: &str| {
+        text.lines()
+            .find_map(|line| line.trim().strip_prefix(name)?.strip_prefix(':'))
+            .map(str::trim)
+    };
+    let source = field("Server").map(|server| {
+        // "185.125.190.56 (ntp.ubuntu.com)": the name when there is one
+        server
+            .split_once(" (")
+            .and_then(|(_, name)| name.strip_suffix(')'))
+            .unwrap_or(server)
+            .to_string()
+    });
+    let offset = field("Offset").and_then(|value| {
+        let split = value.find(char::is_alphabetic)?;
+        let (number, unit) = value.split_at(split);
+        let scale = match unit {
+            "ns" => 1e-9,
+            "us" | "µs" => 1e-6,
+            "ms" => 1e-3,
+            "s" => 1.0,
+            "min" => 60.0,
+            _ => return None,
+        };
+        Some(number.parse::<f64>().ok()? * scale)
+    });
+    (source, offset)
+}
+
+#[async_trait]
+impl TimeSyncManager for SystemTimeSyncManager {
+    async fn configure(&self, servers: &[String]) -> Result<TimeSyncDaemon, String> {
+        let daemon = self.daemon().await?;
+        match daemon {
+            TimeSyncDaemon::Chrony => self.configure_chrony(servers)?,
+            TimeSyncDaemon::Timesyncd => {
+                std::fs::create_dir_all(&self.timesyncd_dir)
+                    .map_err(|e| format!("Failed to create timesyncd drop-in dir: {}", e))?;
+                write_config(
+                    &self.timesyncd_dir.join(DROP_IN_FILE),
+                    &render_timesyncd_drop_in(servers),
+                )?;
+            }
+        }
+
+        // set-ntp enables and starts the daemon; the restart makes it re-read
+        self.run(CommandSpec::privileged("timedatectl").args(["set-ntp", "true"]))
+            .await?;
+        self.run(CommandSpec::privileged("systemctl").args(["restart", daemon.unit()]))
+            .await?;
+        info!("🕰️ {} now syncs from {}", daemon.unit(), servers.join(", "));
+        Ok(daemon)
+    }
+
+    async fn status(&self) -> Result<TimeSyncStatus, String> {
+        let daemon = self.daemon().await?;
+        // The kernel's own flag (adjtimex), whichever daemon disciplines it
+        let synchronized = self
+            .run(CommandSpec::new("timedatectl").args(["show", "-p", "NTPSynchronized", "--value"]))
+            .await?
+            .trim()
+            == "yes";
+
+        // A stopped daemon answers nothing; that is reported, not an error
+        let (servers, (current_source, offset_secs)) = match daemon {
+            TimeSyncDaemon::Chrony => (
+                self.run(CommandSpec::new("chronyc").args(["-c", "-n", "sources"]))
+                    .await
+                    .map(|csv| parse_chrony_sources(&csv))
+                    .unwrap_or_default(),
+                self.run(CommandSpec::new("chronyc").args(["-c", "-n", "tracking"]))
+                    .await
+                    .map(|csv| parse_chrony_tracking(&csv))
+                    .unwrap_or_default(),
+            ),
+            TimeSyncDaemon::Timesyncd => (
+                self.run(CommandSpec::new("timedatectl").args([
+                    "show-timesync",
+                    "-p",
+                    "SystemNTPServers",
+                    "--value",
+                ]))
+                .await
+                .map(|line| line.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_default(),
+                self.run(CommandSpec::new("timedatectl").arg("timesync-status"))
+                    .await
+                    .map(|text| parse_timesync_status(&text))
+                    .unwrap_or_default(),
+            ),
+        };
+
+        Ok(TimeSyncStatus {
+            daemon,
+            synchronized,
+            offset_secs,
+            servers,
+            current_source,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::FakeExecutor;
+
+    #[tokio::test]
+    async fn only_changes_to_the_clock_run_as_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let executor = Arc::new(FakeExecutor::default());
+        let manager = SystemTimeSyncManager::with_executor(
+            dir.path().join("chrony.conf"),
+            dir.path().join("sources.d"),
+            dir.path().join("timesyncd.conf.d"),
+            executor.clone(),
+        );
+
+        executor.answer(&["systemctl", "show"], 0, "not-found\n", "");
+        let daemon = manager
+            .configure(&["time.example.com".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(daemon, TimeSyncDaemon::Timesyncd);
+        assert!(
+            std::fs::read_to_string(dir.path().join("timesyncd.conf.d/kari.conf"))
+                .unwrap()
+                .ends_with("NTP=time.example.com\n")
+        );
+        let privileged: Vec<Vec<String>> = executor
+            .runs()
+            .iter()
+            .filter(|spec| spec.privileged)
+            .map(CommandSpec::argv)
+            .collect();
+        assert_eq!(
+            privileged,
+            [
+                vec!["timedatectl", "set-ntp", "true"],
+                vec!["systemctl", "restart", "systemd-timesyncd.service"],
+            ]
+        );
+
+        executor.answer(&["systemctl", "restart"], 1, "", "Unit not found.");
+        let err = manager.configure(&[]).await.unwrap_err();
+        assert_eq!(
+            err,
+            "systemctl restart systemd-timesyncd.service failed: Unit not found."
+        );
+
+        // A stopped chrony still yields a status
+        executor.answer(&["systemctl", "show"], 0, "loaded\n", "");
+        executor.answer(&["timedatectl", "show"], 0, "yes\n", "");
+        executor.missing(&["chronyc"]);
+        let status = manager.status().await.unwrap();
+        assert_eq!(status.daemon, TimeSyncDaemon::Chrony);
+        assert!(status.synchronized && status.servers.is_empty());
+    }
+
+    #[test]
+    fn chrony_reports_parse_into_sources_and_offsets() {
+        let (source, offset) = parse_chrony_tracking(
+            "A29FC87B,162.159.200.123,3,1760000000.123,0.000250000,-0.000012,0.000031,-12.345,0.001,0.020,0.015,0.000312,0.000104,64.5,Normal\n",
+        );
+        assert_eq!(source.as_deref(), Some("162.159.200.123"));
+        assert_eq!(offset, Some(-0.00025));
+
+        let (source, _) = parse_chrony_tracking(
+            "00000000,,0,0.000000000,0.000000000,0.000000000,0.000000000,0.000,0.000,0.000,0.000,1.000000000,1.000000000,0.0,Not synchronised\n",
+        );
+        assert_eq!(source, None);
+
+        let sources = parse_chrony_sources(
+            "^,*,162.159.200.123,3,6,377,30,-0.000012,-0.000013,0.001\n\
+             ^,?,10.0.0.5,0,6,0,-,0.000000,0.000000,0.000\n\
+             #,-,PPS0,0,4,377,16,0.000001,0.000001,0.000\n",
+        );
+        assert_eq!(sources, ["162.159.200.123", "10.0.0.5"]);
+    }
+
+    #[test]
+    fn timesyncd_status_offsets_carry_their_unit() {
+        let text = "       Server: 185.125.190.56 (ntp.ubuntu.com)\n\
+                    Poll interval: 34min 8s (min: 32s; max 34min 8s)\n\
+                             Leap: normal\n\
+                           Offset: -1.180ms\n\
+                            Delay: 39.062ms\n";
+        let (source, offset) = parse_timesync_status(text);
+        assert_eq!(source.as_deref(), Some("ntp.ubuntu.com"));
+        assert!((offset.unwrap() + 0.00118).abs() < 1e-12);
+
+        let (source, offset) = parse_timesync_status("Server: 10.0.0.5\nOffset: +25us\n");
+        assert_eq!(source.as_deref(), Some("10.0.0.5"));
+        assert!((offset.unwrap() - 0.000025).abs() < 1e-12);
+        assert_eq!(parse_timesync_status("Server: n/a\n").1, None);
+    }
+
+    #[test]
+    fn kari_servers_render_into_their_own_files() {
+        let servers = ["time.example.com".to_string(), "10.0.0.5".to_string()];
+        assert!(
+            render_chrony_sources(&servers)
+                .ends_with("server time.example.com iburst\nserver 10.0.0.5 iburst\n")
+        );
+        assert!(
+            render_timesyncd_drop_in(&servers).ends_with("[Time]\nNTP=time.example.com 10.0.0.5\n")
+        );
+    }
+}
@@ -0,0 +1,105 @@
+// agent/src/sys/tls_policy.rs
+//
+// 🛡️ SOLID: Single-Responsibility — One TLS policy for every HTTPS vhost on the host.
+//
+// The policy is host configuration (KARI_TLS_POLICY), not a per-app option:
+// each vhost records the policy it was written with, and at startup any vhost
+// written under another one is rewritten. Changing the setting and restarting
+// the agent is therefore enough to move a fleet to TLS 1.2+ or 1.3-only.
+// OCSP stapling (KARI_OCSP_STAPLING) is host configuration in the same way.
+
+use tracing::info;
+
+use crate::sys::state::{AppRecord, NS_APPS, list_records, put_record};
+use crate::sys::traits::{OcspStapling, ProxyManager, StateStore, TlsPolicy};
+
+/// What a policy allows, in OpenSSL's names.
+pub struct TlsPreset {
+    pub protocols: &'static [&'static str],
+    /// TLS 1.2-and-older suites; empty leaves OpenSSL's (TLS 1.3 suites are
+    /// not configurable through these directives and are all strong).
+    pub ciphers: &'static str,
+    pub curves: &'static str,
+    pub prefer_server_ciphers: bool,
+}
+
+const CURVES: &str = "X25519:prime256v1:secp384r1";
+
+const INTERMEDIATE_CIPHERS: &str = "ECDHE-ECDSA-AES128-GCM-SHA256:ECDHE-RSA-AES128-GCM-SHA256:\
+ECDHE-ECDSA-AES256-GCM-SHA384:ECDHE-RSA-AES256-GCM-SHA384:ECDHE-ECDSA-CHACHA20-POLY1305:\
+ECDHE-RSA-CHACHA20-POLY1305:DHE-RSA-AES128-GCM-SHA256:DHE-RSA-AES256-GCM-SHA384:\
+DHE-RSA-CHACHA20-POLY1305";
+
+// OpenSSL 3 refuses SHA-1 and TLS 1.0/1.1 at its default security level
+const OLD_CIPHERS: &str = "ECDHE-ECDSA-AES128-GCM-SHA256:ECDHE-RSA-AES128-GCM-SHA256:\
+ECDHE-ECDSA-AES256-GCM-SHA384:ECDHE-RSA-AES256-GCM-SHA384:ECDHE-ECDSA-CHACHA20-POLY1305:\
+ECDHE-RSA-CHACHA20-POLY1305:DHE-RSA-AES128-GCM-SHA256:DHE-RSA-AES256-GCM-SHA384:\
+DHE-RSA-CHACHA20-POLY1305:ECDHE-ECDSA-AES128-SHA256:ECDHE-RSA-AES128-SHA256:\
+ECDHE-ECDSA-AES128-SHA:ECDHE-RSA-AES128-SHA:ECDHE-ECDSA-AES256-SHA384:ECDHE-RSA-AES256-SHA384:\
+ECDHE-ECDSA-AES256-SHA:ECDHE-RSA-AES256-SHA:DHE-RSA-AES128-SHA256:DHE-RSA-AES256-SHA256:\
+AES128-GCM-SHA256:AES256-GCM-SHA384:AES128-SHA256:AES256-SHA256:AES128-SHA:AES256-SHA:\
+DES-CBC3-SHA:@SECLEVEL=0";
+
+pub fn preset(policy: TlsPolicy) -> TlsPreset {
+    match policy {
+        TlsPolicy::Modern => TlsPreset {
+            protocols: &["TLSv1.3"],
+            ciphers: "",
+            curves: CURVES,
+            prefer_server_ciphers: false,
+        },
+        TlsPolicy::Intermediate => TlsPreset {
+            protocols: &["TLSv1.2", "TLSv1.3"],
+            ciphers: INTERMEDIATE_CIPHERS,
+            curves: CURVES,
+            prefer_server_ciphers: false,
+        },
+        // Old clients pick badly, so the server's order wins
+        TlsPolicy::Old => TlsPreset {
+            protocols: &["TLSv1", "TLSv1.1", "TLSv1.2", "TLSv1.3"],
+            ciphers: OLD_CIPHERS,
+            curves: CURVES,
+            prefer_server_ciphers: true,
+        },
+    }
+}
+
+/// Rewrites every HTTPS vhost written under a policy or OCSP stapling other
+/// than `policy` and `stapling`. Returns the domains rewritten.
+pub async fn apply(
+    state: &dyn StateStore,
+    proxy: &dyn ProxyManager,
+    policy: TlsPolicy,
+    stapling: Option<&OcspStapling>,
+) -> Result<Vec<String>, String> {
+    let apps: Vec<AppRecord> = list_records(state, NS_APPS).await?;
+    let mut rewritten = Vec::new();
+    for mut app in apps {
+        let Some(tls) = app
+            .vhost
+            .tls
+            .as_mut()
+            .filter(|tls| tls.policy != policy || tls.ocsp_stapling.as_ref() != stapling)
+        else {
+            continue;
+        };
+        tls.policy = policy;
+        tls.ocsp_stapling = stapling.cloned();
+        // Without a port there is no vhost yet; the next one written picks this up
+        if let Some(port) = app.port {
+            proxy
+                .create_vhost(&app.domain_name, port, &app.vhost)
+                .await
+                .map_err(|e| format!("{}: {}", app.domain_name, e))?;
+        }
+        put_record(state, NS_APPS, &app.app_id, &app).await?;
+        info!(
+            "🔒 TLS policy for {} is now {:?}, OCSP stapling {}",
+            app.domain_name,
+            policy,
+            if stapling.is_some() { "on" } else { "off" }
+        );
+        rewritten.push(app.domain_name);
+    }
+    Ok(rewritten)
+}
@@ -1,11 +1,16 @@
 use async_trait::async_trait;
-use std::collections::HashMap;
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 use tokio::sync::mpsc;
 use tonic::Status;
 
 use crate::server::kari_agent::LogChunk;
+use crate::sys::log_forward::LogTarget;
+use crate::sys::patching::PatchScope;
 use crate::sys::secrets::ProviderCredential;
+use crate::sys::sftp::AuthorizedKey;
 
 // ==============================================================================
 // 1. GitOps & Source Control (Zero-Leak Auth)
@@ -24,25 +29,58 @@ pub trait GitManager: Send + Sync {
         target_dir: &Path, // 🛡️ SLA: Strict Type
         ssh_key: Option<ProviderCredential>,
     ) -> Result<(), String>;
+
+    /// Kills what is left of a clone into `target_dir` whose future was
+    /// dropped (git and every helper it spawned) and returns once none of it
+    /// is running, so the directory can be removed without racing it.
+    async fn stop_clone(&self, target_dir: &Path) -> Result<(), String>;
 }
 
 // ==============================================================================
 // 2. Build & Execution (Telemetry-Aware)
 // ==============================================================================
 
+/// Network access granted to a build step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildNetwork {
+    Host,
+    /// 🛡️ PrivateNetwork=yes: loopback only, so the step cannot exfiltrate or fetch.
+    Isolated,
+}
+
+/// What one build step consumed, as measured from its cgroup. Zero means the
+/// host did not report the figure (e.g. `Memory peak` needs systemd 255+).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BuildUsage {
+    pub peak_memory_bytes: u64,
+    pub cpu_usec: u64,
+}
+
+impl BuildUsage {
+    /// Folds a later step in: CPU adds up, memory keeps the highest peak.
+    pub fn merge(&mut self, other: BuildUsage) {
+        self.peak_memory_bytes = self.peak_memory_bytes.max(other.peak_memory_bytes);
+        self.cpu_usec += other.cpu_usec;
+    }
+}
+
 #[async_trait]
 pub trait BuildManager: Send + Sync {
-    /// Executes a build command within an unprivileged jail.
+    /// Executes a build command within an unprivileged jail, in its own cgroup.
     /// 🛡️ log_tx: A streaming channel to pipe stdout/stderr back to the gRPC stream.
+    /// Dropping the future, timing out, or dropping log_tx's receiver stops the
+    /// step's whole process tree, not just its first process.
+    #[allow(clippy::too_many_arguments)]
     async fn execute_build(
         &self,
-        build_command: &str,
-        working_dir: &Path, // 🛡️ SLA: Strict Type
+        build_argv: &[String], // 🛡️ Zero-Trust: Executed directly, never through a shell
+        working_dir: &Path,    // 🛡️ SLA: Strict Type
         run_as_user: &str,
+        network: BuildNetwork,
         env_vars: &HashMap<String, String>,
         log_tx: mpsc::Sender<Result<LogChunk, Status>>,
         trace_id: String,
-    ) -> Result<(), String>;
+    ) -> Result<BuildUsage, String>;
 }
 
 // ==============================================================================
@@ -68,11 +106,21 @@ pub struct FirewallPolicy {
     pub port: u16,
     pub protocol: Protocol,
     pub source_ip: Option<String>,
+    pub description: Option<String>,
+    pub created_by: Option<String>,
+    pub log_denied: bool, // Log matched packets (deny/reject rules only)
 }
 
 #[async_trait]
 pub trait FirewallManager: Send + Sync {
     async fn apply_policy(&self, policy: &FirewallPolicy) -> Result<(), String>;
+
+    /// Whether every rule `apply_policy` installs for this policy is in place.
+    async fn policy_applied(&self, policy: &FirewallPolicy) -> Result<bool, String>;
+
+    /// Deletes every rule `apply_policy` installs for this policy; rules
+    /// already gone are skipped.
+    async fn remove_policy(&self, policy: &FirewallPolicy) -> Result<(), String>;
 }
 
 // ==============================================================================
@@ -85,27 +133,218 @@ pub struct SslPayload {
 
     /// 🛡️ Zero-Copy Secret. The SslEngine takes ownership of this struct,
     /// writes the key to the protected `/etc/kari/ssl` directory, and immediately
-    /// calls `.destroy()` on it to scrub the RAM.
+    /// calls `.destroy()` on it to scrub the RAM. Empty: the certificate was
+    /// issued for the key `stage_next_key` generated, which goes live with it.
     pub privkey_pem: ProviderCredential,
 }
 
 #[async_trait]
 pub trait SslEngine: Send + Sync {
     async fn install_certificate(&self, payload: SslPayload) -> Result<(), String>;
+
+    /// Whether a full chain and key are installed for the domain.
+    async fn certificate_exists(&self, domain_name: &str) -> Result<bool, String>;
+
+    /// SHA-256 of the installed private key, to tell a new key from a renewal
+    /// that kept the old one. None without a key.
+    async fn key_fingerprint(&self, domain_name: &str) -> Result<Option<String>, String>;
+
+    /// 🔄 Generates the domain's next private key beside the live one and
+    /// returns a CSR (PEM) for it, naming `san_domains` as well. The key
+    /// never leaves the host.
+    async fn stage_next_key(
+        &self,
+        domain_name: &str,
+        san_domains: &[String],
+    ) -> Result<String, String>;
+
+    /// What the installed certificate says about itself. None without one.
+    async fn certificate_details(
+        &self,
+        domain_name: &str,
+    ) -> Result<Option<CertificateDetails>, String>;
+
+    /// 🩺 Asks the certificate's OCSP responder for the response a vhost
+    /// would staple right now.
+    async fn ocsp_staple(&self, domain_name: &str) -> Result<OcspStaple, String>;
+}
+
+/// The parts of an installed certificate that decide how it is served.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CertificateDetails {
+    pub not_after: i64, // Unix seconds
+    /// TLS Feature `status_request`: clients must refuse it without a staple.
+    pub must_staple: bool,
+    pub ocsp_url: Option<String>,
+}
+
+/// An OCSP response for an installed certificate.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OcspStaple {
+    pub status: String,           // "good" | "revoked" | "unknown" | "unavailable"
+    pub this_update: Option<i64>, // Unix seconds
+    pub next_update: Option<i64>,
+    pub detail: String,
 }
 
 // ==============================================================================
 // 5. Proxy Abstraction (Platform-Agnostic Ingress)
 // ==============================================================================
 
+/// 🚦 Per-client ingress limits for one vhost (nginx `limit_req` / `limit_conn`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub requests_per_second: u32, // 0 = no request-rate limit
+    pub burst: u32,               // Requests allowed above the rate before 429s
+    pub max_connections: u32,     // 0 = no connection cap
+}
+
+/// 🐢 Slow-client (slowloris) protection for one vhost. Every vhost gets these;
+/// an app overrides whichever it needs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientLimits {
+    pub header_timeout_secs: u32, // The whole request header must arrive within this
+    pub body_timeout_secs: u32,   // Longest gap between two body reads
+    pub send_timeout_secs: u32,   // Longest gap between two reads by the client
+    pub keepalive_timeout_secs: u32, // Idle keep-alive connections close after this
+    pub max_body_mb: u32,         // Larger request bodies are refused with 413
+    pub max_connections: u32,     // Per client address (nginx), unless rate_limit caps it
+}
+
+impl Default for ClientLimits {
+    fn default() -> Self {
+        Self {
+            header_timeout_secs: 10,
+            body_timeout_secs: 15,
+            send_timeout_secs: 30,
+            keepalive_timeout_secs: 15,
+            max_body_mb: 16,
+            max_connections: 64,
+        }
+    }
+}
+
+/// 🌍 Client addresses from an upstream CDN or load balancer. Only peers in
+/// `trusted_proxies` may set `header`; everyone else is taken at face value.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RealIp {
+    pub header: String,               // e.g. X-Forwarded-For, CF-Connecting-IP
+    pub trusted_proxies: Vec<String>, // CIDRs
+}
+
+/// ↪️ One managed redirect. `path` is matched exactly ("/promo"), or with a
+/// trailing "/*" as a prefix whose remainder is appended to `target`
+/// ("/blog/*" → "https://blog.example.com/" sends /blog/a to .../a).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RedirectRule {
+    pub path: String,
+    pub target: String,  // An http(s) URL, or a path on the same host
+    pub permanent: bool, // 301; otherwise 302
+}
+
+/// 🔒 Protocol, cipher and curve presets for HTTPS vhosts, after Mozilla's
+/// server-side TLS guidelines of the same names.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsPolicy {
+    Modern, // TLS 1.3 only
+    #[default]
+    Intermediate, // TLS 1.2 and 1.3, forward-secret AEAD ciphers only
+    Old,    // Back to TLS 1.0, for clients that cannot be upgraded
+}
+
+impl TlsPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "modern" => Some(Self::Modern),
+            "intermediate" => Some(Self::Intermediate),
+            "old" => Some(Self::Old),
+            _ => None,
+        }
+    }
+}
+
+/// The certificate a vhost serves on 443, and the policy it was written with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VhostTls {
+    pub cert_path: PathBuf, // fullchain.pem
+    pub key_path: PathBuf,
+    pub policy: TlsPolicy,
+    /// 🩺 Staple OCSP responses; None leaves clients to ask the CA themselves.
+    #[serde(default)]
+    pub ocsp_stapling: Option<OcspStapling>,
+}
+
+/// OCSP stapling for a vhost. The proxy fetches responses itself, through
+/// `resolvers` (IP addresses; nginx has no other way to find the responder).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OcspStapling {
+    pub resolvers: Vec<String>,
+}
+
+/// Everything about a vhost beyond its upstream port. Stored with the app, so
+/// every rewrite (deploy, activation, autostart repair) reapplies it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VhostOptions {
+    #[serde(default)]
+    pub rate_limit: Option<RateLimit>,
+    #[serde(default)]
+    pub real_ip: Option<RealIp>,
+    /// 🔌 Serve a 503 maintenance page instead of proxying; set while the
+    /// app's circuit breaker is open.
+    #[serde(default)]
+    pub maintenance: bool,
+    /// 🔒 Also serve HTTPS; set once a certificate is installed for the domain.
+    #[serde(default)]
+    pub tls: Option<VhostTls>,
+    /// ↪️ Answered by the proxy itself, ahead of the app and maintenance alike.
+    #[serde(default)]
+    pub redirects: Vec<RedirectRule>,
+    /// 🐢 Records from before these existed pick up the defaults.
+    #[serde(default)]
+    pub client_limits: ClientLimits,
+    /// 🔎 Give every proxied request an X-Request-ID (the trace id of its
+    /// traceparent, when it has one), sent to the app and back to the client.
+    #[serde(default)]
+    pub request_tracing: bool,
+}
+
 #[async_trait]
 pub trait ProxyManager: Send + Sync {
     /// Creates a virtual host configuration for the given domain,
     /// proxying traffic to the specified internal port.
-    async fn create_vhost(&self, domain: &str, target_port: u16) -> Result<(), String>;
+    async fn create_vhost(
+        &self,
+        domain: &str,
+        target_port: u16,
+        options: &VhostOptions,
+    ) -> Result<(), String>;
 
     /// Removes the virtual host configuration for the given domain.
     async fn remove_vhost(&self, domain: &str) -> Result<(), String>;
+
+    /// Whether the domain's virtual host is configured and enabled.
+    async fn vhost_exists(&self, domain: &str) -> Result<bool, String>;
+
+    /// Whether the vhost is enabled and its file is exactly what `create_vhost`
+    /// would write for these arguments, i.e. nobody has edited it since.
+    async fn vhost_is_current(
+        &self,
+        domain: &str,
+        target_port: u16,
+        options: &VhostOptions,
+    ) -> Result<bool, String>;
+
+    /// 🔏 Answers an ACME HTTP-01 challenge: `key_authorization` is served at
+    /// http://<domain>/.well-known/acme-challenge/<token> until withdrawn.
+    async fn publish_challenge(
+        &self,
+        domain: &str,
+        token: &str,
+        key_authorization: &str,
+    ) -> Result<(), String>;
+
+    async fn withdraw_challenge(&self, domain: &str, token: &str) -> Result<(), String>;
 }
 
 // ==============================================================================
@@ -126,6 +365,15 @@ pub trait JobScheduler: Send + Sync {
     /// Schedules a recurring job using the platform's native scheduler.
     /// 🛡️ SLA: The binary + args split prevents shell interpretation.
     async fn schedule_job(&self, intent: &JobIntent) -> Result<(), String>;
+
+    /// Stops and removes a job's timer and service units. Missing jobs are not an error.
+    async fn unschedule_job(&self, name: &str) -> Result<(), String>;
+
+    /// Whether the job's timer is enabled, i.e. will be armed again after a reboot.
+    async fn is_job_enabled(&self, name: &str) -> Result<bool, String>;
+
+    /// Whether the job's last run failed. Cleared when a later run succeeds.
+    async fn job_failed(&self, name: &str) -> Result<bool, String>;
 }
 
 // ==============================================================================
@@ -134,6 +382,8 @@ pub trait JobScheduler: Send + Sync {
 
 #[async_trait]
 pub trait ReleaseManager: Send + Sync {
+    /// Removes all but the newest `keep_count` releases, never the active
+    /// one, and returns how many went.
     async fn prune_old_releases(
         &self,
         releases_dir: &Path,
@@ -147,5 +397,534 @@ pub trait ReleaseManager: Send + Sync {
 
 #[async_trait]
 pub trait LogManager: Send + Sync {
-    async fn configure_logrotate(&self, domain_name: &str, log_dir: &str) -> Result<(), String>;
+    /// Rotates `log_dir/*.log` daily, as `app_user`.
+    async fn configure_logrotate(
+        &self,
+        domain_name: &str,
+        app_user: &str,
+        log_dir: &Path,
+    ) -> Result<(), String>;
+
+    /// Removes the domain's rotation config. Idempotent.
+    async fn remove_logrotate(&self, domain_name: &str) -> Result<(), String>;
+}
+
+// ==============================================================================
+// 9. Mail Relay (Zero-Secret Tenants)
+// ==============================================================================
+
+/// 🛡️ Zero-Trust: The relay password never leaves the ProviderCredential wrapper.
+pub struct MailRelayConfig {
+    pub relay_host: String,
+    pub relay_port: u16,
+    pub username: String,
+    pub password: ProviderCredential,
+    pub origin_domain: String,
+}
+
+#[async_trait]
+pub trait MailRelayManager: Send + Sync {
+    /// Points the host's local MTA at an authenticated submission relay.
+    /// Jailed apps then submit via localhost without holding SMTP secrets.
+    async fn configure_relay(&self, config: MailRelayConfig) -> Result<(), String>;
+}
+
+// ==============================================================================
+// 10. Secret Store (At-Rest Credentials)
+// ==============================================================================
+
+/// 🛡️ Zero-Trust: Secrets cross this boundary only inside ProviderCredential, so they
+/// are zeroized on drop and can never be accidentally formatted into a log line.
+#[async_trait]
+pub trait SecretStore: Send + Sync {
+    async fn put_secret(&self, name: &str, value: ProviderCredential) -> Result<(), String>;
+
+    /// Returns `Ok(None)` when no secret with this name exists.
+    async fn get_secret(&self, name: &str) -> Result<Option<ProviderCredential>, String>;
+
+    async fn delete_secret(&self, name: &str) -> Result<(), String>;
+}
+
+// ==============================================================================
+// 11. Container Images (Ephemeral Registry Auth)
+// ==============================================================================
+
+#[async_trait]
+pub trait ImageManager: Send + Sync {
+    /// Pulls an image into the app user's rootless storage.
+    /// 🛡️ auth: podman auth.json content, written to an ephemeral file for the pull only.
+    async fn pull_image(
+        &self,
+        image: &str,
+        run_as_user: &str,
+        working_dir: &Path,
+        auth: Option<ProviderCredential>,
+    ) -> Result<(), String>;
+
+    /// Removes every image in the app user's storage except those in `keep`,
+    /// then drops dangling layers. Returns the number of images removed.
+    async fn prune_images(
+        &self,
+        run_as_user: &str,
+        working_dir: &Path,
+        keep: &[String],
+    ) -> Result<usize, String>;
+
+    /// Points the app's container unit at `image`, already pulled; the rest
+    /// of the unit stays as provisioning wrote it. Takes a daemon reload.
+    async fn set_unit_image(&self, service_name: &str, image: &str) -> Result<(), String>;
+}
+
+// ==============================================================================
+// 12. State Store (Agent-Owned Records)
+// ==============================================================================
+
+/// 🛡️ SOLID: Namespaced key/value persistence for everything the agent manages.
+/// Typed access goes through the helpers in `sys::state`.
+#[async_trait]
+pub trait StateStore: Send + Sync {
+    async fn put(&self, namespace: &str, key: &str, value: serde_json::Value)
+    -> Result<(), String>;
+
+    async fn get(&self, namespace: &str, key: &str) -> Result<Option<serde_json::Value>, String>;
+
+    async fn list(&self, namespace: &str) -> Result<Vec<(String, serde_json::Value)>, String>;
+
+    async fn delete(&self, namespace: &str, key: &str) -> Result<(), String>;
+
+    /// Every namespace as one JSON document (state snapshots).
+    async fn snapshot(&self) -> Result<serde_json::Value, String>;
+
+    /// Atomically replaces all namespaces with a document produced by `snapshot`.
+    async fn restore(&self, snapshot: serde_json::Value) -> Result<(), String>;
+}
+
+// ==============================================================================
+// 13. App Environment Files (Dotenv)
+// ==============================================================================
+
+#[async_trait]
+pub trait EnvFileManager: Send + Sync {
+    /// Renders `{shared_dir}/.env` owned by `owner` (0640), keeping the previous
+    /// file as `.env.bak`. 🛡️ Secret values only ever arrive as ProviderCredential.
+    async fn write_env_file(
+        &self,
+        shared_dir: &Path,
+        owner: &str,
+        vars: &BTreeMap<String, String>,
+        secrets: BTreeMap<String, ProviderCredential>,
+    ) -> Result<(), String>;
+}
+
+// ==============================================================================
+// 14. Tenant Quota Groups (Aggregate cgroup Limits)
+// ==============================================================================
+
+/// Limits shared by every app of one tenant. `None` leaves a resource unlimited.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TenantQuota {
+    pub tenant_id: String,
+    pub memory_max_mb: Option<u32>,
+    pub cpu_quota_percent: Option<u32>, // 100 = one full core
+}
+
+#[async_trait]
+pub trait TenantSliceManager: Send + Sync {
+    /// Writes the tenant's slice unit. Limits take effect on the next daemon-reload,
+    /// including for apps already running inside the slice.
+    async fn write_slice(&self, quota: &TenantQuota) -> Result<(), String>;
+}
+
+// ==============================================================================
+// 15. Source Scanning (Post-Clone Policy Hooks)
+// ==============================================================================
+
+/// One scanner hit. 🛡️ Privacy: Never carries the matched content itself.
+#[derive(Debug, Clone)]
+pub struct ScanFinding {
+    pub scanner: String,
+    pub rule: String, // Secret rule name or malware signature
+    pub path: String, // Relative to the scanned release
+    pub line: u32,    // 0 when the scanner is file-granular
+}
+
+#[async_trait]
+pub trait SourceScanner: Send + Sync {
+    fn name(&self) -> &'static str;
+    async fn scan(&self, dir: &Path) -> Result<Vec<ScanFinding>, String>;
+}
+
+// ==============================================================================
+// 16. Firewall Events (Logged Denied Traffic)
+// ==============================================================================
+
+/// One logged packet that a kari deny/reject rule matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirewallHit {
+    pub rule_id: String, // Short id carried in the kernel log prefix
+    pub source_ip: String,
+}
+
+#[async_trait]
+pub trait FirewallEventSource: Send + Sync {
+    /// Streams hits into `tx` until the receiver is dropped or the source ends.
+    async fn follow(&self, tx: mpsc::Sender<FirewallHit>) -> Result<(), String>;
+}
+
+// ==============================================================================
+// 17. Config Change Notifications (Tamper Watch)
+// ==============================================================================
+
+#[async_trait]
+pub trait ConfigChangeSource: Send + Sync {
+    /// Streams the path of every file created, written, moved or deleted in
+    /// `dirs` (or one of their immediate subdirectories) into `tx`, until the
+    /// receiver is dropped.
+    async fn follow(&self, dirs: Vec<PathBuf>, tx: mpsc::Sender<PathBuf>) -> Result<(), String>;
+}
+
+// ==============================================================================
+// 18. Network Inventory (Interfaces & Listening Sockets)
+// ==============================================================================
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub up: bool,
+    pub addresses: Vec<String>, // CIDR form, e.g. "10.0.0.5/24" or "fe80::1/64"
+}
+
+/// A socket accepting traffic (TCP in LISTEN, or a bound UDP socket) and who
+/// holds it. Owner fields are None when no process could be matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listener {
+    pub protocol: Protocol, // Tcp or Udp
+    pub address: String,    // Local address; "0.0.0.0" / "::" for all
+    pub port: u16,
+    pub pid: Option<u32>,
+    pub process: Option<String>, // comm
+    pub unit: Option<String>,    // systemd unit from the process's cgroup
+}
+
+#[async_trait]
+pub trait NetworkInspector: Send + Sync {
+    async fn interfaces(&self) -> Result<Vec<NetworkInterface>, String>;
+    async fn listeners(&self) -> Result<Vec<Listener>, String>;
+}
+
+// ==============================================================================
+// 19. Time Synchronization (Clock Discipline)
+// ==============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeSyncDaemon {
+    Chrony,
+    Timesyncd,
+}
+
+impl TimeSyncDaemon {
+    pub fn unit(self) -> &'static str {
+        match self {
+            TimeSyncDaemon::Chrony => "chronyd.service",
+            TimeSyncDaemon::Timesyncd => "systemd-timesyncd.service",
+        }
+    }
+}
+
+/// Further off than this, certificates and timers are no longer trusted to behave.
+pub const CLOCK_SKEW_TOLERANCE_SECS: f64 = 1.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeSyncStatus {
+    pub daemon: TimeSyncDaemon,
+    pub synchronized: bool,             // The kernel clock is NTP-disciplined
+    pub offset_secs: Option<f64>,       // Local minus reference; None when unreported
+    pub servers: Vec<String>,           // Every configured source, not only Kari's
+    pub current_source: Option<String>, // The server the clock follows right now
+}
+
+impl TimeSyncStatus {
+    pub fn skewed(&self) -> bool {
+        !self.synchronized
+            || self
+                .offset_secs
+                .is_some_and(|offset| offset.abs() > CLOCK_SKEW_TOLERANCE_SECS)
+    }
+}
+
+#[async_trait]
+pub trait TimeSyncManager: Send + Sync {
+    /// Points the host's NTP daemon at `servers` (replacing any Kari set
+    /// before), enables NTP and restarts the daemon. Returns which daemon.
+    async fn configure(&self, servers: &[String]) -> Result<TimeSyncDaemon, String>;
+    async fn status(&self) -> Result<TimeSyncStatus, String>;
+}
+
+// ==============================================================================
+// 20. DNS Lookups (Certificate Issuance Pre-Checks)
+// ==============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecordType {
+    A,
+    Aaaa,
+    Caa,
+}
+
+impl RecordType {
+    pub fn code(self) -> u16 {
+        match self {
+            RecordType::A => 1,
+            RecordType::Aaaa => 28,
+            RecordType::Caa => 257,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DnsRecord {
+    Address(IpAddr),
+    Cname(String),
+    Caa {
+        critical: bool, // Flag 128: a CA that does not know the tag must refuse
+        tag: String,    // issue | issuewild | iodef | ...
+        value: String,
+    },
+}
+
+/// What the host's resolver answered. An empty answer with `nxdomain` unset
+/// means the name exists without records of that type.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DnsAnswer {
+    pub nxdomain: bool,
+    pub records: Vec<DnsRecord>, // CNAMEs followed on the way come first
+}
+
+#[async_trait]
+pub trait DnsResolver: Send + Sync {
+    /// Err for anything but an answer (SERVFAIL, REFUSED, timeouts).
+    async fn query(&self, name: &str, record_type: RecordType) -> Result<DnsAnswer, String>;
+}
+
+// ==============================================================================
+// 21. Control-Plane Heartbeat (Outbound Only)
+// ==============================================================================
+
+#[async_trait]
+pub trait HeartbeatTransport: Send + Sync {
+    /// POSTs one signed heartbeat to `url`. Err for anything but a 2xx.
+    async fn send(&self, url: &str, body: &[u8], signature: &str) -> Result<(), String>;
+}
+
+// ==============================================================================
+// 22. Disk Health (SMART & Kernel I/O Errors)
+// ==============================================================================
+
+/// One disk's SMART data. Counters the drive does not report are zero.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SmartReport {
+    pub device: String, // e.g. "/dev/sda", "/dev/nvme0"
+    pub model: String,
+    pub passed: bool,               // The drive's own overall assessment
+    pub reallocated_sectors: u64,   // ATA attribute 5
+    pub pending_sectors: u64,       // ATA attribute 197
+    pub uncorrectable_sectors: u64, // ATA attribute 198
+    pub media_errors: u64,          // NVMe
+    pub percent_used: u32,          // NVMe rated endurance consumed
+    pub temperature_celsius: u32,
+}
+
+/// A kernel log line reporting an I/O or filesystem error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelDiskError {
+    pub device: String, // As the kernel names it, e.g. "sda", "nvme0n1p2"
+    pub message: String,
+    pub at: i64, // Unix seconds
+}
+
+#[async_trait]
+pub trait DiskHealthProbe: Send + Sync {
+    /// Every disk smartctl can open. Empty when smartctl is not installed.
+    async fn smart_reports(&self) -> Result<Vec<SmartReport>, String>;
+    /// I/O and filesystem errors the kernel logged at or after `since` (Unix seconds).
+    async fn kernel_errors(&self, since: i64) -> Result<Vec<KernelDiskError>, String>;
+}
+
+// ==============================================================================
+// 23. Kernel & Reboot (Advisory and Scheduled Reboots)
+// ==============================================================================
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KernelState {
+    pub running: String,              // uname -r
+    pub installed: Vec<String>,       // Every kernel release that could be booted
+    pub livepatches: Vec<String>,     // Enabled livepatch modules
+    pub reboot_required: bool,        // The distro's /var/run/reboot-required flag
+    pub reboot_packages: Vec<String>, // What set that flag, where the distro says
+    pub booted_at: i64,               // Unix seconds
+}
+
+#[async_trait]
+pub trait RebootManager: Send + Sync {
+    async fn kernel_state(&self) -> Result<KernelState, String>;
+    /// Asks systemd to reboot the host. Returns once the request is queued.
+    async fn reboot(&self) -> Result<(), String>;
+}
+
+// ==============================================================================
+// 24. Mandatory Access Control (Per-App AppArmor / SELinux)
+// ==============================================================================
+
+/// The app a MAC profile confines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MacTarget {
+    pub app_user: String,     // Names the profile, e.g. "kari-app-shop"
+    pub service_name: String, // Without ".service"; its drop-in attaches the profile
+    pub app_dir: PathBuf,     // releases/ (read-only), shared/ and logs/ are all it may touch
+}
+
+/// What the host's MAC framework enforces for one app.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MacStatus {
+    pub framework: String, // "apparmor" | "selinux"; empty when the host enforces none
+    pub profile: String,   // AppArmor profile name, or SELinux context
+    pub mode: String,      // "enforce" | "complain" | "permissive"; empty when not loaded
+    pub loaded: bool,
+}
+
+#[async_trait]
+pub trait MacManager: Send + Sync {
+    /// Generates and loads the app's profile and attaches it to its unit
+    /// (a daemon-reload and restart apply it). Without a MAC framework on the
+    /// host this only removes a stale attachment.
+    async fn confine(&self, target: &MacTarget) -> Result<(), String>;
+    /// Unloads and removes the profile. Nothing to remove is not an error.
+    async fn release(&self, target: &MacTarget) -> Result<(), String>;
+    async fn status(&self, target: &MacTarget) -> Result<MacStatus, String>;
+}
+
+// ==============================================================================
+// 25. Process Inspection (What a Jail Is Running)
+// ==============================================================================
+
+/// One process in a unit's cgroup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnitProcess {
+    pub pid: u32,
+    pub ppid: u32,
+    pub user: String,     // Name, or the uid when it has none
+    pub command: String,  // Full command line; [comm] when there is none
+    pub cpu_percent: f64, // Over a short sampling window; 100 is one core
+    pub rss_bytes: u64,
+    pub threads: u32,
+}
+
+#[async_trait]
+pub trait ProcessInspector: Send + Sync {
+    /// Every process in `unit`'s cgroup, nested cgroups (container payloads) included.
+    async fn unit_processes(&self, unit: &str) -> Result<Vec<UnitProcess>, String>;
+}
+
+// ==============================================================================
+// 26. Certificate Issuance (ACME)
+// ==============================================================================
+
+#[async_trait]
+pub trait CertificateIssuer: Send + Sync {
+    /// Proves control of every domain through `proxy` (HTTP-01) and has the CA
+    /// sign `csr_pem`, which must name exactly `domains`. Returns the full
+    /// chain (PEM), leaf first.
+    async fn issue(
+        &self,
+        domains: &[String],
+        csr_pem: &str,
+        proxy: &dyn ProxyManager,
+    ) -> Result<String, String>;
+}
+
+// ==============================================================================
+// 27. Service Logs (Journal)
+// ==============================================================================
+
+/// Which of one unit's journal entries to read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalQuery {
+    pub unit: String,
+    pub since: Option<i64>, // Unix seconds
+    pub until: Option<i64>,
+    pub max_priority: u8, // syslog level, 0 (emerg) to 7 (debug); less severe entries are left out
+    pub lines: u32,       // The newest this many entries of the range
+    pub follow: bool,     // Keep reading new entries as they are written
+}
+
+/// One journal entry of a unit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub timestamp_us: u64, // Unix microseconds
+    pub priority: u8,
+    pub message: String,
+    pub pid: u32,
+}
+
+#[async_trait]
+pub trait JournalReader: Send + Sync {
+    /// Streams the query's entries into `tx`, oldest first. Returns once they
+    /// are all sent or, when following, once the receiver is dropped.
+    async fn read(
+        &self,
+        query: &JournalQuery,
+        tx: mpsc::Sender<JournalEntry>,
+    ) -> Result<(), String>;
+}
+
+// ==============================================================================
+// 28. File Access (SFTP-Only Logins)
+// ==============================================================================
+
+/// Who may log in over SFTP, and what they see.
+#[derive(Debug, Clone)]
+pub struct SftpGrant {
+    pub app_user: String,
+    pub domain_name: String,
+    pub shared_dir: PathBuf, // Must exist; the session's only directory
+    pub keys: Vec<AuthorizedKey>,
+}
+
+#[async_trait]
+pub trait SftpAccessManager: Send + Sync {
+    /// Lets `keys` log in as the app user, chrooted to its shared directory
+    /// with nothing but SFTP. A later grant replaces the keys.
+    async fn grant(&self, grant: &SftpGrant) -> Result<(), String>;
+
+    /// Undoes `grant` and ends open sessions. Idempotent.
+    async fn revoke(&self, app_user: &str) -> Result<(), String>;
+}
+
+// ==============================================================================
+// 29. OS Patching (Maintenance-Window Upgrades)
+// ==============================================================================
+
+#[async_trait]
+pub trait Patcher: Send + Sync {
+    /// Installed packages and their versions.
+    async fn installed(&self) -> Result<BTreeMap<String, String>, String>;
+    /// Refreshes the package index and lists what `scope` would upgrade.
+    async fn pending(&self, scope: PatchScope) -> Result<Vec<String>, String>;
+    /// Upgrades what `pending` listed.
+    async fn upgrade(&self, scope: PatchScope, pending: &[String]) -> Result<(), String>;
+    /// Turns off the distro's own automatic upgrades; returns what it turned off.
+    async fn disable_distro_automation(&self) -> Result<Vec<String>, String>;
+    /// Turns back on what `disable_distro_automation` returned.
+    async fn restore_distro_automation(&self, disabled: &[String]) -> Result<(), String>;
+}
+
+// ==============================================================================
+// 30. Log Forwarding (Remote Syslog / Loki)
+// ==============================================================================
+
+#[async_trait]
+pub trait LogShipper: Send + Sync {
+    /// Delivers one encoded batch (see `log_forward::encode`) to `target`. Err
+    /// unless the endpoint took all of it.
+    async fn ship(&self, target: &LogTarget, payload: &[u8]) -> Result<(), String>;
 }
@@ -78,7 +78,7 @@ EOF
 cat <<EOF > /etc/systemd/system/kari-agent.service
 [Unit]
 Description=Kari Rust System Agent
-After=network.target
+After=network.target kari-firewall.service
 
 [Service]
 ExecStart=/opt/kari/bin/kari-agent
@@ -97,6 +97,26 @@ RestrictRealtime=true
 WantedBy=multi-user.target
 EOF
 
+# Firewall Restore - iptables rules live only in the kernel, so the agent's
+# stored rules go back in before any interface comes up
+cat <<EOF > /etc/systemd/system/kari-firewall.service
+[Unit]
+Description=Kari Firewall Rules (restored from agent state)
+DefaultDependencies=no
+After=local-fs.target
+Before=network-pre.target kari-agent.service
+Wants=network-pre.target
+
+[Service]
+Type=oneshot
+RemainAfterExit=yes
+ExecStart=/opt/kari/bin/kari-agent --restore-firewall
+EnvironmentFile=-/etc/kari/agent.env
+
+[Install]
+WantedBy=multi-user.target
+EOF
+
 # 4. 🛡️ Binary Integrity
 echo -e "${GRAY}[4/5] Securing executables...${NC}"
 chown -R root:root /opt/kari/bin
@@ -107,7 +127,7 @@ chmod 755 /opt/kari/bin/kari-api   # Brain needs rx
 # 5. 🛡️ Daemon Reload & Enable
 echo -e "${GRAY}[5/5] Reloading systemd daemon...${NC}"
 systemctl daemon-reload
-# systemctl enable kari-agent kari-api # Uncomment to enable on boot
+# systemctl enable kari-firewall kari-agent kari-api # Uncomment to enable on boot
 # systemctl restart kari-agent kari-api # Uncomment to start immediately
 
 echo -e "${TEAL}------------------------------------------------${NC}"